@@ -338,7 +338,8 @@ export default function ArticleUploadPage() {
                 line: image.markdown_line,
                 column: image.markdown_column,
                 old_link: image.original_path,
-                new_link: duplicateResult.existing_url
+                new_link: duplicateResult.existing_url,
+                context: image.context
               })
             } else {
               // 对于非重复图片，使用上传后的URL
@@ -349,7 +350,8 @@ export default function ArticleUploadPage() {
                   line: image.markdown_line,
                   column: image.markdown_column,
                   old_link: image.original_path,
-                  new_link: uploadResult.uploaded_url
+                  new_link: uploadResult.uploaded_url,
+                  context: image.context
                 })
               }
             }
@@ -343,30 +343,11 @@ export default function ImageUploadPage() {
           })
         )
 
-        // 为成功上传的图片添加历史记录
+        // 上传命令已在后端写入历史记录
         try {
-          for (const result of results) {
-            if (result.success && result.uploaded_url) {
-              const uploadFile = filesToActuallyUpload.find(f => f.id === result.image_id)
-              if (uploadFile) {
-                await tauriAPI.addImageHistoryRecord(
-                  uploadFile.fileName,
-                  uploadFile.filePath,
-                  result.uploaded_url,
-                  UploadMode.ImageUpload,
-                  undefined,
-                  true,
-                  uploadFile.fileSize || 0,
-                  undefined,
-                  undefined
-                )
-              }
-            }
-          }
-
           await refreshHistory()
         } catch (error) {
-          console.warn("Failed to save image history records:", error)
+          console.warn("Failed to refresh image history:", error)
         }
       }
 
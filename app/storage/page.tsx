@@ -263,8 +263,17 @@ export default function StoragePage() {
         updated_at: new Date().toISOString(),
       }
 
-      // 保存配置项
-      await configOperations.saveConfigItem(configItem)
+      // 保存配置项（保存时会再次校验，有错误则不保存）
+      const saved = await configOperations.saveConfigItem(configItem)
+      setState(prev => ({ ...prev, validationResult: saved }))
+      if (!saved.valid) {
+        setState(prev => ({
+          ...prev,
+          error: `Configuration validation failed: ${saved.errors.join(', ')}`,
+          isValidating: false
+        }))
+        return
+      }
       
       // 重新加载配置列表
       await loadConfiguration()
@@ -604,7 +613,7 @@ export default function StoragePage() {
           <Label htmlFor="cdn-domain">CDN 域名 (可选)</Label>
           <Input
             id="cdn-domain"
-            placeholder="例如：https://cdn.example.com"
+            placeholder="例如：cdn.example.com"
             value={editConfig.cdn_domain || ""}
             onChange={(e) => setEditConfig(prev => ({ ...prev, cdn_domain: e.target.value || undefined }))}
           />
@@ -228,6 +228,16 @@ function formatUploadMode(mode: string): string {
       return '图片上传'
     case 'ArticleUpload':
       return '文章上传'
+    case 'ClipboardUpload':
+      return '剪贴板上传'
+    case 'UrlImport':
+      return 'URL 导入'
+    case 'WatcherUpload':
+      return '自动上传'
+    case 'Reupload':
+      return '重新上传'
+    case 'Localization':
+      return '链接转换'
     default:
       return mode
   }
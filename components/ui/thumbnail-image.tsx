@@ -20,15 +20,25 @@ interface ThumbnailImageState {
   src: string | null
   loading: boolean
   error: boolean
+  errorLabel: string
   inView: boolean
 }
 
+// 后端错误以 "Not found:" / "Permission denied:" 开头，区分对象已删除和无权访问
+function thumbnailErrorLabel(error: unknown): string {
+  const message = String(error)
+  if (message.includes("Not found:")) return "图片已删除"
+  if (message.includes("Permission denied:")) return "无访问权限"
+  return "加载失败"
+}
+
 const ThumbnailImage = React.forwardRef<HTMLDivElement, ThumbnailImageProps>(
   ({ record, size = 200, className, lazy = true, onClick }, ref) => {
     const [state, setState] = React.useState<ThumbnailImageState>({
       src: null,
       loading: false,
       error: false,
+      errorLabel: "加载失败",
       inView: false,
     })
 
@@ -101,6 +111,7 @@ const ThumbnailImage = React.forwardRef<HTMLDivElement, ThumbnailImageProps>(
           ...prev,
           loading: false,
           error: true,
+          errorLabel: thumbnailErrorLabel(error),
         }))
       }
     }, [state.inView, state.loading, state.src, record.id, record.uploaded_url])
@@ -141,7 +152,7 @@ const ThumbnailImage = React.forwardRef<HTMLDivElement, ThumbnailImageProps>(
         {state.error && (
           <div className="flex flex-col items-center justify-center gap-2 text-muted-foreground p-2">
             <AlertCircle className="h-6 w-6" />
-            <span className="text-xs text-center">加载失败</span>
+            <span className="text-xs text-center">{state.errorLabel}</span>
             <Button
               variant="ghost"
               size="sm"
@@ -2,48 +2,188 @@
 // Provides type-safe communication with Tauri backend
 
 import { invoke } from '@tauri-apps/api/core';
+import { getCurrentWindow } from '@tauri-apps/api/window';
 import type {
   ScanResult,
+  ScanRevalidation,
   ImageInfo,
+  ImageValidation,
   UploadResult,
+  GeneratedThumbnail,
+  CacheStats,
+  ThumbnailFormat,
+  ArticleImageUpload,
   UploadProgress,
   OSSConfig,
   ConfigItem,
   ConfigCollection,
   OSSConnectionTest,
+  ConnectionDiagnosis,
+  ConfigTestResult,
   ConfigValidation,
+  PathTemplateCheck,
+  ProviderCapabilities,
+  OSSProvider,
   SaveOptions,
   ObjectInfo,
   LinkReplacement,
   ReplacementResult,
   BatchReplacementResult,
+  ReplacementOperation,
+  ReplacementUndoResult,
   UploadHistoryRecord,
   PaginatedResult,
   HistoryStatistics,
   FileOperation,
+  FileOperationType,
   DuplicateCheckResult,
   DuplicateInfo,
   SystemHealth,
+  StartupReport,
+  CrashReport,
+  CrashReportSummary,
+  MaintenanceStatus,
+  StoreKind,
+  StoreRecoveryResult,
+  ScanGlobOptions,
+  BatchUploadOptions,
+  ScanSummary,
+  LocalDuplicateReport,
+  LocalIntegrityReport,
+  ReportFormat,
+  ConnectivityStatus,
   NotificationConfig,
   ProgressNotification,
   UploadTaskInfo,
   UploadTaskManager,
+  UploadScope,
   UploadMode,
+  ImageConversionResult,
+  BackupCleanupResult,
+  BackupStorageUsage,
+  StorageUsage,
+  ArchivedOriginal,
+  AppDataInclude,
+  AppDataManifest,
+  AppDataPreview,
+  AppDataImportResult,
+  SystemPermissions,
+  DataDirectoryChange,
+  HistoryQuery,
+  GalleryFormat,
+  DuplicateLookupOptions,
+  HistoryCleanupResult,
+  TrashedHistoryRecord,
+  HistorySortField,
+  HistorySourceGroup,
+  SortOrder,
+  StatsRange,
+  UploadPerformanceStats,
+  BatchEstimate,
+  LinkAnalysisReport,
+  LinkMigrationMode,
+  LinkMigrationReport,
+  CdnMigrationSummary,
+  ReadOnlyPolicy,
+  UnwritableFile,
 } from './types';
 
+// Mirrors src-tauri/src/commands/limits.rs
+export const MAX_HISTORY_BATCH = 500;
+
 /**
  * Centralized Tauri API client providing type-safe methods for all backend operations
  */
+// Label of the calling window; the backend uploads with the config picked for
+// it through sessionDefaultConfig, else the active config
+function windowSession(): string | undefined {
+  try {
+    return getCurrentWindow().label;
+  } catch {
+    return undefined;
+  }
+}
+
 export class TauriAPI {
   // ============================================================================
   // File and Scan Operations
   // ============================================================================
 
   /**
-   * Scan markdown files for image references
+   * Scan markdown files for image references.
+   * Progress is emitted as `scan://progress` / `scan://done` events.
+   * `assetRoots` (absolute static directories) and `scanGlobs` override the settings.
+   */
+  async scanMarkdownFiles(
+    filePaths: string[],
+    scanId?: string,
+    assetRoots?: string[],
+    scanGlobs?: ScanGlobOptions
+  ): Promise<ScanResult[]> {
+    return invoke<ScanResult[]>('scan_markdown_files', { filePaths, scanId, assetRoots, scanGlobs });
+  }
+
+  /**
+   * Scan large sets of markdown files: each result arrives as a `scan://result`
+   * event instead of in the return value, which is the `scan://done` summary
+   */
+  async scanMarkdownFilesStreamed(
+    scanId: string,
+    filePaths: string[],
+    assetRoots?: string[],
+    scanGlobs?: ScanGlobOptions
+  ): Promise<ScanSummary> {
+    return invoke<ScanSummary>('scan_markdown_files_streamed', {
+      scanId,
+      filePaths,
+      assetRoots,
+      scanGlobs,
+    });
+  }
+
+  /**
+   * Stop a running scan before its next file
+   */
+  async cancelScan(scanId: string): Promise<boolean> {
+    return invoke<boolean>('cancel_scan', { scanId });
+  }
+
+  /**
+   * Re-check scan results right before replacing: markdown files edited since
+   * the scan, images that are gone and images that changed
+   */
+  async revalidateScanResults(results: ScanResult[]): Promise<ScanRevalidation> {
+    return invoke<ScanRevalidation>('revalidate_scan_results', { results });
+  }
+
+  /**
+   * Find byte-identical images under a directory. Pass scan results to get
+   * markdown usage counts; cancel with cancelScan(scanId)
    */
-  async scanMarkdownFiles(filePaths: string[]): Promise<ScanResult[]> {
-    return invoke<ScanResult[]>('scan_markdown_files', { filePaths });
+  async findLocalDuplicates(
+    directory: string,
+    recursive?: boolean,
+    scanId?: string,
+    scanResults?: ScanResult[]
+  ): Promise<LocalDuplicateReport> {
+    return invoke<LocalDuplicateReport>('find_local_duplicates', {
+      directory,
+      recursive,
+      scanId,
+      scanResults,
+    });
+  }
+
+  /**
+   * Write a duplicate report to disk, returns the written path
+   */
+  async exportDuplicateReport(
+    report: LocalDuplicateReport,
+    path: string,
+    format?: ReportFormat,
+    overwrite?: boolean
+  ): Promise<string> {
+    return invoke<string>('export_duplicate_report', { report, path, format, overwrite });
   }
 
   /**
@@ -54,10 +194,48 @@ export class TauriAPI {
   }
 
   /**
-   * Generate a thumbnail for an image
+   * Check a file selection before uploading: per path whether it can be
+   * uploaded, its format and dimensions, or why not
+   */
+  async validateImages(paths: string[]): Promise<ImageValidation[]> {
+    return invoke<ImageValidation[]>('validate_images', { paths });
+  }
+
+  /**
+   * Generate a thumbnail for an image. Size is capped by the max_thumbnail_size
+   * setting; use 'png' or 'webp' to keep transparency (default 'jpeg').
+   */
+  async generateThumbnail(
+    imagePath: string,
+    size: number,
+    outputFormat?: ThumbnailFormat
+  ): Promise<GeneratedThumbnail> {
+    return invoke<GeneratedThumbnail>('generate_thumbnail', { imagePath, size, outputFormat });
+  }
+
+  /**
+   * Convert an image to another format, written to outputPath or returned as base64
    */
-  async generateThumbnail(imagePath: string, size: number): Promise<number[]> {
-    return invoke<number[]>('generate_thumbnail', { imagePath, size });
+  async convertImageFormat(
+    imagePath: string,
+    targetFormat: string,
+    outputPath?: string
+  ): Promise<ImageConversionResult> {
+    return invoke<ImageConversionResult>('convert_image_format', {
+      imagePath,
+      targetFormat,
+      outputPath: outputPath ?? null,
+    });
+  }
+
+  /**
+   * Suggest a compression quality (1-100), optionally aiming at a target size
+   */
+  async suggestCompressionQuality(imagePath: string, targetSizeKb?: number): Promise<number> {
+    return invoke<number>('suggest_compression_quality', {
+      imagePath,
+      targetSizeKb: targetSizeKb ?? null,
+    });
   }
 
   // ============================================================================
@@ -78,29 +256,116 @@ export class TauriAPI {
     return invoke<number>('cleanup_thumbnail_cache');
   }
 
+  /**
+   * Hit/miss counters of the in-memory thumbnail cache and disk cache usage
+   */
+  async getCacheStats(): Promise<CacheStats> {
+    return invoke<CacheStats>('get_cache_stats');
+  }
+
   // ============================================================================
   // Upload Operations
   // ============================================================================
 
   /**
-   * Upload multiple images to configured storage provider
+   * Upload multiple images to configured storage provider. `configId` picks a
+   * saved config; without either, the direct upload default from settings or the
+   * active config is used. Progress events carry `sessionId`, a new one when
+   * omitted.
    */
-  async uploadImages(imagePaths: string[], config: OSSConfig): Promise<UploadResult[]> {
-    return invoke<UploadResult[]>('upload_images', { imagePaths, config });
+  async uploadImages(
+    imagePaths: string[],
+    config?: OSSConfig,
+    configId?: string,
+    sessionId?: string
+  ): Promise<UploadResult[]> {
+    return invoke<UploadResult[]>('upload_images', {
+      imagePaths,
+      config,
+      configId,
+      sessionId,
+      windowSession: windowSession(),
+    });
   }
 
   /**
-   * Upload multiple images to OSS with custom IDs for progress tracking
+   * Upload multiple images to OSS with custom IDs for progress tracking.
+   * Up to `concurrency` files upload at once (defaults to the upload_concurrency
+   * setting); results are in input order.
    */
-  async uploadImagesWithIds(imageData: [string, string][], config: OSSConfig): Promise<UploadResult[]> {
-    return invoke<UploadResult[]>('upload_images_with_ids', { imageData, config });
+  async uploadImagesWithIds(
+    imageData: [string, string][],
+    config?: OSSConfig,
+    sourceFile?: string,
+    uploadMode?: UploadMode,
+    concurrency?: number,
+    configId?: string,
+    sessionId?: string
+  ): Promise<UploadResult[]> {
+    return invoke<UploadResult[]>('upload_images_with_ids', {
+      imageData,
+      config,
+      sourceFile,
+      uploadMode,
+      concurrency,
+      configId,
+      sessionId,
+      windowSession: windowSession(),
+    });
+  }
+
+  /**
+   * Upload the selected references of a scan to a saved config, the article
+   * default from settings when `configId` is omitted. Identical files upload
+   * once; every selected reference comes back in document order with its result
+   * and the replacement for its link.
+   */
+  async uploadArticleImages(
+    scanResults: ScanResult[],
+    selectedImageIds: string[],
+    configId?: string,
+    sessionId?: string
+  ): Promise<ArticleImageUpload[]> {
+    return invoke<ArticleImageUpload[]>('upload_article_images', {
+      scanResults,
+      selectedImageIds,
+      configId,
+      sessionId,
+      windowSession: windowSession(),
+    });
   }
 
   /**
    * Upload multiple images in batches with concurrent processing
    */
-  async uploadImagesBatch(imagePaths: string[], config: OSSConfig, batchSize?: number): Promise<UploadResult[]> {
-    return invoke<UploadResult[]>('upload_images_batch', { imagePaths, config, batchSize });
+  async uploadImagesBatch(
+    imagePaths: string[],
+    options?: BatchUploadOptions
+  ): Promise<UploadResult[]> {
+    return invoke<UploadResult[]>('upload_images_batch', {
+      imagePaths,
+      options,
+      windowSession: windowSession(),
+    });
+  }
+
+  /**
+   * Expected size and duration of uploading the images, from file sizes,
+   * cached processing previews and recent upload speed
+   */
+  async estimateBatch(
+    imagePaths: string[],
+    config?: OSSConfig,
+    configId?: string,
+    uploadMode?: UploadMode
+  ): Promise<BatchEstimate> {
+    return invoke<BatchEstimate>('estimate_batch', {
+      imagePaths,
+      config,
+      configId,
+      uploadMode,
+      windowSession: windowSession(),
+    });
   }
 
   /**
@@ -125,10 +390,10 @@ export class TauriAPI {
   }
 
   /**
-   * Get all current upload progress states
+   * Get all current upload progress states, or those of the sessions `scope` matches
    */
-  async getAllUploadProgress(): Promise<UploadProgress[]> {
-    return invoke<UploadProgress[]>('get_all_upload_progress');
+  async getAllUploadProgress(scope?: UploadScope): Promise<UploadProgress[]> {
+    return invoke<UploadProgress[]>('get_all_upload_progress', { scope });
   }
 
   /**
@@ -164,12 +429,21 @@ export class TauriAPI {
   }
 
   /**
-   * Test connection to OSS provider
+   * Test connection to OSS provider.
+   * Each step is also emitted as a `config://test-progress` event while the test runs.
    */
   async testOSSConnection(config: OSSConfig): Promise<OSSConnectionTest> {
     return invoke<OSSConnectionTest>('test_oss_connection', { config });
   }
 
+  /**
+   * Check DNS, TCP, TLS, the system clock and then the signed request, one step at a time.
+   * Reports an explicit finding when the local clock is skewed.
+   */
+  async diagnoseConnection(config: OSSConfig): Promise<ConnectionDiagnosis> {
+    return invoke<ConnectionDiagnosis>('diagnose_connection', { config });
+  }
+
   /**
    * Validate OSS configuration parameters
    */
@@ -177,6 +451,20 @@ export class TauriAPI {
     return invoke<ConfigValidation>('validate_oss_config', { config });
   }
 
+  /**
+   * Check a path template against the provider's key rules, with a sample key
+   */
+  async validatePathTemplate(template: string, provider: OSSProvider): Promise<PathTemplateCheck> {
+    return invoke<PathTemplateCheck>('validate_path_template', { template, provider });
+  }
+
+  /**
+   * Required fields, limits and supported features of a provider
+   */
+  async getProviderCapabilities(provider: OSSProvider): Promise<ProviderCapabilities> {
+    return invoke<ProviderCapabilities>('get_provider_capabilities', { provider });
+  }
+
   /**
    * Get cached connection status for OSS configuration
    */
@@ -191,6 +479,14 @@ export class TauriAPI {
     return invoke<void>('clear_connection_cache');
   }
 
+  /**
+   * Test every saved config; cached results are reused unless deep.
+   * Each finished config is also emitted as a `config://test-all-progress` event.
+   */
+  async testAllConfigs(deep: boolean = false): Promise<ConfigTestResult[]> {
+    return invoke<ConfigTestResult[]>('test_all_configs', { deep });
+  }
+
   /**
    * List objects in OSS bucket with optional prefix
    */
@@ -198,6 +494,19 @@ export class TauriAPI {
     return invoke<ObjectInfo[]>('list_oss_objects', { config, prefix });
   }
 
+  /**
+   * Child prefixes ("directories") of a prefix, for key prefix autocomplete.
+   * Listings are reused for a few seconds; pass refresh to list again.
+   */
+  async listOSSPrefixes(
+    config: OSSConfig,
+    prefix: string = '',
+    delimiter?: string,
+    refresh?: boolean
+  ): Promise<string[]> {
+    return invoke<string[]>('list_oss_prefixes', { config, prefix, delimiter, refresh });
+  }
+
   /**
    * Export OSS configuration as JSON string
    */
@@ -212,6 +521,31 @@ export class TauriAPI {
     return invoke<void>('import_oss_config', { configJson });
   }
 
+  /**
+   * Export configs, settings and history into a zip archive for another machine
+   */
+  async exportAppData(
+    targetZipPath: string,
+    include: AppDataInclude,
+    overwrite?: boolean
+  ): Promise<AppDataManifest> {
+    return invoke<AppDataManifest>('export_app_data', { targetZipPath, include, overwrite });
+  }
+
+  /**
+   * List what an app data archive contains without importing it
+   */
+  async previewAppDataImport(zipPath: string): Promise<AppDataPreview> {
+    return invoke<AppDataPreview>('preview_app_data_import', { zipPath });
+  }
+
+  /**
+   * Import an app data archive, merging with or replacing the local data
+   */
+  async importAppData(zipPath: string, merge: boolean): Promise<AppDataImportResult> {
+    return invoke<AppDataImportResult>('import_app_data', { zipPath, merge });
+  }
+
   // ============================================================================
   // Multi-Config Management
   // ============================================================================
@@ -224,10 +558,10 @@ export class TauriAPI {
   }
 
   /**
-   * Save a configuration item
+   * Validate and save a configuration item; it isn't saved when the result has errors
    */
-  async saveConfigItem(item: ConfigItem): Promise<void> {
-    return invoke<void>('save_config_item', { item });
+  async saveConfigItem(item: ConfigItem): Promise<ConfigValidation> {
+    return invoke<ConfigValidation>('save_config_item', { item });
   }
 
   /**
@@ -245,10 +579,22 @@ export class TauriAPI {
   }
 
   /**
-   * Get the currently active configuration
+   * Get the configuration this window uploads with: the one picked for it with
+   * sessionDefaultConfig, else the persisted active configuration
    */
   async getActiveConfig(): Promise<ConfigItem | null> {
-    return invoke<ConfigItem | null>('get_active_config');
+    return invoke<ConfigItem | null>('get_active_config', { windowSession: windowSession() });
+  }
+
+  /**
+   * Point only this window at a saved configuration, or back at the active one
+   * with null. Not persisted; other windows keep their own choice.
+   */
+  async sessionDefaultConfig(configId: string | null): Promise<void> {
+    return invoke<void>('session_default_config', {
+      sessionId: windowSession() ?? 'main',
+      configId,
+    });
   }
 
   // ============================================================================
@@ -263,10 +609,129 @@ export class TauriAPI {
   }
 
   /**
-   * Replace markdown links and return detailed results
+   * Replace markdown links and return detailed results.
+   * With `atomic`, a file that fails to write rolls back every file changed before it.
+   * With `writeChangelog` or `changelogPath`, the changes are saved for `revertFromChangelog`.
+   * Read-only files abort the call before anything changes unless `readOnlyPolicy` is Skip.
+   */
+  async replaceMarkdownLinksWithResult(
+    replacements: LinkReplacement[],
+    atomic?: boolean,
+    writeChangelog?: boolean,
+    changelogPath?: string,
+    readOnlyPolicy?: ReadOnlyPolicy
+  ): Promise<BatchReplacementResult> {
+    return invoke<BatchReplacementResult>('replace_markdown_links_with_result', {
+      replacements,
+      atomic,
+      writeChangelog,
+      changelogPath,
+      readOnlyPolicy,
+    });
+  }
+
+  /**
+   * Markdown files that can't be opened for writing, checked before a replacement
+   */
+  async findUnwritableFiles(filePaths: string[]): Promise<UnwritableFile[]> {
+    return invoke<UnwritableFile[]>('find_unwritable_files', { filePaths });
+  }
+
+  /**
+   * Undo the replacements recorded in a changelog
+   */
+  async revertFromChangelog(path: string): Promise<BatchReplacementResult> {
+    return invoke<BatchReplacementResult>('revert_from_changelog', { path });
+  }
+
+  /**
+   * Replacement runs that backed up their files, newest first
+   */
+  async listReplacementOperations(): Promise<ReplacementOperation[]> {
+    return invoke<ReplacementOperation[]>('list_replacement_operations');
+  }
+
+  /**
+   * Restore every file of a replacement run (the most recent one by default) from its backups.
+   * Nothing is restored when a file changed since the run; see `conflicts`.
+   */
+  async undoLastReplacement(operationId?: string): Promise<ReplacementUndoResult> {
+    return invoke<ReplacementUndoResult>('undo_last_replacement', { operationId });
+  }
+
+  /**
+   * Count each file's image links as local, on the given config, on another saved config, or external
+   */
+  async analyzeMarkdownLinks(filePaths: string[], configId: string): Promise<LinkAnalysisReport> {
+    return invoke<LinkAnalysisReport>('analyze_markdown_links', { filePaths, configId });
+  }
+
+  /**
+   * Move links that point at other saved configs over to the given config
+   */
+  async migrateConfigLinks(
+    filePaths: string[],
+    configId: string,
+    mode?: LinkMigrationMode,
+    dryRun?: boolean
+  ): Promise<LinkMigrationReport> {
+    return invoke<LinkMigrationReport>('migrate_config_links', {
+      filePaths,
+      configId,
+      mode,
+      dryRun,
+    });
+  }
+
+  /**
+   * Swap the host of uploaded URLs from `oldDomain` to `newDomain` in markdown files
+   * (all history source files when `filePaths` is omitted) and optionally in history.
+   * `strict` skips links whose path matches no uploaded object.
+   */
+  async migrateCdnDomain(
+    oldDomain: string,
+    newDomain: string,
+    filePaths?: string[],
+    updateHistory = false,
+    strict?: boolean,
+    dryRun?: boolean
+  ): Promise<CdnMigrationSummary> {
+    return invoke<CdnMigrationSummary>('migrate_cdn_domain', {
+      oldDomain,
+      newDomain,
+      filePaths,
+      updateHistory,
+      strict,
+      dryRun,
+    });
+  }
+
+  /**
+   * Delete backups outside the retention settings
    */
-  async replaceMarkdownLinksWithResult(replacements: LinkReplacement[]): Promise<BatchReplacementResult> {
-    return invoke<BatchReplacementResult>('replace_markdown_links_with_result', { replacements });
+  async cleanupBackups(): Promise<BackupCleanupResult> {
+    return invoke<BackupCleanupResult>('cleanup_backups');
+  }
+
+  /**
+   * Disk space used by markdown backups
+   */
+  async getBackupStorageUsage(): Promise<BackupStorageUsage> {
+    return invoke<BackupStorageUsage>('get_backup_storage_usage');
+  }
+
+  /**
+   * Disk space used by backups, cached thumbnails and archived originals
+   */
+  async getStorageUsage(): Promise<StorageUsage> {
+    return invoke<StorageUsage>('get_storage_usage');
+  }
+
+  /**
+   * The archived original of an upload, if one was kept
+   */
+  async getArchivedOriginal(recordId: string): Promise<ArchivedOriginal | null> {
+    return invoke<ArchivedOriginal | null>('get_archived_original', { recordId });
   }
 
   /**
@@ -283,8 +748,18 @@ export class TauriAPI {
   /**
    * Get paginated upload history
    */
-  async getUploadHistory(page?: number, pageSize?: number): Promise<PaginatedResult<UploadHistoryRecord>> {
-    return invoke<PaginatedResult<UploadHistoryRecord>>('get_upload_history', { page, pageSize });
+  async getUploadHistory(
+    page?: number,
+    pageSize?: number,
+    sortBy?: HistorySortField,
+    sortOrder?: SortOrder
+  ): Promise<PaginatedResult<UploadHistoryRecord>> {
+    return invoke<PaginatedResult<UploadHistoryRecord>>('get_upload_history', {
+      page,
+      pageSize,
+      sortBy,
+      sortOrder,
+    });
   }
 
   /**
@@ -296,7 +771,9 @@ export class TauriAPI {
     startDate?: string,
     endDate?: string,
     page?: number,
-    pageSize?: number
+    pageSize?: number,
+    sortBy?: HistorySortField,
+    sortOrder?: SortOrder
   ): Promise<PaginatedResult<UploadHistoryRecord>> {
     return invoke<PaginatedResult<UploadHistoryRecord>>('search_history', {
       searchTerm,
@@ -305,9 +782,40 @@ export class TauriAPI {
       endDate,
       page,
       pageSize,
+      sortBy,
+      sortOrder,
     });
   }
 
+  /**
+   * History grouped by source markdown file, paginated over groups
+   */
+  async getHistoryGroupedBySource(
+    query?: HistoryQuery,
+    page?: number,
+    pageSize?: number
+  ): Promise<PaginatedResult<HistorySourceGroup>> {
+    return invoke<PaginatedResult<HistorySourceGroup>>('get_history_grouped_by_source', {
+      query,
+      page,
+      pageSize,
+    });
+  }
+
+  /**
+   * Upload throughput and failure rate per provider/config, defaults to the last week
+   */
+  async getUploadPerformanceStats(range?: StatsRange): Promise<UploadPerformanceStats> {
+    return invoke<UploadPerformanceStats>('get_upload_performance_stats', { range });
+  }
+
+  /**
+   * Find history records by checksum prefix (at least 8 hex characters), newest first
+   */
+  async searchHistoryByChecksum(prefix: string): Promise<UploadHistoryRecord[]> {
+    return invoke<UploadHistoryRecord[]>('search_history_by_checksum', { prefix });
+  }
+
   /**
    * Clear all history records
    */
@@ -316,10 +824,10 @@ export class TauriAPI {
   }
 
   /**
-   * Export history data as JSON string
+   * Export history data as JSON string, with the trash when includeTrashed is set
    */
-  async exportHistory(): Promise<string> {
-    return invoke<string>('export_history');
+  async exportHistory(includeTrashed?: boolean): Promise<string> {
+    return invoke<string>('export_history', { includeTrashed });
   }
 
   /**
@@ -340,6 +848,18 @@ export class TauriAPI {
     URL.revokeObjectURL(url);
   }
 
+  /**
+   * Export history records matching the filter as a standalone gallery page
+   */
+  async exportHistoryGallery(
+    path: string,
+    format?: GalleryFormat,
+    filter?: HistoryQuery,
+    overwrite?: boolean
+  ): Promise<string> {
+    return invoke<string>('export_history_gallery', { path, format, filter, overwrite });
+  }
+
   /**
    * Add a new history record
    */
@@ -364,62 +884,82 @@ export class TauriAPI {
   }
 
   /**
-   * Get history statistics
+   * Get history statistics; trashed records are only counted with includeTrashed
    */
-  async getHistoryStatistics(): Promise<HistoryStatistics> {
-    return invoke<HistoryStatistics>('get_history_statistics');
+  async getHistoryStatistics(includeTrashed?: boolean): Promise<HistoryStatistics> {
+    return invoke<HistoryStatistics>('get_history_statistics', { includeTrashed });
   }
 
   /**
-   * Clean up old history records older than specified days
+   * Move history records older than specified days to the trash.
+   * With dryRun nothing is moved, the result shows what would be.
    */
-  async cleanupOldHistory(olderThanDays: number): Promise<number> {
-    return invoke<number>('cleanup_old_history', { olderThanDays });
+  async cleanupOldHistory(olderThanDays: number, dryRun?: boolean): Promise<HistoryCleanupResult> {
+    return invoke<HistoryCleanupResult>('cleanup_old_history', { olderThanDays, dryRun });
   }
 
   /**
-   * Get file operations with optional limit
+   * Deleted upload history records, most recently deleted first
    */
-  async getFileOperations(limit?: number): Promise<FileOperation[]> {
-    return invoke<FileOperation[]>('get_file_operations', { limit });
+  async listTrashedHistory(): Promise<TrashedHistoryRecord[]> {
+    return invoke<TrashedHistoryRecord[]>('list_trashed_history');
   }
 
-  // ============================================================================
-  // 图片历史记录操作
-  // ============================================================================
+  /**
+   * Move records back from the trash, returns the IDs restored
+   */
+  async restoreHistoryRecords(ids: string[]): Promise<string[]> {
+    return invoke<string[]>('restore_history_records', { ids });
+  }
 
   /**
-   * 添加单个图片历史记录
+   * Delete trashed records for good with their cached thumbnails, all of them
+   * or those deleted more than olderThanDays ago
    */
-  async addImageHistoryRecord(
-    imageName: string,
-    originalPath: string,
-    uploadedUrl: string | undefined,
-    uploadMode: UploadMode,
-    sourceFile?: string,
-    success: boolean = true,
-    fileSize: number = 0,
-    errorMessage?: string,
-    checksum?: string
-  ): Promise<string> {
-    return invoke<string>('add_image_history_record', {
-      imageName,
-      originalPath,
-      uploadedUrl,
-      uploadMode,
-      sourceFile,
-      success,
-      fileSize,
-      errorMessage,
-      checksum,
-    });
+  async purgeTrash(olderThanDays?: number, dryRun?: boolean): Promise<HistoryCleanupResult> {
+    return invoke<HistoryCleanupResult>('purge_trash', { olderThanDays, dryRun });
+  }
+
+  /**
+   * Hash the local originals of uploaded images (those the query matches, all
+   * without one) and compare them with the checksums stored at upload.
+   * Progress comes as integrity://progress; cancel with cancelScan(scanId)
+   */
+  async verifyLocalIntegrity(query?: HistoryQuery, scanId?: string): Promise<LocalIntegrityReport> {
+    return invoke<LocalIntegrityReport>('verify_local_integrity', { query, scanId });
+  }
+
+  /**
+   * Get recent backup/replace/restore operations, newest first
+   */
+  async getFileOperations(
+    limit?: number,
+    operationType?: FileOperationType
+  ): Promise<FileOperation[]> {
+    return invoke<FileOperation[]>('get_file_operations', { limit, operationType });
   }
 
   /**
-   * 批量添加上传历史记录
+   * Clear the file operations log, returns the number of entries removed
+   */
+  async clearFileOperations(): Promise<number> {
+    return invoke<number>('clear_file_operations');
+  }
+
+  // ============================================================================
+  // 图片历史记录操作
+  // ============================================================================
+
+  /**
+   * 批量添加上传历史记录，超过 MAX_HISTORY_BATCH 条时分页发送
    */
   async addBatchUploadHistoryRecords(records: UploadHistoryRecord[]): Promise<string[]> {
-    return invoke<string[]>('add_batch_upload_history_records', { records });
+    const ids: string[] = [];
+    for (let start = 0; start < records.length || start === 0; start += MAX_HISTORY_BATCH) {
+      const page = records.slice(start, start + MAX_HISTORY_BATCH);
+      ids.push(...(await invoke<string[]>('add_batch_upload_history_records', { records: page })));
+    }
+    return ids;
   }
 
   /**
@@ -482,22 +1022,28 @@ export class TauriAPI {
   /**
    * Check if an image is a duplicate based on its checksum
    */
-  async checkDuplicateByChecksum(checksum: string): Promise<DuplicateCheckResult> {
-    return invoke<DuplicateCheckResult>('check_duplicate_by_checksum', { checksum });
+  async checkDuplicateByChecksum(
+    checksum: string,
+    options?: DuplicateLookupOptions
+  ): Promise<DuplicateCheckResult> {
+    return invoke<DuplicateCheckResult>('check_duplicate_by_checksum', { checksum, ...options });
   }
 
   /**
    * Check multiple images for duplicates in batch
    */
-  async checkDuplicatesBatch(imagePaths: string[]): Promise<DuplicateCheckResult[]> {
-    return invoke<DuplicateCheckResult[]>('check_duplicates_batch', { imagePaths });
+  async checkDuplicatesBatch(
+    imagePaths: string[],
+    options?: DuplicateLookupOptions
+  ): Promise<DuplicateCheckResult[]> {
+    return invoke<DuplicateCheckResult[]>('check_duplicates_batch', { imagePaths, ...options });
   }
 
   /**
    * Get detailed information about a duplicate image
    */
-  async getDuplicateInfo(checksum: string): Promise<DuplicateInfo | null> {
-    return invoke<DuplicateInfo | null>('get_duplicate_info', { checksum });
+  async getDuplicateInfo(checksum: string, options?: DuplicateLookupOptions): Promise<DuplicateInfo | null> {
+    return invoke<DuplicateInfo | null>('get_duplicate_info', { checksum, ...options });
   }
 
   // ============================================================================
@@ -507,8 +1053,64 @@ export class TauriAPI {
   /**
    * Get current system health status
    */
-  async getSystemHealth(): Promise<SystemHealth> {
-    return invoke<SystemHealth>('get_system_health');
+  async getSystemHealth(includeNetwork?: boolean): Promise<SystemHealth> {
+    return invoke<SystemHealth>('get_system_health', { includeNetwork });
+  }
+
+  /**
+   * Outcomes of the migrations and cleanups run at startup
+   */
+  async getStartupReport(): Promise<StartupReport> {
+    return invoke<StartupReport>('get_startup_report');
+  }
+
+  /**
+   * Crash reports left by backend panics, newest first
+   */
+  async listCrashReports(): Promise<CrashReportSummary[]> {
+    return invoke<CrashReportSummary[]>('list_crash_reports');
+  }
+
+  /**
+   * One crash report with its backtrace
+   */
+  async getCrashReport(id: string): Promise<CrashReport> {
+    return invoke<CrashReport>('get_crash_report', { id });
+  }
+
+  /**
+   * Background cleanup jobs, whether each is enabled and how its last run went
+   */
+  async getMaintenanceStatus(): Promise<MaintenanceStatus> {
+    return invoke<MaintenanceStatus>('get_maintenance_status');
+  }
+
+  /**
+   * Salvage records from the newest corrupt copy of a store into the live one
+   */
+  async recoverCorruptStore(kind: StoreKind): Promise<StoreRecoveryResult> {
+    return invoke<StoreRecoveryResult>('recover_corrupt_store', { kind });
+  }
+
+  /**
+   * Whether the endpoint is reachable and how many uploads wait in the offline queue
+   */
+  async getConnectivityStatus(): Promise<ConnectivityStatus> {
+    return invoke<ConnectivityStatus>('get_connectivity_status');
+  }
+
+  /**
+   * Check the active config/data directories are writable and report the storage mode
+   */
+  async validateSystemPermissions(): Promise<SystemPermissions> {
+    return invoke<SystemPermissions>('validate_system_permissions');
+  }
+
+  /**
+   * Move configs and data to another directory (null for the default), effective after restart
+   */
+  async setDataDirectory(path: string | null): Promise<DataDirectoryChange> {
+    return invoke<DataDirectoryChange>('set_data_directory', { path });
   }
 
   /**
@@ -537,10 +1139,11 @@ export class TauriAPI {
   // ============================================================================
 
   /**
-   * Cancel a specific upload task
+   * Cancel a specific upload task, or every task of the sessions `scope`
+   * matches when `taskId` is omitted. With both, the task must be in the scope.
    */
-  async cancelUploadTask(taskId: string): Promise<void> {
-    return invoke<void>('cancel_upload_task', { taskId });
+  async cancelUploadTask(taskId?: string, scope?: UploadScope): Promise<void> {
+    return invoke<void>('cancel_upload_task', { taskId, scope });
   }
 
   /**
@@ -558,10 +1161,11 @@ export class TauriAPI {
   }
 
   /**
-   * Get all upload tasks with their current status
+   * Get all upload tasks with their current status, grouped by session in
+   * `sessions`; `scope` narrows them to the matching sessions
    */
-  async getAllUploadTasks(): Promise<UploadTaskManager> {
-    return invoke<UploadTaskManager>('get_all_upload_tasks');
+  async getAllUploadTasks(scope?: UploadScope): Promise<UploadTaskManager> {
+    return invoke<UploadTaskManager>('get_all_upload_tasks', { scope });
   }
 }
 
@@ -570,22 +1174,52 @@ export const tauriAPI = new TauriAPI();
 
 // Export individual method groups for convenience
 export const fileOperations = {
-  scanMarkdownFiles: (filePaths: string[]) => tauriAPI.scanMarkdownFiles(filePaths),
+  scanMarkdownFiles: (filePaths: string[], scanId?: string) =>
+    tauriAPI.scanMarkdownFiles(filePaths, scanId),
+  scanMarkdownFilesStreamed: (scanId: string, filePaths: string[]) =>
+    tauriAPI.scanMarkdownFilesStreamed(scanId, filePaths),
+  cancelScan: (scanId: string) => tauriAPI.cancelScan(scanId),
+  revalidateScanResults: (results: ScanResult[]) => tauriAPI.revalidateScanResults(results),
+  findLocalDuplicates: (directory: string, recursive?: boolean, scanId?: string, scanResults?: ScanResult[]) =>
+    tauriAPI.findLocalDuplicates(directory, recursive, scanId, scanResults),
+  exportDuplicateReport: (report: LocalDuplicateReport, path: string, format?: ReportFormat, overwrite?: boolean) =>
+    tauriAPI.exportDuplicateReport(report, path, format, overwrite),
   getImageInfo: (imagePath: string) => tauriAPI.getImageInfo(imagePath),
-  generateThumbnail: (imagePath: string, size: number) => tauriAPI.generateThumbnail(imagePath, size),
+  validateImages: (paths: string[]) => tauriAPI.validateImages(paths),
+  generateThumbnail: (imagePath: string, size: number, outputFormat?: ThumbnailFormat) =>
+    tauriAPI.generateThumbnail(imagePath, size, outputFormat),
+  convertImageFormat: (imagePath: string, targetFormat: string, outputPath?: string) =>
+    tauriAPI.convertImageFormat(imagePath, targetFormat, outputPath),
+  suggestCompressionQuality: (imagePath: string, targetSizeKb?: number) =>
+    tauriAPI.suggestCompressionQuality(imagePath, targetSizeKb),
+};
+
+export const backupOperations = {
+  cleanupBackups: () => tauriAPI.cleanupBackups(),
+  getBackupStorageUsage: () => tauriAPI.getBackupStorageUsage(),
+  getStorageUsage: () => tauriAPI.getStorageUsage(),
+  getArchivedOriginal: (recordId: string) => tauriAPI.getArchivedOriginal(recordId),
+  listReplacementOperations: () => tauriAPI.listReplacementOperations(),
+  undoLastReplacement: (operationId?: string) => tauriAPI.undoLastReplacement(operationId),
 };
 
 export const thumbnailOperations = {
   getThumbnail: (recordId: string, imageUrl: string) => tauriAPI.getThumbnail(recordId, imageUrl),
   cleanupThumbnailCache: () => tauriAPI.cleanupThumbnailCache(),
+  getCacheStats: () => tauriAPI.getCacheStats(),
 };
 
 export const uploadOperations = {
-  uploadImages: (imagePaths: string[], config: OSSConfig) => tauriAPI.uploadImages(imagePaths, config),
-  uploadImagesWithIds: (imageData: [string, string][], config: OSSConfig) => tauriAPI.uploadImagesWithIds(imageData, config),
-  uploadImagesBatch: (imagePaths: string[], config: OSSConfig, batchSize?: number) => tauriAPI.uploadImagesBatch(imagePaths, config, batchSize),
+  uploadImages: (imagePaths: string[], config?: OSSConfig, configId?: string) =>
+    tauriAPI.uploadImages(imagePaths, config, configId),
+  uploadImagesWithIds: (imageData: [string, string][], config?: OSSConfig) => tauriAPI.uploadImagesWithIds(imageData, config),
+  uploadImagesBatch: (imagePaths: string[], config?: OSSConfig, batchSize?: number) => tauriAPI.uploadImagesBatch(imagePaths, { config, batch_size: batchSize }),
+  estimateBatch: (imagePaths: string[], config?: OSSConfig, configId?: string) =>
+    tauriAPI.estimateBatch(imagePaths, config, configId),
+  uploadArticleImages: (scanResults: ScanResult[], selectedImageIds: string[], configId?: string) =>
+    tauriAPI.uploadArticleImages(scanResults, selectedImageIds, configId),
   getUploadProgress: (taskId: string) => tauriAPI.getUploadProgress(taskId),
-  getAllUploadProgress: () => tauriAPI.getAllUploadProgress(),
+  getAllUploadProgress: (scope?: UploadScope) => tauriAPI.getAllUploadProgress(scope),
   cancelUpload: (taskId: string) => tauriAPI.cancelUpload(taskId),
   retryUpload: (taskId: string) => tauriAPI.retryUpload(taskId),
   clearUploadProgress: () => tauriAPI.clearUploadProgress(),
@@ -596,33 +1230,53 @@ export const configOperations = {
   saveOSSConfig: (config: OSSConfig, options?: SaveOptions) => tauriAPI.saveOSSConfig(config, options),
   loadOSSConfig: () => tauriAPI.loadOSSConfig(),
   testOSSConnection: (config: OSSConfig) => tauriAPI.testOSSConnection(config),
+  diagnoseConnection: (config: OSSConfig) => tauriAPI.diagnoseConnection(config),
   validateOSSConfig: (config: OSSConfig) => tauriAPI.validateOSSConfig(config),
+  validatePathTemplate: (template: string, provider: OSSProvider) => tauriAPI.validatePathTemplate(template, provider),
+  getProviderCapabilities: (provider: OSSProvider) => tauriAPI.getProviderCapabilities(provider),
   getCachedConnectionStatus: (config: OSSConfig) => tauriAPI.getCachedConnectionStatus(config),
   clearConnectionCache: () => tauriAPI.clearConnectionCache(),
+  testAllConfigs: (deep?: boolean) => tauriAPI.testAllConfigs(deep),
   listOSSObjects: (config: OSSConfig, prefix?: string) => tauriAPI.listOSSObjects(config, prefix || ''),
+  listOSSPrefixes: (config: OSSConfig, prefix?: string, delimiter?: string, refresh?: boolean) =>
+    tauriAPI.listOSSPrefixes(config, prefix || '', delimiter, refresh),
   exportOSSConfig: () => tauriAPI.exportOSSConfig(),
   importOSSConfig: (configJson: string) => tauriAPI.importOSSConfig(configJson),
+  exportAppData: (targetZipPath: string, include: AppDataInclude, overwrite?: boolean) =>
+    tauriAPI.exportAppData(targetZipPath, include, overwrite),
+  previewAppDataImport: (zipPath: string) => tauriAPI.previewAppDataImport(zipPath),
+  importAppData: (zipPath: string, merge: boolean) => tauriAPI.importAppData(zipPath, merge),
   // Multi-config management
   getAllConfigs: () => tauriAPI.getAllConfigs(),
   saveConfigItem: (item: ConfigItem) => tauriAPI.saveConfigItem(item),
   setActiveConfig: (configId: string) => tauriAPI.setActiveConfig(configId),
   deleteConfigItem: (configId: string) => tauriAPI.deleteConfigItem(configId),
   getActiveConfig: () => tauriAPI.getActiveConfig(),
+  sessionDefaultConfig: (configId: string | null) => tauriAPI.sessionDefaultConfig(configId),
 };
 
 export const historyOperations = {
-  getUploadHistory: (page?: number, pageSize?: number) => tauriAPI.getUploadHistory(page, pageSize),
+  getUploadHistory: (page?: number, pageSize?: number, sortBy?: HistorySortField, sortOrder?: SortOrder) =>
+    tauriAPI.getUploadHistory(page, pageSize, sortBy, sortOrder),
   searchHistory: (
     searchTerm?: string,
     uploadMode?: string,
     startDate?: string,
     endDate?: string,
     page?: number,
-    pageSize?: number
-  ) => tauriAPI.searchHistory(searchTerm, uploadMode, startDate, endDate, page, pageSize),
+    pageSize?: number,
+    sortBy?: HistorySortField,
+    sortOrder?: SortOrder
+  ) => tauriAPI.searchHistory(searchTerm, uploadMode, startDate, endDate, page, pageSize, sortBy, sortOrder),
+  getHistoryGroupedBySource: (query?: HistoryQuery, page?: number, pageSize?: number) =>
+    tauriAPI.getHistoryGroupedBySource(query, page, pageSize),
+  getUploadPerformanceStats: (range?: StatsRange) => tauriAPI.getUploadPerformanceStats(range),
+  searchHistoryByChecksum: (prefix: string) => tauriAPI.searchHistoryByChecksum(prefix),
   clearHistory: () => tauriAPI.clearHistory(),
-  exportHistory: () => tauriAPI.exportHistory(),
+  exportHistory: (includeTrashed?: boolean) => tauriAPI.exportHistory(includeTrashed),
   exportHistoryToFile: () => tauriAPI.exportHistoryToFile(),
+  exportHistoryGallery: (path: string, format?: GalleryFormat, filter?: HistoryQuery, overwrite?: boolean) =>
+    tauriAPI.exportHistoryGallery(path, format, filter, overwrite),
   addHistoryRecord: (
     operation: string,
     files: string[],
@@ -632,26 +1286,41 @@ export const historyOperations = {
     totalSize?: number,
     errorMessage?: string
   ) => tauriAPI.addHistoryRecord(operation, files, imageCount, success, duration, totalSize, errorMessage),
-  getHistoryStatistics: () => tauriAPI.getHistoryStatistics(),
+  getHistoryStatistics: (includeTrashed?: boolean) => tauriAPI.getHistoryStatistics(includeTrashed),
+  listTrashedHistory: () => tauriAPI.listTrashedHistory(),
+  restoreHistoryRecords: (ids: string[]) => tauriAPI.restoreHistoryRecords(ids),
+  purgeTrash: (olderThanDays?: number, dryRun?: boolean) => tauriAPI.purgeTrash(olderThanDays, dryRun),
+  verifyLocalIntegrity: (query?: HistoryQuery, scanId?: string) => tauriAPI.verifyLocalIntegrity(query, scanId),
 };
 
 export const duplicateOperations = {
   calculateImageChecksum: (imagePath: string) => tauriAPI.calculateImageChecksum(imagePath),
-  checkDuplicateByChecksum: (checksum: string) => tauriAPI.checkDuplicateByChecksum(checksum),
-  checkDuplicatesBatch: (imagePaths: string[]) => tauriAPI.checkDuplicatesBatch(imagePaths),
-  getDuplicateInfo: (checksum: string) => tauriAPI.getDuplicateInfo(checksum),
+  checkDuplicateByChecksum: (checksum: string, options?: DuplicateLookupOptions) =>
+    tauriAPI.checkDuplicateByChecksum(checksum, options),
+  checkDuplicatesBatch: (imagePaths: string[], options?: DuplicateLookupOptions) =>
+    tauriAPI.checkDuplicatesBatch(imagePaths, options),
+  getDuplicateInfo: (checksum: string, options?: DuplicateLookupOptions) =>
+    tauriAPI.getDuplicateInfo(checksum, options),
 };
 
 export const systemHealthOperations = {
-  getSystemHealth: () => tauriAPI.getSystemHealth(),
+  getSystemHealth: (includeNetwork?: boolean) => tauriAPI.getSystemHealth(includeNetwork),
+  getStartupReport: () => tauriAPI.getStartupReport(),
+  getMaintenanceStatus: () => tauriAPI.getMaintenanceStatus(),
+  recoverCorruptStore: (kind: StoreKind) => tauriAPI.recoverCorruptStore(kind),
+  listCrashReports: () => tauriAPI.listCrashReports(),
+  getCrashReport: (id: string) => tauriAPI.getCrashReport(id),
+  getConnectivityStatus: () => tauriAPI.getConnectivityStatus(),
+  validateSystemPermissions: () => tauriAPI.validateSystemPermissions(),
+  setDataDirectory: (path: string | null) => tauriAPI.setDataDirectory(path),
   getNotificationConfig: () => tauriAPI.getNotificationConfig(),
   updateNotificationConfig: (config: NotificationConfig) => tauriAPI.updateNotificationConfig(config),
   sendNotification: (notification: ProgressNotification) => tauriAPI.sendNotification(notification),
 };
 
 export const taskManagementOperations = {
-  cancelUploadTask: (taskId: string) => tauriAPI.cancelUploadTask(taskId),
+  cancelUploadTask: (taskId?: string, scope?: UploadScope) => tauriAPI.cancelUploadTask(taskId, scope),
   retryUploadTask: (taskId: string, maxRetries?: number) => tauriAPI.retryUploadTask(taskId, maxRetries),
   getUploadTaskStatus: (taskId: string) => tauriAPI.getUploadTaskStatus(taskId),
-  getAllUploadTasks: () => tauriAPI.getAllUploadTasks(),
+  getAllUploadTasks: (scope?: UploadScope) => tauriAPI.getAllUploadTasks(scope),
 };
\ No newline at end of file
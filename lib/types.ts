@@ -10,11 +10,145 @@ export interface ScanResult {
   images: ImageReference[];
   status: ScanStatus;
   error?: string;
+  unique_image_count?: number;
+  warnings: ScanWarning[]; // problems that don't fail the file
+  encoding?: string; // set when the file isn't UTF-8, e.g. "GBK" or "Big5"
+  content_hash?: string; // sha256 of the file as scanned
+}
+
+export interface MissingImage {
+  file_path: string;
+  original_path: string;
+  absolute_path: string;
+  line: number;
+  column: number;
+}
+
+// What changed on disk since a scan, from revalidate_scan_results
+export interface ScanRevalidation {
+  changed_files: string[]; // edited, deleted or unreadable; rescan before replacing
+  newly_missing: MissingImage[];
+  changed_images: string[]; // other size or modification time
+}
+
+export type ScanWarningCode =
+  | "unreadable_image"
+  | "permission_denied"
+  | "suspicious_path"
+  | "legacy_encoding" // the file isn't UTF-8, see ScanResult.encoding
+  | "code_fence"
+  | "filtered"; // left out by include/exclude glob rules
+
+// Glob rules for one scan, matched against absolute paths; a list that is set
+// replaces the one from settings, an empty list turns that rule off
+export interface ScanGlobOptions {
+  include_image_globs?: string[];
+  exclude_image_globs?: string[];
+  exclude_file_globs?: string[];
+}
+
+export interface ScanWarning {
+  code: ScanWarningCode;
+  message: string;
+  image_path?: string;
+  line?: number;
+}
+
+// Payload of the scan://progress event
+export interface ScanProgress {
+  scan_id?: string;
+  current: number;
+  total: number;
+  file_path: string;
+  images_found: number;
+}
+
+// Payload of the scan://done event
+export interface ScanSummary {
+  scan_id?: string;
+  total_files: number;
+  scanned_files: number;
+  total_images: number;
+  missing_images: number;
+  failed_files: number;
+  skipped_files: number; // over the markdown size limit, not read
+  ignored_files?: number; // left out of a directory scan by .gitignore, .ignore or .imgtossignore
+  cancelled: boolean;
+}
+
+// Payload of the duplicates://progress and integrity://progress events
+export interface DuplicateScanProgress {
+  scan_id?: string;
+  current: number; // files hashed so far
+  total: number;
+  bytes_hashed: number;
+  total_bytes: number;
+}
+
+export interface LocalDuplicateFile {
+  path: string;
+  usage_count?: number; // markdown references, only when scan results were given
+}
+
+export interface LocalDuplicateGroup {
+  checksum: string;
+  size: number;
+  files: LocalDuplicateFile[];
+  wasted_bytes: number;
+}
+
+export interface LocalDuplicateReport {
+  directory: string;
+  recursive: boolean;
+  generated_at: string;
+  files_scanned: number;
+  files_hashed: number; // only files sharing their size with another are hashed
+  bytes_hashed: number;
+  groups: LocalDuplicateGroup[]; // largest waste first
+  wasted_bytes: number;
+  unreadable_files: string[];
+  cancelled: boolean;
+}
+
+// An uploaded image whose local original verify_local_integrity checked
+export interface LocalIntegrityFile {
+  record_id: string;
+  image_name: string;
+  uploaded_url: string;
+  original_path: string;
+  uploaded_at: string; // ISO timestamp
+  uploaded_size: number; // bytes uploaded, after processing
+  local_size?: number; // unset when the file is missing
+  modified_at?: string; // ISO timestamp, unset when the file is missing
+}
+
+export interface LocalIntegrityReport {
+  scan_id?: string;
+  generated_at: string;
+  records_checked: number; // records with an original path
+  files_hashed: number;
+  bytes_hashed: number;
+  matched: LocalIntegrityFile[];
+  modified: LocalIntegrityFile[]; // re-uploading these would change what readers see
+  missing: LocalIntegrityFile[];
+  unverifiable: string[]; // unreadable files or records without a usable checksum
+  cancelled: boolean;
+}
+
+export type ReportFormat = 'json' | 'markdown';
+
+// Payload of the scan://result event, one per file of a streamed scan
+export interface ScanResultEvent {
+  scan_id: string;
+  current: number; // 1-based
+  total: number;
+  result: ScanResult;
 }
 
 export enum ScanStatus {
   Success = "Success",
   Error = "Error",
+  Skipped = "Skipped", // over the markdown size limit, reason in `error`
 }
 
 export interface ImageReference {
@@ -27,6 +161,11 @@ export interface ImageReference {
   markdown_line: number;
   markdown_column: number;
   thumbnail?: string;
+  reference_group?: string;
+  too_large?: boolean;
+  context?: LinkContext;
+  asset_root?: string; // asset root the image was found under
+  path_suffix?: string; // ?query / #fragment kept in original_path, e.g. ?raw=true
 }
 
 export interface ImageInfo {
@@ -35,6 +174,30 @@ export interface ImageInfo {
   format: string;
   size: number;
   color_space?: string;
+  icc_profile?: string; // embedded ICC profile name, e.g. "Display P3"
+}
+
+// Why validate_images turned a file down; 'svg' is also set on supported SVGs,
+// which upload as-is without dimensions
+export type ImageRejectReason =
+  | 'not_found'
+  | 'not_selected'
+  | 'invalid_path'
+  | 'unsupported_format'
+  | 'missing_codec'
+  | 'svg'
+  | 'too_large'
+  | 'corrupt';
+
+export interface ImageValidation {
+  path: string;
+  supported: boolean;
+  format?: string;
+  width?: number;
+  height?: number;
+  size?: number;
+  reason?: ImageRejectReason;
+  message?: string;
 }
 
 // ============================================================================
@@ -59,11 +222,56 @@ export enum UploadStatus {
   Failed = "Failed",
 }
 
+export interface ImageConversionResult {
+  output_path?: string;
+  data_base64?: string;
+  format: string;
+  size: number;
+}
+
 export interface UploadResult {
   image_id: string;
   success: boolean;
   uploaded_url?: string;
   error?: string;
+  variants?: ImageVariant[];
+  // No connection: the upload waits in the offline queue instead of failing
+  queued_offline?: boolean;
+  // Non-fatal problems, e.g. the history record couldn't be saved yet
+  warnings?: string[];
+}
+
+// One selected reference of upload_article_images; references to the same file share one upload
+export interface ArticleImageUpload {
+  result: UploadResult; // image_id is the selected ImageReference id
+  replacement?: LinkReplacement; // Rewrites this reference to the uploaded URL, missing when the upload failed
+}
+
+export type ThumbnailFormat = 'jpeg' | 'png' | 'webp';
+
+// generate_thumbnail output; PNG and WebP keep transparency
+export interface GeneratedThumbnail {
+  data: number[];
+  format: ThumbnailFormat;
+  mime_type: string; // e.g. "image/png", for the Blob or data URL
+}
+
+// Thumbnail caches: generate_thumbnail results in memory, history thumbnails on disk
+export interface CacheStats {
+  memory_hits: number;
+  memory_misses: number;
+  memory_entries: number;
+  memory_bytes: number;
+  total_files: number; // Disk cache
+  total_size_bytes: number;
+  oldest_file?: string; // ISO 8601
+  newest_file?: string; // ISO 8601
+}
+
+export interface ImageVariant {
+  width: number;
+  url: string;
+  bytes: number;
 }
 
 export interface UploadProgress {
@@ -71,7 +279,18 @@ export interface UploadProgress {
   progress: number;
   bytes_uploaded: number;
   total_bytes: number;
-  speed?: number; // bytes per second
+  speed?: number; // bytes per second, averaged over the last few seconds
+  eta_secs?: number;
+  // Upload session the task belongs to, see UploadScope
+  session_id?: string;
+  config_id?: string;
+}
+
+// One upload command call targeting one config; several can run at once.
+// As a filter, omitted fields match anything.
+export interface UploadScope {
+  session_id?: string;
+  config_id?: string;
 }
 
 // ============================================================================
@@ -89,6 +308,15 @@ export interface OSSConfig {
   cdn_domain?: string;
   compression_enabled: boolean;
   compression_quality: number;
+  object_acl?: ObjectAcl;
+  max_upload_size_kb?: number;
+  size_variants?: number[];
+}
+
+export enum ObjectAcl {
+  Default = "Default",
+  PublicRead = "PublicRead",
+  Private = "Private",
 }
 
 export enum OSSProvider {
@@ -119,6 +347,46 @@ export interface OSSConnectionTest {
   latency?: number; // milliseconds
   bucket_exists?: boolean; // Whether the specified bucket exists
   available_buckets?: string[]; // List of available buckets (if accessible)
+  diagnostics: ConnectionTestStep[]; // What the test attempted, in order
+}
+
+// One connection test step, also the payload of the config://test-progress event
+export interface ConnectionTestStep {
+  step: string; // "config" | "cache" | "dns" | "tls" | "connect" | "auth" | "bucket" | ...
+  ok: boolean;
+  detail?: string; // e.g. "403 SignatureDoesNotMatch"
+}
+
+// Result of diagnose_connection, steps are also emitted as config://test-progress events
+export interface ConnectionDiagnosis {
+  success: boolean;
+  steps: ConnectionTestStep[]; // "config" | "dns" | "connect" | "tls" | "clock" | then the signed test's steps
+  clock_skew_seconds?: number; // Local clock minus server clock, positive when local is ahead
+  findings: string[]; // e.g. "Your system clock is off by 1200 seconds ..."
+}
+
+export type ConfigHealthStatus =
+  | 'healthy'
+  | 'failed' // the provider rejected the config
+  | 'unreachable'
+  | 'timed_out'
+  | 'missing_credentials' // access key or secret empty, nothing was sent
+  | 'invalid'; // field checks failed, nothing was sent
+
+export interface ConfigTestResult {
+  config_id: string;
+  name: string;
+  status: ConfigHealthStatus;
+  test: OSSConnectionTest;
+  cached: boolean; // taken from the connection cache
+  duration_ms: number;
+}
+
+// Payload of the config://test-all-progress event
+export interface ConfigTestProgress {
+  completed: number;
+  total: number;
+  result: ConfigTestResult;
 }
 
 export interface ObjectInfo {
@@ -139,6 +407,131 @@ export interface LinkReplacement {
   column: number;
   old_link: string;
   new_link: string;
+  context?: LinkContext;
+}
+
+// Syntax an image link was found in, decides how the new link is escaped
+export enum LinkContext {
+  MarkdownInline = "MarkdownInline",
+  ReferenceDefinition = "ReferenceDefinition",
+  HtmlAttribute = "HtmlAttribute",
+  FrontMatter = "FrontMatter",
+  WikiEmbed = "WikiEmbed",
+  Verbatim = "Verbatim", // written as given, used to revert from a changelog
+}
+
+// A copy of a markdown file taken before it was rewritten
+export interface BackupInfo {
+  id: string;
+  original_path: string;
+  backup_path: string;
+  timestamp: string;
+  size: number;
+  checksum: string;
+}
+
+// A backed-up replacement run kept for undo_last_replacement
+export interface ReplacementOperation {
+  id: string;
+  timestamp: string;
+  source: string; // command that ran it, e.g. "process_markdown_files"
+  files: ReplacementOperationFile[];
+  undone_at?: string;
+}
+
+export interface ReplacementOperationFile {
+  backup: BackupInfo;
+  checksum_after: string; // sha256 of the file right after the run
+  restored?: boolean; // put back by an undo that didn't finish
+}
+
+export interface ReplacementUndoResult {
+  operation_id: string;
+  undone: boolean;
+  restored_files: string[];
+  conflicts: UndoFileError[]; // stopped the undo before anything was restored
+  failed: UndoFileError[]; // restores that failed after the checks passed
+}
+
+export interface UndoFileError {
+  file_path: string;
+  message: string;
+}
+
+export interface BackupCleanupResult {
+  removed_files: number;
+  removed_bytes: number;
+}
+
+export interface HistoryCleanupResult {
+  dry_run: boolean;
+  removed_records: number;
+  removed_bytes: number; // summed file_size of the removed records
+  removed_thumbnails: number; // only purging the trash removes thumbnails
+  thumbnail_bytes: number;
+}
+
+export interface BackupStorageUsage {
+  backup_count: number;
+  file_count: number;
+  total_bytes: number;
+  oldest_backup?: string;
+}
+
+// Unprocessed source of an upload, kept when archive_originals is on
+export interface ArchivedOriginal {
+  record_id: string;
+  sha256: string;
+  path: string;
+  size: number;
+  archived_at: string; // ISO timestamp
+}
+
+export interface OriginalArchiveUsage {
+  file_count: number;
+  total_bytes: number;
+  record_count: number;
+}
+
+export interface StorageUsage {
+  backups: BackupStorageUsage;
+  thumbnail_files: number;
+  thumbnail_bytes: number;
+  archived_originals: OriginalArchiveUsage;
+  total_bytes: number;
+}
+
+export interface AppDataInclude {
+  configs: boolean;
+  secrets: boolean; // Keep OSS secret keys, marks the archive as sensitive
+  history: boolean;
+  settings: boolean;
+}
+
+export interface AppDataManifest {
+  format_version: number;
+  app_version: string;
+  created_at: string;
+  include: AppDataInclude;
+  sensitive: boolean;
+  schema_versions: Record<string, number>;
+}
+
+export interface AppDataPreview {
+  manifest: AppDataManifest;
+  entries: string[];
+  config_names: string[];
+  upload_record_count: number;
+  file_operation_count: number;
+  has_settings: boolean;
+}
+
+export interface AppDataImportResult {
+  configs_imported: number;
+  configs_missing_secrets: string[];
+  upload_records_imported: number;
+  file_operations_imported: number;
+  settings_imported: boolean;
 }
 
 export interface FileOperation {
@@ -150,6 +543,7 @@ export interface FileOperation {
 }
 
 export enum FileOperationType {
+  Backup = "Backup",
   Replace = "Replace",
   Restore = "Restore",
 }
@@ -160,6 +554,109 @@ export interface ReplacementResult {
   successful_replacements: number;
   failed_replacements: ReplacementError[];
   duration: string; // SystemTime serialized as ISO string
+  changes?: ReplacementChange[];
+  encoding?: string; // non-UTF-8 encoding the file was read in and kept
+  converted_to_utf8?: boolean; // rewritten as UTF-8 (convert_legacy_encoding setting)
+  warnings?: ReplacementWarning[]; // the file read back with more changed than its links
+  restored?: boolean; // put back as before (restore_unverified_replacements setting)
+}
+
+export type ReplacementWarningCode =
+  | 'link_missing'
+  | 'line_changed'
+  | 'line_count_changed'
+  | 'reference_count_changed'
+  | 'unreadable';
+
+export interface ReplacementWarning {
+  code: ReplacementWarningCode;
+  message: string;
+  line?: number;
+}
+
+// One written link rewrite, as kept in .imgtoss-changes.json
+export interface ReplacementChange {
+  file_path: string;
+  line: number;
+  column: number; // where written_text starts in the rewritten line
+  old_link: string;
+  new_link: string;
+  uploaded_url?: string;
+  object_key?: string;
+  history_record_id?: string;
+  replaced_text: string;
+  written_text: string;
+}
+
+export interface ReplacementChangelog {
+  generated_at: string;
+  changes: ReplacementChange[];
+}
+
+// Where an image link points, relative to the config it was analyzed for
+export type LinkLocation = 'local' | 'current_config' | 'other_config' | 'external';
+
+export interface ClassifiedLink {
+  link: string;
+  line: number;
+  column: number;
+  context: LinkContext;
+  location: LinkLocation;
+  config_id?: string;
+  object_key?: string;
+}
+
+export interface FileLinkAnalysis {
+  file_path: string;
+  local: number;
+  current_config: number;
+  other_config: number;
+  external: number;
+  links: ClassifiedLink[];
+  error?: string;
+}
+
+export interface ConfigLinkCount {
+  config_id: string;
+  config_name: string;
+  link_count: number;
+}
+
+export interface LinkAnalysisReport {
+  config_id: string;
+  files: FileLinkAnalysis[];
+  other_configs: ConfigLinkCount[];
+}
+
+export interface CdnMigrationSummary {
+  dry_run: boolean;
+  files_scanned: number;
+  files_changed: string[];
+  replacements: LinkReplacement[];
+  skipped_replacements: LinkReplacement[]; // strict mode: path matches no upload
+  replacement_result?: BatchReplacementResult;
+  backups: BackupInfo[];
+  records_updated: number;
+}
+
+// rewrite_url expects the object to already exist in the current bucket under the same key
+export type LinkMigrationMode = 'rewrite_url' | 'reupload';
+
+export interface ObjectMigration {
+  source_config_id: string;
+  object_key: string;
+  new_url?: string;
+  error?: string;
+}
+
+export interface LinkMigrationReport {
+  dry_run: boolean;
+  mode: LinkMigrationMode;
+  analysis: LinkAnalysisReport;
+  objects: ObjectMigration[];
+  replacements: LinkReplacement[];
+  replacement_result?: BatchReplacementResult;
+  backups: BackupInfo[];
 }
 
 export interface ReplacementError {
@@ -174,6 +671,18 @@ export interface BatchReplacementResult {
   total_failed_replacements: number;
   duration: number; // Duration in milliseconds
   timestamp: string; // SystemTime serialized as ISO string
+  skipped_read_only: UnwritableFile[]; // Left untouched with ReadOnlyPolicy.Skip
+  backups?: BackupInfo[]; // Taken by atomic runs
+}
+
+export enum ReadOnlyPolicy {
+  Abort = "abort", // Fail before any file is modified
+  Skip = "skip",
+}
+
+export interface UnwritableFile {
+  file_path: string;
+  error: string;
 }
 
 // ============================================================================
@@ -189,11 +698,41 @@ export interface UploadHistoryRecord {
   source_file?: string; // 对于文章上传模式，记录来源Markdown文件
   file_size: number;
   checksum: string;
+  variants?: ImageVariant[];
+  config_id?: string;
+  bucket?: string;
+  object_key?: string; // key in the bucket, missing on older records
+  original_path?: string; // local file the image was uploaded from
+  success?: boolean; // false only for failed uploads from the old image history
+  error_message?: string;
+  request_id?: string; // provider request ID of the upload, for support tickets
+  original_archived?: boolean; // the unprocessed source is in the originals archive
 }
 
+// A deleted upload history record, kept in the trash until restored or purged
+export type TrashedHistoryRecord = UploadHistoryRecord & {
+  deleted_at: string; // DateTime serialized as ISO string
+};
+
 export enum UploadMode {
   ImageUpload = 'ImageUpload',
-  ArticleUpload = 'ArticleUpload'
+  ArticleUpload = 'ArticleUpload',
+  ClipboardUpload = 'ClipboardUpload',
+  UrlImport = 'UrlImport', // remote image re-hosted in the bucket
+  WatcherUpload = 'WatcherUpload',
+  Reupload = 'Reupload', // e.g. migrate_config_links in Reupload mode
+  Localization = 'Localization'
+}
+
+// Options for upload_images_batch; the window's or the mode's default config
+// is used when neither config nor config_id is given
+export interface BatchUploadOptions {
+  config?: OSSConfig;
+  config_id?: string;
+  batch_size?: number; // concurrent uploads, 5 by default and at most 10
+  source_file?: string;
+  upload_mode?: UploadMode;
+  session_id?: string;
 }
 
 export interface AppState {
@@ -225,8 +764,62 @@ export interface ValidationResult {
 
 export interface ConfigValidation {
   valid: boolean;
+  // Block saving
   errors: string[];
+  // The config is saved anyway
+  warnings?: ConfigWarning[];
   connection_test?: OSSConnectionTest;
+  // Key the path template gives a sample upload
+  sample_key?: string;
+}
+
+export interface ConfigWarning {
+  code: ConfigWarningCode;
+  // English text, for codes the UI has no translation of
+  message: string;
+}
+
+// Stable codes, safe to key translations on
+export type ConfigWarningCode =
+  | "private_without_cdn"
+  | "bucket_underscore"
+  | "object_size_limit"
+  | "cdn_not_serving";
+
+// Result of validate_path_template
+export interface PathTemplateCheck {
+  valid: boolean;
+  // Rendered for a sample file, also when the template is invalid
+  sample_key: string;
+  errors: string[];
+}
+
+// Result of get_provider_capabilities
+export interface ProviderCapabilities {
+  provider: OSSProvider;
+  display_name: string;
+  // False when uploads to this provider aren't implemented yet
+  implemented: boolean;
+  // OSSConfig field names
+  required_fields: string[];
+  optional_fields: string[];
+  cdn_domain_required: boolean;
+  // Empty when objects always get the bucket's default storage class
+  storage_classes: string[];
+  object_acls: ObjectAcl[];
+  max_object_size?: number; // bytes
+  supports_listing: boolean;
+  supports_deletion: boolean;
+  supports_presigning: boolean;
+  endpoint_hint: string;
+  example: ProviderExample;
+}
+
+export interface ProviderExample {
+  endpoint: string;
+  region: string;
+  bucket: string;
+  cdn_domain?: string;
 }
 
 export interface SaveOptions {
@@ -263,6 +856,74 @@ export interface HistoryQuery {
   end_date?: string;
   limit?: number;
   offset?: number;
+  search_term?: string;
+  sort_by?: HistorySortField; // defaults to Timestamp
+  sort_order?: SortOrder; // defaults to Desc
+  include_trashed?: boolean; // also match records in the trash
+}
+
+export interface HistorySourceGroup {
+  source_file?: string; // absent for direct uploads
+  record_count: number;
+  total_bytes: number;
+  newest_timestamp: string;
+  preview: UploadHistoryRecord[]; // newest few records
+}
+
+export type HistorySortField = 'Timestamp' | 'FileSize' | 'ImageName';
+export type SortOrder = 'Asc' | 'Desc';
+
+export type GalleryFormat = 'html' | 'markdown';
+
+export type StatsRange = 'Day' | 'Week' | 'Month' | 'All';
+
+export interface ProviderPerformance {
+  provider: OSSProvider;
+  config_id?: string;
+  uploads: number;
+  failures: number;
+  failure_rate: number; // 0..1
+  total_bytes: number;
+  avg_bytes_per_sec: number;
+  median_bytes_per_sec: number;
+  p95_bytes_per_sec: number;
+}
+
+export interface ThroughputPoint {
+  timestamp: string; // start of the hourly (Day) or daily bucket
+  provider: OSSProvider;
+  config_id?: string;
+  uploads: number;
+  avg_bytes_per_sec: number;
+}
+
+export interface UploadPerformanceStats {
+  range: StatsRange;
+  since?: string;
+  providers: ProviderPerformance[];
+  series: ThroughputPoint[]; // oldest bucket first
+}
+
+export interface EtaRange {
+  min_secs: number;
+  max_secs: number;
+}
+
+export interface BatchEstimateWarning {
+  image_path: string;
+  message: string;
+}
+
+// Result of estimate_batch, also returned by process_markdown_files dry runs
+export interface BatchEstimate {
+  image_count: number;
+  total_bytes: number;
+  sizes_from_preview: number; // sizes taken from a cached processing preview
+  missing_files: string[];
+  warnings: BatchEstimateWarning[]; // over the app or provider size limit, left out
+  bytes_per_sec?: number; // recent average per upload, unset without stats
+  concurrency: number;
+  eta?: EtaRange;
 }
 
 export interface HistoryStatistics {
@@ -272,6 +933,7 @@ export interface HistoryStatistics {
   upload_modes: Record<string, number>;
   oldest_record?: string; // DateTime serialized as ISO string
   newest_record?: string; // DateTime serialized as ISO string
+  trashed_records: number; // records in the trash, counted above only when included
 }
 
 // ============================================================================
@@ -283,6 +945,19 @@ export interface DuplicateCheckResult {
   is_duplicate: boolean;
   existing_record?: UploadHistoryRecord;
   existing_url?: string;
+  matched_config_id?: string;
+  matched_bucket?: string;
+  found_in_other_config: boolean;
+  matches: UploadHistoryRecord[]; // every upload with this checksum, newest first
+}
+
+export type DuplicateScope = 'same_config' | 'any_config';
+
+// Defaults to the active config when neither configId nor bucket is given
+export interface DuplicateLookupOptions {
+  configId?: string;
+  bucket?: string;
+  scope?: DuplicateScope;
 }
 
 export interface DuplicateInfo {
@@ -313,6 +988,91 @@ export enum HealthStatus {
   Critical = "Critical",
 }
 
+export type StartupTaskStatus = 'pending' | 'running' | 'succeeded' | 'failed' | 'timed_out' | 'skipped';
+
+export interface StartupTaskOutcome {
+  id: string;
+  status: StartupTaskStatus;
+  // What the task did, or why it failed
+  message?: string;
+  duration_ms: number;
+}
+
+// A JSON store that failed to parse is moved to `<file>.corrupt-<timestamp>`
+// and replaced by an empty one; get_system_health reports it until recovered
+export type StoreKind =
+  | 'configs'
+  | 'settings'
+  | 'upload_history'
+  | 'history_trash'
+  | 'file_operations'
+  | 'backup_index'
+  | 'pending_uploads';
+
+export interface StoreRecoveryResult {
+  kind: StoreKind;
+  source: string; // the corrupt copy records were read from
+  recovered: number;
+  unreadable: number; // complete entries that didn't match the record shape
+}
+
+// Written to {data_dir}/crashes/ when the backend panics; never sent anywhere
+export interface CrashReport {
+  id: string;
+  timestamp: string; // ISO timestamp
+  operation?: string; // the guarded work that panicked, e.g. process_image
+  message: string;
+  location?: string; // file:line:column of the panic
+  thread?: string;
+  backtrace: string;
+  app_version: string;
+  os: string;
+  arch: string;
+}
+
+export interface CrashReportSummary {
+  id: string;
+  timestamp: string; // ISO timestamp
+  operation?: string;
+  message: string;
+}
+
+// Outcomes of this launch's startup tasks, filled in while they run
+export interface StartupReport {
+  started_at?: string; // ISO timestamp
+  finished_at?: string; // ISO timestamp, unset while tasks are running
+  tasks: StartupTaskOutcome[];
+}
+
+export type MaintenanceJobResult = 'succeeded' | 'failed' | 'skipped';
+
+// One background maintenance job: history_writes, thumbnail_cache,
+// backup_retention, history_age_limit, history_trash, orphaned_progress,
+// originals_archive or local_integrity
+export interface MaintenanceJobStatus {
+  id: string;
+  enabled: boolean;
+  last_run?: string; // ISO timestamp, unset until the job first ran
+  last_result?: MaintenanceJobResult;
+  message?: string; // What the job did, why it failed or was skipped
+  duration_ms: number;
+}
+
+export interface MaintenanceStatus {
+  running: boolean;
+  last_run?: string; // ISO timestamp
+  next_run?: string; // ISO timestamp
+  jobs: MaintenanceJobStatus[];
+}
+
+// Whether the active endpoint is reachable; changes arrive as "connectivity://changed"
+export interface ConnectivityStatus {
+  online: boolean;
+  queued_uploads: number;
+  last_checked?: string; // ISO timestamp
+}
+
+// Also the payload of the health://error event, e.g. when an upload's history record couldn't be saved
 export interface HealthError {
   component: string;
   message: string;
@@ -327,6 +1087,27 @@ export enum ErrorSeverity {
   Critical = "Critical",
 }
 
+export enum StorageMode {
+  Standard = "Standard",
+  Portable = "Portable", // data/ folder next to the executable
+  Custom = "Custom", // set with setDataDirectory
+}
+
+export interface SystemPermissions {
+  valid: boolean;
+  errors: string[];
+  storage_mode: StorageMode;
+  config_dir?: string;
+  data_dir?: string;
+}
+
+export interface DataDirectoryChange {
+  data_dir?: string;
+  copied_files: number;
+  skipped_files: number;
+  restart_required: boolean;
+}
+
 export interface NotificationConfig {
   enabled: boolean;
   show_progress: boolean;
@@ -334,8 +1115,14 @@ export interface NotificationConfig {
   show_errors: boolean;
   auto_dismiss_success: boolean;
   dismiss_timeout: number; // milliseconds
+  // Batches running at least this long notify even while the window is focused
+  long_batch_secs: number;
 }
 
+// Payload of the batch://notification-opened event: id of the batch whose
+// system notification brought the window back into focus
+export type BatchNotificationOpened = string;
+
 export interface ProgressNotification {
   id: string;
   type: NotificationType;
@@ -364,6 +1151,19 @@ export interface UploadTaskManager {
   completed_tasks: UploadTaskInfo[];
   failed_tasks: UploadTaskInfo[];
   cancelled_tasks: UploadTaskInfo[];
+  sessions: UploadSessionTasks[]; // the tasks grouped by upload session
+}
+
+export interface UploadSessionTasks {
+  session_id?: string; // absent for tasks started without a session
+  config_id?: string;
+  task_ids: string[];
+  completed: number;
+  failed: number;
+  bytes_uploaded: number;
+  total_bytes: number;
+  speed?: number; // bytes per second of the whole session, absent once nothing is in flight
+  eta_secs?: number;
 }
 
 export interface UploadTaskInfo {
@@ -387,4 +1187,5 @@ export enum UploadTaskStatus {
   Failed = "Failed",
   Cancelled = "Cancelled",
   Retrying = "Retrying",
+  QueuedOffline = "QueuedOffline",
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+use crate::log_warn;
+use crate::models::{NotificationConfig, UploadResult};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+/// Emitted with the batch id when the window regains focus after a batch
+/// notification, so the frontend can open the results of that batch
+pub const BATCH_NOTIFICATION_OPENED_EVENT: &str = "batch://notification-opened";
+
+/// Outcome of one finished upload batch
+#[derive(Debug, Clone)]
+pub struct BatchSummary {
+    /// Upload session id when the batch ran as one, so a notification opens
+    /// that session's progress area
+    pub batch_id: String,
+    pub uploaded: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+}
+
+impl BatchSummary {
+    pub fn new(uploaded: usize, failed: usize, started: Instant) -> Self {
+        Self {
+            batch_id: uuid::Uuid::new_v4().to_string(),
+            uploaded,
+            failed,
+            elapsed: started.elapsed(),
+        }
+    }
+
+    pub fn from_results(results: &[UploadResult], started: Instant) -> Self {
+        let uploaded = results.iter().filter(|r| r.success).count();
+        // Uploads waiting in the offline queue haven't failed
+        let failed = results
+            .iter()
+            .filter(|r| !r.success && !r.queued_offline)
+            .count();
+        Self::new(uploaded, failed, started)
+    }
+
+    pub fn in_session(mut self, session_id: &str) -> Self {
+        self.batch_id = session_id.to_string();
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchNotice {
+    pub title: String,
+    pub body: String,
+}
+
+/// The system notification for a finished batch, None when the config or the
+/// situation calls for none. A focused window already shows the results, so
+/// only batches longer than `long_batch_secs` notify then.
+pub fn completion_notice(
+    config: &NotificationConfig,
+    summary: &BatchSummary,
+    window_focused: bool,
+) -> Option<BatchNotice> {
+    if !config.enabled || summary.uploaded + summary.failed == 0 {
+        return None;
+    }
+    if window_focused && summary.elapsed < Duration::from_secs(config.long_batch_secs) {
+        return None;
+    }
+
+    let title = if summary.failed > 0 && config.show_errors {
+        "Upload batch finished with failures"
+    } else if config.show_completion {
+        "Upload batch finished"
+    } else {
+        return None;
+    };
+    let short_id: String = summary.batch_id.chars().take(8).collect();
+    Some(BatchNotice {
+        title: title.to_string(),
+        body: format!(
+            "{} uploaded, {} failed (batch {})",
+            summary.uploaded, summary.failed, short_id
+        ),
+    })
+}
+
+/// Shows a system notification when an upload batch finishes while the user
+/// is in another app
+pub struct BatchNotifier {
+    // Set once in setup; without it nothing is shown (tests, headless runs)
+    app_handle: OnceLock<AppHandle>,
+    // Batch of the last notification, reported when the window is focused again
+    notified_batch: Mutex<Option<String>>,
+}
+
+impl BatchNotifier {
+    pub fn new() -> Self {
+        Self {
+            app_handle: OnceLock::new(),
+            notified_batch: Mutex::new(None),
+        }
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    pub fn batch_finished(&self, config: &NotificationConfig, summary: &BatchSummary) {
+        let Some(app_handle) = self.app_handle.get() else {
+            return;
+        };
+        let window_focused = app_handle
+            .get_webview_window("main")
+            .and_then(|window| window.is_focused().ok())
+            .unwrap_or(false);
+        let Some(notice) = completion_notice(config, summary, window_focused) else {
+            return;
+        };
+
+        if let Err(e) = app_handle
+            .notification()
+            .builder()
+            .title(notice.title)
+            .body(notice.body)
+            .show()
+        {
+            log_warn!(
+                operation = "batch_notification",
+                batch_id = %summary.batch_id,
+                error = %e,
+                "Failed to show batch notification"
+            );
+            return;
+        }
+        if let Ok(mut notified) = self.notified_batch.lock() {
+            *notified = Some(summary.batch_id.clone());
+        }
+    }
+
+    /// Desktop notifications have no click callback, clicking one focuses the
+    /// app, so the first focus after a notification opens that batch
+    pub fn window_focused(&self) {
+        let batch_id = self
+            .notified_batch
+            .lock()
+            .ok()
+            .and_then(|mut notified| notified.take());
+        if let (Some(batch_id), Some(app_handle)) = (batch_id, self.app_handle.get()) {
+            let _ = app_handle.emit(BATCH_NOTIFICATION_OPENED_EVENT, batch_id);
+        }
+    }
+}
+
+impl Default for BatchNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref BATCH_NOTIFIER: BatchNotifier = BatchNotifier::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(uploaded: usize, failed: usize, elapsed_secs: u64) -> BatchSummary {
+        BatchSummary {
+            batch_id: "0123456789abcdef".to_string(),
+            uploaded,
+            failed,
+            elapsed: Duration::from_secs(elapsed_secs),
+        }
+    }
+
+    #[test]
+    fn test_notifies_only_when_unfocused_or_long() {
+        let config = NotificationConfig::default();
+        let notice = completion_notice(&config, &summary(42, 0, 10), false).unwrap();
+        assert_eq!(notice.title, "Upload batch finished");
+        assert_eq!(notice.body, "42 uploaded, 0 failed (batch 01234567)");
+
+        assert!(completion_notice(&config, &summary(42, 0, 10), true).is_none());
+        assert!(completion_notice(&config, &summary(42, 0, 1800), true).is_some());
+        assert!(completion_notice(&config, &summary(0, 0, 1800), false).is_none());
+    }
+
+    #[test]
+    fn test_failures_use_their_own_notice() {
+        let mut config = NotificationConfig::default();
+        let notice = completion_notice(&config, &summary(42, 3, 10), false).unwrap();
+        assert_eq!(notice.title, "Upload batch finished with failures");
+        assert_eq!(notice.body, "42 uploaded, 3 failed (batch 01234567)");
+
+        config.show_errors = false;
+        let notice = completion_notice(&config, &summary(42, 3, 10), false).unwrap();
+        assert_eq!(notice.title, "Upload batch finished");
+
+        config.show_completion = false;
+        assert!(completion_notice(&config, &summary(42, 3, 10), false).is_none());
+        config.show_errors = true;
+        assert!(completion_notice(&config, &summary(42, 0, 10), false).is_none());
+
+        config.enabled = false;
+        assert!(completion_notice(&config, &summary(42, 3, 10), false).is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use super::current_app_settings;
+use crate::log_warn;
+use crate::services::OSSService;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A CDN domain is checked again after this long
+const RECHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How an unsigned HEAD request for an object went
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadOutcome {
+    Status(u16),
+    TimedOut,
+    Failed(String),
+}
+
+impl HeadOutcome {
+    fn is_success(&self) -> bool {
+        matches!(self, Self::Status(code) if (200..300).contains(code))
+    }
+}
+
+/// Warning for an object the origin serves but the CDN domain doesn't. None
+/// when the CDN works, or when the origin fails too (private bucket, no
+/// network), which says nothing about the CDN.
+pub fn cdn_mismatch(cdn_url: &str, cdn: &HeadOutcome, origin: &HeadOutcome) -> Option<String> {
+    if !origin.is_success() {
+        return None;
+    }
+    let problem = match cdn {
+        HeadOutcome::Status(404) => "answers 404".to_string(),
+        HeadOutcome::TimedOut => format!("gave no answer within {}s", HEAD_TIMEOUT.as_secs()),
+        HeadOutcome::Status(_) | HeadOutcome::Failed(_) => return None,
+    };
+    let domain = cdn_url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or(cdn_url);
+    Some(format!(
+        "The CDN domain {} {} for an object the bucket itself serves; it is probably not mapped to this bucket, so links on it will be broken",
+        domain, problem
+    ))
+}
+
+async fn head(url: &str) -> HeadOutcome {
+    match reqwest::Client::new()
+        .head(url)
+        .timeout(HEAD_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(response) => HeadOutcome::Status(response.status().as_u16()),
+        Err(e) if e.is_timeout() => HeadOutcome::TimedOut,
+        Err(e) => HeadOutcome::Failed(e.to_string()),
+    }
+}
+
+struct CheckedDomain {
+    checked_at: Instant,
+    warning: Option<String>,
+}
+
+/// Whether the CDN domain of a config serves what its bucket holds, checked
+/// rarely: after the first upload per day, and on deep connection tests.
+/// Outcomes are kept per CDN domain and bucket endpoint, in memory only.
+pub struct CdnChecks {
+    checked: Mutex<HashMap<String, CheckedDomain>>,
+}
+
+impl CdnChecks {
+    pub fn new() -> Self {
+        Self {
+            checked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Warning for the object an upload just put at `key`, only when the
+    /// domain is due for a check. Later uploads reuse the outcome silently.
+    pub async fn check_upload(&self, oss_service: &OSSService, key: &str) -> Option<String> {
+        let domain = cache_key(oss_service)?;
+        if self.cached(&domain).is_some() || !current_app_settings().await.verify_cdn_domain {
+            return None;
+        }
+        if !self.claim(&domain) {
+            return None;
+        }
+        self.check(oss_service, &domain, key).await
+    }
+
+    /// Check the config's CDN domain on an object already in the bucket and
+    /// return the warning, if any. `force` checks again even when a recent
+    /// outcome is known. None when nothing was checked: no CDN domain, the
+    /// check turned off in settings, or an empty bucket.
+    pub async fn check_config(
+        &self,
+        oss_service: &OSSService,
+        force: bool,
+    ) -> Option<Option<String>> {
+        let domain = cache_key(oss_service)?;
+        if !current_app_settings().await.verify_cdn_domain {
+            return None;
+        }
+        if !force {
+            if let Some(warning) = self.cached(&domain) {
+                return Some(warning);
+            }
+        }
+        let object = oss_service.sample_object().await.ok().flatten()?;
+        self.claim(&domain);
+        Some(self.check(oss_service, &domain, &object.key).await)
+    }
+
+    async fn check(&self, oss_service: &OSSService, domain: &str, key: &str) -> Option<String> {
+        let cdn_url = oss_service.object_url(key);
+        let origin_url = oss_service.origin_url(key)?;
+        let (cdn, origin) = tokio::join!(head(&cdn_url), head(&origin_url));
+        let warning = cdn_mismatch(&cdn_url, &cdn, &origin);
+        if warning.is_some() {
+            log_warn!(
+                operation = "cdn_check",
+                cdn_url = %cdn_url,
+                cdn = ?cdn,
+                origin = ?origin,
+                "CDN domain doesn't serve an object the bucket does"
+            );
+        }
+        self.record(domain, warning.clone());
+        warning
+    }
+
+    // Outcome of a check within the recheck interval, None when one is due
+    fn cached(&self, domain: &str) -> Option<Option<String>> {
+        let checked = self.checked.lock().unwrap_or_else(|e| e.into_inner());
+        checked
+            .get(domain)
+            .filter(|entry| entry.checked_at.elapsed() < RECHECK_INTERVAL)
+            .map(|entry| entry.warning.clone())
+    }
+
+    // Take the check of a due domain, so concurrent uploads don't all run it
+    fn claim(&self, domain: &str) -> bool {
+        let mut checked = self.checked.lock().unwrap_or_else(|e| e.into_inner());
+        if checked
+            .get(domain)
+            .is_some_and(|entry| entry.checked_at.elapsed() < RECHECK_INTERVAL)
+        {
+            return false;
+        }
+        checked.insert(
+            domain.to_string(),
+            CheckedDomain {
+                checked_at: Instant::now(),
+                warning: None,
+            },
+        );
+        true
+    }
+
+    fn record(&self, domain: &str, warning: Option<String>) {
+        let mut checked = self.checked.lock().unwrap_or_else(|e| e.into_inner());
+        checked.insert(
+            domain.to_string(),
+            CheckedDomain {
+                checked_at: Instant::now(),
+                warning,
+            },
+        );
+    }
+}
+
+impl Default for CdnChecks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// CDN and origin base URLs, None when the config has no CDN domain in front
+// of its bucket or the provider has no origin URL to compare with
+fn cache_key(oss_service: &OSSService) -> Option<String> {
+    let cdn = oss_service.object_url("");
+    let origin = oss_service.origin_url("")?;
+    (cdn != origin).then(|| format!("{} -> {}", cdn, origin))
+}
+
+lazy_static::lazy_static! {
+    pub static ref CDN_CHECKS: CdnChecks = CdnChecks::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CDN_URL: &str = "https://img.example.com/posts/a.png";
+
+    #[test]
+    fn test_warns_only_when_the_origin_serves_what_the_cdn_does_not() {
+        let ok = HeadOutcome::Status(200);
+        let warning = cdn_mismatch(CDN_URL, &HeadOutcome::Status(404), &ok).unwrap();
+        assert!(warning.contains("img.example.com answers 404"));
+        assert!(cdn_mismatch(CDN_URL, &HeadOutcome::TimedOut, &ok)
+            .unwrap()
+            .contains("no answer"));
+
+        assert!(cdn_mismatch(CDN_URL, &ok, &ok).is_none());
+        // A private bucket refuses the unsigned origin request: nothing to compare
+        assert!(cdn_mismatch(
+            CDN_URL,
+            &HeadOutcome::Status(404),
+            &HeadOutcome::Status(403)
+        )
+        .is_none());
+        assert!(cdn_mismatch(CDN_URL, &HeadOutcome::TimedOut, &HeadOutcome::TimedOut).is_none());
+    }
+
+    #[test]
+    fn test_a_domain_is_claimed_once_per_interval() {
+        let checks = CdnChecks::new();
+        assert!(checks.cached("cdn -> origin").is_none());
+        assert!(checks.claim("cdn -> origin"));
+        assert!(!checks.claim("cdn -> origin"));
+
+        checks.record("cdn -> origin", Some("broken".to_string()));
+        assert_eq!(
+            checks.cached("cdn -> origin"),
+            Some(Some("broken".to_string()))
+        );
+        assert!(checks.claim("other -> origin"));
+    }
+}
@@ -0,0 +1,185 @@
+use super::pending::PENDING_UPLOADS;
+use crate::models::ConnectivityStatus;
+use crate::services::oss_service::endpoint_host;
+use crate::services::ConfigService;
+use crate::utils::AppError;
+use crate::{log_info, log_warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+/// Emitted with `true`/`false` whenever the connection comes or goes
+pub const CONNECTIVITY_CHANGED_EVENT: &str = "connectivity://changed";
+/// Emitted with the upload results once the offline queue has been flushed
+pub const OFFLINE_QUEUE_FLUSHED_EVENT: &str = "upload://offline-queue-flushed";
+
+/// While offline the endpoint is probed often, so queued uploads start soon
+/// after the connection returns
+const OFFLINE_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+const ONLINE_PROBE_INTERVAL: Duration = Duration::from_secs(120);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// True for failures that mean there's no connection at all, as opposed to
+/// the provider rejecting the request
+pub fn is_offline_error(error: &AppError) -> bool {
+    matches!(error, AppError::Network(e) if e.is_connect() || e.is_timeout())
+}
+
+/// Whether the active endpoint was reachable on the last probe or upload.
+/// Uploads started while offline go into the offline queue instead of failing.
+pub struct ConnectivityMonitor {
+    online: AtomicBool,
+    last_checked: Mutex<Option<chrono::DateTime<chrono::Utc>>>,
+    // Wakes the watcher when an upload finds the connection gone, so probing
+    // switches to the offline interval right away
+    went_offline: Notify,
+    app_handle: OnceLock<AppHandle>,
+}
+
+impl ConnectivityMonitor {
+    pub fn new() -> Self {
+        Self {
+            online: AtomicBool::new(true),
+            last_checked: Mutex::new(None),
+            went_offline: Notify::new(),
+            app_handle: OnceLock::new(),
+        }
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    /// Record the outcome of a probe or upload, true when it changed the state
+    pub fn set_online(&self, online: bool) -> bool {
+        *self.last_checked.lock().unwrap_or_else(|e| e.into_inner()) = Some(chrono::Utc::now());
+        let changed = self.online.swap(online, Ordering::SeqCst) != online;
+        if changed {
+            log_info!(
+                operation = "connectivity",
+                online = online,
+                queued_uploads = PENDING_UPLOADS.offline().len(),
+                "Connectivity changed"
+            );
+            self.emit(CONNECTIVITY_CHANGED_EVENT, online);
+            if !online {
+                self.went_offline.notify_one();
+            }
+        }
+        changed
+    }
+
+    pub fn status(&self) -> ConnectivityStatus {
+        ConnectivityStatus {
+            online: self.is_online(),
+            queued_uploads: PENDING_UPLOADS.offline().len(),
+            last_checked: *self.last_checked.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    pub fn emit<T: serde::Serialize + Clone>(&self, event: &str, payload: T) {
+        if let Some(app_handle) = self.app_handle.get() {
+            if let Err(e) = app_handle.emit(event, payload) {
+                log_warn!(
+                    operation = "connectivity",
+                    event = event,
+                    error = %e,
+                    "Failed to emit event"
+                );
+            }
+        }
+    }
+}
+
+impl Default for ConnectivityMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// An unsigned HEAD to the active config's endpoint: any HTTP answer, a 403
+// included, means the network is there. Without a config there's nothing to
+// reach, which counts as online.
+async fn probe_active_endpoint() -> bool {
+    let active = match ConfigService::new() {
+        Ok(config_service) => config_service.get_active_config().await.ok().flatten(),
+        Err(_) => None,
+    };
+    let Some(active) = active else {
+        return true;
+    };
+    reqwest::Client::new()
+        .head(format!("https://{}/", endpoint_host(&active.config)))
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .is_ok()
+}
+
+/// Probe the endpoint for as long as the app runs and flush the offline queue
+/// whenever the connection is there and uploads are waiting. The first pass
+/// flushes what a previous run left queued.
+pub async fn watch_connectivity() {
+    loop {
+        let online = probe_active_endpoint().await;
+        CONNECTIVITY.set_online(online);
+        if online && !PENDING_UPLOADS.offline().is_empty() {
+            super::flush_offline_queue().await;
+        }
+
+        if CONNECTIVITY.is_online() {
+            tokio::select! {
+                _ = tokio::time::sleep(ONLINE_PROBE_INTERVAL) => {}
+                _ = CONNECTIVITY.went_offline.notified() => {}
+            }
+        } else {
+            tokio::time::sleep(OFFLINE_PROBE_INTERVAL).await;
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref CONNECTIVITY: ConnectivityMonitor = ConnectivityMonitor::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_connection_failures_count_as_offline() {
+        assert!(!is_offline_error(&AppError::Configuration(
+            "Access denied".to_string()
+        )));
+        assert!(!is_offline_error(&AppError::Validation("bad".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_host_is_offline_error() {
+        // Nothing listens on port 9 of the loopback address
+        let error = reqwest::Client::new()
+            .head("http://127.0.0.1:9/")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(is_offline_error(&AppError::Network(error)));
+    }
+
+    #[test]
+    fn test_set_online_reports_changes() {
+        let monitor = ConnectivityMonitor::new();
+        assert!(monitor.is_online());
+        assert!(!monitor.set_online(true));
+        assert!(monitor.set_online(false));
+        assert!(!monitor.is_online());
+        assert!(!monitor.set_online(false));
+        assert!(monitor.set_online(true));
+        assert!(monitor.status().last_checked.is_some());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::models::{ErrorSeverity, HealthError, UploadHistoryRecord};
+use crate::services::HistoryService;
+use crate::utils::AppError;
+use crate::{log_info, log_warn};
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter};
+
+/// Emitted with a HealthError when an upload went through but its history
+/// record couldn't be written
+pub const HEALTH_ERROR_EVENT: &str = "health://error";
+
+// Records kept in memory while the history store is unwritable; past this the
+// oldest are dropped
+const MAX_DEFERRED_RECORDS: usize = 1000;
+
+/// History records of finished uploads the store refused (disk full, store
+/// unreadable). The maintenance scheduler writes them once the store takes
+/// writes again. Kept in memory only: the history store is the place they
+/// couldn't be saved to.
+pub struct HistoryWrites {
+    deferred: Mutex<Vec<UploadHistoryRecord>>,
+    // Set once in setup; without it events are dropped (tests, headless runs)
+    app_handle: OnceLock<AppHandle>,
+}
+
+impl HistoryWrites {
+    pub fn new() -> Self {
+        Self {
+            deferred: Mutex::new(Vec::new()),
+            app_handle: OnceLock::new(),
+        }
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    pub fn deferred_count(&self) -> usize {
+        self.deferred
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Keep `record` for a later write and tell the UI. Returns the warning
+    /// to attach to the upload's result.
+    pub fn defer(&self, record: UploadHistoryRecord, error: &AppError) -> String {
+        let deferred = {
+            let mut deferred = self.deferred.lock().unwrap_or_else(|e| e.into_inner());
+            deferred.push(record);
+            if deferred.len() > MAX_DEFERRED_RECORDS {
+                let excess = deferred.len() - MAX_DEFERRED_RECORDS;
+                deferred.drain(..excess);
+            }
+            deferred.len()
+        };
+        log_warn!(
+            operation = "history_write",
+            error = %error,
+            deferred = deferred,
+            "Upload history record could not be saved, retrying later"
+        );
+
+        let warning = format!(
+            "Uploaded, but the upload history couldn't be saved ({}); duplicate detection won't know this image until it is",
+            error
+        );
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(
+                HEALTH_ERROR_EVENT,
+                HealthError {
+                    component: "History".to_string(),
+                    message: warning.clone(),
+                    severity: ErrorSeverity::Medium,
+                    timestamp: chrono::Utc::now(),
+                },
+            );
+        }
+        warning
+    }
+
+    /// Write the deferred records to `history`. On failure they stay queued
+    /// for the next attempt.
+    pub async fn flush(&self, history: &HistoryService) -> Result<usize, AppError> {
+        let records = std::mem::take(&mut *self.deferred.lock().unwrap_or_else(|e| e.into_inner()));
+        if records.is_empty() {
+            return Ok(0);
+        }
+        let count = records.len();
+        if let Err(e) = history.add_batch_upload_records(records.clone()).await {
+            // Put them back ahead of anything deferred meanwhile
+            let mut deferred = self.deferred.lock().unwrap_or_else(|e| e.into_inner());
+            let newer = std::mem::replace(&mut *deferred, records);
+            deferred.extend(newer);
+            return Err(e);
+        }
+        log_info!(
+            operation = "history_write",
+            written = count,
+            "Deferred upload history records saved"
+        );
+        Ok(count)
+    }
+}
+
+impl Default for HistoryWrites {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Save the history record of a finished upload. A failed write doesn't fail
+/// the upload: the record is deferred and the warning for its result returned.
+pub async fn record_upload(record: UploadHistoryRecord) -> Option<String> {
+    let written = match HistoryService::new() {
+        Ok(history) => history.add_upload_record(record.clone()).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    written.err().map(|e| HISTORY_WRITES.defer(record, &e))
+}
+
+lazy_static::lazy_static! {
+    pub static ref HISTORY_WRITES: HistoryWrites = HistoryWrites::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UploadMode;
+    use tempfile::TempDir;
+
+    fn record(id: &str) -> UploadHistoryRecord {
+        UploadHistoryRecord {
+            id: id.to_string(),
+            timestamp: chrono::Utc::now(),
+            image_name: format!("{}.png", id),
+            uploaded_url: format!("https://cdn.example.com/{}.png", id),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 1024,
+            checksum: String::new(),
+            variants: vec![],
+            config_id: None,
+            bucket: None,
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deferred_records_are_written_once_the_store_works() {
+        let writes = HistoryWrites::new();
+        let error = AppError::FileSystem("No space left on device".to_string());
+        let warning = writes.defer(record("a"), &error);
+        assert!(warning.contains("No space left on device"));
+        writes.defer(record("b"), &error);
+        assert_eq!(writes.deferred_count(), 2);
+
+        // A directory where the history file should be refuses every write
+        let temp_dir = TempDir::new().unwrap();
+        let history_file = temp_dir.path().join("upload_history.json");
+        std::fs::create_dir(&history_file).unwrap();
+        let history = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        assert!(writes.flush(&history).await.is_err());
+        assert_eq!(writes.deferred_count(), 2);
+
+        std::fs::remove_dir(&history_file).unwrap();
+        assert_eq!(writes.flush(&history).await.unwrap(), 2);
+        assert_eq!(writes.deferred_count(), 0);
+        let ids: Vec<String> = history
+            .get_upload_records(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&"a".to_string()) && ids.contains(&"b".to_string()));
+        assert_eq!(writes.flush(&history).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,92 @@
+//! Size limits for command arguments.
+//!
+//! Every validator in `commands` takes its caps from here so the frontend can
+//! mirror them in one place. The checks run after serde has built the
+//! arguments, they don't make parsing a huge payload cheaper, but they keep a
+//! runaway caller from being processed, written to history or logged.
+
+use crate::models::{LinkReplacement, UploadHistoryRecord};
+use crate::utils::AppError;
+
+/// Local file paths (markdown files, images, report and backup targets)
+pub const MAX_PATH_LEN: usize = 4096;
+/// Link texts and URLs, data URIs are longer but never uploaded as links
+pub const MAX_LINK_LEN: usize = 8192;
+/// Image names, config names, checksums and similar short strings
+pub const MAX_NAME_LEN: usize = 1024;
+/// Error messages stored with history records
+pub const MAX_MESSAGE_LEN: usize = 16 * 1024;
+/// S3/OSS object keys are limited to 1024 bytes
+pub const MAX_OBJECT_KEY_LEN: usize = 1023;
+pub const MAX_PREFIX_LEN: usize = 1000;
+
+/// Markdown files per scan_markdown_files_streamed call; results are emitted
+/// instead of held, so this only bounds the path list
+pub const MAX_STREAMED_SCAN_FILES: usize = 100_000;
+/// Images per preview_image_processing call
+pub const MAX_PREVIEW_IMAGES: usize = 5000;
+/// Keys per delete_oss_objects call, the service splits them further into
+/// provider-sized batches
+pub const MAX_DELETE_KEYS: usize = 5000;
+pub const MAX_LINK_REQUESTS: usize = 1000;
+pub const MAX_REPLACEMENTS: usize = 1000;
+pub const MAX_SINGLE_FILE_REPLACEMENTS: usize = 100;
+pub const MAX_DUPLICATE_CHECKS: usize = 100;
+/// Records per add_batch_upload_history_records call, larger imports are
+/// sent in pages of this size
+pub const MAX_HISTORY_BATCH: usize = 500;
+pub const MAX_VARIANTS_PER_RECORD: usize = 32;
+
+/// `value` is at most `max` bytes long
+pub fn check_len(field: &str, value: &str, max: usize) -> Result<(), AppError> {
+    if value.len() > max {
+        return Err(AppError::Validation(format!(
+            "{} too long ({} bytes, max {})",
+            field,
+            value.len(),
+            max
+        )));
+    }
+    Ok(())
+}
+
+/// A list argument holds at most `max` items
+pub fn check_count(what: &str, count: usize, max: usize) -> Result<(), AppError> {
+    if count > max {
+        return Err(AppError::Validation(format!(
+            "Too many {} (max {})",
+            what, max
+        )));
+    }
+    Ok(())
+}
+
+pub fn check_replacement(replacement: &LinkReplacement) -> Result<(), AppError> {
+    check_len("File path", &replacement.file_path, MAX_PATH_LEN)?;
+    check_len("Old link", &replacement.old_link, MAX_LINK_LEN)?;
+    check_len("New link", &replacement.new_link, MAX_LINK_LEN)
+}
+
+pub fn check_history_record(record: &UploadHistoryRecord) -> Result<(), AppError> {
+    check_len("Record id", &record.id, MAX_NAME_LEN)?;
+    check_len("Image name", &record.image_name, MAX_NAME_LEN)?;
+    check_len("Uploaded URL", &record.uploaded_url, MAX_LINK_LEN)?;
+    check_len("Checksum", &record.checksum, MAX_NAME_LEN)?;
+    for path in [&record.source_file, &record.original_path]
+        .into_iter()
+        .flatten()
+    {
+        check_len("File path", path, MAX_PATH_LEN)?;
+    }
+    for name in [&record.config_id, &record.bucket].into_iter().flatten() {
+        check_len("Config field", name, MAX_NAME_LEN)?;
+    }
+    if let Some(message) = &record.error_message {
+        check_len("Error message", message, MAX_MESSAGE_LEN)?;
+    }
+    check_count("variants", record.variants.len(), MAX_VARIANTS_PER_RECORD)?;
+    for variant in &record.variants {
+        check_len("Variant URL", &variant.url, MAX_LINK_LEN)?;
+    }
+    Ok(())
+}
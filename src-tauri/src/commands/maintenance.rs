@@ -0,0 +1,455 @@
+use super::history_writes::HISTORY_WRITES;
+use super::pending::PENDING_UPLOADS;
+use super::progress::PROGRESS_NOTIFIER;
+use crate::models::{
+    AppSettings, MaintenanceJobResult, MaintenanceJobStatus, MaintenanceSettings, MaintenanceStatus,
+};
+use crate::services::backup_service::BackupRetention;
+use crate::services::original_archive::{ArchiveRetention, OriginalArchive};
+use crate::services::{BackupService, HistoryService, ImageService};
+use crate::utils::Result;
+use crate::{log_info, log_warn};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The first run waits this long after launch, so it doesn't compete with
+/// the startup tasks and whatever the user starts right away
+const STARTUP_DEBOUNCE: Duration = Duration::from_secs(2 * 60);
+/// Jobs skipped because uploads were busy are retried this much later instead
+/// of a whole interval
+const BUSY_RETRY_DELAY: Duration = Duration::from_secs(15 * 60);
+const JOB_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// An in-flight progress entry not updated for this long belongs to an upload
+/// that went away without reporting a final status
+pub const ORPHANED_PROGRESS_AGE: Duration = Duration::from_secs(30 * 60);
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<String>> + Send>>;
+
+/// One maintenance job. Like startup tasks they are idempotent and the Ok value
+/// is the line shown in the status ("removed 12 cached thumbnails").
+pub struct MaintenanceJob {
+    pub id: &'static str,
+    pub enabled: bool,
+    run: Box<dyn FnOnce() -> JobFuture + Send>,
+}
+
+impl MaintenanceJob {
+    pub fn new<F, Fut>(id: &'static str, enabled: bool, run: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        Self {
+            id,
+            enabled,
+            run: Box::new(move || Box::pin(run())),
+        }
+    }
+}
+
+/// The jobs of one run, in order, enabled per `settings.maintenance`.
+/// Deferred history records go first so the history jobs see them.
+pub fn maintenance_jobs(settings: &AppSettings) -> Vec<MaintenanceJob> {
+    let maintenance = settings.maintenance.clone();
+    let retention = BackupRetention::from_settings(settings);
+    let archive_retention = ArchiveRetention::from_settings(settings);
+    vec![
+        MaintenanceJob::new("history_writes", true, || async {
+            let written = HISTORY_WRITES.flush(&HistoryService::new()?).await?;
+            Ok(format!("saved {} deferred history records", written))
+        }),
+        MaintenanceJob::new(
+            "thumbnail_cache",
+            maintenance.thumbnail_cache,
+            move || async move {
+                let removed = ImageService::with_cache()?
+                    .cleanup_cache_by_size(maintenance.thumbnail_cache_max_mb)
+                    .await?;
+                Ok(format!("removed {} cached thumbnails", removed))
+            },
+        ),
+        MaintenanceJob::new(
+            "backup_retention",
+            maintenance.backup_retention,
+            move || async move {
+                let result = BackupService::new()?.cleanup(&retention).await?;
+                Ok(format!(
+                    "removed {} backups ({} bytes)",
+                    result.removed_files, result.removed_bytes
+                ))
+            },
+        ),
+        MaintenanceJob::new(
+            "history_age_limit",
+            maintenance.history_age_limit,
+            move || async move {
+                let result = super::cleanup_history(
+                    &HistoryService::new()?,
+                    None,
+                    Some(maintenance.history_max_age_days),
+                    false,
+                )
+                .await?;
+                Ok(format!(
+                    "removed {} history records older than {} days",
+                    result.removed_records, maintenance.history_max_age_days
+                ))
+            },
+        ),
+        MaintenanceJob::new(
+            "history_trash",
+            maintenance.history_trash,
+            move || async move {
+                let result = super::purge_history_trash(
+                    &HistoryService::new()?,
+                    Some(maintenance.trash_retention_days),
+                    false,
+                )
+                .await?;
+                Ok(format!(
+                    "purged {} history records deleted over {} days ago",
+                    result.removed_records, maintenance.trash_retention_days
+                ))
+            },
+        ),
+        MaintenanceJob::new(
+            "orphaned_progress",
+            maintenance.orphaned_progress,
+            || async {
+                let removed = PROGRESS_NOTIFIER.remove_stalled(ORPHANED_PROGRESS_AGE);
+                Ok(format!("removed {} stalled progress entries", removed))
+            },
+        ),
+        MaintenanceJob::new(
+            "originals_archive",
+            maintenance.originals_archive,
+            move || async move {
+                let history = HistoryService::new()?;
+                // Trashed records can be restored, they keep their originals
+                let mut live: HashSet<String> = history
+                    .get_upload_records(None)
+                    .await?
+                    .into_iter()
+                    .map(|record| record.id)
+                    .collect();
+                live.extend(
+                    history
+                        .get_trashed_records()
+                        .await?
+                        .into_iter()
+                        .map(|trashed| trashed.record.id),
+                );
+                let result = OriginalArchive::new()?
+                    .cleanup(&archive_retention, Some(&live))
+                    .await?;
+
+                // Records whose original went no longer claim to have one
+                let released: HashSet<&str> =
+                    result.released_records.iter().map(String::as_str).collect();
+                history
+                    .update_upload_records(
+                        |record| {
+                            let clear =
+                                record.original_archived && released.contains(record.id.as_str());
+                            if clear {
+                                record.original_archived = false;
+                            }
+                            clear
+                        },
+                        false,
+                    )
+                    .await?;
+                Ok(format!(
+                    "removed {} archived originals ({} bytes)",
+                    result.removed_files, result.removed_bytes
+                ))
+            },
+        ),
+        MaintenanceJob::new("local_integrity", maintenance.local_integrity, || async {
+            // One file at a time, this pass isn't waited for
+            let report =
+                super::check_local_integrity(&HistoryService::new()?, None, None, 1).await?;
+            Ok(format!(
+                "{} of {} uploaded images modified locally since upload, {} missing",
+                report.modified.len(),
+                report.records_checked,
+                report.missing.len()
+            ))
+        }),
+    ]
+}
+
+/// Why jobs can't run right now. Every job cleans something uploads write to
+/// (thumbnails and history of new records, backups of rewritten articles,
+/// progress entries), so none runs while an upload is in flight.
+pub fn uploads_busy() -> Option<String> {
+    let in_flight = PENDING_UPLOADS
+        .in_flight()
+        .max(PROGRESS_NOTIFIER.active_count(ORPHANED_PROGRESS_AGE));
+    (in_flight > 0).then(|| format!("{} uploads in progress", in_flight))
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    running: bool,
+    last_run: Option<chrono::DateTime<chrono::Utc>>,
+    next_run: Option<chrono::DateTime<chrono::Utc>>,
+    // Last outcome per job id, kept across runs
+    jobs: HashMap<&'static str, MaintenanceJobStatus>,
+}
+
+/// Runs the maintenance jobs and keeps their last outcomes for
+/// `get_maintenance_status`. A failing job is recorded and the next one still
+/// runs.
+pub struct MaintenanceScheduler {
+    state: Mutex<SchedulerState>,
+}
+
+impl MaintenanceScheduler {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(SchedulerState::default()),
+        }
+    }
+
+    /// Every job of `settings`, with its enabled flag from the current settings
+    pub fn status(&self, settings: &AppSettings) -> MaintenanceStatus {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        MaintenanceStatus {
+            running: state.running,
+            last_run: state.last_run,
+            next_run: state.next_run,
+            jobs: maintenance_jobs(settings)
+                .into_iter()
+                .map(|job| {
+                    let mut status =
+                        state
+                            .jobs
+                            .get(job.id)
+                            .cloned()
+                            .unwrap_or_else(|| MaintenanceJobStatus {
+                                id: job.id.to_string(),
+                                enabled: job.enabled,
+                                last_run: None,
+                                last_result: None,
+                                message: None,
+                                duration_ms: 0,
+                            });
+                    status.enabled = job.enabled;
+                    status
+                })
+                .collect(),
+        }
+    }
+
+    /// Run the enabled `jobs` in order. `busy` is asked before each job, a job
+    /// is skipped while it returns a reason. True when a job was skipped.
+    pub async fn run(&self, jobs: Vec<MaintenanceJob>, busy: impl Fn() -> Option<String>) -> bool {
+        self.update(|state| state.running = true);
+        let mut skipped_any = false;
+
+        for job in jobs.into_iter().filter(|job| job.enabled) {
+            let started_at = chrono::Utc::now();
+            let started = Instant::now();
+            let (result, message) = if let Some(reason) = busy() {
+                skipped_any = true;
+                (MaintenanceJobResult::Skipped, reason)
+            } else {
+                match tokio::time::timeout(JOB_TIMEOUT, (job.run)()).await {
+                    Ok(Ok(summary)) => (MaintenanceJobResult::Succeeded, summary),
+                    Ok(Err(e)) => (MaintenanceJobResult::Failed, e.to_string()),
+                    Err(_) => (
+                        MaintenanceJobResult::Failed,
+                        format!("did not finish within {}s", JOB_TIMEOUT.as_secs()),
+                    ),
+                }
+            };
+            let duration_ms = started.elapsed().as_millis() as u64;
+
+            if result == MaintenanceJobResult::Failed {
+                log_warn!(
+                    operation = "maintenance",
+                    job = job.id,
+                    duration_ms = duration_ms,
+                    error = %message,
+                    "Maintenance job failed"
+                );
+            } else {
+                log_info!(
+                    operation = "maintenance",
+                    job = job.id,
+                    result = ?result,
+                    duration_ms = duration_ms,
+                    summary = %message,
+                    "Maintenance job finished"
+                );
+            }
+            self.update(|state| {
+                state.jobs.insert(
+                    job.id,
+                    MaintenanceJobStatus {
+                        id: job.id.to_string(),
+                        enabled: true,
+                        last_run: Some(started_at),
+                        last_result: Some(result),
+                        message: Some(message),
+                        duration_ms,
+                    },
+                );
+            });
+        }
+
+        self.update(|state| {
+            state.running = false;
+            state.last_run = Some(chrono::Utc::now());
+        });
+        skipped_any
+    }
+
+    fn set_next_run(&self, delay: Duration) {
+        let next_run = chrono::Duration::from_std(delay)
+            .ok()
+            .map(|delay| chrono::Utc::now() + delay);
+        self.update(|state| state.next_run = next_run);
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut SchedulerState)) {
+        apply(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+impl Default for MaintenanceScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run_interval(maintenance: &MaintenanceSettings) -> Duration {
+    Duration::from_secs(u64::from(maintenance.interval_hours.max(1)) * 60 * 60)
+}
+
+/// Entry point for the setup hook: the first run after the startup debounce,
+/// then one per configured interval for as long as the app runs. Settings are
+/// read again for every run, so toggled jobs apply from the next one.
+pub async fn run_maintenance() {
+    MAINTENANCE.set_next_run(STARTUP_DEBOUNCE);
+    tokio::time::sleep(STARTUP_DEBOUNCE).await;
+    loop {
+        let settings = super::current_app_settings().await;
+        let skipped = MAINTENANCE
+            .run(maintenance_jobs(&settings), uploads_busy)
+            .await;
+        let delay = if skipped {
+            BUSY_RETRY_DELAY.min(run_interval(&settings.maintenance))
+        } else {
+            run_interval(&settings.maintenance)
+        };
+        MAINTENANCE.set_next_run(delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref MAINTENANCE: MaintenanceScheduler = MaintenanceScheduler::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AppError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn counting_job(id: &'static str, enabled: bool, runs: &Arc<AtomicUsize>) -> MaintenanceJob {
+        let runs = runs.clone();
+        MaintenanceJob::new(id, enabled, move || async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("{} done", id))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_enabled_jobs_run_and_outcomes_are_kept() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let jobs = vec![
+            counting_job("first", true, &runs),
+            MaintenanceJob::new("broken", true, || async {
+                Err(AppError::FileSystem("disk full".to_string()))
+            }),
+            counting_job("disabled", false, &runs),
+            counting_job("last", true, &runs),
+        ];
+
+        let scheduler = MaintenanceScheduler::new();
+        assert!(!scheduler.run(jobs, || None).await);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        let state = scheduler.state.lock().unwrap();
+        assert!(!state.running);
+        assert!(state.last_run.is_some());
+        assert_eq!(
+            state.jobs["first"].last_result,
+            Some(MaintenanceJobResult::Succeeded)
+        );
+        assert_eq!(state.jobs["first"].message.as_deref(), Some("first done"));
+        assert_eq!(
+            state.jobs["broken"].last_result,
+            Some(MaintenanceJobResult::Failed)
+        );
+        assert!(!state.jobs.contains_key("disabled"));
+        assert!(state.jobs["last"].last_run.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_skipped_while_uploads_are_busy() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let scheduler = MaintenanceScheduler::new();
+        let skipped = scheduler
+            .run(vec![counting_job("cache", true, &runs)], || {
+                Some("2 uploads in progress".to_string())
+            })
+            .await;
+
+        assert!(skipped);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        let state = scheduler.state.lock().unwrap();
+        assert_eq!(
+            state.jobs["cache"].last_result,
+            Some(MaintenanceJobResult::Skipped)
+        );
+        assert_eq!(
+            state.jobs["cache"].message.as_deref(),
+            Some("2 uploads in progress")
+        );
+    }
+
+    #[test]
+    fn test_status_lists_every_job_with_current_toggles() {
+        let mut settings = AppSettings::default();
+        settings.maintenance.thumbnail_cache = false;
+        let status = MaintenanceScheduler::new().status(&settings);
+
+        let jobs: Vec<_> = status
+            .jobs
+            .iter()
+            .map(|job| (job.id.as_str(), job.enabled))
+            .collect();
+        assert_eq!(
+            jobs,
+            vec![
+                ("history_writes", true),
+                ("thumbnail_cache", false),
+                ("backup_retention", true),
+                ("history_age_limit", false),
+                ("history_trash", true),
+                ("orphaned_progress", true),
+                ("originals_archive", true),
+                ("local_integrity", false),
+            ]
+        );
+        assert!(status.jobs.iter().all(|job| job.last_run.is_none()));
+    }
+}
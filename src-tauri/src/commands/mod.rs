@@ -1,24 +1,106 @@
 use crate::models::{
-    BatchReplacementResult, ConfigCollection, ConfigItem, ConfigValidation, ErrorSeverity,
-    FileOperation, HealthError, HealthStatus, ImageInfo, LinkReplacement, NotificationConfig,
-    OSSConfig, OSSConnectionTest, ObjectInfo, PaginatedResult, ProgressNotification,
-    ReplacementResult, SaveOptions, ScanResult, SystemHealth, UploadHistoryRecord, UploadMode,
-    UploadProgress, UploadResult, UploadTaskInfo, UploadTaskManager, UploadTaskStatus,
-    ValidationResult,
+    AppDataImportResult, AppDataInclude, AppDataManifest, AppDataPreview, AppSettings,
+    AppSettingsPatch, ArchivedOriginal, ArticleImageUpload, BackupCleanupResult, BackupInfo,
+    BackupStorageUsage, BatchEstimate, BatchEstimateWarning, BatchReplacementResult,
+    BatchUploadItem, BatchUploadOptions, CacheStats, CdnMigrationSummary, ChecksumAlgorithm,
+    ConfigCollection, ConfigHealthStatus, ConfigItem, ConfigTestProgress, ConfigTestResult,
+    ConfigValidation, ConfigWarning, ConfigWarningCode, ConnectionDiagnosis, ConnectionTestStep,
+    ConnectivityStatus, CorruptStore, CrashReport, CrashReportSummary, DataDirectoryChange,
+    DeferredLinks, DeleteObjectResult, DuplicateScanProgress, DuplicateScope, ErrorSeverity,
+    FileOperation, FileOperationType, GalleryFormat, GeneratedThumbnail, HealthError, HealthStatus,
+    HistoryCleanupResult, ImageConversionResult, ImageInfo, ImageProcessingOptions,
+    ImageProcessingPreview, ImageRejectReason, ImageUsage, ImageValidation, ImageVariant,
+    LinkAnalysisReport, LinkFormat, LinkLocation, LinkMigrationMode, LinkMigrationReport,
+    LinkReplacement, LocalDuplicateReport, LocalIntegrityReport, MaintenanceStatus, MissingImage,
+    NotificationConfig, OSSConfig, OSSConnectionTest, OSSProvider, ObjectAcl, ObjectInfo,
+    ObjectMetadata, ObjectMigration, OrphanScanReport, PaginatedResult, PathTemplateCheck,
+    PendingUpload, PipelineUpload, ProcessMarkdownOptions, ProcessMarkdownReport,
+    ProgressNotification, ProviderCapabilities, ReadOnlyPolicy, RecoveredUploadTask,
+    ReplacementError, ReplacementOperation, ReplacementResult, ReplacementUndoResult, ReportFormat,
+    SaveOptions, ScanGlobOptions, ScanProgress, ScanResult, ScanResultEvent, ScanRevalidation,
+    ScanStatus, ScanSummary, StaleFilePolicy, StartupReport, StorageMode, StorageUsage, StoreKind,
+    StoreRecoveryResult, SystemHealth, SystemPermissions, ThumbnailFormat, TrashedHistoryRecord,
+    UniqueImage, UnwritableFile, UploadHistoryRecord, UploadMode, UploadProgress, UploadResult,
+    UploadScope, UploadSessionTasks, UploadStatus, UploadTarget, UploadTaskInfo, UploadTaskManager,
+    UploadTaskStatus,
 };
-use crate::services::history_service::{HistoryQuery, HistoryStatistics};
-use crate::services::{ConfigService, FileService, HistoryService, ImageService, OSSService};
+use crate::services::backup_service::BackupRetention;
+use crate::services::connection_diagnostics::{self, clock_skew_finding, clock_step};
+use crate::services::history_service::{
+    HistoryQuery, HistorySortField, HistorySourceGroup, HistoryStatistics, SortOrder,
+};
+use crate::services::image_service::read_exif;
+use crate::services::key_template::{
+    self, is_content_addressed, render_object_key, upload_template, uses_content_hash,
+    KeyTemplateContext,
+};
+use crate::services::link_analysis::{analyze_file, other_config_counts};
+use crate::services::link_formatter::{format_links, LinkSource};
+use crate::services::metrics_service::{
+    eta_range, MetricsService, StatsRange, UploadMetric, UploadPerformanceStats,
+};
+use crate::services::original_archive::OriginalArchive;
+use crate::services::oss_registry::OSS_SERVICES;
+use crate::services::oss_service::{
+    object_key_from_url, provider_capabilities, replace_url_host, request_failure_steps,
+    UploadedObject,
+};
+use crate::services::report::{
+    annotate_changes, default_changelog_path, read_changelog, render_duplicate_report,
+    render_history_gallery, render_scan_report, replacement_changelog, replacement_report_markdown,
+    replacement_report_paths, write_changelog, write_report, ScanReport,
+};
+use crate::services::scan_globs::ScanGlobs;
+use crate::services::thumbnail_cache::THUMBNAIL_WORK;
+use crate::services::{local_duplicates, local_integrity};
+use crate::services::{
+    AppDataService, BackupService, ConfigService, FileService, HistoryService, ImageService,
+    OSSService, ReplacementJournal,
+};
+use crate::utils::app_paths;
+use crate::utils::crash_reports::{catch_panic_async, CrashReports};
 use crate::utils::error::AppError;
-use crate::{log_debug, log_error, log_info};
+use crate::utils::paths::{
+    check_path_syntax, validate_user_path, validate_user_path_maybe_missing,
+    validate_user_write_target, PathKind, USER_PATHS,
+};
+use crate::utils::store_recovery::CORRUPT_STORES;
+use crate::{log_debug, log_error, log_info, log_warn};
 use base64::{engine::general_purpose, Engine};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
+pub mod batch_notify;
+pub mod cdn_check;
+pub mod connectivity;
+pub mod history_writes;
+pub mod limits;
+pub mod maintenance;
+pub mod pending;
+pub mod prefix_cache;
+pub mod preview_cache;
 pub mod progress;
-
+pub mod scan_progress;
+pub mod startup_tasks;
+pub mod test_progress;
+pub mod window_sessions;
+
+use batch_notify::{BatchSummary, BATCH_NOTIFIER};
+use cdn_check::CDN_CHECKS;
+use connectivity::{is_offline_error, CONNECTIVITY};
+use history_writes::{record_upload, HISTORY_WRITES};
+use limits::*;
+use maintenance::MAINTENANCE;
+use pending::PENDING_UPLOADS;
+use prefix_cache::OSS_PREFIXES;
+use preview_cache::PROCESSED_SIZES;
 use progress::PROGRESS_NOTIFIER;
+use scan_progress::SCAN_TRACKER;
+use startup_tasks::STARTUP_TASKS;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use test_progress::TEST_PROGRESS;
+use window_sessions::WINDOW_SESSIONS;
 
 #[cfg(test)]
 mod tests;
@@ -29,7 +111,8 @@ mod tests;
 
 struct RateLimiter {
     requests: Arc<Mutex<HashMap<String, Vec<Instant>>>>,
-    max_requests: usize,
+    // Changed by `apply_app_settings` for the upload limiter
+    max_requests: std::sync::atomic::AtomicUsize,
     window: Duration,
 }
 
@@ -37,11 +120,16 @@ impl RateLimiter {
     fn new(max_requests: usize, window: Duration) -> Self {
         Self {
             requests: Arc::new(Mutex::new(HashMap::new())),
-            max_requests,
+            max_requests: std::sync::atomic::AtomicUsize::new(max_requests),
             window,
         }
     }
 
+    fn set_max_requests(&self, max_requests: usize) {
+        self.max_requests
+            .store(max_requests, std::sync::atomic::Ordering::Relaxed);
+    }
+
     fn check_rate_limit(&self, key: &str) -> Result<(), AppError> {
         let now = Instant::now();
         let mut requests = self
@@ -55,7 +143,7 @@ impl RateLimiter {
         entry.retain(|&time| now.duration_since(time) < self.window);
 
         // Check if we're over the limit
-        if entry.len() >= self.max_requests {
+        if entry.len() >= self.max_requests.load(std::sync::atomic::Ordering::Relaxed) {
             return Err(AppError::Security("Rate limit exceeded".to_string()));
         }
 
@@ -67,9 +155,17 @@ impl RateLimiter {
 }
 
 lazy_static::lazy_static! {
-    static ref UPLOAD_RATE_LIMITER: RateLimiter = RateLimiter::new(10, Duration::from_secs(60));
+    // Limit from `AppSettings::upload_rate_limit_per_minute`
+    static ref UPLOAD_RATE_LIMITER: RateLimiter = RateLimiter::new(
+        AppSettings::default().upload_rate_limit_per_minute as usize,
+        Duration::from_secs(60),
+    );
     static ref CONFIG_RATE_LIMITER: RateLimiter = RateLimiter::new(5, Duration::from_secs(60));
     static ref SCAN_RATE_LIMITER: RateLimiter = RateLimiter::new(20, Duration::from_secs(60));
+    // Files the most recent batch skipped for exceeding max_image_size_mb
+    static ref LAST_SKIPPED_OVERSIZED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    // Local originals the last full integrity check found modified since upload
+    static ref LAST_MODIFIED_ORIGINALS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 }
 
 // ============================================================================
@@ -77,17 +173,19 @@ lazy_static::lazy_static! {
 // ============================================================================
 
 /// Validates file paths for security and existence
-pub fn validate_file_paths(paths: &[String]) -> Result<(), AppError> {
+/// `max_files` comes from `AppSettings::max_scan_files`
+pub fn validate_file_paths(paths: &[String], max_files: usize) -> Result<(), AppError> {
     if paths.is_empty() {
         return Err(AppError::Validation(
             "File paths cannot be empty".to_string(),
         ));
     }
 
-    if paths.len() > 100 {
-        return Err(AppError::Validation(
-            "Too many files selected (max 100)".to_string(),
-        ));
+    if paths.len() > max_files {
+        return Err(AppError::Validation(format!(
+            "Too many files selected (max {}, adjustable in settings)",
+            max_files
+        )));
     }
 
     for path in paths {
@@ -96,40 +194,21 @@ pub fn validate_file_paths(paths: &[String]) -> Result<(), AppError> {
                 "File path cannot be empty".to_string(),
             ));
         }
+        check_len("File path", path, MAX_PATH_LEN)?;
+        validate_user_path(path, PathKind::Markdown)?;
+    }
 
-        // Security check: prevent path traversal attacks
-        if path.contains("..") || path.contains("~") {
-            return Err(AppError::Security("Invalid file path detected".to_string()));
-        }
-
-        let path_obj = Path::new(path);
-        if !path_obj.exists() {
-            return Err(AppError::FileSystem(format!("File not found: {}", path)));
-        }
-
-        // Check if it's actually a file
-        if !path_obj.is_file() {
-            return Err(AppError::Validation(format!(
-                "Path is not a file: {}",
-                path
-            )));
-        }
+    Ok(())
+}
 
-        // Check file extension for markdown files
-        if let Some(ext) = path_obj.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            if !["md", "markdown"].contains(&ext_str.as_str()) {
-                return Err(AppError::Validation(format!(
-                    "File is not a markdown file: {}",
-                    path
-                )));
-            }
-        } else {
-            return Err(AppError::Validation(format!(
-                "File has no extension: {}",
-                path
-            )));
-        }
+/// Validates the number of images in one upload call against
+/// `AppSettings::max_upload_images`
+pub fn validate_upload_count(count: usize, max_images: usize) -> Result<(), AppError> {
+    if count > max_images {
+        return Err(AppError::Validation(format!(
+            "Too many images selected (max {}, adjustable in settings)",
+            max_images
+        )));
     }
 
     Ok(())
@@ -137,18 +216,14 @@ pub fn validate_file_paths(paths: &[String]) -> Result<(), AppError> {
 
 /// Validates image IDs
 #[allow(dead_code)]
-pub fn validate_image_ids(image_ids: &[String]) -> Result<(), AppError> {
+pub fn validate_image_ids(image_ids: &[String], max_images: usize) -> Result<(), AppError> {
     if image_ids.is_empty() {
         return Err(AppError::Validation(
             "Image IDs cannot be empty".to_string(),
         ));
     }
 
-    if image_ids.len() > 50 {
-        return Err(AppError::Validation(
-            "Too many images selected (max 50)".to_string(),
-        ));
-    }
+    validate_upload_count(image_ids.len(), max_images)?;
 
     for id in image_ids {
         if id.is_empty() {
@@ -169,49 +244,70 @@ pub fn validate_image_ids(image_ids: &[String]) -> Result<(), AppError> {
 
 /// Validates OSS configuration
 pub fn validate_oss_config_params(config: &OSSConfig) -> Result<(), AppError> {
-    if config.endpoint.is_empty() {
-        return Err(AppError::Validation(
-            "OSS endpoint cannot be empty".to_string(),
-        ));
-    }
-
-    if config.access_key_id.is_empty() {
-        return Err(AppError::Validation(
-            "Access key ID cannot be empty".to_string(),
-        ));
-    }
-
-    if config.access_key_secret.is_empty() {
-        return Err(AppError::Validation(
-            "Access key secret cannot be empty".to_string(),
-        ));
-    }
-
-    if config.bucket.is_empty() {
-        return Err(AppError::Validation(
-            "Bucket name cannot be empty".to_string(),
-        ));
+    // Provider-aware rules live in ConfigService so commands and validate_config agree
+    let errors = ConfigService::config_errors(config);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors.join("; ")));
     }
 
-    if config.region.is_empty() {
-        return Err(AppError::Validation("Region cannot be empty".to_string()));
-    }
+    Ok(())
+}
 
-    // Validate compression quality
-    if config.compression_quality > 100 {
-        return Err(AppError::Validation(
-            "Compression quality must be between 0-100".to_string(),
-        ));
-    }
+/// Asset roots of one scan: the requested ones, else those from the settings.
+/// Roots must be absolute; a root that doesn't exist is only logged.
+pub fn asset_roots_for_scan(
+    requested: Option<Vec<String>>,
+    settings: &AppSettings,
+) -> Result<Vec<std::path::PathBuf>, AppError> {
+    let roots = requested.unwrap_or_else(|| settings.asset_roots.clone());
+    roots
+        .into_iter()
+        .map(|root| {
+            let path = std::path::PathBuf::from(&root);
+            if !path.is_absolute() || root.contains("..") {
+                return Err(AppError::Validation(format!(
+                    "Asset root must be an absolute path: {}",
+                    root
+                )));
+            }
+            if !path.is_dir() {
+                log_warn!(
+                    operation = "asset_roots_for_scan",
+                    asset_root = %root,
+                    "Asset root is not a directory"
+                );
+            }
+            Ok(path)
+        })
+        .collect()
+}
 
-    // Validate endpoint URL format
-    if !config.endpoint.starts_with("http://") && !config.endpoint.starts_with("https://") {
-        return Err(AppError::Validation(
-            "Endpoint must be a valid URL".to_string(),
-        ));
-    }
+/// Glob rules for a scan: each list set in `requested` replaces the one from settings
+pub fn scan_globs_for_scan(
+    requested: Option<ScanGlobOptions>,
+    settings: &AppSettings,
+) -> Result<ScanGlobs, AppError> {
+    let requested = requested.unwrap_or_default();
+    let globs = ScanGlobs::new(
+        requested
+            .include_image_globs
+            .as_ref()
+            .unwrap_or(&settings.include_image_globs),
+        requested
+            .exclude_image_globs
+            .as_ref()
+            .unwrap_or(&settings.exclude_image_globs),
+        requested
+            .exclude_file_globs
+            .as_ref()
+            .unwrap_or(&settings.exclude_file_globs),
+    )?;
+    Ok(globs.with_image_extensions(&settings.allowed_extensions))
+}
 
-    Ok(())
+/// Parse an `upload_mode` argument; unlike history files, unknown names are rejected
+pub fn parse_upload_mode(mode: &str) -> Result<UploadMode, String> {
+    UploadMode::parse(mode).ok_or_else(|| format!("Invalid upload mode: {}", mode))
 }
 
 /// Validates pagination parameters
@@ -276,43 +372,313 @@ pub fn validate_uuid(uuid: &str) -> Result<(), AppError> {
 // File and Scan Commands
 // ============================================================================
 
+/// Scan markdown files, emitting `scan://progress` after each file and
+/// `scan://done` at the end. Scans started with a `scan_id` can be stopped
+/// between files with `cancel_scan`.
 #[tauri::command]
-pub async fn scan_markdown_files(file_paths: Vec<String>) -> Result<Vec<ScanResult>, String> {
+pub async fn scan_markdown_files(
+    file_paths: Vec<String>,
+    scan_id: Option<String>,
+    asset_roots: Option<Vec<String>>,
+    scan_globs: Option<ScanGlobOptions>,
+) -> Result<Vec<ScanResult>, String> {
     // Rate limiting
     SCAN_RATE_LIMITER
         .check_rate_limit("scan_files")
         .map_err(|e| e.to_string())?;
 
     // Validate input parameters
-    validate_file_paths(&file_paths).map_err(|e| e.to_string())?;
+    let settings = current_app_settings().await;
+    validate_file_paths(&file_paths, settings.max_scan_files).map_err(|e| e.to_string())?;
 
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_max_image_size(settings.max_image_bytes())
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_asset_roots(asset_roots_for_scan(asset_roots, &settings).map_err(|e| e.to_string())?)
+        .with_scan_globs(scan_globs_for_scan(scan_globs, &settings).map_err(|e| e.to_string())?);
+    scan_with_events(&file_service, file_paths, scan_id, 0).await
+}
+
+/// Scan markdown files like `scan_markdown_files`, but emit each result as
+/// `scan://result` instead of returning them all at the end, so large sets
+/// don't pile up in memory. Finishes with `scan://done`, whose summary is also
+/// returned; `cancel_scan(scan_id)` stops the emission within the current file.
+#[tauri::command]
+pub async fn scan_markdown_files_streamed(
+    scan_id: String,
+    file_paths: Vec<String>,
+    asset_roots: Option<Vec<String>>,
+    scan_globs: Option<ScanGlobOptions>,
+) -> Result<ScanSummary, String> {
+    SCAN_RATE_LIMITER
+        .check_rate_limit("scan_files")
+        .map_err(|e| e.to_string())?;
+
+    if scan_id.is_empty() {
+        return Err("Scan ID cannot be empty".to_string());
+    }
+    let settings = current_app_settings().await;
+    validate_file_paths(
+        &file_paths,
+        MAX_STREAMED_SCAN_FILES.max(settings.max_scan_files),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_max_image_size(settings.max_image_bytes())
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_asset_roots(asset_roots_for_scan(asset_roots, &settings).map_err(|e| e.to_string())?)
+        .with_scan_globs(scan_globs_for_scan(scan_globs, &settings).map_err(|e| e.to_string())?);
+
+    let total = file_paths.len();
+    let cancelled = SCAN_TRACKER.start(Some(&scan_id));
+    let mut summary = ScanSummary {
+        scan_id: Some(scan_id.clone()),
+        total_files: total,
+        scanned_files: 0,
+        total_images: 0,
+        missing_images: 0,
+        failed_files: 0,
+        skipped_files: 0,
+        ignored_files: 0,
+        cancelled: false,
+    };
+
+    let scanned = file_service
+        .scan_markdown_files_streamed(&file_paths, &cancelled, |index, result| {
+            summary.total_images += result.images.len();
+            summary.missing_images += result.missing_images.len();
+            match result.status {
+                ScanStatus::Error => summary.failed_files += 1,
+                ScanStatus::Skipped => summary.skipped_files += 1,
+                ScanStatus::Success => {}
+            }
+            // Same as scan_with_events: referenced images may be uploaded next
+            for image in result.images.iter().filter(|i| i.exists) {
+                USER_PATHS.allow(Path::new(&image.absolute_path));
+            }
+            SCAN_TRACKER.result(&ScanResultEvent {
+                scan_id: scan_id.clone(),
+                current: index + 1,
+                total,
+                result,
+            });
+        })
+        .await;
+    summary.scanned_files = scanned;
+    summary.cancelled = scanned < total;
+    SCAN_TRACKER.finish(&summary);
+
+    log_info!(
+        operation = "scan_markdown_files_streamed",
+        scan_id = %scan_id,
+        scanned = scanned,
+        total = total,
+        cancelled = summary.cancelled,
+        "Streamed scan finished"
+    );
+
+    Ok(summary)
+}
+
+/// Stop a running scan before its next file; false when no scan has this id
+#[tauri::command]
+pub async fn cancel_scan(scan_id: String) -> Result<bool, String> {
+    if scan_id.is_empty() {
+        return Err("Scan ID cannot be empty".to_string());
+    }
+    Ok(SCAN_TRACKER.cancel(&scan_id))
+}
+
+// Shared by file and directory scans; `ignored_files` only goes into the summary
+async fn scan_with_events(
+    file_service: &FileService,
+    file_paths: Vec<String>,
+    scan_id: Option<String>,
+    ignored_files: usize,
+) -> Result<Vec<ScanResult>, String> {
+    let total = file_paths.len();
+    let cancelled = SCAN_TRACKER.start(scan_id.as_deref());
+    let mut images_found = 0;
+
+    let results = file_service
+        .scan_markdown_files_with_progress(file_paths, &cancelled, |index, result| {
+            images_found += result.images.len();
+            SCAN_TRACKER.progress(&ScanProgress {
+                scan_id: scan_id.clone(),
+                current: index + 1,
+                total,
+                file_path: result.file_path.clone(),
+                images_found,
+            });
+        })
+        .await;
+
+    let summary = match &results {
+        Ok(results) => ScanSummary {
+            scan_id,
+            total_files: total,
+            scanned_files: results.len(),
+            total_images: results.iter().map(|r| r.images.len()).sum(),
+            missing_images: results.iter().map(|r| r.missing_images.len()).sum(),
+            failed_files: results
+                .iter()
+                .filter(|r| matches!(r.status, ScanStatus::Error))
+                .count(),
+            skipped_files: results
+                .iter()
+                .filter(|r| matches!(r.status, ScanStatus::Skipped))
+                .count(),
+            ignored_files,
+            cancelled: results.len() < total,
+        },
+        Err(_) => ScanSummary {
+            scan_id,
+            total_files: total,
+            scanned_files: 0,
+            total_images: 0,
+            missing_images: 0,
+            failed_files: 0,
+            skipped_files: 0,
+            ignored_files,
+            cancelled: false,
+        },
+    };
+    SCAN_TRACKER.finish(&summary);
+
+    // Images a picked document references may be uploaded next, even when
+    // they live outside the picked folder
+    if let Ok(results) = &results {
+        for image in results.iter().flat_map(|r| &r.images).filter(|i| i.exists) {
+            USER_PATHS.allow(Path::new(&image.absolute_path));
+        }
+    }
+
+    results.map_err(|e| e.to_string())
+}
+
+/// Scan the markdown files under a directory one page at a time, so a large
+/// documentation tree is never returned as a single giant result
+#[tauri::command]
+pub async fn scan_markdown_directory(
+    directory: String,
+    page: Option<usize>,
+    page_size: Option<usize>,
+    scan_id: Option<String>,
+    asset_roots: Option<Vec<String>>,
+    scan_globs: Option<ScanGlobOptions>,
+) -> Result<PaginatedResult<ScanResult>, String> {
+    SCAN_RATE_LIMITER
+        .check_rate_limit("scan_directory")
+        .map_err(|e| e.to_string())?;
+
+    let (page, page_size) = validate_pagination(page, page_size).map_err(|e| e.to_string())?;
+
+    validate_user_path(&directory, PathKind::Directory).map_err(|e| e.to_string())?;
+
+    let settings = current_app_settings().await;
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_max_image_size(settings.max_image_bytes())
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_asset_roots(asset_roots_for_scan(asset_roots, &settings).map_err(|e| e.to_string())?)
+        .with_scan_globs(scan_globs_for_scan(scan_globs, &settings).map_err(|e| e.to_string())?)
+        .with_ignore_files(settings.respect_ignore_files);
+    let listing = file_service
+        .list_markdown_files(&directory)
+        .await
+        .map_err(|e| e.to_string())?;
+    let files = listing.files;
+
+    let total = files.len();
+    let offset = (page - 1) * page_size;
+    let page_files: Vec<String> = files.into_iter().skip(offset).take(page_size).collect();
+
+    log_debug!(
+        operation = "scan_markdown_directory",
+        directory = %directory,
+        page = page,
+        total_files = total,
+        ignored_files = listing.ignored,
+        "Scanning directory page"
+    );
+
+    let items = scan_with_events(&file_service, page_files, scan_id, listing.ignored).await?;
+
+    Ok(PaginatedResult {
+        items,
+        total,
+        page,
+        page_size,
+        has_more: offset + page_size < total,
+    })
+}
+
+/// Group scan results by unique image (canonical path, then identical content)
+/// so each image is uploaded once no matter how many files reference it.
+#[tauri::command]
+pub async fn group_scan_results(results: Vec<ScanResult>) -> Result<Vec<UniqueImage>, String> {
     let file_service = FileService::new().map_err(|e| e.to_string())?;
     file_service
-        .scan_markdown_files(file_paths)
+        .group_scan_results(&results)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// Check scan results against the disk right before replacing: markdown files
+/// edited since the scan (their links must be rescanned), referenced images
+/// that are gone, and images that changed
 #[tauri::command]
-pub async fn get_image_info(image_path: String) -> Result<ImageInfo, String> {
-    // Validate input parameters
-    if image_path.is_empty() {
-        return Err("Image path cannot be empty".to_string());
+pub async fn revalidate_scan_results(results: Vec<ScanResult>) -> Result<ScanRevalidation, String> {
+    let settings = current_app_settings().await;
+    check_count("files", results.len(), settings.max_scan_files).map_err(|e| e.to_string())?;
+    // Deleted files are reported, not refused
+    for result in &results {
+        validate_user_path_maybe_missing(&result.file_path, PathKind::Markdown)
+            .map_err(|e| e.to_string())?;
     }
 
-    // Security check: prevent path traversal
-    if image_path.contains("..") || image_path.contains("~") {
-        return Err("Invalid image path detected".to_string());
-    }
+    Ok(FileService::new()
+        .map_err(|e| e.to_string())?
+        .revalidate_scan_results(&results)
+        .await)
+}
 
-    let path = Path::new(&image_path);
-    if !path.exists() {
-        return Err(format!("Image file not found: {}", image_path));
-    }
+/// Fan uploaded URLs back out to every usage. `upload_results` are matched to
+/// groups by `image_id == UniqueImage::id`; failed uploads produce no replacements.
+/// `preserve_link_suffix` keeps each link's `?query`/`#fragment`, off by default.
+#[tauri::command]
+pub async fn expand_group_replacements(
+    groups: Vec<UniqueImage>,
+    upload_results: Vec<UploadResult>,
+    preserve_link_suffix: Option<bool>,
+) -> Result<Vec<LinkReplacement>, String> {
+    let urls: HashMap<&str, &str> = upload_results
+        .iter()
+        .filter(|result| result.success)
+        .filter_map(|result| {
+            result
+                .uploaded_url
+                .as_deref()
+                .map(|url| (result.image_id.as_str(), url))
+        })
+        .collect();
+
+    Ok(groups
+        .iter()
+        .filter_map(|group| {
+            urls.get(group.id.as_str())
+                .map(|url| group.link_replacements(url, preserve_link_suffix.unwrap_or(false)))
+        })
+        .flatten()
+        .collect())
+}
 
-    if !path.is_file() {
-        return Err(format!("Path is not a file: {}", image_path));
-    }
+#[tauri::command]
+pub async fn get_image_info(image_path: String) -> Result<ImageInfo, String> {
+    validate_user_path(&image_path, PathKind::Image).map_err(|e| e.to_string())?;
 
     let image_service = ImageService::new();
     image_service
@@ -321,144 +687,466 @@ pub async fn get_image_info(image_path: String) -> Result<ImageInfo, String> {
         .map_err(|e| e.to_string())
 }
 
+/// Number of files validate_images reads at once
+const VALIDATE_CONCURRENCY: usize = 8;
+
+/// Check a file selection before it's uploaded: per path whether it can be
+/// uploaded, with format and dimensions, or the reason it can't. Paths the
+/// path validator rejects are reported per item instead of failing the call.
 #[tauri::command]
-pub async fn generate_thumbnail(image_path: String, size: u32) -> Result<Vec<u8>, String> {
-    // Validate input parameters
-    if image_path.is_empty() {
-        return Err("Image path cannot be empty".to_string());
-    }
+pub async fn validate_images(paths: Vec<String>) -> Result<Vec<ImageValidation>, String> {
+    check_count("images", paths.len(), MAX_PREVIEW_IMAGES).map_err(|e| e.to_string())?;
+
+    let max_bytes = current_app_settings().await.max_image_bytes();
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(VALIDATE_CONCURRENCY));
+    let image_service = ImageService::new();
+    let mut tasks = Vec::with_capacity(paths.len());
 
-    if size == 0 || size > 1024 {
-        return Err("Thumbnail size must be between 1-1024 pixels".to_string());
+    for path in paths {
+        let semaphore = semaphore.clone();
+        let image_service = image_service.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            // Traversal is a malformed path here, a Security error past the
+            // syntax check means the file wasn't picked in the app
+            let checked = check_path_syntax(&path, PathKind::Any)
+                .map_err(|e| (ImageRejectReason::InvalidPath, e))
+                .and_then(|_| {
+                    validate_user_path(&path, PathKind::Any).map_err(|e| match e {
+                        AppError::FileSystem(_) => (ImageRejectReason::NotFound, e),
+                        AppError::Security(_) => (ImageRejectReason::NotSelected, e),
+                        _ => (ImageRejectReason::InvalidPath, e),
+                    })
+                });
+            if let Err((reason, e)) = checked {
+                return ImageValidation::rejected(&path, reason, e.to_string());
+            }
+            image_service.validate_image(&path, max_bytes).await
+        }));
     }
 
-    // Security check: prevent path traversal
-    if image_path.contains("..") || image_path.contains("~") {
-        return Err("Invalid image path detected".to_string());
+    let mut validations = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        validations.push(task.await.map_err(|e| format!("Task join error: {}", e))?);
     }
+    Ok(validations)
+}
 
-    let path = Path::new(&image_path);
-    if !path.exists() {
-        return Err(format!("Image file not found: {}", image_path));
+/// Thumbnail of at most `size` pixels per edge (capped by the
+/// max_thumbnail_size setting), JPEG unless `output_format` asks for PNG or
+/// WebP to keep transparency
+#[tauri::command]
+pub async fn generate_thumbnail(
+    image_path: String,
+    size: u32,
+    output_format: Option<ThumbnailFormat>,
+) -> Result<GeneratedThumbnail, String> {
+    let settings = current_app_settings().await;
+    let max_size = settings.max_thumbnail_size;
+    if size == 0 || size > max_size {
+        return Err(format!(
+            "Thumbnail size must be between 1-{} pixels",
+            max_size
+        ));
     }
 
+    validate_user_path(&image_path, PathKind::Image).map_err(|e| e.to_string())?;
+
+    let format = output_format.unwrap_or_default();
+    THUMBNAIL_WORK.set_concurrency(settings.thumbnail_concurrency);
     let image_service = ImageService::new();
-    image_service
-        .generate_thumbnail(&image_path, size)
+    let data = image_service
+        .generate_thumbnail(&image_path, size, format)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    Ok(GeneratedThumbnail {
+        data,
+        format,
+        mime_type: format.mime_type().to_string(),
+    })
 }
 
-// ============================================================================
-// Upload Commands
-// ============================================================================
+/// Formats convert_image_format can write
+const CONVERSION_FORMATS: [&str; 8] = ["jpeg", "jpg", "png", "webp", "bmp", "tiff", "tif", "gif"];
 
+/// Convert an image to another format. With `output_path` the result is written
+/// there (never over the source), otherwise it comes back as base64.
 #[tauri::command]
-pub async fn upload_images_with_ids(
-    image_data: Vec<(String, String)>, // (file_id, image_path) pairs
-    config: OSSConfig,
-) -> Result<Vec<UploadResult>, String> {
-    log_info!(
-        operation = "upload_images_with_ids_command",
-        image_count = image_data.len(),
-        provider = ?config.provider,
-        "Starting upload images with IDs command"
-    );
+pub async fn convert_image_format(
+    image_path: String,
+    target_format: String,
+    output_path: Option<String>,
+) -> Result<ImageConversionResult, String> {
+    validate_user_path(&image_path, PathKind::Image).map_err(|e| e.to_string())?;
+
+    let format = target_format.trim().to_lowercase();
+    if !CONVERSION_FORMATS.contains(&format.as_str()) {
+        return Err(format!(
+            "Unsupported target format: {} (supported: {})",
+            target_format,
+            CONVERSION_FORMATS.join(", ")
+        ));
+    }
 
-    // Rate limiting
-    UPLOAD_RATE_LIMITER
-        .check_rate_limit("upload_images")
+    if let Some(output_path) = &output_path {
+        let same_file = match (
+            std::fs::canonicalize(&image_path),
+            std::fs::canonicalize(output_path),
+        ) {
+            (Ok(source), Ok(target)) => source == target,
+            _ => Path::new(&image_path) == Path::new(output_path),
+        };
+        if same_file {
+            return Err(
+                "Converting in place is not supported, choose a different output path".to_string(),
+            );
+        }
+        validate_user_write_target(output_path, PathKind::Image, false)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let image_data = tokio::fs::read(&image_path)
+        .await
+        .map_err(|e| format!("Failed to read image file: {}", e))?;
+
+    let image_service = ImageService::new();
+    let converted = image_service
+        .convert_format(&image_data, &format)
+        .await
         .map_err(|e| {
             log_error!(
-                operation = "upload_images_with_ids_command",
+                operation = "convert_image_format",
+                image_path = %image_path,
+                target_format = %format,
                 error = %e,
-                "Rate limit exceeded"
+                "Image conversion failed"
             );
             e.to_string()
         })?;
 
-    // Validate input parameters
-    if image_data.is_empty() {
-        log_error!(
-            operation = "upload_images_with_ids_command",
-            error = "Image data cannot be empty",
-            "Empty image data provided"
-        );
-        return Err("Image data cannot be empty".to_string());
-    }
+    log_info!(
+        operation = "convert_image_format",
+        image_path = %image_path,
+        target_format = %format,
+        original_size = image_data.len(),
+        converted_size = converted.len(),
+        "Image converted"
+    );
 
-    for (file_id, image_path) in &image_data {
-        // Validate file ID format (should be UUID)
-        if file_id.is_empty()
-            || file_id.len() != 36
-            || file_id.chars().filter(|&c| c == '-').count() != 4
-        {
-            log_error!(
-                operation = "upload_images_with_ids_command",
-                file_id = %file_id,
-                error = "Invalid file ID format",
-                "File ID must be a valid UUID"
-            );
-            return Err(format!("Invalid file ID format: {}", file_id));
+    let size = converted.len() as u64;
+    match output_path {
+        Some(output_path) => {
+            tokio::fs::write(&output_path, &converted)
+                .await
+                .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+            Ok(ImageConversionResult {
+                output_path: Some(output_path),
+                data_base64: None,
+                format,
+                size,
+            })
         }
+        None => Ok(ImageConversionResult {
+            output_path: None,
+            data_base64: Some(general_purpose::STANDARD.encode(&converted)),
+            format,
+            size,
+        }),
+    }
+}
 
-        log_debug!(
-            operation = "upload_images_with_ids_command",
-            path_index = 0,
-            path = %image_path,
-            file_id = %file_id,
-            "Validating image path and file ID"
-        );
+/// Recommended compression quality (1-100) for an image, optionally aiming at a size
+#[tauri::command]
+pub async fn suggest_compression_quality(
+    image_path: String,
+    target_size_kb: Option<u64>,
+) -> Result<u8, String> {
+    validate_user_path(&image_path, PathKind::Image).map_err(|e| e.to_string())?;
+
+    if target_size_kb == Some(0) {
+        return Err("Target size must be greater than 0 KB".to_string());
+    }
 
-        // Basic path validation (sync version like in original upload_images)
-        if image_path.is_empty() {
-            log_error!(
-                operation = "upload_images_with_ids_command",
-                image_path = %image_path,
-                file_id = %file_id,
-                error = "Image path cannot be empty",
-                "Path validation failed"
-            );
-            return Err("Image path cannot be empty".to_string());
-        }
+    let image_service = ImageService::new();
+    image_service
+        .get_optimal_quality(&image_path, target_size_kb)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-        // Security check: prevent path traversal
-        if image_path.contains("..") || image_path.contains("~") {
-            log_error!(
-                operation = "upload_images_with_ids_command",
-                image_path = %image_path,
-                file_id = %file_id,
-                error = "Invalid image path detected",
-                "Security validation failed"
-            );
-            return Err("Invalid image path detected".to_string());
-        }
+/// Number of images processed concurrently during a preview
+const PREVIEW_CONCURRENCY: usize = 4;
 
-        let path_obj = Path::new(image_path);
-        if !path_obj.exists() {
-            log_error!(
-                operation = "upload_images_with_ids_command",
-                image_path = %image_path,
-                file_id = %file_id,
-                error = "Image file not found",
-                "File validation failed"
-            );
-            return Err(format!("Image file not found: {}", image_path));
-        }
+lazy_static::lazy_static! {
+    /// Cancellation flags for running previews, keyed by preview_id
+    static ref PROCESSING_CANCELLATIONS: Mutex<HashMap<String, Arc<std::sync::atomic::AtomicBool>>> =
+        Mutex::new(HashMap::new());
+}
 
-        if !path_obj.is_file() {
-            log_error!(
-                operation = "upload_images_with_ids_command",
-                image_path = %image_path,
-                file_id = %file_id,
-                error = "Path is not a file",
-                "File validation failed"
-            );
-            return Err(format!("Path is not a file: {}", image_path));
-        }
-    }
+// Unregisters a preview's cancellation flag however the preview ends
+struct PreviewRegistration(String);
 
-    // Validate OSS configuration (like in original upload_images)
+impl Drop for PreviewRegistration {
+    fn drop(&mut self) {
+        PROCESSING_CANCELLATIONS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.0);
+    }
+}
+
+/// Run the configured processing pipeline on each image in memory and report
+/// before/after size, dimensions and output format. Nothing is written or uploaded.
+#[tauri::command]
+pub async fn preview_image_processing(
+    image_paths: Vec<String>,
+    config: OSSConfig,
+    preview_id: Option<String>,
+) -> Result<Vec<ImageProcessingPreview>, String> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    if image_paths.is_empty() {
+        return Err("Image paths cannot be empty".to_string());
+    }
+
+    if image_paths.len() > MAX_PREVIEW_IMAGES {
+        return Err(format!(
+            "Too many images selected (max {})",
+            MAX_PREVIEW_IMAGES
+        ));
+    }
+
+    for path in &image_paths {
+        validate_user_path_maybe_missing(path, PathKind::Image).map_err(|e| e.to_string())?;
+    }
+
+    let options = ImageProcessingOptions::from_config(&config);
+    if let Some(format) = &options.output_format {
+        if !["jpeg", "jpg", "png", "webp"].contains(&format.to_lowercase().as_str()) {
+            return Err(format!("Unsupported output format: {}", format));
+        }
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _registration = match &preview_id {
+        Some(id) => {
+            PROCESSING_CANCELLATIONS
+                .lock()
+                .map_err(|e| e.to_string())?
+                .insert(id.clone(), cancelled.clone());
+            Some(PreviewRegistration(id.clone()))
+        }
+        None => None,
+    };
+
+    log_info!(
+        operation = "preview_image_processing",
+        image_count = image_paths.len(),
+        "Starting image processing preview"
+    );
+
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(PREVIEW_CONCURRENCY));
+    let image_service = ImageService::new();
+    let mut tasks = Vec::with_capacity(image_paths.len());
+
+    for image_path in image_paths {
+        let semaphore = semaphore.clone();
+        let cancelled = cancelled.clone();
+        let options = options.clone();
+        let image_service = image_service.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let mut preview = ImageProcessingPreview {
+                image_path: image_path.clone(),
+                original_size: 0,
+                processed_size: 0,
+                original_width: 0,
+                original_height: 0,
+                processed_width: 0,
+                processed_height: 0,
+                original_format: String::new(),
+                output_format: String::new(),
+                has_gps: false,
+                metadata_warning: None,
+                error: None,
+            };
+
+            let _permit = match semaphore.acquire().await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    preview.error = Some(e.to_string());
+                    return preview;
+                }
+            };
+
+            if cancelled.load(Ordering::Relaxed) {
+                preview.error = Some(AppError::Cancelled.to_string());
+                return preview;
+            }
+
+            let result = match tokio::fs::read(&image_path).await {
+                Ok(data) => {
+                    preview.has_gps = read_exif(&mut std::io::Cursor::new(&data))
+                        .has_gps
+                        .unwrap_or(false);
+                    image_service.process_image(data, &options).await
+                }
+                Err(e) => Err(AppError::FileSystem(format!(
+                    "Failed to read image file {}: {}",
+                    image_path, e
+                ))),
+            };
+
+            match result {
+                Ok(processed) => {
+                    preview.original_size = processed.original_size;
+                    preview.processed_size = processed.data.len() as u64;
+                    preview.original_width = processed.original_width;
+                    preview.original_height = processed.original_height;
+                    preview.processed_width = processed.width;
+                    preview.processed_height = processed.height;
+                    preview.original_format = processed.original_format;
+                    preview.output_format = processed.format;
+                    preview.metadata_warning =
+                        gps_retention_warning(Some(preview.has_gps), processed.reencoded);
+                    PROCESSED_SIZES.record(&image_path, &options, preview.processed_size);
+                }
+                Err(e) => preview.error = Some(e.to_string()),
+            }
+
+            preview
+        }));
+    }
+
+    let mut previews = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        previews.push(task.await.map_err(|e| format!("Task join error: {}", e))?);
+    }
+
+    log_info!(
+        operation = "preview_image_processing",
+        image_count = previews.len(),
+        failed = previews.iter().filter(|p| p.error.is_some()).count(),
+        "Image processing preview completed"
+    );
+
+    Ok(previews)
+}
+
+/// Warn when a photo with GPS coordinates would be uploaded with its EXIF intact
+fn gps_retention_warning(has_gps: Option<bool>, reencoded: bool) -> Option<String> {
+    if has_gps == Some(true) && !reencoded {
+        Some(
+            "Image contains GPS location data that will be uploaded; enable strip_metadata to remove it"
+                .to_string(),
+        )
+    } else {
+        None
+    }
+}
+
+#[tauri::command]
+pub async fn cancel_image_processing_preview(preview_id: String) -> Result<bool, String> {
+    let cancellations = PROCESSING_CANCELLATIONS.lock().map_err(|e| e.to_string())?;
+
+    match cancellations.get(&preview_id) {
+        Some(flag) => {
+            flag.store(true, std::sync::atomic::Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// ============================================================================
+// Upload Commands
+// ============================================================================
+
+/// Upload `image_data` with up to `concurrency` files in flight (defaults to
+/// the upload_concurrency setting); results come back in input order
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_images_with_ids(
+    image_data: Vec<(String, String)>, // (file_id, image_path) pairs
+    config: Option<OSSConfig>,
+    source_file: Option<String>,
+    upload_mode: Option<UploadMode>,
+    concurrency: Option<usize>,
+    config_id: Option<String>,
+    session_id: Option<String>,
+    window_session: Option<String>,
+) -> Result<Vec<UploadResult>, String> {
+    let started = Instant::now();
+    log_info!(
+        operation = "upload_images_with_ids_command",
+        image_count = image_data.len(),
+        "Starting upload images with IDs command"
+    );
+
+    // Rate limiting
+    UPLOAD_RATE_LIMITER
+        .check_rate_limit("upload_images")
+        .map_err(|e| {
+            log_error!(
+                operation = "upload_images_with_ids_command",
+                error = %e,
+                "Rate limit exceeded"
+            );
+            e.to_string()
+        })?;
+
+    // Validate input parameters
+    if image_data.is_empty() {
+        log_error!(
+            operation = "upload_images_with_ids_command",
+            error = "Image data cannot be empty",
+            "Empty image data provided"
+        );
+        return Err("Image data cannot be empty".to_string());
+    }
+
+    for (file_id, image_path) in &image_data {
+        // Validate file ID format (should be UUID)
+        if file_id.is_empty()
+            || file_id.len() != 36
+            || file_id.chars().filter(|&c| c == '-').count() != 4
+        {
+            log_error!(
+                operation = "upload_images_with_ids_command",
+                file_id = %file_id,
+                error = "Invalid file ID format",
+                "File ID must be a valid UUID"
+            );
+            return Err(format!("Invalid file ID format: {}", file_id));
+        }
+
+        log_debug!(
+            operation = "upload_images_with_ids_command",
+            path_index = 0,
+            path = %image_path,
+            file_id = %file_id,
+            "Validating image path and file ID"
+        );
+
+        if let Err(e) = validate_user_path_maybe_missing(image_path, PathKind::Image) {
+            log_error!(
+                operation = "upload_images_with_ids_command",
+                image_path = %image_path,
+                file_id = %file_id,
+                error = %e,
+                "Path validation failed"
+            );
+            return Err(e.to_string());
+        }
+
+        // Missing files fail their own item during upload instead of the whole call
+    }
+
+    let history_mode = history_upload_mode(upload_mode, source_file.as_deref());
+    let (config, config_id) =
+        upload_command_config(config, config_id, window_session, &history_mode).await?;
+
+    // Validate OSS configuration (like in original upload_images)
     validate_oss_config_params(&config).map_err(|e| {
         log_error!(
             operation = "upload_images_with_ids_command",
@@ -482,7 +1170,10 @@ pub async fn upload_images_with_ids(
         "OSS configuration loaded"
     );
 
-    let oss_service = OSSService::new(config).map_err(|e| {
+    let upload_target = UploadTarget::new(config_id.clone(), &config);
+    let processing = ImageProcessingOptions::from_config(&config);
+    let path_template = config.path_template.clone();
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| {
         log_error!(
             operation = "upload_images_with_ids_command",
             error = %e,
@@ -496,124 +1187,69 @@ pub async fn upload_images_with_ids(
         "OSS service created successfully"
     );
 
-    let image_service = ImageService::new();
-
-    let mut results = Vec::new();
-
-    for (file_id, image_path) in image_data {
-        log_debug!(
-            operation = "upload_images_with_ids_command",
-            image_path = %image_path,
-            file_id = %file_id,
-            "Processing image for upload"
-        );
-
-        // Generate progress callback using the provided file_id
-        let progress_callback = {
-            let file_id_clone = file_id.clone();
-            move |progress: UploadProgress| {
-                let _ = PROGRESS_NOTIFIER.update_progress(file_id_clone.clone(), progress);
-            }
-        };
-
-        match upload_single_image(
-            &oss_service,
-            &image_service,
-            &image_path,
-            &file_id, // Use provided file_id instead of generating new UUID
-            Some(Box::new(progress_callback)),
-        )
-        .await
-        {
-            Ok((url, checksum)) => {
-                log_info!(
-                    operation = "upload_images_with_ids_command",
-                    image_path = %image_path,
-                    file_id = %file_id,
-                    uploaded_url = %url,
-                    checksum = %checksum,
-                    "Image uploaded successfully"
-                );
-
-                results.push(UploadResult {
-                    image_id: file_id.clone(),
-                    success: true,
-                    uploaded_url: Some(url.clone()),
-                    error: None,
-                });
-
-                // Store in upload history
-                if let Ok(history_service) = HistoryService::new() {
-                    let image_name = std::path::Path::new(&image_path)
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-
-                    let history_record = UploadHistoryRecord {
-                        id: uuid::Uuid::new_v4().to_string(),
-                        timestamp: chrono::Utc::now(),
-                        image_name,
-                        uploaded_url: url,
-                        upload_mode: UploadMode::ImageUpload,
-                        source_file: None,
-                        file_size: std::fs::metadata(&image_path).map(|m| m.len()).unwrap_or(0),
-                        checksum,
-                    };
-
-                    let _ = history_service.add_upload_record(history_record).await;
-                }
-
-                // Send final completion progress before cleanup
-                let final_progress = crate::models::UploadProgress {
-                    image_id: file_id.clone(),
-                    progress: 100.0,
-                    bytes_uploaded: std::fs::metadata(&image_path).map(|m| m.len()).unwrap_or(0),
-                    total_bytes: std::fs::metadata(&image_path).map(|m| m.len()).unwrap_or(0),
-                    speed: None,
-                };
-                let _ = PROGRESS_NOTIFIER.update_progress(file_id.clone(), final_progress);
-
-                // Small delay to ensure frontend receives the completion event
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-                // Remove progress tracking for completed upload
-                let _ = PROGRESS_NOTIFIER.remove_progress(&file_id);
-            }
-            Err(e) => {
-                log_error!(
-                    operation = "upload_images_with_ids_command",
-                    image_path = %image_path,
-                    file_id = %file_id,
-                    error = %e,
-                    "Image upload failed"
-                );
-
-                results.push(UploadResult {
-                    image_id: file_id.clone(),
-                    success: false,
-                    uploaded_url: None,
-                    error: Some(e.to_string()),
-                });
-
-                // Note: We only record successful uploads in the new design
-                // Failed uploads are not stored in history
-
-                // Send final progress for failed upload (progress remains as is, but ensure UI gets final state)
-                if let Ok(Some(mut progress)) = PROGRESS_NOTIFIER.get_progress(&file_id) {
-                    // Mark as completed with error (UI can distinguish by checking results)
-                    progress.progress = 100.0;
-                    let _ = PROGRESS_NOTIFIER.update_progress(file_id.clone(), progress);
-
-                    // Small delay to ensure frontend receives the completion event
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                }
+    let image_service = configured_image_service().await;
+
+    let session_id = upload_session(session_id);
+    enqueue_uploads(
+        image_data
+            .iter()
+            .map(|(file_id, image_path)| {
+                pending_upload(
+                    file_id,
+                    image_path,
+                    config_id.clone(),
+                    &session_id,
+                    source_file.clone(),
+                    history_mode.clone(),
+                )
+            })
+            .collect(),
+    );
 
-                // Remove progress tracking for failed upload
-                let _ = PROGRESS_NOTIFIER.remove_progress(&file_id);
-            }
+    let concurrency = concurrency
+        .unwrap_or(current_app_settings().await.upload_concurrency)
+        .clamp(1, 10);
+    let file_ids: Vec<String> = image_data.iter().map(|(id, _)| id.clone()).collect();
+    let outcomes = run_bounded(image_data, concurrency, |(file_id, image_path)| {
+        let oss_service = oss_service.clone();
+        let image_service = image_service.clone();
+        let processing = processing.clone();
+        let path_template = path_template.clone();
+        let history_mode = history_mode.clone();
+        let source_file = source_file.clone();
+        let upload_target = upload_target.clone();
+        async move {
+            upload_tracked_image(
+                &oss_service,
+                &image_service,
+                &image_path,
+                &file_id,
+                &processing,
+                &path_template,
+                history_mode,
+                source_file,
+                upload_target,
+            )
+            .await
         }
-    }
+    })
+    .await;
+
+    let results: Vec<UploadResult> = file_ids
+        .into_iter()
+        .zip(outcomes)
+        .map(|(file_id, outcome)| {
+            outcome.unwrap_or_else(|e| UploadResult {
+                image_id: file_id,
+                success: false,
+                uploaded_url: None,
+                error: Some(format!("Task join error: {}", e)),
+                variants: Vec::new(),
+                queued_offline: false,
+                warnings: Vec::new(),
+            })
+        })
+        .collect();
 
     log_info!(
         operation = "upload_images_with_ids_command",
@@ -623,14 +1259,21 @@ pub async fn upload_images_with_ids(
         "Upload images with IDs command completed"
     );
 
+    PROGRESS_NOTIFIER.end_session(&session_id);
+    notify_batch_finished(BatchSummary::from_results(&results, started).in_session(&session_id))
+        .await;
     Ok(results)
 }
 
 #[tauri::command]
 pub async fn upload_images(
     image_paths: Vec<String>,
-    config: OSSConfig,
+    config: Option<OSSConfig>,
+    config_id: Option<String>,
+    session_id: Option<String>,
+    window_session: Option<String>,
 ) -> Result<Vec<UploadResult>, String> {
+    let started = Instant::now();
     // Rate limiting
     UPLOAD_RATE_LIMITER
         .check_rate_limit("upload_images")
@@ -653,14 +1296,15 @@ pub async fn upload_images(
         return Err("Image paths cannot be empty".to_string());
     }
 
-    if image_paths.len() > 50 {
+    let settings = current_app_settings().await;
+    if let Err(e) = validate_upload_count(image_paths.len(), settings.max_upload_images) {
         log_error!(
             operation = "upload_images_command",
             image_count = image_paths.len(),
-            error = "Too many images selected (max 50)",
+            error = %e,
             "Validation failed"
         );
-        return Err("Too many images selected (max 50)".to_string());
+        return Err(e.to_string());
     }
 
     // Validate each image path
@@ -672,52 +1316,23 @@ pub async fn upload_images(
             "Validating image path"
         );
 
-        if path.is_empty() {
-            log_error!(
-                operation = "upload_images_command",
-                path_index = index,
-                error = "Image path cannot be empty",
-                "Path validation failed"
-            );
-            return Err("Image path cannot be empty".to_string());
-        }
-
-        // Security check: prevent path traversal
-        if path.contains("..") || path.contains("~") {
+        if let Err(e) = validate_user_path_maybe_missing(path, PathKind::Image) {
             log_error!(
                 operation = "upload_images_command",
                 path_index = index,
                 path = %path,
-                error = "Invalid image path detected",
-                "Security validation failed"
-            );
-            return Err("Invalid image path detected".to_string());
-        }
-
-        let path_obj = Path::new(path);
-        if !path_obj.exists() {
-            log_error!(
-                operation = "upload_images_command",
-                path_index = index,
-                path = %path,
-                error = "Image file not found",
-                "File validation failed"
+                error = %e,
+                "Path validation failed"
             );
-            return Err(format!("Image file not found: {}", path));
+            return Err(e.to_string());
         }
 
-        if !path_obj.is_file() {
-            log_error!(
-                operation = "upload_images_command",
-                path_index = index,
-                path = %path,
-                error = "Path is not a file",
-                "File validation failed"
-            );
-            return Err(format!("Path is not a file: {}", path));
-        }
+        // Missing files fail their own item during upload instead of the whole call
     }
 
+    let (config, config_id) =
+        upload_command_config(config, config_id, window_session, &UploadMode::ImageUpload).await?;
+
     // Log OSS configuration details (without sensitive data)
     log_info!(
         operation = "upload_images_command",
@@ -747,7 +1362,10 @@ pub async fn upload_images(
         "Creating OSS service with validated configuration"
     );
 
-    let oss_service = OSSService::new(config).map_err(|e| {
+    let upload_target = UploadTarget::new(config_id.clone(), &config);
+    let processing = ImageProcessingOptions::from_config(&config);
+    let path_template = config.path_template.clone();
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| {
         log_error!(
             operation = "upload_images_command",
             error = %e,
@@ -761,13 +1379,32 @@ pub async fn upload_images(
         "OSS service created successfully"
     );
 
-    let image_service = ImageService::new();
+    let image_service = configured_image_service().await;
+
+    let items: Vec<(String, String)> = image_paths
+        .into_iter()
+        .map(|image_path| (uuid::Uuid::new_v4().to_string(), image_path))
+        .collect();
+    let session_id = upload_session(session_id);
+    enqueue_uploads(
+        items
+            .iter()
+            .map(|(image_id, image_path)| {
+                pending_upload(
+                    image_id,
+                    image_path,
+                    config_id.clone(),
+                    &session_id,
+                    None,
+                    UploadMode::ImageUpload,
+                )
+            })
+            .collect(),
+    );
 
     let mut results = Vec::new();
 
-    for image_path in image_paths {
-        let image_id = uuid::Uuid::new_v4().to_string();
-
+    for (image_id, image_path) in items {
         log_debug!(
             operation = "upload_images_command",
             image_path = %image_path,
@@ -775,6 +1412,26 @@ pub async fn upload_images(
             "Processing image for upload"
         );
 
+        if let Some(error) = local_file_error(&image_path) {
+            log_warn!(
+                operation = "upload_images_command",
+                image_path = %image_path,
+                error = %error,
+                "Skipping missing image"
+            );
+            PENDING_UPLOADS.finish(&image_id);
+            results.push(UploadResult {
+                image_id,
+                success: false,
+                uploaded_url: None,
+                error: Some(error),
+                variants: Vec::new(),
+                queued_offline: false,
+                warnings: Vec::new(),
+            });
+            continue;
+        }
+
         // Generate progress callback
         let progress_callback = {
             let image_id_clone = image_id.clone();
@@ -788,11 +1445,23 @@ pub async fn upload_images(
             &image_service,
             &image_path,
             &image_id,
+            &processing,
+            &path_template,
+            &upload_target,
             Some(Box::new(progress_callback)),
         )
         .await
         {
-            Ok((url, checksum)) => {
+            Ok(UploadedImage {
+                url,
+                key,
+                checksum,
+                size: uploaded_size,
+                variants,
+                request_id,
+                archived_original,
+                cdn_warning,
+            }) => {
                 log_info!(
                     operation = "upload_images_command",
                     image_path = %image_path,
@@ -802,50 +1471,50 @@ pub async fn upload_images(
                     "Image uploaded successfully"
                 );
 
+                // Store in upload history
+                let image_name = std::path::Path::new(&image_path)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string();
+
+                let record_id = uuid::Uuid::new_v4().to_string();
+                let original_archived =
+                    link_archived_original(&record_id, archived_original.as_deref()).await;
+                let history_record = UploadHistoryRecord {
+                    id: record_id,
+                    timestamp: chrono::Utc::now(),
+                    image_name,
+                    uploaded_url: url.clone(),
+                    upload_mode: UploadMode::ImageUpload,
+                    source_file: None,
+                    file_size: uploaded_size,
+                    checksum,
+                    variants: variants.clone(),
+                    config_id: upload_target.config_id.clone(),
+                    bucket: upload_target.bucket.clone(),
+                    original_path: Some(image_path.clone()),
+                    success: true,
+                    error_message: None,
+                    request_id,
+                    original_archived,
+                    object_key: Some(key),
+                };
+                let history_warning = record_upload(history_record).await;
+
                 results.push(UploadResult {
                     image_id: image_id.clone(),
                     success: true,
-                    uploaded_url: Some(url.clone()),
+                    uploaded_url: Some(url),
                     error: None,
+                    variants,
+                    queued_offline: false,
+                    warnings: cdn_warning.into_iter().chain(history_warning).collect(),
                 });
 
-                // Store in upload history
-                if let Ok(history_service) = HistoryService::new() {
-                    let image_name = std::path::Path::new(&image_path)
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-
-                    let history_record = UploadHistoryRecord {
-                        id: uuid::Uuid::new_v4().to_string(),
-                        timestamp: chrono::Utc::now(),
-                        image_name,
-                        uploaded_url: url,
-                        upload_mode: UploadMode::ImageUpload,
-                        source_file: None,
-                        file_size: std::fs::metadata(&image_path).map(|m| m.len()).unwrap_or(0),
-                        checksum,
-                    };
-
-                    let _ = history_service.add_upload_record(history_record).await;
-                }
-
-                // Send final completion progress before cleanup
-                let final_progress = crate::models::UploadProgress {
-                    image_id: image_id.clone(),
-                    progress: 100.0,
-                    bytes_uploaded: std::fs::metadata(&image_path).map(|m| m.len()).unwrap_or(0),
-                    total_bytes: std::fs::metadata(&image_path).map(|m| m.len()).unwrap_or(0),
-                    speed: None,
-                };
-                let _ = PROGRESS_NOTIFIER.update_progress(image_id.clone(), final_progress);
-
-                // Small delay to ensure frontend receives the completion event
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-                // Remove progress tracking for completed upload
-                let _ = PROGRESS_NOTIFIER.remove_progress(&image_id);
+                // Keep a terminal entry so late polls still see the outcome
+                let _ =
+                    PROGRESS_NOTIFIER.complete_progress(&image_id, UploadStatus::Completed, None);
             }
             Err(e) => {
                 log_error!(
@@ -861,25 +1530,23 @@ pub async fn upload_images(
                     success: false,
                     uploaded_url: None,
                     error: Some(e.to_string()),
+                    variants: Vec::new(),
+                    queued_offline: false,
+                    warnings: Vec::new(),
                 });
 
                 // Note: We only record successful uploads in the new design
                 // Failed uploads are not stored in history
 
-                // Send final progress for failed upload (progress remains as is, but ensure UI gets final state)
-                if let Ok(Some(mut progress)) = PROGRESS_NOTIFIER.get_progress(&image_id) {
-                    // Mark as completed with error (UI can distinguish by checking results)
-                    progress.progress = 100.0;
-                    let _ = PROGRESS_NOTIFIER.update_progress(image_id.clone(), progress);
-
-                    // Small delay to ensure frontend receives the completion event
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                }
-
-                // Remove progress tracking for failed upload
-                let _ = PROGRESS_NOTIFIER.remove_progress(&image_id);
+                let _ = PROGRESS_NOTIFIER.complete_progress(
+                    &image_id,
+                    UploadStatus::Failed,
+                    Some(e.to_string()),
+                );
             }
         }
+
+        PENDING_UPLOADS.finish(&image_id);
     }
 
     log_info!(
@@ -890,17 +1557,451 @@ pub async fn upload_images(
         "Upload images command completed"
     );
 
+    notify_batch_finished(BatchSummary::from_results(&results, started)).await;
     Ok(results)
 }
 
-/// Helper function to upload a single image
+/// Run `task` over `items` with at most `limit` running at once, outputs in
+/// input order
+async fn run_bounded<T, F, Fut>(
+    items: Vec<T>,
+    limit: usize,
+    task: F,
+) -> Vec<Result<Fut::Output, tokio::task::JoinError>>
+where
+    F: Fn(T) -> Fut,
+    Fut: std::future::Future + Send + 'static,
+    Fut::Output: Send + 'static,
+{
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(limit.max(1)));
+    let handles: Vec<_> = items
+        .into_iter()
+        .map(|item| {
+            let semaphore = semaphore.clone();
+            let future = task(item);
+            tokio::spawn(async move {
+                // The semaphore is never closed, the permit is always granted
+                let _permit = semaphore.acquire_owned().await;
+                future.await
+            })
+        })
+        .collect();
+
+    let mut outputs = Vec::with_capacity(handles.len());
+    for handle in handles {
+        outputs.push(handle.await);
+    }
+    outputs
+}
+
+/// Missing or non-file paths fail their own upload item instead of the whole batch
+fn local_file_error(image_path: &str) -> Option<String> {
+    let path = Path::new(image_path);
+    if !path.exists() {
+        Some(format!("Image file not found: {}", image_path))
+    } else if !path.is_file() {
+        Some(format!("Path is not a file: {}", image_path))
+    } else {
+        None
+    }
+}
+
+/// Upload one image with progress tracking and a history record; the shared
+/// per-item body of batch and pipeline uploads
+#[allow(clippy::too_many_arguments)]
+async fn upload_tracked_image(
+    oss_service: &OSSService,
+    image_service: &ImageService,
+    image_path: &str,
+    image_id: &str,
+    processing: &ImageProcessingOptions,
+    path_template: &str,
+    history_mode: UploadMode,
+    source_file: Option<String>,
+    target: UploadTarget,
+) -> UploadResult {
+    if let Some(error) = local_file_error(image_path) {
+        PENDING_UPLOADS.finish(image_id);
+        let _ = PROGRESS_NOTIFIER.complete_progress(
+            image_id,
+            UploadStatus::Failed,
+            Some(error.clone()),
+        );
+        return UploadResult {
+            image_id: image_id.to_string(),
+            success: false,
+            uploaded_url: None,
+            error: Some(error),
+            variants: Vec::new(),
+            queued_offline: false,
+            warnings: Vec::new(),
+        };
+    }
+    if !CONNECTIVITY.is_online() {
+        if let Some(queued) = queue_offline(image_id, &target) {
+            return queued;
+        }
+    }
+
+    // Create progress callback
+    let progress_callback = {
+        let image_id_for_callback = image_id.to_string();
+        move |progress: UploadProgress| {
+            let _ = PROGRESS_NOTIFIER.update_progress(image_id_for_callback.clone(), progress);
+        }
+    };
+
+    let result = upload_single_image(
+        oss_service,
+        image_service,
+        image_path,
+        image_id,
+        processing,
+        path_template,
+        &target,
+        Some(Box::new(progress_callback)),
+    )
+    .await;
+
+    let upload_result = match result {
+        Ok(UploadedImage {
+            url,
+            key,
+            checksum,
+            size: uploaded_size,
+            variants,
+            request_id,
+            archived_original,
+            cdn_warning,
+        }) => {
+            // Store in upload history
+            let image_name = std::path::Path::new(image_path)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string();
+
+            let record_id = uuid::Uuid::new_v4().to_string();
+            let original_archived =
+                link_archived_original(&record_id, archived_original.as_deref()).await;
+            let history_record = UploadHistoryRecord {
+                id: record_id,
+                timestamp: chrono::Utc::now(),
+                image_name,
+                uploaded_url: url.clone(),
+                upload_mode: history_mode,
+                source_file,
+                file_size: uploaded_size,
+                checksum,
+                variants: variants.clone(),
+                config_id: target.config_id,
+                bucket: target.bucket,
+                original_path: Some(image_path.to_string()),
+                success: true,
+                error_message: None,
+                request_id,
+                original_archived,
+                object_key: Some(key),
+            };
+            let history_warning = record_upload(history_record).await;
+
+            UploadResult {
+                image_id: image_id.to_string(),
+                success: true,
+                uploaded_url: Some(url),
+                error: None,
+                variants,
+                queued_offline: false,
+                warnings: cdn_warning.into_iter().chain(history_warning).collect(),
+            }
+        }
+        Err(e) => {
+            if is_offline_error(&e) {
+                CONNECTIVITY.set_online(false);
+                if let Some(queued) = queue_offline(image_id, &target) {
+                    return queued;
+                }
+            }
+            // Note: We only record successful uploads in the new design
+            // Failed uploads are not stored in history
+
+            UploadResult {
+                image_id: image_id.to_string(),
+                success: false,
+                uploaded_url: None,
+                error: Some(e.to_string()),
+                variants: Vec::new(),
+                queued_offline: false,
+                warnings: Vec::new(),
+            }
+        }
+    };
+
+    let (status, error) = if upload_result.success {
+        (UploadStatus::Completed, None)
+    } else {
+        (UploadStatus::Failed, upload_result.error.clone())
+    };
+    let _ = PROGRESS_NOTIFIER.complete_progress(image_id, status, error);
+    PENDING_UPLOADS.finish(image_id);
+
+    upload_result
+}
+
+/// Park an upload in the offline queue instead of failing it. Only uploads to
+/// a saved config can be queued, the flush needs the config to resume them.
+fn queue_offline(image_id: &str, target: &UploadTarget) -> Option<UploadResult> {
+    if target.config_id.is_none() || !PENDING_UPLOADS.park_offline(image_id) {
+        return None;
+    }
+    let _ = PROGRESS_NOTIFIER.remove_progress(image_id);
+    log_info!(
+        operation = "offline_queue",
+        image_id = image_id,
+        "Upload queued until the connection returns"
+    );
+    Some(UploadResult {
+        image_id: image_id.to_string(),
+        success: false,
+        uploaded_url: None,
+        error: Some("No connection, queued until it returns".to_string()),
+        variants: Vec::new(),
+        queued_offline: true,
+        warnings: Vec::new(),
+    })
+}
+
+/// Id of the saved config matching `config`, so a recovered upload can be
+/// resumed against it; None for configs that were never saved
+async fn saved_config_id(config: &OSSConfig) -> Option<String> {
+    let collection = ConfigService::new().ok()?.load_all_configs().await.ok()?;
+    collection
+        .configs
+        .into_iter()
+        .find(|item| {
+            item.config.endpoint == config.endpoint
+                && item.config.bucket == config.bucket
+                && item.config.access_key_id == config.access_key_id
+        })
+        .map(|item| item.id)
+}
+
+/// Saved config an upload runs with: `config_id`, else the config set for the
+/// calling window, else as ConfigService::resolve_upload_config picks it
+async fn saved_upload_config(
+    config_id: Option<&str>,
+    window_session: Option<&str>,
+    mode: &UploadMode,
+) -> Result<ConfigItem, String> {
+    let config_id = config_id
+        .map(str::to_string)
+        .or_else(|| WINDOW_SESSIONS.default_config(window_session));
+    ConfigService::new()
+        .map_err(|e| e.to_string())?
+        .resolve_upload_config(config_id.as_deref(), mode)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Config and saved config id of a direct upload command: `config_id` picks a
+/// saved config, else the config the caller passed is used as is, else the
+/// window's config, the default for `mode` or the active config
+async fn upload_command_config(
+    config: Option<OSSConfig>,
+    config_id: Option<String>,
+    window_session: Option<String>,
+    mode: &UploadMode,
+) -> Result<(OSSConfig, Option<String>), String> {
+    match (config, config_id) {
+        (Some(config), None) => {
+            let config_id = saved_config_id(&config).await;
+            Ok((config, config_id))
+        }
+        (_, config_id) => {
+            let item =
+                saved_upload_config(config_id.as_deref(), window_session.as_deref(), mode).await?;
+            Ok((item.config, Some(item.id)))
+        }
+    }
+}
+
+fn pending_upload(
+    task_id: &str,
+    image_path: &str,
+    config_id: Option<String>,
+    session_id: &str,
+    source_file: Option<String>,
+    upload_mode: UploadMode,
+) -> PendingUpload {
+    PendingUpload {
+        task_id: task_id.to_string(),
+        image_path: image_path.to_string(),
+        config_id,
+        source_file,
+        upload_mode,
+        enqueued_at: chrono::Utc::now(),
+        queued_offline: false,
+        deferred_links: None,
+        session_id: Some(session_id.to_string()),
+    }
+}
+
+/// Session id of an upload command call, a new one when the caller passed none
+fn upload_session(session_id: Option<String>) -> String {
+    session_id
+        .filter(|id| !id.trim().is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Record uploads that are about to start, registering their session so the
+/// progress events of each carry it
+fn enqueue_uploads(tasks: Vec<PendingUpload>) {
+    for task in &tasks {
+        PROGRESS_NOTIFIER.assign_scope(&task.task_id, task.scope());
+    }
+    PENDING_UPLOADS.enqueue(tasks);
+}
+
+/// Upload mode recorded in history: explicit mode wins, otherwise uploads that
+/// name a source markdown file are article uploads
+fn history_upload_mode(upload_mode: Option<UploadMode>, source_file: Option<&str>) -> UploadMode {
+    upload_mode.unwrap_or(match source_file {
+        Some(_) => UploadMode::ArticleUpload,
+        None => UploadMode::ImageUpload,
+    })
+}
+
+/// Cached app settings, defaults when the settings can't be read
+async fn current_app_settings() -> AppSettings {
+    match ConfigService::new() {
+        Ok(config_service) => config_service.app_settings().await.unwrap_or_default(),
+        Err(_) => AppSettings::default(),
+    }
+}
+
+/// System notification for a finished upload batch, per the saved
+/// notification settings
+async fn notify_batch_finished(summary: BatchSummary) {
+    let config = current_app_settings().await.notifications;
+    BATCH_NOTIFIER.batch_finished(&config, &summary);
+}
+
+/// Image service using the checksum algorithm, size limit and originals
+/// archive from app settings
+async fn configured_image_service() -> ImageService {
+    let settings = current_app_settings().await;
+    let original_archive = if settings.archive_originals {
+        OriginalArchive::new()
+            .map_err(|e| {
+                log_warn!(
+                    operation = "configured_image_service",
+                    error = %e,
+                    "Originals archive unavailable, uploading without copies"
+                );
+            })
+            .ok()
+    } else {
+        None
+    };
+    ImageService::new()
+        .with_checksum_algorithm(settings.checksum_algorithm)
+        .with_max_image_size(settings.max_image_bytes())
+        .with_original_archive(original_archive)
+}
+
+/// Point the originals archive at a new history record, true when the
+/// record's original is archived
+async fn link_archived_original(record_id: &str, sha256: Option<&str>) -> bool {
+    let Some(sha256) = sha256 else {
+        return false;
+    };
+    let linked = match OriginalArchive::new() {
+        Ok(archive) => archive.link(record_id, sha256).await,
+        Err(e) => Err(e),
+    };
+    linked
+        .map_err(|e| {
+            log_warn!(
+                operation = "link_archived_original",
+                record_id = %record_id,
+                error = %e,
+                "Failed to index archived original"
+            );
+        })
+        .is_ok()
+}
+
+/// What upload_single_image stored for one source image
+struct UploadedImage {
+    url: String,
+    /// Object key of the full-size upload
+    key: String,
+    checksum: String,
+    /// Bytes of the full-size upload after processing
+    size: u64,
+    /// Size variants plus the full-size upload, empty without size_variants
+    variants: Vec<ImageVariant>,
+    /// Provider request ID of the full-size upload
+    request_id: Option<String>,
+    /// sha256 of the source in the originals archive, when it was archived
+    archived_original: Option<String>,
+    /// Set when the config's CDN domain doesn't serve the uploaded object
+    cdn_warning: Option<String>,
+}
+
+/// Object key of a size variant: `{stem}_{width}.{ext}`
+fn variant_key(key: &str, width: u32) -> String {
+    let (dir, name) = match key.rsplit_once('/') {
+        Some((dir, name)) => (Some(dir), name),
+        None => (None, key),
+    };
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}_{}.{}", stem, width, ext),
+        _ => format!("{}_{}", name, width),
+    };
+    match dir {
+        Some(dir) => format!("{}/{}", dir, name),
+        None => name,
+    }
+}
+
+/// Helper function to upload a single image; a panic while uploading fails
+/// only this image and leaves a crash report
+#[allow(clippy::too_many_arguments)]
 async fn upload_single_image(
+    oss_service: &OSSService,
+    image_service: &ImageService,
+    image_path: &str,
+    image_id: &str,
+    processing: &ImageProcessingOptions,
+    path_template: &str,
+    target: &UploadTarget,
+    progress_callback: Option<Box<dyn Fn(UploadProgress) + Send + Sync>>,
+) -> Result<UploadedImage, AppError> {
+    let upload = upload_image_file(
+        oss_service,
+        image_service,
+        image_path,
+        image_id,
+        processing,
+        path_template,
+        target,
+        progress_callback,
+    );
+    catch_panic_async("upload_image", upload).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_image_file(
     oss_service: &OSSService,
     image_service: &ImageService,
     image_path: &str,
     _image_id: &str,
+    processing: &ImageProcessingOptions,
+    path_template: &str,
+    target: &UploadTarget,
     progress_callback: Option<Box<dyn Fn(UploadProgress) + Send + Sync>>,
-) -> Result<(String, String), AppError> {
+) -> Result<UploadedImage, AppError> {
     use std::fs;
     use std::path::Path;
 
@@ -910,6 +2011,9 @@ async fn upload_single_image(
         "Starting single image upload process"
     );
 
+    // Oversized files are rejected from their metadata, before anything is read
+    check_upload_size(oss_service, image_service, processing, image_path)?;
+
     // Calculate checksum first
     log_debug!(
         operation = "upload_single_image",
@@ -943,7 +2047,7 @@ async fn upload_single_image(
         "Image file read successfully"
     );
 
-    // Generate object key based on file name and timestamp
+    // File name the object key is rendered from
     let file_name = Path::new(image_path)
         .file_name()
         .and_then(|name| name.to_str())
@@ -956,8 +2060,102 @@ async fn upload_single_image(
             AppError::FileSystem("Invalid file name".to_string())
         })?;
 
-    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S").to_string();
-    let key = format!("images/{}_{}", timestamp, file_name);
+    // EXIF is read before processing, re-encoding drops it
+    let exif = read_exif(&mut std::io::Cursor::new(&image_data));
+
+    // Processing may change what gets uploaded, the source is kept first
+    let archived_original = if processing.keeps_size() {
+        None
+    } else {
+        image_service.archive_original(&image_data).await
+    };
+
+    // Run the configured processing pipeline (shared with preview_image_processing)
+    let processed = image_service
+        .process_image(image_data, processing)
+        .await
+        .map_err(|e| {
+            log_error!(
+                operation = "upload_single_image",
+                image_path = %image_path,
+                error = %e,
+                "Image processing failed"
+            );
+            e
+        })?;
+    if let Some(warning) = gps_retention_warning(exif.has_gps, processed.reencoded) {
+        log_warn!(
+            operation = "upload_single_image",
+            image_path = %image_path,
+            warning = %warning,
+            "GPS metadata will be uploaded with the image"
+        );
+    }
+    let mut uploaded_width = processed.width;
+    let mut reencoded = processed.reencoded;
+    let image_data = match processing.max_upload_size_kb {
+        Some(max_kb) => {
+            let target = image_service
+                .compress_to_target(processed.data, max_kb * 1024)
+                .await?;
+            if let Some(warning) = &target.warning {
+                log_warn!(
+                    operation = "upload_single_image",
+                    image_path = %image_path,
+                    warning = %warning,
+                    "Image not brought under the size limit"
+                );
+            }
+            log_debug!(
+                operation = "upload_single_image",
+                original_size = target.original_size,
+                final_size = target.final_size,
+                target_met = target.target_met,
+                quality = ?target.quality,
+                width = target.width,
+                height = target.height,
+                passes = target.passes,
+                "Compress-to-target applied"
+            );
+            uploaded_width = target.width;
+            reencoded |= target.passes > 0;
+            target.data
+        }
+        None => processed.data,
+    };
+
+    // Format conversion changes the extension of the uploaded object
+    let file_name = match &processed.extension {
+        Some(ext) if processed.format != processed.original_format => {
+            let stem = Path::new(file_name)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(file_name);
+            format!("{}.{}", stem, ext)
+        }
+        _ => file_name.to_string(),
+    };
+
+    // {hash} names the bytes being uploaded. The source checksum only does
+    // while processing kept the original bytes.
+    let content_hash = if !uses_content_hash(path_template) {
+        None
+    } else if reencoded {
+        Some(
+            image_service
+                .calculate_checksum_from_data(&image_data)
+                .await?,
+        )
+    } else {
+        Some(checksum.clone())
+    };
+    let mut key_context = KeyTemplateContext::new(&file_name).with_taken_at(exif.taken_at);
+    if let Some(hash) = &content_hash {
+        key_context = key_context.with_hash(hash);
+    }
+    let path_template = upload_template(path_template);
+    let key = render_object_key(path_template, &key_context);
+    let content_addressed = is_content_addressed(path_template);
 
     log_info!(
         operation = "upload_single_image",
@@ -968,20 +2166,50 @@ async fn upload_single_image(
         "Preparing to upload to OSS"
     );
 
-    // Upload to OSS
-    let url = oss_service
-        .upload_image(&key, &image_data, progress_callback)
-        .await
-        .map_err(|e| {
-            log_error!(
+    // Processing may not have brought it under the provider limit
+    oss_service.check_object_size(image_data.len() as u64)?;
+
+    let existing = if content_addressed {
+        existing_object(oss_service, &key).await
+    } else {
+        None
+    };
+    let uploaded = match existing {
+        Some(existing) => {
+            log_info!(
                 operation = "upload_single_image",
                 image_path = %image_path,
                 object_key = %key,
-                error = %e,
-                "OSS upload failed"
+                "Content-addressed object already in the bucket, upload skipped"
             );
-            e
-        })?;
+            existing
+        }
+        None => {
+            // Upload to OSS
+            let upload_started = Instant::now();
+            let uploaded = oss_service
+                .upload_image(&key, &image_data, progress_callback)
+                .await;
+            record_upload_metric(
+                oss_service,
+                target,
+                image_data.len() as u64,
+                upload_started,
+                uploaded.is_ok(),
+            );
+            uploaded.map_err(|e| {
+                log_error!(
+                    operation = "upload_single_image",
+                    image_path = %image_path,
+                    object_key = %key,
+                    error = %e,
+                    "OSS upload failed"
+                );
+                e
+            })?
+        }
+    };
+    let url = uploaded.url;
 
     log_info!(
         operation = "upload_single_image",
@@ -991,7 +2219,159 @@ async fn upload_single_image(
         "Image uploaded successfully"
     );
 
-    Ok((url, checksum))
+    let variants = if processing.size_variants.is_empty() {
+        Vec::new()
+    } else {
+        upload_size_variants(
+            oss_service,
+            image_service,
+            &image_data,
+            &key,
+            processing,
+            content_addressed,
+            ImageVariant {
+                width: uploaded_width,
+                url: url.clone(),
+                bytes: image_data.len() as u64,
+            },
+        )
+        .await?
+    };
+
+    let cdn_warning = CDN_CHECKS.check_upload(oss_service, &key).await;
+
+    Ok(UploadedImage {
+        url,
+        key,
+        checksum,
+        size: image_data.len() as u64,
+        variants,
+        request_id: uploaded.request_id,
+        archived_original,
+        cdn_warning,
+    })
+}
+
+/// The app's size limit, and the provider's limit for a single upload when
+/// processing can't shrink the file below it
+fn check_upload_size(
+    oss_service: &OSSService,
+    image_service: &ImageService,
+    processing: &ImageProcessingOptions,
+    image_path: &str,
+) -> Result<u64, AppError> {
+    let size = image_service.check_image_size(image_path)?;
+    if processing.keeps_size() {
+        oss_service.check_object_size(size)?;
+    }
+    Ok(size)
+}
+
+/// Append the timing of one provider upload to the metrics log; a log that
+/// can't be written never fails the upload
+fn record_upload_metric(
+    oss_service: &OSSService,
+    target: &UploadTarget,
+    bytes: u64,
+    started: Instant,
+    success: bool,
+) {
+    let metric = UploadMetric {
+        timestamp: chrono::Utc::now(),
+        provider: oss_service.kind().clone(),
+        config_id: target.config_id.clone(),
+        bytes,
+        duration_ms: started.elapsed().as_millis() as u64,
+        success,
+    };
+    if let Err(e) = MetricsService::new().and_then(|service| service.record(&metric)) {
+        log_warn!(
+            operation = "record_upload_metric",
+            error = %e,
+            "Failed to record upload metric"
+        );
+    }
+}
+
+/// The object at a content-addressed `key`, None when it has to be uploaded.
+/// Any HEAD failure counts as missing, the upload then reports the real error.
+async fn existing_object(oss_service: &OSSService, key: &str) -> Option<UploadedObject> {
+    match oss_service.get_object_metadata(key).await {
+        Ok(_) => Some(UploadedObject {
+            url: oss_service.object_url(key),
+            request_id: None,
+        }),
+        Err(e) => {
+            log_debug!(
+                operation = "existing_object",
+                object_key = %key,
+                error = %e,
+                "Content-addressed object not found, uploading"
+            );
+            None
+        }
+    }
+}
+
+/// Upload the configured size variants next to `original`, returned with it as the last entry.
+/// Variants of a content-addressed key are content-addressed too and skipped when present.
+async fn upload_size_variants(
+    oss_service: &OSSService,
+    image_service: &ImageService,
+    image_data: &[u8],
+    key: &str,
+    processing: &ImageProcessingOptions,
+    content_addressed: bool,
+    original: ImageVariant,
+) -> Result<Vec<ImageVariant>, AppError> {
+    let quality = if processing.compression_enabled {
+        processing.quality.clamp(1, 100)
+    } else {
+        85
+    };
+    let resized = image_service
+        .generate_size_variants(image_data, &processing.size_variants, quality)
+        .await?;
+
+    let mut variants = Vec::with_capacity(resized.len() + 1);
+    for (width, data) in resized {
+        let variant_key = variant_key(key, width);
+        let existing = if content_addressed {
+            existing_object(oss_service, &variant_key).await
+        } else {
+            None
+        };
+        let uploaded = match existing {
+            Some(existing) => existing,
+            None => oss_service
+                .upload_image(&variant_key, &data, None)
+                .await
+                .map_err(|e| {
+                    log_error!(
+                        operation = "upload_size_variants",
+                        object_key = %variant_key,
+                        error = %e,
+                        "Size variant upload failed"
+                    );
+                    e
+                })?,
+        };
+        variants.push(ImageVariant {
+            width,
+            url: uploaded.url,
+            bytes: data.len() as u64,
+        });
+    }
+    variants.push(original);
+
+    log_info!(
+        operation = "upload_size_variants",
+        object_key = %key,
+        variant_count = variants.len() - 1,
+        "Size variants uploaded"
+    );
+
+    Ok(variants)
 }
 
 #[tauri::command]
@@ -1051,6 +2431,11 @@ pub async fn retry_upload(task_id: String) -> Result<(), String> {
         bytes_uploaded: 0,
         total_bytes: 0,
         speed: None,
+        eta_secs: None,
+        status: UploadStatus::Pending,
+        error: None,
+        session_id: None,
+        config_id: None,
     };
 
     PROGRESS_NOTIFIER
@@ -1063,10 +2448,19 @@ pub async fn retry_upload(task_id: String) -> Result<(), String> {
 
 #[tauri::command]
 pub async fn upload_images_batch(
-    image_paths: Vec<String>,
-    config: OSSConfig,
-    batch_size: Option<usize>,
+    image_paths: Vec<BatchUploadItem>,
+    options: Option<BatchUploadOptions>,
+    window_session: Option<String>,
 ) -> Result<Vec<UploadResult>, String> {
+    let BatchUploadOptions {
+        config,
+        config_id,
+        batch_size,
+        source_file,
+        upload_mode,
+        session_id,
+    } = options.unwrap_or_default();
+    let started = Instant::now();
     // Rate limiting
     UPLOAD_RATE_LIMITER
         .check_rate_limit("upload_images_batch")
@@ -1077,159 +2471,232 @@ pub async fn upload_images_batch(
         return Err("Image paths cannot be empty".to_string());
     }
 
-    if image_paths.len() > 100 {
-        return Err("Too many images selected (max 100)".to_string());
-    }
+    // Selections above max_upload_images are uploaded chunk by chunk, not rejected
+    let settings = current_app_settings().await;
+    let chunk_size = settings.max_upload_images.max(1);
 
-    let batch_size = batch_size.unwrap_or(5).min(10); // Max 10 concurrent uploads
+    let batch_size = batch_size.unwrap_or(5).clamp(1, 10); // Max 10 concurrent uploads
 
     // Validate each image path
-    for path in &image_paths {
-        if path.is_empty() {
-            return Err("Image path cannot be empty".to_string());
-        }
-
-        // Security check: prevent path traversal
-        if path.contains("..") || path.contains("~") {
-            return Err("Invalid image path detected".to_string());
-        }
-
-        let path_obj = Path::new(path);
-        if !path_obj.exists() {
-            return Err(format!("Image file not found: {}", path));
+    for item in &image_paths {
+        if item.file_id().is_some_and(|id| id.is_empty()) {
+            return Err("File ID cannot be empty".to_string());
         }
 
-        if !path_obj.is_file() {
-            return Err(format!("Path is not a file: {}", path));
-        }
+        validate_user_path_maybe_missing(item.path(), PathKind::Image)
+            .map_err(|e| e.to_string())?;
     }
 
+    let history_mode = history_upload_mode(upload_mode, source_file.as_deref());
+    let (config, config_id) =
+        upload_command_config(config, config_id, window_session, &history_mode).await?;
     validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+    let upload_target = UploadTarget::new(config_id.clone(), &config);
+
+    // One service (and HTTP client) for the whole batch instead of one per image
+    let processing = ImageProcessingOptions::from_config(&config);
+    let path_template = config.path_template.clone();
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    let image_service = configured_image_service().await;
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(batch_size));
+    let session_id = upload_session(session_id);
+
+    let mut results = Vec::with_capacity(image_paths.len());
+    let mut skipped_oversized = Vec::new();
+    for chunk in image_paths.chunks(chunk_size) {
+        let items: Vec<(String, String)> = chunk
+            .iter()
+            .map(|item| {
+                let image_id = item
+                    .file_id()
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                (image_id, item.path().to_string())
+            })
+            .collect();
+        enqueue_uploads(
+            items
+                .iter()
+                .map(|(image_id, image_path)| {
+                    pending_upload(
+                        image_id,
+                        image_path,
+                        config_id.clone(),
+                        &session_id,
+                        source_file.clone(),
+                        history_mode.clone(),
+                    )
+                })
+                .collect(),
+        );
 
-    let mut results = Vec::new();
-
-    // Process images in batches
-    for batch in image_paths.chunks(batch_size) {
-        let mut batch_tasks = Vec::new();
+        let mut tasks = Vec::with_capacity(items.len());
+
+        for (image_id, image_path) in items {
+            // Skip oversized files up front so they never take an upload slot
+            if let Err(AppError::FileTooLarge(error)) =
+                check_upload_size(&oss_service, &image_service, &processing, &image_path)
+            {
+                let error = AppError::FileTooLarge(error).to_string();
+                let _ = PROGRESS_NOTIFIER.complete_progress(
+                    &image_id,
+                    UploadStatus::Failed,
+                    Some(error.clone()),
+                );
+                PENDING_UPLOADS.finish(&image_id);
+                skipped_oversized.push(image_path);
+                tasks.push((image_id, Err(error)));
+                continue;
+            }
 
-        for image_path in batch {
-            let image_id = uuid::Uuid::new_v4().to_string();
-            let config_clone = config.clone();
-            let image_path_clone = image_path.clone();
-            let image_id_clone = image_id.clone();
+            let oss_service = oss_service.clone();
+            let image_service = image_service.clone();
+            let processing = processing.clone();
+            let path_template = path_template.clone();
+            let semaphore = semaphore.clone();
+            let task_image_id = image_id.clone();
+            let history_mode = history_mode.clone();
+            let source_file = source_file.clone();
+            let upload_target = upload_target.clone();
 
             let task = tokio::spawn(async move {
-                // Create services inside the task to avoid lifetime issues
-                let oss_service = match OSSService::new(config_clone) {
-                    Ok(service) => service,
+                let image_id = task_image_id;
+                let _permit = match semaphore.acquire().await {
+                    Ok(permit) => permit,
                     Err(e) => {
                         return UploadResult {
-                            image_id: image_id_clone,
+                            image_id,
                             success: false,
                             uploaded_url: None,
                             error: Some(e.to_string()),
+                            variants: Vec::new(),
+                            queued_offline: false,
+                            warnings: Vec::new(),
                         };
                     }
                 };
-                let image_service = ImageService::new();
-
-                // Create progress callback
-                let progress_callback = {
-                    let image_id_for_callback = image_id_clone.clone();
-                    move |progress: UploadProgress| {
-                        let _ = PROGRESS_NOTIFIER
-                            .update_progress(image_id_for_callback.clone(), progress);
-                    }
-                };
 
-                let result = upload_single_image(
+                upload_tracked_image(
                     &oss_service,
                     &image_service,
-                    &image_path_clone,
-                    &image_id_clone,
-                    Some(Box::new(progress_callback)),
+                    &image_path,
+                    &image_id,
+                    &processing,
+                    &path_template,
+                    history_mode,
+                    source_file,
+                    upload_target,
                 )
-                .await;
+                .await
+            });
 
-                let upload_result = match result {
-                    Ok((url, checksum)) => {
-                        // Store in upload history
-                        if let Ok(history_service) = HistoryService::new() {
-                            let image_name = std::path::Path::new(&image_path_clone)
-                                .file_name()
-                                .and_then(|n| n.to_str())
-                                .unwrap_or("unknown")
-                                .to_string();
-
-                            let history_record = UploadHistoryRecord {
-                                id: uuid::Uuid::new_v4().to_string(),
-                                timestamp: chrono::Utc::now(),
-                                image_name,
-                                uploaded_url: url.clone(),
-                                upload_mode: UploadMode::ImageUpload,
-                                source_file: None,
-                                file_size: std::fs::metadata(&image_path_clone)
-                                    .map(|m| m.len())
-                                    .unwrap_or(0),
-                                checksum,
-                            };
-
-                            let _ = history_service.add_upload_record(history_record).await;
-                        }
+            tasks.push((image_id, Ok(task)));
+        }
 
-                        UploadResult {
-                            image_id: image_id_clone.clone(),
-                            success: true,
-                            uploaded_url: Some(url),
-                            error: None,
-                        }
+        // Await in submission order so results line up with the input
+        for (image_id, task) in tasks {
+            let error = match task {
+                Ok(task) => match task.await {
+                    Ok(result) => {
+                        results.push(result);
+                        continue;
                     }
-                    Err(e) => {
-                        // Note: We only record successful uploads in the new design
-                        // Failed uploads are not stored in history
+                    Err(e) => format!("Task join error: {}", e),
+                },
+                Err(error) => error,
+            };
+            results.push(UploadResult {
+                image_id,
+                success: false,
+                uploaded_url: None,
+                error: Some(error),
+                variants: Vec::new(),
+                queued_offline: false,
+                warnings: Vec::new(),
+            });
+        }
+    }
 
-                        UploadResult {
-                            image_id: image_id_clone.clone(),
-                            success: false,
-                            uploaded_url: None,
-                            error: Some(e.to_string()),
-                        }
-                    }
-                };
+    record_skipped_oversized("upload_images_batch", skipped_oversized);
 
-                // Remove progress tracking
-                let _ = PROGRESS_NOTIFIER.remove_progress(&image_id_clone);
+    PROGRESS_NOTIFIER.end_session(&session_id);
+    notify_batch_finished(BatchSummary::from_results(&results, started).in_session(&session_id))
+        .await;
+    Ok(results)
+}
 
-                upload_result
-            });
+/// Remember the files the last batch skipped for being oversized, so the health
+/// check can surface them, and log them as a warning
+fn record_skipped_oversized(operation: &str, skipped: Vec<String>) {
+    if !skipped.is_empty() {
+        log_warn!(
+            operation = %operation,
+            skipped_count = skipped.len(),
+            files = %skipped.join(", "),
+            "Skipped oversized images"
+        );
+    }
 
-            batch_tasks.push(task);
-        }
+    if let Ok(mut last) = LAST_SKIPPED_OVERSIZED.lock() {
+        *last = skipped;
+    }
+}
 
-        // Wait for batch to complete
-        for task in batch_tasks {
-            match task.await {
-                Ok(result) => results.push(result),
-                Err(e) => {
-                    results.push(UploadResult {
-                        image_id: "unknown".to_string(),
-                        success: false,
-                        uploaded_url: None,
-                        error: Some(format!("Task join error: {}", e)),
-                    });
-                }
-            }
-        }
+// Health check line for the files skipped by the last batch, None when there were none
+fn skipped_oversized_message() -> Option<String> {
+    oversized_summary(&LAST_SKIPPED_OVERSIZED.lock().ok()?)
+}
+
+fn oversized_summary(skipped: &[String]) -> Option<String> {
+    if skipped.is_empty() {
+        return None;
     }
 
-    Ok(results)
+    Some(format!(
+        "Last batch skipped {} oversized image(s): {}",
+        skipped.len(),
+        listed_files(skipped)
+    ))
+}
+
+// Health check line for local originals changed since their upload, None when
+// the last full integrity check found none
+fn modified_originals_message() -> Option<String> {
+    let modified = LAST_MODIFIED_ORIGINALS.lock().ok()?;
+    if modified.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "{} uploaded image(s) were modified locally since their upload, re-uploading them would change published articles: {}",
+        modified.len(),
+        listed_files(&modified)
+    ))
+}
+
+// The first few `files`, then how many more there are
+fn listed_files(files: &[String]) -> String {
+    const LISTED_FILES: usize = 5;
+
+    let mut listed = files[..files.len().min(LISTED_FILES)].join(", ");
+    if files.len() > LISTED_FILES {
+        listed.push_str(&format!(" and {} more", files.len() - LISTED_FILES));
+    }
+    listed
 }
 
+/// Progress of every tracked upload, or of the sessions `scope` matches
 #[tauri::command]
-pub async fn get_all_upload_progress() -> Result<Vec<UploadProgress>, String> {
-    PROGRESS_NOTIFIER
+pub async fn get_all_upload_progress(
+    scope: Option<UploadScope>,
+) -> Result<Vec<UploadProgress>, String> {
+    let scope = scope.unwrap_or_default();
+    Ok(PROGRESS_NOTIFIER
         .get_all_progress()
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|progress| scope.matches(&progress.scope()))
+        .collect())
 }
 
 #[tauri::command]
@@ -1264,7 +2731,10 @@ pub async fn save_oss_config(
     // Clear cache if force revalidation is requested
     if let Some(opts) = &options {
         if opts.force_revalidate {
-            println!("🔄 Force revalidation requested, clearing cache for configuration");
+            log_info!(
+                operation = "save_oss_config",
+                "Force revalidation requested, clearing the cached connection test"
+            );
             config_service.clear_config_cache(&config);
         }
     }
@@ -1287,68 +2757,177 @@ pub async fn load_oss_config() -> Result<Option<OSSConfig>, String> {
 
 #[tauri::command]
 pub async fn test_oss_connection(config: OSSConfig) -> Result<OSSConnectionTest, String> {
-    println!("🔍 Starting OSS connection test...");
-    println!("📋 Config details:");
-    println!("   Provider: {:?}", config.provider);
-    println!("   Endpoint: {}", config.endpoint);
-    println!("   Bucket: {}", config.bucket);
-    println!("   Region: {}", config.region);
-    println!(
-        "   Access Key ID: {}***",
-        &config.access_key_id[..config.access_key_id.len().min(8)]
+    log_info!(
+        operation = "test_oss_connection",
+        provider = ?config.provider,
+        endpoint = %config.endpoint,
+        bucket = %config.bucket,
+        region = %config.region,
+        "Starting OSS connection test"
     );
+    let mut diagnostics = Vec::new();
 
     // Validate input parameters
-    println!("✅ Validating configuration parameters...");
     if let Err(e) = validate_oss_config_params(&config) {
-        println!("❌ Configuration validation failed: {}", e);
+        TEST_PROGRESS.report(
+            &mut diagnostics,
+            ConnectionTestStep::failed("config", e.to_string()),
+        );
         return Err(e.to_string());
     }
-    println!("✅ Configuration validation passed");
+    TEST_PROGRESS.report(&mut diagnostics, ConnectionTestStep::passed("config", None));
+
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| {
+        log_error!(
+            operation = "test_oss_connection",
+            error = %e,
+            "Failed to create OSS service"
+        );
+        e.to_string()
+    })?;
 
-    println!("🔧 Creating OSS service...");
-    let oss_service = match OSSService::new(config.clone()) {
-        Ok(service) => {
-            println!("✅ OSS service created successfully");
-            service
+    let mut result = match oss_service.test_connection().await {
+        Ok(result) => result,
+        // No response at all: report which stage failed instead of a bare error
+        Err(AppError::Network(e)) => {
+            let steps = request_failure_steps(&e);
+            log_warn!(
+                operation = "test_oss_connection",
+                error = %e,
+                "Connection test request got no response"
+            );
+            OSSConnectionTest {
+                success: false,
+                error: Some(AppError::Network(e).to_string()),
+                latency: None,
+                bucket_exists: None,
+                available_buckets: None,
+                diagnostics: steps,
+            }
         }
         Err(e) => {
-            println!("❌ Failed to create OSS service: {}", e);
+            log_error!(
+                operation = "test_oss_connection",
+                error = %e,
+                "Connection test failed"
+            );
             return Err(e.to_string());
         }
     };
 
-    println!("🌐 Testing connection...");
-    match oss_service.test_connection().await {
-        Ok(result) => {
-            println!("✅ Connection test completed");
-            println!("📊 Test result:");
-            println!("   Success: {}", result.success);
-            if let Some(latency) = result.latency {
-                println!("   Latency: {}ms", latency);
-            }
-            if let Some(ref error) = result.error {
-                println!("   Error: {}", error);
+    explain_auth_failure(&config, &mut result).await;
+
+    for step in std::mem::take(&mut result.diagnostics) {
+        TEST_PROGRESS.report(&mut diagnostics, step);
+    }
+    result.diagnostics = diagnostics;
+    log_info!(
+        operation = "test_oss_connection",
+        success = result.success,
+        latency_ms = ?result.latency,
+        error = ?result.error,
+        "OSS connection test completed"
+    );
+    Ok(result)
+}
+
+// A skewed clock fails the signature exactly like a wrong secret does
+async fn explain_auth_failure(config: &OSSConfig, result: &mut OSSConnectionTest) {
+    if result
+        .diagnostics
+        .iter()
+        .any(|step| step.step == "auth" && !step.ok)
+    {
+        let skew = connection_diagnostics::clock_skew(config).await;
+        result.diagnostics.push(clock_step(skew));
+        if let Some(finding) = skew.and_then(clock_skew_finding) {
+            result.error = Some(finding);
+        }
+    }
+}
+
+/// Dry run of the connection, layer by layer (DNS, TCP, TLS, server clock,
+/// signed request), to tell network and clock problems from bad credentials
+#[tauri::command]
+pub async fn diagnose_connection(config: OSSConfig) -> Result<ConnectionDiagnosis, String> {
+    let mut steps = Vec::new();
+
+    if let Err(e) = validate_oss_config_params(&config) {
+        TEST_PROGRESS.report(
+            &mut steps,
+            ConnectionTestStep::failed("config", e.to_string()),
+        );
+        return Err(e.to_string());
+    }
+    TEST_PROGRESS.report(&mut steps, ConnectionTestStep::passed("config", None));
+
+    let mut diagnosis = connection_diagnostics::diagnose_connection(&config).await;
+    for step in std::mem::take(&mut diagnosis.steps) {
+        TEST_PROGRESS.report(&mut steps, step);
+    }
+    diagnosis.steps = steps;
+    Ok(diagnosis)
+}
+
+/// Field problems come back together in `errors`; a CDN domain that doesn't
+/// serve the bucket's objects is reported in `warnings` once the connection works
+#[tauri::command]
+pub async fn validate_oss_config(config: OSSConfig) -> Result<ConfigValidation, String> {
+    check_config(&config).await.map_err(|e| e.to_string())
+}
+
+/// Provider rules, connection test and warnings of `config`. Shared by
+/// validate_oss_config and save_config_item so both judge a config alike.
+async fn check_config(config: &OSSConfig) -> Result<ConfigValidation, AppError> {
+    let mut validation = ConfigService::new()?.validate_config(config).await?;
+    // Stream the steps like test_oss_connection, field errors as a failed "config"
+    match validation.connection_test.as_mut() {
+        Some(test) => {
+            let mut diagnostics = Vec::new();
+            for step in std::mem::take(&mut test.diagnostics) {
+                TEST_PROGRESS.report(&mut diagnostics, step);
             }
-            Ok(result)
+            test.diagnostics = diagnostics;
         }
-        Err(e) => {
-            println!("❌ Connection test failed with error: {}", e);
-            Err(e.to_string())
+        None => TEST_PROGRESS.report(
+            &mut Vec::new(),
+            ConnectionTestStep::failed("config", validation.errors.join(", ")),
+        ),
+    }
+
+    let connected = validation
+        .connection_test
+        .as_ref()
+        .is_some_and(|test| test.success);
+    if validation.valid && connected {
+        if let Ok(oss_service) = OSS_SERVICES.get(config) {
+            if let Some(Some(warning)) = CDN_CHECKS.check_config(&oss_service, false).await {
+                validation.warnings.push(ConfigWarning::new(
+                    ConfigWarningCode::CdnNotServing,
+                    warning,
+                ));
+            }
         }
     }
+    Ok(validation)
 }
 
+/// Check a path template against the key rules of `provider` as it's typed,
+/// with the key a sample upload would get
 #[tauri::command]
-pub async fn validate_oss_config(config: OSSConfig) -> Result<ConfigValidation, String> {
-    // Basic parameter validation first
-    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+pub async fn validate_path_template(
+    template: String,
+    provider: OSSProvider,
+) -> Result<PathTemplateCheck, String> {
+    Ok(key_template::validate_path_template(&template, &provider))
+}
 
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
-    config_service
-        .validate_config(&config)
-        .await
-        .map_err(|e| e.to_string())
+/// What `provider` needs and supports, for tailoring the config form
+#[tauri::command]
+pub async fn get_provider_capabilities(
+    provider: OSSProvider,
+) -> Result<ProviderCapabilities, String> {
+    Ok(provider_capabilities(&provider))
 }
 
 #[tauri::command]
@@ -1369,377 +2948,2839 @@ pub async fn clear_connection_cache() -> Result<(), String> {
     Ok(())
 }
 
-#[tauri::command]
-pub async fn list_oss_objects(
-    config: OSSConfig,
-    prefix: String,
-) -> Result<Vec<ObjectInfo>, String> {
-    // Validate input parameters
-    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
-
-    // Validate prefix (allow empty for root listing)
-    if prefix.len() > 1000 {
-        return Err("Prefix too long (max 1000 characters)".to_string());
+const TEST_ALL_CONCURRENCY: usize = 3;
+const CONFIG_TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+fn failed_connection_test(
+    error: String,
+    diagnostics: Vec<ConnectionTestStep>,
+) -> OSSConnectionTest {
+    OSSConnectionTest {
+        success: false,
+        error: Some(error),
+        latency: None,
+        bucket_exists: None,
+        available_buckets: None,
+        diagnostics,
     }
-
-    // For now, return an empty list since list_objects is not implemented in our simplified interface
-    // TODO: Implement list_objects when needed
-    Ok(vec![])
 }
 
-#[tauri::command]
-pub async fn export_oss_config() -> Result<String, String> {
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+// Runs the provider's connection test for one saved config. Configs that
+// can't be tested are reported without touching the network.
+async fn test_config_item(
+    config_service: &ConfigService,
+    item: &ConfigItem,
+    deep: bool,
+) -> ConfigTestResult {
+    let started = std::time::Instant::now();
+    let config = &item.config;
+    let finish =
+        |status: ConfigHealthStatus, test: OSSConnectionTest, cached: bool| ConfigTestResult {
+            config_id: item.id.clone(),
+            name: item.name.clone(),
+            status,
+            test,
+            cached,
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
 
-    let config = config_service
-        .load_config()
-        .await
-        .map_err(|e| e.to_string())?;
+    if config.access_key_id.trim().is_empty() || config.access_key_secret.trim().is_empty() {
+        let test = failed_connection_test(
+            "The access key or secret of this config is missing, enter it again".to_string(),
+            vec![ConnectionTestStep::failed("config", "credentials missing")],
+        );
+        return finish(ConfigHealthStatus::MissingCredentials, test, false);
+    }
+    let errors = ConfigService::config_errors(config);
+    if !errors.is_empty() {
+        let test = failed_connection_test(
+            errors.join(", "),
+            vec![ConnectionTestStep::failed("config", errors.join(", "))],
+        );
+        return finish(ConfigHealthStatus::Invalid, test, false);
+    }
 
-    match config {
-        Some(config) => {
-            let export_data = serde_json::json!({
-                "version": "1.0",
-                "export_date": chrono::Utc::now().to_rfc3339(),
-                "config": config
-            });
-            serde_json::to_string_pretty(&export_data).map_err(|e| e.to_string())
+    if !deep {
+        if let Some(test) = config_service.get_cached_connection_status(config).await {
+            let status = if test.success {
+                ConfigHealthStatus::Healthy
+            } else {
+                ConfigHealthStatus::Failed
+            };
+            return finish(status, test, true);
+        }
+    }
+
+    let run = async {
+        let oss_service = OSS_SERVICES.get(config)?;
+        let mut result = oss_service.test_connection().await?;
+        if deep {
+            explain_auth_failure(config, &mut result).await;
+        }
+        if deep && result.success {
+            match CDN_CHECKS.check_config(&oss_service, true).await {
+                Some(Some(warning)) => result
+                    .diagnostics
+                    .push(ConnectionTestStep::failed("cdn", warning)),
+                Some(None) => result
+                    .diagnostics
+                    .push(ConnectionTestStep::passed("cdn", None)),
+                None => {}
+            }
+        }
+        Ok::<_, AppError>(result)
+    };
+    match tokio::time::timeout(CONFIG_TEST_TIMEOUT, run).await {
+        Ok(Ok(test)) => {
+            config_service.cache_connection_test(config, &test);
+            let status = if test.success {
+                ConfigHealthStatus::Healthy
+            } else {
+                ConfigHealthStatus::Failed
+            };
+            finish(status, test, false)
+        }
+        Ok(Err(AppError::Network(e))) => {
+            let steps = request_failure_steps(&e);
+            let test = failed_connection_test(AppError::Network(e).to_string(), steps);
+            finish(ConfigHealthStatus::Unreachable, test, false)
+        }
+        Ok(Err(e)) => finish(
+            ConfigHealthStatus::Failed,
+            failed_connection_test(e.to_string(), Vec::new()),
+            false,
+        ),
+        Err(_) => {
+            let test = failed_connection_test(
+                format!("No answer within {} seconds", CONFIG_TEST_TIMEOUT.as_secs()),
+                Vec::new(),
+            );
+            finish(ConfigHealthStatus::TimedOut, test, false)
         }
-        None => Err("No configuration found to export".to_string()),
     }
 }
 
+/// Test every saved config, a few at a time, e.g. after rotating credentials.
+/// Cached results are reused unless `deep`, which also runs a fresh test and
+/// checks the clock when the signature is rejected. Each finished config is
+/// emitted as a `config://test-all-progress` event; results keep the order of
+/// the collection.
 #[tauri::command]
-pub async fn import_oss_config(config_json: String) -> Result<(), String> {
-    // Rate limiting
-    CONFIG_RATE_LIMITER
-        .check_rate_limit("import_config")
+pub async fn test_all_configs(deep: bool) -> Result<Vec<ConfigTestResult>, String> {
+    let config_service = Arc::new(ConfigService::new().map_err(|e| e.to_string())?);
+    let collection = config_service
+        .load_all_configs()
+        .await
         .map_err(|e| e.to_string())?;
 
-    // Parse the imported JSON
-    let import_data: serde_json::Value =
-        serde_json::from_str(&config_json).map_err(|e| format!("Invalid JSON format: {}", e))?;
+    let total = collection.configs.len();
+    let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(TEST_ALL_CONCURRENCY));
+    let mut tasks = Vec::with_capacity(total);
+
+    for item in collection.configs {
+        let config_service = config_service.clone();
+        let completed = completed.clone();
+        let semaphore = semaphore.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = test_config_item(&config_service, &item, deep).await;
+            TEST_PROGRESS.report_config(&ConfigTestProgress {
+                completed: completed.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1,
+                total,
+                result: result.clone(),
+            });
+            result
+        }));
+    }
 
-    // Extract the config from the import data
-    let config: OSSConfig = if let Some(config_value) = import_data.get("config") {
-        serde_json::from_value(config_value.clone())
-            .map_err(|e| format!("Invalid configuration format: {}", e))?
-    } else {
-        // Try to parse the entire JSON as a config (for backward compatibility)
-        serde_json::from_str(&config_json)
-            .map_err(|e| format!("Invalid configuration format: {}", e))?
-    };
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.map_err(|e| format!("Task join error: {}", e))?);
+    }
+    log_info!(
+        operation = "test_all_configs",
+        deep = deep,
+        total = total,
+        healthy = results
+            .iter()
+            .filter(|r| r.status == ConfigHealthStatus::Healthy)
+            .count(),
+        "Tested all configs"
+    );
+    Ok(results)
+}
 
-    // Validate the imported config
+#[tauri::command]
+pub async fn list_oss_objects(
+    config: OSSConfig,
+    prefix: String,
+) -> Result<Vec<ObjectInfo>, String> {
+    // Validate input parameters
     validate_oss_config_params(&config).map_err(|e| e.to_string())?;
 
-    // Save the imported config
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    // Validate prefix (allow empty for root listing)
+    if prefix.len() > MAX_PREFIX_LEN {
+        return Err(format!(
+            "Prefix too long (max {} characters)",
+            MAX_PREFIX_LEN
+        ));
+    }
 
-    config_service
-        .save_config(&config)
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    oss_service
+        .list_objects(&prefix)
         .await
         .map_err(|e| e.to_string())
 }
 
-// ============================================================================
-// Multi-Config Management Commands
-// ============================================================================
-
+/// Child prefixes ("directories") of `prefix` for key prefix autocomplete,
+/// split on `delimiter` (default "/"). A listing is reused for a short while
+/// per config and prefix; `refresh` lists the bucket again.
 #[tauri::command]
-pub async fn get_all_configs() -> Result<ConfigCollection, String> {
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
-    config_service
-        .load_all_configs()
+pub async fn list_oss_prefixes(
+    config: OSSConfig,
+    prefix: String,
+    delimiter: Option<String>,
+    refresh: Option<bool>,
+) -> Result<Vec<String>, String> {
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+    if prefix.len() > MAX_PREFIX_LEN {
+        return Err(format!(
+            "Prefix too long (max {} characters)",
+            MAX_PREFIX_LEN
+        ));
+    }
+    let delimiter = delimiter.unwrap_or_else(|| "/".to_string());
+    if delimiter.is_empty() {
+        return Err("Delimiter cannot be empty".to_string());
+    }
+
+    if !refresh.unwrap_or(false) {
+        if let Some(prefixes) = OSS_PREFIXES.get(&config, &prefix, &delimiter) {
+            return Ok(prefixes);
+        }
+    }
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    let prefixes = oss_service
+        .list_prefixes(&prefix, &delimiter)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    OSS_PREFIXES.record(&config, &prefix, &delimiter, &prefixes);
+    Ok(prefixes)
 }
 
-#[tauri::command]
-pub async fn save_config_item(item: ConfigItem) -> Result<(), String> {
-    // Rate limiting
-    CONFIG_RATE_LIMITER
-        .check_rate_limit("save_config_item")
-        .map_err(|e| e.to_string())?;
+fn validate_object_key(key: &str) -> Result<(), AppError> {
+    if key.is_empty() {
+        return Err(AppError::Validation(
+            "Object key cannot be empty".to_string(),
+        ));
+    }
 
-    // Validate the config within the item
-    validate_oss_config_params(&item.config).map_err(|e| e.to_string())?;
+    if key.len() > MAX_OBJECT_KEY_LEN {
+        return Err(AppError::Validation(format!(
+            "Object key too long (max {} characters)",
+            MAX_OBJECT_KEY_LEN
+        )));
+    }
 
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
-    config_service
-        .save_config_item(item)
-        .await
-        .map_err(|e| e.to_string())
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn set_active_config(config_id: String) -> Result<(), String> {
-    // Validate UUID format
-    validate_uuid(&config_id).map_err(|e| e.to_string())?;
+pub async fn get_object_metadata(config: OSSConfig, key: String) -> Result<ObjectMetadata, String> {
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+    validate_object_key(&key).map_err(|e| e.to_string())?;
 
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
-    config_service
-        .set_active_config(config_id)
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    oss_service
+        .get_object_metadata(&key)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn delete_config_item(config_id: String) -> Result<(), String> {
-    // Rate limiting
-    CONFIG_RATE_LIMITER
-        .check_rate_limit("delete_config")
-        .map_err(|e| e.to_string())?;
+pub async fn delete_oss_objects(
+    config: OSSConfig,
+    keys: Vec<String>,
+) -> Result<Vec<DeleteObjectResult>, String> {
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
 
-    // Validate UUID format
-    validate_uuid(&config_id).map_err(|e| e.to_string())?;
+    if keys.is_empty() {
+        return Err("Object keys cannot be empty".to_string());
+    }
 
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
-    config_service
-        .delete_config_item(config_id)
+    if keys.len() > MAX_DELETE_KEYS {
+        return Err(format!(
+            "Too many objects selected (max {})",
+            MAX_DELETE_KEYS
+        ));
+    }
+
+    for key in &keys {
+        validate_object_key(key).map_err(|e| e.to_string())?;
+    }
+
+    log_info!(
+        operation = "delete_oss_objects",
+        bucket = %config.bucket,
+        key_count = keys.len(),
+        "Deleting OSS objects"
+    );
+
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    oss_service
+        .delete_objects(&keys)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_active_config() -> Result<Option<ConfigItem>, String> {
-    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
-    config_service
-        .get_active_config()
+pub async fn download_oss_object(
+    config: OSSConfig,
+    key: String,
+    target_path: String,
+) -> Result<u64, String> {
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+    validate_object_key(&key).map_err(|e| e.to_string())?;
+    validate_user_write_target(&target_path, PathKind::Any, true).map_err(|e| e.to_string())?;
+
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+
+    // Progress is tracked under the object key
+    let task_id = key.clone();
+    let progress_callback: crate::services::oss_service::ProgressCallback =
+        Box::new(move |progress| {
+            let _ = PROGRESS_NOTIFIER.update_progress(task_id.clone(), progress);
+        });
+
+    let result = oss_service
+        .download_object(&key, Path::new(&target_path), Some(progress_callback))
+        .await;
+
+    match &result {
+        Ok(_) => {
+            let _ = PROGRESS_NOTIFIER.complete_progress(&key, UploadStatus::Completed, None);
+        }
+        Err(e) => {
+            log_error!(
+                operation = "download_oss_object",
+                key = %key,
+                error = %e,
+                "Object download failed"
+            );
+            let _ = PROGRESS_NOTIFIER.complete_progress(
+                &key,
+                UploadStatus::Failed,
+                Some(e.to_string()),
+            );
+        }
+    }
+
+    result.map_err(|e| e.to_string())
+}
+
+/// Report objects under `prefix` that are not referenced by upload history
+/// (or by the given markdown files). This is read-only: nothing is deleted
+/// until the caller confirms with `delete_orphan_objects`.
+#[tauri::command]
+pub async fn find_orphan_objects(
+    config: OSSConfig,
+    prefix: String,
+    markdown_files: Option<Vec<String>>,
+) -> Result<OrphanScanReport, String> {
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+
+    if prefix.len() > MAX_PREFIX_LEN {
+        return Err(format!(
+            "Prefix too long (max {} characters)",
+            MAX_PREFIX_LEN
+        ));
+    }
+
+    if let Some(files) = &markdown_files {
+        let settings = current_app_settings().await;
+        validate_file_paths(files, settings.max_scan_files).map_err(|e| e.to_string())?;
+    }
+
+    log_info!(
+        operation = "find_orphan_objects",
+        bucket = %config.bucket,
+        prefix = %prefix,
+        "Searching for orphan objects"
+    );
+
+    // Collect every key referenced by history or markdown, normalised through the
+    // config so CDN and raw endpoint URLs for the same object compare equal.
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    let records = history_service
+        .get_upload_records(None)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut referenced_keys = history_object_keys(&config, &records)?;
+
+    let scanned_markdown_files = markdown_files.as_ref().map_or(0, |files| files.len());
+    if let Some(files) = markdown_files {
+        let file_service = FileService::new()
+            .map_err(|e| e.to_string())?
+            .with_max_markdown_size(current_app_settings().await.max_markdown_bytes());
+        for file in files {
+            let urls = file_service
+                .extract_remote_image_urls(&file)
+                .await
+                .map_err(|e| e.to_string())?;
+            referenced_keys.extend(
+                urls.iter()
+                    .filter_map(|url| object_key_from_url(&config, url)),
+            );
+        }
+    }
+
+    // Paged through directly: `list_objects` stops at MAX_LIST_OBJECTS, which
+    // would leave the rest of a large bucket out of the report
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    let mut total_objects = 0;
+    let mut orphans: Vec<ObjectInfo> = Vec::new();
+    let mut marker: Option<String> = None;
+    loop {
+        let page = oss_service
+            .list_objects_page(&prefix, marker.as_deref())
+            .await
+            .map_err(|e| e.to_string())?;
+        total_objects += page.objects.len();
+        orphans.extend(
+            page.objects
+                .into_iter()
+                .filter(|object| !referenced_keys.contains(&object.key)),
+        );
+        match page.next_marker {
+            // A provider repeating its marker would page forever
+            Some(next) if marker.as_deref() != Some(next.as_str()) => marker = Some(next),
+            _ => break,
+        }
+    }
+    let orphan_bytes = orphans.iter().map(|o| o.size).sum();
+
+    log_info!(
+        operation = "find_orphan_objects",
+        total_objects = total_objects,
+        orphan_count = orphans.len(),
+        "Orphan object search completed"
+    );
+
+    Ok(OrphanScanReport {
+        prefix,
+        total_objects,
+        referenced_objects: total_objects - orphans.len(),
+        orphans,
+        orphan_bytes,
+        scanned_markdown_files,
+    })
+}
+
+/// Object keys in `config`'s bucket that upload history references. Fails
+/// when a record of the bucket has no stored key and a URL that doesn't
+/// resolve, since its object would otherwise be reported as an orphan.
+fn history_object_keys(
+    config: &OSSConfig,
+    records: &[UploadHistoryRecord],
+) -> Result<std::collections::HashSet<String>, String> {
+    let mut keys = std::collections::HashSet::new();
+    let mut unresolved = 0;
+    for record in records {
+        let in_bucket = record.bucket.as_deref() == Some(config.bucket.as_str());
+        let key = record
+            .object_key
+            .clone()
+            .filter(|_| in_bucket)
+            .or_else(|| object_key_from_url(config, &record.uploaded_url));
+        match key {
+            Some(key) => {
+                keys.insert(key);
+            }
+            None if in_bucket && record.success => unresolved += 1,
+            None => {}
+        }
+        keys.extend(
+            record
+                .variants
+                .iter()
+                .filter_map(|variant| object_key_from_url(config, &variant.url)),
+        );
+    }
+
+    if unresolved > 0 {
+        return Err(format!(
+            "{} upload record(s) of bucket {} have a URL on neither its endpoint nor its CDN domain; \
+             check the config's CDN domain before looking for orphans",
+            unresolved, config.bucket
+        ));
+    }
+    Ok(keys)
+}
+
+/// Files hashed at once by `find_local_duplicates`
+const LOCAL_DUPLICATE_CONCURRENCY: usize = 4;
+/// Minimum time between two `duplicates://progress` events
+const DUPLICATE_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Audit a local directory for byte-identical images. Only files sharing
+/// their size with another one are hashed. When `scan_results` are given,
+/// every file gets the number of markdown references pointing at it. Progress
+/// goes out as `duplicates://progress` and `cancel_scan(scan_id)` stops it;
+/// the report then covers what was hashed so far.
+#[tauri::command]
+pub async fn find_local_duplicates(
+    directory: String,
+    recursive: Option<bool>,
+    scan_id: Option<String>,
+    scan_results: Option<Vec<ScanResult>>,
+) -> Result<LocalDuplicateReport, String> {
+    validate_user_path(&directory, PathKind::Directory).map_err(|e| e.to_string())?;
+    let recursive = recursive.unwrap_or(true);
+
+    let settings = current_app_settings().await;
+    let scan_globs = scan_globs_for_scan(None, &settings).map_err(|e| e.to_string())?;
+    let files: Vec<(String, u64)> =
+        local_duplicates::list_image_files(&directory, recursive, &settings.allowed_extensions)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|(path, _)| scan_globs.image_skip_reason(path).is_none())
+            .collect();
+    let files_scanned = files.len();
+
+    log_info!(
+        operation = "find_local_duplicates",
+        directory = %directory,
+        recursive = recursive,
+        files = files_scanned,
+        "Searching for duplicate images"
+    );
+
+    let cancelled = SCAN_TRACKER.start(scan_id.as_deref());
+    let mut last_emit: Option<Instant> = None;
+    let search = local_duplicates::find_duplicates(
+        &ImageService::new(),
+        files,
+        LOCAL_DUPLICATE_CONCURRENCY,
+        cancelled,
+        |current, total, bytes_hashed, total_bytes| {
+            if current < total
+                && last_emit.is_some_and(|at| at.elapsed() < DUPLICATE_PROGRESS_INTERVAL)
+            {
+                return;
+            }
+            last_emit = Some(Instant::now());
+            SCAN_TRACKER.duplicate_progress(&DuplicateScanProgress {
+                scan_id: scan_id.clone(),
+                current,
+                total,
+                bytes_hashed,
+                total_bytes,
+            });
+        },
+    )
+    .await;
+    if let Some(id) = &scan_id {
+        SCAN_TRACKER.forget(id);
+    }
+
+    let mut groups = search.groups;
+    if let Some(results) = scan_results {
+        let mut usage: HashMap<std::path::PathBuf, usize> = HashMap::new();
+        for image in results.iter().flat_map(|r| &r.images) {
+            *usage
+                .entry(canonical_or_raw(&image.absolute_path))
+                .or_default() += 1;
+        }
+        for file in groups.iter_mut().flat_map(|g| g.files.iter_mut()) {
+            file.usage_count = Some(
+                usage
+                    .get(&canonical_or_raw(&file.path))
+                    .copied()
+                    .unwrap_or(0),
+            );
+        }
+    }
+
+    let report = LocalDuplicateReport {
+        directory,
+        recursive,
+        generated_at: chrono::Utc::now(),
+        files_scanned,
+        files_hashed: search.files_hashed,
+        bytes_hashed: search.bytes_hashed,
+        wasted_bytes: groups.iter().map(|g| g.wasted_bytes).sum(),
+        groups,
+        unreadable_files: search.unreadable_files,
+        cancelled: search.cancelled,
+    };
+
+    log_info!(
+        operation = "find_local_duplicates",
+        files_hashed = report.files_hashed,
+        groups = report.groups.len(),
+        wasted_bytes = report.wasted_bytes,
+        cancelled = report.cancelled,
+        "Duplicate image search completed"
+    );
+
+    Ok(report)
+}
+
+// Markdown references and walked files may spell the same path differently
+fn canonical_or_raw(path: &str) -> std::path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| std::path::PathBuf::from(path))
+}
+
+/// Write a `find_local_duplicates` report. Returns the path that was written.
+#[tauri::command]
+pub async fn export_duplicate_report(
+    report: LocalDuplicateReport,
+    path: String,
+    format: Option<ReportFormat>,
+    overwrite: Option<bool>,
+) -> Result<String, String> {
+    validate_user_write_target(&path, PathKind::Any, overwrite.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let content =
+        render_duplicate_report(&report, format.unwrap_or_default()).map_err(|e| e.to_string())?;
+    write_report(Path::new(&path), &content).map_err(|e| e.to_string())?;
+
+    log_info!(
+        operation = "export_duplicate_report",
+        path = %path,
+        groups = report.groups.len(),
+        "Duplicate report exported"
+    );
+
+    Ok(path)
+}
+
+/// Files hashed at once by `verify_local_integrity`
+const LOCAL_INTEGRITY_CONCURRENCY: usize = 4;
+
+/// Check whether the local originals of uploaded images still hold what was
+/// uploaded, for the records `query` matches (the whole history without one).
+/// Every file is hashed and compared with the checksum stored at upload.
+/// Progress goes out as `integrity://progress` and `cancel_scan(scan_id)`
+/// stops it; the report then covers what was hashed so far.
+#[tauri::command]
+pub async fn verify_local_integrity(
+    query: Option<HistoryQuery>,
+    scan_id: Option<String>,
+) -> Result<LocalIntegrityReport, String> {
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    check_local_integrity(
+        &history_service,
+        query,
+        scan_id,
+        LOCAL_INTEGRITY_CONCURRENCY,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// `verify_local_integrity`, also run by the maintenance scheduler. A check of
+/// the whole history that ran to the end replaces the modified files the
+/// health check reports.
+async fn check_local_integrity(
+    history_service: &HistoryService,
+    query: Option<HistoryQuery>,
+    scan_id: Option<String>,
+    concurrency: usize,
+) -> Result<LocalIntegrityReport, AppError> {
+    let whole_history = query.is_none();
+    let records: Vec<UploadHistoryRecord> = history_service
+        .get_upload_records(query)
+        .await?
+        .into_iter()
+        .filter(|record| record.success && record.original_path.is_some())
+        .collect();
+    let records_checked = records.len();
+
+    log_info!(
+        operation = "verify_local_integrity",
+        records = records_checked,
+        "Verifying local originals of uploaded images"
+    );
+
+    let cancelled = SCAN_TRACKER.start(scan_id.as_deref());
+    let mut last_emit: Option<Instant> = None;
+    let check = local_integrity::verify_records(
+        records,
+        concurrency,
+        cancelled,
+        |current, total, bytes_hashed, total_bytes| {
+            if current < total
+                && last_emit.is_some_and(|at| at.elapsed() < DUPLICATE_PROGRESS_INTERVAL)
+            {
+                return;
+            }
+            last_emit = Some(Instant::now());
+            SCAN_TRACKER.integrity_progress(&DuplicateScanProgress {
+                scan_id: scan_id.clone(),
+                current,
+                total,
+                bytes_hashed,
+                total_bytes,
+            });
+        },
+    )
+    .await;
+    if let Some(id) = &scan_id {
+        SCAN_TRACKER.forget(id);
+    }
+
+    if whole_history && !check.cancelled {
+        let mut modified: Vec<String> = check
+            .modified
+            .iter()
+            .map(|file| file.original_path.clone())
+            .collect();
+        modified.dedup();
+        if let Ok(mut last) = LAST_MODIFIED_ORIGINALS.lock() {
+            *last = modified;
+        }
+    }
+
+    let report = LocalIntegrityReport {
+        scan_id,
+        generated_at: chrono::Utc::now(),
+        records_checked,
+        files_hashed: check.files_hashed,
+        bytes_hashed: check.bytes_hashed,
+        matched: check.matched,
+        modified: check.modified,
+        missing: check.missing,
+        unverifiable: check.unverifiable,
+        cancelled: check.cancelled,
+    };
+
+    if report.modified.is_empty() {
+        log_info!(
+            operation = "verify_local_integrity",
+            files_hashed = report.files_hashed,
+            missing = report.missing.len(),
+            cancelled = report.cancelled,
+            "Local originals verified"
+        );
+    } else {
+        log_warn!(
+            operation = "verify_local_integrity",
+            files_hashed = report.files_hashed,
+            modified = report.modified.len(),
+            missing = report.missing.len(),
+            cancelled = report.cancelled,
+            "Local originals modified since upload"
+        );
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn delete_orphan_objects(
+    config: OSSConfig,
+    keys: Vec<String>,
+) -> Result<Vec<DeleteObjectResult>, String> {
+    delete_oss_objects(config, keys).await
+}
+
+#[tauri::command]
+pub async fn export_oss_config() -> Result<String, String> {
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+
+    let config = config_service
+        .load_config()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match config {
+        Some(config) => {
+            let export_data = serde_json::json!({
+                "version": "1.0",
+                "export_date": chrono::Utc::now().to_rfc3339(),
+                "config": config
+            });
+            serde_json::to_string_pretty(&export_data).map_err(|e| e.to_string())
+        }
+        None => Err("No configuration found to export".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn import_oss_config(config_json: String) -> Result<(), String> {
+    // Rate limiting
+    CONFIG_RATE_LIMITER
+        .check_rate_limit("import_config")
+        .map_err(|e| e.to_string())?;
+
+    // Parse the imported JSON
+    let import_data: serde_json::Value =
+        serde_json::from_str(&config_json).map_err(|e| format!("Invalid JSON format: {}", e))?;
+
+    // Extract the config from the import data
+    let config: OSSConfig = if let Some(config_value) = import_data.get("config") {
+        serde_json::from_value(config_value.clone())
+            .map_err(|e| format!("Invalid configuration format: {}", e))?
+    } else {
+        // Try to parse the entire JSON as a config (for backward compatibility)
+        serde_json::from_str(&config_json)
+            .map_err(|e| format!("Invalid configuration format: {}", e))?
+    };
+
+    // Validate the imported config
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+
+    // Save the imported config
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+
+    config_service
+        .save_config(&config)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Multi-Config Management Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_all_configs() -> Result<ConfigCollection, String> {
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    config_service
+        .load_all_configs()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn save_config_item(item: ConfigItem) -> Result<ConfigValidation, String> {
+    // Rate limiting
+    CONFIG_RATE_LIMITER
+        .check_rate_limit("save_config_item")
+        .map_err(|e| e.to_string())?;
+
+    // Errors keep the item from being saved, warnings are returned with it saved
+    let validation = check_config(&item.config)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !validation.valid {
+        return Ok(validation);
+    }
+
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    config_service
+        .save_config_item(item)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(validation)
+}
+
+#[tauri::command]
+pub async fn set_active_config(config_id: String) -> Result<(), String> {
+    // Validate UUID format
+    validate_uuid(&config_id).map_err(|e| e.to_string())?;
+
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    config_service
+        .set_active_config(config_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_config_item(config_id: String) -> Result<(), String> {
+    // Rate limiting
+    CONFIG_RATE_LIMITER
+        .check_rate_limit("delete_config")
+        .map_err(|e| e.to_string())?;
+
+    // Validate UUID format
+    validate_uuid(&config_id).map_err(|e| e.to_string())?;
+
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    config_service
+        .delete_config_item(config_id.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+    WINDOW_SESSIONS.forget_config(&config_id);
+    Ok(())
+}
+
+/// The config `window_session` uploads with when one was set for it with
+/// `session_default_config`, else the persisted active config
+#[tauri::command]
+pub async fn get_active_config(
+    window_session: Option<String>,
+) -> Result<Option<ConfigItem>, String> {
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    if let Some(config_id) = WINDOW_SESSIONS.default_config(window_session.as_deref()) {
+        let collection = config_service
+            .load_all_configs()
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(item) = collection.configs.into_iter().find(|c| c.id == config_id) {
+            return Ok(Some(item));
+        }
+    }
+    config_service
+        .get_active_config()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Point the window `session_id` (its window label) at a saved config, or
+/// back at the active config with None. Only that window's uploads and
+/// `get_active_config` calls see it; the persisted active config is unchanged.
+#[tauri::command]
+pub async fn session_default_config(
+    session_id: String,
+    config_id: Option<String>,
+) -> Result<(), String> {
+    if session_id.trim().is_empty() {
+        return Err("Session ID cannot be empty".to_string());
+    }
+    if let Some(config_id) = &config_id {
+        validate_uuid(config_id).map_err(|e| e.to_string())?;
+        let collection = ConfigService::new()
+            .map_err(|e| e.to_string())?
+            .load_all_configs()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !collection.configs.iter().any(|c| c.id == *config_id) {
+            return Err(format!("Config with ID {} not found", config_id));
+        }
+    }
+    WINDOW_SESSIONS.set_default_config(&session_id, config_id);
+    Ok(())
+}
+
+// ============================================================================
+// App Settings Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_app_settings() -> Result<AppSettings, String> {
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    config_service
+        .app_settings()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Put the settings that aren't read per operation into effect: the upload
+/// rate limit, the log level and the allowed image extensions. Runs at
+/// startup and after every update.
+pub(crate) fn apply_app_settings(settings: &AppSettings) {
+    UPLOAD_RATE_LIMITER.set_max_requests(settings.upload_rate_limit_per_minute as usize);
+    USER_PATHS.set_image_extensions(&settings.allowed_extensions);
+    if let Err(e) = crate::utils::logger::set_log_level(&settings.log_level) {
+        log_warn!(
+            operation = "apply_app_settings",
+            log_level = %settings.log_level,
+            error = %e,
+            "Log level not applied"
+        );
+    }
+}
+
+/// Update only the fields present in `patch` and return the merged settings
+#[tauri::command]
+pub async fn update_app_settings(patch: AppSettingsPatch) -> Result<AppSettings, String> {
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    let settings = config_service
+        .update_app_settings(patch)
+        .await
+        .map_err(|e| e.to_string())?;
+    apply_app_settings(&settings);
+
+    log_info!(
+        operation = "update_app_settings",
+        upload_concurrency = settings.upload_concurrency,
+        log_level = %settings.log_level,
+        "App settings updated"
+    );
+
+    Ok(settings)
+}
+
+/// Render uploaded images as copy-ready links. Each entry is either an upload
+/// history record id or a plain URL; `format` defaults to the configured
+/// `default_copy_format`.
+#[tauri::command]
+pub async fn format_upload_links(
+    record_ids_or_urls: Vec<String>,
+    format: Option<LinkFormat>,
+) -> Result<String, String> {
+    if record_ids_or_urls.is_empty() {
+        return Err("No records or URLs provided".to_string());
+    }
+
+    check_count(
+        "links requested",
+        record_ids_or_urls.len(),
+        MAX_LINK_REQUESTS,
+    )
+    .map_err(|e| e.to_string())?;
+    for value in &record_ids_or_urls {
+        check_len("Record id or URL", value, MAX_LINK_LEN).map_err(|e| e.to_string())?;
+    }
+
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    let settings = config_service
+        .app_settings()
+        .await
+        .map_err(|e| e.to_string())?;
+    let format = format.unwrap_or(settings.default_copy_format);
+
+    let needs_history = record_ids_or_urls
+        .iter()
+        .any(|entry| !entry.starts_with("http://") && !entry.starts_with("https://"));
+    let records: HashMap<String, UploadHistoryRecord> = if needs_history {
+        let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+        history_service
+            .get_upload_records(None)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|record| (record.id.clone(), record))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let mut sources = Vec::with_capacity(record_ids_or_urls.len());
+    for entry in &record_ids_or_urls {
+        if entry.starts_with("http://") || entry.starts_with("https://") {
+            sources.push(LinkSource::from_url(entry));
+        } else {
+            let record = records
+                .get(entry)
+                .ok_or_else(|| format!("Upload record not found: {}", entry))?;
+            sources.push(LinkSource {
+                url: record.uploaded_url.clone(),
+                filename: record.image_name.clone(),
+                variants: record.variants.clone(),
+            });
+        }
+    }
+
+    format_links(&sources, format, settings.custom_link_template.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// File Operations Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn replace_markdown_links(replacements: Vec<LinkReplacement>) -> Result<(), String> {
+    log_info!(
+        operation = "replace_markdown_links_command",
+        replacement_count = replacements.len(),
+        "Received request to replace markdown links"
+    );
+
+    // Validate input parameters
+    if replacements.is_empty() {
+        log_error!(
+            operation = "replace_markdown_links_command",
+            error = "Replacements cannot be empty",
+            "Validation failed"
+        );
+        return Err("Replacements cannot be empty".to_string());
+    }
+
+    if let Err(e) = check_count("replacements", replacements.len(), MAX_REPLACEMENTS) {
+        log_error!(
+            operation = "replace_markdown_links_command",
+            replacement_count = replacements.len(),
+            error = %e,
+            "Validation failed"
+        );
+        return Err(e.to_string());
+    }
+
+    // Validate each replacement
+    for (index, replacement) in replacements.iter().enumerate() {
+        // 先检查长度，避免把超长的链接写进日志
+        if let Err(e) = check_replacement(replacement) {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                replacement_index = index,
+                error = %e,
+                "Validation failed"
+            );
+            return Err(e.to_string());
+        }
+        log_debug!(
+            operation = "validate_replacement",
+            replacement_index = index,
+            file_path = %replacement.file_path,
+            old_link = %replacement.old_link,
+            new_link = %replacement.new_link,
+            line = replacement.line,
+            column = replacement.column,
+            "Validating replacement"
+        );
+        if replacement.file_path.is_empty() {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                replacement_index = index,
+                error = "File path cannot be empty in replacement",
+                "Validation failed"
+            );
+            return Err("File path cannot be empty in replacement".to_string());
+        }
+
+        if replacement.old_link.is_empty() {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                replacement_index = index,
+                error = "Old link cannot be empty in replacement",
+                "Validation failed"
+            );
+            return Err("Old link cannot be empty in replacement".to_string());
+        }
+
+        if replacement.new_link.is_empty() {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                replacement_index = index,
+                error = "New link cannot be empty in replacement",
+                "Validation failed"
+            );
+            return Err("New link cannot be empty in replacement".to_string());
+        }
+
+        if let Err(e) = validate_user_path(&replacement.file_path, PathKind::Markdown) {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                replacement_index = index,
+                file_path = %replacement.file_path,
+                error = %e,
+                "File validation failed"
+            );
+            return Err(e.to_string());
+        }
+    }
+
+    log_info!(
+        operation = "replace_markdown_links_command",
+        replacement_count = replacements.len(),
+        "All replacements validated successfully, proceeding with file service"
+    );
+
+    let settings = current_app_settings().await;
+    let file_service = FileService::new()
+        .map_err(|e| {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                error = %e,
+                "Failed to create FileService"
+            );
+            e.to_string()
+        })?
+        .with_operation_name("replace_markdown_links")
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_operation_log(file_operation_log());
+
+    let result = file_service
+        .replace_image_links_batch(replacements)
+        .await
+        .map_err(|e| {
+            log_error!(
+                operation = "replace_markdown_links_command",
+                error = %e,
+                "FileService batch replacement failed"
+            );
+            e.to_string()
+        })?;
+
+    log_info!(
+        operation = "replace_markdown_links_command",
+        successful_replacements = result.total_successful_replacements,
+        failed_replacements = result.total_failed_replacements,
+        total_files = result.total_files,
+        "Link replacement completed"
+    );
+
+    Ok(())
+}
+
+/// Replace links across files. With `atomic`, every file is backed up first and
+/// a file that fails to read or write rolls the whole batch back. With
+/// `write_changelog` or a `changelog_path`, the changes are also written as a
+/// replacement changelog that `revert_from_changelog` can undo. Files that
+/// can't be written fail the call before anything changes, unless
+/// `read_only_policy` is `Skip`.
+#[tauri::command]
+pub async fn replace_markdown_links_with_result(
+    replacements: Vec<LinkReplacement>,
+    report_path: Option<String>,
+    overwrite: Option<bool>,
+    atomic: Option<bool>,
+    write_changelog: Option<bool>,
+    changelog_path: Option<String>,
+    read_only_policy: Option<ReadOnlyPolicy>,
+) -> Result<BatchReplacementResult, String> {
+    // Validate input parameters
+    if replacements.is_empty() {
+        return Err("Replacements cannot be empty".to_string());
+    }
+
+    check_count("replacements", replacements.len(), MAX_REPLACEMENTS).map_err(|e| e.to_string())?;
+
+    // Validate each replacement
+    for replacement in &replacements {
+        check_replacement(replacement).map_err(|e| e.to_string())?;
+        if replacement.file_path.is_empty() {
+            return Err("File path cannot be empty in replacement".to_string());
+        }
+
+        if replacement.old_link.is_empty() {
+            return Err("Old link cannot be empty in replacement".to_string());
+        }
+
+        if replacement.new_link.is_empty() {
+            return Err("New link cannot be empty in replacement".to_string());
+        }
+
+        validate_user_path(&replacement.file_path, PathKind::Markdown)
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Check the report targets up front so a bad path doesn't surface after files changed
+    let overwrite = overwrite.unwrap_or(false);
+    let report_paths = match &report_path {
+        Some(report_path) => {
+            let (json_path, markdown_path) = replacement_report_paths(report_path);
+            for path in [&json_path, &markdown_path] {
+                validate_user_write_target(&path.to_string_lossy(), PathKind::Any, overwrite)
+                    .map_err(|e| e.to_string())?;
+            }
+            Some((json_path, markdown_path))
+        }
+        None => None,
+    };
+    let changelog_path = changelog_target(
+        write_changelog.unwrap_or(false),
+        changelog_path.as_deref(),
+        &replacements[0].file_path,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let settings = current_app_settings().await;
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_name("replace_markdown_links_with_result")
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_operation_log(file_operation_log())
+        .with_read_only_policy(read_only_policy.unwrap_or_default());
+    let result = if atomic.unwrap_or(false) {
+        let backup_service = BackupService::new()
+            .map_err(|e| e.to_string())?
+            .with_operation_log(file_operation_log());
+        let result = file_service
+            .replace_image_links_batch_atomic(replacements, &backup_service)
+            .await;
+        if let Ok(result) = &result {
+            journal_replacement("replace_markdown_links_with_result", &result.backups).await;
+        }
+        apply_backup_retention(&backup_service, &current_app_settings().await).await;
+        result
+    } else {
+        file_service.replace_image_links_batch(replacements).await
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut result = result;
+    if let Some(path) = &changelog_path {
+        save_replacement_changelog(&mut result, path)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    if let Some((json_path, markdown_path)) = report_paths {
+        let json = serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?;
+        write_report(&json_path, &json).map_err(|e| e.to_string())?;
+        write_report(&markdown_path, &replacement_report_markdown(&result))
+            .map_err(|e| e.to_string())?;
+
+        log_info!(
+            operation = "replace_markdown_links_with_result",
+            report_path = %json_path.display(),
+            "Replacement report written"
+        );
+    }
+
+    Ok(result)
+}
+
+/// Markdown files that can't be opened for writing, so the user can decide to
+/// skip them or fix them before starting a replacement
+#[tauri::command]
+pub async fn find_unwritable_files(file_paths: Vec<String>) -> Result<Vec<UnwritableFile>, String> {
+    check_count("files", file_paths.len(), MAX_REPLACEMENTS).map_err(|e| e.to_string())?;
+    for path in &file_paths {
+        validate_user_path(path, PathKind::Markdown).map_err(|e| e.to_string())?;
+    }
+
+    Ok(FileService::new()
+        .map_err(|e| e.to_string())?
+        .unwritable_files(&file_paths)
+        .await)
+}
+
+/// Undo the changes recorded in a replacement changelog by putting the replaced
+/// text back. Lines edited since the changelog was written fail like any other
+/// replacement whose link is no longer there. Changes to files that aren't
+/// markdown files the user picked in the app are not made and reported as
+/// failed: the changelog is only a JSON file, it doesn't grant access.
+#[tauri::command]
+pub async fn revert_from_changelog(path: String) -> Result<BatchReplacementResult, String> {
+    validate_user_path_maybe_missing(&path, PathKind::Any).map_err(|e| e.to_string())?;
+
+    let changelog = read_changelog(Path::new(&path)).map_err(|e| e.to_string())?;
+    if changelog.changes.is_empty() {
+        return Err("Changelog has no changes to revert".to_string());
+    }
+
+    let mut inverse: Vec<LinkReplacement> = Vec::new();
+    let mut rejected: Vec<ReplacementResult> = Vec::new();
+    for change in &changelog.changes {
+        let replacement = change.inverse();
+        let error = match validate_user_path(&change.file_path, PathKind::Markdown) {
+            Ok(_) => {
+                inverse.push(replacement);
+                continue;
+            }
+            Err(e) => e.to_string(),
+        };
+        log_warn!(
+            operation = "revert_from_changelog",
+            file_path = %change.file_path,
+            error = %error,
+            "Changelog entry skipped"
+        );
+        let failure = ReplacementError { replacement, error };
+        match rejected
+            .iter_mut()
+            .find(|r| r.file_path == change.file_path)
+        {
+            Some(result) => {
+                result.total_replacements += 1;
+                result.failed_replacements.push(failure);
+            }
+            None => rejected.push(ReplacementResult {
+                file_path: change.file_path.clone(),
+                total_replacements: 1,
+                successful_replacements: 0,
+                failed_replacements: vec![failure],
+                duration: std::time::SystemTime::now(),
+                changes: Vec::new(),
+                encoding: None,
+                converted_to_utf8: false,
+                warnings: Vec::new(),
+                restored: false,
+            }),
+        }
+    }
+
+    log_info!(
+        operation = "revert_from_changelog",
+        path = %path,
+        change_count = inverse.len(),
+        skipped_files = rejected.len(),
+        "Reverting changes from changelog"
+    );
+
+    let settings = current_app_settings().await;
+    let mut result = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_name("revert_from_changelog")
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_operation_log(file_operation_log())
+        .replace_image_links_batch(inverse)
+        .await
+        .map_err(|e| e.to_string())?;
+    result.total_files += rejected.len();
+    result.total_failed_replacements += rejected
+        .iter()
+        .map(|r| r.failed_replacements.len())
+        .sum::<usize>();
+    result.results.extend(rejected);
+    Ok(result)
+}
+
+/// Replacement runs that can be undone, newest first: those that backed up
+/// their files (`process_markdown_files`, the migrations and atomic
+/// `replace_markdown_links_with_result`), up to the last 20
+#[tauri::command]
+pub async fn list_replacement_operations() -> Result<Vec<ReplacementOperation>, String> {
+    ReplacementJournal::new()
+        .map_err(|e| e.to_string())?
+        .list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Put back every file of a replacement run from its backups: the run
+/// `operation_id`, or the most recent one not undone yet. Refuses, listing the
+/// files, when any was modified after the run or its backup is gone.
+#[tauri::command]
+pub async fn undo_last_replacement(
+    operation_id: Option<String>,
+) -> Result<ReplacementUndoResult, String> {
+    let backup_service = BackupService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_log(file_operation_log());
+    ReplacementJournal::new()
+        .map_err(|e| e.to_string())?
+        .undo(operation_id.as_deref(), &backup_service)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Journal a replacement run that backed up its files so it can be undone. A
+// journal that can't be written costs the undo, not the run.
+async fn journal_replacement(source: &str, backups: &[BackupInfo]) {
+    if backups.is_empty() {
+        return;
+    }
+    let recorded = match ReplacementJournal::new() {
+        Ok(journal) => journal.record(source, backups).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = recorded {
+        log_warn!(
+            operation = "journal_replacement",
+            source = source,
+            error = %e,
+            "Replacement run could not be recorded for undo"
+        );
+    }
+}
+
+/// Where a replacement changelog goes: `requested`, or the default in the
+/// repository root of `first_file`. None when no changelog was asked for.
+fn changelog_target(
+    write: bool,
+    requested: Option<&str>,
+    first_file: &str,
+) -> Result<Option<std::path::PathBuf>, AppError> {
+    let path = match requested {
+        Some(path) => std::path::PathBuf::from(path),
+        None if write => default_changelog_path(Path::new(first_file)),
+        None => return Ok(None),
+    };
+    // Each run replaces the previous changelog
+    validate_user_write_target(&path.to_string_lossy(), PathKind::Any, true)?;
+    Ok(Some(path))
+}
+
+// Fill in the uploads behind each change from history, then write the changelog.
+// History or configs that can't be loaded only leave the upload fields empty.
+async fn save_replacement_changelog(
+    result: &mut BatchReplacementResult,
+    path: &Path,
+) -> Result<(), AppError> {
+    let records = match HistoryService::new() {
+        Ok(history) => history.get_upload_records(None).await.unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    let configs = match ConfigService::new() {
+        Ok(config_service) => config_service
+            .load_all_configs()
+            .await
+            .map(|collection| collection.configs)
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    for file in &mut result.results {
+        annotate_changes(&mut file.changes, &records, &configs);
+    }
+
+    let changelog = replacement_changelog(result);
+    write_changelog(path, &changelog)?;
+
+    log_info!(
+        operation = "write_changelog",
+        path = %path.display(),
+        change_count = changelog.changes.len(),
+        "Replacement changelog written"
+    );
+    Ok(())
+}
+
+/// Delete backups outside the retention settings; returns what was removed
+#[tauri::command]
+pub async fn cleanup_backups() -> Result<BackupCleanupResult, String> {
+    let backup_service = BackupService::new().map_err(|e| e.to_string())?;
+    let settings = current_app_settings().await;
+    backup_service
+        .cleanup(&BackupRetention::from_settings(&settings))
         .await
         .map_err(|e| e.to_string())
 }
 
-// ============================================================================
-// File Operations Commands
-// ============================================================================
+#[tauri::command]
+pub async fn get_backup_storage_usage() -> Result<BackupStorageUsage, String> {
+    let backup_service = BackupService::new().map_err(|e| e.to_string())?;
+    backup_service
+        .storage_usage()
+        .await
+        .map_err(|e| e.to_string())
+}
 
+/// Disk space taken by backups, cached thumbnails and archived originals
 #[tauri::command]
-pub async fn replace_markdown_links(replacements: Vec<LinkReplacement>) -> Result<(), String> {
+pub async fn get_storage_usage() -> Result<StorageUsage, String> {
+    let backups = BackupService::new()
+        .map_err(|e| e.to_string())?
+        .storage_usage()
+        .await
+        .map_err(|e| e.to_string())?;
+    let thumbnails = ImageService::with_cache()
+        .map_err(|e| e.to_string())?
+        .get_cache_stats()
+        .await
+        .map_err(|e| e.to_string())?;
+    let archived_originals = OriginalArchive::new()
+        .map_err(|e| e.to_string())?
+        .usage()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(StorageUsage {
+        total_bytes: backups.total_bytes
+            + thumbnails.total_size_bytes
+            + archived_originals.total_bytes,
+        backups,
+        thumbnail_files: thumbnails.total_files,
+        thumbnail_bytes: thumbnails.total_size_bytes,
+        archived_originals,
+    })
+}
+
+/// The unprocessed source of an upload, when `archive_originals` kept it and
+/// cleanup hasn't removed it since
+#[tauri::command]
+pub async fn get_archived_original(record_id: String) -> Result<Option<ArchivedOriginal>, String> {
+    if record_id.trim().is_empty() {
+        return Err("Record ID cannot be empty".to_string());
+    }
+    check_len("Record id", &record_id, MAX_NAME_LEN).map_err(|e| e.to_string())?;
+
+    OriginalArchive::new()
+        .map_err(|e| e.to_string())?
+        .lookup(&record_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Log that file rewrites, backups and restores are recorded in; None if the
+// data directory is unusable, the operations then simply go unrecorded
+fn file_operation_log() -> Option<HistoryService> {
+    HistoryService::new()
+        .map_err(|e| {
+            log_warn!(
+                operation = "file_operation_log",
+                error = %e,
+                "File operations will not be recorded"
+            );
+        })
+        .ok()
+}
+
+// Opportunistic cleanup after backups were taken; failures are only logged
+async fn apply_backup_retention(backup_service: &BackupService, settings: &AppSettings) {
+    if let Err(e) = backup_service
+        .cleanup(&BackupRetention::from_settings(settings))
+        .await
+    {
+        log_warn!(
+            operation = "cleanup_backups",
+            error = %e,
+            "Backup cleanup after replacement failed"
+        );
+    }
+}
+
+/// Write an audit report of scan results: per-file counts and every missing
+/// image with its source line. Returns the path that was written.
+#[tauri::command]
+pub async fn export_scan_report(
+    results: Vec<ScanResult>,
+    path: String,
+    format: Option<ReportFormat>,
+    overwrite: Option<bool>,
+) -> Result<String, String> {
+    if results.is_empty() {
+        return Err("Scan results cannot be empty".to_string());
+    }
+
+    validate_user_write_target(&path, PathKind::Any, overwrite.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let report = ScanReport::from_results(&results);
+    let content =
+        render_scan_report(&report, format.unwrap_or_default()).map_err(|e| e.to_string())?;
+    write_report(Path::new(&path), &content).map_err(|e| e.to_string())?;
+
     log_info!(
-        operation = "replace_markdown_links_command",
-        replacement_count = replacements.len(),
-        "Received request to replace markdown links"
+        operation = "export_scan_report",
+        path = %path,
+        total_files = report.total_files,
+        total_missing = report.total_missing,
+        "Scan report exported"
     );
 
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn replace_single_file_links(
+    file_path: String,
+    replacements: Vec<LinkReplacement>,
+) -> Result<ReplacementResult, String> {
     // Validate input parameters
+    if file_path.is_empty() {
+        return Err("File path cannot be empty".to_string());
+    }
+
     if replacements.is_empty() {
-        log_error!(
-            operation = "replace_markdown_links_command",
-            error = "Replacements cannot be empty",
-            "Validation failed"
-        );
         return Err("Replacements cannot be empty".to_string());
     }
 
-    if replacements.len() > 1000 {
-        log_error!(
-            operation = "replace_markdown_links_command",
-            replacement_count = replacements.len(),
-            error = "Too many replacements (max 1000)",
-            "Validation failed"
-        );
-        return Err("Too many replacements (max 1000)".to_string());
+    check_count(
+        "replacements for single file",
+        replacements.len(),
+        MAX_SINGLE_FILE_REPLACEMENTS,
+    )
+    .map_err(|e| e.to_string())?;
+    check_len("File path", &file_path, MAX_PATH_LEN).map_err(|e| e.to_string())?;
+    for replacement in &replacements {
+        check_replacement(replacement).map_err(|e| e.to_string())?;
     }
 
+    validate_user_path(&file_path, PathKind::Markdown).map_err(|e| e.to_string())?;
+
     // Validate each replacement
-    for (index, replacement) in replacements.iter().enumerate() {
-        log_debug!(
-            operation = "validate_replacement",
-            replacement_index = index,
-            file_path = %replacement.file_path,
-            old_link = %replacement.old_link,
-            new_link = %replacement.new_link,
-            line = replacement.line,
-            column = replacement.column,
-            "Validating replacement"
-        );
-        if replacement.file_path.is_empty() {
-            log_error!(
-                operation = "replace_markdown_links_command",
-                replacement_index = index,
-                error = "File path cannot be empty in replacement",
-                "Validation failed"
-            );
-            return Err("File path cannot be empty in replacement".to_string());
+    for replacement in &replacements {
+        if replacement.file_path != file_path {
+            return Err("All replacements must be for the same file".to_string());
         }
 
         if replacement.old_link.is_empty() {
-            log_error!(
-                operation = "replace_markdown_links_command",
-                replacement_index = index,
-                error = "Old link cannot be empty in replacement",
-                "Validation failed"
-            );
             return Err("Old link cannot be empty in replacement".to_string());
         }
 
         if replacement.new_link.is_empty() {
-            log_error!(
-                operation = "replace_markdown_links_command",
-                replacement_index = index,
-                error = "New link cannot be empty in replacement",
-                "Validation failed"
-            );
             return Err("New link cannot be empty in replacement".to_string());
         }
+    }
 
-        // Security check: prevent path traversal
-        if replacement.file_path.contains("..") || replacement.file_path.contains("~") {
-            log_error!(
-                operation = "replace_markdown_links_command",
-                replacement_index = index,
-                file_path = %replacement.file_path,
-                error = "Invalid file path detected in replacement",
-                "Security validation failed"
+    let settings = current_app_settings().await;
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_name("replace_single_file_links")
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_operation_log(file_operation_log());
+    file_service
+        .replace_image_links(&file_path, replacements)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Markdown Pipeline Commands
+// ============================================================================
+
+/// Scan → group → duplicate-check → upload → backup → replace in one call, so
+/// the whole run no longer depends on the frontend staying alive between steps.
+#[tauri::command]
+pub async fn process_markdown_files(
+    file_paths: Vec<String>,
+    config_id: Option<String>,
+    options: Option<ProcessMarkdownOptions>,
+    window_session: Option<String>,
+) -> Result<ProcessMarkdownReport, String> {
+    SCAN_RATE_LIMITER
+        .check_rate_limit("process_markdown_files")
+        .map_err(|e| e.to_string())?;
+
+    if config_id.as_deref() == Some("") {
+        return Err("Config ID cannot be empty".to_string());
+    }
+
+    // No id: the window's config, else the article default from settings,
+    // else the active config
+    let config = saved_upload_config(
+        config_id.as_deref(),
+        window_session.as_deref(),
+        &UploadMode::ArticleUpload,
+    )
+    .await?
+    .config;
+
+    let started = Instant::now();
+    let mut options = options.unwrap_or_default();
+    let session_id = upload_session(options.session_id.take());
+    options.session_id = Some(session_id.clone());
+    let report = run_markdown_pipeline(file_paths, config, options).await?;
+    if !report.dry_run {
+        let uploaded = report
+            .uploads
+            .iter()
+            .filter(|u| !u.reused && u.uploaded_url.is_some())
+            .count();
+        let failed = report
+            .uploads
+            .iter()
+            .filter(|u| u.error.is_some() && !u.queued_offline)
+            .count();
+        notify_batch_finished(BatchSummary::new(uploaded, failed, started).in_session(&session_id))
+            .await;
+    }
+    Ok(report)
+}
+
+/// Pipeline behind `process_markdown_files`, taking the resolved config
+pub async fn run_markdown_pipeline(
+    file_paths: Vec<String>,
+    config: OSSConfig,
+    options: ProcessMarkdownOptions,
+) -> Result<ProcessMarkdownReport, String> {
+    let settings = current_app_settings().await;
+    validate_file_paths(&file_paths, settings.max_scan_files).map_err(|e| e.to_string())?;
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+
+    log_info!(
+        operation = "process_markdown_files",
+        file_count = file_paths.len(),
+        dry_run = options.dry_run,
+        dedupe = options.dedupe,
+        "Starting markdown pipeline"
+    );
+
+    let changelog_path = match file_paths.first() {
+        Some(first_file) => changelog_target(
+            options.write_changelog,
+            options.changelog_path.as_deref(),
+            first_file,
+        )
+        .map_err(|e| e.to_string())?,
+        None => None,
+    };
+
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_name("process_markdown_files")
+        .with_max_image_size(settings.max_image_bytes())
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_asset_roots(
+            asset_roots_for_scan(options.asset_roots.clone(), &settings)
+                .map_err(|e| e.to_string())?,
+        )
+        .with_scan_globs(
+            scan_globs_for_scan(Some(options.scan_globs.clone()), &settings)
+                .map_err(|e| e.to_string())?,
+        )
+        .with_operation_log(file_operation_log());
+    let scan_results = file_service
+        .scan_markdown_files(file_paths)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let missing_images: Vec<MissingImage> = scan_results
+        .iter()
+        .flat_map(|result| result.missing_images.iter().cloned())
+        .collect();
+    if options.abort_on_missing {
+        if let Some(first) = missing_images.first() {
+            return Err(format!(
+                "{} referenced image(s) are missing, first: {} at {}:{}",
+                missing_images.len(),
+                first.original_path,
+                first.file_path,
+                first.line
+            ));
+        }
+    }
+
+    // Missing images are skipped: they stay in the report but get no upload
+    let groups: Vec<UniqueImage> = file_service
+        .group_scan_results(&scan_results)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|group| group.exists)
+        .collect();
+
+    let image_service = ImageService::new()
+        .with_checksum_algorithm(settings.checksum_algorithm)
+        .with_max_image_size(settings.max_image_bytes());
+    let history_service = if options.dedupe {
+        HistoryService::new().ok()
+    } else {
+        None
+    };
+    let dedupe_target = UploadTarget::new(saved_config_id(&config).await, &config);
+    let processing = ImageProcessingOptions::from_config(&config);
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+
+    let mut uploads = Vec::with_capacity(groups.len());
+    let mut skipped_oversized = Vec::new();
+    for group in &groups {
+        if let Err(AppError::FileTooLarge(error)) = check_upload_size(
+            &oss_service,
+            &image_service,
+            &processing,
+            &group.absolute_path,
+        ) {
+            skipped_oversized.push(group.absolute_path.clone());
+            uploads.push(PipelineUpload {
+                image_id: group.id.clone(),
+                absolute_path: group.absolute_path.clone(),
+                reused: false,
+                uploaded_url: None,
+                usage_count: group.usages.len(),
+                error: Some(AppError::FileTooLarge(error).to_string()),
+                queued_offline: false,
+                warnings: Vec::new(),
+            });
+            continue;
+        }
+
+        let mut reused_url = None;
+        if let Some(history_service) = &history_service {
+            if let Ok(checksum) = image_service.calculate_checksum(&group.absolute_path).await {
+                if let Ok(Some(record)) = history_service
+                    .find_duplicate_by_checksum(
+                        &checksum,
+                        Some(&dedupe_target),
+                        options.dedupe_scope,
+                    )
+                    .await
+                {
+                    reused_url = Some(record.uploaded_url);
+                }
+            }
+        }
+
+        uploads.push(PipelineUpload {
+            image_id: group.id.clone(),
+            absolute_path: group.absolute_path.clone(),
+            reused: reused_url.is_some(),
+            uploaded_url: reused_url,
+            usage_count: group.usages.len(),
+            error: None,
+            queued_offline: false,
+            warnings: Vec::new(),
+        });
+    }
+
+    if !options.dry_run {
+        upload_pipeline_images(
+            &groups,
+            &mut uploads,
+            &config,
+            oss_service,
+            &image_service,
+            &settings,
+            options.preserve_link_suffix,
+            &upload_session(options.session_id.clone()),
+        )
+        .await?;
+        record_skipped_oversized("process_markdown_files", skipped_oversized);
+    }
+
+    // What the real run would upload: not reused and not skipped
+    let estimate = if options.dry_run {
+        let to_upload: Vec<String> = uploads
+            .iter()
+            .filter(|upload| !upload.reused && upload.error.is_none())
+            .map(|upload| upload.absolute_path.clone())
+            .collect();
+        let config_id = saved_config_id(&config).await;
+        Some(
+            estimate_uploads(&to_upload, &config, config_id.as_deref(), &settings)
+                .await
+                .map_err(|e| e.to_string())?,
+        )
+    } else {
+        None
+    };
+
+    let replacements: Vec<LinkReplacement> = groups
+        .iter()
+        .zip(&uploads)
+        .filter_map(|(group, upload)| {
+            upload
+                .uploaded_url
+                .as_deref()
+                .map(|url| group.link_replacements(url, options.preserve_link_suffix))
+        })
+        .flatten()
+        .collect();
+
+    let mut report = ProcessMarkdownReport {
+        dry_run: options.dry_run,
+        scan_results,
+        missing_images,
+        uploads,
+        replacements,
+        replacement_result: None,
+        backups: Vec::new(),
+        changelog_path: None,
+        estimate,
+        revalidation: None,
+    };
+
+    if options.dry_run || report.replacements.is_empty() {
+        return Ok(report);
+    }
+
+    // Files edited while the images uploaded no longer have the links where
+    // the scan found them
+    let revalidation = file_service
+        .revalidate_scan_results(&report.scan_results)
+        .await;
+    if !revalidation.changed_files.is_empty() {
+        match options.on_changed_files {
+            StaleFilePolicy::Abort => {
+                let stale: Vec<&str> = revalidation
+                    .changed_files
+                    .iter()
+                    .filter(|file| report.replacements.iter().any(|r| &r.file_path == *file))
+                    .map(String::as_str)
+                    .collect();
+                if !stale.is_empty() {
+                    return Err(format!(
+                        "{} file(s) changed since they were scanned, nothing was replaced: {}",
+                        stale.len(),
+                        stale.join(", ")
+                    ));
+                }
+            }
+            StaleFilePolicy::Rescan => {
+                rescan_changed_files(
+                    &file_service,
+                    &mut report,
+                    &groups,
+                    &revalidation.changed_files,
+                    options.preserve_link_suffix,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    report.revalidation = Some(revalidation);
+    if report.replacements.is_empty() {
+        return Ok(report);
+    }
+
+    // Back up every file before the first one is rewritten
+    let backup_service = BackupService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_log(file_operation_log());
+    report.backups = backup_replacement_targets(&backup_service, &report.replacements)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut result = file_service
+        .replace_image_links_batch(report.replacements.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+    journal_replacement("process_markdown_files", &report.backups).await;
+    apply_backup_retention(&backup_service, &settings).await;
+
+    if let Some(path) = changelog_path {
+        save_replacement_changelog(&mut result, &path)
+            .await
+            .map_err(|e| e.to_string())?;
+        report.changelog_path = Some(path.to_string_lossy().to_string());
+    }
+
+    log_info!(
+        operation = "process_markdown_files",
+        uploaded = report
+            .uploads
+            .iter()
+            .filter(|u| u.uploaded_url.is_some())
+            .count(),
+        replaced = result.total_successful_replacements,
+        failed = result.total_failed_replacements,
+        "Markdown pipeline finished"
+    );
+
+    report.replacement_result = Some(result);
+    Ok(report)
+}
+
+// Upload the groups that weren't reused from history, bounded by upload_concurrency
+/// Upload the references `selected_image_ids` of `scan_results` to the saved
+/// config `config_id`, or the article default when it's None. Each file is uploaded once however often it's selected,
+/// history records the markdown file as source in article mode, and every
+/// selected reference comes back, in document order, with its result and the
+/// replacement for its link.
+#[tauri::command]
+pub async fn upload_article_images(
+    scan_results: Vec<ScanResult>,
+    selected_image_ids: Vec<String>,
+    config_id: Option<String>,
+    session_id: Option<String>,
+    window_session: Option<String>,
+) -> Result<Vec<ArticleImageUpload>, String> {
+    let started = Instant::now();
+    UPLOAD_RATE_LIMITER
+        .check_rate_limit("upload_images")
+        .map_err(|e| e.to_string())?;
+
+    let settings = current_app_settings().await;
+    validate_upload_count(selected_image_ids.len(), settings.max_upload_images)
+        .map_err(|e| e.to_string())?;
+    let article =
+        select_article_images(scan_results, &selected_image_ids).map_err(|e| e.to_string())?;
+    for result in &article {
+        validate_user_path(&result.file_path, PathKind::Markdown).map_err(|e| e.to_string())?;
+        for image in &result.images {
+            validate_user_path_maybe_missing(&image.absolute_path, PathKind::Image)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let current = saved_upload_config(
+        config_id.as_deref(),
+        window_session.as_deref(),
+        &UploadMode::ArticleUpload,
+    )
+    .await?;
+    let config_id = current.id;
+    let config = current.config;
+    validate_oss_config_params(&config).map_err(|e| e.to_string())?;
+
+    log_info!(
+        operation = "upload_article_images",
+        file_count = article.len(),
+        selected_count = selected_image_ids.len(),
+        config_id = %config_id,
+        "Starting article image upload"
+    );
+
+    let image_service = ImageService::new()
+        .with_checksum_algorithm(settings.checksum_algorithm)
+        .with_max_image_size(settings.max_image_bytes());
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_max_image_size(settings.max_image_bytes());
+    let mut groups = file_service
+        .group_scan_results(&article)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Upload progress is reported under a reference id the frontend knows
+    for group in &mut groups {
+        if let Some(usage) = group.usages.first() {
+            group.id = usage.image_id.clone();
+        }
+    }
+
+    let processing = ImageProcessingOptions::from_config(&config);
+    let oss_service = OSS_SERVICES.get(&config).map_err(|e| e.to_string())?;
+    let mut skipped_oversized = Vec::new();
+    let mut uploads: Vec<PipelineUpload> = groups
+        .iter()
+        .map(|group| {
+            let error = if !group.exists {
+                Some(format!("Image file not found: {}", group.absolute_path))
+            } else if let Err(AppError::FileTooLarge(error)) = check_upload_size(
+                &oss_service,
+                &image_service,
+                &processing,
+                &group.absolute_path,
+            ) {
+                skipped_oversized.push(group.absolute_path.clone());
+                Some(AppError::FileTooLarge(error).to_string())
+            } else {
+                None
+            };
+            PipelineUpload {
+                image_id: group.id.clone(),
+                absolute_path: group.absolute_path.clone(),
+                uploaded_url: None,
+                reused: false,
+                usage_count: group.usages.len(),
+                error,
+                queued_offline: false,
+                warnings: Vec::new(),
+            }
+        })
+        .collect();
+
+    let session_id = upload_session(session_id);
+    upload_pipeline_images(
+        &groups,
+        &mut uploads,
+        &config,
+        oss_service,
+        &image_service,
+        &settings,
+        false,
+        &session_id,
+    )
+    .await?;
+    record_skipped_oversized("upload_article_images", skipped_oversized);
+
+    let uploaded = uploads.iter().filter(|u| u.uploaded_url.is_some()).count();
+    let queued = uploads.iter().filter(|u| u.queued_offline).count();
+    let failed = uploads.len() - uploaded - queued;
+    log_info!(
+        operation = "upload_article_images",
+        unique_images = uploads.len(),
+        uploaded,
+        failed,
+        queued_offline = queued,
+        "Article image upload completed"
+    );
+    notify_batch_finished(BatchSummary::new(uploaded, failed, started).in_session(&session_id))
+        .await;
+
+    Ok(article_image_uploads(&article, &groups, &uploads))
+}
+
+/// `scan_results` narrowed to the references in `selected_ids`, files without
+/// any selected reference dropped. Every selected id must be found.
+pub fn select_article_images(
+    scan_results: Vec<ScanResult>,
+    selected_ids: &[String],
+) -> Result<Vec<ScanResult>, AppError> {
+    if selected_ids.is_empty() {
+        return Err(AppError::Validation("No images selected".to_string()));
+    }
+    let selected: HashSet<&str> = selected_ids.iter().map(String::as_str).collect();
+
+    let article: Vec<ScanResult> = scan_results
+        .into_iter()
+        .filter_map(|mut result| {
+            result
+                .images
+                .retain(|image| selected.contains(image.id.as_str()));
+            (!result.images.is_empty()).then_some(result)
+        })
+        .collect();
+
+    let found: HashSet<&str> = article
+        .iter()
+        .flat_map(|result| &result.images)
+        .map(|image| image.id.as_str())
+        .collect();
+    if let Some(missing) = selected.iter().find(|id| !found.contains(*id)) {
+        return Err(AppError::Validation(format!(
+            "Selected image not in scan results: {}",
+            missing
+        )));
+    }
+    Ok(article)
+}
+
+/// Pair every reference of `article` with the outcome of the group it was
+/// uploaded in. `uploads` is parallel to `groups`.
+pub fn article_image_uploads(
+    article: &[ScanResult],
+    groups: &[UniqueImage],
+    uploads: &[PipelineUpload],
+) -> Vec<ArticleImageUpload> {
+    let mut by_reference: HashMap<&str, ArticleImageUpload> = HashMap::new();
+    for (group, upload) in groups.iter().zip(uploads) {
+        let replacements = upload
+            .uploaded_url
+            .as_deref()
+            .map(|url| group.link_replacements(url, false))
+            .unwrap_or_default();
+        for (index, usage) in group.usages.iter().enumerate() {
+            by_reference.insert(
+                usage.image_id.as_str(),
+                ArticleImageUpload {
+                    result: UploadResult {
+                        image_id: usage.image_id.clone(),
+                        success: upload.uploaded_url.is_some(),
+                        uploaded_url: upload.uploaded_url.clone(),
+                        error: upload.error.clone(),
+                        variants: Vec::new(),
+                        queued_offline: upload.queued_offline,
+                        warnings: upload.warnings.clone(),
+                    },
+                    replacement: replacements.get(index).cloned(),
+                },
             );
-            return Err("Invalid file path detected in replacement".to_string());
         }
+    }
 
-        let path = Path::new(&replacement.file_path);
-        if !path.exists() {
-            log_error!(
-                operation = "replace_markdown_links_command",
-                replacement_index = index,
-                file_path = %replacement.file_path,
-                error = "File not found",
-                "File validation failed"
-            );
-            return Err(format!("File not found: {}", replacement.file_path));
+    article
+        .iter()
+        .flat_map(|result| &result.images)
+        .filter_map(|image| by_reference.remove(image.id.as_str()))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_pipeline_images(
+    groups: &[UniqueImage],
+    uploads: &mut [PipelineUpload],
+    config: &OSSConfig,
+    oss_service: Arc<OSSService>,
+    image_service: &ImageService,
+    settings: &AppSettings,
+    preserve_link_suffix: bool,
+    session_id: &str,
+) -> Result<(), String> {
+    let config_id = saved_config_id(config).await;
+    let upload_target = UploadTarget::new(config_id.clone(), config);
+    let processing = ImageProcessingOptions::from_config(config);
+    let path_template = config.path_template.clone();
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(
+        settings.upload_concurrency.max(1),
+    ));
+
+    let pending: Vec<PendingUpload> = groups
+        .iter()
+        .zip(uploads.iter())
+        .filter(|(_, upload)| upload.uploaded_url.is_none() && upload.error.is_none())
+        .map(|(group, _)| {
+            pending_upload(
+                &group.id,
+                &group.absolute_path,
+                config_id.clone(),
+                session_id,
+                group.usages.first().map(|usage| usage.file_path.clone()),
+                UploadMode::ArticleUpload,
+            )
+        })
+        .collect();
+    enqueue_uploads(pending);
+
+    let mut tasks = Vec::new();
+    for (index, group) in groups.iter().enumerate() {
+        // Reused from history, or skipped before upload (oversized)
+        if uploads[index].uploaded_url.is_some() || uploads[index].error.is_some() {
+            continue;
+        }
+
+        let oss_service = oss_service.clone();
+        let image_service = image_service.clone();
+        let processing = processing.clone();
+        let path_template = path_template.clone();
+        let semaphore = semaphore.clone();
+        let image_id = group.id.clone();
+        let image_path = group.absolute_path.clone();
+        let source_file = group.usages.first().map(|usage| usage.file_path.clone());
+        let upload_target = upload_target.clone();
+
+        let task = tokio::spawn(async move {
+            let _permit = match semaphore.acquire().await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    return UploadResult {
+                        image_id,
+                        success: false,
+                        uploaded_url: None,
+                        error: Some(e.to_string()),
+                        variants: Vec::new(),
+                        queued_offline: false,
+                        warnings: Vec::new(),
+                    };
+                }
+            };
+
+            upload_tracked_image(
+                &oss_service,
+                &image_service,
+                &image_path,
+                &image_id,
+                &processing,
+                &path_template,
+                UploadMode::ArticleUpload,
+                source_file,
+                upload_target,
+            )
+            .await
+        });
+        tasks.push((index, task));
+    }
+
+    for (index, task) in tasks {
+        match task.await {
+            Ok(result) => {
+                // The links of a queued upload are rewritten when the offline
+                // queue is flushed, the caller won't see its URL
+                if result.queued_offline {
+                    PENDING_UPLOADS.defer_links(
+                        &result.image_id,
+                        DeferredLinks {
+                            usages: groups[index].usages.clone(),
+                            preserve_link_suffix,
+                        },
+                    );
+                }
+                uploads[index].uploaded_url = result.uploaded_url;
+                uploads[index].error = result.error;
+                uploads[index].queued_offline = result.queued_offline;
+                uploads[index].warnings = result.warnings;
+            }
+            Err(e) => uploads[index].error = Some(format!("Task join error: {}", e)),
         }
     }
+    PROGRESS_NOTIFIER.end_session(session_id);
+
+    Ok(())
+}
+
+/// Classify every image link of the files as local, on the config `config_id`,
+/// on another saved config, or external, with counts per file
+#[tauri::command]
+pub async fn analyze_markdown_links(
+    file_paths: Vec<String>,
+    config_id: String,
+) -> Result<LinkAnalysisReport, String> {
+    let settings = current_app_settings().await;
+    validate_file_paths(&file_paths, settings.max_scan_files).map_err(|e| e.to_string())?;
+    let (current, configs) = load_config_with_others(&config_id).await?;
+
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_max_markdown_size(settings.max_markdown_bytes());
+    Ok(analyze_links(&file_service, &file_paths, &current, &configs).await)
+}
+
+/// Move links that point at other saved configs over to the config `config_id`.
+/// Each object is moved once however often it is linked, then every link to it
+/// is rewritten; files are backed up first. Dry runs only plan the rewrite.
+#[tauri::command]
+pub async fn migrate_config_links(
+    file_paths: Vec<String>,
+    config_id: String,
+    mode: Option<LinkMigrationMode>,
+    dry_run: Option<bool>,
+) -> Result<LinkMigrationReport, String> {
+    let settings = current_app_settings().await;
+    validate_file_paths(&file_paths, settings.max_scan_files).map_err(|e| e.to_string())?;
+    let (current, configs) = load_config_with_others(&config_id).await?;
+    let mode = mode.unwrap_or_default();
+    let dry_run = dry_run.unwrap_or(false);
+
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_name("migrate_config_links")
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_operation_log(file_operation_log());
+    let analysis = analyze_links(&file_service, &file_paths, &current, &configs).await;
 
     log_info!(
-        operation = "replace_markdown_links_command",
-        replacement_count = replacements.len(),
-        "All replacements validated successfully, proceeding with file service"
+        operation = "migrate_config_links",
+        config_id = %config_id,
+        mode = ?mode,
+        dry_run = dry_run,
+        other_config_links = analysis.files.iter().map(|f| f.other_config).sum::<usize>(),
+        "Migrating links from other configs"
     );
 
-    let file_service = FileService::new().map_err(|e| {
-        log_error!(
-            operation = "replace_markdown_links_command",
-            error = %e,
-            "Failed to create FileService"
-        );
-        e.to_string()
-    })?;
+    let target = OSS_SERVICES
+        .get(&current.config)
+        .map_err(|e| e.to_string())?;
+    let mut objects: Vec<ObjectMigration> = Vec::new();
+    for link in analysis.files.iter().flat_map(|file| &file.links) {
+        let (LinkLocation::OtherConfig, Some(source_id), Some(key)) =
+            (link.location, &link.config_id, &link.object_key)
+        else {
+            continue;
+        };
+        if objects
+            .iter()
+            .any(|o| &o.source_config_id == source_id && &o.object_key == key)
+        {
+            continue;
+        }
 
-    let result = file_service
-        .replace_image_links_batch(replacements)
-        .await
-        .map_err(|e| {
-            log_error!(
-                operation = "replace_markdown_links_command",
+        let source = configs.iter().find(|item| &item.id == source_id);
+        let outcome = match (dry_run, source) {
+            (true, _) => Ok(target.object_url(key)),
+            (false, Some(source)) => migrate_object(&target, &current, source, key, mode).await,
+            (false, None) => Err(AppError::Configuration(format!(
+                "Config not found: {}",
+                source_id
+            ))),
+        };
+        if let Err(e) = &outcome {
+            log_warn!(
+                operation = "migrate_config_links",
+                object_key = %key,
+                source_config_id = %source_id,
                 error = %e,
-                "FileService batch replacement failed"
+                "Object could not be migrated"
             );
-            e.to_string()
-        })?;
+        }
+        objects.push(ObjectMigration {
+            source_config_id: source_id.clone(),
+            object_key: key.clone(),
+            new_url: outcome.as_ref().ok().cloned(),
+            error: outcome.err().map(|e| e.to_string()),
+        });
+    }
 
-    log_info!(
-        operation = "replace_markdown_links_command",
-        successful_replacements = result.total_successful_replacements,
-        failed_replacements = result.total_failed_replacements,
-        total_files = result.total_files,
-        "Link replacement completed"
-    );
+    let replacements: Vec<LinkReplacement> = analysis
+        .files
+        .iter()
+        .flat_map(|file| file.links.iter().map(move |link| (file, link)))
+        .filter_map(|(file, link)| {
+            let new_url = objects
+                .iter()
+                .find(|o| {
+                    link.config_id.as_ref() == Some(&o.source_config_id)
+                        && link.object_key.as_ref() == Some(&o.object_key)
+                })?
+                .new_url
+                .clone()?;
+            Some(LinkReplacement {
+                file_path: file.file_path.clone(),
+                line: link.line,
+                column: link.column,
+                old_link: link.link.clone(),
+                new_link: new_url,
+                context: link.context,
+            })
+        })
+        .collect();
+
+    let mut report = LinkMigrationReport {
+        dry_run,
+        mode,
+        analysis,
+        objects,
+        replacements,
+        replacement_result: None,
+        backups: Vec::new(),
+    };
+    if dry_run || report.replacements.is_empty() {
+        return Ok(report);
+    }
 
-    Ok(())
+    let backup_service = BackupService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_log(file_operation_log());
+    report.backups = backup_replacement_targets(&backup_service, &report.replacements)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let result = file_service
+        .replace_image_links_batch(report.replacements.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+    journal_replacement("migrate_config_links", &report.backups).await;
+    apply_backup_retention(&backup_service, &settings).await;
+
+    report.replacement_result = Some(result);
+    Ok(report)
 }
 
+/// Move uploaded URLs from `old_domain` to `new_domain` (e.g. a new CDN in front
+/// of the same bucket): links in the files, or in every markdown file named as
+/// a source in upload history, get their host swapped with backups taken first,
+/// and `update_history` rewrites the matching history records too. `strict`
+/// leaves links alone whose path matches no uploaded object in history.
 #[tauri::command]
-pub async fn replace_markdown_links_with_result(
-    replacements: Vec<LinkReplacement>,
-) -> Result<BatchReplacementResult, String> {
-    // Validate input parameters
-    if replacements.is_empty() {
-        return Err("Replacements cannot be empty".to_string());
+pub async fn migrate_cdn_domain(
+    old_domain: String,
+    new_domain: String,
+    file_paths: Option<Vec<String>>,
+    update_history: bool,
+    strict: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<CdnMigrationSummary, String> {
+    let old_host = domain_host(&old_domain).map_err(|e| e.to_string())?;
+    let new_host = domain_host(&new_domain).map_err(|e| e.to_string())?;
+    if old_host.eq_ignore_ascii_case(new_host) {
+        return Err("Old and new domain are the same".to_string());
     }
+    let strict = strict.unwrap_or(false);
+    let dry_run = dry_run.unwrap_or(false);
 
-    if replacements.len() > 1000 {
-        return Err("Too many replacements (max 1000)".to_string());
-    }
+    let settings = current_app_settings().await;
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    let records = history_service
+        .get_upload_records(None)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    // Validate each replacement
-    for replacement in &replacements {
-        if replacement.file_path.is_empty() {
-            return Err("File path cannot be empty in replacement".to_string());
+    let file_paths = match file_paths {
+        Some(paths) => {
+            validate_file_paths(&paths, settings.max_scan_files).map_err(|e| e.to_string())?;
+            paths
         }
-
-        if replacement.old_link.is_empty() {
-            return Err("Old link cannot be empty in replacement".to_string());
+        None => {
+            let mut paths: Vec<String> = Vec::new();
+            for source in records.iter().filter_map(|r| r.source_file.as_ref()) {
+                if !paths.contains(source) && Path::new(source).is_file() {
+                    paths.push(source.clone());
+                }
+            }
+            paths
         }
+    };
 
-        if replacement.new_link.is_empty() {
-            return Err("New link cannot be empty in replacement".to_string());
-        }
+    log_info!(
+        operation = "migrate_cdn_domain",
+        old_domain = %old_domain,
+        new_domain = %new_domain,
+        file_count = file_paths.len(),
+        strict = strict,
+        dry_run = dry_run,
+        "Migrating CDN domain"
+    );
 
-        // Security check: prevent path traversal
-        if replacement.file_path.contains("..") || replacement.file_path.contains("~") {
-            return Err("Invalid file path detected in replacement".to_string());
+    // Paths of objects history knows about, on either domain
+    let known_paths: std::collections::HashSet<String> = records
+        .iter()
+        .filter_map(|record| {
+            replace_url_host(&record.uploaded_url, &old_domain, &new_domain)
+                .or_else(|| replace_url_host(&record.uploaded_url, &new_domain, &new_domain))
+        })
+        .map(|url| url_object_path(&url).to_string())
+        .collect();
+
+    let file_service = FileService::new()
+        .map_err(|e| e.to_string())?
+        .with_operation_name("migrate_cdn_domain")
+        .with_max_markdown_size(settings.max_markdown_bytes())
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .with_operation_log(file_operation_log());
+    let mut replacements = Vec::new();
+    let mut skipped_replacements = Vec::new();
+    for file_path in &file_paths {
+        let references = match file_service.all_image_references(file_path).await {
+            Ok(references) => references,
+            Err(e) => {
+                log_warn!(
+                    operation = "migrate_cdn_domain",
+                    file_path = %file_path,
+                    error = %e,
+                    "File skipped"
+                );
+                continue;
+            }
+        };
+        for reference in references {
+            let Some(new_link) =
+                replace_url_host(&reference.original_path, &old_domain, &new_domain)
+            else {
+                continue;
+            };
+            let replacement = LinkReplacement {
+                file_path: file_path.clone(),
+                line: reference.markdown_line,
+                column: reference.markdown_column,
+                old_link: reference.original_path,
+                new_link,
+                context: reference.context,
+            };
+            if strict && !known_paths.contains(url_object_path(&replacement.new_link)) {
+                skipped_replacements.push(replacement);
+            } else {
+                replacements.push(replacement);
+            }
         }
+    }
 
-        let path = Path::new(&replacement.file_path);
-        if !path.exists() {
-            return Err(format!("File not found: {}", replacement.file_path));
+    let mut files_changed: Vec<String> = Vec::new();
+    for replacement in &replacements {
+        if !files_changed.contains(&replacement.file_path) {
+            files_changed.push(replacement.file_path.clone());
         }
     }
 
-    let file_service = FileService::new().map_err(|e| e.to_string())?;
-    file_service
-        .replace_image_links_batch(replacements)
-        .await
-        .map_err(|e| e.to_string())
-}
+    let mut summary = CdnMigrationSummary {
+        dry_run,
+        files_scanned: file_paths.len(),
+        files_changed,
+        replacements,
+        skipped_replacements,
+        replacement_result: None,
+        backups: Vec::new(),
+        records_updated: 0,
+    };
 
-#[tauri::command]
-pub async fn replace_single_file_links(
-    file_path: String,
-    replacements: Vec<LinkReplacement>,
-) -> Result<ReplacementResult, String> {
-    // Validate input parameters
-    if file_path.is_empty() {
-        return Err("File path cannot be empty".to_string());
+    if !dry_run && !summary.replacements.is_empty() {
+        let backup_service = BackupService::new()
+            .map_err(|e| e.to_string())?
+            .with_operation_log(file_operation_log());
+        summary.backups = backup_replacement_targets(&backup_service, &summary.replacements)
+            .await
+            .map_err(|e| e.to_string())?;
+        let result = file_service
+            .replace_image_links_batch(summary.replacements.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        journal_replacement("migrate_cdn_domain", &summary.backups).await;
+        apply_backup_retention(&backup_service, &settings).await;
+        summary.replacement_result = Some(result);
     }
 
-    if replacements.is_empty() {
-        return Err("Replacements cannot be empty".to_string());
+    if update_history {
+        let updated = history_service
+            .update_upload_records(
+                |record| {
+                    let mut changed = false;
+                    if let Some(url) =
+                        replace_url_host(&record.uploaded_url, &old_domain, &new_domain)
+                    {
+                        record.uploaded_url = url;
+                        changed = true;
+                    }
+                    for variant in &mut record.variants {
+                        if let Some(url) = replace_url_host(&variant.url, &old_domain, &new_domain)
+                        {
+                            variant.url = url;
+                            changed = true;
+                        }
+                    }
+                    changed
+                },
+                dry_run,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        summary.records_updated = updated.len();
     }
 
-    if replacements.len() > 100 {
-        return Err("Too many replacements for single file (max 100)".to_string());
-    }
+    log_info!(
+        operation = "migrate_cdn_domain",
+        files_changed = summary.files_changed.len(),
+        links = summary.replacements.len(),
+        skipped = summary.skipped_replacements.len(),
+        records_updated = summary.records_updated,
+        "CDN domain migration finished"
+    );
 
-    // Security check: prevent path traversal
-    if file_path.contains("..") || file_path.contains("~") {
-        return Err("Invalid file path detected".to_string());
-    }
+    Ok(summary)
+}
 
-    let path = Path::new(&file_path);
-    if !path.exists() {
-        return Err(format!("File not found: {}", file_path));
+// Host of a domain given as a bare host name, optionally with a scheme
+fn domain_host(domain: &str) -> Result<&str, AppError> {
+    let host = domain
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+    if host.is_empty() || host.contains(['/', '?', '#', ' ']) {
+        return Err(AppError::Validation(format!(
+            "Invalid domain: '{}'",
+            domain
+        )));
     }
+    Ok(host)
+}
 
-    // Validate each replacement
-    for replacement in &replacements {
-        if replacement.file_path != file_path {
-            return Err("All replacements must be for the same file".to_string());
+// Path of a URL without scheme, host, query or fragment
+fn url_object_path(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = rest.find('/').map_or("", |start| &rest[start..]);
+    path.split(['?', '#']).next().unwrap_or_default()
+}
+
+// Scan `changed` files of a pipeline run again and swap in their new scan
+// results and replacements: links found now are rewritten when their image was
+// uploaded (or reused) in this run
+async fn rescan_changed_files(
+    file_service: &FileService,
+    report: &mut ProcessMarkdownReport,
+    groups: &[UniqueImage],
+    changed: &[String],
+    preserve_suffix: bool,
+) -> Result<(), AppError> {
+    // Groups and uploads are built in the same order
+    let mut uploaded: HashMap<std::path::PathBuf, String> = HashMap::new();
+    for (group, upload) in groups.iter().zip(&report.uploads) {
+        if let Some(url) = &upload.uploaded_url {
+            for path in std::iter::once(&group.absolute_path).chain(&group.duplicate_paths) {
+                uploaded.insert(std::path::PathBuf::from(path), url.clone());
+            }
         }
+    }
 
-        if replacement.old_link.is_empty() {
-            return Err("Old link cannot be empty in replacement".to_string());
+    log_info!(
+        operation = "process_markdown_files",
+        file_count = changed.len(),
+        "Rescanning files changed since the scan"
+    );
+    let rescanned = file_service.scan_markdown_files(changed.to_vec()).await?;
+    report
+        .replacements
+        .retain(|replacement| !changed.contains(&replacement.file_path));
+    for result in rescanned {
+        for image in &result.images {
+            // Grouping keys images by canonical path
+            let canonical = std::fs::canonicalize(&image.absolute_path)
+                .unwrap_or_else(|_| std::path::PathBuf::from(&image.absolute_path));
+            if let Some(url) = uploaded.get(&canonical) {
+                report.replacements.push(
+                    ImageUsage::of(&result.file_path, image).link_replacement(url, preserve_suffix),
+                );
+            }
+        }
+        match report
+            .scan_results
+            .iter_mut()
+            .find(|scanned| scanned.file_path == result.file_path)
+        {
+            Some(scanned) => *scanned = result,
+            None => report.scan_results.push(result),
         }
+    }
+    report.missing_images = report
+        .scan_results
+        .iter()
+        .flat_map(|result| result.missing_images.iter().cloned())
+        .collect();
+    Ok(())
+}
 
-        if replacement.new_link.is_empty() {
-            return Err("New link cannot be empty in replacement".to_string());
+// Back up every file the replacements touch before the first one is rewritten
+async fn backup_replacement_targets(
+    backup_service: &BackupService,
+    replacements: &[LinkReplacement],
+) -> Result<Vec<BackupInfo>, AppError> {
+    let mut touched_files: Vec<&str> = Vec::new();
+    for replacement in replacements {
+        if !touched_files.contains(&replacement.file_path.as_str()) {
+            touched_files.push(&replacement.file_path);
         }
     }
 
-    let file_service = FileService::new().map_err(|e| e.to_string())?;
-    file_service
-        .replace_image_links(&file_path, replacements)
+    let mut backups = Vec::with_capacity(touched_files.len());
+    for file_path in touched_files {
+        backups.push(backup_service.create_backup(file_path).await?);
+    }
+    Ok(backups)
+}
+
+// The saved config `config_id` and every saved config
+async fn load_config_with_others(config_id: &str) -> Result<(ConfigItem, Vec<ConfigItem>), String> {
+    if config_id.is_empty() {
+        return Err("Config ID cannot be empty".to_string());
+    }
+
+    let configs = ConfigService::new()
+        .map_err(|e| e.to_string())?
+        .load_all_configs()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?
+        .configs;
+    let current = configs
+        .iter()
+        .find(|item| item.id == config_id)
+        .cloned()
+        .ok_or_else(|| format!("Config not found: {}", config_id))?;
+    Ok((current, configs))
+}
+
+// Files that can't be read are reported with their error instead of failing the run
+async fn analyze_links(
+    file_service: &FileService,
+    file_paths: &[String],
+    current: &ConfigItem,
+    configs: &[ConfigItem],
+) -> LinkAnalysisReport {
+    let mut files = Vec::with_capacity(file_paths.len());
+    for file_path in file_paths {
+        let analysis = match file_service.all_image_references(file_path).await {
+            Ok(references) => analyze_file(file_path, &references, current, configs),
+            Err(e) => {
+                let mut analysis = analyze_file(file_path, &[], current, configs);
+                analysis.error = Some(e.to_string());
+                analysis
+            }
+        };
+        files.push(analysis);
+    }
+
+    LinkAnalysisReport {
+        config_id: current.id.clone(),
+        other_configs: other_config_counts(&files, configs),
+        files,
+    }
+}
+
+// Put `key` of `source` into `target`, returning its URL there
+async fn migrate_object(
+    target: &OSSService,
+    current: &ConfigItem,
+    source: &ConfigItem,
+    key: &str,
+    mode: LinkMigrationMode,
+) -> Result<String, AppError> {
+    match mode {
+        LinkMigrationMode::RewriteUrl => {
+            target.get_object_metadata(key).await.map_err(|e| {
+                AppError::Validation(format!(
+                    "{} is not in the current bucket ({}), migrate with reupload instead",
+                    key, e
+                ))
+            })?;
+            Ok(target.object_url(key))
+        }
+        LinkMigrationMode::Reupload => {
+            let data = OSS_SERVICES.get(&source.config)?.fetch_object(key).await?;
+            target.check_object_size(data.len() as u64)?;
+            let uploaded = target.upload_image(key, &data, None).await?;
+            let url = uploaded.url;
+
+            let checksum = ImageService::new()
+                .calculate_checksum_from_data(&data)
+                .await
+                .unwrap_or_default();
+            let upload_target = UploadTarget::new(Some(current.id.clone()), &current.config);
+            let record = UploadHistoryRecord {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: chrono::Utc::now(),
+                image_name: key.rsplit('/').next().unwrap_or(key).to_string(),
+                uploaded_url: url.clone(),
+                upload_mode: UploadMode::Reupload,
+                source_file: None,
+                file_size: data.len() as u64,
+                checksum,
+                variants: Vec::new(),
+                config_id: upload_target.config_id,
+                bucket: upload_target.bucket,
+                original_path: None,
+                success: true,
+                error_message: None,
+                request_id: uploaded.request_id,
+                original_archived: false,
+                object_key: Some(key.to_string()),
+            };
+            // The migration report has no per-object warnings, a failed write
+            // reaches the UI through the health event
+            record_upload(record).await;
+            Ok(url)
+        }
+    }
 }
 
 // ============================================================================
@@ -1750,6 +5791,8 @@ pub async fn replace_single_file_links(
 pub async fn get_upload_history(
     page: Option<usize>,
     page_size: Option<usize>,
+    sort_by: Option<HistorySortField>,
+    sort_order: Option<SortOrder>,
 ) -> Result<PaginatedResult<UploadHistoryRecord>, String> {
     // Validate pagination parameters
     let (validated_page, validated_page_size) =
@@ -1764,29 +5807,28 @@ pub async fn get_upload_history(
         end_date: None,
         limit: Some(validated_page_size),
         offset: Some(offset),
+        search_term: None,
+        sort_by,
+        sort_order,
+        include_trashed: false,
     };
 
-    let service_records = history_service
-        .get_upload_records(Some(query))
-        .await
-        .map_err(|e| e.to_string())?;
-    let all_records = history_service
-        .get_upload_records(None)
+    let (service_records, total) = history_service
+        .get_upload_records_page(query)
         .await
         .map_err(|e| e.to_string())?;
-    let total = all_records.len();
 
     // 直接返回服务记录，不需要转换
-    Ok(PaginatedResult {
-        items: service_records,
+    Ok(PaginatedResult::new(
+        service_records,
         total,
-        page: validated_page,
-        page_size: validated_page_size,
-        has_more: offset + validated_page_size < total,
-    })
+        validated_page,
+        validated_page_size,
+    ))
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_history(
     search_term: Option<String>,
     upload_mode: Option<String>,
@@ -1794,6 +5836,8 @@ pub async fn search_history(
     end_date: Option<String>,
     page: Option<usize>,
     page_size: Option<usize>,
+    sort_by: Option<HistorySortField>,
+    sort_order: Option<SortOrder>,
 ) -> Result<PaginatedResult<UploadHistoryRecord>, String> {
     // Validate pagination parameters
     let (validated_page, validated_page_size) =
@@ -1802,15 +5846,7 @@ pub async fn search_history(
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
 
     // Parse upload mode
-    let parsed_upload_mode = if let Some(mode) = upload_mode {
-        match mode.as_str() {
-            "ImageUpload" => Some(UploadMode::ImageUpload),
-            "ArticleUpload" => Some(UploadMode::ArticleUpload),
-            _ => return Err("Invalid upload mode".to_string()),
-        }
-    } else {
-        None // 返回所有模式
-    };
+    let parsed_upload_mode = upload_mode.as_deref().map(parse_upload_mode).transpose()?;
 
     // Parse dates
     let parsed_start_date = if let Some(date_str) = start_date {
@@ -1840,64 +5876,268 @@ pub async fn search_history(
         end_date: parsed_end_date,
         limit: Some(validated_page_size),
         offset: Some(offset),
+        search_term,
+        sort_by,
+        sort_order,
+        include_trashed: false,
     };
 
-    let mut service_records = history_service
-        .get_upload_records(Some(query))
+    let (service_records, total) = history_service
+        .get_upload_records_page(query)
         .await
         .map_err(|e| e.to_string())?;
 
-    // Apply search term filter if provided
-    if let Some(term) = search_term {
-        let term_lower = term.to_lowercase();
-        service_records.retain(|record| {
-            record.image_name.to_lowercase().contains(&term_lower)
-                || record.uploaded_url.to_lowercase().contains(&term_lower)
-                || record
-                    .source_file
-                    .as_ref()
-                    .is_some_and(|f| f.to_lowercase().contains(&term_lower))
-        });
+    Ok(PaginatedResult::new(
+        service_records,
+        total,
+        validated_page,
+        validated_page_size,
+    ))
+}
+
+/// History grouped by source markdown file, most recently updated first.
+/// `query` filters the records like search_history; pages count groups.
+#[tauri::command]
+pub async fn get_history_grouped_by_source(
+    query: Option<HistoryQuery>,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<PaginatedResult<HistorySourceGroup>, String> {
+    let (validated_page, validated_page_size) =
+        validate_pagination(page, page_size).map_err(|e| e.to_string())?;
+
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    let query = HistoryQuery {
+        limit: Some(validated_page_size),
+        offset: Some((validated_page - 1) * validated_page_size),
+        ..query.unwrap_or_default()
+    };
+
+    let (groups, total) = history_service
+        .get_grouped_by_source(query)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(PaginatedResult::new(
+        groups,
+        total,
+        validated_page,
+        validated_page_size,
+    ))
+}
+
+/// Expected size and duration of uploading `image_paths`, resolving the config
+/// like `upload_images_batch`. Reads local files and the upload stats only.
+#[tauri::command]
+pub async fn estimate_batch(
+    image_paths: Vec<BatchUploadItem>,
+    config: Option<OSSConfig>,
+    config_id: Option<String>,
+    upload_mode: Option<UploadMode>,
+    window_session: Option<String>,
+) -> Result<BatchEstimate, String> {
+    if image_paths.is_empty() {
+        return Err("Image paths cannot be empty".to_string());
+    }
+    check_count("images", image_paths.len(), MAX_PREVIEW_IMAGES).map_err(|e| e.to_string())?;
+    let image_paths: Vec<String> = image_paths
+        .iter()
+        .map(|item| item.path().to_string())
+        .collect();
+    for path in &image_paths {
+        validate_user_path_maybe_missing(path, PathKind::Image).map_err(|e| e.to_string())?;
     }
 
-    let total = service_records.len();
+    let mode = upload_mode.unwrap_or(UploadMode::ImageUpload);
+    let (config, config_id) =
+        upload_command_config(config, config_id, window_session, &mode).await?;
+    let settings = current_app_settings().await;
+    estimate_uploads(&image_paths, &config, config_id.as_deref(), &settings)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Bytes the images would send with `config`: the processed size from a cached
+/// preview when there is one, else the file size. Images upload would skip for
+/// their size are warned about and left out; the ETA uses the last week's
+/// average speed for the config, or the provider.
+async fn estimate_uploads(
+    image_paths: &[String],
+    config: &OSSConfig,
+    config_id: Option<&str>,
+    settings: &AppSettings,
+) -> Result<BatchEstimate, AppError> {
+    let image_service = ImageService::new().with_max_image_size(settings.max_image_bytes());
+    let oss_service = OSS_SERVICES.get(config)?;
+    let processing = ImageProcessingOptions::from_config(config);
+    let mut estimate = BatchEstimate {
+        concurrency: settings.upload_concurrency.max(1),
+        ..BatchEstimate::default()
+    };
+
+    for path in image_paths {
+        let warning = |message: String| BatchEstimateWarning {
+            image_path: path.clone(),
+            message,
+        };
+        let file_size = match image_service.check_image_size(path) {
+            Ok(size) => size,
+            Err(AppError::FileTooLarge(message)) => {
+                estimate.warnings.push(warning(message));
+                continue;
+            }
+            Err(_) => {
+                estimate.missing_files.push(path.clone());
+                continue;
+            }
+        };
+        let upload_size = match PROCESSED_SIZES.get(path, &processing) {
+            Some(size) if !processing.keeps_size() => {
+                estimate.sizes_from_preview += 1;
+                size
+            }
+            _ => file_size,
+        };
+        if let Err(e) = oss_service.check_object_size(upload_size) {
+            estimate.warnings.push(warning(e.to_string()));
+            continue;
+        }
+        estimate.image_count += 1;
+        estimate.total_bytes += upload_size;
+    }
+
+    estimate.bytes_per_sec = MetricsService::new()
+        .and_then(|service| service.performance_stats(StatsRange::Week))
+        .ok()
+        .and_then(|stats| stats.average_throughput(&config.provider, config_id));
+    estimate.eta = estimate.bytes_per_sec.map(|bytes_per_sec| {
+        eta_range(
+            estimate.total_bytes,
+            bytes_per_sec,
+            estimate.image_count,
+            estimate.concurrency,
+        )
+    });
+    Ok(estimate)
+}
 
-    Ok(PaginatedResult {
-        items: service_records,
-        total,
-        page: validated_page,
-        page_size: validated_page_size,
-        has_more: offset + validated_page_size < total,
-    })
+/// Throughput (average, median, p95) and failure rate per provider and config
+/// over `range`, plus per-bucket averages for a chart. Defaults to the last week.
+#[tauri::command]
+pub async fn get_upload_performance_stats(
+    range: Option<StatsRange>,
+) -> Result<UploadPerformanceStats, String> {
+    MetricsService::new()
+        .and_then(|service| service.performance_stats(range.unwrap_or_default()))
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn clear_history() -> Result<(), String> {
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    cleanup_history(&history_service, None, None, false)
+        .await
+        .map_err(|e| e.to_string())?;
     history_service
-        .clear_upload_history(None, None)
+        .clear_file_operations()
         .await
         .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// Upload records and file operations as JSON; trashed records are added
+/// under their own key with `include_trashed`
 #[tauri::command]
-pub async fn export_history() -> Result<String, String> {
+pub async fn export_history(include_trashed: Option<bool>) -> Result<String, String> {
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
     let records = history_service
         .get_upload_records(None)
         .await
         .map_err(|e| e.to_string())?;
+    let file_operations = history_service
+        .get_file_operations(usize::MAX, None)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    let export_data = serde_json::json!({
+    let mut export_data = serde_json::json!({
         "records": records,
+        "file_operations": file_operations,
         "export_date": chrono::Utc::now().to_rfc3339(),
         "version": "1.0"
     });
+    if include_trashed.unwrap_or(false) {
+        let trashed = history_service
+            .get_trashed_records()
+            .await
+            .map_err(|e| e.to_string())?;
+        export_data["trashed_records"] = serde_json::json!(trashed);
+    }
 
     serde_json::to_string_pretty(&export_data).map_err(|e| e.to_string())
 }
 
+/// Write the upload records matching `filter` as a standalone gallery page,
+/// trashed ones too when the filter includes them.
+/// Cached thumbnails are embedded, other images load from their uploaded URL.
+#[tauri::command]
+pub async fn export_history_gallery(
+    path: String,
+    format: Option<GalleryFormat>,
+    filter: Option<HistoryQuery>,
+    overwrite: Option<bool>,
+) -> Result<String, String> {
+    validate_user_write_target(&path, PathKind::Any, overwrite.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    let records = history_service
+        .get_upload_records(filter)
+        .await
+        .map_err(|e| e.to_string())?;
+    if records.is_empty() {
+        return Err("No history records match the filter".to_string());
+    }
+
+    // 没有缓存时直接使用上传地址
+    let thumbnails: HashMap<String, String> = match ImageService::with_cache() {
+        Ok(image_service) => records
+            .iter()
+            .filter_map(|record| {
+                image_service.read_cached_thumbnail(&record.id).map(|data| {
+                    (
+                        record.id.clone(),
+                        format!(
+                            "data:image/jpeg;base64,{}",
+                            general_purpose::STANDARD.encode(data)
+                        ),
+                    )
+                })
+            })
+            .collect(),
+        Err(e) => {
+            log_warn!(
+                operation = "export_history_gallery",
+                error = %e,
+                "Thumbnail cache unavailable, linking images directly"
+            );
+            HashMap::new()
+        }
+    };
+
+    let content = render_history_gallery(&records, &thumbnails, format.unwrap_or_default());
+    write_report(Path::new(&path), &content).map_err(|e| e.to_string())?;
+
+    log_info!(
+        operation = "export_history_gallery",
+        path = %path,
+        record_count = records.len(),
+        embedded_thumbnails = thumbnails.len(),
+        "History gallery exported"
+    );
+
+    Ok(path)
+}
+
 // 上传历史记录命令
 #[tauri::command]
 pub async fn add_upload_history_record(
@@ -1907,6 +6147,7 @@ pub async fn add_upload_history_record(
     source_file: Option<String>,
     file_size: u64,
     checksum: String,
+    original_path: Option<String>,
 ) -> Result<String, String> {
     // 参数验证
     if image_name.is_empty() {
@@ -1922,11 +6163,7 @@ pub async fn add_upload_history_record(
     }
 
     // 验证上传模式
-    let upload_mode_enum = match upload_mode.as_str() {
-        "ImageUpload" => UploadMode::ImageUpload,
-        "ArticleUpload" => UploadMode::ArticleUpload,
-        _ => return Err("Invalid upload mode".to_string()),
-    };
+    let upload_mode_enum = parse_upload_mode(&upload_mode)?;
 
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
     let record = UploadHistoryRecord {
@@ -1938,7 +6175,17 @@ pub async fn add_upload_history_record(
         source_file,
         file_size,
         checksum,
+        variants: Vec::new(),
+        config_id: None,
+        bucket: None,
+        original_path,
+        success: true,
+        error_message: None,
+        request_id: None,
+        original_archived: false,
+        object_key: None,
     };
+    check_history_record(&record).map_err(|e| e.to_string())?;
 
     history_service
         .add_upload_record(record)
@@ -1946,11 +6193,14 @@ pub async fn add_upload_history_record(
         .map_err(|e| e.to_string())
 }
 
+/// Totals over the history; trashed records count only with `include_trashed`
 #[tauri::command]
-pub async fn get_history_statistics() -> Result<HistoryStatistics, String> {
+pub async fn get_history_statistics(
+    include_trashed: Option<bool>,
+) -> Result<HistoryStatistics, String> {
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
     history_service
-        .get_statistics()
+        .get_statistics(include_trashed.unwrap_or(false))
         .await
         .map_err(|e| e.to_string())
 }
@@ -1964,8 +6214,13 @@ pub async fn add_batch_upload_history_records(
         return Err("Records cannot be empty".to_string());
     }
 
+    // 更大的导入由前端按 MAX_HISTORY_BATCH 分页发送
+    check_count("records in one batch", records.len(), MAX_HISTORY_BATCH)
+        .map_err(|e| e.to_string())?;
+
     // 验证每条记录
     for record in &records {
+        check_history_record(record).map_err(|e| e.to_string())?;
         if record.image_name.is_empty() {
             return Err("Image name cannot be empty".to_string());
         }
@@ -1995,15 +6250,7 @@ pub async fn get_upload_history_records(
 ) -> Result<Vec<UploadHistoryRecord>, String> {
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
 
-    let upload_mode_enum = if let Some(mode) = upload_mode {
-        match mode.as_str() {
-            "ImageUpload" => Some(UploadMode::ImageUpload),
-            "ArticleUpload" => Some(UploadMode::ArticleUpload),
-            _ => return Err("Invalid upload mode".to_string()),
-        }
-    } else {
-        None
-    };
+    let upload_mode_enum = upload_mode.as_deref().map(parse_upload_mode).transpose()?;
 
     let start_date_parsed = if let Some(date_str) = start_date {
         Some(
@@ -2031,6 +6278,10 @@ pub async fn get_upload_history_records(
         end_date: end_date_parsed,
         limit,
         offset,
+        search_term: None,
+        sort_by: None,
+        sort_order: None,
+        include_trashed: false,
     };
 
     history_service
@@ -2043,14 +6294,30 @@ pub async fn get_upload_history_records(
 #[tauri::command]
 pub async fn find_duplicate_by_checksum(
     checksum: String,
+    config_id: Option<String>,
+    bucket: Option<String>,
+    scope: Option<DuplicateScope>,
 ) -> Result<Option<UploadHistoryRecord>, String> {
     if checksum.is_empty() {
         return Err("Checksum cannot be empty".to_string());
     }
 
+    let target = duplicate_target(config_id, bucket).await;
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    history_service
+        .find_duplicate_by_checksum(&checksum, target.as_ref(), scope.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Upload records whose checksum starts with `prefix`, newest first
+#[tauri::command]
+pub async fn search_history_by_checksum(
+    prefix: String,
+) -> Result<Vec<UploadHistoryRecord>, String> {
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
     history_service
-        .find_duplicate_by_checksum(&checksum)
+        .search_by_checksum_prefix(prefix.trim())
         .await
         .map_err(|e| e.to_string())
 }
@@ -2069,29 +6336,140 @@ pub async fn delete_upload_history_record(id: String) -> Result<bool, String> {
         .map_err(|e| e.to_string())
 }
 
-// 清空上传历史记录
-#[tauri::command]
-pub async fn clear_upload_history(
-    upload_mode: Option<String>,
+/// Move history records to the trash, or on a dry run only report what
+/// would go. Their cached thumbnails stay until the trash is purged.
+async fn cleanup_history(
+    history_service: &HistoryService,
+    upload_mode: Option<UploadMode>,
     older_than_days: Option<u32>,
-) -> Result<usize, String> {
-    let upload_mode_enum = if let Some(mode) = upload_mode {
-        match mode.as_str() {
-            "ImageUpload" => Some(UploadMode::ImageUpload),
-            "ArticleUpload" => Some(UploadMode::ArticleUpload),
-            _ => return Err("Invalid upload mode".to_string()),
+    dry_run: bool,
+) -> Result<HistoryCleanupResult, AppError> {
+    let removed = history_service
+        .clear_upload_history(upload_mode, older_than_days, dry_run)
+        .await?;
+
+    let result = HistoryCleanupResult {
+        dry_run,
+        removed_records: removed.len(),
+        removed_bytes: removed.iter().map(|record| record.file_size).sum(),
+        removed_thumbnails: 0,
+        thumbnail_bytes: 0,
+    };
+    log_info!(
+        operation = "cleanup_history",
+        dry_run = dry_run,
+        removed_records = result.removed_records,
+        "History records moved to the trash"
+    );
+
+    Ok(result)
+}
+
+/// Delete trashed records deleted at least `older_than_days` ago (all of them
+/// without) and their cached thumbnails, or on a dry run only report them
+async fn purge_history_trash(
+    history_service: &HistoryService,
+    older_than_days: Option<u32>,
+    dry_run: bool,
+) -> Result<HistoryCleanupResult, AppError> {
+    let purged = history_service
+        .purge_trash(older_than_days, dry_run)
+        .await?;
+
+    // 缩略图按记录ID缓存，一并删除避免孤立文件
+    let (removed_thumbnails, thumbnail_bytes) = match ImageService::with_cache() {
+        Ok(image_service) => image_service
+            .remove_cached_thumbnails(purged.iter().map(|record| record.id.as_str()), dry_run),
+        Err(e) => {
+            log_warn!(
+                operation = "purge_history_trash",
+                error = %e,
+                "Thumbnail cache unavailable, leaving thumbnails in place"
+            );
+            (0, 0)
         }
-    } else {
-        None
     };
 
+    let result = HistoryCleanupResult {
+        dry_run,
+        removed_records: purged.len(),
+        removed_bytes: purged.iter().map(|record| record.file_size).sum(),
+        removed_thumbnails,
+        thumbnail_bytes,
+    };
+    log_info!(
+        operation = "purge_history_trash",
+        dry_run = dry_run,
+        removed_records = result.removed_records,
+        removed_thumbnails = result.removed_thumbnails,
+        thumbnail_bytes = result.thumbnail_bytes,
+        "History trash purged"
+    );
+
+    Ok(result)
+}
+
+/// History records in the trash, most recently deleted first
+#[tauri::command]
+pub async fn list_trashed_history() -> Result<Vec<TrashedHistoryRecord>, String> {
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    history_service
+        .get_trashed_records()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Put trashed records back into the history, returns the ids restored
+#[tauri::command]
+pub async fn restore_history_records(ids: Vec<String>) -> Result<Vec<String>, String> {
+    if ids.is_empty() {
+        return Err("Record IDs cannot be empty".to_string());
+    }
+    check_count("records to restore", ids.len(), MAX_HISTORY_BATCH).map_err(|e| e.to_string())?;
+
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
     history_service
-        .clear_upload_history(upload_mode_enum, older_than_days)
+        .restore_records(&ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Empty the trash, or only drop records deleted at least `older_than_days` ago
+#[tauri::command]
+pub async fn purge_trash(
+    older_than_days: Option<u32>,
+    dry_run: Option<bool>,
+) -> Result<HistoryCleanupResult, String> {
+    if older_than_days.is_some_and(|days| days > 3650) {
+        return Err("Days cannot exceed 3650".to_string());
+    }
+
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    purge_history_trash(&history_service, older_than_days, dry_run.unwrap_or(false))
         .await
         .map_err(|e| e.to_string())
 }
 
+// 清空上传历史记录
+#[tauri::command]
+pub async fn clear_upload_history(
+    upload_mode: Option<String>,
+    older_than_days: Option<u32>,
+    dry_run: Option<bool>,
+) -> Result<HistoryCleanupResult, String> {
+    let upload_mode_enum = upload_mode.as_deref().map(parse_upload_mode).transpose()?;
+
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    cleanup_history(
+        &history_service,
+        upload_mode_enum,
+        older_than_days,
+        dry_run.unwrap_or(false),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_image_history(
     upload_mode: Option<String>,
@@ -2105,15 +6483,7 @@ pub async fn get_image_history(
     }
 
     // 解析上传模式
-    let upload_mode_enum = if let Some(mode) = upload_mode {
-        match mode.as_str() {
-            "ImageUpload" => Some(UploadMode::ImageUpload),
-            "ArticleUpload" => Some(UploadMode::ArticleUpload),
-            _ => return Err("Invalid upload mode".to_string()),
-        }
-    } else {
-        None
-    };
+    let upload_mode_enum = upload_mode.as_deref().map(parse_upload_mode).transpose()?;
 
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
 
@@ -2123,6 +6493,10 @@ pub async fn get_image_history(
         end_date: None,
         limit,
         offset: None,
+        search_term: None,
+        sort_by: None,
+        sort_order: None,
+        include_trashed: false,
     };
 
     history_service
@@ -2150,25 +6524,20 @@ pub async fn clear_image_history(
     older_than_days: Option<u32>,
 ) -> Result<usize, String> {
     // 解析上传模式
-    let upload_mode_enum = if let Some(mode) = upload_mode {
-        match mode.as_str() {
-            "ImageUpload" => Some(UploadMode::ImageUpload),
-            "ArticleUpload" => Some(UploadMode::ArticleUpload),
-            _ => return Err("Invalid upload mode".to_string()),
-        }
-    } else {
-        None
-    };
+    let upload_mode_enum = upload_mode.as_deref().map(parse_upload_mode).transpose()?;
 
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
-    history_service
-        .clear_upload_history(upload_mode_enum, older_than_days)
+    cleanup_history(&history_service, upload_mode_enum, older_than_days, false)
         .await
+        .map(|result| result.removed_records)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn cleanup_old_history(older_than_days: u32) -> Result<usize, String> {
+pub async fn cleanup_old_history(
+    older_than_days: u32,
+    dry_run: Option<bool>,
+) -> Result<HistoryCleanupResult, String> {
     if older_than_days == 0 {
         return Err("Days must be greater than 0".to_string());
     }
@@ -2178,18 +6547,96 @@ pub async fn cleanup_old_history(older_than_days: u32) -> Result<usize, String>
         return Err("Days cannot exceed 3650".to_string());
     }
 
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    cleanup_history(
+        &history_service,
+        None,
+        Some(older_than_days),
+        dry_run.unwrap_or(false),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+const DEFAULT_FILE_OPERATIONS_LIMIT: usize = 100;
+
+/// Most recent backup/replace/restore operations, newest first
+#[tauri::command]
+pub async fn get_file_operations(
+    limit: Option<usize>,
+    operation_type: Option<FileOperationType>,
+) -> Result<Vec<FileOperation>, String> {
+    let limit = limit.unwrap_or(DEFAULT_FILE_OPERATIONS_LIMIT);
+    if limit == 0 {
+        return Err("Limit must be greater than 0".to_string());
+    }
+
+    let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    history_service
+        .get_file_operations(limit, operation_type)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Empty the file operations log; returns the number of entries removed
+#[tauri::command]
+pub async fn clear_file_operations() -> Result<usize, String> {
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
     history_service
-        .clear_upload_history(None, Some(older_than_days))
+        .clear_file_operations()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// App Data Migration Commands
+// ============================================================================
+
+/// Bundle configs, settings and history into a zip archive for another machine
+#[tauri::command]
+pub async fn export_app_data(
+    target_zip_path: String,
+    include: AppDataInclude,
+    overwrite: Option<bool>,
+) -> Result<AppDataManifest, String> {
+    validate_user_write_target(&target_zip_path, PathKind::Any, overwrite.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    let app_data_service = AppDataService::new().map_err(|e| e.to_string())?;
+    app_data_service
+        .export(Path::new(&target_zip_path), include)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List what an app data archive holds without importing anything
+#[tauri::command]
+pub async fn preview_app_data_import(zip_path: String) -> Result<AppDataPreview, String> {
+    validate_archive_path(&zip_path).map_err(|e| e.to_string())?;
+
+    let app_data_service = AppDataService::new().map_err(|e| e.to_string())?;
+    app_data_service
+        .preview(Path::new(&zip_path))
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_file_operations(_limit: Option<usize>) -> Result<Vec<FileOperation>, String> {
-    // 在简化的设计中，我们不再跟踪文件操作
-    // 返回空列表
-    Ok(vec![])
+pub async fn import_app_data(zip_path: String, merge: bool) -> Result<AppDataImportResult, String> {
+    CONFIG_RATE_LIMITER
+        .check_rate_limit("import_app_data")
+        .map_err(|e| e.to_string())?;
+    validate_archive_path(&zip_path).map_err(|e| e.to_string())?;
+
+    let app_data_service = AppDataService::new().map_err(|e| e.to_string())?;
+    app_data_service
+        .import(Path::new(&zip_path), merge)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn validate_archive_path(zip_path: &str) -> Result<(), AppError> {
+    validate_user_path(zip_path, PathKind::Any).map(|_| ())
 }
 
 // ============================================================================
@@ -2203,39 +6650,15 @@ pub async fn get_app_version() -> Result<String, String> {
 
 #[tauri::command]
 pub async fn validate_file_path(path: String) -> Result<bool, String> {
-    // Validate input parameters
-    if path.is_empty() {
-        return Err("File path cannot be empty".to_string());
-    }
+    check_path_syntax(&path, PathKind::Any).map_err(|e| e.to_string())?;
 
-    // Security check: prevent path traversal
-    if path.contains("..") || path.contains("~") {
-        return Err("Invalid file path detected".to_string());
-    }
-
-    Ok(std::path::Path::new(&path).exists())
+    // Paths outside what the user picked read as missing
+    Ok(validate_user_path_maybe_missing(&path, PathKind::Any).is_ok() && Path::new(&path).exists())
 }
 
 #[tauri::command]
 pub async fn get_file_size(path: String) -> Result<u64, String> {
-    // Validate input parameters
-    if path.is_empty() {
-        return Err("File path cannot be empty".to_string());
-    }
-
-    // Security check: prevent path traversal
-    if path.contains("..") || path.contains("~") {
-        return Err("Invalid file path detected".to_string());
-    }
-
-    let path_obj = Path::new(&path);
-    if !path_obj.exists() {
-        return Err(format!("File not found: {}", path));
-    }
-
-    if !path_obj.is_file() {
-        return Err(format!("Path is not a file: {}", path));
-    }
+    validate_user_path(&path, PathKind::Any).map_err(|e| e.to_string())?;
 
     std::fs::metadata(&path)
         .map(|metadata| metadata.len())
@@ -2292,7 +6715,7 @@ pub async fn health_check() -> Result<HashMap<String, String>, String> {
 }
 
 #[tauri::command]
-pub async fn validate_system_permissions() -> Result<ValidationResult, String> {
+pub async fn validate_system_permissions() -> Result<SystemPermissions, String> {
     let mut errors = Vec::new();
 
     // Check if we can create temporary files
@@ -2301,19 +6724,87 @@ pub async fn validate_system_permissions() -> Result<ValidationResult, String> {
         Err(e) => errors.push(format!("Cannot create temporary files: {}", e)),
     }
 
-    // Check if we can access the config directory
-    if let Some(config_dir) = dirs::config_dir() {
-        let app_config_dir = config_dir.join("imgtoss");
-        if let Err(e) = std::fs::create_dir_all(&app_config_dir) {
-            errors.push(format!("Cannot access config directory: {}", e));
+    // Check that the active config and data roots can be written to; in
+    // portable mode that is the folder next to the executable
+    let paths = app_paths::app_paths();
+    let config_dir = paths.config_dir();
+    let data_dir = paths.data_dir();
+    for (name, dir) in [("config", &config_dir), ("data", &data_dir)] {
+        match dir {
+            Ok(dir) => {
+                if let Err(e) = check_directory_writable(dir) {
+                    errors.push(format!("Cannot write to {} directory: {}", name, e));
+                }
+            }
+            Err(e) => errors.push(format!("Cannot determine {} directory: {}", name, e)),
         }
-    } else {
-        errors.push("Cannot determine config directory".to_string());
     }
 
-    Ok(ValidationResult {
+    Ok(SystemPermissions {
         valid: errors.is_empty(),
         errors,
+        storage_mode: paths.mode,
+        config_dir: config_dir.ok().map(|dir| dir.to_string_lossy().to_string()),
+        data_dir: data_dir.ok().map(|dir| dir.to_string_lossy().to_string()),
+    })
+}
+
+fn check_directory_writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    tempfile::NamedTempFile::new_in(dir).map(|_| ())
+}
+
+/// Keep configs and data in `path` (e.g. a synced folder) from the next start,
+/// copying the current ones over; None goes back to the default location.
+/// The running app keeps using the old location until it is restarted.
+#[tauri::command]
+pub async fn set_data_directory(path: Option<String>) -> Result<DataDirectoryChange, String> {
+    let paths = app_paths::app_paths();
+    if paths.mode == StorageMode::Portable {
+        return Err(
+            "Portable mode keeps data next to the executable, remove portable.flag first"
+                .to_string(),
+        );
+    }
+
+    let Some(path) = path else {
+        app_paths::save_data_dir_override(None).map_err(|e| e.to_string())?;
+        log_info!(
+            operation = "set_data_directory",
+            "Data directory reset to the default location"
+        );
+        return Ok(DataDirectoryChange {
+            data_dir: None,
+            copied_files: 0,
+            skipped_files: 0,
+            restart_required: paths.mode != StorageMode::Standard,
+        });
+    };
+
+    validate_user_path_maybe_missing(&path, PathKind::Directory).map_err(|e| e.to_string())?;
+    let root = Path::new(&path);
+    if !root.is_absolute() {
+        return Err(format!("Data directory must be an absolute path: {}", path));
+    }
+    check_directory_writable(root)
+        .map_err(|e| format!("Cannot write to data directory {}: {}", path, e))?;
+
+    let (copied_files, skipped_files) = paths.copy_to(root).map_err(|e| e.to_string())?;
+    app_paths::save_data_dir_override(Some(root)).map_err(|e| e.to_string())?;
+
+    log_info!(
+        operation = "set_data_directory",
+        data_dir = %path,
+        copied_files = copied_files,
+        skipped_files = skipped_files,
+        "Data directory changed, takes effect after restart"
+    );
+
+    Ok(DataDirectoryChange {
+        data_dir: Some(path),
+        copied_files,
+        skipped_files,
+        restart_required: true,
     })
 }
 
@@ -2327,6 +6818,17 @@ pub struct DuplicateCheckResult {
     pub is_duplicate: bool,
     pub existing_record: Option<UploadHistoryRecord>,
     pub existing_url: Option<String>,
+    /// Config and bucket the matching upload went to
+    #[serde(default)]
+    pub matched_config_id: Option<String>,
+    #[serde(default)]
+    pub matched_bucket: Option<String>,
+    /// Not a duplicate, but the image was uploaded with another config
+    #[serde(default)]
+    pub found_in_other_config: bool,
+    /// Every upload with this checksum, newest first, whatever the scope
+    #[serde(default)]
+    pub matches: Vec<UploadHistoryRecord>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -2340,171 +6842,167 @@ pub struct DuplicateInfo {
 
 #[tauri::command]
 pub async fn calculate_image_checksum(image_path: String) -> Result<String, String> {
-    // Validate input parameters
-    if image_path.is_empty() {
-        return Err("Image path cannot be empty".to_string());
-    }
-
-    // Security check: prevent path traversal
-    if image_path.contains("..") || image_path.contains("~") {
-        return Err("Invalid image path detected".to_string());
-    }
-
-    let path = Path::new(&image_path);
-    if !path.exists() {
-        return Err(format!("Image file not found: {}", image_path));
-    }
+    validate_user_path(&image_path, PathKind::Image).map_err(|e| e.to_string())?;
 
-    if !path.is_file() {
-        return Err(format!("Path is not a file: {}", image_path));
-    }
-
-    let image_service = ImageService::new();
+    let image_service = configured_image_service().await;
     image_service
         .calculate_checksum(&image_path)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// The config duplicates are matched against: the given config or bucket,
+/// otherwise the active config
+async fn duplicate_target(
+    config_id: Option<String>,
+    bucket: Option<String>,
+) -> Option<UploadTarget> {
+    if config_id.is_some() || bucket.is_some() {
+        return Some(UploadTarget { config_id, bucket });
+    }
+
+    let active = ConfigService::new()
+        .ok()?
+        .get_active_config()
+        .await
+        .ok()??;
+    Some(UploadTarget::new(Some(active.id), &active.config))
+}
+
+async fn check_duplicate(
+    history_service: &HistoryService,
+    checksum: String,
+    target: Option<&UploadTarget>,
+    scope: DuplicateScope,
+) -> Result<DuplicateCheckResult, AppError> {
+    let matches = history_service
+        .find_duplicates_by_checksum(&checksum)
+        .await?;
+    let existing_record = scope.pick(&matches, target).cloned();
+
+    Ok(DuplicateCheckResult {
+        checksum,
+        is_duplicate: existing_record.is_some(),
+        existing_url: existing_record
+            .as_ref()
+            .map(|record| record.uploaded_url.clone()),
+        matched_config_id: existing_record
+            .as_ref()
+            .and_then(|record| record.config_id.clone()),
+        matched_bucket: existing_record
+            .as_ref()
+            .and_then(|record| record.bucket.clone()),
+        // 其他配置中上传过，供界面说明
+        found_in_other_config: existing_record.is_none() && !matches.is_empty(),
+        existing_record,
+        matches,
+    })
+}
+
 #[tauri::command]
-pub async fn check_duplicate_by_checksum(checksum: String) -> Result<DuplicateCheckResult, String> {
+pub async fn check_duplicate_by_checksum(
+    checksum: String,
+    config_id: Option<String>,
+    bucket: Option<String>,
+    scope: Option<DuplicateScope>,
+) -> Result<DuplicateCheckResult, String> {
     // Validate input parameters
     if checksum.is_empty() {
         return Err("Checksum cannot be empty".to_string());
     }
 
-    // Basic checksum format validation (SHA256 should be 64 hex characters)
-    if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+    // Accepts `{algo}:{hex}` as well as legacy plain sha256 hex
+    if ChecksumAlgorithm::split_checksum(&checksum).is_none() {
         return Err("Invalid checksum format".to_string());
     }
 
+    let target = duplicate_target(config_id, bucket).await;
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
-
-    match history_service
-        .find_duplicate_by_checksum(&checksum)
-        .await
-        .map_err(|e| e.to_string())?
-    {
-        Some(service_record) => {
-            // 直接使用 UploadHistoryRecord，不需要转换
-            let existing_url = Some(service_record.uploaded_url.clone());
-
-            Ok(DuplicateCheckResult {
-                checksum,
-                is_duplicate: true,
-                existing_record: Some(service_record),
-                existing_url,
-            })
-        }
-        None => Ok(DuplicateCheckResult {
-            checksum,
-            is_duplicate: false,
-            existing_record: None,
-            existing_url: None,
-        }),
-    }
+    check_duplicate(
+        &history_service,
+        checksum,
+        target.as_ref(),
+        scope.unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn check_duplicates_batch(
     image_paths: Vec<String>,
+    config_id: Option<String>,
+    bucket: Option<String>,
+    scope: Option<DuplicateScope>,
 ) -> Result<Vec<DuplicateCheckResult>, String> {
     // Validate input parameters
     if image_paths.is_empty() {
         return Err("Image paths cannot be empty".to_string());
     }
 
-    if image_paths.len() > 100 {
-        return Err("Too many images to check (max 100)".to_string());
-    }
+    check_count("images to check", image_paths.len(), MAX_DUPLICATE_CHECKS)
+        .map_err(|e| e.to_string())?;
 
     // Validate each image path
     for path in &image_paths {
-        if path.is_empty() {
-            return Err("Image path cannot be empty".to_string());
-        }
-
-        // Security check: prevent path traversal
-        if path.contains("..") || path.contains("~") {
-            return Err("Invalid image path detected".to_string());
-        }
-
-        let path_obj = Path::new(path);
-        if !path_obj.exists() {
-            return Err(format!("Image file not found: {}", path));
-        }
-
-        if !path_obj.is_file() {
-            return Err(format!("Path is not a file: {}", path));
-        }
+        validate_user_path(path, PathKind::Image).map_err(|e| e.to_string())?;
     }
 
-    let image_service = ImageService::new();
+    let image_service = configured_image_service().await;
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
+    let target = duplicate_target(config_id, bucket).await;
+    let scope = scope.unwrap_or_default();
 
     let mut results = Vec::new();
 
-    for image_path in image_paths {
-        // Calculate checksum
-        let checksum = image_service
-            .calculate_checksum(&image_path)
-            .await
-            .map_err(|e| e.to_string())?;
-
-        // Check for duplicate
-        match history_service
-            .find_duplicate_by_checksum(&checksum)
-            .await
-            .map_err(|e| e.to_string())?
-        {
-            Some(service_record) => {
-                // 直接使用 UploadHistoryRecord，不需要转换
-                let existing_url = Some(service_record.uploaded_url.clone());
-
-                results.push(DuplicateCheckResult {
-                    checksum,
-                    is_duplicate: true,
-                    existing_record: Some(service_record),
-                    existing_url,
-                });
-            }
-            None => {
-                results.push(DuplicateCheckResult {
-                    checksum,
-                    is_duplicate: false,
-                    existing_record: None,
-                    existing_url: None,
-                });
-            }
-        }
+    for image_path in image_paths {
+        // Calculate checksum
+        let checksum = image_service
+            .calculate_checksum(&image_path)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Check for duplicate
+        results.push(
+            check_duplicate(&history_service, checksum, target.as_ref(), scope)
+                .await
+                .map_err(|e| e.to_string())?,
+        );
     }
 
     Ok(results)
 }
 
 #[tauri::command]
-pub async fn get_duplicate_info(checksum: String) -> Result<Option<DuplicateInfo>, String> {
+pub async fn get_duplicate_info(
+    checksum: String,
+    config_id: Option<String>,
+    bucket: Option<String>,
+    scope: Option<DuplicateScope>,
+) -> Result<Option<DuplicateInfo>, String> {
     // Validate input parameters
     if checksum.is_empty() {
         return Err("Checksum cannot be empty".to_string());
     }
 
-    // Basic checksum format validation (SHA256 should be 64 hex characters)
-    if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+    // Accepts `{algo}:{hex}` as well as legacy plain sha256 hex
+    if ChecksumAlgorithm::split_checksum(&checksum).is_none() {
         return Err("Invalid checksum format".to_string());
     }
 
+    let target = duplicate_target(config_id, bucket).await;
     let history_service = HistoryService::new().map_err(|e| e.to_string())?;
 
     match history_service
-        .find_duplicate_by_checksum(&checksum)
+        .find_duplicate_by_checksum(&checksum, target.as_ref(), scope.unwrap_or_default())
         .await
         .map_err(|e| e.to_string())?
     {
         Some(record) => {
             let existing_url = record.uploaded_url.clone();
             let file_size = record.file_size;
-            let original_path = record.image_name.clone();
+            // Older records only know the file name
+            let original_path = record.original_path.unwrap_or(record.image_name);
 
             Ok(Some(DuplicateInfo {
                 checksum,
@@ -2521,8 +7019,68 @@ pub async fn get_duplicate_info(checksum: String) -> Result<Option<DuplicateInfo
 // System Health and Monitoring Commands
 // ============================================================================
 
+/// Salvage complete records from the newest `*.corrupt-*` copy of a store
+/// and merge them into the live one. The corrupt copy is left in place.
+#[tauri::command]
+pub async fn recover_corrupt_store(kind: StoreKind) -> Result<StoreRecoveryResult, String> {
+    let result = match kind {
+        StoreKind::Configs | StoreKind::Settings => {
+            ConfigService::new()
+                .map_err(|e| e.to_string())?
+                .recover_corrupt_store(kind)
+                .await
+        }
+        StoreKind::UploadHistory | StoreKind::FileOperations | StoreKind::HistoryTrash => {
+            HistoryService::new()
+                .map_err(|e| e.to_string())?
+                .recover_corrupt_store(kind)
+                .await
+        }
+        StoreKind::BackupIndex => {
+            BackupService::new()
+                .map_err(|e| e.to_string())?
+                .recover_corrupt_index()
+                .await
+        }
+        StoreKind::PendingUploads => PENDING_UPLOADS.recover_corrupt_file().await,
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Crash reports left by backend panics, newest first
+#[tauri::command]
+pub async fn list_crash_reports() -> Result<Vec<CrashReportSummary>, String> {
+    CrashReports::new()
+        .and_then(|reports| reports.list())
+        .map_err(|e| e.to_string())
+}
+
+/// One crash report with its backtrace
+#[tauri::command]
+pub async fn get_crash_report(id: String) -> Result<CrashReport, String> {
+    CrashReports::new()
+        .and_then(|reports| reports.get(&id))
+        .map_err(|e| e.to_string())
+}
+
+/// Outcomes of this launch's startup tasks; `finished_at` is None while they
+/// are still running
+#[tauri::command]
+pub async fn get_startup_report() -> Result<StartupReport, String> {
+    Ok(STARTUP_TASKS.report())
+}
+
+/// Background maintenance jobs with their toggles and last outcomes; `running`
+/// is set while a run is in progress
 #[tauri::command]
-pub async fn get_system_health() -> Result<SystemHealth, String> {
+pub async fn get_maintenance_status() -> Result<MaintenanceStatus, String> {
+    Ok(MAINTENANCE.status(&current_app_settings().await))
+}
+
+/// `include_network` also runs a connection test against the active config,
+/// reusing a cached result when there is a recent one
+#[tauri::command]
+pub async fn get_system_health(include_network: Option<bool>) -> Result<SystemHealth, String> {
     let _start_time = std::time::Instant::now();
 
     // Get system information
@@ -2607,6 +7165,63 @@ pub async fn get_system_health() -> Result<SystemHealth, String> {
         });
     }
 
+    // Uploads whose history records are waiting for the store to take writes
+    let deferred_records = HISTORY_WRITES.deferred_count();
+    if deferred_records > 0 {
+        if matches!(status, HealthStatus::Healthy) {
+            status = HealthStatus::Warning;
+        }
+        errors.push(HealthError {
+            component: "History".to_string(),
+            message: format!(
+                "{} uploaded images are not in the upload history yet, the history store can't be written",
+                deferred_records
+            ),
+            severity: ErrorSeverity::Medium,
+            timestamp: chrono::Utc::now(),
+        });
+    }
+
+    // Local originals changed since upload, found by the last integrity check
+    if let Some(message) = modified_originals_message() {
+        if matches!(status, HealthStatus::Healthy) {
+            status = HealthStatus::Warning;
+        }
+        errors.push(HealthError {
+            component: "History".to_string(),
+            message,
+            severity: ErrorSeverity::Low,
+            timestamp: chrono::Utc::now(),
+        });
+    }
+
+    // Files the last batch skipped for being over the size limit
+    if let Some(message) = skipped_oversized_message() {
+        if matches!(status, HealthStatus::Healthy) {
+            status = HealthStatus::Warning;
+        }
+        errors.push(HealthError {
+            component: "Uploads".to_string(),
+            message,
+            severity: ErrorSeverity::Low,
+            timestamp: chrono::Utc::now(),
+        });
+    }
+
+    let config_dir = app_paths::config_dir().map_err(|e| e.to_string())?;
+    let data_dir = app_paths::data_dir().map_err(|e| e.to_string())?;
+    for error in probe_components(&config_dir, &data_dir, include_network.unwrap_or(false)).await {
+        if matches!(
+            error.severity,
+            ErrorSeverity::High | ErrorSeverity::Critical
+        ) {
+            status = HealthStatus::Critical;
+        } else if matches!(status, HealthStatus::Healthy) {
+            status = HealthStatus::Warning;
+        }
+        errors.push(error);
+    }
+
     Ok(SystemHealth {
         status,
         uptime,
@@ -2618,21 +7233,179 @@ pub async fn get_system_health() -> Result<SystemHealth, String> {
     })
 }
 
+// Stores under `dir` moved aside this run and not recovered yet
+fn corrupt_stores_in(dir: &Path) -> Vec<CorruptStore> {
+    CORRUPT_STORES
+        .list()
+        .into_iter()
+        .filter(|store| Path::new(&store.path).starts_with(dir))
+        .collect()
+}
+
+fn corrupt_store_message(store: &CorruptStore) -> String {
+    format!(
+        "{} could not be read ({}) and was moved to {}; run recover_corrupt_store to salvage its records",
+        store.path, store.error, store.moved_to
+    )
+}
+
+/// Check that the config and history stores can be read and written, and
+/// that the active config is usable. One HealthError per failing component,
+/// and one per store found corrupt and moved aside.
+///
+/// OSS credentials live in the config store, the Stronghold vault is never
+/// opened by the backend, so the config store is what gets probed for them.
+pub(crate) async fn probe_components(
+    config_dir: &Path,
+    data_dir: &Path,
+    include_network: bool,
+) -> Vec<HealthError> {
+    let mut errors = Vec::new();
+    let mut report = |component: &str, message: String, severity: ErrorSeverity| {
+        errors.push(HealthError {
+            component: component.to_string(),
+            message,
+            severity,
+            timestamp: chrono::Utc::now(),
+        });
+    };
+
+    // 配置存储
+    let active = match ConfigService::new_with_dir(config_dir.to_path_buf()) {
+        Ok(config_service) => match config_service.load_all_configs().await {
+            // An unparsable store was moved aside and reads as empty, the
+            // missing configs are the problem to report, not the missing active one
+            Ok(_) if !corrupt_stores_in(config_dir).is_empty() => {
+                for store in corrupt_stores_in(config_dir) {
+                    report(
+                        "Config",
+                        corrupt_store_message(&store),
+                        ErrorSeverity::Critical,
+                    );
+                }
+                None
+            }
+            Ok(collection) => {
+                let active = collection
+                    .active_config_id
+                    .and_then(|id| collection.configs.into_iter().find(|c| c.id == id));
+                Some((config_service, active))
+            }
+            Err(e) => {
+                report(
+                    "Config",
+                    format!("Config store is not readable: {}", e),
+                    ErrorSeverity::Critical,
+                );
+                None
+            }
+        },
+        Err(e) => {
+            report("Config", e.to_string(), ErrorSeverity::Critical);
+            None
+        }
+    };
+    if let Err(e) = check_directory_writable(config_dir) {
+        report(
+            "Config",
+            format!("Config store is not writable: {}", e),
+            ErrorSeverity::High,
+        );
+    }
+
+    // 历史记录存储
+    match HistoryService::new_with_dir(data_dir.to_path_buf()) {
+        Ok(history_service) => {
+            if let Err(e) = history_service.get_upload_records(None).await {
+                report(
+                    "History",
+                    format!("Upload history is not readable: {}", e),
+                    ErrorSeverity::High,
+                );
+            }
+            for store in corrupt_stores_in(data_dir) {
+                report(
+                    "History",
+                    corrupt_store_message(&store),
+                    ErrorSeverity::High,
+                );
+            }
+        }
+        Err(e) => report("History", e.to_string(), ErrorSeverity::High),
+    }
+    if let Err(e) = check_directory_writable(data_dir) {
+        report(
+            "History",
+            format!("Upload history is not writable: {}", e),
+            ErrorSeverity::High,
+        );
+    }
+
+    // 当前启用的 OSS 配置
+    let Some((config_service, active)) = active else {
+        return errors;
+    };
+    let Some(active) = active else {
+        report(
+            "OSS",
+            "No active OSS configuration".to_string(),
+            ErrorSeverity::Low,
+        );
+        return errors;
+    };
+    let config_errors = ConfigService::config_errors(&active.config);
+    if !config_errors.is_empty() {
+        report(
+            "OSS",
+            format!("{}: {}", active.name, config_errors.join(", ")),
+            ErrorSeverity::High,
+        );
+        return errors;
+    }
+    if !include_network {
+        return errors;
+    }
+
+    match config_service.validate_config(&active.config).await {
+        Ok(validation) => {
+            if let Some(test) = validation.connection_test.filter(|t| !t.success) {
+                report(
+                    "OSS",
+                    format!(
+                        "Connection test for {} failed: {}",
+                        active.name,
+                        test.error.unwrap_or_else(|| "unknown error".to_string())
+                    ),
+                    ErrorSeverity::High,
+                );
+            }
+        }
+        Err(e) => report(
+            "OSS",
+            format!("Connection test for {} failed: {}", active.name, e),
+            ErrorSeverity::High,
+        ),
+    }
+
+    errors
+}
+
+/// Notification preferences, stored with the app settings
 #[tauri::command]
 pub async fn get_notification_config() -> Result<NotificationConfig, String> {
-    // For now, return default config. In a real implementation, this would be loaded from storage
-    Ok(NotificationConfig::default())
+    Ok(current_app_settings().await.notifications)
 }
 
 #[tauri::command]
 pub async fn update_notification_config(config: NotificationConfig) -> Result<(), String> {
-    // Validate config
-    if config.dismiss_timeout > 60000 {
-        return Err("Dismiss timeout cannot exceed 60 seconds".to_string());
-    }
-
-    // TODO: Save config to storage
-    // For now, just validate and return success
+    let config_service = ConfigService::new().map_err(|e| e.to_string())?;
+    config_service
+        .update_app_settings(AppSettingsPatch {
+            notifications: Some(config),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -2656,8 +7429,29 @@ pub async fn send_notification(notification: ProgressNotification) -> Result<(),
 // Enhanced Upload Task Management Commands
 // ============================================================================
 
+/// Cancel one task, or every task of the sessions `scope` matches when no
+/// task id is given. With both, the task must belong to the scope, so one
+/// session's view can't cancel another's uploads.
 #[tauri::command]
-pub async fn cancel_upload_task(task_id: String) -> Result<(), String> {
+pub async fn cancel_upload_task(
+    task_id: Option<String>,
+    scope: Option<UploadScope>,
+) -> Result<(), String> {
+    let Some(task_id) = task_id else {
+        let scope = scope
+            .filter(|scope| !scope.is_empty())
+            .ok_or("A task ID or an upload scope is required")?;
+        let cancelled = PROGRESS_NOTIFIER.remove_scope(&scope)?;
+        log_info!(
+            operation = "cancel_upload_task",
+            session_id = ?scope.session_id,
+            config_id = ?scope.config_id,
+            cancelled = cancelled.len(),
+            "Upload session cancelled"
+        );
+        return Ok(());
+    };
+
     // Validate input parameters
     if task_id.is_empty() {
         return Err("Task ID cannot be empty".to_string());
@@ -2668,6 +7462,15 @@ pub async fn cancel_upload_task(task_id: String) -> Result<(), String> {
         return Err("Invalid task ID format".to_string());
     }
 
+    if let (Some(scope), Some(task_scope)) = (&scope, PROGRESS_NOTIFIER.scope_of(&task_id)) {
+        if !scope.matches(&task_scope) {
+            return Err(format!(
+                "Task {} doesn't belong to this upload session",
+                task_id
+            ));
+        }
+    }
+
     // Remove progress tracking for cancelled upload
     PROGRESS_NOTIFIER
         .remove_progress(&task_id)
@@ -2706,6 +7509,11 @@ pub async fn retry_upload_task(task_id: String, max_retries: Option<u32>) -> Res
         bytes_uploaded: 0,
         total_bytes: 0,
         speed: None,
+        eta_secs: None,
+        status: UploadStatus::Pending,
+        error: None,
+        session_id: None,
+        config_id: None,
     };
 
     PROGRESS_NOTIFIER
@@ -2745,19 +7553,13 @@ pub async fn get_upload_task_status(task_id: String) -> Result<Option<UploadTask
             let task_info = UploadTaskInfo {
                 id: task_id,
                 image_path: "Unknown".to_string(), // Would be stored in a real task manager
-                status: if progress.progress >= 100.0 {
-                    UploadTaskStatus::Completed
-                } else if progress.progress > 0.0 {
-                    UploadTaskStatus::Uploading
-                } else {
-                    UploadTaskStatus::Queued
-                },
+                status: task_status_from_progress(&progress),
+                error: progress.error.clone(),
                 progress,
                 start_time: chrono::Utc::now(), // Would be stored in a real task manager
                 end_time: None,
                 retry_count: 0,
                 max_retries: 3,
-                error: None,
                 cancellation_token: None,
             };
             Ok(Some(task_info))
@@ -2766,8 +7568,21 @@ pub async fn get_upload_task_status(task_id: String) -> Result<Option<UploadTask
     }
 }
 
+fn task_status_from_progress(progress: &UploadProgress) -> UploadTaskStatus {
+    match progress.status {
+        UploadStatus::Pending => UploadTaskStatus::Queued,
+        UploadStatus::Uploading if progress.progress > 0.0 => UploadTaskStatus::Uploading,
+        UploadStatus::Uploading => UploadTaskStatus::Starting,
+        UploadStatus::Completed => UploadTaskStatus::Completed,
+        UploadStatus::Failed => UploadTaskStatus::Failed,
+    }
+}
+
+/// Tracked and offline-queued tasks, also grouped by upload session. `scope`
+/// narrows them to the matching sessions.
 #[tauri::command]
-pub async fn get_all_upload_tasks() -> Result<UploadTaskManager, String> {
+pub async fn get_all_upload_tasks(scope: Option<UploadScope>) -> Result<UploadTaskManager, String> {
+    let scope = scope.unwrap_or_default();
     let all_progress = PROGRESS_NOTIFIER
         .get_all_progress()
         .map_err(|e| e.to_string())?;
@@ -2775,28 +7590,69 @@ pub async fn get_all_upload_tasks() -> Result<UploadTaskManager, String> {
     let mut active_tasks = std::collections::HashMap::new();
 
     for progress in all_progress {
+        if !scope.matches(&progress.scope()) {
+            continue;
+        }
         let task_info = UploadTaskInfo {
             id: progress.image_id.clone(),
             image_path: "Unknown".to_string(), // Would be stored in a real task manager
-            status: if progress.progress >= 100.0 {
-                UploadTaskStatus::Completed
-            } else if progress.progress > 0.0 {
-                UploadTaskStatus::Uploading
-            } else {
-                UploadTaskStatus::Queued
-            },
+            status: task_status_from_progress(&progress),
+            error: progress.error.clone(),
             progress,
             start_time: chrono::Utc::now(), // Would be stored in a real task manager
             end_time: None,
             retry_count: 0,
             max_retries: 3,
+            cancellation_token: None,
+        };
+        active_tasks.insert(task_info.id.clone(), task_info);
+    }
+    for task in PENDING_UPLOADS.offline() {
+        if !scope.matches(&task.scope()) {
+            continue;
+        }
+        let task_info = UploadTaskInfo {
+            id: task.task_id.clone(),
+            image_path: task.image_path,
+            status: UploadTaskStatus::QueuedOffline,
+            progress: UploadProgress {
+                image_id: task.task_id,
+                progress: 0.0,
+                bytes_uploaded: 0,
+                total_bytes: 0,
+                speed: None,
+                eta_secs: None,
+                status: UploadStatus::Pending,
+                error: None,
+                session_id: task.session_id,
+                config_id: task.config_id,
+            },
+            start_time: task.enqueued_at,
+            end_time: None,
             error: None,
+            retry_count: 0,
+            max_retries: 3,
             cancellation_token: None,
         };
         active_tasks.insert(task_info.id.clone(), task_info);
     }
 
+    let mut sessions = group_tasks_by_session(active_tasks.values());
+    for session in &mut sessions {
+        let in_flight = session.completed + session.failed < session.task_ids.len();
+        session.speed = session
+            .session_id
+            .as_deref()
+            .filter(|_| in_flight)
+            .and_then(|session_id| PROGRESS_NOTIFIER.session_speed(session_id));
+        session.eta_secs = progress::eta_secs(
+            session.total_bytes.saturating_sub(session.bytes_uploaded),
+            session.speed,
+        );
+    }
+
     Ok(UploadTaskManager {
+        sessions,
         active_tasks,
         completed_tasks: Vec::new(), // Would be populated from persistent storage
         failed_tasks: Vec::new(),    // Would be populated from persistent storage
@@ -2804,6 +7660,318 @@ pub async fn get_all_upload_tasks() -> Result<UploadTaskManager, String> {
     })
 }
 
+// One entry per (session, config), sessions in id order and tasks in start order
+fn group_tasks_by_session<'a>(
+    tasks: impl Iterator<Item = &'a UploadTaskInfo>,
+) -> Vec<UploadSessionTasks> {
+    let mut tasks: Vec<&UploadTaskInfo> = tasks.collect();
+    tasks.sort_by_key(|task| (&task.progress.session_id, task.start_time, &task.id));
+
+    let mut sessions: Vec<UploadSessionTasks> = Vec::new();
+    for task in tasks {
+        let progress = &task.progress;
+        let session = match sessions.iter_mut().find(|session| {
+            session.session_id == progress.session_id && session.config_id == progress.config_id
+        }) {
+            Some(session) => session,
+            None => {
+                sessions.push(UploadSessionTasks {
+                    session_id: progress.session_id.clone(),
+                    config_id: progress.config_id.clone(),
+                    task_ids: Vec::new(),
+                    completed: 0,
+                    failed: 0,
+                    bytes_uploaded: 0,
+                    total_bytes: 0,
+                    speed: None,
+                    eta_secs: None,
+                });
+                sessions.last_mut().expect("session was just added")
+            }
+        };
+        session.task_ids.push(task.id.clone());
+        match progress.status {
+            UploadStatus::Completed => session.completed += 1,
+            UploadStatus::Failed => session.failed += 1,
+            UploadStatus::Pending | UploadStatus::Uploading => {}
+        }
+        session.bytes_uploaded += progress.bytes_uploaded;
+        session.total_bytes += progress.total_bytes;
+    }
+    sessions
+}
+
+/// Uploads a previous run left unfinished (crash, forced quit), each re-checked
+/// against the current file system and saved configs
+#[tauri::command]
+pub async fn get_recovered_tasks() -> Result<Vec<RecoveredUploadTask>, String> {
+    let configs = match ConfigService::new() {
+        Ok(config_service) => config_service
+            .load_all_configs()
+            .await
+            .map(|collection| collection.configs)
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+
+    Ok(PENDING_UPLOADS
+        .recovered()
+        .into_iter()
+        .map(|task| recovered_task(task, &configs))
+        .collect())
+}
+
+fn recovered_task(task: PendingUpload, configs: &[ConfigItem]) -> RecoveredUploadTask {
+    RecoveredUploadTask {
+        file_exists: local_file_error(&task.image_path).is_none(),
+        config_exists: task
+            .config_id
+            .as_ref()
+            .is_some_and(|id| configs.iter().any(|item| &item.id == id)),
+        task,
+    }
+}
+
+/// Re-enqueue recovered uploads (all when `task_ids` and `scope` are None).
+/// Tasks whose file or config no longer exists are not uploaded and stay in the
+/// recovered list. Resumed tasks report under the session they were started in.
+#[tauri::command]
+pub async fn resume_recovered_tasks(
+    task_ids: Option<Vec<String>>,
+    scope: Option<UploadScope>,
+) -> Result<Vec<UploadResult>, String> {
+    let started = Instant::now();
+    UPLOAD_RATE_LIMITER
+        .check_rate_limit("resume_recovered_tasks")
+        .map_err(|e| e.to_string())?;
+
+    let configs = ConfigService::new()
+        .map_err(|e| e.to_string())?
+        .load_all_configs()
+        .await
+        .map_err(|e| e.to_string())?
+        .configs;
+
+    let mut results = Vec::new();
+    let mut resumable = Vec::new();
+    for task in recovered_in_scope(task_ids.as_deref(), scope.as_ref()) {
+        let recovered = recovered_task(task, &configs);
+        if recovered.file_exists && recovered.config_exists {
+            resumable.push(recovered.task.task_id);
+        } else {
+            let error = if recovered.file_exists {
+                "Config no longer exists".to_string()
+            } else {
+                format!("Image file not found: {}", recovered.task.image_path)
+            };
+            results.push(UploadResult {
+                image_id: recovered.task.task_id,
+                success: false,
+                uploaded_url: None,
+                error: Some(error),
+                variants: Vec::new(),
+                queued_offline: false,
+                warnings: Vec::new(),
+            });
+        }
+    }
+
+    let tasks = PENDING_UPLOADS.take_recovered(Some(&resumable));
+    PENDING_UPLOADS.enqueue(tasks.clone());
+
+    results.extend(upload_pending_tasks(tasks, &configs).await);
+
+    log_info!(
+        operation = "resume_recovered_tasks",
+        resumed = resumable.len(),
+        total_results = results.len(),
+        "Recovered uploads resumed"
+    );
+
+    notify_batch_finished(BatchSummary::from_results(&results, started)).await;
+    Ok(results)
+}
+
+/// Forget recovered uploads (all when `task_ids` and `scope` are None), returns
+/// how many were dropped
+#[tauri::command]
+pub async fn discard_recovered_tasks(
+    task_ids: Option<Vec<String>>,
+    scope: Option<UploadScope>,
+) -> Result<usize, String> {
+    let ids: Vec<String> = recovered_in_scope(task_ids.as_deref(), scope.as_ref())
+        .into_iter()
+        .map(|task| task.task_id)
+        .collect();
+    let discarded = PENDING_UPLOADS.take_recovered(Some(&ids));
+    Ok(discarded.len())
+}
+
+// Recovered tasks among `task_ids` (any when None) in the sessions `scope` matches
+fn recovered_in_scope(
+    task_ids: Option<&[String]>,
+    scope: Option<&UploadScope>,
+) -> Vec<PendingUpload> {
+    PENDING_UPLOADS
+        .recovered()
+        .into_iter()
+        .filter(|task| task_ids.is_none_or(|ids| ids.contains(&task.task_id)))
+        .filter(|task| scope.is_none_or(|scope| scope.matches(&task.scope())))
+        .collect()
+}
+
+/// Whether the endpoint is reachable and how many uploads wait for it
+#[tauri::command]
+pub async fn get_connectivity_status() -> Result<ConnectivityStatus, String> {
+    Ok(CONNECTIVITY.status())
+}
+
+// Upload tasks that are already in the pending list, each with the saved
+// config it was enqueued for
+async fn upload_pending_tasks(
+    tasks: Vec<PendingUpload>,
+    configs: &[ConfigItem],
+) -> Vec<UploadResult> {
+    let image_service = configured_image_service().await;
+    let mut results = Vec::new();
+    for task in tasks {
+        let Some(config) = task
+            .config_id
+            .as_ref()
+            .and_then(|id| configs.iter().find(|item| &item.id == id))
+            .map(|item| item.config.clone())
+        else {
+            PENDING_UPLOADS.finish(&task.task_id);
+            results.push(UploadResult {
+                image_id: task.task_id,
+                success: false,
+                uploaded_url: None,
+                error: Some("Config no longer exists".to_string()),
+                variants: Vec::new(),
+                queued_offline: false,
+                warnings: Vec::new(),
+            });
+            continue;
+        };
+
+        let processing = ImageProcessingOptions::from_config(&config);
+        let path_template = config.path_template.clone();
+        let upload_target = UploadTarget::new(task.config_id.clone(), &config);
+        // Reported under the session that enqueued it
+        PROGRESS_NOTIFIER.assign_scope(&task.task_id, task.scope());
+        let task_id = task.task_id.clone();
+        let result = match OSS_SERVICES.get(&config) {
+            Ok(oss_service) => {
+                upload_tracked_image(
+                    &oss_service,
+                    &image_service,
+                    &task.image_path,
+                    &task.task_id,
+                    &processing,
+                    &path_template,
+                    task.upload_mode,
+                    task.source_file,
+                    upload_target,
+                )
+                .await
+            }
+            Err(e) => {
+                PENDING_UPLOADS.finish(&task.task_id);
+                UploadResult {
+                    image_id: task.task_id,
+                    success: false,
+                    uploaded_url: None,
+                    error: Some(e.to_string()),
+                    variants: Vec::new(),
+                    queued_offline: false,
+                    warnings: Vec::new(),
+                }
+            }
+        };
+        PROGRESS_NOTIFIER.release_scope(&task_id);
+        results.push(result);
+    }
+    results
+}
+
+/// Upload everything in the offline queue, then rewrite the links article
+/// uploads held back. Called by the connectivity watcher once it's back online.
+pub(crate) async fn flush_offline_queue() -> Vec<UploadResult> {
+    let started = Instant::now();
+    let tasks = PENDING_UPLOADS.take_offline();
+    if tasks.is_empty() {
+        return Vec::new();
+    }
+    let configs = match ConfigService::new() {
+        Ok(config_service) => config_service
+            .load_all_configs()
+            .await
+            .map(|collection| collection.configs)
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+
+    let deferred: HashMap<String, DeferredLinks> = tasks
+        .iter()
+        .filter_map(|task| {
+            task.deferred_links
+                .clone()
+                .map(|links| (task.task_id.clone(), links))
+        })
+        .collect();
+    let results = upload_pending_tasks(tasks, &configs).await;
+
+    let replacements: Vec<LinkReplacement> = results
+        .iter()
+        .filter_map(|result| {
+            let links = deferred.get(&result.image_id)?;
+            Some(links.replacements(result.uploaded_url.as_deref()?))
+        })
+        .flatten()
+        .collect();
+    if !replacements.is_empty() {
+        if let Err(e) = apply_deferred_links(replacements).await {
+            log_error!(
+                operation = "offline_queue",
+                error = %e,
+                "Failed to rewrite links of uploads from the offline queue"
+            );
+        }
+    }
+
+    log_info!(
+        operation = "offline_queue",
+        uploaded = results.iter().filter(|r| r.success).count(),
+        queued_again = results.iter().filter(|r| r.queued_offline).count(),
+        total = results.len(),
+        "Offline queue flushed"
+    );
+    CONNECTIVITY.emit(connectivity::OFFLINE_QUEUE_FLUSHED_EVENT, results.clone());
+    notify_batch_finished(BatchSummary::from_results(&results, started)).await;
+    results
+}
+
+// Same backup and retention as the markdown pipeline around the rewrite
+async fn apply_deferred_links(replacements: Vec<LinkReplacement>) -> Result<(), AppError> {
+    let settings = current_app_settings().await;
+    let backup_service = BackupService::new()?.with_operation_log(file_operation_log());
+    backup_replacement_targets(&backup_service, &replacements).await?;
+    let result = FileService::new()?
+        .with_operation_name("offline_queue")
+        .with_utf8_conversion(settings.convert_legacy_encoding)
+        .with_verification_restore(settings.restore_unverified_replacements)
+        .replace_image_links_batch(replacements)
+        .await?;
+    apply_backup_retention(&backup_service, &settings).await;
+    log_info!(
+        operation = "offline_queue",
+        replaced = result.total_successful_replacements,
+        failed = result.total_failed_replacements,
+        "Deferred links rewritten"
+    );
+    Ok(())
+}
+
 // ============================================================================
 // System Utility Functions
 // ============================================================================
@@ -2832,6 +8000,9 @@ fn get_available_disk_space() -> Result<u64, String> {
 // Thumbnail Commands
 // ============================================================================
 
+// 预签名链接只用于下载一次，缓存按记录 ID 命中
+const THUMBNAIL_PRESIGN_EXPIRY_SECS: u64 = 900;
+
 #[tauri::command]
 pub async fn get_thumbnail(record_id: String, image_url: String) -> Result<String, String> {
     log_info!(
@@ -2868,9 +8039,11 @@ pub async fn get_thumbnail(record_id: String, image_url: String) -> Result<Strin
         e.to_string()
     })?;
 
+    let sources = thumbnail_sources(&image_url).await;
+
     // 获取缓存的缩略图
     match image_service
-        .get_cached_thumbnail(&record_id, &image_url)
+        .get_cached_thumbnail(&record_id, &sources)
         .await
     {
         Ok(thumbnail_data) => {
@@ -2899,6 +8072,37 @@ pub async fn get_thumbnail(record_id: String, image_url: String) -> Result<Strin
     }
 }
 
+/// URLs to fetch a history image from. When a saved config owns the URL a presigned
+/// URL is added: tried first for private objects, otherwise as the fallback after a 403.
+async fn thumbnail_sources(image_url: &str) -> Vec<String> {
+    let configs = match ConfigService::new() {
+        Ok(service) => service
+            .load_all_configs()
+            .await
+            .map(|c| c.configs)
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    thumbnail_sources_for(image_url, &configs)
+}
+
+fn thumbnail_sources_for(image_url: &str, configs: &[ConfigItem]) -> Vec<String> {
+    let presigned = configs.iter().find_map(|item| {
+        let key = object_key_from_url(&item.config, image_url)?;
+        let oss_service = OSS_SERVICES.get(&item.config).ok()?;
+        Some((
+            item.config.object_acl,
+            oss_service.presigned_url(&key, THUMBNAIL_PRESIGN_EXPIRY_SECS),
+        ))
+    });
+
+    match presigned {
+        Some((ObjectAcl::Private, presigned_url)) => vec![presigned_url],
+        Some((_, presigned_url)) => vec![image_url.to_string(), presigned_url],
+        None => vec![image_url.to_string()],
+    }
+}
+
 #[tauri::command]
 pub async fn cleanup_thumbnail_cache() -> Result<usize, String> {
     log_info!(
@@ -2937,3 +8141,21 @@ pub async fn cleanup_thumbnail_cache() -> Result<usize, String> {
         }
     }
 }
+
+/// Hit/miss counters of the in-memory thumbnail cache and the size of the
+/// history thumbnail cache on disk
+#[tauri::command]
+pub async fn get_cache_stats() -> Result<CacheStats, String> {
+    let image_service = ImageService::with_cache().unwrap_or_else(|e| {
+        log_warn!(
+            operation = "get_cache_stats_command",
+            error = %e,
+            "Disk thumbnail cache unavailable, reporting memory cache only"
+        );
+        ImageService::new()
+    });
+    image_service
+        .get_cache_stats()
+        .await
+        .map_err(|e| e.to_string())
+}
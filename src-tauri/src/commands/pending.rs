@@ -0,0 +1,536 @@
+use crate::models::{DeferredLinks, PendingUpload, StoreKind, StoreRecoveryResult};
+use crate::utils::app_paths;
+use crate::utils::store_lock::lock_store;
+use crate::utils::store_recovery::{
+    latest_corrupt_file, salvage_array, write_atomic, CORRUPT_STORES,
+};
+use crate::utils::{AppError, Result};
+use crate::{log_error, log_info};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const PENDING_UPLOADS_FILE: &str = "pending_uploads.json";
+
+/// Writes snapshots of the store off the caller's thread. Snapshots taken
+/// while a write is running are coalesced, only the newest one is written.
+struct PersistQueue {
+    path: PathBuf,
+    // Newest snapshot not written yet, and whether a writer task is running
+    latest: Mutex<(Option<String>, bool)>,
+    // Held from taking a snapshot until it's on disk, so an older snapshot
+    // never lands after a newer one
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl PersistQueue {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            latest: Mutex::new((None, false)),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn submit(self: &Arc<Self>, content: String) {
+        let start_writer = {
+            let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+            latest.0 = Some(content);
+            !std::mem::replace(&mut latest.1, true)
+        };
+        if !start_writer {
+            return;
+        }
+
+        let queue = self.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move { queue.run_writer().await });
+            }
+            // Called outside the runtime: write before returning
+            Err(_) => match tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+            {
+                Ok(runtime) => runtime.block_on(queue.run_writer()),
+                Err(e) => {
+                    log_error!(
+                        operation = "persist_pending_uploads",
+                        error = %e,
+                        "Failed to start a runtime to persist pending uploads"
+                    );
+                }
+            },
+        }
+    }
+
+    async fn run_writer(&self) {
+        loop {
+            self.write_latest().await;
+            let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+            if latest.0.is_none() {
+                latest.1 = false;
+                return;
+            }
+        }
+    }
+
+    async fn write_latest(&self) {
+        let _writing = self.writing.lock().await;
+        let Some(content) = self
+            .latest
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+            .take()
+        else {
+            return;
+        };
+
+        let result = match lock_store(&self.path).await {
+            Ok(lock) => {
+                let path = self.path.clone();
+                tokio::task::spawn_blocking(move || {
+                    let _lock = lock;
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    write_atomic(&path, &content)
+                })
+                .await
+                .map_err(|e| AppError::Panicked(e.to_string()))
+                .and_then(|written| written.map_err(AppError::from))
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log_error!(
+                operation = "persist_pending_uploads",
+                error = %e,
+                "Failed to persist pending uploads"
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingState {
+    // Enqueued by this run and not finished yet
+    pending: Vec<PendingUpload>,
+    // Left in the file by a previous run that didn't finish them
+    recovered: Vec<PendingUpload>,
+    // Waiting for the connection to return, kept across restarts
+    offline: Vec<PendingUpload>,
+}
+
+/// Disk-backed list of unfinished uploads.
+///
+/// Every enqueue/finish rewrites the file in the background, so after a crash
+/// the entries still in it are the uploads that never completed. They are loaded as
+/// "recovered" on startup and stay in the file until resumed or discarded,
+/// except the ones queued offline, which go back into the offline queue.
+pub struct PendingUploadStore {
+    // None when the data directory is unavailable, the store is then memory-only
+    file: Option<Arc<PersistQueue>>,
+    state: Mutex<PendingState>,
+}
+
+impl PendingUploadStore {
+    pub fn new() -> Self {
+        let file = app_paths::data_dir()
+            .ok()
+            .map(|dir| dir.join(PENDING_UPLOADS_FILE));
+        Self::with_file(file)
+    }
+
+    pub fn with_file(file: Option<PathBuf>) -> Self {
+        let (offline, recovered): (Vec<_>, Vec<_>) = file
+            .as_deref()
+            .map(Self::load)
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|task| task.queued_offline);
+        if !recovered.is_empty() {
+            log_info!(
+                operation = "recover_pending_uploads",
+                task_count = recovered.len(),
+                "Found unfinished uploads from a previous run"
+            );
+        }
+
+        Self {
+            file: file.map(|path| Arc::new(PersistQueue::new(path))),
+            state: Mutex::new(PendingState {
+                pending: Vec::new(),
+                recovered,
+                offline,
+            }),
+        }
+    }
+
+    /// Record uploads that are about to start
+    pub fn enqueue(&self, tasks: Vec<PendingUpload>) {
+        if tasks.is_empty() {
+            return;
+        }
+        if let Ok(mut state) = self.state.lock() {
+            state.pending.extend(tasks);
+            self.persist(&state);
+        }
+    }
+
+    /// Drop a task once it completed or failed
+    pub fn finish(&self, task_id: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            let before = state.pending.len();
+            state.pending.retain(|task| task.task_id != task_id);
+            if state.pending.len() != before {
+                self.persist(&state);
+            }
+        }
+    }
+
+    /// Uploads of this run that have started and not finished yet
+    pub fn in_flight(&self) -> usize {
+        self.state
+            .lock()
+            .map(|state| state.pending.len())
+            .unwrap_or(0)
+    }
+
+    /// Unfinished uploads of previous runs
+    pub fn recovered(&self) -> Vec<PendingUpload> {
+        self.state
+            .lock()
+            .map(|state| state.recovered.clone())
+            .unwrap_or_default()
+    }
+
+    /// Remove recovered tasks (all of them when `task_ids` is None) and return them
+    pub fn take_recovered(&self, task_ids: Option<&[String]>) -> Vec<PendingUpload> {
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+
+        let (taken, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut state.recovered)
+            .into_iter()
+            .partition(|task| task_ids.is_none_or(|ids| ids.contains(&task.task_id)));
+        state.recovered = kept;
+        if !taken.is_empty() {
+            self.persist(&state);
+        }
+        taken
+    }
+
+    /// Move a started task into the offline queue instead of failing it.
+    /// False when the task isn't pending (never enqueued or already finished).
+    pub fn park_offline(&self, task_id: &str) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        let Some(index) = state.pending.iter().position(|t| t.task_id == task_id) else {
+            return false;
+        };
+        let mut task = state.pending.remove(index);
+        task.queued_offline = true;
+        state.offline.push(task);
+        self.persist(&state);
+        true
+    }
+
+    /// Attach the link rewrites an article upload in the offline queue holds back
+    pub fn defer_links(&self, task_id: &str, links: DeferredLinks) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(task) = state.offline.iter_mut().find(|t| t.task_id == task_id) {
+                task.deferred_links = Some(links);
+                self.persist(&state);
+            }
+        }
+    }
+
+    /// Uploads waiting for the connection to return
+    pub fn offline(&self) -> Vec<PendingUpload> {
+        self.state
+            .lock()
+            .map(|state| state.offline.clone())
+            .unwrap_or_default()
+    }
+
+    /// Empty the offline queue for flushing; the tasks are pending again until
+    /// they finish or get parked once more
+    pub fn take_offline(&self) -> Vec<PendingUpload> {
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let tasks: Vec<PendingUpload> = std::mem::take(&mut state.offline)
+            .into_iter()
+            .map(|mut task| {
+                task.queued_offline = false;
+                task
+            })
+            .collect();
+        if !tasks.is_empty() {
+            state.pending.extend(tasks.iter().cloned());
+            self.persist(&state);
+        }
+        tasks
+    }
+
+    /// Wait until every change made so far is on disk
+    pub async fn flush(&self) {
+        if let Some(queue) = &self.file {
+            queue.write_latest().await;
+        }
+    }
+
+    /// Salvage the complete tasks of the newest corrupt copy of the file and
+    /// add them to the recovered or offline tasks. Tasks already known (same
+    /// id) are skipped.
+    pub async fn recover_corrupt_file(&self) -> Result<StoreRecoveryResult> {
+        let Some(queue) = &self.file else {
+            return Err(AppError::Validation(
+                "Pending uploads are not stored on disk".to_string(),
+            ));
+        };
+        let source = latest_corrupt_file(&queue.path).ok_or_else(|| {
+            AppError::Validation(format!(
+                "No corrupt copy of {} to recover",
+                queue.path.display()
+            ))
+        })?;
+        let content = String::from_utf8_lossy(&std::fs::read(&source)?).to_string();
+        let values = salvage_array(&content, None);
+        let total = values.len();
+        let salvaged: Vec<PendingUpload> = values
+            .into_iter()
+            .filter_map(|value| serde_json::from_value(value).ok())
+            .collect();
+        let unreadable = total - salvaged.len();
+
+        let recovered = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let known: HashSet<String> = state
+                .recovered
+                .iter()
+                .chain(&state.offline)
+                .chain(&state.pending)
+                .map(|task| task.task_id.clone())
+                .collect();
+            let mut recovered = 0;
+            for task in salvaged {
+                if known.contains(&task.task_id) {
+                    continue;
+                }
+                recovered += 1;
+                if task.queued_offline {
+                    state.offline.push(task);
+                } else {
+                    state.recovered.push(task);
+                }
+            }
+            self.persist(&state);
+            recovered
+        };
+        self.flush().await;
+
+        CORRUPT_STORES.resolve(StoreKind::PendingUploads);
+        log_info!(
+            operation = "recover_corrupt_store",
+            kind = ?StoreKind::PendingUploads,
+            source = %source.display(),
+            recovered = recovered,
+            "Recovered pending uploads from a corrupt file"
+        );
+
+        Ok(StoreRecoveryResult {
+            kind: StoreKind::PendingUploads,
+            source: source.to_string_lossy().to_string(),
+            recovered,
+            unreadable,
+        })
+    }
+
+    // 解析失败时把文件移到一边，可以用 recover_corrupt_file 找回
+    fn load(path: &Path) -> Vec<PendingUpload> {
+        match std::fs::read_to_string(path) {
+            Ok(content) if !content.trim().is_empty() => serde_json::from_str(&content)
+                .unwrap_or_else(|e| {
+                    CORRUPT_STORES.quarantine(StoreKind::PendingUploads, path, &e.to_string());
+                    Vec::new()
+                }),
+            _ => Vec::new(),
+        }
+    }
+
+    // 只在锁内拍下快照，写盘交给 PersistQueue，经 write_atomic 写入
+    fn persist(&self, state: &PendingState) {
+        let Some(queue) = &self.file else {
+            return;
+        };
+
+        let tasks: Vec<&PendingUpload> = state
+            .recovered
+            .iter()
+            .chain(&state.offline)
+            .chain(&state.pending)
+            .collect();
+        match serde_json::to_string(&tasks) {
+            Ok(content) => queue.submit(content),
+            Err(e) => {
+                log_error!(
+                    operation = "persist_pending_uploads",
+                    error = %e,
+                    "Failed to serialize pending uploads"
+                );
+            }
+        }
+    }
+}
+
+impl Default for PendingUploadStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Global pending upload store, loads leftovers of the previous run on first use
+lazy_static::lazy_static! {
+    pub static ref PENDING_UPLOADS: PendingUploadStore = PendingUploadStore::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UploadMode;
+    use tempfile::TempDir;
+
+    fn task(task_id: &str) -> PendingUpload {
+        PendingUpload {
+            task_id: task_id.to_string(),
+            image_path: format!("/images/{}.png", task_id),
+            config_id: Some("config-1".to_string()),
+            source_file: None,
+            upload_mode: UploadMode::ImageUpload,
+            enqueued_at: chrono::Utc::now(),
+            queued_offline: false,
+            deferred_links: None,
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn test_unfinished_tasks_are_recovered_by_next_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(PENDING_UPLOADS_FILE);
+
+        let store = PendingUploadStore::with_file(Some(file.clone()));
+        assert!(store.recovered().is_empty());
+        store.enqueue(vec![task("a"), task("b"), task("c")]);
+        store.finish("b");
+
+        // Simulated restart
+        let next_run = PendingUploadStore::with_file(Some(file));
+        let recovered: Vec<String> = next_run
+            .recovered()
+            .into_iter()
+            .map(|task| task.task_id)
+            .collect();
+        assert_eq!(recovered, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_take_recovered_prunes_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(PENDING_UPLOADS_FILE);
+        PendingUploadStore::with_file(Some(file.clone())).enqueue(vec![task("a"), task("b")]);
+
+        let store = PendingUploadStore::with_file(Some(file.clone()));
+        let taken = store.take_recovered(Some(&["a".to_string()]));
+        assert_eq!(taken.len(), 1);
+        assert_eq!(store.recovered().len(), 1);
+
+        assert_eq!(store.take_recovered(None).len(), 1);
+        assert!(PendingUploadStore::with_file(Some(file))
+            .recovered()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_offline_queue_survives_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(PENDING_UPLOADS_FILE);
+
+        let store = PendingUploadStore::with_file(Some(file.clone()));
+        store.enqueue(vec![task("a"), task("b")]);
+        assert!(store.park_offline("a"));
+        assert!(!store.park_offline("missing"));
+
+        // Parked uploads come back queued, not as crash leftovers
+        let next_run = PendingUploadStore::with_file(Some(file.clone()));
+        let offline = next_run.offline();
+        assert_eq!(offline.len(), 1);
+        assert_eq!(offline[0].task_id, "a");
+        assert!(offline[0].queued_offline);
+        let recovered: Vec<String> = next_run
+            .recovered()
+            .into_iter()
+            .map(|task| task.task_id)
+            .collect();
+        assert_eq!(recovered, vec!["b"]);
+
+        let taken = next_run.take_offline();
+        assert_eq!(taken.len(), 1);
+        assert!(!taken[0].queued_offline);
+        assert!(next_run.offline().is_empty());
+        next_run.finish("a");
+        assert!(PendingUploadStore::with_file(Some(file))
+            .offline()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_writes_from_the_runtime_land_after_flush() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(PENDING_UPLOADS_FILE);
+
+        let store = PendingUploadStore::with_file(Some(file.clone()));
+        for id in ["a", "b", "c", "d"] {
+            store.enqueue(vec![task(id)]);
+        }
+        store.finish("a");
+        store.flush().await;
+
+        let recovered: Vec<String> = PendingUploadStore::with_file(Some(file))
+            .recovered()
+            .into_iter()
+            .map(|task| task.task_id)
+            .collect();
+        assert_eq!(recovered, vec!["b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_file_is_moved_aside_and_recovered() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(PENDING_UPLOADS_FILE);
+        let content = serde_json::to_string(&vec![task("a")]).unwrap();
+        // Cut off in the middle of a second task
+        std::fs::write(
+            &file,
+            format!("{},{{\"task_id\": \"b", &content[..content.len() - 1]),
+        )
+        .unwrap();
+
+        let store = PendingUploadStore::with_file(Some(file.clone()));
+        assert!(store.recovered().is_empty());
+        assert!(!file.exists());
+        assert!(latest_corrupt_file(&file).is_some());
+
+        let result = store.recover_corrupt_file().await.unwrap();
+        assert_eq!((result.recovered, result.unreadable), (1, 0));
+        assert_eq!(store.recovered()[0].task_id, "a");
+        assert_eq!(
+            PendingUploadStore::with_file(Some(file)).recovered().len(),
+            1
+        );
+    }
+}
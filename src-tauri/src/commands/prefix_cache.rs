@@ -0,0 +1,71 @@
+use crate::models::OSSConfig;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a listing is reused; long enough to cover typing a prefix
+const PREFIX_TTL: Duration = Duration::from_secs(30);
+// The cache is emptied once it grows past this
+const MAX_ENTRIES: usize = 500;
+
+struct CachedPrefixes {
+    listed_at: Instant,
+    prefixes: Vec<String>,
+}
+
+/// Child prefixes from `list_oss_prefixes`, so autocomplete doesn't list the
+/// bucket again on every keystroke. Keyed by bucket, prefix and delimiter.
+pub struct PrefixCache {
+    entries: Mutex<HashMap<(String, String, String), CachedPrefixes>>,
+}
+
+impl PrefixCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Prefixes listed less than `PREFIX_TTL` ago, None otherwise
+    pub fn get(&self, config: &OSSConfig, prefix: &str, delimiter: &str) -> Option<Vec<String>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let cached = entries.get(&Self::key(config, prefix, delimiter))?;
+        (cached.listed_at.elapsed() < PREFIX_TTL).then(|| cached.prefixes.clone())
+    }
+
+    pub fn record(&self, config: &OSSConfig, prefix: &str, delimiter: &str, prefixes: &[String]) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_ENTRIES {
+            entries.clear();
+        }
+        entries.insert(
+            Self::key(config, prefix, delimiter),
+            CachedPrefixes {
+                listed_at: Instant::now(),
+                prefixes: prefixes.to_vec(),
+            },
+        );
+    }
+
+    // Credentials count too, another key may not see the same objects
+    fn key(config: &OSSConfig, prefix: &str, delimiter: &str) -> (String, String, String) {
+        (
+            format!(
+                "{:?}|{}|{}|{}",
+                config.provider, config.endpoint, config.bucket, config.access_key_id
+            ),
+            prefix.to_string(),
+            delimiter.to_string(),
+        )
+    }
+}
+
+impl Default for PrefixCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref OSS_PREFIXES: PrefixCache = PrefixCache::new();
+}
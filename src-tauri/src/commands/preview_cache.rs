@@ -0,0 +1,109 @@
+use crate::models::ImageProcessingOptions;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// The cache is emptied once it grows past this, previews are cheap to redo
+const MAX_ENTRIES: usize = 10_000;
+
+struct CachedSize {
+    modified: Option<SystemTime>,
+    len: u64,
+    processed_size: u64,
+}
+
+/// Output sizes from `preview_image_processing`, so `estimate_batch` can use
+/// the size after compression instead of the file size. Keyed by path and
+/// processing options; an entry no longer counts once the file has changed.
+pub struct ProcessedSizeCache {
+    entries: Mutex<HashMap<(String, String), CachedSize>>,
+}
+
+impl ProcessedSizeCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, image_path: &str, options: &ImageProcessingOptions, processed_size: u64) {
+        let Ok(metadata) = std::fs::metadata(image_path) else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_ENTRIES {
+            entries.clear();
+        }
+        entries.insert(
+            Self::key(image_path, options),
+            CachedSize {
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+                processed_size,
+            },
+        );
+    }
+
+    /// Processed size of the file as it is now, None when it wasn't previewed
+    /// with these options or changed since
+    pub fn get(&self, image_path: &str, options: &ImageProcessingOptions) -> Option<u64> {
+        let metadata = std::fs::metadata(image_path).ok()?;
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let cached = entries.get(&Self::key(image_path, options))?;
+        (cached.len == metadata.len() && cached.modified == metadata.modified().ok())
+            .then_some(cached.processed_size)
+    }
+
+    fn key(image_path: &str, options: &ImageProcessingOptions) -> (String, String) {
+        (
+            image_path.to_string(),
+            serde_json::to_string(options).unwrap_or_default(),
+        )
+    }
+}
+
+impl Default for ProcessedSizeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref PROCESSED_SIZES: ProcessedSizeCache = ProcessedSizeCache::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn options(quality: u8) -> ImageProcessingOptions {
+        ImageProcessingOptions {
+            compression_enabled: true,
+            quality,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sizes_are_per_options_and_dropped_when_the_file_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let image = temp_dir.path().join("a.png");
+        std::fs::write(&image, vec![0u8; 1000]).unwrap();
+        let path = image.to_string_lossy().to_string();
+
+        let cache = ProcessedSizeCache::new();
+        assert_eq!(cache.get(&path, &options(80)), None);
+        cache.record(&path, &options(80), 400);
+        assert_eq!(cache.get(&path, &options(80)), Some(400));
+        assert_eq!(cache.get(&path, &options(60)), None);
+
+        std::fs::write(&image, vec![0u8; 2000]).unwrap();
+        assert_eq!(cache.get(&path, &options(80)), None);
+    }
+}
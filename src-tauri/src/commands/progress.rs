@@ -1,15 +1,114 @@
-use crate::models::UploadProgress;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use crate::models::{UploadProgress, UploadScope, UploadStatus};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 use tokio::sync::broadcast;
 
+/// How long Completed/Failed entries stay queryable after they finish
+pub const TERMINAL_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// Updates of a task in flight are sent to listeners at most this often,
+/// unless they cross a whole percent; the rest only update the stored value
+pub const EMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress samples older than this don't count towards the speed
+pub const SPEED_WINDOW: Duration = Duration::from_secs(3);
+// Shorter spans give no speed: the first chunks of an upload leave in a burst
+const MIN_SPEED_SPAN: Duration = Duration::from_millis(500);
+
+/// Bytes per second over the last `SPEED_WINDOW` of progress samples. Uploads
+/// report in chunks, so the speed between two updates swings between a burst
+/// and nothing; the window averages over several of them.
+#[derive(Debug, Default)]
+pub struct SpeedWindow {
+    // (time, cumulative bytes), oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedWindow {
+    /// Add the byte count reached at `at` and return the speed so far
+    pub fn record(&mut self, at: Instant, bytes: u64) -> Option<u64> {
+        // Fewer bytes than before: the upload started over (retry)
+        if self.samples.back().is_some_and(|(_, last)| bytes < *last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((at, bytes));
+        // Keep one sample from before the window so it spans all of it
+        while self.samples.len() > 2
+            && at.saturating_duration_since(self.samples[1].0) >= SPEED_WINDOW
+        {
+            self.samples.pop_front();
+        }
+        self.speed()
+    }
+
+    pub fn speed(&self) -> Option<u64> {
+        let (first_at, first_bytes) = *self.samples.front()?;
+        let (last_at, last_bytes) = *self.samples.back()?;
+        let span = last_at.saturating_duration_since(first_at);
+        if span < MIN_SPEED_SPAN {
+            return None;
+        }
+        Some(((last_bytes - first_bytes) as f64 / span.as_secs_f64()).round() as u64)
+    }
+}
+
+/// Seconds `remaining` bytes take at `speed`
+pub fn eta_secs(remaining: u64, speed: Option<u64>) -> Option<u64> {
+    speed
+        .filter(|speed| *speed > 0)
+        .map(|speed| remaining.div_ceil(speed))
+}
+
+struct ProgressEntry {
+    progress: UploadProgress,
+    // Set once the task reaches a terminal status, drives retention pruning
+    finished_at: Option<Instant>,
+    updated_at: Instant,
+    speed: SpeedWindow,
+    // Last update sent to listeners: when, its whole percent and status
+    emitted: Option<(Instant, u32, UploadStatus)>,
+    // The stored progress is newer than what listeners saw
+    unsent: bool,
+}
+
+impl ProgressEntry {
+    // Terminal and status changes go out right away, in-flight updates once
+    // per `EMIT_INTERVAL` or when they reach another whole percent
+    fn should_emit(&self, now: Instant) -> bool {
+        let Some((at, percent, status)) = self.emitted else {
+            return true;
+        };
+        self.finished_at.is_some()
+            || status != self.progress.status
+            || percent != self.progress.progress.floor() as u32
+            || now.saturating_duration_since(at) >= EMIT_INTERVAL
+    }
+
+    fn mark_emitted(&mut self, now: Instant) {
+        self.emitted = Some((
+            now,
+            self.progress.progress.floor() as u32,
+            self.progress.status,
+        ));
+        self.unsent = false;
+    }
+}
+
 /// Progress notification system for async operations
 #[derive(Clone)]
 pub struct ProgressNotifier {
-    progress_map: Arc<Mutex<HashMap<String, UploadProgress>>>,
+    progress_map: Arc<Mutex<HashMap<String, ProgressEntry>>>,
+    // Session of each task that hasn't reached a terminal status yet; stamped
+    // on its progress updates, which the upload services send unscoped
+    scopes: Arc<Mutex<HashMap<String, UploadScope>>>,
+    // Bytes of all tasks of a session, for the speed of the batch as a whole
+    session_speeds: Arc<Mutex<HashMap<String, SpeedWindow>>>,
     sender: broadcast::Sender<UploadProgress>,
-    app_handle: Option<AppHandle>,
+    // Set once in setup; without it only broadcast receivers see updates
+    app_handle: OnceLock<AppHandle>,
+    retention: Duration,
 }
 
 impl ProgressNotifier {
@@ -17,60 +116,270 @@ impl ProgressNotifier {
         let (sender, _) = broadcast::channel(1000);
         Self {
             progress_map: Arc::new(Mutex::new(HashMap::new())),
+            scopes: Arc::new(Mutex::new(HashMap::new())),
+            session_speeds: Arc::new(Mutex::new(HashMap::new())),
             sender,
-            app_handle: None,
+            app_handle: OnceLock::new(),
+            retention: TERMINAL_RETENTION,
         }
     }
 
     #[allow(dead_code)]
     pub fn with_app_handle(app_handle: AppHandle) -> Self {
-        let (sender, _) = broadcast::channel(1000);
-        Self {
-            progress_map: Arc::new(Mutex::new(HashMap::new())),
-            sender,
-            app_handle: Some(app_handle),
-        }
+        let notifier = Self::new();
+        notifier.set_app_handle(app_handle);
+        notifier
     }
 
+    /// Override how long terminal entries are kept
     #[allow(dead_code)]
-    pub fn set_app_handle(&mut self, app_handle: AppHandle) {
-        self.app_handle = Some(app_handle);
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    /// Report the task's updates under `scope` until it finishes
+    pub fn assign_scope(&self, task_id: &str, scope: UploadScope) {
+        if let Ok(mut scopes) = self.scopes.lock() {
+            scopes.insert(task_id.to_string(), scope);
+        }
+    }
+
+    /// Forget the scope of a task that won't report anymore
+    pub fn release_scope(&self, task_id: &str) {
+        if let Ok(mut scopes) = self.scopes.lock() {
+            scopes.remove(task_id);
+        }
+    }
+
+    /// Forget the scopes of a session's tasks that never reported a final
+    /// status (skipped, parked offline). Their entries keep the scope they had.
+    pub fn end_session(&self, session_id: &str) {
+        if let Ok(mut scopes) = self.scopes.lock() {
+            scopes.retain(|_, scope| scope.session_id.as_deref() != Some(session_id));
+        }
+        if let Ok(mut speeds) = self.session_speeds.lock() {
+            speeds.remove(session_id);
+        }
+    }
+
+    /// Smoothed speed of a session's uploads taken together
+    pub fn session_speed(&self, session_id: &str) -> Option<u64> {
+        self.session_speeds
+            .lock()
+            .ok()
+            .and_then(|speeds| speeds.get(session_id).and_then(SpeedWindow::speed))
     }
 
-    /// Update progress for a specific task
+    /// Update progress for a specific task. Uploaders leave `speed` unset,
+    /// it is estimated here from the byte counts they report. The stored
+    /// value is always the latest; listeners get it coalesced (see
+    /// `EMIT_INTERVAL`), the rest is sent by `flush_pending`.
     pub fn update_progress(&self, task_id: String, progress: UploadProgress) -> Result<(), String> {
+        self.update_progress_at(task_id, progress, Instant::now())
+    }
+
+    fn update_progress_at(
+        &self,
+        task_id: String,
+        mut progress: UploadProgress,
+        now: Instant,
+    ) -> Result<(), String> {
+        let finished_at = match progress.status {
+            UploadStatus::Completed | UploadStatus::Failed => Some(now),
+            UploadStatus::Pending | UploadStatus::Uploading => None,
+        };
+        let assigned = self.scopes.lock().ok().and_then(|mut scopes| {
+            if finished_at.is_some() {
+                scopes.remove(&task_id)
+            } else {
+                scopes.get(&task_id).cloned()
+            }
+        });
+
         // Update the progress map
-        {
+        let emit = {
             let mut map = self.progress_map.lock().map_err(|e| e.to_string())?;
-            map.insert(task_id.clone(), progress.clone());
+            self.prune_expired(&mut map);
+            if progress.scope().is_empty() {
+                // Updates without a scope keep the one the task was reported under
+                let scope = assigned.or_else(|| map.get(&task_id).map(|e| e.progress.scope()));
+                if let Some(scope) = scope {
+                    progress.session_id = scope.session_id;
+                    progress.config_id = scope.config_id;
+                }
+            }
+
+            let previous = map.remove(&task_id);
+            let emitted = previous.as_ref().and_then(|entry| entry.emitted);
+            let mut speed = previous.map(|entry| entry.speed).unwrap_or_default();
+            if progress.status == UploadStatus::Uploading {
+                let estimated = speed.record(now, progress.bytes_uploaded);
+                progress.speed = progress.speed.or(estimated);
+                progress.eta_secs = eta_secs(
+                    progress.total_bytes.saturating_sub(progress.bytes_uploaded),
+                    progress.speed,
+                );
+            } else {
+                progress.eta_secs = None;
+            }
+            let mut entry = ProgressEntry {
+                progress: progress.clone(),
+                finished_at,
+                updated_at: now,
+                speed,
+                emitted,
+                unsent: true,
+            };
+            let emit = entry.should_emit(now);
+            if emit {
+                entry.mark_emitted(now);
+            }
+            map.insert(task_id.clone(), entry);
+
+            if let Some(session_id) = &progress.session_id {
+                let session_bytes = map
+                    .values()
+                    .filter(|entry| entry.progress.session_id.as_ref() == Some(session_id))
+                    .map(|entry| entry.progress.bytes_uploaded)
+                    .sum();
+                if let Ok(mut speeds) = self.session_speeds.lock() {
+                    speeds
+                        .entry(session_id.clone())
+                        .or_default()
+                        .record(now, session_bytes);
+                }
+            }
+            emit
+        };
+
+        if emit {
+            self.emit(&progress);
         }
 
-        // Broadcast the update
-        if self.sender.send(progress.clone()).is_err() {
-            // No receivers, which is fine
-        }
+        Ok(())
+    }
 
-        // Emit Tauri event for frontend listeners
-        if let Some(app_handle) = &self.app_handle {
-            let _ = app_handle.emit("upload-progress", &progress);
+    /// Mark a task as finished, keeping its entry for the retention window so
+    /// late polls still see the outcome
+    pub fn complete_progress(
+        &self,
+        task_id: &str,
+        status: UploadStatus,
+        error: Option<String>,
+    ) -> Result<(), String> {
+        let progress = {
+            let map = self.progress_map.lock().map_err(|e| e.to_string())?;
+            map.get(task_id).map(|entry| entry.progress.clone())
+        };
+
+        let mut progress = progress.unwrap_or_else(|| UploadProgress {
+            image_id: task_id.to_string(),
+            progress: 0.0,
+            bytes_uploaded: 0,
+            total_bytes: 0,
+            speed: None,
+            eta_secs: None,
+            status: UploadStatus::Pending,
+            error: None,
+            session_id: None,
+            config_id: None,
+        });
+
+        if status == UploadStatus::Completed {
+            progress.progress = 100.0;
+            progress.bytes_uploaded = progress.total_bytes;
         }
+        progress.status = status;
+        progress.error = error;
+        progress.speed = None;
 
-        Ok(())
+        self.update_progress(task_id.to_string(), progress)
     }
 
     /// Get current progress for a task
     pub fn get_progress(&self, task_id: &str) -> Result<Option<UploadProgress>, String> {
-        let map = self.progress_map.lock().map_err(|e| e.to_string())?;
-        Ok(map.get(task_id).cloned())
+        let mut map = self.progress_map.lock().map_err(|e| e.to_string())?;
+        self.prune_expired(&mut map);
+        Ok(map.get(task_id).map(|entry| entry.progress.clone()))
     }
 
     /// Remove progress tracking for a completed task
     pub fn remove_progress(&self, task_id: &str) -> Result<(), String> {
+        self.release_scope(task_id);
         let mut map = self.progress_map.lock().map_err(|e| e.to_string())?;
         map.remove(task_id);
         Ok(())
     }
 
+    /// Remove tracking for every task `filter` matches, including the ones
+    /// that haven't reported progress yet. Returns the removed task ids.
+    pub fn remove_scope(&self, filter: &UploadScope) -> Result<Vec<String>, String> {
+        let mut removed: Vec<String> = Vec::new();
+        if let Ok(mut scopes) = self.scopes.lock() {
+            scopes.retain(|task_id, scope| {
+                let matched = filter.matches(scope);
+                if matched {
+                    removed.push(task_id.clone());
+                }
+                !matched
+            });
+        }
+        let mut map = self.progress_map.lock().map_err(|e| e.to_string())?;
+        map.retain(|task_id, entry| {
+            let matched = filter.matches(&entry.progress.scope());
+            if matched && !removed.contains(task_id) {
+                removed.push(task_id.clone());
+            }
+            !matched
+        });
+        if let (Some(session_id), Ok(mut speeds)) = (&filter.session_id, self.session_speeds.lock())
+        {
+            speeds.remove(session_id);
+        }
+        Ok(removed)
+    }
+
+    /// Scope a task was reported under, None when it isn't tracked
+    pub fn scope_of(&self, task_id: &str) -> Option<UploadScope> {
+        let assigned = self
+            .scopes
+            .lock()
+            .ok()
+            .and_then(|scopes| scopes.get(task_id).cloned());
+        assigned.or_else(|| {
+            self.progress_map
+                .lock()
+                .ok()
+                .and_then(|map| map.get(task_id).map(|entry| entry.progress.scope()))
+        })
+    }
+
+    /// Send listeners the latest value of every task whose last updates were
+    /// held back. Returns how many were sent.
+    pub fn flush_pending(&self) -> usize {
+        self.flush_pending_at(Instant::now())
+    }
+
+    fn flush_pending_at(&self, now: Instant) -> usize {
+        let Ok(mut map) = self.progress_map.lock() else {
+            return 0;
+        };
+        // Sent under the lock, so a terminal update can't overtake the
+        // in-flight value flushed before it
+        let mut flushed = 0;
+        for entry in map.values_mut().filter(|entry| entry.unsent) {
+            entry.mark_emitted(now);
+            self.emit(&entry.progress);
+            flushed += 1;
+        }
+        flushed
+    }
+
     /// Get a receiver for progress updates
     #[allow(dead_code)]
     pub fn subscribe(&self) -> broadcast::Receiver<UploadProgress> {
@@ -79,16 +388,71 @@ impl ProgressNotifier {
 
     /// Get all current progress states
     pub fn get_all_progress(&self) -> Result<Vec<UploadProgress>, String> {
-        let map = self.progress_map.lock().map_err(|e| e.to_string())?;
-        Ok(map.values().cloned().collect())
+        let mut map = self.progress_map.lock().map_err(|e| e.to_string())?;
+        self.prune_expired(&mut map);
+        Ok(map.values().map(|entry| entry.progress.clone()).collect())
+    }
+
+    /// In-flight entries updated within `idle`, i.e. uploads still reporting
+    pub fn active_count(&self, idle: Duration) -> usize {
+        self.progress_map
+            .lock()
+            .map(|map| {
+                map.values()
+                    .filter(|entry| {
+                        entry.finished_at.is_none() && entry.updated_at.elapsed() < idle
+                    })
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Drop in-flight entries not updated for `idle`: uploads that went away
+    /// without reporting a final status. Returns how many were removed.
+    pub fn remove_stalled(&self, idle: Duration) -> usize {
+        let Ok(mut map) = self.progress_map.lock() else {
+            return 0;
+        };
+        let before = map.len();
+        map.retain(|_, entry| entry.finished_at.is_some() || entry.updated_at.elapsed() < idle);
+        before - map.len()
     }
 
     /// Clear all progress data
     pub fn clear_all(&self) -> Result<(), String> {
+        if let Ok(mut scopes) = self.scopes.lock() {
+            scopes.clear();
+        }
+        if let Ok(mut speeds) = self.session_speeds.lock() {
+            speeds.clear();
+        }
         let mut map = self.progress_map.lock().map_err(|e| e.to_string())?;
         map.clear();
         Ok(())
     }
+
+    // Lazy pruning: terminal entries older than the retention window are dropped
+    // whenever the map is touched, so it can't grow without bound
+    fn prune_expired(&self, map: &mut HashMap<String, ProgressEntry>) {
+        let retention = self.retention;
+        map.retain(|_, entry| {
+            entry
+                .finished_at
+                .is_none_or(|finished| finished.elapsed() < retention)
+        });
+    }
+
+    fn emit(&self, progress: &UploadProgress) {
+        // Broadcast the update
+        if self.sender.send(progress.clone()).is_err() {
+            // No receivers, which is fine
+        }
+
+        // Emit Tauri event for frontend listeners
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit("upload-progress", progress);
+        }
+    }
 }
 
 impl Default for ProgressNotifier {
@@ -102,6 +466,14 @@ lazy_static::lazy_static! {
     pub static ref PROGRESS_NOTIFIER: ProgressNotifier = ProgressNotifier::new();
 }
 
+/// Sends the held-back progress updates every `EMIT_INTERVAL`
+pub async fn flush_progress_events() {
+    loop {
+        tokio::time::sleep(EMIT_INTERVAL).await;
+        PROGRESS_NOTIFIER.flush_pending();
+    }
+}
+
 /// Helper function to create progress update
 #[allow(dead_code)]
 pub fn create_progress_update(
@@ -117,6 +489,11 @@ pub fn create_progress_update(
         bytes_uploaded,
         total_bytes,
         speed,
+        eta_secs: None,
+        status: UploadStatus::Uploading,
+        error: None,
+        session_id: None,
+        config_id: None,
     }
 }
 
@@ -206,6 +583,348 @@ mod tests {
         assert!(notifier.get_all_progress().unwrap().is_empty());
     }
 
+    #[test]
+    fn test_terminal_entries_are_kept() {
+        let notifier = ProgressNotifier::new();
+        let progress = create_progress_update("image-done".to_string(), 40.0, 400, 1000, None);
+        notifier
+            .update_progress("task-done".to_string(), progress)
+            .unwrap();
+
+        notifier
+            .complete_progress("task-done", UploadStatus::Completed, None)
+            .unwrap();
+        let done = notifier.get_progress("task-done").unwrap().unwrap();
+        assert_eq!(done.status, UploadStatus::Completed);
+        assert_eq!(done.progress, 100.0);
+        assert_eq!(done.bytes_uploaded, 1000);
+
+        // A task that failed before reporting any progress still gets an entry
+        notifier
+            .complete_progress(
+                "task-failed",
+                UploadStatus::Failed,
+                Some("boom".to_string()),
+            )
+            .unwrap();
+        let failed = notifier.get_progress("task-failed").unwrap().unwrap();
+        assert_eq!(failed.status, UploadStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_terminal_entries_expire() {
+        let notifier = ProgressNotifier::new().with_retention(Duration::ZERO);
+        let progress = create_progress_update("image-live".to_string(), 10.0, 10, 100, None);
+        notifier
+            .update_progress("task-live".to_string(), progress)
+            .unwrap();
+        notifier
+            .complete_progress("task-old", UploadStatus::Completed, None)
+            .unwrap();
+
+        // In-flight entries are never pruned
+        let all = notifier.get_all_progress().unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].image_id, "image-live");
+    }
+
+    #[test]
+    fn test_stalled_entries_are_removed() {
+        let notifier = ProgressNotifier::new();
+        let progress = create_progress_update("image-stalled".to_string(), 10.0, 10, 100, None);
+        notifier
+            .update_progress("task-stalled".to_string(), progress)
+            .unwrap();
+        notifier
+            .complete_progress("task-done", UploadStatus::Completed, None)
+            .unwrap();
+        assert_eq!(notifier.active_count(Duration::from_secs(60)), 1);
+        assert_eq!(notifier.remove_stalled(Duration::from_secs(60)), 0);
+
+        // Everything in flight counts as stalled with a zero idle window;
+        // finished entries are left to retention pruning
+        assert_eq!(notifier.active_count(Duration::ZERO), 0);
+        assert_eq!(notifier.remove_stalled(Duration::ZERO), 1);
+        let all = notifier.get_all_progress().unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].status, UploadStatus::Completed);
+    }
+
+    #[test]
+    fn test_concurrent_sessions_are_reported_and_cancelled_separately() {
+        let notifier = ProgressNotifier::new();
+        let scope = |session: &str, config: &str| UploadScope {
+            session_id: Some(session.to_string()),
+            config_id: Some(config.to_string()),
+        };
+        notifier.assign_scope("task-a1", scope("session-a", "config-a"));
+        notifier.assign_scope("task-a2", scope("session-a", "config-a"));
+        notifier.assign_scope("task-b1", scope("session-b", "config-b"));
+
+        // Uploaders report unscoped updates, the notifier fills in the session
+        for task_id in ["task-a1", "task-b1"] {
+            let progress = create_progress_update(task_id.to_string(), 50.0, 50, 100, None);
+            notifier
+                .update_progress(task_id.to_string(), progress)
+                .unwrap();
+        }
+        let a1 = notifier.get_progress("task-a1").unwrap().unwrap();
+        assert_eq!(a1.scope(), scope("session-a", "config-a"));
+        let b1 = notifier.get_progress("task-b1").unwrap().unwrap();
+        assert_eq!(b1.session_id.as_deref(), Some("session-b"));
+
+        // Finished tasks keep their scope after the assignment is released
+        notifier
+            .complete_progress("task-b1", UploadStatus::Completed, None)
+            .unwrap();
+        notifier.end_session("session-b");
+        assert_eq!(
+            notifier.scope_of("task-b1"),
+            Some(scope("session-b", "config-b"))
+        );
+
+        // Cancelling session a also drops its task that hasn't started yet
+        let filter = UploadScope {
+            session_id: Some("session-a".to_string()),
+            config_id: None,
+        };
+        let mut removed = notifier.remove_scope(&filter).unwrap();
+        removed.sort();
+        assert_eq!(removed, vec!["task-a1", "task-a2"]);
+        assert!(notifier.scope_of("task-a2").is_none());
+        let remaining = notifier.get_all_progress().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].image_id, "task-b1");
+    }
+
+    // 64 KB chunks arriving 0.2s and 0.8s apart in turn: 128 KB/s on
+    // average, 320 KB/s and 80 KB/s between neighbouring updates
+    fn bursty_chunks(start: Instant, count: usize) -> Vec<(Instant, u64)> {
+        let mut at = start;
+        (1..=count)
+            .map(|i| {
+                at += if i % 2 == 0 {
+                    Duration::from_millis(800)
+                } else {
+                    Duration::from_millis(200)
+                };
+                (at, i as u64 * 64 * 1024)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_speed_window_smooths_bursty_chunks() {
+        let start = Instant::now();
+        let mut window = SpeedWindow::default();
+        assert_eq!(window.record(start, 0), None);
+
+        let average = 128.0 * 1024.0;
+        for (at, bytes) in bursty_chunks(start, 20) {
+            let speed = window.record(at, bytes);
+            if at - start >= SPEED_WINDOW {
+                let speed = speed.unwrap() as f64;
+                assert!(
+                    (speed - average).abs() < average * 0.2,
+                    "{} B/s at {:?}",
+                    speed,
+                    at - start
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_speed_window_follows_a_slowdown_and_restarts() {
+        let start = Instant::now();
+        let mut window = SpeedWindow::default();
+        // 1 MB/s for 4s, then 256 KB/s, updates every 250ms
+        let mut bytes = 0;
+        for tick in 0..=40u64 {
+            let speed = window.record(start + Duration::from_millis(tick * 250), bytes);
+            if tick == 16 {
+                assert_eq!(speed, Some(1024 * 1024));
+            }
+            if tick == 40 {
+                assert_eq!(speed, Some(256 * 1024));
+            }
+            bytes += if tick < 16 { 256 * 1024 } else { 64 * 1024 };
+        }
+
+        // A retry reports from zero again, the old samples don't count
+        let retry = start + Duration::from_secs(11);
+        assert_eq!(window.record(retry, 0), None);
+        assert_eq!(
+            window.record(retry + Duration::from_secs(1), 300 * 1024),
+            Some(300 * 1024)
+        );
+    }
+
+    #[test]
+    fn test_notifier_estimates_task_and_session_speed() {
+        let notifier = ProgressNotifier::new();
+        let scope = UploadScope {
+            session_id: Some("session".to_string()),
+            config_id: None,
+        };
+        notifier.assign_scope("task-a", scope.clone());
+        notifier.assign_scope("task-b", scope);
+
+        let start = Instant::now();
+        let total = 4 * 1024 * 1024;
+        for (at, bytes) in bursty_chunks(start, 8) {
+            for task_id in ["task-a", "task-b"] {
+                let progress = create_progress_update(
+                    task_id.to_string(),
+                    bytes as f32 / total as f32 * 100.0,
+                    bytes,
+                    total,
+                    None,
+                );
+                notifier
+                    .update_progress_at(task_id.to_string(), progress, at)
+                    .unwrap();
+            }
+        }
+
+        let a = notifier.get_progress("task-a").unwrap().unwrap();
+        let speed = a.speed.unwrap();
+        assert!((110 * 1024..150 * 1024).contains(&speed), "{}", speed);
+        assert_eq!(a.eta_secs, eta_secs(total - 8 * 64 * 1024, a.speed));
+        let session = notifier.session_speed("session").unwrap();
+        assert!(
+            (2 * 110 * 1024..2 * 150 * 1024).contains(&session),
+            "{}",
+            session
+        );
+
+        notifier
+            .complete_progress("task-a", UploadStatus::Completed, None)
+            .unwrap();
+        let a = notifier.get_progress("task-a").unwrap().unwrap();
+        assert_eq!((a.speed, a.eta_secs), (None, None));
+        notifier.end_session("session");
+        assert_eq!(notifier.session_speed("session"), None);
+    }
+
+    #[test]
+    fn test_in_flight_updates_are_coalesced() {
+        let notifier = ProgressNotifier::new();
+        let mut receiver = notifier.subscribe();
+        let start = Instant::now();
+        let update = |millis: u64, percent: f32| {
+            let progress =
+                create_progress_update("task".to_string(), percent, percent as u64, 100, None);
+            notifier
+                .update_progress_at(
+                    "task".to_string(),
+                    progress,
+                    start + Duration::from_millis(millis),
+                )
+                .unwrap();
+        };
+
+        // The first update goes out, the next two stay within its percent and tick
+        update(0, 10.2);
+        update(20, 10.5);
+        update(40, 10.9);
+        assert_eq!(receiver.try_recv().unwrap().progress, 10.2);
+        assert!(receiver.try_recv().is_err());
+        // Polling sees the held-back value
+        let stored = notifier.get_progress("task").unwrap().unwrap();
+        assert_eq!(stored.progress, 10.9);
+
+        // Reaching another whole percent or the next tick sends right away
+        update(60, 11.0);
+        assert_eq!(receiver.try_recv().unwrap().progress, 11.0);
+        update(170, 11.4);
+        assert_eq!(receiver.try_recv().unwrap().progress, 11.4);
+
+        // A flush sends what's held back, once
+        update(180, 11.6);
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(
+            notifier.flush_pending_at(start + Duration::from_millis(200)),
+            1
+        );
+        assert_eq!(receiver.try_recv().unwrap().progress, 11.6);
+        assert_eq!(notifier.flush_pending(), 0);
+    }
+
+    #[test]
+    fn test_hammered_updates_emit_bounded_events_and_every_terminal_status() {
+        const TASKS: usize = 8;
+        const UPDATES: usize = 2_000;
+        let notifier = ProgressNotifier::new();
+        let mut receiver = notifier.subscribe();
+        let start = Instant::now();
+
+        std::thread::scope(|scope| {
+            for task in 0..TASKS {
+                let notifier = &notifier;
+                scope.spawn(move || {
+                    let task_id = format!("task-{}", task);
+                    for i in 0..UPDATES {
+                        let bytes = i as u64 * 100;
+                        let progress = create_progress_update(
+                            task_id.clone(),
+                            i as f32 / UPDATES as f32 * 100.0,
+                            bytes,
+                            UPDATES as u64 * 100,
+                            None,
+                        );
+                        notifier.update_progress(task_id.clone(), progress).unwrap();
+                    }
+                    let status = if task % 2 == 0 {
+                        UploadStatus::Completed
+                    } else {
+                        UploadStatus::Failed
+                    };
+                    notifier.complete_progress(&task_id, status, None).unwrap();
+                });
+            }
+        });
+        let elapsed = start.elapsed();
+
+        let mut emitted = 0;
+        let mut terminal = HashMap::new();
+        loop {
+            match receiver.try_recv() {
+                Ok(progress) => {
+                    emitted += 1;
+                    if progress.status != UploadStatus::Uploading {
+                        terminal.insert(progress.image_id.clone(), progress.status);
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => emitted += skipped as usize,
+                Err(_) => break,
+            }
+        }
+
+        // Per task: the first update, one per whole percent, one per tick and
+        // the terminal status
+        let ticks = (elapsed.as_millis() / EMIT_INTERVAL.as_millis()) as usize + 1;
+        let bound = TASKS * (1 + 100 + ticks + 1);
+        assert!(emitted <= bound, "{} events, bound {}", emitted, bound);
+        assert!(emitted < TASKS * UPDATES / 10, "{} events", emitted);
+
+        assert_eq!(terminal.len(), TASKS);
+        for task in 0..TASKS {
+            let task_id = format!("task-{}", task);
+            let expected = if task % 2 == 0 {
+                UploadStatus::Completed
+            } else {
+                UploadStatus::Failed
+            };
+            assert_eq!(terminal[&task_id], expected);
+            let stored = notifier.get_progress(&task_id).unwrap().unwrap();
+            assert_eq!(stored.status, expected);
+        }
+        // Terminal updates went out as they happened, nothing is held back
+        assert_eq!(notifier.flush_pending(), 0);
+    }
+
     #[test]
     fn test_subscribe() {
         let notifier = ProgressNotifier::new();
@@ -0,0 +1,146 @@
+use crate::models::{DuplicateScanProgress, ScanProgress, ScanResultEvent, ScanSummary};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Emitter};
+
+pub const SCAN_PROGRESS_EVENT: &str = "scan://progress";
+pub const SCAN_DONE_EVENT: &str = "scan://done";
+pub const SCAN_RESULT_EVENT: &str = "scan://result";
+pub const DUPLICATES_PROGRESS_EVENT: &str = "duplicates://progress";
+pub const INTEGRITY_PROGRESS_EVENT: &str = "integrity://progress";
+
+/// Emits scan progress events and keeps the cancellation flag of every
+/// running scan that was started with a scan_id
+pub struct ScanTracker {
+    // Set once in setup; without it events are dropped (tests, headless runs)
+    app_handle: OnceLock<AppHandle>,
+    cancellations: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl ScanTracker {
+    pub fn new() -> Self {
+        Self {
+            app_handle: OnceLock::new(),
+            cancellations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    /// Cancellation flag for a new scan; anonymous scans get one nobody can set
+    pub fn start(&self, scan_id: Option<&str>) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        if let (Some(id), Ok(mut cancellations)) = (scan_id, self.cancellations.lock()) {
+            cancellations.insert(id.to_string(), flag.clone());
+        }
+        flag
+    }
+
+    /// Ask a running scan to stop before its next file; false if it isn't running
+    pub fn cancel(&self, scan_id: &str) -> bool {
+        match self.cancellations.lock() {
+            Ok(cancellations) => match cancellations.get(scan_id) {
+                Some(flag) => {
+                    flag.store(true, Ordering::Relaxed);
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn progress(&self, progress: &ScanProgress) {
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(SCAN_PROGRESS_EVENT, progress);
+        }
+    }
+
+    pub fn result(&self, event: &ScanResultEvent) {
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(SCAN_RESULT_EVENT, event);
+        }
+    }
+
+    pub fn duplicate_progress(&self, progress: &DuplicateScanProgress) {
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(DUPLICATES_PROGRESS_EVENT, progress);
+        }
+    }
+
+    pub fn integrity_progress(&self, progress: &DuplicateScanProgress) {
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(INTEGRITY_PROGRESS_EVENT, progress);
+        }
+    }
+
+    /// Emit `scan://done` and forget the scan's cancellation flag
+    pub fn finish(&self, summary: &ScanSummary) {
+        if let Some(id) = &summary.scan_id {
+            self.forget(id);
+        }
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(SCAN_DONE_EVENT, summary);
+        }
+    }
+
+    /// Forget a scan's cancellation flag without emitting anything
+    pub fn forget(&self, scan_id: &str) {
+        if let Ok(mut cancellations) = self.cancellations.lock() {
+            cancellations.remove(scan_id);
+        }
+    }
+}
+
+impl Default for ScanTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref SCAN_TRACKER: ScanTracker = ScanTracker::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(scan_id: &str) -> ScanSummary {
+        ScanSummary {
+            scan_id: Some(scan_id.to_string()),
+            total_files: 2,
+            scanned_files: 1,
+            total_images: 0,
+            missing_images: 0,
+            failed_files: 0,
+            skipped_files: 0,
+            ignored_files: 0,
+            cancelled: true,
+        }
+    }
+
+    #[test]
+    fn test_cancel_sets_flag_until_finished() {
+        let tracker = ScanTracker::new();
+        let flag = tracker.start(Some("scan-1"));
+        assert!(!tracker.cancel("scan-2"));
+
+        assert!(tracker.cancel("scan-1"));
+        assert!(flag.load(Ordering::Relaxed));
+
+        tracker.finish(&summary("scan-1"));
+        assert!(!tracker.cancel("scan-1"));
+    }
+
+    #[test]
+    fn test_anonymous_scan_is_not_cancellable() {
+        let tracker = ScanTracker::new();
+        let flag = tracker.start(None);
+        assert!(!flag.load(Ordering::Relaxed));
+        assert!(tracker.cancellations.lock().unwrap().is_empty());
+    }
+}
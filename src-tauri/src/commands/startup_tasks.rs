@@ -0,0 +1,253 @@
+use super::pending::PENDING_UPLOADS;
+use crate::models::{StartupReport, StartupTaskOutcome, StartupTaskStatus};
+use crate::services::ImageService;
+use crate::utils::Result;
+use crate::{log_info, log_warn};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A task still running after this long is abandoned and the next one starts
+const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(60);
+
+type TaskFuture = Pin<Box<dyn Future<Output = Result<String>> + Send>>;
+
+/// One piece of startup work. Tasks run on every launch, so each must be
+/// idempotent: a run after a successful one finds nothing left to do. The Ok
+/// value is the line shown in the startup report.
+pub struct StartupTask {
+    pub id: &'static str,
+    pub timeout: Duration,
+    run: Box<dyn Fn() -> TaskFuture + Send + Sync>,
+}
+
+impl StartupTask {
+    pub fn new<F, Fut>(id: &'static str, run: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        Self {
+            id,
+            timeout: DEFAULT_TASK_TIMEOUT,
+            run: Box::new(move || Box::pin(run())),
+        }
+    }
+}
+
+/// The tasks every launch runs, in order
+pub fn startup_tasks() -> Vec<StartupTask> {
+    vec![
+        StartupTask::new("recovered_uploads", || async {
+            // Loaded before the window opened, see lib.rs
+            Ok(match PENDING_UPLOADS.recovered().len() {
+                0 => "no unfinished uploads".to_string(),
+                count => format!("{} unfinished uploads from the last run", count),
+            })
+        }),
+        StartupTask::new("thumbnail_cache_cleanup", || async {
+            let removed = ImageService::with_cache()?.cleanup_old_cache().await?;
+            Ok(format!("removed {} cached thumbnails", removed))
+        }),
+    ]
+}
+
+/// Runs the startup tasks one after another in the background and keeps their
+/// outcomes for `get_startup_report`. A failing or hanging task is recorded
+/// and the next one still runs; the window never waits for any of them.
+pub struct StartupRunner {
+    report: Mutex<StartupReport>,
+}
+
+impl StartupRunner {
+    pub fn new() -> Self {
+        Self {
+            report: Mutex::new(StartupReport::default()),
+        }
+    }
+
+    pub fn report(&self) -> StartupReport {
+        self.report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Run `tasks` in order, except the ids listed in `skipped`
+    pub async fn run(&self, tasks: Vec<StartupTask>, skipped: &[String]) {
+        self.update(|report| {
+            *report = StartupReport {
+                started_at: Some(chrono::Utc::now()),
+                finished_at: None,
+                tasks: tasks
+                    .iter()
+                    .map(|task| StartupTaskOutcome {
+                        id: task.id.to_string(),
+                        status: StartupTaskStatus::Pending,
+                        message: None,
+                        duration_ms: 0,
+                    })
+                    .collect(),
+            }
+        });
+
+        for (index, task) in tasks.iter().enumerate() {
+            if skipped.iter().any(|id| id == task.id) {
+                log_info!(
+                    operation = "startup_task",
+                    task = task.id,
+                    "Startup task skipped by settings"
+                );
+                self.update(|report| report.tasks[index].status = StartupTaskStatus::Skipped);
+                continue;
+            }
+
+            self.update(|report| report.tasks[index].status = StartupTaskStatus::Running);
+            let started = Instant::now();
+            let (status, message) = match tokio::time::timeout(task.timeout, (task.run)()).await {
+                Ok(Ok(summary)) => (StartupTaskStatus::Succeeded, summary),
+                Ok(Err(e)) => (StartupTaskStatus::Failed, e.to_string()),
+                Err(_) => (
+                    StartupTaskStatus::TimedOut,
+                    format!("did not finish within {}s", task.timeout.as_secs()),
+                ),
+            };
+            let duration_ms = started.elapsed().as_millis() as u64;
+
+            if status == StartupTaskStatus::Succeeded {
+                log_info!(
+                    operation = "startup_task",
+                    task = task.id,
+                    duration_ms = duration_ms,
+                    summary = %message,
+                    "Startup task finished"
+                );
+            } else {
+                log_warn!(
+                    operation = "startup_task",
+                    task = task.id,
+                    status = ?status,
+                    duration_ms = duration_ms,
+                    error = %message,
+                    "Startup task did not succeed"
+                );
+            }
+            self.update(|report| {
+                let outcome = &mut report.tasks[index];
+                outcome.status = status;
+                outcome.message = Some(message);
+                outcome.duration_ms = duration_ms;
+            });
+        }
+
+        self.update(|report| report.finished_at = Some(chrono::Utc::now()));
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut StartupReport)) {
+        apply(&mut self.report.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+impl Default for StartupRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entry point for the setup hook
+pub async fn run_startup_tasks() {
+    let settings = super::current_app_settings().await;
+    super::apply_app_settings(&settings);
+    STARTUP_TASKS
+        .run(startup_tasks(), &settings.skipped_startup_tasks)
+        .await;
+}
+
+lazy_static::lazy_static! {
+    pub static ref STARTUP_TASKS: StartupRunner = StartupRunner::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AppError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_tasks_run_in_order_and_failures_do_not_stop_the_rest() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let task = |id: &'static str, result: Result<String>| {
+            let order = order.clone();
+            let result = Arc::new(Mutex::new(Some(result)));
+            StartupTask::new(id, move || {
+                order.lock().unwrap().push(id);
+                let result = result.lock().unwrap().take().unwrap();
+                async move { result }
+            })
+        };
+        let tasks = vec![
+            task("migrate", Ok("migrated 3 records".to_string())),
+            task("broken", Err(AppError::FileSystem("disk full".to_string()))),
+            task("skipped", Ok(String::new())),
+            task("last", Ok("done".to_string())),
+        ];
+
+        let runner = StartupRunner::new();
+        runner.run(tasks, &["skipped".to_string()]).await;
+
+        assert_eq!(*order.lock().unwrap(), vec!["migrate", "broken", "last"]);
+        let report = runner.report();
+        assert!(report.finished_at.is_some());
+        let statuses: Vec<_> = report.tasks.iter().map(|t| t.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                StartupTaskStatus::Succeeded,
+                StartupTaskStatus::Failed,
+                StartupTaskStatus::Skipped,
+                StartupTaskStatus::Succeeded,
+            ]
+        );
+        assert_eq!(
+            report.tasks[0].message.as_deref(),
+            Some("migrated 3 records")
+        );
+        assert!(report.tasks[1]
+            .message
+            .as_deref()
+            .unwrap()
+            .contains("disk full"));
+        assert!(report.tasks[2].message.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hanging_task_times_out() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let counter = finished.clone();
+        let mut hangs = StartupTask::new("hangs", || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(String::new())
+        });
+        hangs.timeout = Duration::from_millis(20);
+        let tasks = vec![
+            hangs,
+            StartupTask::new("next", move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok("ran".to_string())
+                }
+            }),
+        ];
+
+        let runner = StartupRunner::new();
+        runner.run(tasks, &[]).await;
+
+        let report = runner.report();
+        assert_eq!(report.tasks[0].status, StartupTaskStatus::TimedOut);
+        assert_eq!(report.tasks[1].status, StartupTaskStatus::Succeeded);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::models::{ConfigTestProgress, ConnectionTestStep};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Emitter};
+
+pub const TEST_PROGRESS_EVENT: &str = "config://test-progress";
+pub const TEST_ALL_PROGRESS_EVENT: &str = "config://test-all-progress";
+
+/// Streams the steps of connection tests and config validations, and the
+/// per-config results of `test_all_configs`, to the settings UI
+pub struct TestProgress {
+    // Set once in setup; without it events are dropped (tests, headless runs)
+    app_handle: OnceLock<AppHandle>,
+}
+
+impl TestProgress {
+    pub fn new() -> Self {
+        Self {
+            app_handle: OnceLock::new(),
+        }
+    }
+
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    /// Emit `step` and add it to the diagnostics of the running test
+    pub fn report(&self, diagnostics: &mut Vec<ConnectionTestStep>, step: ConnectionTestStep) {
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(TEST_PROGRESS_EVENT, &step);
+        }
+        diagnostics.push(step);
+    }
+
+    /// Emit a config finished by `test_all_configs`
+    pub fn report_config(&self, progress: &ConfigTestProgress) {
+        if let Some(app_handle) = self.app_handle.get() {
+            let _ = app_handle.emit(TEST_ALL_PROGRESS_EVENT, progress);
+        }
+    }
+}
+
+impl Default for TestProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref TEST_PROGRESS: TestProgress = TestProgress::new();
+}
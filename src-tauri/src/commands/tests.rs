@@ -42,6 +42,16 @@ mod command_tests {
         (temp_dir, file_path.to_string_lossy().to_string())
     }
 
+    // Helper function to create a PNG the image crate can actually decode
+    fn create_decodable_image_file() -> (TempDir, String) {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("photo.png");
+        image::RgbImage::from_fn(16, 8, |x, y| image::Rgb([x as u8 * 16, y as u8 * 32, 128]))
+            .save(&file_path)
+            .unwrap();
+        (temp_dir, file_path.to_string_lossy().to_string())
+    }
+
     // Helper function to create a test OSS config
     fn create_test_oss_config() -> OSSConfig {
         OSSConfig {
@@ -52,9 +62,16 @@ mod command_tests {
             bucket: "test-bucket".to_string(),
             region: "cn-hangzhou".to_string(),
             path_template: "images/{date}/{filename}".to_string(),
-            cdn_domain: Some("https://cdn.example.com".to_string()),
+            cdn_domain: Some("cdn.example.com".to_string()),
             compression_enabled: true,
             compression_quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
         }
     }
 
@@ -64,7 +81,7 @@ mod command_tests {
 
     #[test]
     fn test_validate_file_paths_empty() {
-        let result = validate_file_paths(&[]);
+        let result = validate_file_paths(&[], 100);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("cannot be empty"));
     }
@@ -72,15 +89,26 @@ mod command_tests {
     #[test]
     fn test_validate_file_paths_too_many() {
         let paths: Vec<String> = (0..101).map(|i| format!("file{}.md", i)).collect();
-        let result = validate_file_paths(&paths);
+        let result = validate_file_paths(&paths, 100);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Too many files"));
     }
 
+    #[test]
+    fn test_validate_limits_state_configured_max() {
+        let paths: Vec<String> = (0..4).map(|i| format!("file{}.md", i)).collect();
+        let error = validate_file_paths(&paths, 3).unwrap_err().to_string();
+        assert!(error.contains("max 3, adjustable in settings"));
+
+        let error = validate_upload_count(601, 600).unwrap_err().to_string();
+        assert!(error.contains("max 600, adjustable in settings"));
+        assert!(validate_upload_count(600, 600).is_ok());
+    }
+
     #[test]
     fn test_validate_file_paths_path_traversal() {
         let paths = vec!["../../../etc/passwd".to_string()];
-        let result = validate_file_paths(&paths);
+        let result = validate_file_paths(&paths, 100);
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -91,14 +119,14 @@ mod command_tests {
     #[test]
     fn test_validate_file_paths_nonexistent() {
         let paths = vec!["/nonexistent/file.md".to_string()];
-        let result = validate_file_paths(&paths);
+        let result = validate_file_paths(&paths, 100);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("File not found"));
     }
 
     #[test]
     fn test_validate_image_ids_empty() {
-        let result = validate_image_ids(&[]);
+        let result = validate_image_ids(&[], 50);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("cannot be empty"));
     }
@@ -108,7 +136,7 @@ mod command_tests {
         let ids: Vec<String> = (0..51)
             .map(|i| format!("12345678-1234-1234-1234-12345678901{:01}", i))
             .collect();
-        let result = validate_image_ids(&ids);
+        let result = validate_image_ids(&ids, 50);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Too many images"));
     }
@@ -116,7 +144,7 @@ mod command_tests {
     #[test]
     fn test_validate_image_ids_invalid_format() {
         let ids = vec!["invalid-id".to_string()];
-        let result = validate_image_ids(&ids);
+        let result = validate_image_ids(&ids, 50);
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -127,7 +155,7 @@ mod command_tests {
     #[test]
     fn test_validate_image_ids_valid() {
         let ids = vec!["12345678-1234-1234-1234-123456789012".to_string()];
-        let result = validate_image_ids(&ids);
+        let result = validate_image_ids(&ids, 50);
         assert!(result.is_ok());
     }
 
@@ -140,7 +168,7 @@ mod command_tests {
         assert!(result
             .unwrap_err()
             .to_string()
-            .contains("endpoint cannot be empty"));
+            .contains("Endpoint is required"));
     }
 
     #[test]
@@ -164,7 +192,7 @@ mod command_tests {
         assert!(result
             .unwrap_err()
             .to_string()
-            .contains("must be between 0-100"));
+            .contains("must be between 0 and 100"));
     }
 
     #[test]
@@ -224,14 +252,14 @@ mod command_tests {
 
     #[tokio::test]
     async fn test_scan_markdown_files_empty_paths() {
-        let result = scan_markdown_files(vec![]).await;
+        let result = scan_markdown_files(vec![], None, None, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("cannot be empty"));
     }
 
     #[tokio::test]
     async fn test_scan_markdown_files_invalid_path() {
-        let result = scan_markdown_files(vec!["../invalid.md".to_string()]).await;
+        let result = scan_markdown_files(vec!["../invalid.md".to_string()], None, None, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Invalid file path"));
     }
@@ -257,9 +285,110 @@ mod command_tests {
         assert!(result.unwrap_err().contains("not found"));
     }
 
+    #[tokio::test]
+    async fn test_validate_images_reports_each_path() {
+        let (temp_dir, image_path) = create_decodable_image_file();
+        let heic = temp_dir.path().join("IMG_0001.heic");
+        fs::write(&heic, b"ftypheic").unwrap();
+
+        let results = validate_images(vec![
+            image_path.clone(),
+            heic.to_string_lossy().to_string(),
+            "/nonexistent/image.png".to_string(),
+            "../escape.png".to_string(),
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].path, image_path);
+        assert!(results[0].supported);
+        assert_eq!((results[0].width, results[0].height), (Some(16), Some(8)));
+        assert_eq!(results[1].reason, Some(ImageRejectReason::MissingCodec));
+        assert_eq!(results[2].reason, Some(ImageRejectReason::NotFound));
+        assert_eq!(results[3].reason, Some(ImageRejectReason::InvalidPath));
+        assert!(results[1..].iter().all(|result| !result.supported));
+
+        let too_many = vec![image_path; 5001];
+        assert!(validate_images(too_many).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_convert_image_format_writes_output_or_base64() {
+        use base64::{engine::general_purpose, Engine};
+
+        let (temp_dir, image_path) = create_decodable_image_file();
+        let output = temp_dir.path().join("converted.jpg");
+
+        let result = convert_image_format(
+            image_path.clone(),
+            "JPEG".to_string(),
+            Some(output.to_string_lossy().to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.format, "jpeg");
+        assert!(result.data_base64.is_none());
+        assert_eq!(fs::metadata(&output).unwrap().len(), result.size);
+
+        let inline = convert_image_format(image_path, "webp".to_string(), None)
+            .await
+            .unwrap();
+        assert!(inline.output_path.is_none());
+        let bytes = general_purpose::STANDARD
+            .decode(inline.data_base64.unwrap())
+            .unwrap();
+        assert_eq!(bytes.len() as u64, inline.size);
+    }
+
+    #[tokio::test]
+    async fn test_convert_image_format_rejects_bad_requests() {
+        let (_temp_dir, image_path) = create_temp_image_file();
+
+        let error = convert_image_format(image_path.clone(), "heic".to_string(), None)
+            .await
+            .unwrap_err();
+        assert!(error.contains("Unsupported target format: heic"));
+
+        let error = convert_image_format(
+            image_path.clone(),
+            "png".to_string(),
+            Some(image_path.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("in place"));
+
+        let error = convert_image_format("../a.png".to_string(), "png".to_string(), None)
+            .await
+            .unwrap_err();
+        assert!(error.contains("Invalid image path"));
+    }
+
+    #[tokio::test]
+    async fn test_suggest_compression_quality() {
+        let (_temp_dir, image_path) = create_decodable_image_file();
+
+        let quality = suggest_compression_quality(image_path.clone(), None)
+            .await
+            .unwrap();
+        assert!((1..=100).contains(&quality));
+
+        assert!(suggest_compression_quality(image_path, Some(0))
+            .await
+            .unwrap_err()
+            .contains("greater than 0"));
+        assert!(
+            suggest_compression_quality("/nonexistent/a.png".to_string(), None)
+                .await
+                .unwrap_err()
+                .contains("not found")
+        );
+    }
+
     #[tokio::test]
     async fn test_generate_thumbnail_empty_path() {
-        let result = generate_thumbnail("".to_string(), 100).await;
+        let result = generate_thumbnail("".to_string(), 100, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("cannot be empty"));
     }
@@ -267,7 +396,7 @@ mod command_tests {
     #[tokio::test]
     async fn test_generate_thumbnail_invalid_size() {
         let (_temp_dir, image_path) = create_temp_image_file();
-        let result = generate_thumbnail(image_path, 0).await;
+        let result = generate_thumbnail(image_path, 0, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("must be between 1-1024"));
     }
@@ -275,19 +404,336 @@ mod command_tests {
     #[tokio::test]
     async fn test_generate_thumbnail_large_size() {
         let (_temp_dir, image_path) = create_temp_image_file();
-        let result = generate_thumbnail(image_path, 2000).await;
+        let result = generate_thumbnail(image_path, 2000, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("must be between 1-1024"));
     }
 
+    #[tokio::test]
+    async fn test_scan_markdown_files_streamed_returns_summary_only() {
+        let (temp_dir, first) = create_temp_markdown_file("![a](./gone.png)\n![b](./also.png)");
+        let second = temp_dir.path().join("second.md");
+        fs::write(&second, "no images").unwrap();
+        let files = vec![first, second.to_string_lossy().to_string()];
+
+        let summary = scan_markdown_files_streamed("streamed-1".to_string(), files, None, None)
+            .await
+            .unwrap();
+        assert_eq!(summary.scan_id.as_deref(), Some("streamed-1"));
+        assert_eq!(summary.scanned_files, 2);
+        assert_eq!(summary.total_images, 2);
+        assert_eq!(summary.missing_images, 2);
+        assert!(!summary.cancelled);
+        // The scan is forgotten once done
+        assert!(!cancel_scan("streamed-1".to_string()).await.unwrap());
+
+        let result =
+            scan_markdown_files_streamed(String::new(), vec!["/tmp/a.md".to_string()], None, None)
+                .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_markdown_directory_pages() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..3 {
+            fs::write(temp_dir.path().join(format!("doc{}.md", i)), "# doc").unwrap();
+        }
+        let directory = temp_dir.path().to_string_lossy().to_string();
+
+        let first = scan_markdown_directory(directory.clone(), Some(1), Some(2), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(first.items.len(), 2);
+        assert!(first.has_more);
+
+        let second = scan_markdown_directory(directory, Some(2), Some(2), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert!(second.items[0].file_path.ends_with("doc2.md"));
+        assert!(!second.has_more);
+    }
+
+    #[tokio::test]
+    async fn test_run_markdown_pipeline_dry_run() {
+        let (_image_dir, image_path) = create_temp_image_file();
+        let content = format!("![a]({})\n![b](./missing.png)\n", image_path);
+        let (_md_dir, md_path) = create_temp_markdown_file(&content);
+
+        let options = ProcessMarkdownOptions {
+            dry_run: true,
+            dedupe: false,
+            abort_on_missing: false,
+            ..Default::default()
+        };
+        let report =
+            run_markdown_pipeline(vec![md_path.clone()], create_test_oss_config(), options)
+                .await
+                .unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.uploads.len(), 1);
+        assert!(report.uploads[0].uploaded_url.is_none());
+        assert_eq!(report.missing_images.len(), 1);
+        assert!(report.replacements.is_empty());
+        assert!(report.backups.is_empty());
+        assert!(report.replacement_result.is_none());
+        assert_eq!(fs::read_to_string(&md_path).unwrap(), content);
+
+        let estimate = report.estimate.unwrap();
+        assert_eq!(estimate.image_count, 1);
+        assert_eq!(
+            estimate.total_bytes,
+            fs::metadata(&image_path).unwrap().len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimate_uses_cached_preview_sizes() {
+        let (_image_dir, image_path) = create_temp_image_file();
+        let missing = format!("{}.gone.png", image_path);
+        let config = create_test_oss_config();
+        let settings = AppSettings::default();
+
+        let estimate = estimate_uploads(
+            &[image_path.clone(), missing.clone()],
+            &config,
+            None,
+            &settings,
+        )
+        .await
+        .unwrap();
+        assert_eq!(estimate.image_count, 1);
+        assert_eq!(
+            estimate.total_bytes,
+            fs::metadata(&image_path).unwrap().len()
+        );
+        assert_eq!(estimate.sizes_from_preview, 0);
+        assert_eq!(estimate.missing_files, vec![missing]);
+        assert_eq!(estimate.concurrency, settings.upload_concurrency);
+
+        crate::commands::preview_cache::PROCESSED_SIZES.record(
+            &image_path,
+            &ImageProcessingOptions::from_config(&config),
+            7,
+        );
+        let estimate = estimate_uploads(&[image_path], &config, None, &settings)
+            .await
+            .unwrap();
+        assert_eq!(estimate.total_bytes, 7);
+        assert_eq!(estimate.sizes_from_preview, 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_markdown_pipeline_uses_asset_roots() {
+        // Hugo layout: /images/... in content/ resolves against static/
+        let site = TempDir::new().unwrap();
+        let static_dir = site.path().join("static");
+        fs::create_dir_all(static_dir.join("images")).unwrap();
+        fs::copy(create_temp_image_file().1, static_dir.join("images/a.png")).unwrap();
+        fs::create_dir_all(site.path().join("content")).unwrap();
+        let md_path = site.path().join("content/post.md");
+        fs::write(&md_path, "![a](/images/a.png)\n").unwrap();
+
+        let options = ProcessMarkdownOptions {
+            dry_run: true,
+            asset_roots: Some(vec![static_dir.to_string_lossy().to_string()]),
+            ..Default::default()
+        };
+        let report = run_markdown_pipeline(
+            vec![md_path.to_string_lossy().to_string()],
+            create_test_oss_config(),
+            options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.uploads.len(), 1);
+        assert!(report.missing_images.is_empty());
+
+        let options = ProcessMarkdownOptions {
+            dry_run: true,
+            asset_roots: Some(vec!["static".to_string()]),
+            ..Default::default()
+        };
+        let result = run_markdown_pipeline(
+            vec![md_path.to_string_lossy().to_string()],
+            create_test_oss_config(),
+            options,
+        )
+        .await;
+        assert!(result.unwrap_err().contains("absolute"));
+    }
+
+    #[tokio::test]
+    async fn test_run_markdown_pipeline_abort_on_missing() {
+        let (_md_dir, md_path) = create_temp_markdown_file("![b](./missing.png)\n");
+
+        let options = ProcessMarkdownOptions {
+            dry_run: true,
+            dedupe: false,
+            abort_on_missing: true,
+            ..Default::default()
+        };
+        let result = run_markdown_pipeline(vec![md_path], create_test_oss_config(), options).await;
+        assert!(result.unwrap_err().contains("missing"));
+    }
+
+    #[test]
+    fn test_recovered_task_revalidates_file_and_config() {
+        let (_temp_dir, image_path) = create_temp_image_file();
+        let configs = vec![ConfigItem {
+            id: "config-1".to_string(),
+            name: "Main".to_string(),
+            config: create_test_oss_config(),
+            is_active: true,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }];
+        let task = |image_path: &str, config_id: &str| PendingUpload {
+            task_id: uuid::Uuid::new_v4().to_string(),
+            image_path: image_path.to_string(),
+            config_id: Some(config_id.to_string()),
+            source_file: None,
+            upload_mode: UploadMode::ImageUpload,
+            enqueued_at: chrono::Utc::now(),
+            queued_offline: false,
+            deferred_links: None,
+            session_id: None,
+        };
+
+        let recovered = recovered_task(task(&image_path, "config-1"), &configs);
+        assert!(recovered.file_exists && recovered.config_exists);
+
+        let recovered = recovered_task(task(&image_path, "deleted"), &configs);
+        assert!(recovered.file_exists && !recovered.config_exists);
+
+        let recovered = recovered_task(task("/nonexistent/a.png", "config-1"), &configs);
+        assert!(!recovered.file_exists && recovered.config_exists);
+    }
+
+    #[test]
+    fn test_variant_key_suffixes_stem() {
+        assert_eq!(
+            variant_key("images/2024/hero.png", 480),
+            "images/2024/hero_480.png"
+        );
+        assert_eq!(variant_key("hero.tar.gz", 960), "hero.tar_960.gz");
+        assert_eq!(variant_key("images/hero", 480), "images/hero_480");
+        assert_eq!(variant_key("images/.hidden", 480), "images/.hidden_480");
+    }
+
+    #[test]
+    fn test_thumbnail_sources_presign_owned_urls() {
+        let mut configs = vec![ConfigItem {
+            id: "config-1".to_string(),
+            name: "Main".to_string(),
+            config: create_test_oss_config(),
+            is_active: true,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }];
+        let url = "https://cdn.example.com/images/a.png";
+
+        // Public config: plain URL first, presigned only as the 403 fallback
+        let sources = thumbnail_sources_for(url, &configs);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0], url);
+        assert!(sources[1].contains("/images/a.png?"));
+        assert!(sources[1].contains("Signature="));
+
+        configs[0].config.object_acl = ObjectAcl::Private;
+        let sources = thumbnail_sources_for(url, &configs);
+        assert_eq!(sources.len(), 1);
+        assert!(sources[0].contains("Signature="));
+
+        // URLs no saved config owns are fetched as-is
+        let other = "https://other.example.com/a.png";
+        assert_eq!(thumbnail_sources_for(other, &configs), vec![other]);
+    }
+
     #[tokio::test]
     async fn test_upload_images_empty_ids() {
         let config = create_test_oss_config();
-        let result = upload_images(vec![], config).await;
+        let result = upload_images(vec![], Some(config), None, None, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("cannot be empty"));
     }
 
+    #[tokio::test]
+    async fn test_upload_images_batch_empty_file_id() {
+        let (_temp_dir, image_path) = create_temp_image_file();
+        let config = create_test_oss_config();
+        let items = vec![BatchUploadItem::WithId(String::new(), image_path)];
+        let result = upload_images_batch(
+            items,
+            Some(BatchUploadOptions {
+                config: Some(config),
+                ..Default::default()
+            }),
+            None,
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("File ID cannot be empty"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_images_batch_missing_file_fails_item() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.png");
+        let config = create_test_oss_config();
+        let items = vec![BatchUploadItem::WithId(
+            "missing-id".to_string(),
+            missing.to_string_lossy().to_string(),
+        )];
+
+        let results = upload_images_batch(
+            items,
+            Some(BatchUploadOptions {
+                config: Some(config),
+                ..Default::default()
+            }),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].image_id, "missing-id");
+        assert!(!results[0].success);
+        assert!(results[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("Image file not found"));
+    }
+
+    #[test]
+    fn test_oversized_summary_lists_skipped_files() {
+        assert!(oversized_summary(&[]).is_none());
+
+        let skipped: Vec<String> = (1..=7).map(|i| format!("/img/{}.mov.gif", i)).collect();
+        let message = oversized_summary(&skipped).unwrap();
+        assert!(message.starts_with("Last batch skipped 7 oversized image(s): /img/1.mov.gif"));
+        assert!(message.contains("/img/5.mov.gif"));
+        assert!(!message.contains("/img/6.mov.gif"));
+        assert!(message.ends_with("and 2 more"));
+    }
+
+    #[test]
+    fn test_history_upload_mode_defaults() {
+        assert_eq!(history_upload_mode(None, None), UploadMode::ImageUpload);
+        assert_eq!(
+            history_upload_mode(None, Some("/docs/post.md")),
+            UploadMode::ArticleUpload
+        );
+        assert_eq!(
+            history_upload_mode(Some(UploadMode::ImageUpload), Some("/docs/post.md")),
+            UploadMode::ImageUpload
+        );
+    }
+
     #[tokio::test]
     async fn test_get_upload_progress_empty_id() {
         let result = get_upload_progress("".to_string()).await;
@@ -322,7 +768,7 @@ mod command_tests {
         config.bucket = "".to_string();
         let result = save_oss_config(config, None).await;
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Bucket name cannot be empty"));
+        assert!(result.unwrap_err().contains("Bucket name is required"));
     }
 
     #[tokio::test]
@@ -331,18 +777,95 @@ mod command_tests {
         config.access_key_id = "".to_string();
         let result = test_oss_connection(config).await;
         assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Access Key ID is required"));
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_connection_invalid() {
+        let mut config = create_test_oss_config();
+        config.bucket = "".to_string();
+        let result = diagnose_connection(config).await;
+        assert!(result.unwrap_err().contains("Bucket name is required"));
+    }
+
+    #[tokio::test]
+    async fn test_untestable_configs_are_reported_without_network() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_service =
+            crate::services::ConfigService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let item = |id: &str, config: OSSConfig| ConfigItem {
+            id: id.to_string(),
+            name: format!("Config {}", id),
+            config,
+            is_active: false,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+
+        let mut no_secret = create_test_oss_config();
+        no_secret.access_key_secret.clear();
+        let result = test_config_item(&config_service, &item("a", no_secret), true).await;
+        assert_eq!(result.status, ConfigHealthStatus::MissingCredentials);
+        assert_eq!(result.config_id, "a");
+        assert!(!result.test.success);
+
+        let mut no_bucket = create_test_oss_config();
+        no_bucket.bucket.clear();
+        let result = test_config_item(&config_service, &item("b", no_bucket), false).await;
+        assert_eq!(result.status, ConfigHealthStatus::Invalid);
         assert!(result
-            .unwrap_err()
-            .contains("Access key ID cannot be empty"));
+            .test
+            .error
+            .unwrap()
+            .contains("Bucket name is required"));
     }
 
     #[tokio::test]
     async fn test_validate_oss_config_invalid() {
         let mut config = create_test_oss_config();
+        config.provider = OSSProvider::Tencent;
         config.region = "".to_string();
-        let result = validate_oss_config(config).await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Region cannot be empty"));
+        let validation = validate_oss_config(config).await.unwrap();
+        assert!(!validation.valid);
+        assert!(validation.connection_test.is_none());
+        // Every problem is reported at once
+        let errors = validation.errors.join(" ");
+        assert!(errors.contains("Region is required"));
+        assert!(errors.contains("APPID suffix"));
+    }
+
+    #[test]
+    fn test_validate_oss_config_provider_rules() {
+        // Aliyun doesn't use the region
+        let mut config = create_test_oss_config();
+        config.region = "".to_string();
+        assert!(validate_oss_config_params(&config).is_ok());
+
+        config.endpoint = "https://s3.example.com".to_string();
+        assert!(validate_oss_config_params(&config)
+            .unwrap_err()
+            .to_string()
+            .contains("oss-<region>.aliyuncs.com"));
+
+        let mut config = create_test_oss_config();
+        config.provider = OSSProvider::Aws;
+        config.endpoint = "https://s3.amazonaws.com".to_string();
+        config.region = "mars-north-1".to_string();
+        assert!(validate_oss_config_params(&config)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown AWS region"));
+
+        // S3-compatible endpoints may use any region name
+        config.endpoint = "https://minio.internal:9000".to_string();
+        assert!(validate_oss_config_params(&config).is_ok());
+
+        let mut config = create_test_oss_config();
+        config.cdn_domain = Some("https://cdn.example.com".to_string());
+        config.path_template = "images/{sha}/{filename}".to_string();
+        let error = validate_oss_config_params(&config).unwrap_err().to_string();
+        assert!(error.contains("CDN domain must not include a scheme"));
+        assert!(error.contains("{sha}"));
     }
 
     #[tokio::test]
@@ -363,6 +886,30 @@ mod command_tests {
         assert!(result.unwrap_err().contains("Prefix too long"));
     }
 
+    #[tokio::test]
+    async fn test_list_oss_prefixes_reuses_a_recent_listing() {
+        let mut config = create_test_oss_config();
+        config.bucket = "prefix-cache-test".to_string();
+        let listed = vec!["blog/2023/".to_string(), "blog/2024/".to_string()];
+        prefix_cache::OSS_PREFIXES.record(&config, "blog/", "/", &listed);
+
+        // Served from the cache, the test bucket is never contacted
+        let result = list_oss_prefixes(config.clone(), "blog/".to_string(), None, None).await;
+        assert_eq!(result.unwrap(), listed);
+        assert!(prefix_cache::OSS_PREFIXES
+            .get(&config, "blog/", "-")
+            .is_none());
+        let mut other_bucket = config.clone();
+        other_bucket.bucket = "prefix-cache-other".to_string();
+        assert!(prefix_cache::OSS_PREFIXES
+            .get(&other_bucket, "blog/", "/")
+            .is_none());
+
+        let result =
+            list_oss_prefixes(config, "blog/".to_string(), Some(String::new()), None).await;
+        assert!(result.unwrap_err().contains("Delimiter cannot be empty"));
+    }
+
     #[tokio::test]
     async fn test_replace_markdown_links_empty() {
         let result = replace_markdown_links(vec![]).await;
@@ -379,6 +926,7 @@ mod command_tests {
                 column: 1,
                 old_link: "old".to_string(),
                 new_link: "new".to_string(),
+                context: LinkContext::MarkdownInline,
             })
             .collect();
         let result = replace_markdown_links(replacements).await;
@@ -386,6 +934,104 @@ mod command_tests {
         assert!(result.unwrap_err().contains("Too many replacements"));
     }
 
+    #[tokio::test]
+    async fn test_replace_markdown_links_rejects_oversized_link() {
+        let replacement = LinkReplacement {
+            file_path: "file.md".to_string(),
+            line: 1,
+            column: 1,
+            old_link: "old".to_string(),
+            new_link: format!("https://example.com/{}", "a".repeat(limits::MAX_LINK_LEN)),
+            context: LinkContext::MarkdownInline,
+        };
+        let result = replace_markdown_links(vec![replacement]).await;
+        assert!(result.unwrap_err().contains("New link too long"));
+    }
+
+    #[tokio::test]
+    async fn test_add_batch_history_records_caps_page_size() {
+        let record = UploadHistoryRecord {
+            id: String::new(),
+            timestamp: chrono::Utc::now(),
+            image_name: "a.png".to_string(),
+            uploaded_url: "https://limits.invalid/a.png".to_string(),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 1,
+            checksum: "abc".to_string(),
+            variants: Vec::new(),
+            config_id: None,
+            bucket: None,
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        };
+
+        let records = vec![record.clone(); limits::MAX_HISTORY_BATCH + 1];
+        let result = add_batch_upload_history_records(records).await;
+        assert!(result
+            .unwrap_err()
+            .contains("Too many records in one batch"));
+
+        let long_name = UploadHistoryRecord {
+            image_name: "a".repeat(limits::MAX_NAME_LEN + 1),
+            ..record
+        };
+        let result = add_batch_upload_history_records(vec![long_name]).await;
+        assert!(result.unwrap_err().contains("Image name too long"));
+    }
+
+    #[test]
+    fn test_orphan_scan_refuses_unresolvable_history() {
+        let config = create_test_oss_config();
+        let record = |url: &str, object_key: Option<&str>| UploadHistoryRecord {
+            id: String::new(),
+            timestamp: chrono::Utc::now(),
+            image_name: "a.png".to_string(),
+            uploaded_url: url.to_string(),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 1,
+            checksum: "abc".to_string(),
+            variants: Vec::new(),
+            config_id: None,
+            bucket: Some("test-bucket".to_string()),
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: object_key.map(String::from),
+        };
+
+        let keys = history_object_keys(
+            &config,
+            &[
+                record("https://cdn.example.com/images/a.png", None),
+                record("https://old-cdn.example.com/b.png", Some("images/b.png")),
+            ],
+        )
+        .unwrap();
+        assert!(keys.contains("images/a.png"));
+        assert!(keys.contains("images/b.png"));
+
+        // The CDN domain changed since: the record's object can't be told apart from an orphan
+        let error = history_object_keys(
+            &config,
+            &[record("https://old-cdn.example.com/c.png", None)],
+        )
+        .unwrap_err();
+        assert!(error.contains("1 upload record(s) of bucket test-bucket"));
+
+        // Records of other buckets don't matter
+        let mut other = record("https://elsewhere.example.com/c.png", None);
+        other.bucket = Some("other-bucket".to_string());
+        assert!(history_object_keys(&config, &[other]).unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_replace_markdown_links_invalid_path() {
         let replacements = vec![LinkReplacement {
@@ -394,6 +1040,7 @@ mod command_tests {
             column: 1,
             old_link: "old".to_string(),
             new_link: "new".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
         let result = replace_markdown_links(replacements).await;
         assert!(result.is_err());
@@ -402,14 +1049,14 @@ mod command_tests {
 
     #[tokio::test]
     async fn test_get_upload_history_invalid_pagination() {
-        let result = get_upload_history(Some(0), Some(20)).await;
+        let result = get_upload_history(Some(0), Some(20), None, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("must be greater than 0"));
     }
 
     #[tokio::test]
     async fn test_get_upload_history_valid_pagination() {
-        let result = get_upload_history(Some(2), Some(10)).await;
+        let result = get_upload_history(Some(2), Some(10), None, None).await;
         assert!(result.is_ok());
         let paginated = result.unwrap();
         assert_eq!(paginated.page, 2);
@@ -493,7 +1140,8 @@ mod command_tests {
 
     #[tokio::test]
     async fn test_replace_markdown_links_with_result_empty() {
-        let result = replace_markdown_links_with_result(vec![]).await;
+        let result =
+            replace_markdown_links_with_result(vec![], None, None, None, None, None, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("cannot be empty"));
     }
@@ -507,9 +1155,12 @@ mod command_tests {
                 column: 1,
                 old_link: "old".to_string(),
                 new_link: "new".to_string(),
+                context: LinkContext::MarkdownInline,
             })
             .collect();
-        let result = replace_markdown_links_with_result(replacements).await;
+        let result =
+            replace_markdown_links_with_result(replacements, None, None, None, None, None, None)
+                .await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Too many replacements"));
     }
@@ -522,8 +1173,11 @@ mod command_tests {
             column: 1,
             old_link: "old".to_string(),
             new_link: "new".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
-        let result = replace_markdown_links_with_result(replacements).await;
+        let result =
+            replace_markdown_links_with_result(replacements, None, None, None, None, None, None)
+                .await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Invalid file path"));
     }
@@ -537,8 +1191,11 @@ mod command_tests {
             column: 1,
             old_link: "".to_string(),
             new_link: "new".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
-        let result = replace_markdown_links_with_result(replacements).await;
+        let result =
+            replace_markdown_links_with_result(replacements, None, None, None, None, None, None)
+                .await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Old link cannot be empty"));
     }
@@ -552,8 +1209,11 @@ mod command_tests {
             column: 1,
             old_link: "old".to_string(),
             new_link: "".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
-        let result = replace_markdown_links_with_result(replacements).await;
+        let result =
+            replace_markdown_links_with_result(replacements, None, None, None, None, None, None)
+                .await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("New link cannot be empty"));
     }
@@ -566,8 +1226,11 @@ mod command_tests {
             column: 1,
             old_link: "old".to_string(),
             new_link: "new".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
-        let result = replace_markdown_links_with_result(replacements).await;
+        let result =
+            replace_markdown_links_with_result(replacements, None, None, None, None, None, None)
+                .await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("File not found"));
     }
@@ -597,6 +1260,7 @@ mod command_tests {
                 column: i + 1,
                 old_link: format!("old{}", i),
                 new_link: format!("new{}", i),
+                context: LinkContext::MarkdownInline,
             })
             .collect();
         let result = replace_single_file_links(file_path, replacements).await;
@@ -613,6 +1277,7 @@ mod command_tests {
             column: 1,
             old_link: "old".to_string(),
             new_link: "new".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
         let result = replace_single_file_links(file_path, replacements).await;
         assert!(result.is_err());
@@ -627,6 +1292,7 @@ mod command_tests {
             column: 1,
             old_link: "old".to_string(),
             new_link: "new".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
         let result = replace_single_file_links("../invalid.md".to_string(), replacements).await;
         assert!(result.is_err());
@@ -637,6 +1303,459 @@ mod command_tests {
     // Integration Tests for Link Replacement with Real Files
     // ============================================================================
 
+    #[tokio::test]
+    async fn test_replace_markdown_links_with_result_writes_report() {
+        let content = "Here's an image: ![Alt text](./test.png)";
+        let (temp_dir, file_path) = create_temp_markdown_file(content);
+        let report_path = temp_dir.path().join("report");
+
+        let replacements = vec![LinkReplacement {
+            file_path,
+            line: 1,
+            column: 31,
+            old_link: "./test.png".to_string(),
+            new_link: "https://cdn.example.com/test.png".to_string(),
+            context: LinkContext::MarkdownInline,
+        }];
+
+        let result = replace_markdown_links_with_result(
+            replacements.clone(),
+            Some(report_path.to_string_lossy().to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await;
+        assert!(result.is_ok());
+
+        let json = fs::read_to_string(temp_dir.path().join("report.json")).unwrap();
+        let parsed: BatchReplacementResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.total_successful_replacements, 1);
+        let markdown = fs::read_to_string(temp_dir.path().join("report.md")).unwrap();
+        assert!(markdown.contains("Successful replacements: 1"));
+
+        // Existing reports are not overwritten unless asked to
+        let result = replace_markdown_links_with_result(
+            replacements,
+            Some(report_path.to_string_lossy().to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await;
+        assert!(result.unwrap_err().contains("already exists"));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_rescan_moves_replacements_of_edited_files() {
+        let (temp_dir, file_path) = create_temp_markdown_file("![a](./a.png)\n");
+        fs::write(temp_dir.path().join("a.png"), b"image").unwrap();
+        let file_service = crate::services::FileService::new().unwrap();
+        let scan_results = file_service
+            .scan_markdown_files(vec![file_path.clone()])
+            .await
+            .unwrap();
+        let groups = file_service
+            .group_scan_results(&scan_results)
+            .await
+            .unwrap();
+        let url = "https://cdn.example.com/a.png";
+        let mut report = ProcessMarkdownReport {
+            dry_run: false,
+            scan_results,
+            missing_images: vec![],
+            uploads: vec![PipelineUpload {
+                image_id: groups[0].id.clone(),
+                absolute_path: groups[0].absolute_path.clone(),
+                uploaded_url: Some(url.to_string()),
+                reused: false,
+                usage_count: 1,
+                error: None,
+                queued_offline: false,
+                warnings: vec![],
+            }],
+            replacements: groups[0].link_replacements(url, false),
+            replacement_result: None,
+            backups: vec![],
+            changelog_path: None,
+            estimate: None,
+            revalidation: None,
+        };
+        assert_eq!(report.replacements[0].line, 1);
+
+        // Edited while the upload ran: the link moved two lines down
+        fs::write(&file_path, "# Title\n\n![a](./a.png)\n").unwrap();
+        let revalidation = file_service
+            .revalidate_scan_results(&report.scan_results)
+            .await;
+        assert_eq!(revalidation.changed_files, vec![file_path.clone()]);
+
+        rescan_changed_files(
+            &file_service,
+            &mut report,
+            &groups,
+            &revalidation.changed_files,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.replacements.len(), 1);
+        assert_eq!(report.replacements[0].line, 3);
+        assert!(file_service
+            .revalidate_scan_results(&report.scan_results)
+            .await
+            .changed_files
+            .is_empty());
+
+        let result = file_service
+            .replace_image_links_batch(report.replacements)
+            .await
+            .unwrap();
+        assert_eq!(result.total_successful_replacements, 1);
+        assert!(fs::read_to_string(&file_path)
+            .unwrap()
+            .starts_with(&format!("# Title\n\n![a]({})", url)));
+    }
+
+    #[tokio::test]
+    async fn test_changelog_records_and_reverts_replacements() {
+        let content = "# Post\n![a](./a.png) and ![b](./b b.png)\n![[c.png|Cover]]";
+        let (temp_dir, file_path) = create_temp_markdown_file(content);
+        fs::create_dir(temp_dir.path().join(".git")).unwrap();
+        let replacement = |line, column, old: &str, new: &str, context| LinkReplacement {
+            file_path: file_path.clone(),
+            line,
+            column,
+            old_link: old.to_string(),
+            new_link: new.to_string(),
+            context,
+        };
+        let replacements = vec![
+            replacement(
+                2,
+                6,
+                "./a.png",
+                "https://cdn.example.com/a.png",
+                LinkContext::MarkdownInline,
+            ),
+            replacement(
+                2,
+                24,
+                "./b b.png",
+                "https://cdn.example.com/b b.png",
+                LinkContext::MarkdownInline,
+            ),
+            replacement(
+                3,
+                4,
+                "c.png",
+                "https://cdn.example.com/c.png",
+                LinkContext::WikiEmbed,
+            ),
+        ];
+
+        let result = replace_markdown_links_with_result(
+            replacements,
+            None,
+            None,
+            None,
+            Some(true),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.total_successful_replacements, 3);
+        let rewritten = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(
+            rewritten,
+            "# Post\n![a](https://cdn.example.com/a.png) and ![b](https://cdn.example.com/b%20b.png)\n![Cover](https://cdn.example.com/c.png)"
+        );
+
+        let changelog_path = temp_dir.path().join(".imgtoss-changes.json");
+        let changelog: ReplacementChangelog =
+            serde_json::from_str(&fs::read_to_string(&changelog_path).unwrap()).unwrap();
+        let changes: Vec<_> = changelog
+            .changes
+            .iter()
+            .map(|c| (c.line, c.column, c.written_text.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (2, 6, "https://cdn.example.com/a.png"),
+                (2, 46, "https://cdn.example.com/b%20b.png"),
+                // The embed syntax changed too, `![` is all that stayed
+                (3, 3, "Cover](https://cdn.example.com/c.png)"),
+            ]
+        );
+        assert_eq!(changelog.changes[2].replaced_text, "[c.png|Cover]]");
+
+        // An entry for a file nobody picked in the app is reported, not applied
+        let mut edited: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&changelog_path).unwrap()).unwrap();
+        let changes = edited["changes"].as_array_mut().unwrap();
+        let mut outside = changes[0].clone();
+        outside["file_path"] = "/etc/imgtoss-outside.md".into();
+        changes.push(outside);
+        fs::write(&changelog_path, edited.to_string()).unwrap();
+
+        let reverted = revert_from_changelog(changelog_path.to_string_lossy().to_string())
+            .await
+            .unwrap();
+        assert_eq!(reverted.total_successful_replacements, 3);
+        assert_eq!(reverted.total_failed_replacements, 1);
+        assert_eq!(reverted.total_files, 2);
+        let skipped = reverted
+            .results
+            .iter()
+            .find(|r| r.file_path == "/etc/imgtoss-outside.md")
+            .unwrap();
+        assert_eq!(skipped.failed_replacements.len(), 1);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_link_migration_requires_a_saved_config() {
+        let (_temp_dir, file_path) =
+            create_temp_markdown_file("![a](https://cdn.example.com/a.png)");
+
+        let result = analyze_markdown_links(vec![file_path.clone()], String::new()).await;
+        assert!(result.unwrap_err().contains("Config ID cannot be empty"));
+
+        let result = migrate_config_links(
+            vec![file_path],
+            "no-such-config".to_string(),
+            None,
+            Some(true),
+        )
+        .await;
+        assert!(result.unwrap_err().contains("Config not found"));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_cdn_domain_swaps_only_the_host() {
+        let content = "![a](https://img.cdn-move.invalid/images/a.png?w=1)\n\
+                       <img src=\"https://img.cdn-move.invalid.example/b.png\">\n\
+                       ![c](https://other.invalid/c.png)";
+        let (_temp_dir, file_path) = create_temp_markdown_file(content);
+
+        // Strict: no upload history points at this domain, so nothing is known
+        let summary = migrate_cdn_domain(
+            "img.cdn-move.invalid".to_string(),
+            "https://cdn.cdn-move.invalid".to_string(),
+            Some(vec![file_path.clone()]),
+            false,
+            Some(true),
+            Some(true),
+        )
+        .await
+        .unwrap();
+        assert!(summary.replacements.is_empty());
+        assert_eq!(summary.skipped_replacements.len(), 1);
+
+        let summary = migrate_cdn_domain(
+            "img.cdn-move.invalid".to_string(),
+            "https://cdn.cdn-move.invalid".to_string(),
+            Some(vec![file_path.clone()]),
+            false,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.files_changed, vec![file_path.clone()]);
+        assert_eq!(summary.backups.len(), 1);
+        assert_eq!(
+            summary
+                .replacement_result
+                .unwrap()
+                .total_successful_replacements,
+            1
+        );
+        let rewritten = fs::read_to_string(&file_path).unwrap();
+        assert!(rewritten.starts_with("![a](https://cdn.cdn-move.invalid/images/a.png?w=1)\n"));
+        assert!(rewritten.contains("https://img.cdn-move.invalid.example/b.png"));
+
+        let result = migrate_cdn_domain(
+            "img.cdn-move.invalid".to_string(),
+            "IMG.cdn-move.invalid/".to_string(),
+            Some(vec![file_path]),
+            false,
+            None,
+            Some(true),
+        )
+        .await;
+        assert!(result.unwrap_err().contains("same"));
+    }
+
+    #[tokio::test]
+    async fn test_revert_from_changelog_missing_file() {
+        let missing = std::env::temp_dir().join("imgtoss-no-such-dir/.imgtoss-changes.json");
+        let result = revert_from_changelog(missing.to_string_lossy().to_string()).await;
+        assert!(result.unwrap_err().contains("Failed to read changelog"));
+    }
+
+    #[tokio::test]
+    async fn test_article_image_uploads_pairs_references_with_results() {
+        let (temp_dir, file_path) =
+            create_temp_markdown_file("![a](./a.png)\n![b](./b.png)\n![again](./a.png)\n");
+        fs::write(temp_dir.path().join("a.png"), b"first image").unwrap();
+        fs::write(temp_dir.path().join("b.png"), b"second image").unwrap();
+        let scan = scan_markdown_files(vec![file_path], None, None, None)
+            .await
+            .unwrap();
+        let ids: Vec<String> = scan[0].images.iter().map(|i| i.id.clone()).collect();
+
+        let result = upload_article_images(
+            scan.clone(),
+            Vec::new(),
+            Some("config".to_string()),
+            None,
+            None,
+        )
+        .await;
+        assert!(result.unwrap_err().contains("No images selected"));
+        let unknown = select_article_images(scan.clone(), &["nope".to_string()]).unwrap_err();
+        assert!(unknown.to_string().contains("not in scan results"));
+
+        // Both references to a.png, b.png left out
+        let article = select_article_images(scan, &[ids[2].clone(), ids[0].clone()]).unwrap();
+        assert_eq!(article[0].images.len(), 2);
+        let groups = crate::services::FileService::new()
+            .unwrap()
+            .group_scan_results(&article)
+            .await
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+
+        let uploaded = PipelineUpload {
+            image_id: groups[0].id.clone(),
+            absolute_path: groups[0].absolute_path.clone(),
+            uploaded_url: Some("https://cdn.example.com/a.png".to_string()),
+            reused: false,
+            usage_count: 2,
+            error: None,
+            queued_offline: false,
+            warnings: Vec::new(),
+        };
+        let paired = article_image_uploads(&article, &groups, std::slice::from_ref(&uploaded));
+        let ids_and_lines: Vec<_> = paired
+            .iter()
+            .map(|p| {
+                let replacement = p.replacement.as_ref().unwrap();
+                assert_eq!(replacement.new_link, "https://cdn.example.com/a.png");
+                assert!(p.result.success);
+                (p.result.image_id.clone(), replacement.line)
+            })
+            .collect();
+        assert_eq!(ids_and_lines, [(ids[0].clone(), 1), (ids[2].clone(), 3)]);
+
+        // A failed upload fails every reference to it, nothing to replace
+        let failed = PipelineUpload {
+            uploaded_url: None,
+            error: Some("timed out".to_string()),
+            ..uploaded
+        };
+        let paired = article_image_uploads(&article, &groups, &[failed]);
+        assert_eq!(paired.len(), 2);
+        assert!(paired
+            .iter()
+            .all(|p| !p.result.success && p.replacement.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_export_scan_report() {
+        let (temp_dir, file_path) =
+            create_temp_markdown_file("![missing](./nope.png)\n![also](./gone.png)");
+        let results = scan_markdown_files(vec![file_path], None, None, None)
+            .await
+            .unwrap();
+        let report_path = temp_dir
+            .path()
+            .join("scan.md")
+            .to_string_lossy()
+            .to_string();
+
+        let written = export_scan_report(
+            results.clone(),
+            report_path.clone(),
+            Some(ReportFormat::Markdown),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, report_path);
+
+        let markdown = fs::read_to_string(&report_path).unwrap();
+        assert!(markdown.contains("Missing images: 2"));
+        assert!(markdown.contains("./gone.png"));
+
+        let result = export_scan_report(
+            results.clone(),
+            report_path.clone(),
+            Some(ReportFormat::Json),
+            None,
+        )
+        .await;
+        assert!(result.is_err());
+
+        let result =
+            export_scan_report(results, report_path, Some(ReportFormat::Json), Some(true)).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_find_local_duplicates_counts_markdown_usage() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("assets")).unwrap();
+        fs::write(temp_dir.path().join("a.png"), b"same image bytes").unwrap();
+        fs::write(temp_dir.path().join("assets/b.png"), b"same image bytes").unwrap();
+        fs::write(temp_dir.path().join("c.png"), b"other image byte").unwrap();
+        let doc = temp_dir.path().join("doc.md");
+        fs::write(&doc, "![a](./a.png)\n![again](a.png)\n![c](./c.png)").unwrap();
+        let directory = temp_dir.path().to_string_lossy().to_string();
+
+        let results =
+            scan_markdown_files(vec![doc.to_string_lossy().to_string()], None, None, None)
+                .await
+                .unwrap();
+        let report = find_local_duplicates(directory.clone(), None, None, Some(results))
+            .await
+            .unwrap();
+        assert_eq!(report.files_scanned, 3);
+        assert_eq!(report.files_hashed, 3);
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.wasted_bytes, 16);
+        let usage: Vec<_> = report.groups[0]
+            .files
+            .iter()
+            .map(|f| f.usage_count)
+            .collect();
+        assert_eq!(usage, vec![Some(2), Some(0)]);
+
+        let shallow = find_local_duplicates(directory, Some(false), None, None)
+            .await
+            .unwrap();
+        assert!(shallow.groups.is_empty());
+
+        let report_path = temp_dir.path().join("duplicates.md");
+        let written = export_duplicate_report(
+            report,
+            report_path.to_string_lossy().to_string(),
+            Some(ReportFormat::Markdown),
+            None,
+        )
+        .await
+        .unwrap();
+        let markdown = fs::read_to_string(written).unwrap();
+        assert!(markdown.contains("- Duplicate groups: 1"));
+    }
+
     #[tokio::test]
     async fn test_replace_markdown_links_with_result_real_file() {
         let content = "Here's an image: ![Alt text](./test.png)";
@@ -648,9 +1767,12 @@ mod command_tests {
             column: 31,
             old_link: "./test.png".to_string(),
             new_link: "https://cdn.example.com/test.png".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
 
-        let result = replace_markdown_links_with_result(replacements).await;
+        let result =
+            replace_markdown_links_with_result(replacements, None, None, None, None, None, None)
+                .await;
         assert!(result.is_ok());
 
         let batch_result = result.unwrap();
@@ -676,6 +1798,7 @@ mod command_tests {
                 column: 13,
                 old_link: "./img1.png".to_string(),
                 new_link: "https://cdn.example.com/img1.png".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: file_path.clone(),
@@ -683,6 +1806,7 @@ mod command_tests {
                 column: 43,
                 old_link: "./img2.jpg".to_string(),
                 new_link: "https://cdn.example.com/img2.jpg".to_string(),
+                context: LinkContext::MarkdownInline,
             },
         ];
 
@@ -701,4 +1825,208 @@ mod command_tests {
         assert!(!updated_content.contains("./img1.png"));
         assert!(!updated_content.contains("./img2.jpg"));
     }
+
+    #[tokio::test]
+    async fn test_probe_components_reports_broken_stores() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_dir = temp_dir.path().join("config");
+        let data_dir = temp_dir.path().join("data");
+
+        // Fresh stores only lack an active config
+        let errors = probe_components(&config_dir, &data_dir, true).await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].component, "OSS");
+        assert!(matches!(errors[0].severity, ErrorSeverity::Low));
+
+        fs::write(config_dir.join("configs.json"), "{not json").unwrap();
+        fs::write(data_dir.join("upload_history.json"), "[{").unwrap();
+        let errors = probe_components(&config_dir, &data_dir, true).await;
+        let components: Vec<_> = errors.iter().map(|e| e.component.as_str()).collect();
+        assert_eq!(components, ["Config", "History"]);
+        assert!(matches!(errors[0].severity, ErrorSeverity::Critical));
+        assert!(matches!(errors[1].severity, ErrorSeverity::High));
+    }
+
+    // Provider that takes a fixed time per upload and tracks how many overlap
+    #[derive(Clone, Default)]
+    struct SlowProvider {
+        in_flight: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        peak: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    fn unsupported<T>(operation: &str) -> crate::utils::Result<T> {
+        Err(crate::utils::AppError::InvalidInput(format!(
+            "SlowProvider does not support {}",
+            operation
+        )))
+    }
+
+    #[async_trait::async_trait]
+    impl crate::services::oss_service::OSSProviderTrait for SlowProvider {
+        async fn test_connection(&self) -> crate::utils::Result<OSSConnectionTest> {
+            unsupported("test_connection")
+        }
+
+        async fn upload(
+            &self,
+            key: &str,
+            _data: &[u8],
+            _content_type: &str,
+            _progress_callback: Option<&crate::services::oss_service::ProgressCallback>,
+        ) -> crate::utils::Result<crate::services::oss_service::UploadedObject> {
+            use std::sync::atomic::Ordering;
+            assert!(!key.starts_with("present/"), "{} is already there", key);
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(crate::services::oss_service::UploadedObject {
+                url: self.get_object_url(key),
+                request_id: None,
+            })
+        }
+
+        fn get_object_url(&self, key: &str) -> String {
+            format!("https://cdn.example.com/{}", key)
+        }
+
+        fn max_object_size(&self) -> u64 {
+            u64::MAX
+        }
+
+        async fn list_objects_page(
+            &self,
+            _prefix: &str,
+            _delimiter: Option<&str>,
+            _marker: Option<&str>,
+        ) -> crate::utils::Result<crate::services::oss_service::ObjectListPage> {
+            unsupported("list_objects_page")
+        }
+
+        // Objects under present/ exist, nothing else does
+        async fn head_object(&self, key: &str) -> crate::utils::Result<ObjectMetadata> {
+            if !key.starts_with("present/") {
+                return Err(crate::utils::AppError::NotFound(key.to_string()));
+            }
+            Ok(ObjectMetadata {
+                key: key.to_string(),
+                size: 3,
+                content_type: None,
+                etag: None,
+                last_modified: None,
+                url: self.get_object_url(key),
+            })
+        }
+
+        async fn delete_objects(
+            &self,
+            _keys: &[String],
+        ) -> crate::utils::Result<Vec<DeleteObjectResult>> {
+            unsupported("delete_objects")
+        }
+
+        async fn get_object(&self, _key: &str) -> crate::utils::Result<reqwest::Response> {
+            unsupported("get_object")
+        }
+
+        fn presigned_url(&self, key: &str, _expires_in: u64) -> String {
+            self.get_object_url(key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_caps_concurrency_and_keeps_order() {
+        let provider = SlowProvider::default();
+        let oss_service = std::sync::Arc::new(crate::services::OSSService::with_provider(
+            Box::new(provider.clone()),
+            OSSProvider::Aws,
+        ));
+        let keys: Vec<String> = (0..7).map(|i| format!("{}.png", i)).collect();
+
+        let urls = run_bounded(keys.clone(), 3, |key| {
+            let oss_service = oss_service.clone();
+            async move { oss_service.upload_image(&key, b"png", None).await }
+        })
+        .await;
+
+        // Never more than the limit in flight, and the limit is actually used
+        assert_eq!(provider.peak.load(std::sync::atomic::Ordering::SeqCst), 3);
+        let urls: Vec<String> = urls
+            .into_iter()
+            .map(|uploaded| uploaded.unwrap().unwrap().url)
+            .collect();
+        let expected: Vec<String> = keys
+            .iter()
+            .map(|key| format!("https://cdn.example.com/{}", key))
+            .collect();
+        assert_eq!(urls, expected);
+    }
+
+    #[tokio::test]
+    async fn test_content_addressed_reupload_is_skipped() {
+        let oss_service = crate::services::OSSService::with_provider(
+            Box::new(SlowProvider::default()),
+            OSSProvider::Aws,
+        );
+        let image_service = crate::services::ImageService::new();
+        let (_temp_dir, image_path) = create_decodable_image_file();
+        let checksum = image_service.calculate_checksum(&image_path).await.unwrap();
+        let hash8 = &checksum.split_once(':').unwrap().1[..8];
+
+        // Original bytes go up, so their checksum is the content hash
+        let config = OSSConfig {
+            compression_enabled: false,
+            ..create_test_oss_config()
+        };
+        let processing = ImageProcessingOptions::from_config(&config);
+        assert!(processing.keeps_size());
+        let uploaded = upload_single_image(
+            &oss_service,
+            &image_service,
+            &image_path,
+            "id",
+            &processing,
+            "present/{hash8}.{ext}",
+            &UploadTarget::default(),
+            None,
+        )
+        .await
+        .unwrap();
+        // Same bytes, same key: the canonical URL without a PUT
+        assert_eq!(
+            uploaded.url,
+            format!("https://cdn.example.com/present/{}.png", hash8)
+        );
+        assert!(uploaded.request_id.is_none());
+
+        // Not there yet: uploaded under the hash key
+        let uploaded = upload_single_image(
+            &oss_service,
+            &image_service,
+            &image_path,
+            "id",
+            &processing,
+            "img/{hash8}.{ext}",
+            &UploadTarget::default(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            uploaded.url,
+            format!("https://cdn.example.com/img/{}.png", hash8)
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_follows_the_setting() {
+        let limiter = RateLimiter::new(1, std::time::Duration::from_secs(60));
+        assert!(limiter.check_rate_limit("upload").is_ok());
+        assert!(limiter.check_rate_limit("upload").is_err());
+
+        limiter.set_max_requests(3);
+        assert!(limiter.check_rate_limit("upload").is_ok());
+        assert!(limiter.check_rate_limit("upload").is_ok());
+        assert!(limiter.check_rate_limit("upload").is_err());
+    }
 }
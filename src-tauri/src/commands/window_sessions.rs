@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Saved config each app window uploads with, keyed by window label, so two
+/// windows can point at different buckets. Windows without one fall back to
+/// the persisted active config. Kept in memory only, a window's choice goes
+/// away when it closes.
+pub struct WindowSessions {
+    configs: Mutex<HashMap<String, String>>,
+}
+
+impl WindowSessions {
+    pub fn new() -> Self {
+        Self {
+            configs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Point `session` at `config_id`, None goes back to the active config
+    pub fn set_default_config(&self, session: &str, config_id: Option<String>) {
+        let mut configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+        match config_id {
+            Some(config_id) => configs.insert(session.to_string(), config_id),
+            None => configs.remove(session),
+        };
+    }
+
+    /// Config id set for `session`, None when it has none or no session was given
+    pub fn default_config(&self, session: Option<&str>) -> Option<String> {
+        let configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+        configs.get(session?).cloned()
+    }
+
+    pub fn window_closed(&self, session: &str) {
+        self.set_default_config(session, None);
+    }
+
+    /// Sessions using a deleted config go back to the active config
+    pub fn forget_config(&self, config_id: &str) {
+        let mut configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+        configs.retain(|_, id| id != config_id);
+    }
+}
+
+impl Default for WindowSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref WINDOW_SESSIONS: WindowSessions = WindowSessions::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_window_keeps_its_own_config() {
+        let sessions = WindowSessions::new();
+        sessions.set_default_config("main", Some("config-a".to_string()));
+        sessions.set_default_config("vault-2", Some("config-b".to_string()));
+        assert_eq!(
+            sessions.default_config(Some("main")).as_deref(),
+            Some("config-a")
+        );
+        assert_eq!(
+            sessions.default_config(Some("vault-2")).as_deref(),
+            Some("config-b")
+        );
+        assert_eq!(sessions.default_config(Some("vault-3")), None);
+        assert_eq!(sessions.default_config(None), None);
+
+        sessions.forget_config("config-a");
+        assert_eq!(sessions.default_config(Some("main")), None);
+        sessions.window_closed("vault-2");
+        assert_eq!(sessions.default_config(Some("vault-2")), None);
+    }
+}
@@ -12,6 +12,10 @@ pub fn run() {
     if let Err(e) = init_logger(None) {
         eprintln!("Failed to initialize logger: {}", e);
     }
+    // Panics leave a report in {data_dir}/crashes/ before the default output
+    utils::crash_reports::install_panic_hook();
+    // Load uploads the previous run left unfinished before new ones are enqueued
+    lazy_static::initialize(&commands::pending::PENDING_UPLOADS);
     tauri::Builder::default()
         .plugin(
             tauri_plugin_stronghold::Builder::new(|_| {
@@ -23,15 +27,69 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            // Scan progress events need a handle to reach the frontend
+            commands::scan_progress::SCAN_TRACKER.set_app_handle(app.handle().clone());
+            commands::batch_notify::BATCH_NOTIFIER.set_app_handle(app.handle().clone());
+            commands::test_progress::TEST_PROGRESS.set_app_handle(app.handle().clone());
+            commands::connectivity::CONNECTIVITY.set_app_handle(app.handle().clone());
+            commands::history_writes::HISTORY_WRITES.set_app_handle(app.handle().clone());
+            commands::progress::PROGRESS_NOTIFIER.set_app_handle(app.handle().clone());
+            // Migrations and cleanups run in the background, the window opens right away
+            tauri::async_runtime::spawn(commands::startup_tasks::run_startup_tasks());
+            // Probes the endpoint and flushes the offline queue when it's back
+            tauri::async_runtime::spawn(commands::connectivity::watch_connectivity());
+            // Cache, backup and history cleanup, shortly after launch and then periodically
+            tauri::async_runtime::spawn(commands::maintenance::run_maintenance());
+            // Upload progress held back by coalescing reaches the frontend within a tick
+            tauri::async_runtime::spawn(commands::progress::flush_progress_events());
+            // The dialog plugin adds every file or folder picked in an open
+            // dialog to the asset protocol scope; commands only accept those
+            use tauri::Manager;
+            app.asset_protocol_scope().listen(|event| {
+                if let tauri::scope::fs::Event::PathAllowed(path) = event {
+                    utils::paths::USER_PATHS.allow(path);
+                }
+            });
+            Ok(())
+        })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Focused(true) => {
+                commands::batch_notify::BATCH_NOTIFIER.window_focused();
+            }
+            tauri::WindowEvent::Destroyed => {
+                commands::window_sessions::WINDOW_SESSIONS.window_closed(window.label());
+            }
+            tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
+                for path in paths {
+                    utils::paths::USER_PATHS.allow(path);
+                }
+            }
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
             // File and Scan Commands
             scan_markdown_files,
+            scan_markdown_directory,
+            scan_markdown_files_streamed,
+            cancel_scan,
+            group_scan_results,
+            expand_group_replacements,
+            revalidate_scan_results,
             get_image_info,
+            validate_images,
             generate_thumbnail,
+            convert_image_format,
+            suggest_compression_quality,
+            preview_image_processing,
+            cancel_image_processing_preview,
             // Upload Commands
             upload_images,
             upload_images_with_ids,
             upload_images_batch,
+            estimate_batch,
+            upload_article_images,
             get_upload_progress,
             cancel_upload,
             retry_upload,
@@ -39,41 +97,83 @@ pub fn run() {
             save_oss_config,
             load_oss_config,
             test_oss_connection,
+            diagnose_connection,
             validate_oss_config,
+            validate_path_template,
+            get_provider_capabilities,
             get_cached_connection_status,
             clear_connection_cache,
+            test_all_configs,
             list_oss_objects,
+            list_oss_prefixes,
+            get_object_metadata,
+            delete_oss_objects,
+            download_oss_object,
+            find_orphan_objects,
+            delete_orphan_objects,
+            find_local_duplicates,
+            export_duplicate_report,
+            verify_local_integrity,
             export_oss_config,
             import_oss_config,
+            export_app_data,
+            preview_app_data_import,
+            import_app_data,
             // Multi-Config Management Commands
             get_all_configs,
             save_config_item,
             set_active_config,
             delete_config_item,
             get_active_config,
+            session_default_config,
+            // App Settings Commands
+            get_app_settings,
+            update_app_settings,
+            format_upload_links,
             // File Operations Commands
             replace_markdown_links,
             replace_markdown_links_with_result,
+            find_unwritable_files,
+            revert_from_changelog,
+            list_replacement_operations,
+            undo_last_replacement,
+            cleanup_backups,
+            get_backup_storage_usage,
+            get_storage_usage,
+            get_archived_original,
+            export_scan_report,
             replace_single_file_links,
             // History Commands
+            process_markdown_files,
+            analyze_markdown_links,
+            migrate_config_links,
+            migrate_cdn_domain,
             get_upload_history,
             search_history,
+            get_history_grouped_by_source,
+            get_upload_performance_stats,
             clear_history,
             export_history,
+            export_history_gallery,
             get_history_statistics,
             // 上传历史记录命令
             add_upload_history_record,
             add_batch_upload_history_records,
             get_upload_history_records,
             find_duplicate_by_checksum,
+            search_history_by_checksum,
             delete_upload_history_record,
             clear_upload_history,
+            list_trashed_history,
+            restore_history_records,
+            purge_trash,
             // 图片历史记录命令
             get_image_history,
             delete_image_history_record,
             clear_image_history,
             cleanup_old_history,
             get_file_operations,
+            clear_file_operations,
             // Progress Monitoring Commands
             get_all_upload_progress,
             clear_upload_progress,
@@ -81,6 +181,7 @@ pub fn run() {
             // Security and Health Commands
             health_check,
             validate_system_permissions,
+            set_data_directory,
             // Utility Commands
             get_app_version,
             validate_file_path,
@@ -92,6 +193,11 @@ pub fn run() {
             get_duplicate_info,
             // System Health and Monitoring Commands
             get_system_health,
+            get_startup_report,
+            get_maintenance_status,
+            recover_corrupt_store,
+            list_crash_reports,
+            get_crash_report,
             get_notification_config,
             update_notification_config,
             send_notification,
@@ -100,9 +206,14 @@ pub fn run() {
             retry_upload_task,
             get_upload_task_status,
             get_all_upload_tasks,
+            get_recovered_tasks,
+            resume_recovered_tasks,
+            discard_recovered_tasks,
+            get_connectivity_status,
             // Thumbnail Commands
             get_thumbnail,
             cleanup_thumbnail_cache,
+            get_cache_stats,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -14,12 +14,149 @@ pub struct ScanResult {
     pub images: Vec<ImageReference>,
     pub status: ScanStatus,
     pub error: Option<String>,
+    /// References whose local file doesn't exist, reported apart from scan errors
+    #[serde(default)]
+    pub missing_images: Vec<MissingImage>,
+    /// Distinct files referenced by this document; `images.len()` is the total
+    #[serde(default)]
+    pub unique_image_count: usize,
+    /// Problems that don't fail the file, status stays Success
+    #[serde(default)]
+    pub warnings: Vec<ScanWarning>,
+    /// Encoding the file was read in when it isn't UTF-8 ("GBK", "Big5"...)
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Plain sha256 hex of the file as scanned, None when the scan failed
+    #[serde(default)]
+    pub content_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanWarningCode {
+    /// The referenced path exists but can't be read as a file
+    UnreadableImage,
+    /// Checking the referenced path was refused by the OS
+    PermissionDenied,
+    /// A path that most likely won't resolve the way the author meant
+    SuspiciousPath,
+    /// The file isn't UTF-8; it was read in the encoding named in `encoding`
+    LegacyEncoding,
+    /// An image link inside a fenced code block, left alone
+    CodeFence,
+    /// An image or markdown file left out by the scan's glob rules
+    Filtered,
+}
+
+/// Something about one file of a scan that the user should know
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanWarning {
+    pub code: ScanWarningCode,
+    pub message: String,
+    pub image_path: Option<String>,
+    pub line: Option<usize>,
+}
+
+/// An image reference whose local file doesn't exist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissingImage {
+    pub file_path: String,
+    pub original_path: String,
+    pub absolute_path: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl MissingImage {
+    pub fn new(file_path: &str, image: &ImageReference) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            original_path: image.original_path.clone(),
+            absolute_path: image.absolute_path.clone(),
+            line: image.markdown_line,
+            column: image.markdown_column,
+        }
+    }
+
+    /// Missing references among `images`, located in `file_path`
+    pub fn collect(file_path: &str, images: &[ImageReference]) -> Vec<MissingImage> {
+        images
+            .iter()
+            .filter(|image| !image.exists)
+            .map(|image| MissingImage::new(file_path, image))
+            .collect()
+    }
+}
+
+/// Result of `revalidate_scan_results`: what changed on disk since a scan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanRevalidation {
+    /// Markdown files whose content no longer matches the scan (edited,
+    /// deleted or unreadable); their links must be rescanned before replacing
+    pub changed_files: Vec<String>,
+    /// References whose image existed when scanned and is gone now
+    pub newly_missing: Vec<MissingImage>,
+    /// Images with another size or modification time than when scanned
+    pub changed_images: Vec<String>,
+}
+
+/// What `process_markdown_files` does with files edited between its scan and
+/// the replace phase
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleFilePolicy {
+    /// Scan them again and rewrite the links found now that have an upload
+    #[default]
+    Rescan,
+    /// Fail before any file is backed up or rewritten, naming the files
+    Abort,
+}
+
+/// Payload of `scan://progress`, sent after each file of a scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanProgress {
+    pub scan_id: Option<String>,
+    /// 1-based index of the file that just finished
+    pub current: usize,
+    pub total: usize,
+    pub file_path: String,
+    pub images_found: usize,
+}
+
+/// Payload of `scan://done`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanSummary {
+    pub scan_id: Option<String>,
+    pub total_files: usize,
+    pub scanned_files: usize,
+    pub total_images: usize,
+    pub missing_images: usize,
+    pub failed_files: usize,
+    /// Files over the markdown size limit, not read
+    #[serde(default)]
+    pub skipped_files: usize,
+    /// Markdown files a directory scan left out because of ignore files
+    #[serde(default)]
+    pub ignored_files: usize,
+    pub cancelled: bool,
+}
+
+/// Payload of `scan://result`, one per file of a streamed scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanResultEvent {
+    pub scan_id: String,
+    /// 1-based position of the file, out of `total`
+    pub current: usize,
+    pub total: usize,
+    pub result: ScanResult,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ScanStatus {
     Success,
     Error,
+    /// Over `AppSettings::max_markdown_size_mb`, not read at all
+    Skipped,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +170,50 @@ pub struct ImageReference {
     pub markdown_line: usize,
     pub markdown_column: usize,
     pub thumbnail: Option<String>,
+    /// Shared by references in the same file that resolve to the same image,
+    /// set to the id of the first of them. None when the image is used once.
+    #[serde(default)]
+    pub reference_group: Option<String>,
+    /// Larger than `AppSettings::max_image_size_mb`, uploads will skip it
+    #[serde(default)]
+    pub too_large: bool,
+    #[serde(default)]
+    pub context: LinkContext,
+    /// Asset root the image was found under, None when resolved from the markdown file
+    #[serde(default)]
+    pub asset_root: Option<String>,
+    /// `?query` and/or `#fragment` after the file name, e.g. `?raw=true`.
+    /// Still part of `original_path`, left out when resolving the file.
+    #[serde(default)]
+    pub path_suffix: Option<String>,
+}
+
+/// One place a unique image is referenced from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageUsage {
+    pub file_path: String,
+    pub image_id: String,
+    pub original_path: String,
+    pub line: usize,
+    pub column: usize,
+    #[serde(default)]
+    pub context: LinkContext,
+    /// See `ImageReference::path_suffix`
+    #[serde(default)]
+    pub path_suffix: Option<String>,
+}
+
+/// Image referenced from one or more markdown files, uploaded once and fanned out
+/// to every usage. `duplicate_paths` holds byte-identical files under other names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UniqueImage {
+    pub id: String,
+    pub absolute_path: String,
+    pub duplicate_paths: Vec<String>,
+    pub checksum: Option<String>,
+    pub exists: bool,
+    pub size: u64,
+    pub usages: Vec<ImageUsage>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +223,72 @@ pub struct ImageInfo {
     pub format: String,
     pub size: u64,
     pub color_space: Option<String>,
+    // EXIF fields, None when the format carries no (readable) EXIF block
+    #[serde(default)]
+    pub taken_at: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    pub camera_make: Option<String>,
+    #[serde(default)]
+    pub camera_model: Option<String>,
+    #[serde(default)]
+    pub orientation: Option<u16>,
+    #[serde(default)]
+    pub has_gps: Option<bool>,
+    /// Name of the embedded ICC profile, e.g. "Display P3"
+    #[serde(default)]
+    pub icc_profile: Option<String>,
+}
+
+/// Why `validate_images` turned a file down
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageRejectReason {
+    NotFound,
+    /// Outside the files and folders picked in the app
+    NotSelected,
+    /// Not a regular file, or a malformed path
+    InvalidPath,
+    /// Not an image format the app handles
+    UnsupportedFormat,
+    /// HEIC/HEIF or AVIF, which this build has no decoder for
+    MissingCodec,
+    /// Vector image: uploaded as-is, but without dimensions. The only reason
+    /// given for a supported file.
+    Svg,
+    /// Over the max_image_size_mb setting, uploads skip it
+    TooLarge,
+    /// Has an image extension but doesn't decode
+    Corrupt,
+}
+
+/// One path checked by `validate_images`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageValidation {
+    pub path: String,
+    /// Can be uploaded
+    pub supported: bool,
+    pub format: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size: Option<u64>,
+    pub reason: Option<ImageRejectReason>,
+    /// Detail for the reason, like the decoder error
+    pub message: Option<String>,
+}
+
+impl ImageValidation {
+    pub fn rejected(path: &str, reason: ImageRejectReason, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            supported: false,
+            format: None,
+            width: None,
+            height: None,
+            size: None,
+            reason: Some(reason),
+            message: Some(message.into()),
+        }
+    }
 }
 
 // ============================================================================
@@ -60,20 +307,81 @@ pub struct UploadTask {
     pub end_time: Option<SystemTime>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
 pub enum UploadStatus {
     Pending,
+    #[default]
     Uploading,
     Completed,
     Failed,
 }
 
+/// Entry accepted by upload_images_batch: a bare path, or a `(file_id, path)` pair
+/// so the caller can correlate progress with its own rows
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum BatchUploadItem {
+    Path(String),
+    WithId(String, String),
+}
+
+impl BatchUploadItem {
+    pub fn path(&self) -> &str {
+        match self {
+            BatchUploadItem::Path(path) => path,
+            BatchUploadItem::WithId(_, path) => path,
+        }
+    }
+
+    pub fn file_id(&self) -> Option<&str> {
+        match self {
+            BatchUploadItem::Path(_) => None,
+            BatchUploadItem::WithId(file_id, _) => Some(file_id),
+        }
+    }
+}
+
+/// Options for `upload_images_batch`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchUploadOptions {
+    /// Upload with this config instead of a saved one
+    pub config: Option<OSSConfig>,
+    /// Saved config to upload with; the window's or the mode's default when omitted
+    pub config_id: Option<String>,
+    /// Concurrent uploads, 5 by default and at most 10
+    pub batch_size: Option<usize>,
+    /// Markdown file the images come from, recorded in history
+    pub source_file: Option<String>,
+    pub upload_mode: Option<UploadMode>,
+    /// Upload session the progress events are reported under; a new one when omitted
+    pub session_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UploadResult {
     pub image_id: String,
     pub success: bool,
     pub uploaded_url: Option<String>,
     pub error: Option<String>,
+    /// Responsive size variants, empty unless the config sets size_variants
+    #[serde(default)]
+    pub variants: Vec<ImageVariant>,
+    /// Not uploaded yet: no connection, the upload runs once it returns
+    #[serde(default)]
+    pub queued_offline: bool,
+    /// Problems that didn't fail the upload, e.g. its history record
+    /// couldn't be saved yet
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// One uploaded width of an image; the full-size upload is the last entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageVariant {
+    pub width: u32,
+    pub url: String,
+    pub bytes: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,7 +390,145 @@ pub struct UploadProgress {
     pub progress: f32,
     pub bytes_uploaded: u64,
     pub total_bytes: u64,
-    pub speed: Option<u64>, // bytes per second
+    pub speed: Option<u64>, // bytes per second, smoothed by the progress notifier
+    /// Seconds left at `speed`, None while it isn't known
+    #[serde(default)]
+    pub eta_secs: Option<u64>,
+    #[serde(default)]
+    pub status: UploadStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Upload session the task belongs to, see `UploadScope`
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub config_id: Option<String>,
+}
+
+impl UploadProgress {
+    pub fn scope(&self) -> UploadScope {
+        UploadScope {
+            session_id: self.session_id.clone(),
+            config_id: self.config_id.clone(),
+        }
+    }
+}
+
+/// Which uploads a task belongs to. Every upload command call is a session
+/// targeting one config, and several sessions can run at once; progress events
+/// carry the scope so each gets its own progress area. As a filter (cancel,
+/// resume, task listing) unset fields match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct UploadScope {
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub config_id: Option<String>,
+}
+
+impl UploadScope {
+    /// Filter that matches nothing more specific than "everything"
+    pub fn is_empty(&self) -> bool {
+        self.session_id.is_none() && self.config_id.is_none()
+    }
+
+    /// Whether a task in `scope` passes this filter
+    pub fn matches(&self, scope: &UploadScope) -> bool {
+        let field_matches =
+            |filter: &Option<String>, value: &Option<String>| filter.is_none() || filter == value;
+        field_matches(&self.session_id, &scope.session_id)
+            && field_matches(&self.config_id, &scope.config_id)
+    }
+}
+
+/// Processing applied to image bytes before upload, derived from OSSConfig
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageProcessingOptions {
+    pub compression_enabled: bool,
+    pub quality: u8,
+    pub output_format: Option<String>,
+    pub max_dimension: Option<u32>,
+    pub strip_metadata: bool,
+    /// Convert pixels from an embedded non-sRGB profile to sRGB when re-encoding
+    #[serde(default)]
+    pub convert_to_srgb: bool,
+    /// Upload size limit, enforced by compress-to-target after the pipeline
+    #[serde(default)]
+    pub max_upload_size_kb: Option<u64>,
+    /// Widths of downscaled copies uploaded next to the original
+    #[serde(default)]
+    pub size_variants: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageProcessingPreview {
+    pub image_path: String,
+    pub original_size: u64,
+    pub processed_size: u64,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub processed_width: u32,
+    pub processed_height: u32,
+    pub original_format: String,
+    pub output_format: String,
+    pub has_gps: bool,
+    /// Set when GPS coordinates would survive into the uploaded file
+    pub metadata_warning: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Output of convert_image_format: written to `output_path`, or returned inline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageConversionResult {
+    pub output_path: Option<String>,
+    /// Base64 of the converted bytes when no output path was given
+    pub data_base64: Option<String>,
+    pub format: String,
+    pub size: u64,
+}
+
+/// Encoding of a generated thumbnail. PNG and WebP keep transparency, JPEG
+/// drops the alpha channel.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ThumbnailFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "image/jpeg",
+            ThumbnailFormat::Png => "image/png",
+            ThumbnailFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/// Output of generate_thumbnail, with the MIME type to display `data` under
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedThumbnail {
+    pub data: Vec<u8>,
+    pub format: ThumbnailFormat,
+    pub mime_type: String,
+}
+
+/// Thumbnail caches: generate_thumbnail results kept in memory, and the
+/// history thumbnails on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub memory_hits: u64,
+    pub memory_misses: u64,
+    pub memory_entries: usize,
+    pub memory_bytes: usize,
+    /// Files in the disk cache, 0 when it isn't enabled
+    pub total_files: usize,
+    pub total_size_bytes: u64,
+    pub oldest_file: Option<chrono::DateTime<chrono::Utc>>,
+    pub newest_file: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 // ============================================================================
@@ -101,6 +547,20 @@ pub struct OSSConfig {
     pub cdn_domain: Option<String>,
     pub compression_enabled: bool,
     pub compression_quality: u8,
+    #[serde(default)]
+    pub output_format: Option<String>, // None keeps the source format
+    #[serde(default)]
+    pub max_dimension: Option<u32>, // Longest edge in pixels, larger images are downscaled
+    #[serde(default)]
+    pub strip_metadata: bool,
+    #[serde(default)]
+    pub convert_to_srgb: bool, // Re-encoded wide-gamut images are converted to sRGB
+    #[serde(default)]
+    pub object_acl: ObjectAcl,
+    #[serde(default)]
+    pub max_upload_size_kb: Option<u64>, // Images larger than this are recompressed / downscaled
+    #[serde(default)]
+    pub size_variants: Vec<u32>, // e.g. [480, 960, 1920] for srcset
 }
 
 // New: Configuration item for multi-config support
@@ -129,6 +589,61 @@ pub enum OSSProvider {
     Custom,
 }
 
+impl OSSProvider {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            OSSProvider::Aliyun => "Aliyun OSS",
+            OSSProvider::Tencent => "Tencent COS",
+            OSSProvider::Aws => "AWS S3",
+            OSSProvider::Custom => "Custom provider",
+        }
+    }
+}
+
+/// ACL applied to uploaded objects, Default leaves it to the bucket policy
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum ObjectAcl {
+    #[default]
+    Default,
+    PublicRead,
+    Private,
+}
+
+/// What a provider needs and supports, so the config form only offers what
+/// works for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    pub provider: OSSProvider,
+    pub display_name: String,
+    /// False when uploads to this provider aren't implemented yet
+    pub implemented: bool,
+    /// OSSConfig fields that must be set, by their serialized name
+    pub required_fields: Vec<String>,
+    /// OSSConfig fields the provider uses when set
+    pub optional_fields: Vec<String>,
+    pub cdn_domain_required: bool,
+    /// Storage classes uploads can be stored in, empty when the bucket default is always used
+    pub storage_classes: Vec<String>,
+    pub object_acls: Vec<ObjectAcl>,
+    /// Largest object one upload can send, in bytes
+    pub max_object_size: Option<u64>,
+    pub supports_listing: bool,
+    pub supports_deletion: bool,
+    pub supports_presigning: bool,
+    /// What goes in the endpoint field
+    pub endpoint_hint: String,
+    pub example: ProviderExample,
+}
+
+/// Sample values of a working config for a provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderExample {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub cdn_domain: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OSSConnectionTest {
     pub success: bool,
@@ -136,6 +651,84 @@ pub struct OSSConnectionTest {
     pub latency: Option<u64>,                   // milliseconds
     pub bucket_exists: Option<bool>,            // Whether the specified bucket exists
     pub available_buckets: Option<Vec<String>>, // List of available buckets (if accessible)
+    /// What the test attempted, in order; no credentials, signatures or response bodies
+    #[serde(default)]
+    pub diagnostics: Vec<ConnectionTestStep>,
+}
+
+/// One step of a connection test ("config", "dns", "tls", "auth", "bucket"...),
+/// also emitted as a `config://test-progress` event while the test runs
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConnectionTestStep {
+    pub step: String,
+    pub ok: bool,
+    pub detail: Option<String>,
+}
+
+impl ConnectionTestStep {
+    pub fn passed(step: &str, detail: Option<String>) -> Self {
+        Self {
+            step: step.to_string(),
+            ok: true,
+            detail,
+        }
+    }
+
+    pub fn failed(step: &str, detail: impl Into<String>) -> Self {
+        Self {
+            step: step.to_string(),
+            ok: false,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+/// Result of `diagnose_connection`: the network steps up to the signed request,
+/// plus plain-language findings such as a skewed system clock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionDiagnosis {
+    pub success: bool,
+    /// "config", "dns", "connect", "tls", "clock", then the signed test's steps
+    pub steps: Vec<ConnectionTestStep>,
+    /// Local clock minus the server's Date header, positive when the local clock is ahead
+    pub clock_skew_seconds: Option<i64>,
+    pub findings: Vec<String>,
+}
+
+/// Outcome of testing one saved config in `test_all_configs`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigHealthStatus {
+    Healthy,
+    /// The provider answered and rejected the config (credentials, bucket...)
+    Failed,
+    /// No response at all: DNS, connection or TLS failure
+    Unreachable,
+    TimedOut,
+    /// The access key or secret is empty, e.g. after importing app data
+    /// exported without secrets; nothing was sent
+    MissingCredentials,
+    /// Field checks failed, nothing was sent
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigTestResult {
+    pub config_id: String,
+    pub name: String,
+    pub status: ConfigHealthStatus,
+    pub test: OSSConnectionTest,
+    /// Taken from the connection cache instead of tested now
+    pub cached: bool,
+    pub duration_ms: u64,
+}
+
+/// Emitted as `config://test-all-progress` each time a config finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigTestProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub result: ConfigTestResult,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -147,6 +740,120 @@ pub struct ObjectInfo {
     pub url: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectMetadata {
+    pub key: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>, // HTTP date as returned by the provider
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanScanReport {
+    pub prefix: String,
+    pub total_objects: usize,
+    pub referenced_objects: usize,
+    pub orphans: Vec<ObjectInfo>,
+    pub orphan_bytes: u64,
+    pub scanned_markdown_files: usize,
+}
+
+/// Files of one local directory tree with identical content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalDuplicateGroup {
+    pub checksum: String,
+    /// Size of each copy
+    pub size: u64,
+    pub files: Vec<LocalDuplicateFile>,
+    /// Space taken by every copy but one
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalDuplicateFile {
+    pub path: String,
+    /// Markdown references to this file, None when no scan results were given
+    pub usage_count: Option<usize>,
+}
+
+/// Result of `find_local_duplicates`, exportable with `export_duplicate_report`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalDuplicateReport {
+    pub directory: String,
+    pub recursive: bool,
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    /// Image files found in the tree
+    pub files_scanned: usize,
+    /// Files sharing their size with another one, the only ones hashed
+    pub files_hashed: usize,
+    pub bytes_hashed: u64,
+    /// Largest waste first
+    pub groups: Vec<LocalDuplicateGroup>,
+    pub wasted_bytes: u64,
+    /// Files that couldn't be read, with the reason
+    pub unreadable_files: Vec<String>,
+    /// Stopped by `cancel_scan`, the groups only cover what was hashed by then
+    pub cancelled: bool,
+}
+
+/// An uploaded image whose local original `verify_local_integrity` checked
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LocalIntegrityFile {
+    pub record_id: String,
+    pub image_name: String,
+    pub uploaded_url: String,
+    pub original_path: String,
+    pub uploaded_at: chrono::DateTime<chrono::Utc>,
+    /// Bytes uploaded, after processing
+    pub uploaded_size: u64,
+    /// Size of the local file now, None when it's missing
+    pub local_size: Option<u64>,
+    /// Last modification of the local file, None when it's missing
+    pub modified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Result of `verify_local_integrity`: history records whose `original_path`
+/// still holds what was uploaded, was changed since, or is gone. Records
+/// without an original path aren't checked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalIntegrityReport {
+    pub scan_id: Option<String>,
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    /// Records with an original path
+    pub records_checked: usize,
+    /// Distinct local files hashed
+    pub files_hashed: usize,
+    pub bytes_hashed: u64,
+    pub matched: Vec<LocalIntegrityFile>,
+    /// Re-uploading these would change what readers see
+    pub modified: Vec<LocalIntegrityFile>,
+    pub missing: Vec<LocalIntegrityFile>,
+    /// Files that couldn't be read or records without a usable checksum, with the reason
+    pub unverifiable: Vec<String>,
+    /// Stopped by `cancel_scan`, the lists only cover what was hashed by then
+    pub cancelled: bool,
+}
+
+/// Payload of `duplicates://progress` and `integrity://progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateScanProgress {
+    pub scan_id: Option<String>,
+    /// Files hashed so far, out of `total`
+    pub current: usize,
+    pub total: usize,
+    pub bytes_hashed: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteObjectResult {
+    pub key: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
 // ============================================================================
 // File Operations Models
 // ============================================================================
@@ -158,6 +865,28 @@ pub struct LinkReplacement {
     pub column: usize,
     pub old_link: String,
     pub new_link: String,
+    /// Where old_link sits, decides how new_link is escaped when written
+    #[serde(default)]
+    pub context: LinkContext,
+}
+
+/// Syntax an image link was found in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum LinkContext {
+    /// `![alt](path)`
+    #[default]
+    MarkdownInline,
+    /// `[ref]: path`
+    ReferenceDefinition,
+    /// `<img src="path">` / `srcset="path 2x"`
+    HtmlAttribute,
+    /// A YAML value in the front matter block, e.g. `cover: path`
+    FrontMatter,
+    /// `![[path]]` or `![[path|alt]]`
+    WikiEmbed,
+    /// Not a scanned syntax: the new link is written exactly as given, without
+    /// escaping. Used to put back text recorded in a replacement changelog.
+    Verbatim,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -169,12 +898,291 @@ pub struct FileOperation {
     pub error: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum FileOperationType {
+    Backup,
     Replace,
     Restore,
 }
 
+/// A copy of a markdown file taken before it was rewritten
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub id: String,
+    pub original_path: String,
+    pub backup_path: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub size: u64,
+    /// Plain sha256 hex of the backed-up content
+    pub checksum: String,
+}
+
+/// A replacement run that backed up its files first, kept in the replacement
+/// journal so `undo_last_replacement` can put those files back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementOperation {
+    pub id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Command that ran it, e.g. "process_markdown_files"
+    pub source: String,
+    pub files: Vec<ReplacementOperationFile>,
+    /// Set once undone; an undone run can't be undone again
+    #[serde(default)]
+    pub undone_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A file of a `ReplacementOperation` and the backup taken before it was rewritten
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementOperationFile {
+    pub backup: BackupInfo,
+    /// Plain sha256 hex of the file right after the run; undo refuses when
+    /// the file no longer matches
+    pub checksum_after: String,
+    /// Put back by an undo that failed on another file; a retry checks it
+    /// against the backup instead and leaves it as it is
+    #[serde(default)]
+    pub restored: bool,
+}
+
+/// Outcome of `undo_last_replacement`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementUndoResult {
+    pub operation_id: String,
+    /// True when every file was restored and the run is marked undone
+    pub undone: bool,
+    /// Files back as they were, including those an earlier attempt restored
+    pub restored_files: Vec<String>,
+    /// Files that stopped the undo before anything was restored, e.g. edited
+    /// since the run or with a missing backup
+    pub conflicts: Vec<UndoFileError>,
+    /// Files whose restore failed after the checks passed
+    pub failed: Vec<UndoFileError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UndoFileError {
+    pub file_path: String,
+    pub message: String,
+}
+
+/// What a backup cleanup deleted
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BackupCleanupResult {
+    pub removed_files: usize,
+    pub removed_bytes: u64,
+}
+
+/// What a history cleanup moved to the trash or a trash purge deleted, or on
+/// a dry run would
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HistoryCleanupResult {
+    pub dry_run: bool,
+    pub removed_records: usize,
+    /// Summed `file_size` of the removed records
+    pub removed_bytes: u64,
+    /// Cached thumbnails of the removed records; they stay while the records
+    /// are in the trash, so this is 0 unless the trash was purged
+    pub removed_thumbnails: usize,
+    pub thumbnail_bytes: u64,
+}
+
+/// Disk space taken by backups, for the settings screen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupStorageUsage {
+    pub backup_count: usize,
+    /// Distinct original files that have at least one backup
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub oldest_backup: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A source image kept by the originals archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedOriginal {
+    pub record_id: String,
+    pub sha256: String,
+    /// `{data dir}/originals/{sha256[0..2]}/{sha256}`, shared by every record
+    /// uploaded from the same file
+    pub path: String,
+    pub size: u64,
+    pub archived_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// What an originals archive cleanup deleted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OriginalArchiveCleanupResult {
+    pub removed_files: usize,
+    pub removed_bytes: u64,
+    /// History records whose original is no longer archived
+    pub released_records: Vec<String>,
+}
+
+/// Disk space taken by the originals archive
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OriginalArchiveUsage {
+    pub file_count: usize,
+    pub total_bytes: u64,
+    /// History records pointing at an archived file
+    pub record_count: usize,
+}
+
+/// Result of `get_storage_usage`: what the app keeps on disk besides the
+/// stores, for weighing the cleanup settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub backups: BackupStorageUsage,
+    pub thumbnail_files: usize,
+    pub thumbnail_bytes: u64,
+    pub archived_originals: OriginalArchiveUsage,
+    pub total_bytes: u64,
+}
+
+/// Glob rules for one scan; each list that is set replaces the one from
+/// `AppSettings`, an empty list turns that rule off
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanGlobOptions {
+    pub include_image_globs: Option<Vec<String>>,
+    pub exclude_image_globs: Option<Vec<String>>,
+    pub exclude_file_globs: Option<Vec<String>>,
+}
+
+/// Options for `process_markdown_files`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessMarkdownOptions {
+    /// Scan, group and duplicate-check only: nothing is uploaded or written
+    pub dry_run: bool,
+    /// Reuse URLs of images already in upload history instead of uploading again
+    pub dedupe: bool,
+    /// Fail the whole run when a referenced image is missing instead of skipping it
+    pub abort_on_missing: bool,
+    /// Which earlier uploads `dedupe` may reuse
+    pub dedupe_scope: DuplicateScope,
+    /// Overrides `AppSettings::asset_roots` for this run
+    pub asset_roots: Option<Vec<String>>,
+    /// Overrides the glob rules from settings for this run
+    pub scan_globs: ScanGlobOptions,
+    /// Keep a link's `?query`/`#fragment` on the uploaded URL, e.g. for
+    /// `#gh-dark-mode-only`. Off by default since queries like `?raw=true` only
+    /// make sense for the original host.
+    pub preserve_link_suffix: bool,
+    /// Write a replacement changelog, see `ReplacementChangelog`
+    pub write_changelog: bool,
+    /// Where to write it; defaults to `.imgtoss-changes.json` in the git
+    /// repository root of the first rewritten file
+    pub changelog_path: Option<String>,
+    /// Upload session the run's progress events are reported under; a new
+    /// one when omitted
+    pub session_id: Option<String>,
+    /// Files edited while the images uploaded, see `StaleFilePolicy`
+    pub on_changed_files: StaleFilePolicy,
+}
+
+impl Default for ProcessMarkdownOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            dedupe: true,
+            abort_on_missing: false,
+            dedupe_scope: DuplicateScope::default(),
+            asset_roots: None,
+            scan_globs: ScanGlobOptions::default(),
+            preserve_link_suffix: false,
+            write_changelog: false,
+            changelog_path: None,
+            session_id: None,
+            on_changed_files: StaleFilePolicy::default(),
+        }
+    }
+}
+
+/// Seconds a batch is expected to take, best case to worst case
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct EtaRange {
+    pub min_secs: u64,
+    pub max_secs: u64,
+}
+
+/// An image of an estimated batch that won't upload as it is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEstimateWarning {
+    pub image_path: String,
+    pub message: String,
+}
+
+/// Result of `estimate_batch`: what a batch would send and how long it may take
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchEstimate {
+    pub image_count: usize,
+    /// Bytes that would be uploaded, size variants not included
+    pub total_bytes: u64,
+    /// Images whose size comes from a processing preview of the same config
+    /// rather than the file on disk
+    pub sizes_from_preview: usize,
+    /// Paths that don't exist, left out of the totals
+    pub missing_files: Vec<String>,
+    /// Images over the app's or the provider's size limit
+    pub warnings: Vec<BatchEstimateWarning>,
+    /// Recent average speed of one upload, None without upload stats to go by
+    pub bytes_per_sec: Option<f64>,
+    pub concurrency: usize,
+    /// None when `bytes_per_sec` is
+    pub eta: Option<EtaRange>,
+}
+
+/// Outcome of one unique image in a `process_markdown_files` run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineUpload {
+    /// `UniqueImage::id`, also the progress task id of the upload
+    pub image_id: String,
+    pub absolute_path: String,
+    pub uploaded_url: Option<String>,
+    /// URL taken from upload history rather than a new upload
+    pub reused: bool,
+    pub usage_count: usize,
+    pub error: Option<String>,
+    /// Waiting in the offline queue, its links are rewritten after the upload
+    #[serde(default)]
+    pub queued_offline: bool,
+    /// Non-fatal problems of the upload, see `UploadResult::warnings`
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// One selected reference of an `upload_article_images` call. References to
+/// the same file share one upload and its URL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArticleImageUpload {
+    /// `image_id` is the selected `ImageReference::id`
+    pub result: UploadResult,
+    /// Rewrites this reference to the uploaded URL, None when the upload failed
+    pub replacement: Option<LinkReplacement>,
+}
+
+/// Structured report of a `process_markdown_files` run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessMarkdownReport {
+    pub dry_run: bool,
+    pub scan_results: Vec<ScanResult>,
+    pub missing_images: Vec<MissingImage>,
+    pub uploads: Vec<PipelineUpload>,
+    pub replacements: Vec<LinkReplacement>,
+    /// None for dry runs or when nothing had to be replaced
+    pub replacement_result: Option<BatchReplacementResult>,
+    pub backups: Vec<BackupInfo>,
+    /// Where the replacement changelog was written, if it was
+    #[serde(default)]
+    pub changelog_path: Option<String>,
+    /// Size and duration of the uploads a dry run found, None otherwise
+    #[serde(default)]
+    pub estimate: Option<BatchEstimate>,
+    /// Disk check right before the replace phase; files in `changed_files`
+    /// were rescanned and their entries in `scan_results` replaced
+    #[serde(default)]
+    pub revalidation: Option<ScanRevalidation>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReplacementResult {
     pub file_path: String,
@@ -182,6 +1190,203 @@ pub struct ReplacementResult {
     pub successful_replacements: usize,
     pub failed_replacements: Vec<ReplacementError>,
     pub duration: SystemTime,
+    /// Replacements that were written, see `ReplacementChange`
+    #[serde(default)]
+    pub changes: Vec<ReplacementChange>,
+    /// Encoding the file was read in when it isn't UTF-8; it's written back
+    /// in it unless `converted_to_utf8`
+    #[serde(default)]
+    pub encoding: Option<String>,
+    #[serde(default)]
+    pub converted_to_utf8: bool,
+    /// What the check of the rewritten file found, empty when it reads back
+    /// as the original with only the links changed
+    #[serde(default)]
+    pub warnings: Vec<ReplacementWarning>,
+    /// The file failed that check and was put back as it was before the
+    /// rewrite (`restore_unverified_replacements` setting); its replacements
+    /// are then listed as failed
+    #[serde(default)]
+    pub restored: bool,
+}
+
+/// A difference between a rewritten markdown file and what replacing its
+/// links should have produced
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplacementWarning {
+    pub code: ReplacementWarningCode,
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplacementWarningCode {
+    /// A written link isn't where the replacement put it
+    LinkMissing,
+    /// A line differs from the original outside the replaced links
+    LineChanged,
+    /// The file has more or fewer lines than before
+    LineCountChanged,
+    /// A rewritten line has more or fewer image references than before
+    ReferenceCountChanged,
+    /// The file couldn't be read back after writing
+    Unreadable,
+}
+
+/// One link rewrite that was written to a file, as kept in a replacement changelog
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplacementChange {
+    pub file_path: String,
+    pub line: usize,
+    /// Where `written_text` starts in the rewritten line (1-based bytes)
+    pub column: usize,
+    pub old_link: String,
+    pub new_link: String,
+    /// Upload the new link points at, None when upload history has no match
+    #[serde(default)]
+    pub uploaded_url: Option<String>,
+    #[serde(default)]
+    pub object_key: Option<String>,
+    #[serde(default)]
+    pub history_record_id: Option<String>,
+    /// Exact text taken out of the line; differs from `old_link` when the
+    /// rewrite also changed surrounding syntax, e.g. a wiki embed
+    pub replaced_text: String,
+    /// Exact text put in its place, `new_link` after escaping
+    pub written_text: String,
+}
+
+impl ReplacementChange {
+    /// Replacement that puts `replaced_text` back
+    pub fn inverse(&self) -> LinkReplacement {
+        LinkReplacement {
+            file_path: self.file_path.clone(),
+            line: self.line,
+            column: self.column,
+            old_link: self.written_text.clone(),
+            new_link: self.replaced_text.clone(),
+            context: LinkContext::Verbatim,
+        }
+    }
+}
+
+/// Where an image link in a markdown file points
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkLocation {
+    /// A file path, uploaded by the scan + upload flow
+    Local,
+    /// An object of the config the analysis was run for
+    CurrentConfig,
+    /// An object of another saved config, matched by its endpoint or CDN domain
+    OtherConfig,
+    /// Any other URL
+    External,
+}
+
+/// One image link of a markdown file and where it points
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassifiedLink {
+    pub link: String,
+    pub line: usize,
+    pub column: usize,
+    pub context: LinkContext,
+    pub location: LinkLocation,
+    /// Saved config the object belongs to, for current/other config links
+    pub config_id: Option<String>,
+    pub object_key: Option<String>,
+}
+
+/// Image links of one markdown file, counted by location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLinkAnalysis {
+    pub file_path: String,
+    pub local: usize,
+    pub current_config: usize,
+    pub other_config: usize,
+    pub external: usize,
+    pub links: Vec<ClassifiedLink>,
+    /// Set when the file couldn't be read, the counts are then zero
+    pub error: Option<String>,
+}
+
+/// Number of links pointing at one other saved config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfigLinkCount {
+    pub config_id: String,
+    pub config_name: String,
+    pub link_count: usize,
+}
+
+/// Result of `analyze_markdown_links`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkAnalysisReport {
+    /// Config links were compared against
+    pub config_id: String,
+    pub files: Vec<FileLinkAnalysis>,
+    /// Links already on other saved configs, most used first
+    pub other_configs: Vec<ConfigLinkCount>,
+}
+
+/// How `migrate_config_links` moves links from other configs to the current one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkMigrationMode {
+    /// Only rewrite the URL; the object must already exist in the current
+    /// bucket under the same key, e.g. after a bucket sync
+    #[default]
+    RewriteUrl,
+    /// Download each object from its config and upload it to the current one
+    /// under the same key
+    Reupload,
+}
+
+/// Outcome of moving one object in `migrate_config_links`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectMigration {
+    pub source_config_id: String,
+    pub object_key: String,
+    pub new_url: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Result of `migrate_config_links`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkMigrationReport {
+    pub dry_run: bool,
+    pub mode: LinkMigrationMode,
+    pub analysis: LinkAnalysisReport,
+    pub objects: Vec<ObjectMigration>,
+    pub replacements: Vec<LinkReplacement>,
+    /// None for dry runs or when nothing had to be replaced
+    pub replacement_result: Option<BatchReplacementResult>,
+    pub backups: Vec<BackupInfo>,
+}
+
+/// Result of `migrate_cdn_domain`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdnMigrationSummary {
+    pub dry_run: bool,
+    pub files_scanned: usize,
+    /// Files with at least one link rewritten (or to rewrite, for dry runs)
+    pub files_changed: Vec<String>,
+    pub replacements: Vec<LinkReplacement>,
+    /// Links on the old domain left alone in strict mode because their path
+    /// matches no uploaded object
+    pub skipped_replacements: Vec<LinkReplacement>,
+    /// None for dry runs or when nothing had to be replaced
+    pub replacement_result: Option<BatchReplacementResult>,
+    pub backups: Vec<BackupInfo>,
+    pub records_updated: usize,
+}
+
+/// Flat list of the changes of one replacement run, written as
+/// `.imgtoss-changes.json` so a git diff can be traced back to its uploads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementChangelog {
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    pub changes: Vec<ReplacementChange>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -198,6 +1403,49 @@ pub struct BatchReplacementResult {
     pub total_failed_replacements: usize,
     pub duration: std::time::Duration,
     pub timestamp: SystemTime,
+    /// Read-only or locked files left untouched under `ReadOnlyPolicy::Skip`,
+    /// not counted in `total_files` or as failures
+    #[serde(default)]
+    pub skipped_read_only: Vec<UnwritableFile>,
+    /// Backups the atomic variant took before rewriting, empty otherwise
+    #[serde(default)]
+    pub backups: Vec<BackupInfo>,
+}
+
+/// What a replacement batch does with files it can't open for writing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadOnlyPolicy {
+    /// Fail before any file is modified, naming the files
+    #[default]
+    Abort,
+    /// Leave them out and report them in `skipped_read_only`
+    Skip,
+}
+
+/// A markdown file that can't be opened for writing
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnwritableFile {
+    pub file_path: String,
+    pub error: String,
+}
+
+/// Output format of exported audit reports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+/// Output format of an exported upload history gallery
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GalleryFormat {
+    #[default]
+    Html,
+    Markdown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -215,26 +1463,202 @@ pub struct RollbackError {
     pub error: String,
 }
 
-// ============================================================================
-// History and State Models
-// ============================================================================
+// ============================================================================
+// History and State Models
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadHistoryRecord {
+    pub id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub image_name: String,
+    /// Empty for failed uploads kept by the old image history
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub uploaded_url: String,
+    pub upload_mode: UploadMode,
+    pub source_file: Option<String>, // 对于文章上传模式，记录来源Markdown文件
+    pub file_size: u64,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub checksum: String,
+    /// Size variants uploaded together with this image
+    #[serde(default)]
+    pub variants: Vec<ImageVariant>,
+    /// Saved config the image was uploaded with, None for unsaved configs and
+    /// older records
+    #[serde(default)]
+    pub config_id: Option<String>,
+    #[serde(default)]
+    pub bucket: Option<String>,
+    /// Key of the object in `bucket`, None for older records and records
+    /// added by the frontend
+    #[serde(default)]
+    pub object_key: Option<String>,
+    /// Local file the image was uploaded from, None for older records and
+    /// uploads that didn't come from a file
+    #[serde(default)]
+    pub original_path: Option<String>,
+    /// Only the old image history stored failed uploads
+    #[serde(default = "default_success")]
+    pub success: bool,
+    #[serde(default)]
+    pub error_message: Option<String>,
+    /// Provider request ID of the upload, for support tickets; None for older
+    /// records and responses without one
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// The source file was kept in the originals archive before processing
+    /// changed it, see `get_archived_original`
+    #[serde(default)]
+    pub original_archived: bool,
+}
+
+/// An upload record deleted from the history, kept in the trash until it is
+/// restored or purged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedHistoryRecord {
+    #[serde(flatten)]
+    pub record: UploadHistoryRecord,
+    pub deleted_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn default_success() -> bool {
+    true
+}
+
+// The old image history wrote null where an upload had no URL or checksum
+fn null_as_empty<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+/// The saved config and bucket an upload goes to
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UploadTarget {
+    pub config_id: Option<String>,
+    pub bucket: Option<String>,
+}
+
+impl UploadTarget {
+    pub fn new(config_id: Option<String>, config: &OSSConfig) -> Self {
+        Self {
+            config_id,
+            bucket: Some(config.bucket.clone()),
+        }
+    }
+
+    /// Whether `record` was uploaded to this target. Records from before
+    /// uploads carried their config can't tell and always match.
+    pub fn matches(&self, record: &UploadHistoryRecord) -> bool {
+        if record.config_id.is_none() && record.bucket.is_none() {
+            return true;
+        }
+        if let (Some(expected), Some(actual)) = (&self.config_id, &record.config_id) {
+            return expected == actual;
+        }
+        matches!((&self.bucket, &record.bucket), (Some(expected), Some(actual)) if expected == actual)
+    }
+}
+
+/// Which earlier uploads count as duplicates of an image
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateScope {
+    /// Only uploads to the same config (or bucket), URLs are not reused across buckets
+    #[default]
+    SameConfig,
+    /// Any earlier upload, wherever it went
+    AnyConfig,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UploadHistoryRecord {
-    pub id: String,
-    pub timestamp: chrono::DateTime<chrono::Utc>,
-    pub image_name: String,
-    pub uploaded_url: String,
-    pub upload_mode: UploadMode,
-    pub source_file: Option<String>, // 对于文章上传模式，记录来源Markdown文件
-    pub file_size: u64,
-    pub checksum: String,
+impl DuplicateScope {
+    /// The match to reuse among `matches` (newest first): one uploaded to
+    /// `target` if there is one, otherwise the newest when any config counts.
+    /// Without a target every match is fair game.
+    pub fn pick<'a>(
+        self,
+        matches: &'a [UploadHistoryRecord],
+        target: Option<&UploadTarget>,
+    ) -> Option<&'a UploadHistoryRecord> {
+        let Some(target) = target else {
+            return matches.first();
+        };
+        let same_config = matches.iter().find(|record| target.matches(record));
+        match self {
+            DuplicateScope::SameConfig => same_config,
+            DuplicateScope::AnyConfig => same_config.or(matches.first()),
+        }
+    }
 }
 
+/// How an image was uploaded, kept with every history record.
+///
+/// Names unknown to this version (exports from a newer one) read as
+/// `ImageUpload` instead of failing the whole file.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "String")]
 pub enum UploadMode {
     ImageUpload,
     ArticleUpload,
+    ClipboardUpload,
+    /// A remote image downloaded and uploaded to the bucket
+    UrlImport,
+    /// Uploaded automatically by a folder watcher
+    WatcherUpload,
+    /// An object copied again, e.g. by `migrate_config_links` with Reupload
+    Reupload,
+    /// Uploaded while turning local links of a document into remote ones
+    Localization,
+}
+
+impl UploadMode {
+    pub const ALL: [UploadMode; 7] = [
+        UploadMode::ImageUpload,
+        UploadMode::ArticleUpload,
+        UploadMode::ClipboardUpload,
+        UploadMode::UrlImport,
+        UploadMode::WatcherUpload,
+        UploadMode::Reupload,
+        UploadMode::Localization,
+    ];
+
+    /// Serialized name, also what commands take as `upload_mode`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadMode::ImageUpload => "ImageUpload",
+            UploadMode::ArticleUpload => "ArticleUpload",
+            UploadMode::ClipboardUpload => "ClipboardUpload",
+            UploadMode::UrlImport => "UrlImport",
+            UploadMode::WatcherUpload => "WatcherUpload",
+            UploadMode::Reupload => "Reupload",
+            UploadMode::Localization => "Localization",
+        }
+    }
+
+    /// Strict counterpart of the serde fallback, for command arguments
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.as_str() == name)
+    }
+
+    /// Key in `HistoryStatistics::upload_modes`
+    pub fn stats_key(&self) -> &'static str {
+        match self {
+            UploadMode::ImageUpload => "image_upload",
+            UploadMode::ArticleUpload => "article_upload",
+            UploadMode::ClipboardUpload => "clipboard_upload",
+            UploadMode::UrlImport => "url_import",
+            UploadMode::WatcherUpload => "watcher_upload",
+            UploadMode::Reupload => "reupload",
+            UploadMode::Localization => "localization",
+        }
+    }
+}
+
+impl From<String> for UploadMode {
+    fn from(name: String) -> Self {
+        Self::parse(&name).unwrap_or(UploadMode::ImageUpload)
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -248,6 +1672,240 @@ pub struct AppState {
     pub is_uploading: bool,
 }
 
+// ============================================================================
+// Application Settings Models
+// ============================================================================
+
+/// Output syntax used when copying uploaded image links
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkFormat {
+    #[default]
+    Markdown,
+    Html,
+    Bbcode,
+    Url,
+    Custom,
+}
+
+/// Hash used for upload checksums and duplicate detection
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// Default for `AppSettings::max_markdown_size_mb`
+pub const DEFAULT_MAX_MARKDOWN_SIZE_MB: u64 = 20;
+
+/// App-wide preferences (not tied to a particular OSS config).
+/// Every field has a serde default so older settings files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AppSettings {
+    pub default_copy_format: LinkFormat,
+    /// Template for LinkFormat::Custom, supports {url}, {filename}, {alt} and {srcset}
+    pub custom_link_template: Option<String>,
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// Concurrent uploads per batch
+    pub upload_concurrency: usize,
+    /// Upload commands allowed per minute
+    pub upload_rate_limit_per_minute: u32,
+    /// Lowercase image extensions without the leading dot. Commands reject
+    /// other images and scans leave them out with a `Filtered` warning.
+    pub allowed_extensions: Vec<String>,
+    /// Backups older than this are removed by cleanup
+    pub backup_retention_days: u32,
+    /// Most recent backups kept per file, 0 keeps all of them
+    pub backup_keep_per_file: usize,
+    pub language: String,
+    pub log_level: String,
+    /// Markdown files accepted by a single scan
+    pub max_scan_files: usize,
+    /// Images accepted by a single upload call; batch uploads chunk by this size
+    pub max_upload_images: usize,
+    /// Larger image files are flagged by scans and skipped by uploads
+    pub max_image_size_mb: u64,
+    /// Larger markdown files are skipped by scans and refused by link replacement
+    pub max_markdown_size_mb: u64,
+    /// Largest edge in pixels generate_thumbnail accepts
+    pub max_thumbnail_size: u32,
+    /// Thumbnails decoded and resized at once, 0 uses half the CPU cores
+    pub thumbnail_concurrency: usize,
+    /// Absolute static directories (a site's `static/`) tried in order for image
+    /// paths starting with `/` or not found next to the markdown file
+    pub asset_roots: Vec<String>,
+    /// Startup task ids not run on launch, for troubleshooting
+    pub skipped_startup_tasks: Vec<String>,
+    /// When set, scans keep only images whose absolute path matches one of these
+    pub include_image_globs: Vec<String>,
+    /// Images whose absolute path matches one of these are left out of scans
+    pub exclude_image_globs: Vec<String>,
+    /// Markdown files matching one of these are not scanned
+    pub exclude_file_globs: Vec<String>,
+    /// Directory scans skip what `.gitignore`, `.ignore` and the scan root's
+    /// `.imgtossignore` exclude
+    pub respect_ignore_files: bool,
+    /// Keep a copy of each source image whose upload processing changes it
+    /// (compression, resizing, conversion) in the originals archive
+    pub archive_originals: bool,
+    /// Rewrite markdown files that aren't UTF-8 (GBK, Big5...) as UTF-8 when
+    /// replacing links; off keeps each file in the encoding it was read in
+    pub convert_legacy_encoding: bool,
+    /// Put a markdown file back as it was when reading it back after link
+    /// replacement shows more changed than the links
+    pub restore_unverified_replacements: bool,
+    /// After the first upload with a CDN domain, check that the CDN serves the
+    /// object too. Off for private CDNs that refuse HEAD requests.
+    pub verify_cdn_domain: bool,
+    /// Saved config article uploads use, None falls back to the active config
+    pub default_config_for_article: Option<String>,
+    /// Saved config direct image uploads use, None falls back to the active config
+    pub default_config_for_direct: Option<String>,
+    pub notifications: NotificationConfig,
+    pub maintenance: MaintenanceSettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            default_copy_format: LinkFormat::default(),
+            custom_link_template: None,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            upload_concurrency: 3,
+            upload_rate_limit_per_minute: 10,
+            allowed_extensions: crate::utils::paths::DEFAULT_IMAGE_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            backup_retention_days: 30,
+            backup_keep_per_file: 10,
+            language: "zh-CN".to_string(),
+            log_level: "info".to_string(),
+            max_scan_files: 1000,
+            max_upload_images: 200,
+            max_image_size_mb: 100,
+            max_markdown_size_mb: DEFAULT_MAX_MARKDOWN_SIZE_MB,
+            max_thumbnail_size: 1024,
+            thumbnail_concurrency: 0,
+            asset_roots: Vec::new(),
+            skipped_startup_tasks: Vec::new(),
+            include_image_globs: Vec::new(),
+            exclude_image_globs: Vec::new(),
+            exclude_file_globs: Vec::new(),
+            respect_ignore_files: true,
+            archive_originals: false,
+            convert_legacy_encoding: false,
+            restore_unverified_replacements: false,
+            verify_cdn_domain: true,
+            default_config_for_article: None,
+            default_config_for_direct: None,
+            notifications: NotificationConfig::default(),
+            maintenance: MaintenanceSettings::default(),
+        }
+    }
+}
+
+// Distinguishes an explicit `null` (Some(None)) from a missing field (None)
+fn deserialize_some<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Partial update for AppSettings: only the fields that are set get applied,
+/// so two windows changing different fields don't overwrite each other
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct AppSettingsPatch {
+    pub default_copy_format: Option<LinkFormat>,
+    /// Explicit `null` clears the template, a missing field leaves it unchanged
+    #[serde(deserialize_with = "deserialize_some")]
+    pub custom_link_template: Option<Option<String>>,
+    pub checksum_algorithm: Option<ChecksumAlgorithm>,
+    pub upload_concurrency: Option<usize>,
+    pub upload_rate_limit_per_minute: Option<u32>,
+    pub allowed_extensions: Option<Vec<String>>,
+    pub backup_retention_days: Option<u32>,
+    pub backup_keep_per_file: Option<usize>,
+    pub language: Option<String>,
+    pub log_level: Option<String>,
+    pub max_scan_files: Option<usize>,
+    pub max_upload_images: Option<usize>,
+    pub max_image_size_mb: Option<u64>,
+    pub max_markdown_size_mb: Option<u64>,
+    pub max_thumbnail_size: Option<u32>,
+    pub thumbnail_concurrency: Option<usize>,
+    pub asset_roots: Option<Vec<String>>,
+    pub skipped_startup_tasks: Option<Vec<String>>,
+    pub include_image_globs: Option<Vec<String>>,
+    pub exclude_image_globs: Option<Vec<String>>,
+    pub exclude_file_globs: Option<Vec<String>>,
+    pub respect_ignore_files: Option<bool>,
+    pub archive_originals: Option<bool>,
+    pub convert_legacy_encoding: Option<bool>,
+    pub restore_unverified_replacements: Option<bool>,
+    pub verify_cdn_domain: Option<bool>,
+    /// Explicit `null` goes back to the active config
+    #[serde(deserialize_with = "deserialize_some")]
+    pub default_config_for_article: Option<Option<String>>,
+    #[serde(deserialize_with = "deserialize_some")]
+    pub default_config_for_direct: Option<Option<String>>,
+    pub notifications: Option<NotificationConfig>,
+    pub maintenance: Option<MaintenanceSettings>,
+}
+
+/// What `export_app_data` puts into the archive
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AppDataInclude {
+    /// OSS configurations, with their secret keys blanked unless `secrets` is set
+    pub configs: bool,
+    /// Keep the secret keys in the exported configurations
+    pub secrets: bool,
+    /// Upload history and the file operations log
+    pub history: bool,
+    pub settings: bool,
+}
+
+/// `manifest.json` of an app data archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppDataManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub include: AppDataInclude,
+    /// The archive holds credentials and must be handled like a password
+    pub sensitive: bool,
+    /// Schema version of every data entry in the archive, keyed by entry name
+    pub schema_versions: std::collections::BTreeMap<String, u32>,
+}
+
+/// What importing an archive would bring in, without changing anything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppDataPreview {
+    pub manifest: AppDataManifest,
+    pub entries: Vec<String>,
+    pub config_names: Vec<String>,
+    pub upload_record_count: usize,
+    pub file_operation_count: usize,
+    pub has_settings: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppDataImportResult {
+    pub configs_imported: usize,
+    /// Imported configurations that still need their secret key entered
+    pub configs_missing_secrets: Vec<String>,
+    pub upload_records_imported: usize,
+    pub file_operations_imported: usize,
+    pub settings_imported: bool,
+}
+
 // ============================================================================
 // Utility Models
 // ============================================================================
@@ -261,6 +1919,20 @@ pub struct PaginatedResult<T> {
     pub has_more: bool,
 }
 
+impl<T> PaginatedResult<T> {
+    /// `page` is 1-based, `total` counts every item matching the query
+    pub fn new(items: Vec<T>, total: usize, page: usize, page_size: usize) -> Self {
+        Self {
+            items,
+            total,
+            page,
+            page_size,
+            has_more: page * page_size < total,
+        }
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub valid: bool,
@@ -271,7 +1943,54 @@ pub struct ValidationResult {
 pub struct ConfigValidation {
     pub valid: bool,
     pub errors: Vec<String>,
+    /// Problems that don't block saving the config
+    #[serde(default)]
+    pub warnings: Vec<ConfigWarning>,
     pub connection_test: Option<OSSConnectionTest>,
+    /// Key the path template gives a sample upload, for display
+    #[serde(default)]
+    pub sample_key: Option<String>,
+}
+
+/// A setting that works but probably isn't what the user wants
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfigWarning {
+    pub code: ConfigWarningCode,
+    /// English text, the UI may show its own for `code` instead
+    pub message: String,
+}
+
+impl ConfigWarning {
+    pub fn new(code: ConfigWarningCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Stable identifier of a config warning; new kinds get new codes, existing
+/// codes keep their meaning
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigWarningCode {
+    /// Private objects and no CDN domain: links won't open for readers
+    PrivateWithoutCdn,
+    /// Bucket names with `_` are rejected by some providers and tools
+    BucketUnderscore,
+    /// The app accepts images larger than the provider takes in one upload
+    ObjectSizeLimit,
+    /// The CDN domain doesn't serve an object the bucket has
+    CdnNotServing,
+}
+
+/// Result of `validate_path_template`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathTemplateCheck {
+    pub valid: bool,
+    /// The template rendered for a sample file, shown even when it's invalid
+    pub sample_key: String,
+    pub errors: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -299,7 +2018,289 @@ impl UploadTask {
     }
 }
 
+impl ImageProcessingOptions {
+    pub fn from_config(config: &OSSConfig) -> Self {
+        Self {
+            compression_enabled: config.compression_enabled,
+            quality: config.compression_quality,
+            output_format: config.output_format.clone(),
+            max_dimension: config.max_dimension,
+            strip_metadata: config.strip_metadata,
+            convert_to_srgb: config.convert_to_srgb,
+            max_upload_size_kb: config.max_upload_size_kb,
+            size_variants: config.size_variants.clone(),
+        }
+    }
+
+    /// True when nothing would change the original bytes
+    pub fn is_passthrough(&self) -> bool {
+        !self.compression_enabled
+            && self.output_format.is_none()
+            && self.max_dimension.is_none()
+            && !self.strip_metadata
+    }
+
+    /// True when the uploaded file can't end up smaller than the original
+    pub fn keeps_size(&self) -> bool {
+        self.is_passthrough() && self.max_upload_size_kb.is_none()
+    }
+}
+
+impl ImageUsage {
+    /// Where `image`, found in `file_path`, is used
+    pub fn of(file_path: &str, image: &ImageReference) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            image_id: image.id.clone(),
+            original_path: image.original_path.clone(),
+            line: image.markdown_line,
+            column: image.markdown_column,
+            context: image.context,
+            path_suffix: image.path_suffix.clone(),
+        }
+    }
+
+    /// Rewrite of this usage to `uploaded_url`; `preserve_suffix` appends its
+    /// `?query`/`#fragment` to the URL
+    pub fn link_replacement(&self, uploaded_url: &str, preserve_suffix: bool) -> LinkReplacement {
+        let new_link = match self.path_suffix.as_deref() {
+            Some(suffix) if preserve_suffix => join_link_suffix(uploaded_url, suffix),
+            _ => uploaded_url.to_string(),
+        };
+        LinkReplacement {
+            file_path: self.file_path.clone(),
+            line: self.line,
+            column: self.column,
+            old_link: self.original_path.clone(),
+            new_link,
+            context: self.context,
+        }
+    }
+}
+
+impl UniqueImage {
+    /// One replacement per usage, all pointing at the single uploaded URL.
+    /// `preserve_suffix` appends each usage's `?query`/`#fragment` to the URL.
+    pub fn link_replacements(
+        &self,
+        uploaded_url: &str,
+        preserve_suffix: bool,
+    ) -> Vec<LinkReplacement> {
+        usage_replacements(&self.usages, uploaded_url, preserve_suffix)
+    }
+}
+
+impl DeferredLinks {
+    /// The held-back rewrites, now that the upload has a URL
+    pub fn replacements(&self, uploaded_url: &str) -> Vec<LinkReplacement> {
+        usage_replacements(&self.usages, uploaded_url, self.preserve_link_suffix)
+    }
+}
+
+fn usage_replacements(
+    usages: &[ImageUsage],
+    uploaded_url: &str,
+    preserve_suffix: bool,
+) -> Vec<LinkReplacement> {
+    usages
+        .iter()
+        .map(|usage| usage.link_replacement(uploaded_url, preserve_suffix))
+        .collect()
+}
+
+impl AppSettings {
+    /// Apply the fields set in `patch`
+    pub fn apply_patch(&mut self, patch: AppSettingsPatch) {
+        if let Some(format) = patch.default_copy_format {
+            self.default_copy_format = format;
+        }
+        if let Some(template) = patch.custom_link_template {
+            self.custom_link_template = template;
+        }
+        if let Some(algorithm) = patch.checksum_algorithm {
+            self.checksum_algorithm = algorithm;
+        }
+        if let Some(concurrency) = patch.upload_concurrency {
+            self.upload_concurrency = concurrency;
+        }
+        if let Some(limit) = patch.upload_rate_limit_per_minute {
+            self.upload_rate_limit_per_minute = limit;
+        }
+        if let Some(extensions) = patch.allowed_extensions {
+            self.allowed_extensions = extensions
+                .iter()
+                .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect();
+        }
+        if let Some(days) = patch.backup_retention_days {
+            self.backup_retention_days = days;
+        }
+        if let Some(keep) = patch.backup_keep_per_file {
+            self.backup_keep_per_file = keep;
+        }
+        if let Some(language) = patch.language {
+            self.language = language;
+        }
+        if let Some(level) = patch.log_level {
+            self.log_level = level.to_lowercase();
+        }
+        if let Some(limit) = patch.max_scan_files {
+            self.max_scan_files = limit;
+        }
+        if let Some(limit) = patch.max_upload_images {
+            self.max_upload_images = limit;
+        }
+        if let Some(limit) = patch.max_image_size_mb {
+            self.max_image_size_mb = limit;
+        }
+        if let Some(limit) = patch.max_markdown_size_mb {
+            self.max_markdown_size_mb = limit;
+        }
+        if let Some(limit) = patch.max_thumbnail_size {
+            self.max_thumbnail_size = limit;
+        }
+        if let Some(concurrency) = patch.thumbnail_concurrency {
+            self.thumbnail_concurrency = concurrency;
+        }
+        if let Some(roots) = patch.asset_roots {
+            self.asset_roots = roots;
+        }
+        if let Some(tasks) = patch.skipped_startup_tasks {
+            self.skipped_startup_tasks = tasks;
+        }
+        if let Some(globs) = patch.include_image_globs {
+            self.include_image_globs = globs;
+        }
+        if let Some(globs) = patch.exclude_image_globs {
+            self.exclude_image_globs = globs;
+        }
+        if let Some(globs) = patch.exclude_file_globs {
+            self.exclude_file_globs = globs;
+        }
+        if let Some(respect) = patch.respect_ignore_files {
+            self.respect_ignore_files = respect;
+        }
+        if let Some(archive) = patch.archive_originals {
+            self.archive_originals = archive;
+        }
+        if let Some(convert) = patch.convert_legacy_encoding {
+            self.convert_legacy_encoding = convert;
+        }
+        if let Some(restore) = patch.restore_unverified_replacements {
+            self.restore_unverified_replacements = restore;
+        }
+        if let Some(verify) = patch.verify_cdn_domain {
+            self.verify_cdn_domain = verify;
+        }
+        if let Some(config_id) = patch.default_config_for_article {
+            self.default_config_for_article = config_id;
+        }
+        if let Some(config_id) = patch.default_config_for_direct {
+            self.default_config_for_direct = config_id;
+        }
+        if let Some(notifications) = patch.notifications {
+            self.notifications = notifications;
+        }
+        if let Some(maintenance) = patch.maintenance {
+            self.maintenance = maintenance;
+        }
+    }
+
+    pub fn max_image_bytes(&self) -> u64 {
+        self.max_image_size_mb.saturating_mul(1024 * 1024)
+    }
+
+    pub fn max_markdown_bytes(&self) -> u64 {
+        self.max_markdown_size_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+impl ObjectAcl {
+    /// Value of the provider ACL header, None sends no header
+    pub fn header_value(&self) -> Option<&'static str> {
+        match self {
+            ObjectAcl::Default => None,
+            ObjectAcl::PublicRead => Some("public-read"),
+            ObjectAcl::Private => Some("private"),
+        }
+    }
+}
+
+impl ChecksumAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Checksums are stored as `{algo}:{hex}`; plain hex predates the prefix and is sha256
+    pub fn split_checksum(checksum: &str) -> Option<(ChecksumAlgorithm, &str)> {
+        let (algorithm, hex) = match checksum.split_once(':') {
+            Some(("sha256", hex)) => (ChecksumAlgorithm::Sha256, hex),
+            Some(("blake3", hex)) => (ChecksumAlgorithm::Blake3, hex),
+            Some(_) => return None,
+            None => (ChecksumAlgorithm::Sha256, checksum),
+        };
+
+        // Both algorithms produce 256-bit digests
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        Some((algorithm, hex))
+    }
+
+    /// True when both checksums use the same algorithm and digest
+    pub fn checksums_match(a: &str, b: &str) -> bool {
+        match (Self::split_checksum(a), Self::split_checksum(b)) {
+            (Some((algo_a, hex_a)), Some((algo_b, hex_b))) => {
+                algo_a == algo_b && hex_a.eq_ignore_ascii_case(hex_b)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `url` with a link's `?query`/`#fragment` appended. A query merges into one
+/// the URL already has; a fragment replaces any existing one.
+fn join_link_suffix(url: &str, suffix: &str) -> String {
+    let (url, url_fragment) = match url.split_once('#') {
+        Some((base, fragment)) => (base, Some(fragment)),
+        None => (url, None),
+    };
+    let (query, fragment) = match suffix.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (suffix, None),
+    };
+
+    let mut joined = url.to_string();
+    if let Some(query) = query.strip_prefix('?').filter(|q| !q.is_empty()) {
+        joined.push(if url.contains('?') { '&' } else { '?' });
+        joined.push_str(query);
+    }
+    if let Some(fragment) = fragment.or(url_fragment) {
+        joined.push('#');
+        joined.push_str(fragment);
+    }
+    joined
+}
+
 impl ImageReference {
+    /// Number of distinct images among `images`, counting each reference group once
+    pub fn unique_count(images: &[ImageReference]) -> usize {
+        images
+            .iter()
+            .filter(|image| {
+                image
+                    .reference_group
+                    .as_ref()
+                    .is_none_or(|group| group == &image.id)
+            })
+            .count()
+    }
+
     pub fn new(
         original_path: String,
         absolute_path: String,
@@ -316,8 +2317,21 @@ impl ImageReference {
             markdown_line,
             markdown_column,
             thumbnail: None, // Will be set during validation for existing images
+            reference_group: None,
+            too_large: false,
+            context: LinkContext::MarkdownInline,
+            asset_root: None,
+            path_suffix: None,
         }
     }
+
+    /// `original_path` without its `?query`/`#fragment`, the part that names the file
+    pub fn bare_path(&self) -> &str {
+        self.path_suffix
+            .as_deref()
+            .and_then(|suffix| self.original_path.strip_suffix(suffix))
+            .unwrap_or(&self.original_path)
+    }
 }
 
 // ============================================================================
@@ -358,7 +2372,228 @@ pub enum ErrorSeverity {
     Critical,
 }
 
+/// A JSON store that can be moved aside as corrupt and recovered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    Configs,
+    Settings,
+    UploadHistory,
+    FileOperations,
+    HistoryTrash,
+    BackupIndex,
+    PendingUploads,
+}
+
+/// A store that failed to parse and was replaced by an empty one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorruptStore {
+    pub kind: StoreKind,
+    pub path: String,
+    /// Where the unreadable file was moved, `<path>.corrupt-<timestamp>`
+    pub moved_to: String,
+    pub error: String,
+    pub detected_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Outcome of `recover_corrupt_store`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreRecoveryResult {
+    pub kind: StoreKind,
+    /// The corrupt file records were read from
+    pub source: String,
+    /// Records (or settings fields) salvaged and merged into the live store
+    pub recovered: usize,
+    /// Complete entries that didn't match the record shape
+    pub unreadable: usize,
+}
+
+/// A backend panic, written to `{data_dir}/crashes/` and never sent anywhere.
+/// Users attach these to bug reports.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CrashReport {
+    pub id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Guarded work that panicked ("process_image", "upload_image"), None for
+    /// panics outside of it
+    pub operation: Option<String>,
+    pub message: String,
+    /// `file:line:column` of the panic
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    /// First frames only
+    pub backtrace: String,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+}
+
+/// Entry of `list_crash_reports`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CrashReportSummary {
+    pub id: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub operation: Option<String>,
+    pub message: String,
+}
+
+/// Where imgtoss keeps its configs and data
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StorageMode {
+    /// The platform's config/data directories
+    Standard,
+    /// A `data/` folder next to the executable
+    Portable,
+    /// A directory chosen with `set_data_directory`, e.g. inside a synced folder
+    Custom,
+}
+
+/// Result of `set_data_directory`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataDirectoryChange {
+    /// The new data directory, None when reverting to the default location
+    pub data_dir: Option<String>,
+    /// Existing files copied over
+    pub copied_files: usize,
+    /// Files the new directory already had, left untouched
+    pub skipped_files: usize,
+    /// Services only pick up the new location after a restart
+    pub restart_required: bool,
+}
+
+/// Result of `validate_system_permissions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemPermissions {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub storage_mode: StorageMode,
+    pub config_dir: Option<String>,
+    pub data_dir: Option<String>,
+}
+
+/// Result of `get_connectivity_status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityStatus {
+    pub online: bool,
+    /// Uploads waiting in the offline queue
+    pub queued_uploads: usize,
+    pub last_checked: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupTaskStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    TimedOut,
+    /// Listed in the skipped_startup_tasks setting
+    Skipped,
+}
+
+/// One startup task of this launch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartupTaskOutcome {
+    pub id: String,
+    pub status: StartupTaskStatus,
+    /// What the task did ("removed 12 cached thumbnails") or why it failed
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Result of `get_startup_report`, filled in while the tasks run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartupReport {
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// None until every task has run
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub tasks: Vec<StartupTaskOutcome>,
+}
+
+/// Background maintenance jobs, run shortly after launch and then every
+/// `interval_hours`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MaintenanceSettings {
+    pub interval_hours: u32,
+    /// Trim the thumbnail cache, oldest thumbnails first, to `thumbnail_cache_max_mb`
+    pub thumbnail_cache: bool,
+    pub thumbnail_cache_max_mb: u64,
+    /// Remove backups past `backup_retention_days` / `backup_keep_per_file`
+    pub backup_retention: bool,
+    /// Delete upload history older than `history_max_age_days`, off by default
+    pub history_age_limit: bool,
+    pub history_max_age_days: u32,
+    /// Purge history records deleted more than `trash_retention_days` ago
+    pub history_trash: bool,
+    pub trash_retention_days: u32,
+    /// Drop progress entries of uploads that stopped reporting without finishing
+    pub orphaned_progress: bool,
+    /// Prune the originals archive to `originals_max_age_days` and
+    /// `originals_max_mb`, and drop originals of deleted history records
+    pub originals_archive: bool,
+    pub originals_max_age_days: u32,
+    pub originals_max_mb: u64,
+    /// Hash the local originals of uploaded images and report the ones
+    /// modified since, off by default
+    pub local_integrity: bool,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            interval_hours: 24,
+            thumbnail_cache: true,
+            thumbnail_cache_max_mb: 500,
+            backup_retention: true,
+            history_age_limit: false,
+            history_max_age_days: 365,
+            history_trash: true,
+            trash_retention_days: 30,
+            orphaned_progress: true,
+            originals_archive: true,
+            originals_max_age_days: 365,
+            originals_max_mb: 2048,
+            local_integrity: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceJobResult {
+    Succeeded,
+    Failed,
+    /// Uploads were using what the job cleans
+    Skipped,
+}
+
+/// One maintenance job and how its last run went
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceJobStatus {
+    pub id: String,
+    /// Per the current settings
+    pub enabled: bool,
+    /// None until the job first ran (or was skipped)
+    pub last_run: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_result: Option<MaintenanceJobResult>,
+    /// What the job did, why it failed or why it was skipped
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Result of `get_maintenance_status`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub running: bool,
+    pub last_run: Option<chrono::DateTime<chrono::Utc>>,
+    pub next_run: Option<chrono::DateTime<chrono::Utc>>,
+    pub jobs: Vec<MaintenanceJobStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct NotificationConfig {
     pub enabled: bool,
     pub show_progress: bool,
@@ -366,6 +2601,8 @@ pub struct NotificationConfig {
     pub show_errors: bool,
     pub auto_dismiss_success: bool,
     pub dismiss_timeout: u64, // milliseconds
+    /// Batches running at least this long notify even while the window is focused
+    pub long_batch_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -399,6 +2636,27 @@ pub struct UploadTaskManager {
     pub completed_tasks: Vec<UploadTaskInfo>,
     pub failed_tasks: Vec<UploadTaskInfo>,
     pub cancelled_tasks: Vec<UploadTaskInfo>,
+    /// The tasks above grouped by upload session
+    #[serde(default)]
+    pub sessions: Vec<UploadSessionTasks>,
+}
+
+/// Tasks of one upload session and their combined progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSessionTasks {
+    /// None for tasks started without a session, e.g. left by an older version
+    pub session_id: Option<String>,
+    pub config_id: Option<String>,
+    pub task_ids: Vec<String>,
+    pub completed: usize,
+    pub failed: usize,
+    pub bytes_uploaded: u64,
+    pub total_bytes: u64,
+    /// Bytes per second of the whole session, None once nothing is in flight
+    #[serde(default)]
+    pub speed: Option<u64>,
+    #[serde(default)]
+    pub eta_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -415,6 +2673,52 @@ pub struct UploadTaskInfo {
     pub cancellation_token: Option<String>,
 }
 
+/// An upload that was enqueued but hasn't finished, persisted so it survives a crash
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingUpload {
+    pub task_id: String,
+    pub image_path: String,
+    /// Saved config the upload targets, None when it was started with an unsaved config
+    pub config_id: Option<String>,
+    /// Upload session that enqueued it, kept when it is resumed or flushed
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub source_file: Option<String>,
+    pub upload_mode: UploadMode,
+    pub enqueued_at: chrono::DateTime<chrono::Utc>,
+    /// Waiting for the connection to return instead of failing
+    #[serde(default)]
+    pub queued_offline: bool,
+    /// Links of a queued article upload, rewritten once it has finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deferred_links: Option<DeferredLinks>,
+}
+
+impl PendingUpload {
+    pub fn scope(&self) -> UploadScope {
+        UploadScope {
+            session_id: self.session_id.clone(),
+            config_id: self.config_id.clone(),
+        }
+    }
+}
+
+/// Link rewrites an article upload holds back until its image is uploaded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeferredLinks {
+    pub usages: Vec<ImageUsage>,
+    pub preserve_link_suffix: bool,
+}
+
+/// A pending upload left over from a previous run, re-validated for resuming
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveredUploadTask {
+    #[serde(flatten)]
+    pub task: PendingUpload,
+    pub file_exists: bool,
+    pub config_exists: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UploadTaskStatus {
     Queued,
@@ -424,6 +2728,8 @@ pub enum UploadTaskStatus {
     Failed,
     Cancelled,
     Retrying,
+    /// Held in the offline queue until the connection returns
+    QueuedOffline,
 }
 
 impl Default for SystemHealth {
@@ -449,6 +2755,7 @@ impl Default for NotificationConfig {
             show_errors: true,
             auto_dismiss_success: true,
             dismiss_timeout: 5000, // 5 seconds
+            long_batch_secs: 300,
         }
     }
 }
@@ -467,6 +2774,11 @@ impl UploadTaskInfo {
                 bytes_uploaded: 0,
                 total_bytes: 0,
                 speed: None,
+                eta_secs: None,
+                status: UploadStatus::Pending,
+                error: None,
+                session_id: None,
+                config_id: None,
             },
             start_time: chrono::Utc::now(),
             end_time: None,
@@ -20,6 +20,21 @@ mod models_tests {
         assert_eq!(image_ref.size, 0); // Default value
     }
 
+    #[test]
+    fn test_upload_mode_names_round_trip_and_unknown_falls_back() {
+        for mode in UploadMode::ALL {
+            let json = serde_json::to_string(&mode).unwrap();
+            assert_eq!(json, format!("\"{}\"", mode.as_str()));
+            assert_eq!(serde_json::from_str::<UploadMode>(&json).unwrap(), mode);
+            assert_eq!(UploadMode::parse(mode.as_str()), Some(mode));
+        }
+
+        // Exports from newer versions may carry modes this one doesn't know
+        let mode: UploadMode = serde_json::from_str("\"ScreenshotUpload\"").unwrap();
+        assert_eq!(mode, UploadMode::ImageUpload);
+        assert_eq!(UploadMode::parse("ScreenshotUpload"), None);
+    }
+
     #[test]
     fn test_upload_task_creation() {
         let task = UploadTask::new("image123".to_string());
@@ -54,6 +69,11 @@ mod models_tests {
             images: vec![],
             status: ScanStatus::Success,
             error: None,
+            missing_images: vec![],
+            unique_image_count: 0,
+            warnings: vec![],
+            encoding: None,
+            content_hash: None,
         };
 
         let json = serde_json::to_string(&scan_result).unwrap();
@@ -77,6 +97,13 @@ mod models_tests {
             cdn_domain: Some("https://cdn.example.com".to_string()),
             compression_enabled: true,
             compression_quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -96,6 +123,9 @@ mod models_tests {
             success: true,
             uploaded_url: Some("https://example.com/image.png".to_string()),
             error: None,
+            variants: Vec::new(),
+            queued_offline: false,
+            warnings: Vec::new(),
         };
 
         assert_eq!(result.image_id, "img123");
@@ -112,6 +142,7 @@ mod models_tests {
             column: 20,
             old_link: "./images/old.png".to_string(),
             new_link: "https://cdn.example.com/new.png".to_string(),
+            context: LinkContext::MarkdownInline,
         };
 
         assert_eq!(replacement.file_path, "/path/to/file.md");
@@ -157,4 +188,151 @@ mod models_tests {
         assert!(!invalid_result.valid);
         assert_eq!(invalid_result.errors.len(), 2);
     }
+
+    #[test]
+    fn test_checksum_prefix_parsing() {
+        let hex = "a".repeat(64);
+
+        let (algorithm, digest) = ChecksumAlgorithm::split_checksum(&hex).unwrap();
+        assert_eq!(algorithm, ChecksumAlgorithm::Sha256);
+        assert_eq!(digest, hex);
+
+        let prefixed = format!("blake3:{}", hex);
+        let (algorithm, _) = ChecksumAlgorithm::split_checksum(&prefixed).unwrap();
+        assert_eq!(algorithm, ChecksumAlgorithm::Blake3);
+
+        assert!(ChecksumAlgorithm::split_checksum("md5:abc").is_none());
+        assert!(ChecksumAlgorithm::split_checksum("sha256:xyz").is_none());
+    }
+
+    #[test]
+    fn test_checksums_match_within_algorithm() {
+        let hex = "b".repeat(64);
+
+        // Legacy plain hex is sha256
+        assert!(ChecksumAlgorithm::checksums_match(
+            &hex,
+            &format!("sha256:{}", hex)
+        ));
+        assert!(!ChecksumAlgorithm::checksums_match(
+            &format!("sha256:{}", hex),
+            &format!("blake3:{}", hex)
+        ));
+    }
+
+    #[test]
+    fn test_batch_upload_item_accepts_paths_and_pairs() {
+        let items: Vec<BatchUploadItem> =
+            serde_json::from_str(r#"["/a.png", ["row-1", "/b.png"]]"#).unwrap();
+
+        assert_eq!(items[0], BatchUploadItem::Path("/a.png".to_string()));
+        assert_eq!(items[0].file_id(), None);
+        assert_eq!(items[1].file_id(), Some("row-1"));
+        assert_eq!(items[1].path(), "/b.png");
+    }
+
+    #[test]
+    fn test_upload_target_matches_config_then_bucket() {
+        let record = |config_id: Option<&str>, bucket: Option<&str>| UploadHistoryRecord {
+            id: "1".to_string(),
+            timestamp: chrono::Utc::now(),
+            image_name: "a.png".to_string(),
+            uploaded_url: "https://blog.example.com/a.png".to_string(),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 1,
+            checksum: "sha256:00".to_string(),
+            variants: vec![],
+            config_id: config_id.map(str::to_string),
+            bucket: bucket.map(str::to_string),
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        };
+        let wiki = UploadTarget {
+            config_id: Some("wiki".to_string()),
+            bucket: Some("wiki-bucket".to_string()),
+        };
+
+        assert!(wiki.matches(&record(Some("wiki"), Some("old-name"))));
+        assert!(!wiki.matches(&record(Some("blog"), Some("wiki-bucket"))));
+        // Unsaved config: only the bucket is known
+        assert!(wiki.matches(&record(None, Some("wiki-bucket"))));
+        assert!(!wiki.matches(&record(None, Some("blog-bucket"))));
+        // Records from before uploads were tagged with their config
+        assert!(wiki.matches(&record(None, None)));
+    }
+
+    #[test]
+    fn test_history_record_without_original_path_still_loads() {
+        let json = r#"{
+            "id": "1",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "image_name": "hero.png",
+            "uploaded_url": "https://cdn.example.com/hero.png",
+            "upload_mode": "ImageUpload",
+            "source_file": null,
+            "file_size": 1,
+            "checksum": "sha256:00"
+        }"#;
+        let record: UploadHistoryRecord = serde_json::from_str(json).unwrap();
+        assert_eq!(record.original_path, None);
+
+        let record = UploadHistoryRecord {
+            original_path: Some("/home/me/Pictures/hero.png".to_string()),
+            ..record
+        };
+        let reloaded: UploadHistoryRecord =
+            serde_json::from_str(&serde_json::to_string(&record).unwrap()).unwrap();
+        assert_eq!(
+            reloaded.original_path.as_deref(),
+            Some("/home/me/Pictures/hero.png")
+        );
+    }
+
+    #[test]
+    fn test_link_replacements_preserve_suffix() {
+        let usage = |original: &str, suffix: Option<&str>| ImageUsage {
+            file_path: "/docs/a.md".to_string(),
+            image_id: "i".to_string(),
+            original_path: original.to_string(),
+            line: 1,
+            column: 5,
+            context: LinkContext::MarkdownInline,
+            path_suffix: suffix.map(str::to_string),
+        };
+        let group = UniqueImage {
+            id: "g".to_string(),
+            absolute_path: "/docs/a.png".to_string(),
+            duplicate_paths: Vec::new(),
+            checksum: None,
+            exists: true,
+            size: 1,
+            usages: vec![
+                usage("a.png", None),
+                usage("a.png?raw=true", Some("?raw=true")),
+                usage("a.png?v=2#dark", Some("?v=2#dark")),
+            ],
+        };
+
+        let links = |url: &str, preserve| -> Vec<String> {
+            group
+                .link_replacements(url, preserve)
+                .into_iter()
+                .map(|r| r.new_link)
+                .collect()
+        };
+        assert_eq!(links("https://cdn/a.png", false), ["https://cdn/a.png"; 3]);
+        assert_eq!(
+            links("https://cdn/a.png?sig=1", true),
+            [
+                "https://cdn/a.png?sig=1",
+                "https://cdn/a.png?sig=1&raw=true",
+                "https://cdn/a.png?sig=1&v=2#dark",
+            ]
+        );
+    }
 }
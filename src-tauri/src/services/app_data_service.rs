@@ -0,0 +1,733 @@
+use crate::models::{
+    AppDataImportResult, AppDataInclude, AppDataManifest, AppDataPreview, AppSettings,
+    ConfigCollection, FileOperation, UploadHistoryRecord,
+};
+use crate::services::{ConfigService, HistoryService};
+use crate::utils::{AppError, Result};
+use crate::{log_error, log_info, log_warn};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CONFIGS_ENTRY: &str = "configs.json";
+const SETTINGS_ENTRY: &str = "settings.json";
+const UPLOAD_HISTORY_ENTRY: &str = "history/upload_history.json";
+const FILE_OPERATIONS_ENTRY: &str = "history/file_operations.json";
+
+/// Layout of the archive itself
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+/// Schema of the data entries, recorded per entry in the manifest
+const DATA_SCHEMA_VERSION: u32 = 1;
+
+// An export is a few MB at most, anything bigger is not ours
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+const ARCHIVE_COMMENT: &str = "imgtoss app data";
+const SENSITIVE_ARCHIVE_COMMENT: &str =
+    "imgtoss app data - SENSITIVE: contains OSS secret keys, keep it private";
+
+/// Moves configs, settings and history between machines as one zip archive.
+///
+/// The archive holds a `manifest.json` next to one JSON entry per kind of data.
+/// Secret keys are blanked out of the configs unless explicitly included, in
+/// which case the manifest and the zip comment flag the archive as sensitive.
+pub struct AppDataService {
+    config_service: ConfigService,
+    history_service: HistoryService,
+}
+
+impl AppDataService {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_services(
+            ConfigService::new()?,
+            HistoryService::new()?,
+        ))
+    }
+
+    pub fn with_services(config_service: ConfigService, history_service: HistoryService) -> Self {
+        Self {
+            config_service,
+            history_service,
+        }
+    }
+
+    /// Write the selected data to `target` and return the archive's manifest
+    pub async fn export(&self, target: &Path, include: AppDataInclude) -> Result<AppDataManifest> {
+        if !(include.configs || include.settings || include.history) {
+            return Err(AppError::Validation(
+                "Select at least one of configs, settings or history to export".to_string(),
+            ));
+        }
+        // Secrets only ever travel inside the configs
+        let include = AppDataInclude {
+            secrets: include.secrets && include.configs,
+            ..include
+        };
+
+        let mut entries: Vec<(&str, Vec<u8>)> = Vec::new();
+        if include.configs {
+            let mut collection = self.config_service.load_all_configs().await?;
+            if !include.secrets {
+                for item in &mut collection.configs {
+                    item.config.access_key_secret.clear();
+                }
+            }
+            entries.push((CONFIGS_ENTRY, serde_json::to_vec_pretty(&collection)?));
+        }
+        if include.settings {
+            let settings = self.config_service.load_app_settings().await?;
+            entries.push((SETTINGS_ENTRY, serde_json::to_vec_pretty(&settings)?));
+        }
+        if include.history {
+            let records = self.history_service.get_upload_records(None).await?;
+            let operations = self
+                .history_service
+                .get_file_operations(usize::MAX, None)
+                .await?;
+            entries.push((UPLOAD_HISTORY_ENTRY, serde_json::to_vec(&records)?));
+            entries.push((FILE_OPERATIONS_ENTRY, serde_json::to_vec(&operations)?));
+        }
+
+        let manifest = AppDataManifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: chrono::Utc::now(),
+            include,
+            sensitive: include.secrets,
+            schema_versions: entries
+                .iter()
+                .map(|(name, _)| (name.to_string(), DATA_SCHEMA_VERSION))
+                .collect(),
+        };
+        write_archive(target, &manifest, &entries)?;
+
+        log_info!(
+            operation = "export_app_data",
+            target = %target.display(),
+            entry_count = entries.len(),
+            "App data exported"
+        );
+        if manifest.sensitive {
+            log_warn!(
+                operation = "export_app_data",
+                target = %target.display(),
+                "Exported app data contains secret keys"
+            );
+        }
+
+        Ok(manifest)
+    }
+
+    /// Dry run of `import`: validates the archive and lists what it holds
+    pub async fn preview(&self, archive: &Path) -> Result<AppDataPreview> {
+        let contents = ArchiveContents::read(archive)?;
+
+        Ok(AppDataPreview {
+            config_names: contents
+                .configs
+                .iter()
+                .flat_map(|collection| &collection.configs)
+                .map(|item| item.name.clone())
+                .collect(),
+            upload_record_count: contents.upload_records.as_ref().map_or(0, Vec::len),
+            file_operation_count: contents.file_operations.as_ref().map_or(0, Vec::len),
+            has_settings: contents.settings.is_some(),
+            entries: contents.entries,
+            manifest: contents.manifest,
+        })
+    }
+
+    /// Restore an archive into this machine's config and data directories.
+    ///
+    /// With `merge`, configs and history are added to what is already there
+    /// (configs with the same id are replaced by the archive's); without it
+    /// they are replaced. Settings are a single document and are always taken
+    /// from the archive when it has them. Configs exported without their
+    /// secret keep the local secret of the same config, if there is one.
+    /// Nothing is written unless every config and the settings validate.
+    pub async fn import(&self, archive: &Path, merge: bool) -> Result<AppDataImportResult> {
+        let contents = ArchiveContents::read(archive)?;
+
+        // Validate everything before the first write
+        if let Some(settings) = &contents.settings {
+            ConfigService::validate_app_settings(settings)?;
+        }
+        for item in contents.configs.iter().flat_map(|c| &c.configs) {
+            let mut config = item.config.clone();
+            // Archives exported without secrets are valid, the secret is filled in later
+            if config.access_key_secret.is_empty() {
+                config.access_key_secret = "-".to_string();
+            }
+            let errors = ConfigService::config_errors(&config);
+            if !errors.is_empty() {
+                return Err(AppError::Validation(format!(
+                    "Config {} in the archive is invalid: {}",
+                    item.name,
+                    errors.join(", ")
+                )));
+            }
+        }
+
+        let mut result = AppDataImportResult::default();
+
+        // History goes first: it is the step most likely to be refused (record limit),
+        // and nothing else has been written at that point
+        if contents.upload_records.is_some() || contents.file_operations.is_some() {
+            let (records, operations) = self
+                .history_service
+                .import_history(
+                    contents.upload_records.unwrap_or_default(),
+                    contents.file_operations.unwrap_or_default(),
+                    merge,
+                )
+                .await?;
+            result.upload_records_imported = records;
+            result.file_operations_imported = operations;
+        }
+
+        let mut previous_configs = None;
+        if let Some(imported) = contents.configs {
+            result.configs_imported = imported.configs.len();
+            let local = self.config_service.load_all_configs().await?;
+            let (collection, missing_secrets) = merge_configs(local.clone(), imported, merge);
+            self.config_service
+                .save_config_collection(&collection)
+                .await?;
+            result.configs_missing_secrets = missing_secrets;
+            previous_configs = Some(local);
+        }
+
+        if let Some(settings) = contents.settings {
+            if let Err(e) = self.config_service.save_app_settings(&settings).await {
+                // Leave configs and settings as they were rather than half imported
+                if let Some(previous) = previous_configs {
+                    if let Err(restore_error) =
+                        self.config_service.save_config_collection(&previous).await
+                    {
+                        log_error!(
+                            operation = "import_app_data",
+                            error = %restore_error,
+                            "Failed to restore configs after a failed import"
+                        );
+                    }
+                }
+                return Err(e);
+            }
+            result.settings_imported = true;
+        }
+
+        log_info!(
+            operation = "import_app_data",
+            archive = %archive.display(),
+            merge = merge,
+            configs = result.configs_imported,
+            upload_records = result.upload_records_imported,
+            settings = result.settings_imported,
+            "App data imported"
+        );
+
+        Ok(result)
+    }
+}
+
+/// Everything an archive holds, parsed and migrated to the current schema
+struct ArchiveContents {
+    manifest: AppDataManifest,
+    entries: Vec<String>,
+    configs: Option<ConfigCollection>,
+    settings: Option<AppSettings>,
+    upload_records: Option<Vec<UploadHistoryRecord>>,
+    file_operations: Option<Vec<FileOperation>>,
+}
+
+impl ArchiveContents {
+    fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| {
+            AppError::FileSystem(format!("Failed to open {}: {}", path.display(), e))
+        })?;
+        let mut archive = ZipArchive::new(file).map_err(invalid_archive)?;
+
+        let mut entries: Vec<String> = archive.file_names().map(String::from).collect();
+        entries.sort();
+
+        let manifest = read_entry(&mut archive, MANIFEST_ENTRY)?.ok_or_else(|| {
+            AppError::Validation(
+                "Not an imgtoss app data archive: manifest.json is missing".to_string(),
+            )
+        })?;
+        let manifest: AppDataManifest = serde_json::from_slice(&manifest)
+            .map_err(|e| AppError::Validation(format!("Invalid manifest.json: {}", e)))?;
+        if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(AppError::Validation(format!(
+                "Archive was created by imgtoss {} in a newer format ({}), update imgtoss to import it",
+                manifest.app_version, manifest.format_version
+            )));
+        }
+
+        Ok(Self {
+            configs: load_entry(&mut archive, &manifest, CONFIGS_ENTRY)?,
+            settings: load_entry(&mut archive, &manifest, SETTINGS_ENTRY)?,
+            upload_records: load_entry(&mut archive, &manifest, UPLOAD_HISTORY_ENTRY)?,
+            file_operations: load_entry(&mut archive, &manifest, FILE_OPERATIONS_ENTRY)?,
+            entries,
+            manifest,
+        })
+    }
+}
+
+/// Parse an entry listed in the manifest; None when the archive doesn't have it
+fn load_entry<T: DeserializeOwned>(
+    archive: &mut ZipArchive<File>,
+    manifest: &AppDataManifest,
+    name: &str,
+) -> Result<Option<T>> {
+    let Some(&version) = manifest.schema_versions.get(name) else {
+        return Ok(None);
+    };
+    let bytes = read_entry(archive, name)?.ok_or_else(|| {
+        AppError::Validation(format!(
+            "{} is listed in the manifest but missing from the archive",
+            name
+        ))
+    })?;
+
+    let value: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::Validation(format!("Invalid {}: {}", name, e)))?;
+    let value = migrate_entry(name, version, value)?;
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|e| AppError::Validation(format!("Invalid {}: {}", name, e)))
+}
+
+/// Bring an entry written with an older schema up to the current one.
+///
+/// Every entry is still at its first schema and fields added since are filled
+/// in by serde defaults, so there is nothing to rewrite yet. A schema bump adds
+/// its step here.
+fn migrate_entry(name: &str, version: u32, value: serde_json::Value) -> Result<serde_json::Value> {
+    if version > DATA_SCHEMA_VERSION {
+        return Err(AppError::Validation(format!(
+            "{} uses schema {} which this version of imgtoss can't read (max {})",
+            name, version, DATA_SCHEMA_VERSION
+        )));
+    }
+    Ok(value)
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<Vec<u8>>> {
+    let entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(invalid_archive(e)),
+    };
+
+    let mut bytes = Vec::new();
+    entry.take(MAX_ENTRY_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_ENTRY_BYTES {
+        return Err(AppError::Validation(format!(
+            "{} is larger than {} bytes",
+            name, MAX_ENTRY_BYTES
+        )));
+    }
+    Ok(Some(bytes))
+}
+
+fn invalid_archive(error: ZipError) -> AppError {
+    AppError::Validation(format!("Invalid app data archive: {}", error))
+}
+
+// 先写临时文件再重命名，失败时不会留下半个压缩包
+fn write_archive(
+    target: &Path,
+    manifest: &AppDataManifest,
+    entries: &[(&str, Vec<u8>)],
+) -> Result<()> {
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+    let tmp_path = target.with_extension("zip.tmp");
+
+    let written = (|| -> std::result::Result<(), ZipError> {
+        let mut zip = ZipWriter::new(File::create(&tmp_path)?);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.set_comment(if manifest.sensitive {
+            SENSITIVE_ARCHIVE_COMMENT
+        } else {
+            ARCHIVE_COMMENT
+        });
+
+        zip.start_file(MANIFEST_ENTRY, options)?;
+        zip.write_all(&manifest_json)?;
+        for (name, bytes) in entries {
+            zip.start_file(*name, options)?;
+            zip.write_all(bytes)?;
+        }
+        zip.finish()?;
+        Ok(())
+    })()
+    .and_then(|_| std::fs::rename(&tmp_path, target).map_err(ZipError::from));
+
+    written.map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        AppError::FileSystem(format!(
+            "Failed to write archive {}: {}",
+            target.display(),
+            e
+        ))
+    })
+}
+
+/// Combine the local configs with imported ones; returns the collection to
+/// save and the names of configs left without a secret key
+fn merge_configs(
+    local: ConfigCollection,
+    imported: ConfigCollection,
+    merge: bool,
+) -> (ConfigCollection, Vec<String>) {
+    let mut local_secrets: HashMap<String, String> = local
+        .configs
+        .iter()
+        .filter(|item| !item.config.access_key_secret.is_empty())
+        .map(|item| (item.id.clone(), item.config.access_key_secret.clone()))
+        .collect();
+
+    let mut configs = if merge { local.configs } else { Vec::new() };
+    let mut missing_secrets = Vec::new();
+    for mut item in imported.configs {
+        if item.config.access_key_secret.is_empty() {
+            match local_secrets.remove(&item.id) {
+                Some(secret) => item.config.access_key_secret = secret,
+                None => missing_secrets.push(item.name.clone()),
+            }
+        }
+        match configs.iter_mut().find(|existing| existing.id == item.id) {
+            Some(existing) => *existing = item,
+            None => configs.push(item),
+        }
+    }
+
+    // Merging keeps the local choice of active config when it still exists
+    let preferred = if merge {
+        local.active_config_id.or(imported.active_config_id)
+    } else {
+        imported.active_config_id
+    };
+    let active_config_id = preferred
+        .filter(|id| configs.iter().any(|item| &item.id == id))
+        .or_else(|| configs.first().map(|item| item.id.clone()));
+    for item in &mut configs {
+        item.is_active = active_config_id.as_ref() == Some(&item.id);
+    }
+
+    (
+        ConfigCollection {
+            configs,
+            active_config_id,
+        },
+        missing_secrets,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        ConfigItem, FileOperationType, OSSConfig, OSSProvider, ObjectAcl, UploadMode,
+    };
+    use tempfile::TempDir;
+
+    fn config_item(id: &str, secret: &str) -> ConfigItem {
+        ConfigItem {
+            id: id.to_string(),
+            name: format!("{} bucket", id),
+            config: OSSConfig {
+                provider: OSSProvider::Aws,
+                endpoint: "https://s3.amazonaws.com".to_string(),
+                access_key_id: "AKIAEXAMPLE".to_string(),
+                access_key_secret: secret.to_string(),
+                bucket: format!("{}-bucket", id),
+                region: "us-east-1".to_string(),
+                path_template: "images/{filename}".to_string(),
+                cdn_domain: None,
+                compression_enabled: false,
+                compression_quality: 80,
+                output_format: None,
+                max_dimension: None,
+                strip_metadata: false,
+                convert_to_srgb: false,
+                object_acl: ObjectAcl::Default,
+                max_upload_size_kb: None,
+                size_variants: Vec::new(),
+            },
+            is_active: false,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn service(root: &Path) -> AppDataService {
+        AppDataService::with_services(
+            ConfigService::new_with_dir(root.join("config")).unwrap(),
+            HistoryService::new_with_dir(root.join("data")).unwrap(),
+        )
+    }
+
+    async fn seed(service: &AppDataService, config_id: &str) {
+        service
+            .config_service
+            .save_config_collection(&ConfigCollection {
+                configs: vec![config_item(config_id, "super-secret")],
+                active_config_id: Some(config_id.to_string()),
+            })
+            .await
+            .unwrap();
+        service
+            .history_service
+            .add_upload_record(UploadHistoryRecord {
+                id: format!("record-{}", config_id),
+                timestamp: chrono::Utc::now(),
+                image_name: "a.png".to_string(),
+                uploaded_url: "https://cdn.example.com/a.png".to_string(),
+                upload_mode: UploadMode::ImageUpload,
+                source_file: None,
+                file_size: 42,
+                checksum: "sha256:abc".to_string(),
+                variants: Vec::new(),
+                config_id: None,
+                bucket: None,
+                original_path: None,
+                success: true,
+                error_message: None,
+                request_id: None,
+                original_archived: false,
+                object_key: None,
+            })
+            .await
+            .unwrap();
+        service
+            .history_service
+            .record_file_operation(FileOperationType::Replace, "/docs/a.md", None)
+            .await;
+    }
+
+    const EVERYTHING: AppDataInclude = AppDataInclude {
+        configs: true,
+        secrets: false,
+        history: true,
+        settings: true,
+    };
+
+    #[tokio::test]
+    async fn test_export_without_secrets_then_import_on_new_machine() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_machine = service(&temp_dir.path().join("old"));
+        seed(&old_machine, "main").await;
+        let settings = AppSettings {
+            backup_keep_per_file: 3,
+            ..Default::default()
+        };
+        old_machine
+            .config_service
+            .save_app_settings(&settings)
+            .await
+            .unwrap();
+
+        let archive = temp_dir.path().join("imgtoss.zip");
+        let manifest = old_machine.export(&archive, EVERYTHING).await.unwrap();
+        assert!(!manifest.sensitive);
+        assert_eq!(manifest.schema_versions.len(), 4);
+
+        let new_machine = service(&temp_dir.path().join("new"));
+        let preview = new_machine.preview(&archive).await.unwrap();
+        assert_eq!(preview.config_names, vec!["main bucket"]);
+        assert_eq!(preview.upload_record_count, 1);
+        assert_eq!(preview.file_operation_count, 1);
+        assert!(preview.has_settings);
+        assert!(preview.entries.contains(&MANIFEST_ENTRY.to_string()));
+        // Preview is a dry run
+        assert!(new_machine
+            .config_service
+            .load_all_configs()
+            .await
+            .unwrap()
+            .configs
+            .is_empty());
+
+        let result = new_machine.import(&archive, false).await.unwrap();
+        assert_eq!(result.configs_imported, 1);
+        assert_eq!(result.configs_missing_secrets, vec!["main bucket"]);
+        assert_eq!(result.upload_records_imported, 1);
+        assert!(result.settings_imported);
+
+        let configs = new_machine.config_service.load_all_configs().await.unwrap();
+        assert_eq!(configs.active_config_id.as_deref(), Some("main"));
+        assert!(configs.configs[0].config.access_key_secret.is_empty());
+        assert_eq!(
+            new_machine
+                .config_service
+                .load_app_settings()
+                .await
+                .unwrap()
+                .backup_keep_per_file,
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_import_keeps_local_configs_and_secrets() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = service(&temp_dir.path().join("source"));
+        seed(&source, "shared").await;
+        let target = service(&temp_dir.path().join("target"));
+        seed(&target, "local").await;
+        // The target already knows the shared config, with its own secret
+        target
+            .config_service
+            .save_config_collection(&ConfigCollection {
+                configs: vec![
+                    config_item("local", "local-secret"),
+                    config_item("shared", "target-secret"),
+                ],
+                active_config_id: Some("local".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let archive = temp_dir.path().join("imgtoss.zip");
+        source.export(&archive, EVERYTHING).await.unwrap();
+        let result = target.import(&archive, true).await.unwrap();
+        assert!(result.configs_missing_secrets.is_empty());
+
+        let configs = target.config_service.load_all_configs().await.unwrap();
+        assert_eq!(configs.configs.len(), 2);
+        assert_eq!(configs.active_config_id.as_deref(), Some("local"));
+        let shared = configs.configs.iter().find(|c| c.id == "shared").unwrap();
+        assert_eq!(shared.config.access_key_secret, "target-secret");
+        assert_eq!(
+            target
+                .history_service
+                .get_upload_records(None)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_secrets_are_only_exported_on_request() {
+        let temp_dir = TempDir::new().unwrap();
+        let machine = service(temp_dir.path());
+        seed(&machine, "main").await;
+
+        let archive = temp_dir.path().join("with-secrets.zip");
+        let manifest = machine
+            .export(
+                &archive,
+                AppDataInclude {
+                    configs: true,
+                    secrets: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(manifest.sensitive);
+        assert_eq!(manifest.schema_versions.len(), 1);
+
+        let zip = ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        assert_eq!(zip.comment(), SENSITIVE_ARCHIVE_COMMENT.as_bytes());
+
+        let preview = machine.preview(&archive).await.unwrap();
+        assert!(preview.manifest.sensitive);
+        assert!(!preview.has_settings);
+
+        // Secrets without configs have nothing to travel in
+        let manifest = machine
+            .export(
+                &temp_dir.path().join("settings.zip"),
+                AppDataInclude {
+                    settings: true,
+                    secrets: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(!manifest.sensitive);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_in_archive_imports_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = service(&temp_dir.path().join("source"));
+        seed(&source, "broken").await;
+        let mut broken = config_item("broken", "super-secret");
+        broken.config.bucket = String::new();
+        source
+            .config_service
+            .save_config_collection(&ConfigCollection {
+                configs: vec![broken],
+                active_config_id: Some("broken".to_string()),
+            })
+            .await
+            .unwrap();
+        let archive = temp_dir.path().join("broken.zip");
+        source.export(&archive, EVERYTHING).await.unwrap();
+
+        let target = service(&temp_dir.path().join("target"));
+        let error = target.import(&archive, false).await.unwrap_err();
+        assert!(error.to_string().contains("Bucket name is required"));
+        assert!(target
+            .config_service
+            .load_all_configs()
+            .await
+            .unwrap()
+            .configs
+            .is_empty());
+        assert!(target
+            .history_service
+            .get_upload_records(None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_foreign_and_newer_archives() {
+        let temp_dir = TempDir::new().unwrap();
+        let machine = service(temp_dir.path());
+
+        let not_zip = temp_dir.path().join("notes.zip");
+        std::fs::write(&not_zip, "hello").unwrap();
+        assert!(machine.preview(&not_zip).await.is_err());
+
+        let newer = temp_dir.path().join("newer.zip");
+        let mut manifest = machine
+            .export(
+                &newer,
+                AppDataInclude {
+                    settings: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        manifest.format_version = ARCHIVE_FORMAT_VERSION + 1;
+        write_archive(&newer, &manifest, &[]).unwrap();
+        let error = machine.import(&newer, false).await.unwrap_err().to_string();
+        assert!(error.contains("newer format"));
+
+        assert!(machine
+            .export(
+                &temp_dir.path().join("empty.zip"),
+                AppDataInclude::default()
+            )
+            .await
+            .is_err());
+    }
+}
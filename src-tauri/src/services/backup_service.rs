@@ -0,0 +1,566 @@
+use crate::models::{
+    AppSettings, BackupCleanupResult, BackupInfo, BackupStorageUsage, FileOperationType, StoreKind,
+    StoreRecoveryResult,
+};
+use crate::services::HistoryService;
+use crate::utils::store_lock::lock_store;
+use crate::utils::store_recovery::{
+    latest_corrupt_file, salvage_array, write_atomic, CORRUPT_STORES,
+};
+use crate::utils::{app_paths, AppError, Result};
+use crate::{log_debug, log_info, log_warn};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+const BACKUP_INDEX_FILE: &str = "index.json";
+
+/// The last backup of a file touched this recently is never cleaned up
+const RECENT_CHANGE_GUARD_HOURS: i64 = 24;
+
+/// Which backups cleanup keeps
+#[derive(Debug, Clone, Copy)]
+pub struct BackupRetention {
+    /// Most recent backups kept per original file, 0 for no limit
+    pub keep_per_file: usize,
+    /// Backups older than this many days are removed, 0 for no limit
+    pub max_age_days: u32,
+}
+
+impl BackupRetention {
+    pub fn from_settings(settings: &AppSettings) -> Self {
+        Self {
+            keep_per_file: settings.backup_keep_per_file,
+            max_age_days: settings.backup_retention_days,
+        }
+    }
+}
+
+/// Copies of markdown files taken before they are rewritten.
+///
+/// Backups live in `<data dir>/imgtoss/backups`, each file stored as
+/// `<backup id>_<file name>` and described by an entry in `index.json`.
+pub struct BackupService {
+    backup_dir: PathBuf,
+    // Where backups and restores are recorded, if anywhere
+    operation_log: Option<HistoryService>,
+}
+
+impl BackupService {
+    pub fn new() -> Result<Self> {
+        Self::new_with_dir(app_paths::data_dir()?.join("backups"))
+    }
+
+    pub fn new_with_dir(backup_dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&backup_dir).map_err(|e| {
+            AppError::FileSystem(format!("Failed to create backup directory: {}", e))
+        })?;
+
+        Ok(Self {
+            backup_dir,
+            operation_log: None,
+        })
+    }
+
+    /// Record every backup and restore in the file operations log
+    pub fn with_operation_log(mut self, operation_log: Option<HistoryService>) -> Self {
+        self.operation_log = operation_log;
+        self
+    }
+
+    /// Copy `file_path` into the backup directory and record it in the index
+    pub async fn create_backup(&self, file_path: &str) -> Result<BackupInfo> {
+        let result = self.write_backup(file_path).await;
+        self.record(FileOperationType::Backup, file_path, &result)
+            .await;
+        result
+    }
+
+    async fn write_backup(&self, file_path: &str) -> Result<BackupInfo> {
+        let content = fs::read(file_path).await.map_err(|e| {
+            AppError::FileSystem(format!("Failed to read {} for backup: {}", file_path, e))
+        })?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let file_name = Path::new(file_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("file.md");
+        let backup_path = self.backup_dir.join(format!("{}_{}", id, file_name));
+
+        fs::write(&backup_path, &content).await.map_err(|e| {
+            AppError::FileSystem(format!(
+                "Failed to write backup {}: {}",
+                backup_path.display(),
+                e
+            ))
+        })?;
+
+        let info = BackupInfo {
+            id,
+            original_path: file_path.to_string(),
+            backup_path: backup_path.to_string_lossy().to_string(),
+            timestamp: chrono::Utc::now(),
+            size: content.len() as u64,
+            checksum: format!("{:x}", Sha256::digest(&content)),
+        };
+
+        let _lock = lock_store(&self.index_path()).await?;
+        let mut index = self.load_index().await?;
+        index.push(info.clone());
+        self.save_index(&index).await?;
+
+        log_info!(
+            operation = "create_backup",
+            file_path = %file_path,
+            backup_id = %info.id,
+            size = info.size,
+            "Backup created"
+        );
+
+        Ok(info)
+    }
+
+    /// Write a backup's content back over its original file
+    pub async fn restore_backup(&self, info: &BackupInfo) -> Result<()> {
+        let result = fs::copy(&info.backup_path, &info.original_path)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                AppError::FileSystem(format!(
+                    "Failed to restore {} from backup {}: {}",
+                    info.original_path, info.id, e
+                ))
+            });
+        self.record(FileOperationType::Restore, &info.original_path, &result)
+            .await;
+        result?;
+
+        log_info!(
+            operation = "restore_backup",
+            file_path = %info.original_path,
+            backup_id = %info.id,
+            "Backup restored"
+        );
+
+        Ok(())
+    }
+
+    /// Delete backups outside the retention policy and drop them from the index.
+    ///
+    /// Per original file, backups beyond the `keep_per_file` most recent and
+    /// those older than `max_age_days` go. The newest backup of a file that was
+    /// changed in the last 24 hours is always kept, even if it is the only one.
+    pub async fn cleanup(&self, retention: &BackupRetention) -> Result<BackupCleanupResult> {
+        let now = chrono::Utc::now();
+        let _lock = lock_store(&self.index_path()).await?;
+        let index = self.load_index().await?;
+        let guard_since = now - chrono::Duration::hours(RECENT_CHANGE_GUARD_HOURS);
+        let expire_before = (retention.max_age_days > 0)
+            .then(|| now - chrono::Duration::days(i64::from(retention.max_age_days)));
+
+        // Newest first within each original file
+        let mut by_file: HashMap<&str, Vec<&BackupInfo>> = HashMap::new();
+        for info in &index {
+            by_file.entry(&info.original_path).or_default().push(info);
+        }
+
+        let mut removed_ids = HashSet::new();
+        for (original_path, mut backups) in by_file {
+            backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+            let newest = backups[0];
+            let recently_changed = newest.timestamp > guard_since
+                || std::fs::metadata(original_path)
+                    .and_then(|m| m.modified())
+                    .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified) > guard_since)
+                    .unwrap_or(false);
+
+            for (position, info) in backups.iter().enumerate() {
+                let over_count = retention.keep_per_file > 0 && position >= retention.keep_per_file;
+                let expired = expire_before.is_some_and(|before| info.timestamp < before);
+                let guarded = position == 0 && recently_changed;
+                if (over_count || expired) && !guarded {
+                    removed_ids.insert(info.id.as_str());
+                }
+            }
+        }
+
+        if removed_ids.is_empty() {
+            return Ok(BackupCleanupResult::default());
+        }
+
+        let mut result = BackupCleanupResult::default();
+        let mut kept = Vec::with_capacity(index.len() - removed_ids.len());
+        for info in &index {
+            if !removed_ids.contains(info.id.as_str()) {
+                kept.push(info.clone());
+                continue;
+            }
+
+            match fs::remove_file(&info.backup_path).await {
+                Ok(()) => {
+                    result.removed_files += 1;
+                    result.removed_bytes += info.size;
+                }
+                // Already gone, only the index entry is left to drop
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    log_warn!(
+                        operation = "cleanup_backups",
+                        backup_path = %info.backup_path,
+                        error = %e,
+                        "Failed to delete backup, keeping it"
+                    );
+                    kept.push(info.clone());
+                }
+            }
+        }
+        self.save_index(&kept).await?;
+
+        log_info!(
+            operation = "cleanup_backups",
+            removed_files = result.removed_files,
+            removed_bytes = result.removed_bytes,
+            "Backups cleaned up"
+        );
+
+        Ok(result)
+    }
+
+    pub async fn storage_usage(&self) -> Result<BackupStorageUsage> {
+        let index = self.load_index().await?;
+        let files: HashSet<&str> = index
+            .iter()
+            .map(|info| info.original_path.as_str())
+            .collect();
+
+        Ok(BackupStorageUsage {
+            backup_count: index.len(),
+            file_count: files.len(),
+            total_bytes: index.iter().map(|info| info.size).sum(),
+            oldest_backup: index.iter().map(|info| info.timestamp).min(),
+        })
+    }
+
+    // 记录到文件操作日志（如果配置了）
+    async fn record<T>(
+        &self,
+        operation_type: FileOperationType,
+        file_path: &str,
+        result: &Result<T>,
+    ) {
+        if let Some(log) = &self.operation_log {
+            let error = result.as_ref().err().map(|e| e.to_string());
+            log.record_file_operation(operation_type, file_path, error)
+                .await;
+        }
+    }
+
+    /// Salvage the complete entries of the newest corrupt copy of the index
+    /// and merge them into the live one. Entries already present (same id)
+    /// and those whose backup file is gone are skipped.
+    pub async fn recover_corrupt_index(&self) -> Result<StoreRecoveryResult> {
+        let index_path = self.index_path();
+        let source = latest_corrupt_file(&index_path).ok_or_else(|| {
+            AppError::Validation(format!(
+                "No corrupt copy of {} to recover",
+                index_path.display()
+            ))
+        })?;
+        let content = String::from_utf8_lossy(&fs::read(&source).await?).to_string();
+        let values = salvage_array(&content, None);
+        let total = values.len();
+        let salvaged: Vec<BackupInfo> = values
+            .into_iter()
+            .filter_map(|value| serde_json::from_value(value).ok())
+            .collect();
+        let unreadable = total - salvaged.len();
+
+        let _lock = lock_store(&index_path).await?;
+        let mut index = self.load_index().await?;
+        let known: HashSet<String> = index.iter().map(|info| info.id.clone()).collect();
+        let before = index.len();
+        index.extend(
+            salvaged
+                .into_iter()
+                .filter(|info| !known.contains(&info.id) && Path::new(&info.backup_path).exists()),
+        );
+        let recovered = index.len() - before;
+        index.sort_by_key(|info| info.timestamp);
+        self.save_index(&index).await?;
+
+        CORRUPT_STORES.resolve(StoreKind::BackupIndex);
+        log_info!(
+            operation = "recover_corrupt_store",
+            kind = ?StoreKind::BackupIndex,
+            source = %source.display(),
+            recovered = recovered,
+            "Recovered backups from a corrupt index"
+        );
+
+        Ok(StoreRecoveryResult {
+            kind: StoreKind::BackupIndex,
+            source: source.to_string_lossy().to_string(),
+            recovered,
+            unreadable,
+        })
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.backup_dir.join(BACKUP_INDEX_FILE)
+    }
+
+    // 读取备份索引，文件不存在时视为空；解析失败时把文件移到一边，从空索引开始
+    async fn load_index(&self) -> Result<Vec<BackupInfo>> {
+        let index_path = self.index_path();
+        if !index_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&index_path).await?;
+        if content.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        match serde_json::from_str(&content) {
+            Ok(index) => Ok(index),
+            Err(e) => {
+                CORRUPT_STORES.quarantine(StoreKind::BackupIndex, &index_path, &e.to_string());
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    // Callers hold lock_store(index_path) from load_index through here
+    async fn save_index(&self, index: &[BackupInfo]) -> Result<()> {
+        let content = serde_json::to_string_pretty(index)?;
+        write_atomic(&self.index_path(), &content)?;
+
+        log_debug!(
+            operation = "save_backup_index",
+            backup_count = index.len(),
+            "Backup index saved"
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_create_backup_copies_file_and_indexes_it() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap();
+
+        let source = temp_dir.path().join("post.md");
+        std::fs::write(&source, "![a](./a.png)\n").unwrap();
+
+        let info = service
+            .create_backup(&source.to_string_lossy())
+            .await
+            .unwrap();
+        assert_eq!(info.size, 14);
+        assert!(info.backup_path.ends_with("_post.md"));
+        assert_eq!(
+            std::fs::read_to_string(&info.backup_path).unwrap(),
+            "![a](./a.png)\n"
+        );
+
+        let second = service
+            .create_backup(&source.to_string_lossy())
+            .await
+            .unwrap();
+        assert_ne!(info.id, second.id);
+        assert_eq!(info.checksum, second.checksum);
+
+        let index = service.load_index().await.unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index[0].id, info.id);
+    }
+
+    #[tokio::test]
+    async fn test_create_backup_missing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = BackupService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let missing = temp_dir.path().join("missing.md");
+
+        let result = service.create_backup(&missing.to_string_lossy()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restore_backup_overwrites_original() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap();
+        let source = temp_dir.path().join("post.md");
+        std::fs::write(&source, "before").unwrap();
+
+        let info = service
+            .create_backup(&source.to_string_lossy())
+            .await
+            .unwrap();
+        std::fs::write(&source, "after").unwrap();
+
+        service.restore_backup(&info).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "before");
+    }
+
+    // Index entries with a given age; the files only exist so removal can be counted
+    async fn seed_backups(
+        service: &BackupService,
+        dir: &Path,
+        original: &Path,
+        ages_in_days: &[i64],
+    ) -> Vec<BackupInfo> {
+        let now = chrono::Utc::now();
+        let mut index = service.load_index().await.unwrap();
+        let mut seeded = Vec::new();
+        for age in ages_in_days {
+            let id = uuid::Uuid::new_v4().to_string();
+            let backup_path = dir.join(format!("{}_seed.md", id));
+            std::fs::write(&backup_path, "0123456789").unwrap();
+            let info = BackupInfo {
+                id,
+                original_path: original.to_string_lossy().to_string(),
+                backup_path: backup_path.to_string_lossy().to_string(),
+                timestamp: now - chrono::Duration::days(*age),
+                size: 10,
+                checksum: String::new(),
+            };
+            index.push(info.clone());
+            seeded.push(info);
+        }
+        service.save_index(&index).await.unwrap();
+        seeded
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_keeps_most_recent_per_file_and_drops_expired() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        let service = BackupService::new_with_dir(backup_dir.clone()).unwrap();
+        let post = temp_dir.path().join("post.md");
+        let backups = seed_backups(&service, &backup_dir, &post, &[2, 3, 4, 40]).await;
+
+        let usage = service.storage_usage().await.unwrap();
+        assert_eq!(usage.backup_count, 4);
+        assert_eq!(usage.file_count, 1);
+        assert_eq!(usage.total_bytes, 40);
+
+        let retention = BackupRetention {
+            keep_per_file: 2,
+            max_age_days: 30,
+        };
+        let result = service.cleanup(&retention).await.unwrap();
+        assert_eq!(
+            result,
+            BackupCleanupResult {
+                removed_files: 2,
+                removed_bytes: 20
+            }
+        );
+
+        let index = service.load_index().await.unwrap();
+        let kept: Vec<&str> = index.iter().map(|info| info.id.as_str()).collect();
+        assert_eq!(kept, vec![backups[0].id.as_str(), backups[1].id.as_str()]);
+        assert!(!Path::new(&backups[3].backup_path).exists());
+
+        // Nothing left to do on a second run
+        assert_eq!(
+            service.cleanup(&retention).await.unwrap(),
+            BackupCleanupResult::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_never_removes_only_backup_of_recent_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        let service = BackupService::new_with_dir(backup_dir.clone()).unwrap();
+        let retention = BackupRetention {
+            keep_per_file: 0,
+            max_age_days: 1,
+        };
+
+        // The original was just rewritten, its only backup is old but stays
+        let post = temp_dir.path().join("post.md");
+        std::fs::write(&post, "rewritten").unwrap();
+        let recent = seed_backups(&service, &backup_dir, &post, &[10]).await;
+
+        // A file nobody touched loses its expired backup
+        let stale = temp_dir.path().join("missing.md");
+        seed_backups(&service, &backup_dir, &stale, &[10]).await;
+
+        let result = service.cleanup(&retention).await.unwrap();
+        assert_eq!(result.removed_files, 1);
+        let index = service.load_index().await.unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].id, recent[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_backups_all_reach_the_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = std::sync::Arc::new(
+            BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap(),
+        );
+        let source = temp_dir.path().join("post.md");
+        std::fs::write(&source, "# post\n").unwrap();
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let service = service.clone();
+                let source = source.to_string_lossy().to_string();
+                tokio::spawn(async move { service.create_backup(&source).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(service.load_index().await.unwrap().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_index_is_moved_aside_and_recovered() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap();
+        let source = temp_dir.path().join("post.md");
+        std::fs::write(&source, "# post\n").unwrap();
+        let first = service
+            .create_backup(&source.to_string_lossy())
+            .await
+            .unwrap();
+
+        // Cut off in the middle of a second entry
+        let index_path = service.index_path();
+        let content = std::fs::read_to_string(&index_path).unwrap();
+        let entry = content.trim_end().trim_end_matches(']').trim_end();
+        std::fs::write(&index_path, format!("{},\n  {{\"id\": \"cut", entry)).unwrap();
+
+        assert!(service.load_index().await.unwrap().is_empty());
+        assert!(!index_path.exists());
+        assert!(latest_corrupt_file(&index_path).is_some());
+
+        let second = service
+            .create_backup(&source.to_string_lossy())
+            .await
+            .unwrap();
+        let result = service.recover_corrupt_index().await.unwrap();
+        assert_eq!((result.recovered, result.unreadable), (1, 0));
+        let ids: Vec<String> = service
+            .load_index()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|info| info.id)
+            .collect();
+        assert_eq!(ids, [first.id, second.id]);
+    }
+}
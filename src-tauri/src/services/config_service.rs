@@ -1,6 +1,16 @@
-use crate::models::{ConfigCollection, ConfigItem, ConfigValidation, OSSConfig, OSSConnectionTest};
-use crate::services::oss_service::OSSService;
-use crate::utils::{AppError, Result};
+use crate::models::{
+    AppSettings, AppSettingsPatch, ConfigCollection, ConfigItem, ConfigValidation, ConfigWarning,
+    ConfigWarningCode, ConnectionTestStep, ImageProcessingOptions, OSSConfig, OSSConnectionTest,
+    OSSProvider, ObjectAcl, StoreKind, StoreRecoveryResult, UploadMode,
+};
+use crate::services::key_template::validate_path_template;
+use crate::services::oss_registry::OSS_SERVICES;
+use crate::utils::store_lock::lock_store;
+use crate::utils::store_recovery::{
+    latest_corrupt_file, salvage_array, salvage_object, write_atomic, CORRUPT_STORES,
+};
+use crate::utils::{app_paths, AppError, Result};
+use crate::{log_debug, log_info, log_warn};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -10,9 +20,9 @@ use std::sync::Mutex;
 use std::time::SystemTime;
 
 const CACHE_FILE_NAME: &str = "connection_cache.json";
-const CONFIG_DIR_NAME: &str = "imgtoss";
 const CACHE_EXPIRY_SECONDS: u64 = 300; // 5 minutes
 const CONFIGS_FILE_NAME: &str = "configs.json"; // New: multi-config file
+const SETTINGS_FILE_NAME: &str = "settings.json"; // App-wide settings, separate from OSS configs
 #[allow(dead_code)]
 const LEGACY_CONFIG_FILE_NAME: &str = "config.json"; // Legacy single config file
 #[allow(dead_code)]
@@ -39,13 +49,82 @@ impl CachedTestResult {
 static CONNECTION_TEST_CACHE: Lazy<Mutex<HashMap<String, CachedTestResult>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+// Cached app settings keyed by settings file, refreshed on every save so
+// services don't re-read the file for each operation
+static APP_SETTINGS_CACHE: Lazy<Mutex<HashMap<PathBuf, AppSettings>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Serializes read-modify-write of settings so concurrent patches don't race
+static APP_SETTINGS_WRITE_LOCK: Lazy<tokio::sync::Mutex<()>> =
+    Lazy::new(|| tokio::sync::Mutex::new(()));
+
+const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+// Regions accepted for AWS configs using the default amazonaws.com endpoint
+const AWS_REGIONS: [&str; 35] = [
+    "us-east-1",
+    "us-east-2",
+    "us-west-1",
+    "us-west-2",
+    "us-gov-east-1",
+    "us-gov-west-1",
+    "af-south-1",
+    "ap-east-1",
+    "ap-south-1",
+    "ap-south-2",
+    "ap-southeast-1",
+    "ap-southeast-2",
+    "ap-southeast-3",
+    "ap-southeast-4",
+    "ap-southeast-5",
+    "ap-northeast-1",
+    "ap-northeast-2",
+    "ap-northeast-3",
+    "ca-central-1",
+    "ca-west-1",
+    "cn-north-1",
+    "cn-northwest-1",
+    "eu-central-1",
+    "eu-central-2",
+    "eu-west-1",
+    "eu-west-2",
+    "eu-west-3",
+    "eu-south-1",
+    "eu-south-2",
+    "eu-north-1",
+    "il-central-1",
+    "me-south-1",
+    "me-central-1",
+    "mx-central-1",
+    "sa-east-1",
+];
+
+static ALIYUN_ENDPOINT: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"^oss-[a-z0-9-]+\.aliyuncs\.com$").unwrap());
+static TENCENT_BUCKET: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"^.+-\d+$").unwrap());
+
+// Upper bounds for the user-configurable scan/upload limits
+const MAX_SCAN_FILES_LIMIT: usize = 20_000;
+const MAX_UPLOAD_IMAGES_LIMIT: usize = 5_000;
+const MAX_IMAGE_SIZE_MB_LIMIT: u64 = 10_240;
+const MAX_MARKDOWN_SIZE_MB_LIMIT: u64 = 1024;
+// Previews for hi-dpi screens, larger is better served by the image itself
+const MAX_THUMBNAIL_SIZE_LIMIT: u32 = 4096;
+const MAX_THUMBNAIL_CONCURRENCY: usize = 64;
+const MAX_BACKUPS_PER_FILE: usize = 1_000;
+const MAX_MAINTENANCE_INTERVAL_HOURS: u32 = 24 * 7;
+
+// Responsive size variants per image
+const MAX_SIZE_VARIANTS: usize = 8;
+const MAX_VARIANT_WIDTH: u32 = 10_000;
+
 pub struct ConfigService {
     config_dir: PathBuf,
 }
 
 impl ConfigService {
     pub fn new() -> Result<Self> {
-        let config_dir = Self::get_config_dir()?;
+        let config_dir = app_paths::config_dir()?;
 
         // Ensure config directory exists
         if !config_dir.exists() {
@@ -57,7 +136,6 @@ impl ConfigService {
         Ok(Self { config_dir })
     }
 
-    #[allow(dead_code)]
     pub fn new_with_dir(config_dir: PathBuf) -> Result<Self> {
         // Ensure config directory exists
         if !config_dir.exists() {
@@ -85,8 +163,9 @@ impl ConfigService {
         let config_json = serde_json::to_string_pretty(config)
             .map_err(|e| AppError::Configuration(format!("Failed to serialize config: {}", e)))?;
 
-        std::fs::write(&config_path, config_json)
+        write_atomic(&config_path, &config_json)
             .map_err(|e| AppError::Configuration(format!("Failed to save config: {}", e)))?;
+        OSS_SERVICES.clear();
 
         Ok(())
     }
@@ -159,20 +238,29 @@ impl ConfigService {
                     Ok(mut cache) => {
                         // Remove expired entries
                         cache.retain(|_, cached| !cached.is_expired());
-                        println!(
-                            "📂 Loaded {} cached connection results from file",
-                            cache.len()
+                        log_debug!(
+                            operation = "load_connection_cache",
+                            entries = cache.len(),
+                            "Loaded cached connection test results"
                         );
                         cache
                     }
                     Err(e) => {
-                        println!("⚠️ Failed to parse cache file: {}, starting fresh", e);
+                        log_warn!(
+                            operation = "load_connection_cache",
+                            error = %e,
+                            "Failed to parse connection cache, starting fresh"
+                        );
                         HashMap::new()
                     }
                 }
             }
             Err(e) => {
-                println!("⚠️ Failed to read cache file: {}, starting fresh", e);
+                log_warn!(
+                    operation = "load_connection_cache",
+                    error = %e,
+                    "Failed to read connection cache, starting fresh"
+                );
                 HashMap::new()
             }
         }
@@ -185,16 +273,25 @@ impl ConfigService {
         match serde_json::to_string_pretty(cache) {
             Ok(content) => {
                 if let Err(e) = std::fs::write(&cache_path, content) {
-                    println!("⚠️ Failed to save cache to file: {}", e);
+                    log_warn!(
+                        operation = "save_connection_cache",
+                        error = %e,
+                        "Failed to save connection cache"
+                    );
                 } else {
-                    println!(
-                        "💾 Saved {} connection test results to cache file",
-                        cache.len()
+                    log_debug!(
+                        operation = "save_connection_cache",
+                        entries = cache.len(),
+                        "Saved connection test results"
                     );
                 }
             }
             Err(e) => {
-                println!("⚠️ Failed to serialize cache: {}", e);
+                log_warn!(
+                    operation = "save_connection_cache",
+                    error = %e,
+                    "Failed to serialize connection cache"
+                );
             }
         }
     }
@@ -222,9 +319,10 @@ impl ConfigService {
         if cached_result.is_expired() {
             None
         } else {
-            println!(
-                "✅ Using cached connection test result for config hash: {}...",
-                &config_hash[..8]
+            log_debug!(
+                operation = "connection_cache_hit",
+                config_hash = %&config_hash[..8],
+                "Using cached connection test result"
             );
             Some(cached_result.result.clone())
         }
@@ -253,7 +351,11 @@ impl ConfigService {
         let config_hash = self.calculate_config_hash(config);
         if let Ok(mut cache) = CONNECTION_TEST_CACHE.lock() {
             cache.remove(&config_hash);
-            println!("🗑️ Cleared cache for config hash: {}...", &config_hash[..8]);
+            log_debug!(
+                operation = "clear_connection_cache",
+                config_hash = %&config_hash[..8],
+                "Cleared cached connection test result"
+            );
 
             // Save to file after clearing cache
             self.save_cache_to_file(&cache);
@@ -265,7 +367,11 @@ impl ConfigService {
         if let Ok(mut cache) = CONNECTION_TEST_CACHE.lock() {
             let count = cache.len();
             cache.clear();
-            println!("🗑️ Cleared all {} cached connection results", count);
+            log_debug!(
+                operation = "clear_connection_cache",
+                entries = count,
+                "Cleared all cached connection test results"
+            );
 
             // Save to file after clearing all cache
             self.save_cache_to_file(&cache);
@@ -280,13 +386,21 @@ impl ConfigService {
         let config_hash = self.calculate_config_hash(config);
         self.get_cached_test_result(&config_hash)
     }
+    /// Store the result of a test run outside `validate_config`, so later
+    /// validations and `get_cached_connection_status` see it
+    pub fn cache_connection_test(&self, config: &OSSConfig, result: &OSSConnectionTest) {
+        let config_hash = self.calculate_config_hash(config);
+        self.cache_test_result(config_hash, result.clone());
+    }
+
     /// Perform actual connection test using OSSService
     async fn perform_connection_test(&self, config: &OSSConfig) -> Result<OSSConnectionTest> {
-        println!(
-            "🔄 Performing actual connection test for provider: {:?}",
-            config.provider
+        log_debug!(
+            operation = "connection_test",
+            provider = ?config.provider,
+            "Running connection test"
         );
-        let oss_service = OSSService::new(config.clone())?;
+        let oss_service = OSS_SERVICES.get(config)?;
         oss_service.test_connection().await
     }
 
@@ -295,7 +409,17 @@ impl ConfigService {
         let config_hash = self.calculate_config_hash(config);
 
         // Check cache first
-        if let Some(cached_result) = self.get_cached_test_result(&config_hash) {
+        if let Some(mut cached_result) = self.get_cached_test_result(&config_hash) {
+            cached_result.diagnostics.insert(
+                0,
+                ConnectionTestStep::passed(
+                    "cache",
+                    Some(format!(
+                        "Result of a test run in the last {} minutes",
+                        CACHE_EXPIRY_SECONDS / 60
+                    )),
+                ),
+            );
             return Ok(cached_result);
         }
 
@@ -309,12 +433,90 @@ impl ConfigService {
     }
 
     pub async fn validate_config(&self, config: &OSSConfig) -> Result<ConfigValidation> {
+        // Basic field validation (no network operations)
+        let errors = Self::config_errors(config);
+
+        // Smart connection test with caching (only if basic validation passes)
+        let connection_test = if errors.is_empty() {
+            log_debug!(
+                operation = "validate_config",
+                provider = ?config.provider,
+                "Fields valid, running the connection test"
+            );
+            let mut test = self.smart_connection_test(config).await?;
+            test.diagnostics
+                .insert(0, ConnectionTestStep::passed("config", None));
+            Some(test)
+        } else {
+            log_debug!(
+                operation = "validate_config",
+                errors = errors.len(),
+                "Fields invalid, skipping the connection test"
+            );
+            None
+        };
+
+        let mut warnings = Self::config_warnings(config);
+        warnings.extend(self.object_size_warning(config).await);
+
+        let sample_key = (!config.path_template.trim().is_empty())
+            .then(|| validate_path_template(&config.path_template, &config.provider).sample_key);
+
+        Ok(ConfigValidation {
+            valid: errors.is_empty() && connection_test.as_ref().is_some_and(|t| t.success),
+            errors,
+            warnings,
+            connection_test,
+            sample_key,
+        })
+    }
+
+    /// Set when the image size limit from the app settings lets through files
+    /// the provider can't take in one upload and nothing shrinks them first
+    async fn object_size_warning(&self, config: &OSSConfig) -> Option<ConfigWarning> {
+        if !ImageProcessingOptions::from_config(config).keeps_size() {
+            return None;
+        }
+        let max_image_bytes = self.app_settings().await.ok()?.max_image_bytes();
+        let limit = OSS_SERVICES.get(config).ok()?.max_object_size();
+        (max_image_bytes > limit).then(|| {
+            ConfigWarning::new(
+                ConfigWarningCode::ObjectSizeLimit,
+                format!(
+                    "Images up to {} MB are accepted but {} takes at most {} MB per upload: larger files will be skipped unless compression or a max upload size is set",
+                    max_image_bytes / (1024 * 1024),
+                    config.provider.display_name(),
+                    limit / (1024 * 1024)
+                ),
+            )
+        })
+    }
+
+    /// Every problem with `config`, checked against the rules of its provider.
+    /// Pure field checks, nothing here touches the network.
+    pub fn config_errors(config: &OSSConfig) -> Vec<String> {
         let mut errors = Vec::new();
+        let endpoint = config.endpoint.trim();
+        let region = config.region.trim();
 
-        // Basic field validation (no network operations)
-        if config.endpoint.trim().is_empty() {
+        // Tencent and AWS build their host from bucket + region, the endpoint is optional there
+        let endpoint_required =
+            matches!(config.provider, OSSProvider::Aliyun | OSSProvider::Custom);
+        if endpoint_required && endpoint.is_empty() {
             errors.push("Endpoint is required".to_string());
         }
+        if (endpoint_required || !endpoint.is_empty())
+            && !endpoint.starts_with("http://")
+            && !endpoint.starts_with("https://")
+        {
+            errors
+                .push("Endpoint must be a valid URL starting with http:// or https://".to_string());
+        }
+        let endpoint_host = endpoint
+            .split_once("://")
+            .map(|(_, rest)| rest.split('/').next().unwrap_or(""))
+            .unwrap_or("")
+            .to_lowercase();
 
         if config.access_key_id.trim().is_empty() {
             errors.push("Access Key ID is required".to_string());
@@ -328,40 +530,109 @@ impl ConfigService {
             errors.push("Bucket name is required".to_string());
         }
 
-        if config.region.trim().is_empty() {
-            errors.push("Region is required".to_string());
+        match config.provider {
+            OSSProvider::Aliyun => {
+                if !endpoint_host.is_empty() && !ALIYUN_ENDPOINT.is_match(&endpoint_host) {
+                    errors.push(format!(
+                        "Aliyun endpoint must look like oss-<region>.aliyuncs.com (got {}), use the Custom provider for other endpoints",
+                        endpoint_host
+                    ));
+                }
+            }
+            OSSProvider::Tencent => {
+                if region.is_empty() {
+                    errors.push("Region is required".to_string());
+                }
+                let bucket = config.bucket.trim();
+                if !bucket.is_empty() && !TENCENT_BUCKET.is_match(bucket) {
+                    errors.push(format!(
+                        "Tencent bucket must include the APPID suffix, e.g. {}-1250000000",
+                        bucket
+                    ));
+                }
+            }
+            OSSProvider::Aws => {
+                let custom_endpoint = !endpoint_host.is_empty()
+                    && !endpoint_host.ends_with("amazonaws.com")
+                    && !endpoint_host.ends_with("amazonaws.com.cn");
+                if region.is_empty() {
+                    errors.push("Region is required".to_string());
+                } else if !custom_endpoint && !AWS_REGIONS.contains(&region) {
+                    errors.push(format!("Unknown AWS region: {}", region));
+                }
+            }
+            OSSProvider::Custom => {}
+        }
+
+        if let Some(cdn_domain) = &config.cdn_domain {
+            if cdn_domain.contains("://") {
+                errors.push(format!(
+                    "CDN domain must not include a scheme (use {})",
+                    cdn_domain.split_once("://").map(|(_, d)| d).unwrap_or("")
+                ));
+            }
         }
 
-        // Validate path template
         if config.path_template.trim().is_empty() {
             errors.push("Path template is required".to_string());
+        } else {
+            errors.extend(validate_path_template(&config.path_template, &config.provider).errors);
         }
 
-        // Validate compression quality
         if config.compression_quality > 100 {
             errors.push("Compression quality must be between 0 and 100".to_string());
         }
 
-        // Validate endpoint URL format
-        if !config.endpoint.starts_with("http://") && !config.endpoint.starts_with("https://") {
-            errors
-                .push("Endpoint must be a valid URL starting with http:// or https://".to_string());
+        if config.max_upload_size_kb == Some(0) {
+            errors.push("Max upload size must be greater than 0 KB".to_string());
         }
 
-        // Smart connection test with caching (only if basic validation passes)
-        let connection_test = if errors.is_empty() {
-            println!("🔍 Basic validation passed, proceeding with smart connection test...");
-            Some(self.smart_connection_test(config).await?)
-        } else {
-            println!("❌ Basic validation failed, skipping connection test");
-            None
-        };
+        if config.size_variants.len() > MAX_SIZE_VARIANTS {
+            errors.push(format!(
+                "At most {} size variants are allowed",
+                MAX_SIZE_VARIANTS
+            ));
+        }
+        if config
+            .size_variants
+            .iter()
+            .any(|width| *width == 0 || *width > MAX_VARIANT_WIDTH)
+        {
+            errors.push(format!(
+                "Size variant widths must be between 1 and {} pixels",
+                MAX_VARIANT_WIDTH
+            ));
+        }
 
-        Ok(ConfigValidation {
-            valid: errors.is_empty() && connection_test.as_ref().is_some_and(|t| t.success),
-            errors,
-            connection_test,
-        })
+        errors
+    }
+
+    /// Settings that are valid but probably not what the user wants
+    pub fn config_warnings(config: &OSSConfig) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+
+        let has_cdn = config
+            .cdn_domain
+            .as_deref()
+            .is_some_and(|domain| !domain.trim().is_empty());
+        if config.object_acl == ObjectAcl::Private && !has_cdn {
+            warnings.push(ConfigWarning::new(
+                ConfigWarningCode::PrivateWithoutCdn,
+                "Objects are uploaded as private and no CDN domain is set: generated markdown links won't be publicly readable",
+            ));
+        }
+
+        if config.bucket.contains('_') {
+            warnings.push(ConfigWarning::new(
+                ConfigWarningCode::BucketUnderscore,
+                format!(
+                    "Bucket name {} contains underscores, which some providers and tools reject",
+                    config.bucket.trim()
+                ),
+            ));
+        }
+
+        warnings
     }
 
     #[allow(dead_code)]
@@ -395,23 +666,26 @@ impl ConfigService {
         let config_json = std::fs::read_to_string(&configs_path)
             .map_err(|e| AppError::Configuration(format!("Failed to read configs: {}", e)))?;
 
-        let collection: ConfigCollection = serde_json::from_str(&config_json)
-            .map_err(|e| AppError::Configuration(format!("Failed to parse configs: {}", e)))?;
-
-        Ok(collection)
+        match serde_json::from_str(&config_json) {
+            Ok(collection) => Ok(collection),
+            // A file cut off by a crash must not lock the user out of every
+            // command; it's kept for recover_corrupt_store
+            Err(e) => {
+                CORRUPT_STORES.quarantine(StoreKind::Configs, &configs_path, &e.to_string());
+                Ok(ConfigCollection {
+                    configs: Vec::new(),
+                    active_config_id: None,
+                })
+            }
+        }
     }
 
     /// Save a configuration item
+    /// Add or update a config item. The caller validates it first, the
+    /// save_config_item command with the same checks as validate_oss_config.
     pub async fn save_config_item(&self, item: ConfigItem) -> Result<()> {
-        // Validate the config before saving
-        let validation = self.validate_config(&item.config).await?;
-        if !validation.valid {
-            return Err(AppError::Configuration(format!(
-                "Invalid configuration: {}",
-                validation.errors.join(", ")
-            )));
-        }
-
+        // Another instance may have saved a config since we last read the file
+        let _lock = lock_store(&self.get_configs_file_path()).await?;
         let mut collection = self.load_all_configs().await.unwrap_or(ConfigCollection {
             configs: Vec::new(),
             active_config_id: None,
@@ -440,6 +714,7 @@ impl ConfigService {
 
     /// Set active configuration
     pub async fn set_active_config(&self, config_id: String) -> Result<()> {
+        let _lock = lock_store(&self.get_configs_file_path()).await?;
         let mut collection = self.load_all_configs().await?;
 
         // Check if config exists
@@ -462,13 +737,14 @@ impl ConfigService {
 
     /// Delete a configuration item
     pub async fn delete_config_item(&self, config_id: String) -> Result<()> {
+        let _lock = lock_store(&self.get_configs_file_path()).await?;
         let mut collection = self.load_all_configs().await?;
 
         // Remove the config
         collection.configs.retain(|c| c.id != config_id);
 
         // If deleted config was active, set first config as active
-        if collection.active_config_id == Some(config_id) {
+        if collection.active_config_id.as_deref() == Some(config_id.as_str()) {
             collection.active_config_id = collection.configs.first().map(|c| c.id.clone());
             if let Some(ref active_id) = collection.active_config_id {
                 for config in &mut collection.configs {
@@ -477,7 +753,30 @@ impl ConfigService {
             }
         }
 
-        self.save_config_collection(&collection).await
+        self.save_config_collection(&collection).await?;
+        self.forget_default_config(&config_id).await
+    }
+
+    // A workflow default pointing at a deleted config goes back to the active one
+    async fn forget_default_config(&self, config_id: &str) -> Result<()> {
+        let _guard = APP_SETTINGS_WRITE_LOCK.lock().await;
+        let _lock = lock_store(&self.get_settings_file_path()).await?;
+
+        let mut settings = self.load_app_settings().await?;
+        let mut changed = false;
+        for default in [
+            &mut settings.default_config_for_article,
+            &mut settings.default_config_for_direct,
+        ] {
+            if default.as_deref() == Some(config_id) {
+                *default = None;
+                changed = true;
+            }
+        }
+        if changed {
+            self.save_app_settings(&settings).await?;
+        }
+        Ok(())
     }
 
     /// Get the active configuration
@@ -491,44 +790,395 @@ impl ConfigService {
         }
     }
 
+    /// The saved config an upload runs with: `config_id` when given, else the
+    /// default set for the workflow in settings, else the active config
+    pub async fn resolve_upload_config(
+        &self,
+        config_id: Option<&str>,
+        mode: &UploadMode,
+    ) -> Result<ConfigItem> {
+        let collection = self.load_all_configs().await?;
+        let find = |id: &str| collection.configs.iter().find(|c| c.id == id).cloned();
+
+        if let Some(id) = config_id {
+            return find(id)
+                .ok_or_else(|| AppError::NotFound(format!("Config {} no longer exists", id)));
+        }
+
+        let settings = self.app_settings().await?;
+        let (default, workflow) = match mode {
+            UploadMode::ArticleUpload => (settings.default_config_for_article, "article"),
+            _ => (settings.default_config_for_direct, "direct"),
+        };
+        if let Some(id) = default {
+            return find(&id).ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "The default config for {} uploads ({}) no longer exists, pick another one in settings",
+                    workflow, id
+                ))
+            });
+        }
+
+        self.get_active_config().await?.ok_or_else(|| {
+            AppError::Configuration("No active config, add or select one first".to_string())
+        })
+    }
+
     /// Save the entire config collection
-    async fn save_config_collection(&self, collection: &ConfigCollection) -> Result<()> {
+    pub async fn save_config_collection(&self, collection: &ConfigCollection) -> Result<()> {
         let configs_path = self.get_configs_file_path();
         let config_json = serde_json::to_string_pretty(collection)
             .map_err(|e| AppError::Configuration(format!("Failed to serialize configs: {}", e)))?;
 
-        std::fs::write(&configs_path, config_json)
+        write_atomic(&configs_path, &config_json)
             .map_err(|e| AppError::Configuration(format!("Failed to save configs: {}", e)))?;
+        // Services built from the old configs must not be handed out again
+        OSS_SERVICES.clear();
 
         Ok(())
     }
 
-    // Private helper methods
+    // ============================================================================
+    // App Settings
+    // ============================================================================
+
+    /// Load app settings, falling back to defaults when the file doesn't exist yet.
+    /// Missing fields take their serde defaults so older files keep loading.
+    /// An unparsable file is moved aside and the defaults are used.
+    pub async fn load_app_settings(&self) -> Result<AppSettings> {
+        let settings_path = self.get_settings_file_path();
 
-    fn get_config_dir() -> Result<PathBuf> {
-        let config_dir = if cfg!(target_os = "windows") {
-            dirs::config_dir()
-                .ok_or_else(|| {
-                    AppError::Configuration("Failed to get config directory".to_string())
-                })?
-                .join(CONFIG_DIR_NAME)
-        } else if cfg!(target_os = "macos") {
-            dirs::config_dir()
-                .ok_or_else(|| {
-                    AppError::Configuration("Failed to get config directory".to_string())
-                })?
-                .join(CONFIG_DIR_NAME)
+        if !settings_path.exists() {
+            return Ok(AppSettings::default());
+        }
+
+        let settings_json = std::fs::read_to_string(&settings_path)
+            .map_err(|e| AppError::Configuration(format!("Failed to read settings: {}", e)))?;
+
+        match serde_json::from_str(&settings_json) {
+            Ok(settings) => Ok(settings),
+            Err(e) => {
+                CORRUPT_STORES.quarantine(StoreKind::Settings, &settings_path, &e.to_string());
+                Ok(AppSettings::default())
+            }
+        }
+    }
+
+    pub async fn save_app_settings(&self, settings: &AppSettings) -> Result<()> {
+        let settings_path = self.get_settings_file_path();
+        let settings_json = serde_json::to_string_pretty(settings)
+            .map_err(|e| AppError::Configuration(format!("Failed to serialize settings: {}", e)))?;
+
+        write_atomic(&settings_path, &settings_json)
+            .map_err(|e| AppError::Configuration(format!("Failed to save settings: {}", e)))?;
+
+        // Refresh the cached handle so readers see the new values immediately
+        if let Ok(mut cache) = APP_SETTINGS_CACHE.lock() {
+            cache.insert(settings_path, settings.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Cached app settings; the file is only read the first time
+    pub async fn app_settings(&self) -> Result<AppSettings> {
+        let settings_path = self.get_settings_file_path();
+
+        if let Some(settings) = APP_SETTINGS_CACHE
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&settings_path).cloned())
+        {
+            return Ok(settings);
+        }
+
+        let settings = self.load_app_settings().await?;
+        if let Ok(mut cache) = APP_SETTINGS_CACHE.lock() {
+            cache.insert(settings_path, settings.clone());
+        }
+
+        Ok(settings)
+    }
+
+    /// Apply a partial update, validate the merged result and persist it
+    pub async fn update_app_settings(&self, patch: AppSettingsPatch) -> Result<AppSettings> {
+        let _guard = APP_SETTINGS_WRITE_LOCK.lock().await;
+        let _lock = lock_store(&self.get_settings_file_path()).await?;
+
+        // Only check the defaults this patch sets, older ones are cleared on delete
+        let new_defaults: Vec<String> = [
+            &patch.default_config_for_article,
+            &patch.default_config_for_direct,
+        ]
+        .into_iter()
+        .filter_map(|default| default.clone().flatten())
+        .collect();
+        if !new_defaults.is_empty() {
+            let collection = self.load_all_configs().await?;
+            if let Some(missing) = new_defaults
+                .iter()
+                .find(|id| !collection.configs.iter().any(|c| &c.id == *id))
+            {
+                return Err(AppError::Validation(format!(
+                    "Config {} does not exist",
+                    missing
+                )));
+            }
+        }
+
+        // Merge onto what's on disk, another process may have written since we cached
+        let mut settings = self.load_app_settings().await?;
+        settings.apply_patch(patch);
+        Self::validate_app_settings(&settings)?;
+
+        self.save_app_settings(&settings).await?;
+        Ok(settings)
+    }
+
+    /// Salvage what can be read from the newest corrupt copy of the configs
+    /// or settings store. Configs are added unless one with the same id
+    /// exists by now; complete settings fields are applied over the current
+    /// settings, skipping the result if it doesn't validate.
+    pub async fn recover_corrupt_store(&self, kind: StoreKind) -> Result<StoreRecoveryResult> {
+        let path = match kind {
+            StoreKind::Configs => self.get_configs_file_path(),
+            StoreKind::Settings => self.get_settings_file_path(),
+            _ => {
+                return Err(AppError::Validation(format!(
+                    "{:?} is not a config store",
+                    kind
+                )))
+            }
+        };
+        let source = latest_corrupt_file(&path).ok_or_else(|| {
+            AppError::Validation(format!("No corrupt copy of {} to recover", path.display()))
+        })?;
+        let content = String::from_utf8_lossy(&std::fs::read(&source)?).to_string();
+        // Taken before the store lock, in the same order as update_app_settings
+        let _guard = match kind {
+            StoreKind::Settings => Some(APP_SETTINGS_WRITE_LOCK.lock().await),
+            _ => None,
+        };
+        let _lock = lock_store(&path).await?;
+
+        let (recovered, unreadable) = if kind == StoreKind::Configs {
+            let values = salvage_array(&content, Some("configs"));
+            let total = values.len();
+            let items: Vec<ConfigItem> = values
+                .into_iter()
+                .filter_map(|value| serde_json::from_value(value).ok())
+                .collect();
+            let unreadable = total - items.len();
+            let mut collection = self.load_all_configs().await?;
+            let mut recovered = 0;
+            for item in items {
+                if !collection.configs.iter().any(|c| c.id == item.id) {
+                    collection.configs.push(item);
+                    recovered += 1;
+                }
+            }
+            // The active id follows the configs in the file, so it's usually lost
+            if collection.active_config_id.is_none() {
+                collection.active_config_id = collection.configs.first().map(|c| c.id.clone());
+            }
+            for config in &mut collection.configs {
+                config.is_active = collection.active_config_id.as_deref() == Some(&config.id);
+            }
+            self.save_config_collection(&collection).await?;
+            (recovered, unreadable)
         } else {
-            dirs::config_dir()
-                .ok_or_else(|| {
-                    AppError::Configuration("Failed to get config directory".to_string())
-                })?
-                .join(CONFIG_DIR_NAME)
+            let entries = salvage_object(&content);
+            let mut merged = serde_json::to_value(self.load_app_settings().await?)?;
+            let mut recovered = 0;
+            if let Some(current) = merged.as_object_mut() {
+                for (key, value) in entries {
+                    if current.contains_key(&key) {
+                        current.insert(key, value);
+                        recovered += 1;
+                    }
+                }
+            }
+            let settings: AppSettings = serde_json::from_value(merged).map_err(|e| {
+                AppError::Validation(format!("Recovered settings are not usable: {}", e))
+            })?;
+            Self::validate_app_settings(&settings)?;
+            self.save_app_settings(&settings).await?;
+            (recovered, 0)
         };
 
-        Ok(config_dir)
+        CORRUPT_STORES.resolve(kind);
+        log_info!(
+            operation = "recover_corrupt_store",
+            kind = ?kind,
+            source = %source.display(),
+            recovered = recovered,
+            "Recovered records from a corrupt store"
+        );
+
+        Ok(StoreRecoveryResult {
+            kind,
+            source: source.to_string_lossy().to_string(),
+            recovered,
+            unreadable,
+        })
     }
 
+    pub fn validate_app_settings(settings: &AppSettings) -> Result<()> {
+        match &settings.custom_link_template {
+            Some(template) => crate::services::link_formatter::validate_link_template(template)?,
+            None if settings.default_copy_format == crate::models::LinkFormat::Custom => {
+                return Err(AppError::Validation(
+                    "Custom copy format requires a link template".to_string(),
+                ));
+            }
+            None => {}
+        }
+
+        crate::services::scan_globs::ScanGlobs::new(
+            &settings.include_image_globs,
+            &settings.exclude_image_globs,
+            &settings.exclude_file_globs,
+        )?;
+
+        if !(1..=10).contains(&settings.upload_concurrency) {
+            return Err(AppError::Validation(
+                "Upload concurrency must be between 1 and 10".to_string(),
+            ));
+        }
+
+        if !(1..=600).contains(&settings.upload_rate_limit_per_minute) {
+            return Err(AppError::Validation(
+                "Upload rate limit must be between 1 and 600 per minute".to_string(),
+            ));
+        }
+
+        if settings.allowed_extensions.is_empty() {
+            return Err(AppError::Validation(
+                "At least one image extension must be allowed".to_string(),
+            ));
+        }
+
+        if !(1..=365).contains(&settings.backup_retention_days) {
+            return Err(AppError::Validation(
+                "Backup retention must be between 1 and 365 days".to_string(),
+            ));
+        }
+
+        if settings.backup_keep_per_file > MAX_BACKUPS_PER_FILE {
+            return Err(AppError::Validation(format!(
+                "Backups kept per file cannot exceed {}",
+                MAX_BACKUPS_PER_FILE
+            )));
+        }
+
+        if settings.language.trim().is_empty() {
+            return Err(AppError::Validation("Language cannot be empty".to_string()));
+        }
+
+        if !LOG_LEVELS.contains(&settings.log_level.as_str()) {
+            return Err(AppError::Validation(format!(
+                "Invalid log level: {}",
+                settings.log_level
+            )));
+        }
+
+        if !(1..=MAX_SCAN_FILES_LIMIT).contains(&settings.max_scan_files) {
+            return Err(AppError::Validation(format!(
+                "Max scan files must be between 1 and {}",
+                MAX_SCAN_FILES_LIMIT
+            )));
+        }
+
+        if !(1..=MAX_UPLOAD_IMAGES_LIMIT).contains(&settings.max_upload_images) {
+            return Err(AppError::Validation(format!(
+                "Max upload images must be between 1 and {}",
+                MAX_UPLOAD_IMAGES_LIMIT
+            )));
+        }
+
+        if !(1..=MAX_IMAGE_SIZE_MB_LIMIT).contains(&settings.max_image_size_mb) {
+            return Err(AppError::Validation(format!(
+                "Max image size must be between 1 and {} MB",
+                MAX_IMAGE_SIZE_MB_LIMIT
+            )));
+        }
+
+        if !(1..=MAX_MARKDOWN_SIZE_MB_LIMIT).contains(&settings.max_markdown_size_mb) {
+            return Err(AppError::Validation(format!(
+                "Max markdown file size must be between 1 and {} MB",
+                MAX_MARKDOWN_SIZE_MB_LIMIT
+            )));
+        }
+
+        if !(1..=MAX_THUMBNAIL_SIZE_LIMIT).contains(&settings.max_thumbnail_size) {
+            return Err(AppError::Validation(format!(
+                "Max thumbnail size must be between 1 and {} pixels",
+                MAX_THUMBNAIL_SIZE_LIMIT
+            )));
+        }
+
+        if settings.thumbnail_concurrency > MAX_THUMBNAIL_CONCURRENCY {
+            return Err(AppError::Validation(format!(
+                "Thumbnail concurrency cannot exceed {}",
+                MAX_THUMBNAIL_CONCURRENCY
+            )));
+        }
+
+        if settings.notifications.dismiss_timeout > 60000 {
+            return Err(AppError::Validation(
+                "Dismiss timeout cannot exceed 60 seconds".to_string(),
+            ));
+        }
+
+        let maintenance = &settings.maintenance;
+        if !(1..=MAX_MAINTENANCE_INTERVAL_HOURS).contains(&maintenance.interval_hours) {
+            return Err(AppError::Validation(format!(
+                "Maintenance interval must be between 1 and {} hours",
+                MAX_MAINTENANCE_INTERVAL_HOURS
+            )));
+        }
+        if maintenance.thumbnail_cache_max_mb == 0 {
+            return Err(AppError::Validation(
+                "Thumbnail cache limit must be at least 1 MB".to_string(),
+            ));
+        }
+        if !(1..=3650).contains(&maintenance.history_max_age_days) {
+            return Err(AppError::Validation(
+                "History age limit must be between 1 and 3650 days".to_string(),
+            ));
+        }
+        if !(1..=3650).contains(&maintenance.trash_retention_days) {
+            return Err(AppError::Validation(
+                "History trash retention must be between 1 and 3650 days".to_string(),
+            ));
+        }
+        if !(1..=3650).contains(&maintenance.originals_max_age_days) {
+            return Err(AppError::Validation(
+                "Archived originals age limit must be between 1 and 3650 days".to_string(),
+            ));
+        }
+        if maintenance.originals_max_mb == 0 {
+            return Err(AppError::Validation(
+                "Archived originals limit must be at least 1 MB".to_string(),
+            ));
+        }
+
+        if let Some(root) = settings
+            .asset_roots
+            .iter()
+            .find(|root| !std::path::Path::new(root).is_absolute())
+        {
+            return Err(AppError::Validation(format!(
+                "Asset root must be an absolute path: {}",
+                root
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Private helper methods
+
     fn get_config_file_path(&self) -> PathBuf {
         self.config_dir.join("config.json")
     }
@@ -540,11 +1190,15 @@ impl ConfigService {
     fn get_cache_file_path(&self) -> PathBuf {
         self.config_dir.join(CACHE_FILE_NAME)
     }
+
+    fn get_settings_file_path(&self) -> PathBuf {
+        self.config_dir.join(SETTINGS_FILE_NAME)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::models::OSSProvider;
+    use crate::models::{MaintenanceSettings, NotificationConfig, OSSProvider};
 
     use super::*;
     use tempfile::TempDir;
@@ -558,9 +1212,16 @@ mod tests {
             bucket: "test-bucket".to_string(),
             region: "cn-hangzhou".to_string(),
             path_template: "images/{date}/{filename}".to_string(),
-            cdn_domain: Some("https://cdn.example.com".to_string()),
+            cdn_domain: Some("cdn.example.com".to_string()),
             compression_enabled: true,
             compression_quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
         }
     }
 
@@ -576,6 +1237,13 @@ mod tests {
             cdn_domain: None,
             compression_enabled: true,
             compression_quality: 150, // Invalid: > 100
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
         }
     }
 
@@ -624,6 +1292,81 @@ mod tests {
         assert!(validation.errors.len() >= 2);
     }
 
+    #[test]
+    fn test_size_variant_validation() {
+        let mut config = create_test_config();
+        config.size_variants = vec![480, 960, 1920];
+        assert!(ConfigService::config_errors(&config).is_empty());
+
+        config.size_variants = vec![480, 0];
+        assert!(ConfigService::config_errors(&config)
+            .join(" ")
+            .contains("between 1 and"));
+
+        config.size_variants = (1..=9).map(|i| i * 100).collect();
+        assert!(ConfigService::config_errors(&config)
+            .join(" ")
+            .contains("At most 8 size variants"));
+    }
+
+    #[test]
+    fn test_private_acl_without_cdn_warns() {
+        let mut config = create_test_config();
+        assert!(ConfigService::config_warnings(&config).is_empty());
+
+        config.object_acl = ObjectAcl::Private;
+        config.cdn_domain = None;
+        let warnings = ConfigService::config_warnings(&config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, ConfigWarningCode::PrivateWithoutCdn);
+        assert!(warnings[0].message.contains("won't be publicly readable"));
+        // A warning is not a validation error
+        assert!(ConfigService::config_errors(&config).is_empty());
+
+        config.cdn_domain = Some("cdn.example.com".to_string());
+        assert!(ConfigService::config_warnings(&config).is_empty());
+    }
+
+    #[test]
+    fn test_bucket_underscore_warns_with_a_stable_code() {
+        let mut config = create_test_config();
+        config.bucket = "my_images".to_string();
+        let warnings = ConfigService::config_warnings(&config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, ConfigWarningCode::BucketUnderscore);
+        assert!(ConfigService::config_errors(&config).is_empty());
+        assert_eq!(
+            serde_json::to_value(&warnings[0]).unwrap()["code"],
+            "bucket_underscore"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validation_reports_its_steps() {
+        let (service, _temp_dir) = create_test_service().await;
+        let mut config = create_test_config();
+        config.bucket = "validation-steps".to_string();
+        let tested = OSSConnectionTest {
+            success: true,
+            error: None,
+            latency: Some(20),
+            bucket_exists: Some(true),
+            available_buckets: None,
+            diagnostics: vec![ConnectionTestStep::passed("auth", None)],
+        };
+        service.cache_connection_test(&config, &tested);
+
+        let validation = service.validate_config(&config).await.unwrap();
+        let steps: Vec<String> = validation
+            .connection_test
+            .unwrap()
+            .diagnostics
+            .into_iter()
+            .map(|step| step.step)
+            .collect();
+        assert_eq!(steps, ["config", "cache", "auth"]);
+    }
+
     #[tokio::test]
     async fn test_aws_region_handling() {
         let (service, _temp_dir) = create_test_service().await;
@@ -655,4 +1398,289 @@ mod tests {
         assert!(cache_path.ends_with(CACHE_FILE_NAME));
         assert!(cache_path.parent().unwrap().exists());
     }
+
+    #[tokio::test]
+    async fn test_app_settings_roundtrip_and_defaults() {
+        let (service, _temp_dir) = create_test_service().await;
+
+        // Defaults when nothing has been saved yet
+        let settings = service.load_app_settings().await.unwrap();
+        assert_eq!(
+            settings.default_copy_format,
+            crate::models::LinkFormat::Markdown
+        );
+
+        let mut settings = settings;
+        settings.default_copy_format = crate::models::LinkFormat::Html;
+        service.save_app_settings(&settings).await.unwrap();
+
+        let loaded = service.load_app_settings().await.unwrap();
+        assert_eq!(loaded.default_copy_format, crate::models::LinkFormat::Html);
+
+        // Unknown-to-this-version files with missing fields still load
+        std::fs::write(service.get_settings_file_path(), "{}").unwrap();
+        let loaded = service.load_app_settings().await.unwrap();
+        assert!(loaded.custom_link_template.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_app_settings_patch() {
+        let (service, _temp_dir) = create_test_service().await;
+
+        let patch: AppSettingsPatch = serde_json::from_str(
+            r#"{"upload_concurrency": 5, "allowed_extensions": [".PNG", "jpg"]}"#,
+        )
+        .unwrap();
+        let updated = service.update_app_settings(patch).await.unwrap();
+        assert_eq!(updated.upload_concurrency, 5);
+        assert_eq!(updated.allowed_extensions, vec!["png", "jpg"]);
+        // Untouched fields keep their defaults
+        assert_eq!(updated.log_level, "info");
+
+        // The cached handle reflects the update without re-reading the file
+        assert_eq!(service.app_settings().await.unwrap(), updated);
+
+        let patch: AppSettingsPatch =
+            serde_json::from_str(r#"{"custom_link_template": "<{url}>"}"#).unwrap();
+        let updated = service.update_app_settings(patch).await.unwrap();
+        assert_eq!(updated.custom_link_template.as_deref(), Some("<{url}>"));
+        assert_eq!(updated.upload_concurrency, 5);
+
+        // Explicit null clears the template
+        let patch: AppSettingsPatch =
+            serde_json::from_str(r#"{"custom_link_template": null}"#).unwrap();
+        let updated = service.update_app_settings(patch).await.unwrap();
+        assert!(updated.custom_link_template.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_app_settings_rejects_invalid_values() {
+        let (service, _temp_dir) = create_test_service().await;
+
+        let patch = AppSettingsPatch {
+            upload_concurrency: Some(0),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            log_level: Some("loud".to_string()),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            max_upload_images: Some(MAX_UPLOAD_IMAGES_LIMIT + 1),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            max_image_size_mb: Some(0),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            max_thumbnail_size: Some(MAX_THUMBNAIL_SIZE_LIMIT + 1),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            thumbnail_concurrency: Some(MAX_THUMBNAIL_CONCURRENCY + 1),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            notifications: Some(NotificationConfig {
+                dismiss_timeout: 120_000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        let patch = AppSettingsPatch {
+            maintenance: Some(MaintenanceSettings {
+                interval_hours: 0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(service.update_app_settings(patch).await.is_err());
+
+        // Nothing was persisted by the rejected patches
+        let settings = service.load_app_settings().await.unwrap();
+        assert_eq!(settings, AppSettings::default());
+    }
+
+    // Written directly, save_config_item would run a connection test
+    async fn save_test_configs(service: &ConfigService, ids: &[&str], active: &str) {
+        let configs = ids
+            .iter()
+            .map(|id| ConfigItem {
+                id: id.to_string(),
+                name: id.to_string(),
+                config: create_test_config(),
+                is_active: *id == active,
+                created_at: String::new(),
+                updated_at: String::new(),
+            })
+            .collect();
+        service
+            .save_config_collection(&ConfigCollection {
+                configs,
+                active_config_id: Some(active.to_string()),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_truncated_configs_are_moved_aside_and_recovered() {
+        let (service, _temp_dir) = create_test_service().await;
+        save_test_configs(&service, &["a", "b", "c"], "c").await;
+        let path = service.get_configs_file_path();
+        let content = std::fs::read_to_string(&path).unwrap();
+        // Power loss in the middle of the third config
+        let cut = content.find("\"id\": \"c\"").unwrap() + 4;
+        std::fs::write(&path, &content[..cut]).unwrap();
+
+        let collection = service.load_all_configs().await.unwrap();
+        assert!(collection.configs.is_empty());
+        assert!(!path.exists());
+        assert!(latest_corrupt_file(&path).is_some());
+        // Saving works again right away
+        assert!(service.get_active_config().await.unwrap().is_none());
+
+        let result = service
+            .recover_corrupt_store(StoreKind::Configs)
+            .await
+            .unwrap();
+        assert_eq!(result.recovered, 2);
+        assert_eq!(result.unreadable, 0);
+        let collection = service.load_all_configs().await.unwrap();
+        let ids: Vec<_> = collection.configs.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(collection.active_config_id.as_deref(), Some("a"));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_settings_fall_back_to_defaults_until_recovered() {
+        let (service, _temp_dir) = create_test_service().await;
+        let settings = AppSettings {
+            language: "en".to_string(),
+            upload_concurrency: 5,
+            ..AppSettings::default()
+        };
+        service.save_app_settings(&settings).await.unwrap();
+        let path = service.get_settings_file_path();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &content[..content.len() - 20]).unwrap();
+
+        assert_eq!(
+            service.load_app_settings().await.unwrap(),
+            AppSettings::default()
+        );
+        assert!(!path.exists());
+
+        let result = service
+            .recover_corrupt_store(StoreKind::Settings)
+            .await
+            .unwrap();
+        assert!(result.recovered > 0);
+        let recovered = service.load_app_settings().await.unwrap();
+        assert_eq!(recovered.language, "en");
+        assert_eq!(recovered.upload_concurrency, 5);
+
+        // Nothing left to recover from a clean directory
+        let (clean, _clean_dir) = create_test_service().await;
+        assert!(clean
+            .recover_corrupt_store(StoreKind::Settings)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_upload_config_order() {
+        let (service, _temp_dir) = create_test_service().await;
+        save_test_configs(&service, &["main", "blog", "images"], "main").await;
+
+        let resolve = |id: Option<&'static str>, mode: UploadMode| {
+            let service = &service;
+            async move { service.resolve_upload_config(id, &mode).await }
+        };
+        assert_eq!(
+            resolve(None, UploadMode::ArticleUpload).await.unwrap().id,
+            "main"
+        );
+
+        let patch: AppSettingsPatch =
+            serde_json::from_str(r#"{"default_config_for_article": "blog"}"#).unwrap();
+        service.update_app_settings(patch).await.unwrap();
+        assert_eq!(
+            resolve(None, UploadMode::ArticleUpload).await.unwrap().id,
+            "blog"
+        );
+        assert_eq!(
+            resolve(None, UploadMode::ImageUpload).await.unwrap().id,
+            "main"
+        );
+        // An explicit id wins over the default
+        assert_eq!(
+            resolve(Some("images"), UploadMode::ArticleUpload)
+                .await
+                .unwrap()
+                .id,
+            "images"
+        );
+
+        let err = resolve(Some("gone"), UploadMode::ImageUpload)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Config gone no longer exists"));
+
+        let patch: AppSettingsPatch =
+            serde_json::from_str(r#"{"default_config_for_direct": "gone"}"#).unwrap();
+        assert!(service.update_app_settings(patch).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deleting_a_default_config_clears_the_default() {
+        let (service, _temp_dir) = create_test_service().await;
+        save_test_configs(&service, &["main", "blog"], "main").await;
+        let patch: AppSettingsPatch = serde_json::from_str(
+            r#"{"default_config_for_article": "blog", "default_config_for_direct": "main"}"#,
+        )
+        .unwrap();
+        service.update_app_settings(patch).await.unwrap();
+
+        service
+            .delete_config_item("blog".to_string())
+            .await
+            .unwrap();
+
+        let settings = service.app_settings().await.unwrap();
+        assert!(settings.default_config_for_article.is_none());
+        assert_eq!(settings.default_config_for_direct.as_deref(), Some("main"));
+        let article = service
+            .resolve_upload_config(None, &UploadMode::ArticleUpload)
+            .await
+            .unwrap();
+        assert_eq!(article.id, "main");
+
+        // A default left stale by another writer fails with a clear error
+        let mut settings = service.load_app_settings().await.unwrap();
+        settings.default_config_for_direct = Some("blog".to_string());
+        service.save_app_settings(&settings).await.unwrap();
+        let err = service
+            .resolve_upload_config(None, &UploadMode::ImageUpload)
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("default config for direct uploads"));
+    }
 }
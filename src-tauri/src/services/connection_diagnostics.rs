@@ -0,0 +1,273 @@
+use crate::models::{ConnectionDiagnosis, ConnectionTestStep, OSSConfig};
+use crate::services::oss_service::{endpoint_host, request_failure_steps};
+use crate::services::OSSService;
+use crate::utils::AppError;
+use crate::{log_info, log_warn};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+
+/// Providers reject signatures once the clocks are about 15 minutes apart,
+/// report the skew well before that
+pub const CLOCK_SKEW_THRESHOLD_SECS: i64 = 300;
+
+const STEP_TIMEOUT: Duration = Duration::from_secs(10);
+const HTTPS_PORT: u16 = 443;
+
+/// Parse an HTTP `Date` header ("Sun, 06 Nov 1994 08:49:37 GMT")
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// What to tell the user about a measured skew, None while it's harmless
+pub fn clock_skew_finding(skew_seconds: i64) -> Option<String> {
+    if skew_seconds.abs() <= CLOCK_SKEW_THRESHOLD_SECS {
+        return None;
+    }
+    let direction = if skew_seconds > 0 { "ahead" } else { "behind" };
+    Some(format!(
+        "Your system clock is off by {} seconds ({} of the server). Providers reject signed requests once it is off by about 15 minutes, sync the clock in your system settings",
+        skew_seconds.abs(),
+        direction
+    ))
+}
+
+/// The "clock" step for a measured skew
+pub fn clock_step(skew_seconds: Option<i64>) -> ConnectionTestStep {
+    match skew_seconds {
+        Some(skew) if clock_skew_finding(skew).is_some() => {
+            ConnectionTestStep::failed("clock", format!("off by {}s", skew))
+        }
+        Some(skew) => ConnectionTestStep::passed("clock", Some(format!("off by {}s", skew))),
+        None => ConnectionTestStep::passed(
+            "clock",
+            Some("not checked, the server sent no Date header".to_string()),
+        ),
+    }
+}
+
+/// Send an unsigned HEAD to `host` and compare its `Date` header with the
+/// local clock. Any response carries the header, a 403 included.
+async fn measure_clock_skew(
+    client: &Client,
+    host: &str,
+) -> std::result::Result<Option<i64>, reqwest::Error> {
+    let sent = Utc::now();
+    let response = client
+        .head(format!("https://{}/", host))
+        .timeout(STEP_TIMEOUT)
+        .send()
+        .await?;
+    let received = Utc::now();
+
+    let server_date = response
+        .headers()
+        .get("date")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date);
+    // The server stamped the response somewhere during the round trip
+    let local = sent + (received - sent) / 2;
+    Ok(server_date.map(|server| (local - server).num_seconds()))
+}
+
+/// Clock skew against the config's endpoint, None when it can't be measured
+pub async fn clock_skew(config: &OSSConfig) -> Option<i64> {
+    let host = endpoint_host(config);
+    match measure_clock_skew(&Client::new(), &host).await {
+        Ok(skew) => skew,
+        Err(e) => {
+            log_warn!(
+                operation = "clock_skew",
+                host = %host,
+                error = %e,
+                "Could not read the server clock"
+            );
+            None
+        }
+    }
+}
+
+/// Check the connection to the config's endpoint one layer at a time: DNS,
+/// TCP, TLS with an unsigned request that also reads the server clock, then
+/// the signed test request. Stops at the first network step that fails.
+pub async fn diagnose_connection(config: &OSSConfig) -> ConnectionDiagnosis {
+    let host = endpoint_host(config);
+    log_info!(
+        operation = "diagnose_connection",
+        host = %host,
+        "Starting connection diagnostics"
+    );
+    let mut diagnosis = ConnectionDiagnosis {
+        success: false,
+        steps: Vec::new(),
+        clock_skew_seconds: None,
+        findings: Vec::new(),
+    };
+
+    let addrs: Vec<SocketAddr> =
+        match timeout(STEP_TIMEOUT, lookup_host((host.as_str(), HTTPS_PORT))).await {
+            Ok(Ok(addrs)) => addrs.collect(),
+            Ok(Err(_)) => Vec::new(),
+            Err(_) => {
+                diagnosis
+                    .steps
+                    .push(ConnectionTestStep::failed("dns", "lookup timed out"));
+                return diagnosis;
+            }
+        };
+    let Some(first) = addrs.first() else {
+        diagnosis.steps.push(ConnectionTestStep::failed(
+            "dns",
+            format!("{} could not be resolved", host),
+        ));
+        return diagnosis;
+    };
+    diagnosis.steps.push(ConnectionTestStep::passed(
+        "dns",
+        Some(first.ip().to_string()),
+    ));
+
+    match timeout(STEP_TIMEOUT, TcpStream::connect(&addrs[..])).await {
+        Ok(Ok(_)) => diagnosis
+            .steps
+            .push(ConnectionTestStep::passed("connect", None)),
+        Ok(Err(e)) => {
+            diagnosis
+                .steps
+                .push(ConnectionTestStep::failed("connect", e.to_string()));
+            return diagnosis;
+        }
+        Err(_) => {
+            diagnosis
+                .steps
+                .push(ConnectionTestStep::failed("connect", "timed out"));
+            return diagnosis;
+        }
+    }
+
+    match measure_clock_skew(&Client::new(), &host).await {
+        Ok(skew) => {
+            diagnosis
+                .steps
+                .push(ConnectionTestStep::passed("tls", None));
+            diagnosis.steps.push(clock_step(skew));
+            diagnosis.clock_skew_seconds = skew;
+            diagnosis.findings.extend(skew.and_then(clock_skew_finding));
+        }
+        Err(e) => {
+            diagnosis.steps.extend(network_steps(&e));
+            return diagnosis;
+        }
+    }
+
+    let signed = match OSSService::new(config.clone()) {
+        Ok(service) => service.test_connection().await,
+        Err(e) => Err(e),
+    };
+    match signed {
+        Ok(result) => {
+            diagnosis.success = result.success;
+            diagnosis.steps.extend(
+                result
+                    .diagnostics
+                    .into_iter()
+                    .filter(|step| step.step != "dns" && step.step != "tls"),
+            );
+        }
+        Err(AppError::Network(e)) => diagnosis.steps.extend(network_steps(&e)),
+        Err(e) => diagnosis
+            .steps
+            .push(ConnectionTestStep::failed("auth", e.to_string())),
+    }
+
+    let auth_failed = diagnosis
+        .steps
+        .iter()
+        .any(|step| step.step == "auth" && !step.ok);
+    if auth_failed && diagnosis.findings.is_empty() && diagnosis.clock_skew_seconds.is_some() {
+        diagnosis.findings.push(
+            "Your system clock is in sync, so the provider rejected the access key or secret"
+                .to_string(),
+        );
+    }
+
+    log_info!(
+        operation = "diagnose_connection",
+        host = %host,
+        success = diagnosis.success,
+        clock_skew_seconds = ?diagnosis.clock_skew_seconds,
+        "Connection diagnostics completed"
+    );
+    diagnosis
+}
+
+// DNS already passed by the time a request is sent
+fn network_steps(error: &reqwest::Error) -> Vec<ConnectionTestStep> {
+    request_failure_steps(error)
+        .into_iter()
+        .filter(|step| step.step != "dns")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OSSProvider, ObjectAcl};
+
+    #[test]
+    fn test_parse_http_date() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(date.to_rfc3339(), "1994-11-06T08:49:37+00:00");
+        assert!(parse_http_date("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_clock_skew_finding_over_threshold() {
+        assert!(clock_skew_finding(CLOCK_SKEW_THRESHOLD_SECS).is_none());
+        assert!(clock_skew_finding(-42).is_none());
+
+        let ahead = clock_skew_finding(1200).unwrap();
+        assert!(ahead.contains("off by 1200 seconds (ahead"));
+        let behind = clock_skew_finding(-1200).unwrap();
+        assert!(behind.contains("off by 1200 seconds (behind"));
+
+        assert!(clock_step(Some(3)).ok);
+        assert!(!clock_step(Some(-1200)).ok);
+        assert!(clock_step(None).ok);
+    }
+
+    #[tokio::test]
+    async fn test_unresolvable_endpoint_stops_at_dns() {
+        let config = OSSConfig {
+            provider: OSSProvider::Aliyun,
+            endpoint: "oss.invalid".to_string(),
+            access_key_id: "id".to_string(),
+            access_key_secret: "secret".to_string(),
+            bucket: "bucket".to_string(),
+            region: "cn-hangzhou".to_string(),
+            path_template: "images/{filename}".to_string(),
+            cdn_domain: None,
+            compression_enabled: false,
+            compression_quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
+        };
+
+        let diagnosis = diagnose_connection(&config).await;
+        assert!(!diagnosis.success);
+        assert_eq!(diagnosis.steps.len(), 1);
+        assert_eq!(diagnosis.steps[0].step, "dns");
+        assert!(!diagnosis.steps[0].ok);
+        assert!(diagnosis.clock_skew_seconds.is_none());
+    }
+}
@@ -1,155 +1,1037 @@
 use crate::models::{
-    BatchReplacementResult, ImageReference, LinkReplacement, ReplacementError, ReplacementResult,
-    ScanResult, ScanStatus,
+    BackupInfo, BatchReplacementResult, FileOperationType, ImageReference, ImageUsage, LinkContext,
+    LinkReplacement, MissingImage, ReadOnlyPolicy, ReplacementChange, ReplacementError,
+    ReplacementResult, ReplacementWarning, ReplacementWarningCode, ScanResult, ScanRevalidation,
+    ScanStatus, ScanWarning, ScanWarningCode, UniqueImage, UnwritableFile,
+    DEFAULT_MAX_MARKDOWN_SIZE_MB,
 };
-use crate::services::ImageService;
+use crate::services::scan_globs::ScanGlobs;
+use crate::services::{BackupService, HistoryService, ImageService};
+use crate::utils::crash_reports::{catch_panic_async, run_blocking};
+use crate::utils::html::escape_html;
+use crate::utils::text_encoding::{decode_text, encode_text, DecodedText};
 use crate::utils::{AppError, Result};
 use crate::{log_debug, log_error, log_info, log_warn};
 use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::future::Future;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 use tokio::fs as async_fs;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+// Editors and virus scanners briefly hold files open exclusively on Windows
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+// Extensions the image regexes match
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "gif", "bmp", "webp", "svg"];
+
+// Index of the `<img src>` regex in `image_patterns`
+const HTML_IMG_PATTERN: usize = 2;
+
+// How far (in bytes) a link may drift from its recorded column and still match
+const COLUMN_TOLERANCE: usize = 5;
+
+// Larger markdown files are scanned line by line instead of read whole
+const STREAMING_SCAN_BYTES: u64 = 1024 * 1024;
+
+// Lines between cancellation checks while streaming a file
+const CANCEL_CHECK_LINES: usize = 10_000;
+
+// Ignore file read from the scan root only, on top of .gitignore and .ignore
+const IMGTOSS_IGNORE_FILE: &str = ".imgtossignore";
+
+lazy_static::lazy_static! {
+    // Markdown files being rewritten right now, by canonical path, with the
+    // operation rewriting them. Shared by every FileService of the process.
+    static ref FILES_IN_USE: Mutex<HashMap<PathBuf, &'static str>> = Mutex::new(HashMap::new());
+}
+
+/// Markdown files a replacement is rewriting, released when dropped
+struct FileLocks {
+    paths: Vec<PathBuf>,
+}
+
+impl Drop for FileLocks {
+    fn drop(&mut self) {
+        let mut in_use = FILES_IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+        for path in &self.paths {
+            in_use.remove(path);
+        }
+    }
+}
+
+/// Claim every file of `file_paths` for `operation`, all or none. Fails right
+/// away when one is being rewritten by another operation instead of waiting,
+/// so two runs never interleave writes to a file. Paths are claimed in sorted
+/// order, a conflict always names the same file.
+fn lock_files<'a>(
+    file_paths: impl IntoIterator<Item = &'a String>,
+    operation: &'static str,
+) -> Result<FileLocks> {
+    // Missing files fail their own replacements, they are claimed as given
+    let paths: BTreeSet<PathBuf> = file_paths
+        .into_iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)))
+        .collect();
+
+    let mut in_use = FILES_IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((path, holder)) = paths
+        .iter()
+        .find_map(|path| in_use.get(path).map(|holder| (path, *holder)))
+    {
+        return Err(AppError::FileSystem(format!(
+            "File busy with another operation ({}): {}",
+            holder,
+            path.display()
+        )));
+    }
+    for path in &paths {
+        in_use.insert(path.clone(), operation);
+    }
+    Ok(FileLocks {
+        paths: paths.into_iter().collect(),
+    })
+}
+
+/// Markdown files of a directory scan
+#[derive(Debug, Default)]
+pub struct MarkdownFileListing {
+    pub files: Vec<String>,
+    /// Markdown files left out because an ignore file excludes them
+    pub ignored: usize,
+}
+
+/// Image references of one markdown file and what was noticed on the way
+#[derive(Default)]
+struct FileReferences {
+    images: Vec<ImageReference>,
+    warnings: Vec<ScanWarning>,
+    // http(s) links, not part of a scan result
+    remote: Vec<ImageReference>,
+    // Set when the file isn't UTF-8
+    encoding: Option<&'static encoding_rs::Encoding>,
+}
+
+/// Fence character and length when `line` opens or closes a fenced code block
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let trimmed = &line[indent..];
+    let ch = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == ch).count();
+    (indent <= 3 && len >= 3).then_some((ch, len))
+}
+
+/// Split `?query`/`#fragment` off a link path. The split is at the first `?` or
+/// `#` right after an image extension, so `a#1.png` keeps its `#`.
+fn split_link_suffix(path: &str) -> (&str, Option<&str>) {
+    path.match_indices(['?', '#'])
+        .map(|(index, _)| path.split_at(index))
+        .find(|(bare, _)| {
+            Path::new(bare)
+                .extension()
+                .map(|ext| {
+                    IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+                })
+                .unwrap_or(false)
+        })
+        .map(|(bare, suffix)| (bare, Some(suffix)))
+        .unwrap_or((path, None))
+}
+
+/// Why a local image path probably doesn't point where the author meant
+fn suspicious_path_reason(path: &str) -> Option<&'static str> {
+    if path.starts_with('~') {
+        Some("`~` is not expanded in markdown links, the path is taken literally")
+    } else if path.to_ascii_lowercase().starts_with("file:") {
+        Some("file: URLs are not resolved, use a plain path")
+    } else if path.contains('\\') {
+        Some("Backslashes only work as separators on Windows")
+    } else if path.contains('%') {
+        Some("Percent-encoded characters are taken literally in local paths")
+    } else {
+        None
+    }
+}
 
 pub struct FileService {
     // Regex patterns for matching image references in Markdown
     image_patterns: Vec<Regex>,
-    #[allow(dead_code)]
     image_service: ImageService,
+    // Where replacements are recorded, if anywhere
+    operation_log: Option<HistoryService>,
+    // Larger markdown files are skipped by scans and refused by replacement
+    max_markdown_bytes: u64,
+    // Tried in order for image paths the markdown file's directory doesn't resolve
+    asset_roots: Vec<PathBuf>,
+    // What replacement batches do with files they can't write
+    read_only_policy: ReadOnlyPolicy,
+    // Images and markdown files scans leave out
+    scan_globs: ScanGlobs,
+    // Directory listings skip what .gitignore, .ignore and .imgtossignore exclude
+    respect_ignore_files: bool,
+    // Replacement rewrites non-UTF-8 files as UTF-8 instead of in their encoding
+    convert_to_utf8: bool,
+    // A rewritten file that doesn't read back as expected is put back as it was
+    restore_unverified: bool,
+    // Named in the error of another run that wants a file this one is rewriting
+    operation: &'static str,
+    // Rewrites of this file write half their bytes and fail
+    #[cfg(test)]
+    write_fault: Option<PathBuf>,
 }
 
 impl FileService {
     pub fn new() -> Result<Self> {
         // Create regex patterns for different image reference formats (case-insensitive)
         let image_patterns = vec![
-            // ![alt text](path/to/image.jpg) - path is in group 1
-            Regex::new(r"(?i)!\[[^\]]*\]\(([^)]+\.(jpg|jpeg|png|gif|bmp|webp|svg))\)")?,
+            // ![alt text](path/to/image.jpg) - path is in group 1, with any ?query/#fragment
+            Regex::new(
+                r"(?i)!\[[^\]]*\]\(([^)]+\.(jpg|jpeg|png|gif|bmp|webp|svg)(?:[?#][^)\s]*)?)\)",
+            )?,
             // ![alt text](path/to/image.jpg "title") - path is in group 1
             Regex::new(
-                r#"(?i)!\[[^\]]*\]\(([^)]+\.(jpg|jpeg|png|gif|bmp|webp|svg))\s+["'][^"']*["']\)"#,
+                r#"(?i)!\[[^\]]*\]\(([^)]+\.(jpg|jpeg|png|gif|bmp|webp|svg)(?:[?#][^)\s]*)?)\s+["'][^"']*["']\)"#,
             )?,
             // <img src="path/to/image.jpg" /> - path is in group 1
             Regex::new(
-                r#"(?i)<img[^>]+src=["']([^"']*\.(jpg|jpeg|png|gif|bmp|webp|svg))["'][^>]*/?>"#,
+                r#"(?i)<img[^>]+src=["']([^"']*\.(jpg|jpeg|png|gif|bmp|webp|svg)(?:[?#][^"']*)?)["'][^>]*/?>"#,
             )?,
         ];
 
         Ok(Self {
             image_patterns,
             image_service: ImageService::new(),
+            operation_log: None,
+            max_markdown_bytes: DEFAULT_MAX_MARKDOWN_SIZE_MB * 1024 * 1024,
+            asset_roots: Vec::new(),
+            read_only_policy: ReadOnlyPolicy::default(),
+            scan_globs: ScanGlobs::default(),
+            respect_ignore_files: true,
+            convert_to_utf8: false,
+            restore_unverified: false,
+            operation: "replace_image_links",
+            #[cfg(test)]
+            write_fault: None,
         })
     }
 
+    /// Skip read-only files in replacement batches instead of aborting them
+    pub fn with_read_only_policy(mut self, policy: ReadOnlyPolicy) -> Self {
+        self.read_only_policy = policy;
+        self
+    }
+
+    /// Record every file rewrite in the file operations log
+    pub fn with_operation_log(mut self, operation_log: Option<HistoryService>) -> Self {
+        self.operation_log = operation_log;
+        self
+    }
+
+    /// Flag referenced images larger than `max_bytes` as `too_large`
+    pub fn with_max_image_size(mut self, max_bytes: u64) -> Self {
+        self.image_service = self.image_service.with_max_image_size(max_bytes);
+        self
+    }
+
+    /// Skip (scan) or refuse (replacement) markdown files larger than `max_bytes`
+    pub fn with_max_markdown_size(mut self, max_bytes: u64) -> Self {
+        self.max_markdown_bytes = max_bytes;
+        self
+    }
+
+    /// Static directories for site-root paths like `/images/a.png`, see `resolve_image_path`
+    pub fn with_asset_roots(mut self, asset_roots: Vec<PathBuf>) -> Self {
+        self.asset_roots = asset_roots;
+        self
+    }
+
+    /// Leave images and markdown files matching these rules out of scans, each
+    /// with a `Filtered` warning saying which rule applied
+    pub fn with_scan_globs(mut self, scan_globs: ScanGlobs) -> Self {
+        self.scan_globs = scan_globs;
+        self
+    }
+
+    /// Whether `list_markdown_files` honors ignore files, on by default
+    pub fn with_ignore_files(mut self, respect: bool) -> Self {
+        self.respect_ignore_files = respect;
+        self
+    }
+
+    /// Write files that aren't UTF-8 back as UTF-8 after replacing links. Off
+    /// by default: they keep their encoding, and a replacement that needs
+    /// characters the encoding lacks fails.
+    pub fn with_utf8_conversion(mut self, convert: bool) -> Self {
+        self.convert_to_utf8 = convert;
+        self
+    }
+
+    /// Write a file back as it was before replacing its links when reading it
+    /// back shows warnings; the content is the same the commands just backed up.
+    /// Off by default: the rewrite stays and the warnings are reported.
+    pub fn with_verification_restore(mut self, restore: bool) -> Self {
+        self.restore_unverified = restore;
+        self
+    }
+
+    /// The command replacing links, named when another one finds its files busy
+    pub fn with_operation_name(mut self, operation: &'static str) -> Self {
+        self.operation = operation;
+        self
+    }
+
     /// Scan multiple markdown files and extract image references
     pub async fn scan_markdown_files(&self, file_paths: Vec<String>) -> Result<Vec<ScanResult>> {
-        let mut results = Vec::new();
+        self.scan_markdown_files_with_progress(file_paths, &AtomicBool::new(false), |_, _| {})
+            .await
+    }
 
-        for file_path in file_paths {
-            let result = self.scan_single_file(&file_path).await;
+    /// Scan files in order, calling `on_file` with the 0-based index of each
+    /// finished file. Once `cancelled` is set the scan stops before the next
+    /// file and returns the results collected so far.
+    pub async fn scan_markdown_files_with_progress<F>(
+        &self,
+        file_paths: Vec<String>,
+        cancelled: &AtomicBool,
+        mut on_file: F,
+    ) -> Result<Vec<ScanResult>>
+    where
+        F: FnMut(usize, &ScanResult),
+    {
+        let mut results = Vec::with_capacity(file_paths.len());
+        self.scan_markdown_files_streamed(&file_paths, cancelled, |index, result| {
+            on_file(index, &result);
             results.push(result);
+        })
+        .await;
+        Ok(results)
+    }
+
+    /// Like `scan_markdown_files_with_progress`, but every result is handed
+    /// over to `on_file` instead of being collected. Returns the number of
+    /// files scanned, less than `file_paths.len()` when cancelled.
+    pub async fn scan_markdown_files_streamed<F>(
+        &self,
+        file_paths: &[String],
+        cancelled: &AtomicBool,
+        mut on_file: F,
+    ) -> usize
+    where
+        F: FnMut(usize, ScanResult),
+    {
+        for (index, file_path) in file_paths.iter().enumerate() {
+            let result = if cancelled.load(Ordering::Relaxed) {
+                None
+            } else {
+                self.scan_single_file(file_path, cancelled).await
+            };
+            let Some(result) = result else {
+                log_info!(
+                    operation = "scan_markdown_files",
+                    scanned = index,
+                    total = file_paths.len(),
+                    "Scan cancelled"
+                );
+                return index;
+            };
+
+            on_file(index, result);
         }
 
-        Ok(results)
+        file_paths.len()
     }
 
-    /// Scan a single markdown file
-    async fn scan_single_file(&self, file_path: &str) -> ScanResult {
-        match self.scan_file_internal(file_path).await {
-            Ok(images) => ScanResult {
-                file_path: file_path.to_string(),
-                images,
-                status: ScanStatus::Success,
-                error: None,
-            },
-            Err(e) => ScanResult {
+    /// Markdown files under `directory`, recursively and sorted so pages stay stable.
+    /// Hidden entries (`.git`, `.obsidian`, ...) are skipped, and so is whatever
+    /// `.gitignore`, `.ignore` or the root's `.imgtossignore` exclude unless
+    /// ignore files are turned off. Files left out that way are only counted.
+    pub async fn list_markdown_files(&self, directory: &str) -> Result<MarkdownFileListing> {
+        let root = PathBuf::from(directory);
+        let respect_ignore_files = self.respect_ignore_files;
+        run_blocking("list_markdown_files", move || {
+            let files = Self::walk_markdown_files(&root, respect_ignore_files)?;
+            let ignored = if respect_ignore_files {
+                Self::walk_markdown_files(&root, false)?.len() - files.len()
+            } else {
+                0
+            };
+            Ok(MarkdownFileListing { files, ignored })
+        })
+        .await
+    }
+
+    fn walk_markdown_files(root: &Path, respect_ignore_files: bool) -> Result<Vec<String>> {
+        let mut builder = ignore::WalkBuilder::new(root);
+        builder
+            .standard_filters(false)
+            .hidden(true)
+            .ignore(respect_ignore_files)
+            .git_ignore(respect_ignore_files)
+            .parents(respect_ignore_files)
+            // Vaults are rarely git repositories, .gitignore still applies
+            .require_git(false);
+        let root_ignore = root.join(IMGTOSS_IGNORE_FILE);
+        if respect_ignore_files && root_ignore.is_file() {
+            if let Some(e) = builder.add_ignore(&root_ignore) {
+                log_warn!(
+                    operation = "list_markdown_files",
+                    file = %root_ignore.display(),
+                    error = %e,
+                    "Ignore file has invalid lines"
+                );
+            }
+        }
+
+        let mut files = Vec::new();
+        for entry in builder.build() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.io_error().is_some() => {
+                    return Err(AppError::FileSystem(e.to_string()));
+                }
+                // A malformed ignore line shouldn't stop the scan
+                Err(e) => {
+                    log_warn!(
+                        operation = "list_markdown_files",
+                        error = %e,
+                        "Ignore file has invalid lines"
+                    );
+                    continue;
+                }
+            };
+            let is_file = entry.file_type().is_some_and(|t| t.is_file());
+            if is_file && Self::is_markdown_path(entry.path()) {
+                files.push(entry.path().to_string_lossy().to_string());
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    fn is_markdown_path(path: &Path) -> bool {
+        path.extension()
+            .map(|ext| {
+                let ext = ext.to_string_lossy().to_lowercase();
+                ext == "md" || ext == "markdown"
+            })
+            .unwrap_or(false)
+    }
+
+    /// Group scan results by unique image so each one is uploaded once.
+    ///
+    /// References are keyed by canonical absolute path first; existing files with
+    /// identical content under different paths are then merged by checksum.
+    /// Groups keep the order in which images were first referenced.
+    pub async fn group_scan_results(&self, results: &[ScanResult]) -> Result<Vec<UniqueImage>> {
+        let mut groups: Vec<UniqueImage> = Vec::new();
+        let mut by_path: HashMap<PathBuf, usize> = HashMap::new();
+
+        for result in results {
+            for image in &result.images {
+                let canonical = fs::canonicalize(&image.absolute_path)
+                    .unwrap_or_else(|_| PathBuf::from(&image.absolute_path));
+
+                let usage = ImageUsage::of(&result.file_path, image);
+
+                match by_path.get(&canonical) {
+                    Some(&index) => groups[index].usages.push(usage),
+                    None => {
+                        by_path.insert(canonical.clone(), groups.len());
+                        groups.push(UniqueImage {
+                            id: uuid::Uuid::new_v4().to_string(),
+                            absolute_path: canonical.to_string_lossy().to_string(),
+                            duplicate_paths: Vec::new(),
+                            checksum: None,
+                            exists: image.exists,
+                            size: image.size,
+                            usages: vec![usage],
+                        });
+                    }
+                }
+            }
+        }
+
+        // Merge byte-identical files; missing files have nothing to compare
+        let mut merged: Vec<UniqueImage> = Vec::with_capacity(groups.len());
+        let mut by_checksum: HashMap<String, usize> = HashMap::new();
+
+        for mut group in groups {
+            if group.exists {
+                match self
+                    .image_service
+                    .calculate_checksum(&group.absolute_path)
+                    .await
+                {
+                    Ok(checksum) => group.checksum = Some(checksum),
+                    Err(e) => {
+                        log_warn!(
+                            operation = "group_scan_results",
+                            image_path = %group.absolute_path,
+                            error = %e,
+                            "Failed to checksum image, grouping by path only"
+                        );
+                    }
+                }
+            }
+
+            let existing = group
+                .checksum
+                .as_ref()
+                .and_then(|checksum| by_checksum.get(checksum).copied());
+
+            match existing {
+                Some(index) => {
+                    let target = &mut merged[index];
+                    target.duplicate_paths.push(group.absolute_path);
+                    target.usages.extend(group.usages);
+                }
+                None => {
+                    if let Some(checksum) = &group.checksum {
+                        by_checksum.insert(checksum.clone(), merged.len());
+                    }
+                    merged.push(group);
+                }
+            }
+        }
+
+        log_debug!(
+            operation = "group_scan_results",
+            unique_images = merged.len(),
+            "Grouped scan results"
+        );
+
+        Ok(merged)
+    }
+
+    /// What changed on disk since `results` were scanned: markdown files whose
+    /// content hash no longer matches (edited, deleted or unreadable), images
+    /// found then and gone now, and images with another size or modification
+    /// time. Files that didn't scan successfully have nothing to replace and
+    /// are skipped.
+    pub async fn revalidate_scan_results(&self, results: &[ScanResult]) -> ScanRevalidation {
+        let mut revalidation = ScanRevalidation::default();
+        let mut changed_images = HashSet::new();
+        for result in results
+            .iter()
+            .filter(|result| matches!(result.status, ScanStatus::Success))
+        {
+            let current_hash = file_content_hash(&result.file_path).await.ok();
+            if current_hash.is_none() || current_hash != result.content_hash {
+                revalidation.changed_files.push(result.file_path.clone());
+            }
+
+            for image in result.images.iter().filter(|image| image.exists) {
+                let Ok(metadata) = fs::metadata(&image.absolute_path) else {
+                    revalidation
+                        .newly_missing
+                        .push(MissingImage::new(&result.file_path, image));
+                    continue;
+                };
+                let modified = metadata
+                    .modified()
+                    .is_ok_and(|modified| modified != image.last_modified);
+                if (metadata.len() != image.size || modified)
+                    && changed_images.insert(image.absolute_path.as_str())
+                {
+                    revalidation
+                        .changed_images
+                        .push(image.absolute_path.clone());
+                }
+            }
+        }
+
+        log_info!(
+            operation = "revalidate_scan_results",
+            file_count = results.len(),
+            changed_files = revalidation.changed_files.len(),
+            newly_missing = revalidation.newly_missing.len(),
+            changed_images = revalidation.changed_images.len(),
+            "Scan results revalidated"
+        );
+        revalidation
+    }
+
+    /// Scan a single markdown file, None when the scan was cancelled midway
+    async fn scan_single_file(
+        &self,
+        file_path: &str,
+        cancelled: &AtomicBool,
+    ) -> Option<ScanResult> {
+        if let Some(reason) = self.scan_globs.file_skip_reason(file_path) {
+            log_debug!(
+                operation = "scan_markdown_files",
+                file_path = %file_path,
+                reason = %reason,
+                "Markdown file excluded by glob rules"
+            );
+            return Some(ScanResult {
                 file_path: file_path.to_string(),
                 images: vec![],
-                status: ScanStatus::Error,
-                error: Some(e.to_string()),
-            },
+                status: ScanStatus::Skipped,
+                error: Some(reason.clone()),
+                missing_images: vec![],
+                unique_image_count: 0,
+                warnings: vec![ScanWarning {
+                    code: ScanWarningCode::Filtered,
+                    message: reason,
+                    image_path: None,
+                    line: None,
+                }],
+                encoding: None,
+                content_hash: None,
+            });
         }
+
+        // Hashed before scanning, so an edit during the scan counts as a change
+        let content_hash = file_content_hash(file_path).await.ok();
+        let scan = self.scan_file_internal(file_path, cancelled);
+        let result = match catch_panic_async("scan_markdown_file", scan).await {
+            Ok(FileReferences {
+                images,
+                mut warnings,
+                encoding,
+                ..
+            }) => {
+                if let Some(encoding) = encoding {
+                    warnings.insert(
+                        0,
+                        ScanWarning {
+                            code: ScanWarningCode::LegacyEncoding,
+                            message: format!(
+                                "File is encoded in {}, not UTF-8; link replacement keeps that encoding unless conversion to UTF-8 is enabled",
+                                encoding.name()
+                            ),
+                            image_path: None,
+                            line: None,
+                        },
+                    );
+                }
+                ScanResult {
+                    file_path: file_path.to_string(),
+                    missing_images: MissingImage::collect(file_path, &images),
+                    unique_image_count: ImageReference::unique_count(&images),
+                    images,
+                    status: ScanStatus::Success,
+                    error: None,
+                    warnings,
+                    encoding: encoding.map(|encoding| encoding.name().to_string()),
+                    content_hash,
+                }
+            }
+            Err(AppError::Cancelled) => return None,
+            Err(e) => {
+                let status = if matches!(e, AppError::FileTooLarge(_)) {
+                    log_warn!(
+                        operation = "scan_markdown_files",
+                        file_path = %file_path,
+                        error = %e,
+                        "Skipping markdown file over the size limit"
+                    );
+                    ScanStatus::Skipped
+                } else {
+                    ScanStatus::Error
+                };
+                ScanResult {
+                    file_path: file_path.to_string(),
+                    images: vec![],
+                    status,
+                    error: Some(e.to_string()),
+                    missing_images: vec![],
+                    unique_image_count: 0,
+                    warnings: vec![],
+                    encoding: None,
+                    content_hash: None,
+                }
+            }
+        };
+        Some(result)
     }
 
     /// Internal implementation for scanning a file
-    async fn scan_file_internal(&self, file_path: &str) -> Result<Vec<ImageReference>> {
-        // Read file content
-        let content = async_fs::read_to_string(file_path).await?;
-
-        // Extract image references with file path context
-        let mut images = self.extract_image_references(&content).await?;
+    async fn scan_file_internal(
+        &self,
+        file_path: &str,
+        cancelled: &AtomicBool,
+    ) -> Result<FileReferences> {
+        let size = self.check_markdown_size(file_path).await?;
+
+        // Extract image references with file path context. Streaming reads
+        // UTF-8 only, other files are decoded whole.
+        let streamed = if size > STREAMING_SCAN_BYTES {
+            self.stream_image_references(file_path, cancelled).await?
+        } else {
+            None
+        };
+        let mut references = match streamed {
+            Some(references) => references,
+            None => {
+                let DecodedText { text, encoding } = self.read_markdown(file_path).await?;
+                FileReferences {
+                    encoding,
+                    ..self.collect_references(&text)
+                }
+            }
+        };
 
         // Resolve relative paths and validate existence
         let base_dir = Path::new(file_path)
             .parent()
             .ok_or_else(|| AppError::FileSystem("Invalid file path".to_string()))?;
 
-        for image in &mut images {
+        let FileReferences {
+            images, warnings, ..
+        } = &mut references;
+        let mut kept = Vec::with_capacity(images.len());
+        for mut image in std::mem::take(images) {
             // Resolve absolute path
-            let absolute_path = if Path::new(&image.original_path).is_absolute() {
-                PathBuf::from(&image.original_path)
-            } else {
-                base_dir.join(&image.original_path)
+            let (absolute_path, asset_root) = self.resolve_image_path(base_dir, image.bare_path());
+            image.absolute_path = absolute_path.to_string_lossy().to_string();
+            image.asset_root = asset_root.map(|root| root.to_string_lossy().to_string());
+            if let Some(reason) = self.scan_globs.image_skip_reason(&image.absolute_path) {
+                warnings.push(ScanWarning {
+                    code: ScanWarningCode::Filtered,
+                    message: reason,
+                    image_path: Some(image.original_path.clone()),
+                    line: Some(image.markdown_line),
+                });
+                continue;
+            }
+            let warning = |code, message: String| ScanWarning {
+                code,
+                message,
+                image_path: Some(image.original_path.clone()),
+                line: Some(image.markdown_line),
             };
 
-            image.absolute_path = absolute_path.to_string_lossy().to_string();
+            if let Some(reason) = suspicious_path_reason(image.bare_path()) {
+                warnings.push(warning(ScanWarningCode::SuspiciousPath, reason.to_string()));
+            }
 
             // Validate file existence and get metadata
-            if let Ok(metadata) = fs::metadata(&absolute_path) {
-                image.exists = true;
-                image.size = metadata.len();
-                image.last_modified = metadata.modified().unwrap_or(SystemTime::now());
+            match fs::metadata(&absolute_path) {
+                Ok(metadata) => {
+                    image.exists = true;
+                    image.size = metadata.len();
+                    image.last_modified = metadata.modified().unwrap_or(SystemTime::now());
+                    image.too_large = self.image_service.exceeds_size_limit(image.size);
+
+                    // 移除缩略图生成，直接使用原图预览
+                    if !metadata.is_file() {
+                        warnings.push(warning(
+                            ScanWarningCode::UnreadableImage,
+                            "Path exists but is not a file".to_string(),
+                        ));
+                    } else if let Err(e) = fs::File::open(&absolute_path) {
+                        warnings.push(warning(
+                            ScanWarningCode::UnreadableImage,
+                            format!("Image can't be read: {}", e),
+                        ));
+                    }
+                }
+                Err(e) => {
+                    image.exists = false;
+                    image.size = 0;
+                    image.last_modified = SystemTime::now();
+                    // Reported as missing too, but the file may well be there
+                    if e.kind() == std::io::ErrorKind::PermissionDenied {
+                        warnings.push(warning(
+                            ScanWarningCode::PermissionDenied,
+                            format!("Image can't be checked: {}", e),
+                        ));
+                    }
+                }
+            }
+            kept.push(image);
+        }
+        *images = kept;
 
-                println!("Processing image: {}", &image.absolute_path);
-                // 移除缩略图生成，直接使用原图预览
-            } else {
-                image.exists = false;
-                image.size = 0;
-                image.last_modified = SystemTime::now();
+        Self::assign_reference_groups(&mut references.images);
+        // Code fence warnings were collected before the per-image ones
+        references.warnings.sort_by_key(|warning| warning.line);
+
+        Ok(references)
+    }
+
+    /// Where a referenced image is, and the asset root it was found under. The
+    /// path as written (relative to the markdown file, or absolute) wins when it
+    /// exists; otherwise each asset root is tried, so `/images/a.png` can resolve
+    /// to `<root>/images/a.png`. Nothing found keeps the path as written.
+    fn resolve_image_path(&self, base_dir: &Path, original_path: &str) -> (PathBuf, Option<&Path>) {
+        let direct = if Path::new(original_path).is_absolute() {
+            PathBuf::from(original_path)
+        } else {
+            base_dir.join(original_path)
+        };
+        if self.asset_roots.is_empty() || fs::metadata(&direct).is_ok() {
+            return (direct, None);
+        }
+
+        let relative = original_path.trim_start_matches('/');
+        self.asset_roots
+            .iter()
+            .map(|root| (root.join(relative), root))
+            .find(|(candidate, _)| fs::metadata(candidate).is_ok())
+            .map(|(candidate, root)| (candidate, Some(root.as_path())))
+            .unwrap_or((direct, None))
+    }
+
+    /// Tag references that resolve to the same file with a shared group id.
+    ///
+    /// Paths are compared canonicalized, so `./a.png` and `img/../a.png` are the
+    /// same image; missing files fall back to their joined absolute path.
+    fn assign_reference_groups(images: &mut [ImageReference]) {
+        let mut by_path: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+        for (index, image) in images.iter().enumerate() {
+            let canonical = fs::canonicalize(&image.absolute_path)
+                .unwrap_or_else(|_| PathBuf::from(&image.absolute_path));
+            by_path.entry(canonical).or_default().push(index);
+        }
+
+        for indices in by_path.values().filter(|indices| indices.len() > 1) {
+            let group = images[indices[0]].id.clone();
+            for &index in indices {
+                images[index].reference_group = Some(group.clone());
             }
         }
+    }
 
-        Ok(images)
+    /// Size of a markdown file, FileTooLarge when it is over the markdown size limit
+    async fn check_markdown_size(&self, file_path: &str) -> Result<u64> {
+        let size = async_fs::metadata(file_path).await?.len();
+        if size > self.max_markdown_bytes {
+            return Err(AppError::FileTooLarge(format!(
+                "{} is {:.1} MB, over the {:.1} MB limit for markdown files",
+                file_path,
+                size as f64 / (1024.0 * 1024.0),
+                self.max_markdown_bytes as f64 / (1024.0 * 1024.0)
+            )));
+        }
+        Ok(size)
     }
 
     /// Extract image references from markdown content
-    pub async fn extract_image_references(&self, content: &str) -> Result<Vec<ImageReference>> {
-        let mut images = Vec::new();
+    fn collect_references(&self, content: &str) -> FileReferences {
+        let mut references = FileReferences::default();
+        let mut fence = None;
 
         // Split content into lines for line/column tracking
-        let lines: Vec<&str> = content.lines().collect();
+        for (line_idx, line) in content.lines().enumerate() {
+            self.collect_line_references(line_idx, line, &mut fence, &mut references);
+        }
 
-        for (line_idx, line) in lines.iter().enumerate() {
-            for pattern in &self.image_patterns {
-                for capture in pattern.captures_iter(line) {
-                    // Get the path from group 1 (which contains the full path for all patterns)
-                    let path_match = capture.get(1).unwrap();
-                    let image_path = path_match.as_str().to_string();
+        references
+    }
 
-                    // Skip URLs (http/https)
-                    if image_path.starts_with("http://") || image_path.starts_with("https://") {
-                        continue;
-                    }
+    /// Contents of a markdown file, decoded from its encoding when it isn't UTF-8
+    async fn read_markdown(&self, file_path: &str) -> Result<DecodedText> {
+        let bytes = async_fs::read(file_path).await?;
+        Ok(Self::decode_markdown(file_path, &bytes))
+    }
+
+    fn decode_markdown(file_path: &str, bytes: &[u8]) -> DecodedText {
+        let decoded = decode_text(bytes);
+        if let Some(encoding) = decoded.encoding {
+            log_debug!(
+                operation = "read_markdown",
+                file_path = %file_path,
+                encoding = encoding.name(),
+                "Markdown file is not UTF-8, decoded from detected encoding"
+            );
+        }
+        decoded
+    }
+
+    /// Same as `collect_references` but reading the file line by line, so
+    /// memory follows the longest line rather than the file size. None when
+    /// the file turns out not to be UTF-8.
+    async fn stream_image_references(
+        &self,
+        file_path: &str,
+        cancelled: &AtomicBool,
+    ) -> Result<Option<FileReferences>> {
+        let file = async_fs::File::open(file_path).await?;
+        let mut lines = tokio::io::BufReader::new(file).lines();
+        let mut references = FileReferences::default();
+        let mut fence = None;
+        let mut line_idx = 0;
+
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            if line_idx % CANCEL_CHECK_LINES == 0 && cancelled.load(Ordering::Relaxed) {
+                return Err(AppError::Cancelled);
+            }
+            self.collect_line_references(line_idx, &line, &mut fence, &mut references);
+            line_idx += 1;
+        }
+
+        Ok(Some(references))
+    }
+
+    /// `fence` is the opening marker of the fenced code block the line is in;
+    /// links inside one are example code, they are reported and left alone
+    fn collect_line_references(
+        &self,
+        line_idx: usize,
+        line: &str,
+        fence: &mut Option<(char, usize)>,
+        references: &mut FileReferences,
+    ) {
+        if let Some(marker) = fence_marker(line) {
+            match *fence {
+                None => *fence = Some(marker),
+                // A closing fence is at least as long as the opening one and has no info string
+                Some((ch, len))
+                    if marker.0 == ch && marker.1 >= len && line.trim().len() == marker.1 =>
+                {
+                    *fence = None
+                }
+                Some(_) => {}
+            }
+            return;
+        }
+        if fence.is_some() {
+            if let Some(path) = self
+                .image_patterns
+                .iter()
+                .find_map(|pattern| pattern.captures(line).and_then(|c| c.get(1)))
+            {
+                references.warnings.push(ScanWarning {
+                    code: ScanWarningCode::CodeFence,
+                    message: "Image link inside a code block was skipped".to_string(),
+                    image_path: Some(path.as_str().to_string()),
+                    line: Some(line_idx + 1),
+                });
+            }
+            return;
+        }
+
+        for (pattern_idx, pattern) in self.image_patterns.iter().enumerate() {
+            // The last pattern is the HTML <img> one, the others are markdown
+            let context = if pattern_idx == HTML_IMG_PATTERN {
+                LinkContext::HtmlAttribute
+            } else {
+                LinkContext::MarkdownInline
+            };
 
-                    let image_ref = ImageReference::new(
+            for capture in pattern.captures_iter(line) {
+                // Get the path from group 1 (which contains the full path for all patterns)
+                let path_match = capture.get(1).unwrap();
+                let image_path = path_match.as_str().to_string();
+
+                // URLs (http/https) are kept apart, scans only look at local images
+                if image_path.starts_with("http://") || image_path.starts_with("https://") {
+                    let mut remote_ref = ImageReference::new(
                         image_path,
-                        String::new(),          // Will be set in scan_file_internal
-                        line_idx + 1,           // Line numbers are 1-based
-                        path_match.start() + 1, // Column numbers are 1-based
+                        String::new(),
+                        line_idx + 1,
+                        path_match.start() + 1,
                     );
-
-                    images.push(image_ref);
+                    remote_ref.context = context;
+                    references.remote.push(remote_ref);
+                    continue;
                 }
+
+                let path_suffix = split_link_suffix(&image_path).1.map(str::to_string);
+                let mut image_ref = ImageReference::new(
+                    image_path,
+                    String::new(),          // Will be set in scan_file_internal
+                    line_idx + 1,           // Line numbers are 1-based
+                    path_match.start() + 1, // Column numbers are 1-based
+                );
+                image_ref.context = context;
+                image_ref.path_suffix = path_suffix;
+
+                references.images.push(image_ref);
             }
         }
+    }
 
+    /// Every image link of a markdown file, local and remote, in file order.
+    /// Local paths are not resolved; links in fenced code blocks are left out.
+    pub async fn all_image_references(&self, file_path: &str) -> Result<Vec<ImageReference>> {
+        self.check_markdown_size(file_path).await?;
+        let content = self.read_markdown(file_path).await?.text;
+        let FileReferences {
+            mut images, remote, ..
+        } = self.collect_references(&content);
+
+        images.extend(remote);
+        images.sort_by_key(|image| (image.markdown_line, image.markdown_column));
         Ok(images)
     }
 
-    /// Replace image links in a markdown file
+    /// Collect the remote (http/https) image URLs referenced by a markdown file
+    pub async fn extract_remote_image_urls(&self, file_path: &str) -> Result<Vec<String>> {
+        self.check_markdown_size(file_path).await?;
+        let content = self.read_markdown(file_path).await?.text;
+        let mut urls = Vec::new();
+
+        for line in content.lines() {
+            for pattern in &self.image_patterns {
+                for capture in pattern.captures_iter(line) {
+                    let image_path = capture.get(1).unwrap().as_str();
+                    if image_path.starts_with("http://") || image_path.starts_with("https://") {
+                        urls.push(image_path.to_string());
+                    }
+                }
+            }
+        }
+
+        Ok(urls)
+    }
+
+    /// Replace image links in a markdown file. Fails when another replacement
+    /// is rewriting the file.
     pub async fn replace_image_links(
         &self,
         file_path: &str,
         replacements: Vec<LinkReplacement>,
+    ) -> Result<ReplacementResult> {
+        let _locks = lock_files([&file_path.to_string()], self.operation)?;
+        self.replace_locked_file_links(file_path, replacements)
+            .await
+    }
+
+    // `replace_image_links` for a file the caller holds the lock of
+    async fn replace_locked_file_links(
+        &self,
+        file_path: &str,
+        replacements: Vec<LinkReplacement>,
+    ) -> Result<ReplacementResult> {
+        let result = self.replace_file_links(file_path, replacements).await;
+
+        if let Some(log) = &self.operation_log {
+            let error = match &result {
+                Ok(r) if r.failed_replacements.is_empty() => None,
+                Ok(r) => Some(format!(
+                    "{} of {} replacement(s) failed",
+                    r.failed_replacements.len(),
+                    r.total_replacements
+                )),
+                Err(e) => Some(e.to_string()),
+            };
+            log.record_file_operation(FileOperationType::Replace, file_path, error)
+                .await;
+        }
+
+        result
+    }
+
+    async fn replace_file_links(
+        &self,
+        file_path: &str,
+        replacements: Vec<LinkReplacement>,
     ) -> Result<ReplacementResult> {
         log_info!(
             operation = "replace_image_links",
@@ -178,9 +1060,17 @@ impl FileService {
             "File exists, proceeding with replacements"
         );
 
-        // Read file content
-        let content = async_fs::read_to_string(file_path).await?;
-        let lines: Vec<&str> = content.lines().collect();
+        // Replacement rewrites the whole file, so oversized files are refused up front
+        self.check_markdown_size(file_path).await?;
+        // Kept to put the file back when the rewrite doesn't verify
+        let original = async_fs::read(file_path).await?;
+        let DecodedText {
+            text: content,
+            encoding,
+        } = Self::decode_markdown(file_path, &original);
+        // Each line keeps its own terminator, so CRLF files and the final
+        // newline (or its absence) come back as they were
+        let (lines, endings): (Vec<&str>, Vec<&str>) = split_lines(&content).into_iter().unzip();
         let mut modified_lines = lines
             .iter()
             .map(|&s| s.to_string())
@@ -196,6 +1086,10 @@ impl FileService {
 
         let mut successful_replacements = 0;
         let mut failed_replacements = Vec::new();
+        let mut applied = Vec::new();
+        // Written changes with their byte offset before later (leftward) rewrites
+        // on the same line, and how much each one grew or shrank its line
+        let mut changes: Vec<(ReplacementChange, isize)> = Vec::new();
 
         // Group replacements by file path (should all be the same file in this call)
         let file_replacements: Vec<&LinkReplacement> = replacements
@@ -266,25 +1160,46 @@ impl FileService {
                 "Retrieved line content"
             );
 
-            // Find the old link in the line
-            if let Some(start_pos) = line.find(&replacement.old_link) {
-                log_debug!(
-                    operation = "find_old_link",
-                    file_path = %file_path,
-                    old_link = %replacement.old_link,
-                    found_position = start_pos,
-                    expected_column = replacement.column,
-                    "Found old link in line"
-                );
+            // Locate this occurrence of the old link by its column, not the first
+            // match on the line, so repeated links are rewritten one at a time
+            let expected_pos = replacement.column.saturating_sub(1); // Convert to 0-based
+            match nearest_occurrence(line, &replacement.old_link, expected_pos) {
+                Some(start_pos) if start_pos.abs_diff(expected_pos) <= COLUMN_TOLERANCE => {
+                    log_debug!(
+                        operation = "find_old_link",
+                        file_path = %file_path,
+                        old_link = %replacement.old_link,
+                        found_position = start_pos,
+                        expected_column = replacement.column,
+                        context = ?replacement.context,
+                        "Found old link in line"
+                    );
 
-                // Verify the position matches approximately (allow some tolerance for column differences)
-                let expected_pos = replacement.column.saturating_sub(1); // Convert to 0-based
-                if start_pos.abs_diff(expected_pos) <= 5 {
-                    // Allow 5 character tolerance
-                    // Replace the old link with the new link
-                    let new_line = line.replace(&replacement.old_link, &replacement.new_link);
+                    let new_line = rewrite_link(line, start_pos, replacement);
+                    let (offset, replaced_text, written_text) = changed_span(
+                        line,
+                        &new_line,
+                        start_pos,
+                        start_pos + replacement.old_link.len(),
+                    );
+                    changes.push((
+                        ReplacementChange {
+                            file_path: file_path.to_string(),
+                            line: replacement.line,
+                            column: offset,
+                            old_link: replacement.old_link.clone(),
+                            new_link: replacement.new_link.clone(),
+                            uploaded_url: None,
+                            object_key: None,
+                            history_record_id: None,
+                            replaced_text: replaced_text.to_string(),
+                            written_text: written_text.to_string(),
+                        },
+                        new_line.len() as isize - line.len() as isize,
+                    ));
                     modified_lines[line_index] = new_line.clone();
                     successful_replacements += 1;
+                    applied.push((*replacement).clone());
 
                     log_info!(
                         operation = "replacement_success",
@@ -295,7 +1210,8 @@ impl FileService {
                         new_line = %new_line,
                         "Successfully replaced link"
                     );
-                } else {
+                }
+                Some(start_pos) => {
                     log_warn!(
                         operation = "replacement_position_mismatch",
                         file_path = %file_path,
@@ -315,26 +1231,83 @@ impl FileService {
                         ),
                     });
                 }
-            } else {
-                log_error!(
-                    operation = "replacement_link_not_found",
-                    file_path = %file_path,
-                    line = replacement.line,
-                    line_content = %line,
-                    old_link = %replacement.old_link,
-                    "Old link not found in line"
-                );
+                None => {
+                    log_error!(
+                        operation = "replacement_link_not_found",
+                        file_path = %file_path,
+                        line = replacement.line,
+                        line_content = %line,
+                        old_link = %replacement.old_link,
+                        "Old link not found in line"
+                    );
 
-                failed_replacements.push(ReplacementError {
-                    replacement: (*replacement).clone(),
-                    error: format!("Old link not found in line: '{}'", replacement.old_link),
-                });
+                    failed_replacements.push(ReplacementError {
+                        replacement: (*replacement).clone(),
+                        error: format!("Old link not found in line: '{}'", replacement.old_link),
+                    });
+                }
             }
         }
 
-        // Write the modified content back to file
-        let new_content = modified_lines.join("\n");
-        async_fs::write(file_path, new_content).await?;
+        // Write the modified content back to file, in the encoding it was
+        // read in unless conversion was asked for
+        let new_content: String = modified_lines
+            .iter()
+            .zip(&endings)
+            .flat_map(|(line, ending)| [line.as_str(), ending])
+            .collect();
+        let mut converted_to_utf8 = encoding.is_some() && self.convert_to_utf8;
+        let new_bytes = if converted_to_utf8 {
+            new_content.into_bytes()
+        } else {
+            encode_text(&new_content, encoding)?
+        };
+        self.write_markdown(file_path, &new_bytes).await?;
+        if converted_to_utf8 {
+            log_info!(
+                operation = "replace_image_links",
+                file_path = %file_path,
+                encoding = encoding.map(|e| e.name()).unwrap_or_default(),
+                "Converted file to UTF-8"
+            );
+        }
+
+        // Rewrites ran right to left, so a change moves by the size difference of
+        // every change made after it on the same line
+        let mut written = Vec::with_capacity(changes.len());
+        for (index, (change, _)) in changes.iter().enumerate() {
+            let shift: isize = changes[index + 1..]
+                .iter()
+                .filter(|(later, _)| later.line == change.line)
+                .map(|(_, delta)| delta)
+                .sum();
+            let mut change = change.clone();
+            change.column = (change.column as isize + shift) as usize + 1;
+            written.push(change);
+        }
+        written.reverse();
+
+        let warnings = self.verify_rewrite(file_path, &content, &written).await;
+        let restored = !warnings.is_empty() && self.restore_unverified;
+        if !warnings.is_empty() {
+            log_warn!(
+                operation = "verify_replacement",
+                file_path = %file_path,
+                warnings = warnings.len(),
+                restored = restored,
+                "Rewritten file differs from the original beyond the replaced links"
+            );
+        }
+        if restored {
+            self.write_markdown(file_path, &original).await?;
+            failed_replacements.extend(applied.into_iter().map(|replacement| ReplacementError {
+                replacement,
+                error: "Restored, the rewritten file failed verification".to_string(),
+            }));
+            successful_replacements = 0;
+            written.clear();
+            converted_to_utf8 = false;
+        }
 
         Ok(ReplacementResult {
             file_path: file_path.to_string(),
@@ -342,10 +1315,159 @@ impl FileService {
             successful_replacements,
             failed_replacements,
             duration: std::time::SystemTime::now(),
+            changes: written,
+            encoding: encoding.map(|encoding| encoding.name().to_string()),
+            converted_to_utf8,
+            warnings,
+            restored,
         })
     }
 
-    /// Replace image links in multiple markdown files (batch operation)
+    async fn write_markdown(&self, file_path: &str, bytes: &[u8]) -> std::io::Result<()> {
+        #[cfg(test)]
+        if self.write_fault.as_deref() == Some(Path::new(file_path)) {
+            // Like a disk filling up mid-write
+            async_fs::write(file_path, &bytes[..bytes.len() / 2]).await?;
+            return Err(std::io::Error::other("injected write failure"));
+        }
+        retry_locked(|| async_fs::write(file_path, bytes)).await
+    }
+
+    /// Read `file_path` back after a rewrite and compare it with `before`, see
+    /// `compare_rewrite`
+    async fn verify_rewrite(
+        &self,
+        file_path: &str,
+        before: &str,
+        written: &[ReplacementChange],
+    ) -> Vec<ReplacementWarning> {
+        match self.read_markdown(file_path).await {
+            Ok(after) => self.compare_rewrite(before, &after.text, written),
+            Err(e) => vec![ReplacementWarning {
+                code: ReplacementWarningCode::Unreadable,
+                message: format!("Couldn't read the file back: {}", e),
+                line: None,
+            }],
+        }
+    }
+
+    /// Differences between `after` and `before` with `written` applied: each
+    /// change must be at its line and column, undoing the changes must give
+    /// back every original line byte for byte, line terminator included, and
+    /// rewritten lines keep their number of image references
+    fn compare_rewrite(
+        &self,
+        before: &str,
+        after: &str,
+        written: &[ReplacementChange],
+    ) -> Vec<ReplacementWarning> {
+        let warning = |code, line, message| ReplacementWarning {
+            code,
+            message,
+            line,
+        };
+        let before_lines = split_lines(before);
+        let after_lines = split_lines(after);
+        if before_lines.len() != after_lines.len() {
+            return vec![warning(
+                ReplacementWarningCode::LineCountChanged,
+                None,
+                format!(
+                    "{} lines before the rewrite, {} after",
+                    before_lines.len(),
+                    after_lines.len()
+                ),
+            )];
+        }
+
+        let mut changes_by_line: BTreeMap<usize, Vec<&ReplacementChange>> = BTreeMap::new();
+        for change in written {
+            changes_by_line.entry(change.line).or_default().push(change);
+        }
+
+        let mut warnings = Vec::new();
+        for (index, ((before_line, before_ending), (after_line, after_ending))) in
+            before_lines.iter().zip(&after_lines).enumerate()
+        {
+            let line = index + 1;
+            if before_ending != after_ending {
+                warnings.push(warning(
+                    ReplacementWarningCode::LineChanged,
+                    Some(line),
+                    format!(
+                        "Line ending changed from {:?} to {:?}",
+                        before_ending, after_ending
+                    ),
+                ));
+            }
+            let mut undone = after_line.to_string();
+            let mut changes = changes_by_line.get(&line).cloned().unwrap_or_default();
+            // Right to left, so undoing one doesn't move the others
+            changes.sort_by_key(|change| std::cmp::Reverse(change.column));
+            let mut all_found = true;
+            for change in changes {
+                let start = change.column.saturating_sub(1);
+                let end = start + change.written_text.len();
+                if undone.get(start..end) != Some(change.written_text.as_str()) {
+                    all_found = false;
+                    warnings.push(warning(
+                        ReplacementWarningCode::LinkMissing,
+                        Some(line),
+                        format!("{} isn't at column {}", change.new_link, change.column),
+                    ));
+                    continue;
+                }
+                undone = splice(&undone, start, end, &change.replaced_text);
+            }
+            if all_found && undone != *before_line {
+                warnings.push(warning(
+                    ReplacementWarningCode::LineChanged,
+                    Some(line),
+                    "Line differs from the original outside the replaced links".to_string(),
+                ));
+            }
+        }
+
+        // Rewrites that changed the syntax around a link (wiki embeds) may
+        // change what counts as a reference, their lines are left out
+        let before_refs = self.references_per_line(before);
+        let after_refs = self.references_per_line(after);
+        for (line, changes) in &changes_by_line {
+            if changes
+                .iter()
+                .any(|change| change.replaced_text != change.old_link)
+            {
+                continue;
+            }
+            let before_count = before_refs.get(line).copied().unwrap_or(0);
+            let after_count = after_refs.get(line).copied().unwrap_or(0);
+            if before_count != after_count {
+                warnings.push(warning(
+                    ReplacementWarningCode::ReferenceCountChanged,
+                    Some(*line),
+                    format!(
+                        "{} image reference(s) before the rewrite, {} after",
+                        before_count, after_count
+                    ),
+                ));
+            }
+        }
+        warnings
+    }
+
+    // Local and remote image references of `content`, counted per 1-based line
+    fn references_per_line(&self, content: &str) -> HashMap<usize, usize> {
+        let references = self.collect_references(content);
+        let mut counts = HashMap::new();
+        for reference in references.images.iter().chain(&references.remote) {
+            *counts.entry(reference.markdown_line).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Replace image links in multiple markdown files (batch operation). Every
+    /// file is claimed before the first is touched; when another replacement
+    /// is rewriting one of them, nothing is done.
     pub async fn replace_image_links_batch(
         &self,
         replacements: Vec<LinkReplacement>,
@@ -379,6 +1501,10 @@ impl FileService {
                 .push(replacement);
         }
 
+        let skipped_read_only = self.check_writable(file_groups.keys()).await?;
+        file_groups.retain(|path, _| !skipped_read_only.iter().any(|f| &f.file_path == path));
+        let _locks = lock_files(file_groups.keys(), self.operation)?;
+
         log_info!(
             operation = "replace_image_links_batch",
             total_files = file_groups.len(),
@@ -401,7 +1527,7 @@ impl FileService {
             );
 
             match self
-                .replace_image_links(&file_path, file_replacements)
+                .replace_locked_file_links(&file_path, file_replacements)
                 .await
             {
                 Ok(result) => {
@@ -437,10 +1563,16 @@ impl FileService {
                                 column: 0,
                                 old_link: String::new(),
                                 new_link: String::new(),
+                                context: LinkContext::MarkdownInline,
                             },
                             error: format!("File processing failed: {}", e),
                         }],
                         duration: SystemTime::now(),
+                        changes: Vec::new(),
+                        encoding: None,
+                        converted_to_utf8: false,
+                        warnings: Vec::new(),
+                        restored: false,
                     };
                     total_failed += 1;
                     results.push(failed_result);
@@ -457,8 +1589,342 @@ impl FileService {
             total_failed_replacements: total_failed,
             duration,
             timestamp: SystemTime::now(),
+            skipped_read_only,
+            backups: Vec::new(),
+        })
+    }
+
+    /// All-or-nothing variant of `replace_image_links_batch`.
+    ///
+    /// Every target file is backed up before the first one is touched. If a file
+    /// can't be read or written, the files already rewritten are restored from
+    /// those backups and the error names the file that caused the abort. Links
+    /// that simply aren't found don't abort; they are reported as before.
+    pub async fn replace_image_links_batch_atomic(
+        &self,
+        replacements: Vec<LinkReplacement>,
+        backup_service: &BackupService,
+    ) -> Result<BatchReplacementResult> {
+        let start_time = std::time::Instant::now();
+
+        // Sorted so a failed run always stops at the same file
+        let mut file_groups: BTreeMap<String, Vec<LinkReplacement>> = BTreeMap::new();
+        for replacement in replacements {
+            file_groups
+                .entry(replacement.file_path.clone())
+                .or_default()
+                .push(replacement);
+        }
+
+        let skipped_read_only = self.check_writable(file_groups.keys()).await?;
+        file_groups.retain(|path, _| !skipped_read_only.iter().any(|f| &f.file_path == path));
+        let _locks = lock_files(file_groups.keys(), self.operation)?;
+
+        log_info!(
+            operation = "replace_image_links_batch_atomic",
+            total_files = file_groups.len(),
+            "Backing up files before atomic replacement"
+        );
+
+        let mut backups = HashMap::with_capacity(file_groups.len());
+        for file_path in file_groups.keys() {
+            let backup = backup_service.create_backup(file_path).await?;
+            backups.insert(file_path.clone(), backup);
+        }
+
+        let total_files = file_groups.len();
+        let mut results = Vec::with_capacity(total_files);
+        let mut modified: Vec<&str> = Vec::new();
+        for (file_path, file_replacements) in &file_groups {
+            match self
+                .replace_locked_file_links(file_path, file_replacements.clone())
+                .await
+            {
+                Ok(result) => {
+                    modified.push(file_path);
+                    results.push(result);
+                }
+                Err(e) => {
+                    log_error!(
+                        operation = "replace_image_links_batch_atomic",
+                        file_path = %file_path,
+                        error = %e,
+                        restoring = modified.len(),
+                        "File failed, rolling back the batch"
+                    );
+
+                    // The failed file may have been written before the error
+                    if changed_since_backup(file_path, &backups[file_path]).await {
+                        modified.push(file_path);
+                    }
+                    let mut restore_errors = Vec::new();
+                    for done in &modified {
+                        if let Err(restore_error) =
+                            backup_service.restore_backup(&backups[*done]).await
+                        {
+                            restore_errors.push(restore_error.to_string());
+                        }
+                    }
+
+                    let mut message = format!(
+                        "Atomic replacement aborted at {}: {}. Restored {} of {} modified file(s)",
+                        file_path,
+                        e,
+                        modified.len() - restore_errors.len(),
+                        modified.len()
+                    );
+                    if !restore_errors.is_empty() {
+                        message.push_str(&format!(
+                            "; restore failures: {}",
+                            restore_errors.join("; ")
+                        ));
+                    }
+                    return Err(AppError::FileSystem(message));
+                }
+            }
+        }
+
+        Ok(BatchReplacementResult {
+            total_successful_replacements: results.iter().map(|r| r.successful_replacements).sum(),
+            total_failed_replacements: results.iter().map(|r| r.failed_replacements.len()).sum(),
+            results,
+            total_files,
+            duration: start_time.elapsed(),
+            timestamp: SystemTime::now(),
+            skipped_read_only,
+            backups: file_groups
+                .keys()
+                .map(|path| backups[path].clone())
+                .collect(),
         })
     }
+
+    /// Existing files among `file_paths` that can't be opened for writing:
+    /// read-only, or locked by another program past one retry on Windows
+    pub async fn unwritable_files<'a>(
+        &self,
+        file_paths: impl IntoIterator<Item = &'a String>,
+    ) -> Vec<UnwritableFile> {
+        // Opening for write without truncating leaves the content alone
+        let mut options = async_fs::OpenOptions::new();
+        options.write(true);
+
+        let mut unwritable = Vec::new();
+        for file_path in file_paths {
+            // Missing files fail their own replacements as before
+            if !Path::new(file_path).is_file() {
+                continue;
+            }
+            if let Err(e) = retry_locked(|| options.open(file_path)).await {
+                unwritable.push(UnwritableFile {
+                    file_path: file_path.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+        unwritable
+    }
+
+    // Checked before any file of a batch is touched, so an abort leaves every
+    // file as it was. Under `Skip` the unwritable files are returned for the
+    // caller to leave out.
+    async fn check_writable<'a>(
+        &self,
+        file_paths: impl IntoIterator<Item = &'a String>,
+    ) -> Result<Vec<UnwritableFile>> {
+        let unwritable = self.unwritable_files(file_paths).await;
+        if unwritable.is_empty() {
+            return Ok(unwritable);
+        }
+
+        let listed: Vec<String> = unwritable
+            .iter()
+            .map(|file| format!("{} ({})", file.file_path, file.error))
+            .collect();
+        if self.read_only_policy == ReadOnlyPolicy::Skip {
+            log_warn!(
+                operation = "replace_check_writable",
+                skipped_files = unwritable.len(),
+                files = %listed.join("; "),
+                "Skipping files that can't be written"
+            );
+            return Ok(unwritable);
+        }
+        Err(AppError::PermissionDenied(format!(
+            "{} file(s) can't be written, nothing was changed: {}",
+            unwritable.len(),
+            listed.join("; ")
+        )))
+    }
+}
+
+/// Plain sha256 hex of a file's bytes, read in chunks
+async fn file_content_hash(file_path: &str) -> std::io::Result<String> {
+    let mut file = async_fs::File::open(file_path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether `file_path` no longer matches its backup; unreadable counts as changed
+async fn changed_since_backup(file_path: &str, backup: &BackupInfo) -> bool {
+    match async_fs::read(file_path).await {
+        Ok(content) => format!("{:x}", Sha256::digest(&content)) != backup.checksum,
+        Err(_) => true,
+    }
+}
+
+/// Run a file operation, retrying once after a short delay when Windows
+/// reports the file as locked
+async fn retry_locked<T, F, Fut>(mut operation: F) -> std::io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::io::Result<T>>,
+{
+    match operation().await {
+        Err(e) if is_transient_lock(&e) => {
+            log_debug!(
+                operation = "retry_locked_file",
+                error = %e,
+                "File locked, retrying once"
+            );
+            tokio::time::sleep(LOCK_RETRY_DELAY).await;
+            operation().await
+        }
+        result => result,
+    }
+}
+
+// Sharing and lock violations, plus the access denied some scanners cause
+fn is_transient_lock(error: &std::io::Error) -> bool {
+    cfg!(windows)
+        && (matches!(error.raw_os_error(), Some(32) | Some(33))
+            || error.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+/// Byte offset of the occurrence of `needle` in `line` closest to `expected`
+fn nearest_occurrence(line: &str, needle: &str, expected: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    line.match_indices(needle)
+        .map(|(pos, _)| pos)
+        .min_by_key(|pos| pos.abs_diff(expected))
+}
+
+/// Replace the old link starting at byte `start` of `line`, escaping the new
+/// link for the syntax it is written into
+fn rewrite_link(line: &str, start: usize, replacement: &LinkReplacement) -> String {
+    let end = start + replacement.old_link.len();
+    let url = &replacement.new_link;
+
+    match replacement.context {
+        LinkContext::MarkdownInline => splice(line, start, end, &escape_markdown_url(url)),
+        // A definition ends at the first space, which would start a title
+        LinkContext::ReferenceDefinition | LinkContext::FrontMatter => {
+            splice(line, start, end, &url.replace(' ', "%20"))
+        }
+        LinkContext::HtmlAttribute => splice(line, start, end, &escape_html_attribute(url)),
+        LinkContext::WikiEmbed => rewrite_wiki_embed(line, start, end, url)
+            .unwrap_or_else(|| splice(line, start, end, &escape_markdown_url(url))),
+        LinkContext::Verbatim => splice(line, start, end, url),
+    }
+}
+
+/// The part of `before` that a rewrite changed into `after`, as its byte offset,
+/// the old text and the new text. The span always covers `start..end`, the link
+/// being replaced, and grows to cover any syntax the rewrite changed around it.
+fn changed_span<'a>(
+    before: &'a str,
+    after: &'a str,
+    start: usize,
+    end: usize,
+) -> (usize, &'a str, &'a str) {
+    let mut prefix = before
+        .bytes()
+        .zip(after.bytes())
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(start);
+    while !before.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let mut suffix = before
+        .bytes()
+        .rev()
+        .zip(after.bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(before.len() - end)
+        .min(after.len() - prefix);
+    while !before.is_char_boundary(before.len() - suffix) {
+        suffix -= 1;
+    }
+
+    (
+        prefix,
+        &before[prefix..before.len() - suffix],
+        &after[prefix..after.len() - suffix],
+    )
+}
+
+fn splice(line: &str, start: usize, end: usize, text: &str) -> String {
+    format!("{}{}{}", &line[..start], text, &line[end..])
+}
+
+// Spaces and parentheses would end a `(...)` destination early
+fn escape_markdown_url(url: &str) -> String {
+    url.replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+}
+
+// Lines of `text` with their terminators ("\r\n", "\n", or "" for a last line
+// without one), numbered the way `str::lines` numbers them
+fn split_lines(text: &str) -> Vec<(&str, &str)> {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let body = line
+                .strip_suffix("\r\n")
+                .or_else(|| line.strip_suffix('\n'))
+                .unwrap_or(line);
+            (body, &line[body.len()..])
+        })
+        .collect()
+}
+
+// Attribute values are HTML-escaped; spaces and commas are also encoded because
+// inside srcset they separate the URL from its descriptor and the next entry
+fn escape_html_attribute(url: &str) -> String {
+    escape_html(url).replace(' ', "%20").replace(',', "%2C")
+}
+
+// `![[local.png|alt]]` can't point at a URL, so the whole embed becomes `![alt](url)`
+fn rewrite_wiki_embed(line: &str, start: usize, end: usize, url: &str) -> Option<String> {
+    let embed_start = line[..start].strip_suffix("![[")?.len();
+    let close = end + line[end..].find("]]")?;
+    let rest = &line[end..close];
+    let alt = match rest.strip_prefix('|') {
+        Some(alt) => alt,
+        None if rest.is_empty() => "",
+        None => return None,
+    };
+
+    Some(format!(
+        "{}![{}]({}){}",
+        &line[..embed_start],
+        alt,
+        escape_markdown_url(url),
+        &line[close + 2..]
+    ))
 }
 
 #[cfg(test)]
@@ -494,6 +1960,182 @@ mod tests {
         Ok(image_path)
     }
 
+    fn scan_result(file_path: &Path, images: Vec<ImageReference>) -> ScanResult {
+        ScanResult {
+            file_path: file_path.to_string_lossy().to_string(),
+            images,
+            status: ScanStatus::Success,
+            error: None,
+            missing_images: vec![],
+            unique_image_count: 0,
+            warnings: vec![],
+            encoding: None,
+            content_hash: None,
+        }
+    }
+
+    fn reference(image_path: &Path, original: &str, line: usize) -> ImageReference {
+        let mut image = ImageReference::new(
+            original.to_string(),
+            image_path.to_string_lossy().to_string(),
+            line,
+            1,
+        );
+        image.exists = image_path.exists();
+        image
+    }
+
+    #[tokio::test]
+    async fn test_group_scan_results_same_image_from_two_files() {
+        let temp_dir = tempdir().unwrap();
+        let image = create_temp_image_file(temp_dir.path(), "diagram.png")
+            .await
+            .unwrap();
+        let doc_a = temp_dir.path().join("a.md");
+        let doc_b = temp_dir.path().join("b.md");
+
+        let results = vec![
+            scan_result(&doc_a, vec![reference(&image, "./diagram.png", 3)]),
+            scan_result(&doc_b, vec![reference(&image, "diagram.png", 9)]),
+        ];
+
+        let service = FileService::new().unwrap();
+        let groups = service.group_scan_results(&results).await.unwrap();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].usages.len(), 2);
+        assert!(groups[0].duplicate_paths.is_empty());
+
+        let replacements =
+            groups[0].link_replacements("https://cdn.example.com/diagram.png", false);
+        assert_eq!(replacements.len(), 2);
+        assert_eq!(replacements[0].old_link, "./diagram.png");
+        assert_eq!(replacements[1].old_link, "diagram.png");
+        assert_eq!(replacements[1].line, 9);
+        assert!(replacements
+            .iter()
+            .all(|r| r.new_link == "https://cdn.example.com/diagram.png"));
+    }
+
+    #[tokio::test]
+    async fn test_group_scan_results_identical_content_different_names() {
+        let temp_dir = tempdir().unwrap();
+        let first = create_temp_image_file(temp_dir.path(), "logo.png")
+            .await
+            .unwrap();
+        let copy = create_temp_image_file(temp_dir.path(), "logo-copy.png")
+            .await
+            .unwrap();
+        let missing = temp_dir.path().join("missing.png");
+        let doc = temp_dir.path().join("doc.md");
+
+        let results = vec![scan_result(
+            &doc,
+            vec![
+                reference(&first, "logo.png", 1),
+                reference(&copy, "logo-copy.png", 2),
+                reference(&missing, "missing.png", 3),
+            ],
+        )];
+
+        let service = FileService::new().unwrap();
+        let groups = service.group_scan_results(&results).await.unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].usages.len(), 2);
+        assert_eq!(groups[0].duplicate_paths.len(), 1);
+        assert!(groups[0].duplicate_paths[0].ends_with("logo-copy.png"));
+        assert!(!groups[1].exists);
+        assert!(groups[1].checksum.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_markdown_files_recursive_and_sorted() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        async_fs::create_dir_all(root.join("posts/2024"))
+            .await
+            .unwrap();
+        async_fs::create_dir_all(root.join(".git")).await.unwrap();
+        async_fs::write(root.join("b.md"), "# b").await.unwrap();
+        async_fs::write(root.join("posts/2024/a.markdown"), "# a")
+            .await
+            .unwrap();
+        async_fs::write(root.join("posts/notes.txt"), "text")
+            .await
+            .unwrap();
+        async_fs::write(root.join(".git/HEAD.md"), "hidden")
+            .await
+            .unwrap();
+
+        let service = FileService::new().unwrap();
+        let files = service
+            .list_markdown_files(&root.to_string_lossy())
+            .await
+            .unwrap()
+            .files;
+
+        let expected: Vec<String> = vec![
+            root.join("b.md").to_string_lossy().to_string(),
+            root.join("posts/2024/a.markdown")
+                .to_string_lossy()
+                .to_string(),
+        ];
+        assert_eq!(files, expected);
+    }
+
+    #[tokio::test]
+    async fn test_list_markdown_files_honors_ignore_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        for dir in ["posts", "public/posts", "drafts", "notes/.trash"] {
+            async_fs::create_dir_all(root.join(dir)).await.unwrap();
+        }
+        for file in [
+            "posts/a.md",
+            "public/posts/a.md",
+            "public/index.md",
+            "drafts/wip.md",
+            "notes/keep.md",
+            "notes/scratch.md",
+            "notes/.trash/old.md",
+        ] {
+            async_fs::write(root.join(file), "# doc").await.unwrap();
+        }
+        async_fs::write(root.join(".gitignore"), "public/\n")
+            .await
+            .unwrap();
+        async_fs::write(root.join(".imgtossignore"), "drafts/\n")
+            .await
+            .unwrap();
+        async_fs::write(root.join("notes/.ignore"), "scratch.md\n")
+            .await
+            .unwrap();
+
+        let directory = root.to_string_lossy().to_string();
+        let listing = FileService::new()
+            .unwrap()
+            .list_markdown_files(&directory)
+            .await
+            .unwrap();
+        let expected: Vec<String> = ["notes/keep.md", "posts/a.md"]
+            .iter()
+            .map(|file| root.join(file).to_string_lossy().to_string())
+            .collect();
+        assert_eq!(listing.files, expected);
+        // Hidden .trash isn't an ignored file, it's never listed
+        assert_eq!(listing.ignored, 4);
+
+        let listing = FileService::new()
+            .unwrap()
+            .with_ignore_files(false)
+            .list_markdown_files(&directory)
+            .await
+            .unwrap();
+        assert_eq!(listing.files.len(), 6);
+        assert_eq!(listing.ignored, 0);
+    }
+
     #[tokio::test]
     async fn test_file_service_creation() {
         let service = FileService::new();
@@ -516,7 +2158,7 @@ And one more: ![](relative/path/image.gif)
 This should be ignored: ![Remote](https://example.com/image.png)
 "#;
 
-        let images = service.extract_image_references(content).await.unwrap();
+        let images = service.collect_references(content).images;
 
         assert_eq!(images.len(), 3);
 
@@ -534,65 +2176,623 @@ This should be ignored: ![Remote](https://example.com/image.png)
     }
 
     #[tokio::test]
-    async fn test_extract_image_references_html_syntax() {
-        let service = FileService::new().unwrap();
-
-        let content = r#"
-<img src="./test.png" alt="Test" />
-<img src="../another.jpg" width="100" height="200" />
-<img src="https://example.com/remote.png" />
-"#;
-
-        let images = service.extract_image_references(content).await.unwrap();
+    async fn test_extract_image_references_html_syntax() {
+        let service = FileService::new().unwrap();
+
+        let content = r#"
+<img src="./test.png" alt="Test" />
+<img src="../another.jpg" width="100" height="200" />
+<img src="https://example.com/remote.png" />
+"#;
+
+        let images = service.collect_references(content).images;
+
+        assert_eq!(images.len(), 2); // Remote image should be ignored
+        assert_eq!(images[0].original_path, "./test.png");
+        assert_eq!(images[1].original_path, "../another.jpg");
+    }
+
+    #[tokio::test]
+    async fn test_scan_single_file_with_existing_images() {
+        let temp_dir = tempdir().unwrap();
+        let temp_dir_path = temp_dir.path();
+
+        // Create test images
+        create_temp_image_file(temp_dir_path, "test1.png")
+            .await
+            .unwrap();
+        create_temp_image_file(temp_dir_path, "test2.jpg")
+            .await
+            .unwrap();
+
+        // Create markdown content
+        let md_content = format!(
+            r#"
+# Test Document
+
+![Image 1]({}/test1.png)
+![Image 2]({}/test2.jpg)
+![Missing](./missing.png)
+"#,
+            temp_dir_path.display(),
+            temp_dir_path.display()
+        );
+
+        let md_file = create_temp_md_file(&md_content).await.unwrap();
+
+        let service = FileService::new().unwrap();
+        let result = service
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        assert!(matches!(result.status, ScanStatus::Success));
+        assert_eq!(result.images.len(), 3);
+
+        // Check existing images
+        assert!(result.images[0].exists);
+        assert!(result.images[0].size > 0);
+        assert!(result.images[1].exists);
+        assert!(result.images[1].size > 0);
+
+        // Check missing image
+        assert!(!result.images[2].exists);
+        assert_eq!(result.images[2].size, 0);
+
+        // Missing references are listed separately with their location
+        assert!(result.error.is_none());
+        assert_eq!(result.missing_images.len(), 1);
+        assert_eq!(result.missing_images[0].original_path, "./missing.png");
+        assert_eq!(result.missing_images[0].line, 6);
+    }
+
+    #[tokio::test]
+    async fn test_scan_groups_repeated_references() {
+        let temp_dir = tempdir().unwrap();
+        create_temp_image_file(temp_dir.path(), "chart.png")
+            .await
+            .unwrap();
+        create_temp_image_file(temp_dir.path(), "other.png")
+            .await
+            .unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        async_fs::write(
+            &md_file,
+            "![a](./chart.png)\n![b](./other.png)\n![c](./chart.png)\n![d](./chart.png)\n",
+        )
+        .await
+        .unwrap();
+
+        let service = FileService::new().unwrap();
+        let result = service
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        assert_eq!(result.images.len(), 4);
+        assert_eq!(result.unique_image_count, 2);
+        let group = result.images[0].reference_group.clone().unwrap();
+        assert_eq!(group, result.images[0].id);
+        assert_eq!(result.images[2].reference_group.as_ref(), Some(&group));
+        assert_eq!(result.images[3].reference_group.as_ref(), Some(&group));
+        assert!(result.images[1].reference_group.is_none());
+
+        // Grouping survives the round trip to the frontend
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: ScanResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.images[3].reference_group, Some(group));
+        assert_eq!(parsed.unique_image_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_scan_groups_different_paths_to_same_file() {
+        let temp_dir = tempdir().unwrap();
+        std::fs::create_dir(temp_dir.path().join("img")).unwrap();
+        create_temp_image_file(&temp_dir.path().join("img"), "logo.png")
+            .await
+            .unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        async_fs::write(
+            &md_file,
+            "![a](img/logo.png)\n![b](./img/../img/logo.png)\n",
+        )
+        .await
+        .unwrap();
+
+        let service = FileService::new().unwrap();
+        let result = service
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        assert_eq!(result.images.len(), 2);
+        assert_eq!(result.unique_image_count, 1);
+        assert!(result.images[0].reference_group.is_some());
+        assert_eq!(
+            result.images[0].reference_group,
+            result.images[1].reference_group
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_flags_oversized_images() {
+        let temp_dir = tempdir().unwrap();
+        create_temp_image_file(temp_dir.path(), "small.png")
+            .await
+            .unwrap();
+        async_fs::write(temp_dir.path().join("huge.gif"), vec![0u8; 4096])
+            .await
+            .unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        async_fs::write(&md_file, "![a](./small.png)\n![b](./huge.gif)\n")
+            .await
+            .unwrap();
+
+        let service = FileService::new().unwrap().with_max_image_size(1024);
+        let result = service
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        assert!(matches!(result.status, ScanStatus::Success));
+        assert_eq!(result.images.len(), 2);
+        assert!(!result.images[0].too_large);
+        assert!(result.images[1].exists);
+        assert!(result.images[1].too_large);
+    }
+
+    #[tokio::test]
+    async fn test_revalidate_reports_what_changed_since_the_scan() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("a.png"), b"first image").unwrap();
+        std::fs::write(dir.join("b.png"), b"second image").unwrap();
+        let edited = dir.join("edited.md");
+        let untouched = dir.join("untouched.md");
+        std::fs::write(&edited, "![a](./a.png)\n").unwrap();
+        std::fs::write(&untouched, "![b](./b.png)\n").unwrap();
+
+        let service = FileService::new().unwrap();
+        let results = service
+            .scan_markdown_files(vec![
+                edited.to_string_lossy().to_string(),
+                untouched.to_string_lossy().to_string(),
+            ])
+            .await
+            .unwrap();
+        assert!(results.iter().all(|result| result.content_hash.is_some()));
+        let fresh = service.revalidate_scan_results(&results).await;
+        assert!(fresh.changed_files.is_empty());
+        assert!(fresh.newly_missing.is_empty() && fresh.changed_images.is_empty());
+
+        std::fs::write(&edited, "# Title\n\n![a](./a.png)\n").unwrap();
+        std::fs::remove_file(dir.join("a.png")).unwrap();
+        std::fs::write(dir.join("b.png"), b"second image, edited").unwrap();
+        let stale = service.revalidate_scan_results(&results).await;
+        assert_eq!(
+            stale.changed_files,
+            vec![edited.to_string_lossy().to_string()]
+        );
+        assert_eq!(stale.newly_missing.len(), 1);
+        assert_eq!(stale.newly_missing[0].original_path, "./a.png");
+        assert_eq!(
+            stale.changed_images,
+            vec![results[1].images[0].absolute_path.clone()]
+        );
+
+        std::fs::remove_file(&edited).unwrap();
+        let deleted = service.revalidate_scan_results(&results).await;
+        assert_eq!(deleted.changed_files.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_scan_progress_and_cancellation() {
+        let temp_dir = tempdir().unwrap();
+        let mut files = Vec::new();
+        for name in ["a.md", "b.md", "c.md"] {
+            let path = temp_dir.path().join(name);
+            async_fs::write(&path, "![x](./x.png)\n![y](./y.png)\n")
+                .await
+                .unwrap();
+            files.push(path.to_string_lossy().to_string());
+        }
+
+        let service = FileService::new().unwrap();
+        let mut seen = Vec::new();
+        let results = service
+            .scan_markdown_files_with_progress(
+                files.clone(),
+                &AtomicBool::new(false),
+                |index, result| seen.push((index, result.images.len())),
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(seen, vec![(0, 2), (1, 2), (2, 2)]);
+
+        // Cancelled after the first file: the rest is never scanned
+        let cancelled = AtomicBool::new(false);
+        let results = service
+            .scan_markdown_files_with_progress(files, &cancelled, |_, _| {
+                cancelled.store(true, Ordering::Relaxed)
+            })
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_scan_warnings_keep_the_file_successful() {
+        let temp_dir = tempdir().unwrap();
+        create_temp_image_file(temp_dir.path(), "ok.png")
+            .await
+            .unwrap();
+        // Exists but can't be read as an image file
+        async_fs::create_dir(temp_dir.path().join("unreadable.png"))
+            .await
+            .unwrap();
+        let md_file = temp_dir.path().join("doc.md");
+        async_fs::write(
+            &md_file,
+            "![ok](./ok.png)\n![bad](./unreadable.png)\n```md\n![example](./example.png)\n```\n![home](~/pics/a.png)\n",
+        )
+        .await
+        .unwrap();
+
+        let service = FileService::new().unwrap();
+        let result = service
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, ScanStatus::Success));
+        assert!(result.error.is_none());
+
+        let paths: Vec<_> = result
+            .images
+            .iter()
+            .map(|i| i.original_path.as_str())
+            .collect();
+        assert_eq!(paths, ["./ok.png", "./unreadable.png", "~/pics/a.png"]);
+
+        let warnings: Vec<_> = result
+            .warnings
+            .iter()
+            .map(|w| (w.code, w.image_path.as_deref().unwrap(), w.line.unwrap()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (ScanWarningCode::UnreadableImage, "./unreadable.png", 2),
+                (ScanWarningCode::CodeFence, "./example.png", 4),
+                (ScanWarningCode::SuspiciousPath, "~/pics/a.png", 6),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_globs_filter_images_and_files() {
+        let temp_dir = tempdir().unwrap();
+        for dir in ["drafts", "node_modules/theme"] {
+            async_fs::create_dir_all(temp_dir.path().join(dir))
+                .await
+                .unwrap();
+        }
+        for image in [
+            "keep.png",
+            "drafts/wip.png",
+            "node_modules/theme/sample.png",
+        ] {
+            create_temp_image_file(temp_dir.path(), image)
+                .await
+                .unwrap();
+        }
+        let post = temp_dir.path().join("post.md");
+        async_fs::write(
+            &post,
+            "![a](./keep.png)\n![b](./drafts/wip.png)\n![c](node_modules/theme/sample.png)\n",
+        )
+        .await
+        .unwrap();
+        let theme_readme = temp_dir.path().join("node_modules/theme/README.md");
+        async_fs::write(&theme_readme, "![c](./sample.png)\n")
+            .await
+            .unwrap();
+
+        let globs = ScanGlobs::new(
+            &[],
+            &["drafts/**".to_string(), "node_modules".to_string()],
+            &["node_modules".to_string()],
+        )
+        .unwrap();
+        let service = FileService::new().unwrap().with_scan_globs(globs);
+        let results = service
+            .scan_markdown_files(vec![
+                post.to_string_lossy().to_string(),
+                theme_readme.to_string_lossy().to_string(),
+            ])
+            .await
+            .unwrap();
+
+        let post_result = &results[0];
+        assert!(matches!(post_result.status, ScanStatus::Success));
+        assert_eq!(post_result.images.len(), 1);
+        assert_eq!(post_result.images[0].original_path, "./keep.png");
+        let filtered: Vec<_> = post_result
+            .warnings
+            .iter()
+            .map(|w| (w.code, w.line.unwrap(), w.message.as_str()))
+            .collect();
+        assert_eq!(
+            filtered,
+            [
+                (
+                    ScanWarningCode::Filtered,
+                    2,
+                    "Matches exclude_image_globs pattern `drafts/**`"
+                ),
+                (
+                    ScanWarningCode::Filtered,
+                    3,
+                    "Matches exclude_image_globs pattern `node_modules`"
+                ),
+            ]
+        );
+
+        let readme_result = &results[1];
+        assert!(matches!(readme_result.status, ScanStatus::Skipped));
+        assert!(readme_result.images.is_empty());
+        assert_eq!(readme_result.warnings[0].code, ScanWarningCode::Filtered);
+        assert!(readme_result.warnings[0]
+            .message
+            .contains("exclude_file_globs"));
+    }
+
+    #[tokio::test]
+    async fn test_asset_roots_resolve_site_paths() {
+        // Hugo-style site: content/ holds the pages, static/ is served from /
+        let site = tempdir().unwrap();
+        let static_dir = site.path().join("static");
+        let content_dir = site.path().join("content/posts");
+        async_fs::create_dir_all(static_dir.join("images"))
+            .await
+            .unwrap();
+        async_fs::create_dir_all(&content_dir).await.unwrap();
+        create_temp_image_file(&static_dir.join("images"), "logo.png")
+            .await
+            .unwrap();
+        create_temp_image_file(&static_dir, "banner.png")
+            .await
+            .unwrap();
+        create_temp_image_file(&content_dir, "local.png")
+            .await
+            .unwrap();
+        let md_file = content_dir.join("post.md");
+        async_fs::write(
+            &md_file,
+            "![](/images/logo.png)\n![](banner.png)\n![](local.png)\n![](/images/gone.png)\n",
+        )
+        .await
+        .unwrap();
+        let md_file = md_file.to_string_lossy().to_string();
+
+        let service = FileService::new()
+            .unwrap()
+            .with_asset_roots(vec![site.path().join("themes/none"), static_dir.clone()]);
+        let result = service
+            .scan_single_file(&md_file, &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        let root = Some(static_dir.to_string_lossy().to_string());
+        let resolved: Vec<_> = result
+            .images
+            .iter()
+            .map(|i| (i.exists, i.asset_root.clone()))
+            .collect();
+        assert_eq!(
+            resolved,
+            [
+                (true, root.clone()),
+                (true, root),
+                (true, None),
+                (false, None)
+            ]
+        );
+        assert_eq!(
+            result.images[0].absolute_path,
+            static_dir.join("images/logo.png").to_string_lossy()
+        );
+        assert_eq!(result.missing_images.len(), 1);
+
+        // Without roots the site paths are missing
+        let result = FileService::new()
+            .unwrap()
+            .scan_single_file(&md_file, &AtomicBool::new(false))
+            .await
+            .unwrap();
+        assert_eq!(result.missing_images.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_link_query_and_fragment_are_split_off() {
+        let temp_dir = tempdir().unwrap();
+        create_temp_image_file(temp_dir.path(), "pic.png")
+            .await
+            .unwrap();
+        create_temp_image_file(temp_dir.path(), "diagram.svg")
+            .await
+            .unwrap();
+        let md_file = temp_dir.path().join("README.md");
+        async_fs::write(
+            &md_file,
+            "![x](./pic.png?raw=true)\n![y](diagram.svg#light \"Light\")\n<img src=\"pic.png?v=2#top\">\n",
+        )
+        .await
+        .unwrap();
+        let md_file = md_file.to_string_lossy().to_string();
+
+        let service = FileService::new().unwrap();
+        let result = service
+            .scan_single_file(&md_file, &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        let found: Vec<_> = result
+            .images
+            .iter()
+            .map(|i| (i.original_path.as_str(), i.path_suffix.as_deref(), i.exists))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("./pic.png?raw=true", Some("?raw=true"), true),
+                ("diagram.svg#light", Some("#light"), true),
+                ("pic.png?v=2#top", Some("?v=2#top"), true),
+            ]
+        );
+        assert_eq!(result.images[0].bare_path(), "./pic.png");
+        assert!(result.missing_images.is_empty());
+        assert!(result.warnings.is_empty());
+
+        // Replacement matches the full link text including the suffix
+        let groups = service
+            .group_scan_results(std::slice::from_ref(&result))
+            .await
+            .unwrap();
+        let replacements: Vec<_> = groups
+            .iter()
+            .flat_map(|group| group.link_replacements("https://cdn.example.com/p.png", true))
+            .collect();
+        let replaced = service
+            .replace_image_links(&md_file, replacements)
+            .await
+            .unwrap();
+        assert_eq!(replaced.successful_replacements, 3);
+
+        let content = async_fs::read_to_string(&md_file).await.unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            [
+                "![x](https://cdn.example.com/p.png?raw=true)",
+                "![y](https://cdn.example.com/p.png#light \"Light\")",
+                "<img src=\"https://cdn.example.com/p.png?v=2#top\">",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_all_image_references_include_remote_links() {
+        let temp_dir = tempdir().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        async_fs::write(
+            &md_file,
+            "<img src=\"https://cdn.example.com/b.png\"> ![a](./a.png)\n```\n![x](https://cdn.example.com/x.png)\n```\n![c](http://old.example.com/c.png?v=1)\n",
+        )
+        .await
+        .unwrap();
+
+        let references = FileService::new()
+            .unwrap()
+            .all_image_references(&md_file.to_string_lossy())
+            .await
+            .unwrap();
+        let found: Vec<_> = references
+            .iter()
+            .map(|r| (r.original_path.as_str(), r.markdown_line, r.context))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "https://cdn.example.com/b.png",
+                    1,
+                    LinkContext::HtmlAttribute
+                ),
+                ("./a.png", 1, LinkContext::MarkdownInline),
+                (
+                    "http://old.example.com/c.png?v=1",
+                    5,
+                    LinkContext::MarkdownInline
+                ),
+            ]
+        );
+    }
 
-        assert_eq!(images.len(), 2); // Remote image should be ignored
-        assert_eq!(images[0].original_path, "./test.png");
-        assert_eq!(images[1].original_path, "../another.jpg");
+    #[test]
+    fn test_split_link_suffix() {
+        assert_eq!(split_link_suffix("a.png"), ("a.png", None));
+        assert_eq!(split_link_suffix("a.PNG?raw=1"), ("a.PNG", Some("?raw=1")));
+        assert_eq!(
+            split_link_suffix("a#1.png#dark"),
+            ("a#1.png", Some("#dark"))
+        );
+        assert_eq!(
+            split_link_suffix("a.png?x=b.png"),
+            ("a.png", Some("?x=b.png"))
+        );
     }
 
     #[tokio::test]
-    async fn test_scan_single_file_with_existing_images() {
+    async fn test_oversized_markdown_is_skipped() {
         let temp_dir = tempdir().unwrap();
-        let temp_dir_path = temp_dir.path();
-
-        // Create test images
-        create_temp_image_file(temp_dir_path, "test1.png")
+        let path = temp_dir.path().join("huge.md");
+        async_fs::write(&path, "![x](./x.png)\n".repeat(200))
             .await
             .unwrap();
-        create_temp_image_file(temp_dir_path, "test2.jpg")
+        let path = path.to_string_lossy().to_string();
+
+        let service = FileService::new().unwrap().with_max_markdown_size(1024);
+        let results = service
+            .scan_markdown_files(vec![path.clone()])
             .await
             .unwrap();
+        assert!(matches!(results[0].status, ScanStatus::Skipped));
+        assert!(results[0].images.is_empty());
+        assert!(results[0].error.as_ref().unwrap().contains("limit"));
 
-        // Create markdown content
-        let md_content = format!(
-            r#"
-# Test Document
-
-![Image 1]({}/test1.png)
-![Image 2]({}/test2.jpg)
-![Missing](./missing.png)
-"#,
-            temp_dir_path.display(),
-            temp_dir_path.display()
-        );
+        // Replacement reads the whole file, so it refuses instead
+        let replacement = LinkReplacement {
+            file_path: path.clone(),
+            line: 1,
+            column: 6,
+            old_link: "./x.png".to_string(),
+            new_link: "https://cdn.example.com/x.png".to_string(),
+            context: LinkContext::MarkdownInline,
+        };
+        let error = service
+            .replace_image_links(&path, vec![replacement])
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::FileTooLarge(_)));
+    }
 
-        let md_file = create_temp_md_file(&md_content).await.unwrap();
+    #[tokio::test]
+    async fn test_large_markdown_is_streamed() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("large.md");
+        let filler = "plain text without images\n".repeat(50_000);
+        let content = format!("![a](./a.png)\n{}<img src=\"./b.png\" />\n", filler);
+        assert!(content.len() as u64 > STREAMING_SCAN_BYTES);
+        async_fs::write(&path, &content).await.unwrap();
+        let path = path.to_string_lossy().to_string();
 
         let service = FileService::new().unwrap();
-        let result = service.scan_single_file(&md_file.to_string_lossy()).await;
-
-        assert!(matches!(result.status, ScanStatus::Success));
-        assert_eq!(result.images.len(), 3);
-
-        // Check existing images
-        assert!(result.images[0].exists);
-        assert!(result.images[0].size > 0);
-        assert!(result.images[1].exists);
-        assert!(result.images[1].size > 0);
+        let result = service
+            .scan_single_file(&path, &AtomicBool::new(false))
+            .await
+            .unwrap();
+        let found: Vec<_> = result
+            .images
+            .iter()
+            .map(|i| (i.original_path.as_str(), i.markdown_line))
+            .collect();
+        assert_eq!(found, [("./a.png", 1), ("./b.png", 50_002)]);
 
-        // Check missing image
-        assert!(!result.images[2].exists);
-        assert_eq!(result.images[2].size, 0);
+        // A cancelled scan stops inside the file and reports nothing for it
+        assert!(service
+            .scan_single_file(&path, &AtomicBool::new(true))
+            .await
+            .is_none());
     }
 
     #[tokio::test]
@@ -655,7 +2855,10 @@ This should be ignored: ![Remote](https://example.com/image.png)
         async_fs::write(&md_file, md_content).await.unwrap();
 
         let service = FileService::new().unwrap();
-        let result = service.scan_single_file(&md_file.to_string_lossy()).await;
+        let result = service
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
 
         assert!(matches!(result.status, ScanStatus::Success));
         assert_eq!(result.images.len(), 1);
@@ -678,7 +2881,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
 ![Uppercase](./TEST.PNG)
 "#;
 
-        let images = service.extract_image_references(content).await.unwrap();
+        let images = service.collect_references(content).images;
 
         assert_eq!(images.len(), 8);
 
@@ -701,7 +2904,10 @@ This should be ignored: ![Remote](https://example.com/image.png)
     #[tokio::test]
     async fn test_scan_file_with_io_error() {
         let service = FileService::new().unwrap();
-        let result = service.scan_single_file("/nonexistent/file.md").await;
+        let result = service
+            .scan_single_file("/nonexistent/file.md", &AtomicBool::new(false))
+            .await
+            .unwrap();
 
         assert!(matches!(result.status, ScanStatus::Error));
         assert!(result.error.is_some());
@@ -712,6 +2918,102 @@ This should be ignored: ![Remote](https://example.com/image.png)
     // Link Replacement Tests
     // ============================================================================
 
+    // A GBK note with a Chinese image path, the kind older Windows editors saved
+    async fn gbk_note(dir: &Path) -> PathBuf {
+        let images = dir.join("图片");
+        async_fs::create_dir_all(&images).await.unwrap();
+        create_temp_image_file(&images, "西湖.png").await.unwrap();
+        let md_file = dir.join("旅行笔记.md");
+        let content = "# 旅行笔记\n\n今天我们去了西湖，天气很好。\n\n![西湖](./图片/西湖.png)\n";
+        let (bytes, _, _) = encoding_rs::GBK.encode(content);
+        async_fs::write(&md_file, &bytes).await.unwrap();
+        md_file
+    }
+
+    fn replacement_for(md_file: &Path, image: &ImageReference, new_link: &str) -> LinkReplacement {
+        LinkReplacement {
+            file_path: md_file.to_string_lossy().to_string(),
+            line: image.markdown_line,
+            column: image.markdown_column,
+            old_link: image.original_path.clone(),
+            new_link: new_link.to_string(),
+            context: LinkContext::MarkdownInline,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_reads_gbk_file_with_chinese_paths() {
+        let temp_dir = tempdir().unwrap();
+        let md_file = gbk_note(temp_dir.path()).await;
+
+        let result = FileService::new()
+            .unwrap()
+            .scan_single_file(&md_file.to_string_lossy(), &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        assert!(matches!(result.status, ScanStatus::Success));
+        assert_eq!(result.encoding.as_deref(), Some("GBK"));
+        assert_eq!(result.warnings[0].code, ScanWarningCode::LegacyEncoding);
+        assert_eq!(result.images.len(), 1);
+        assert_eq!(result.images[0].original_path, "./图片/西湖.png");
+        assert!(result.images[0].exists);
+    }
+
+    #[tokio::test]
+    async fn test_replace_keeps_gbk_unless_conversion_is_enabled() {
+        let temp_dir = tempdir().unwrap();
+        let md_file = gbk_note(temp_dir.path()).await;
+        let path = md_file.to_string_lossy().to_string();
+        let service = FileService::new().unwrap();
+        let image = service
+            .scan_single_file(&path, &AtomicBool::new(false))
+            .await
+            .unwrap()
+            .images
+            .remove(0);
+
+        // A link GBK can't store fails and leaves the file alone
+        let before = async_fs::read(&md_file).await.unwrap();
+        let unmappable = replacement_for(&md_file, &image, "https://cdn.example.com/😀.png");
+        assert!(service
+            .replace_image_links(&path, vec![unmappable])
+            .await
+            .is_err());
+        assert_eq!(async_fs::read(&md_file).await.unwrap(), before);
+
+        let replacement = replacement_for(&md_file, &image, "https://cdn.example.com/西湖.png");
+        let result = service
+            .replace_image_links(&path, vec![replacement])
+            .await
+            .unwrap();
+        assert_eq!(result.successful_replacements, 1);
+        assert_eq!(result.encoding.as_deref(), Some("GBK"));
+        assert!(!result.converted_to_utf8);
+        let bytes = async_fs::read(&md_file).await.unwrap();
+        assert!(std::str::from_utf8(&bytes).is_err());
+        let (text, _, had_errors) = encoding_rs::GBK.decode(&bytes);
+        assert!(!had_errors);
+        assert!(text.contains("![西湖](https://cdn.example.com/西湖.png)"));
+        assert!(text.contains("今天我们去了西湖"));
+
+        // Opting in rewrites it as UTF-8
+        let replacement = LinkReplacement {
+            old_link: "https://cdn.example.com/西湖.png".to_string(),
+            ..replacement_for(&md_file, &image, "https://cdn.example.com/xihu.png")
+        };
+        let result = service
+            .with_utf8_conversion(true)
+            .replace_image_links(&path, vec![replacement])
+            .await
+            .unwrap();
+        assert_eq!(result.successful_replacements, 1);
+        assert!(result.converted_to_utf8);
+        let text = async_fs::read_to_string(&md_file).await.unwrap();
+        assert!(text.contains("![西湖](https://cdn.example.com/xihu.png)"));
+        assert!(text.contains("今天我们去了西湖"));
+    }
+
     #[tokio::test]
     async fn test_replace_image_links_single_replacement() {
         let temp_dir = tempdir().unwrap();
@@ -729,6 +3031,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
             column: 31, // Position where ./images/test.png starts
             old_link: "./images/test.png".to_string(),
             new_link: "https://cdn.example.com/test.png".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
 
         let result = service
@@ -780,6 +3083,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img1.png".to_string(),
                 new_link: "https://cdn.example.com/img1.png".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: md_file.to_string_lossy().to_string(),
@@ -787,6 +3091,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img2.jpg".to_string(),
                 new_link: "https://cdn.example.com/img2.jpg".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: md_file.to_string_lossy().to_string(),
@@ -794,6 +3099,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img3.gif".to_string(),
                 new_link: "https://cdn.example.com/img3.gif".to_string(),
+                context: LinkContext::MarkdownInline,
             },
         ];
 
@@ -838,6 +3144,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img1.png".to_string(),
                 new_link: "https://cdn.example.com/img1.png".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: md_file.to_string_lossy().to_string(),
@@ -845,6 +3152,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./nonexistent.jpg".to_string(), // This should fail
                 new_link: "https://cdn.example.com/img2.jpg".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: md_file.to_string_lossy().to_string(),
@@ -852,6 +3160,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img3.gif".to_string(),
                 new_link: "https://cdn.example.com/img3.gif".to_string(),
+                context: LinkContext::MarkdownInline,
             },
         ];
 
@@ -871,6 +3180,137 @@ This should be ignored: ![Remote](https://example.com/image.png)
         assert!(!updated_content.contains("./img1.png"));
     }
 
+    fn link_replacement(file: &Path, new_link: &str) -> LinkReplacement {
+        LinkReplacement {
+            file_path: file.to_string_lossy().to_string(),
+            line: 1,
+            column: 8,
+            old_link: "./a.png".to_string(),
+            new_link: new_link.to_string(),
+            context: LinkContext::MarkdownInline,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_busy_files_fail_the_batch_before_any_write() {
+        let temp_dir = tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.md", "b.md", "c.md"]
+            .iter()
+            .map(|name| temp_dir.path().join(name))
+            .collect();
+        for file in &files {
+            async_fs::write(file, "![a](./a.png)").await.unwrap();
+        }
+        let service = FileService::new()
+            .unwrap()
+            .with_operation_name("replace_markdown_links");
+        let new_link = "https://cdn.example.com/a.png";
+
+        let held = lock_files(
+            [&files[0].to_string_lossy().to_string()],
+            "process_markdown_files",
+        )
+        .unwrap();
+        let error = service
+            .replace_image_links_batch(vec![
+                link_replacement(&files[1], new_link),
+                link_replacement(&files[0], new_link),
+            ])
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("File busy with another operation (process_markdown_files)"));
+        assert!(error.contains("a.md"));
+        assert_eq!(
+            async_fs::read_to_string(&files[1]).await.unwrap(),
+            "![a](./a.png)"
+        );
+        assert!(service
+            .replace_image_links(
+                &files[0].to_string_lossy(),
+                vec![link_replacement(&files[0], new_link)]
+            )
+            .await
+            .is_err());
+
+        // Files nobody holds are still free
+        let result = service
+            .replace_image_links_batch(vec![link_replacement(&files[2], new_link)])
+            .await
+            .unwrap();
+        assert_eq!(result.total_successful_replacements, 1);
+
+        drop(held);
+        let result = service
+            .replace_image_links_batch(vec![
+                link_replacement(&files[0], new_link),
+                link_replacement(&files[1], new_link),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(result.total_successful_replacements, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_simultaneous_batches_over_overlapping_files() {
+        let temp_dir = tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.md", "b.md", "c.md"]
+            .iter()
+            .map(|name| temp_dir.path().join(name))
+            .collect();
+        for file in &files {
+            async_fs::write(file, "![a](./a.png)\n".repeat(200))
+                .await
+                .unwrap();
+        }
+        let first = FileService::new()
+            .unwrap()
+            .with_operation_name("process_markdown_files");
+        let second = FileService::new()
+            .unwrap()
+            .with_operation_name("replace_markdown_links");
+        let batch = |file_indexes: [usize; 2], new_link: &str| -> Vec<LinkReplacement> {
+            file_indexes
+                .iter()
+                .flat_map(|&index| {
+                    let replacement = link_replacement(&files[index], new_link);
+                    (1..=200).map(move |line| LinkReplacement {
+                        line,
+                        ..replacement.clone()
+                    })
+                })
+                .collect()
+        };
+
+        let (first_result, second_result) = tokio::join!(
+            first.replace_image_links_batch(batch([0, 1], "https://one.example.com/a.png")),
+            second.replace_image_links_batch(batch([1, 2], "https://two.example.com/a.png")),
+        );
+        assert!(first_result.is_ok() || second_result.is_ok());
+        for result in [&first_result, &second_result] {
+            if let Err(e) = result {
+                assert!(e.to_string().contains("File busy with another operation"));
+            }
+        }
+
+        // The shared file went through one run or the other, never a mix
+        let shared = async_fs::read_to_string(&files[1]).await.unwrap();
+        let one = "![a](https://one.example.com/a.png)\n".repeat(200);
+        let two = "![a](https://two.example.com/a.png)\n".repeat(200);
+        assert!(
+            shared.trim_end() == one.trim_end() || shared.trim_end() == two.trim_end(),
+            "{}",
+            shared
+        );
+
+        // Both runs released their files
+        let paths: Vec<String> = files
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect();
+        assert!(lock_files(&paths, "replace_markdown_links").is_ok());
+    }
+
     #[tokio::test]
     async fn test_replace_image_links_batch() {
         let temp_dir = tempdir().unwrap();
@@ -894,6 +3334,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img1.png".to_string(),
                 new_link: "https://cdn.example.com/img1.png".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: md_file2.to_string_lossy().to_string(),
@@ -901,6 +3342,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img2.jpg".to_string(),
                 new_link: "https://cdn.example.com/img2.jpg".to_string(),
+                context: LinkContext::MarkdownInline,
             },
         ];
 
@@ -932,6 +3374,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
             column: 10,
             old_link: "./img.png".to_string(),
             new_link: "https://cdn.example.com/img.png".to_string(),
+            context: LinkContext::MarkdownInline,
         }];
 
         let result = service
@@ -958,6 +3401,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 13,
                 old_link: "./img1.png".to_string(),
                 new_link: "https://cdn.example.com/img1.png".to_string(),
+                context: LinkContext::MarkdownInline,
             },
             LinkReplacement {
                 file_path: md_file.to_string_lossy().to_string(),
@@ -965,6 +3409,7 @@ This should be ignored: ![Remote](https://example.com/image.png)
                 column: 43,
                 old_link: "./img2.jpg".to_string(),
                 new_link: "https://cdn.example.com/img2.jpg".to_string(),
+                context: LinkContext::MarkdownInline,
             },
         ];
 
@@ -983,4 +3428,490 @@ This should be ignored: ![Remote](https://example.com/image.png)
         assert!(!updated_content.contains("./img1.png"));
         assert!(!updated_content.contains("./img2.jpg"));
     }
+
+    fn replacement_at(
+        md_file: &Path,
+        line: usize,
+        column: usize,
+        old_link: &str,
+        new_link: &str,
+        context: LinkContext,
+    ) -> LinkReplacement {
+        LinkReplacement {
+            file_path: md_file.to_string_lossy().to_string(),
+            line,
+            column,
+            old_link: old_link.to_string(),
+            new_link: new_link.to_string(),
+            context,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replace_applies_context_escaping() {
+        let temp_dir = tempdir().unwrap();
+        let md_file = temp_dir.path().join("test.md");
+        let content = [
+            r#"<img src="./a.png" srcset="./a.png 2x, ./b.png 3x" />"#,
+            "[logo]: ./logo.png \"Logo\"",
+            "Embed: ![[diagram.png|Flow chart]] end",
+            "![a](./a.png) ![again](./a.png)",
+        ]
+        .join("\n");
+        async_fs::write(&md_file, &content).await.unwrap();
+
+        let signed = "https://cdn.example.com/a b.png?x=1&y=2";
+        let replacements = vec![
+            replacement_at(
+                &md_file,
+                1,
+                11,
+                "./a.png",
+                signed,
+                LinkContext::HtmlAttribute,
+            ),
+            replacement_at(
+                &md_file,
+                1,
+                28,
+                "./a.png",
+                signed,
+                LinkContext::HtmlAttribute,
+            ),
+            replacement_at(
+                &md_file,
+                2,
+                9,
+                "./logo.png",
+                "https://cdn.example.com/my logo.png",
+                LinkContext::ReferenceDefinition,
+            ),
+            replacement_at(
+                &md_file,
+                3,
+                11,
+                "diagram.png",
+                "https://cdn.example.com/diagram (1).png",
+                LinkContext::WikiEmbed,
+            ),
+            replacement_at(
+                &md_file,
+                4,
+                25,
+                "./a.png",
+                "https://cdn.example.com/a.png",
+                LinkContext::MarkdownInline,
+            ),
+        ];
+
+        let service = FileService::new().unwrap();
+        let result = service
+            .replace_image_links(&md_file.to_string_lossy(), replacements)
+            .await
+            .unwrap();
+        assert_eq!(result.successful_replacements, 5);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+
+        let updated = async_fs::read_to_string(&md_file).await.unwrap();
+        let lines: Vec<&str> = updated.lines().collect();
+        let escaped = "https://cdn.example.com/a%20b.png?x=1&amp;y=2";
+        assert_eq!(
+            lines[0],
+            format!(r#"<img src="{0}" srcset="{0} 2x, ./b.png 3x" />"#, escaped)
+        );
+        assert_eq!(
+            lines[1],
+            "[logo]: https://cdn.example.com/my%20logo.png \"Logo\""
+        );
+        assert_eq!(
+            lines[2],
+            "Embed: ![Flow chart](https://cdn.example.com/diagram%20%281%29.png) end"
+        );
+        // Only the occurrence at the recorded column is rewritten
+        assert_eq!(
+            lines[3],
+            "![a](./a.png) ![again](https://cdn.example.com/a.png)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rewritten_tables_and_lists_read_back_clean() {
+        let temp_dir = tempdir().unwrap();
+        let md_file = temp_dir.path().join("table.md");
+        let content = [
+            "| Before | After |",
+            "|--------|-------|",
+            "| ![a](./a.png) | ![b](./b.png) |",
+            "- item",
+            "  - nested ![c](./c.png)",
+            "",
+        ]
+        .join("\r\n");
+        async_fs::write(&md_file, &content).await.unwrap();
+
+        let replacements = vec![
+            replacement_at(
+                &md_file,
+                3,
+                8,
+                "./a.png",
+                "https://cdn.example.com/a.png",
+                LinkContext::MarkdownInline,
+            ),
+            replacement_at(
+                &md_file,
+                3,
+                24,
+                "./b.png",
+                "https://cdn.example.com/b (1).png",
+                LinkContext::MarkdownInline,
+            ),
+            replacement_at(
+                &md_file,
+                5,
+                17,
+                "./c.png",
+                "https://cdn.example.com/c.png",
+                LinkContext::MarkdownInline,
+            ),
+        ];
+        let result = FileService::new()
+            .unwrap()
+            .replace_image_links(&md_file.to_string_lossy(), replacements)
+            .await
+            .unwrap();
+        assert_eq!(result.successful_replacements, 3);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert!(!result.restored);
+
+        // Byte for byte the original apart from the links: CRLF and the
+        // final newline are kept
+        let mut expected = content.clone();
+        for change in &result.changes {
+            expected = expected.replacen(&change.replaced_text, &change.written_text, 1);
+        }
+        assert_eq!(
+            async_fs::read(&md_file).await.unwrap(),
+            expected.into_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compare_rewrite_reports_what_changed_beyond_the_links() {
+        let service = FileService::new().unwrap();
+        let before = "# Title\n![a](./a.png) and ![b](./b.png)\n| x | y |";
+        let change = |column: usize| ReplacementChange {
+            file_path: "note.md".to_string(),
+            line: 2,
+            column,
+            old_link: "./a.png".to_string(),
+            new_link: "https://cdn.example.com/a.png".to_string(),
+            uploaded_url: None,
+            object_key: None,
+            history_record_id: None,
+            replaced_text: "./a.png".to_string(),
+            written_text: "https://cdn.example.com/a.png".to_string(),
+        };
+        let codes =
+            |warnings: Vec<ReplacementWarning>| -> Vec<(ReplacementWarningCode, Option<usize>)> {
+                warnings.into_iter().map(|w| (w.code, w.line)).collect()
+            };
+
+        let after = "# Title\n![a](https://cdn.example.com/a.png) and ![b](./b.png)\n| x | y |";
+        assert!(service
+            .compare_rewrite(before, after, &[change(6)])
+            .is_empty());
+
+        // A table cell lost its separator
+        let after = "# Title\n![a](https://cdn.example.com/a.png) and ![b](./b.png)\n| x  y |";
+        assert_eq!(
+            codes(service.compare_rewrite(before, after, &[change(6)])),
+            vec![(ReplacementWarningCode::LineChanged, Some(3))]
+        );
+        // The link went somewhere else, and the second image lost its syntax
+        let after = "# Title\n![a](x) https://cdn.example.com/a.png and ![b(./b.png)\n| x | y |";
+        assert_eq!(
+            codes(service.compare_rewrite(before, after, &[change(6)])),
+            vec![
+                (ReplacementWarningCode::LinkMissing, Some(2)),
+                (ReplacementWarningCode::ReferenceCountChanged, Some(2)),
+            ]
+        );
+        let after = "# Title\n![a](https://cdn.example.com/a.png) and ![b](./b.png)";
+        assert_eq!(
+            codes(service.compare_rewrite(before, after, &[change(6)])),
+            vec![(ReplacementWarningCode::LineCountChanged, None)]
+        );
+        // Converted to CRLF with a final newline added
+        let after = "# Title\r\n![a](https://cdn.example.com/a.png) and ![b](./b.png)\n| x | y |\n";
+        assert_eq!(
+            codes(service.compare_rewrite(before, after, &[change(6)])),
+            vec![
+                (ReplacementWarningCode::LineChanged, Some(1)),
+                (ReplacementWarningCode::LineChanged, Some(3)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unverified_rewrite_is_restored_when_asked() {
+        let temp_dir = tempdir().unwrap();
+        let md_file = temp_dir.path().join("note.md");
+        let content = "Intro\n![a](./a.png)\n";
+        // Without an extension the new link no longer reads as an image reference
+        let replacement = || {
+            vec![replacement_at(
+                &md_file,
+                2,
+                6,
+                "./a.png",
+                "https://cdn.example.com/a",
+                LinkContext::MarkdownInline,
+            )]
+        };
+
+        async_fs::write(&md_file, content).await.unwrap();
+        let kept = FileService::new()
+            .unwrap()
+            .replace_image_links(&md_file.to_string_lossy(), replacement())
+            .await
+            .unwrap();
+        assert_eq!(kept.warnings.len(), 1);
+        assert_eq!(
+            kept.warnings[0].code,
+            ReplacementWarningCode::ReferenceCountChanged
+        );
+        assert!(!kept.restored);
+        assert_eq!(kept.successful_replacements, 1);
+        assert!(async_fs::read_to_string(&md_file)
+            .await
+            .unwrap()
+            .contains("https://cdn.example.com/a)"));
+
+        async_fs::write(&md_file, content).await.unwrap();
+        let restored = FileService::new()
+            .unwrap()
+            .with_verification_restore(true)
+            .replace_image_links(&md_file.to_string_lossy(), replacement())
+            .await
+            .unwrap();
+        assert!(restored.restored);
+        assert_eq!(restored.successful_replacements, 0);
+        assert_eq!(restored.failed_replacements.len(), 1);
+        assert!(restored.changes.is_empty());
+        // Byte for byte, trailing newline included
+        assert_eq!(async_fs::read_to_string(&md_file).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_extract_image_references_sets_context() {
+        let service = FileService::new().unwrap();
+        let content = "![md](./a.png)\n<img src=\"./b.png\" />";
+
+        let images = service.collect_references(content).images;
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].context, LinkContext::MarkdownInline);
+        assert_eq!(images[1].context, LinkContext::HtmlAttribute);
+    }
+
+    #[tokio::test]
+    async fn test_atomic_batch_rolls_back_on_file_failure() {
+        let temp_dir = tempdir().unwrap();
+        let backup_service = BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap();
+        let original = "![a](./a.png)\n";
+
+        let mut replacements = Vec::new();
+        for name in ["a.md", "b.md", "c.md"] {
+            let path = temp_dir.path().join(name);
+            async_fs::write(&path, original).await.unwrap();
+            replacements.push(replacement_at(
+                &path,
+                1,
+                6,
+                "./a.png",
+                "https://cdn.example.com/a.png",
+                LinkContext::MarkdownInline,
+            ));
+        }
+        // c.md is GBK and its new link has a character GBK lacks, so it fails
+        // after a.md and b.md were already rewritten
+        let broken = temp_dir.path().join("c.md");
+        let (gbk, _, _) = encoding_rs::GBK.encode("![a](./a.png) 笔记\n");
+        async_fs::write(&broken, &gbk).await.unwrap();
+        replacements[2].new_link = "https://cdn.example.com/😀.png".to_string();
+
+        let service = FileService::new().unwrap();
+        let error = service
+            .replace_image_links_batch_atomic(replacements.clone(), &backup_service)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("c.md"));
+        assert!(error.contains("Restored 2 of 2"));
+        for name in ["a.md", "b.md"] {
+            let content = async_fs::read_to_string(temp_dir.path().join(name))
+                .await
+                .unwrap();
+            assert_eq!(content, original);
+        }
+
+        // Saved as UTF-8 the whole batch goes through
+        async_fs::write(&broken, original).await.unwrap();
+        let result = service
+            .replace_image_links_batch_atomic(replacements, &backup_service)
+            .await
+            .unwrap();
+        assert_eq!(result.total_files, 3);
+        assert_eq!(result.total_successful_replacements, 3);
+    }
+
+    #[tokio::test]
+    async fn test_atomic_batch_restores_the_file_whose_write_failed() {
+        let temp_dir = tempdir().unwrap();
+        let backup_service = BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap();
+        let original = "![a](./a.png)\n";
+
+        let mut replacements = Vec::new();
+        for name in ["a.md", "b.md", "c.md"] {
+            let path = temp_dir.path().join(name);
+            async_fs::write(&path, original).await.unwrap();
+            replacements.push(replacement_at(
+                &path,
+                1,
+                6,
+                "./a.png",
+                "https://cdn.example.com/a.png",
+                LinkContext::MarkdownInline,
+            ));
+        }
+
+        // a.md and b.md are rewritten, then writing c.md fails halfway through
+        let mut service = FileService::new().unwrap();
+        service.write_fault = Some(temp_dir.path().join("c.md"));
+        let error = service
+            .replace_image_links_batch_atomic(replacements, &backup_service)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("injected write failure"));
+        assert!(error.contains("Restored 3 of 3"));
+        for name in ["a.md", "b.md", "c.md"] {
+            let content = async_fs::read_to_string(temp_dir.path().join(name))
+                .await
+                .unwrap();
+            assert_eq!(content, original);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_files_abort_or_skip_before_writing() {
+        let temp_dir = tempdir().unwrap();
+        let original = "![a](./a.png)\n";
+
+        let mut replacements = Vec::new();
+        for name in ["a.md", "locked.md"] {
+            let path = temp_dir.path().join(name);
+            async_fs::write(&path, original).await.unwrap();
+            replacements.push(replacement_at(
+                &path,
+                1,
+                6,
+                "./a.png",
+                "https://cdn.example.com/a.png",
+                LinkContext::MarkdownInline,
+            ));
+        }
+        let locked = temp_dir.path().join("locked.md");
+        let mut permissions = fs::metadata(&locked).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&locked, permissions).unwrap();
+        if fs::OpenOptions::new().write(true).open(&locked).is_ok() {
+            // Permission bits don't stop root
+            return;
+        }
+
+        let service = FileService::new().unwrap();
+        let error = service
+            .replace_image_links_batch(replacements.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::PermissionDenied(_)));
+        assert!(error.to_string().contains("locked.md"));
+        let content = async_fs::read_to_string(temp_dir.path().join("a.md"))
+            .await
+            .unwrap();
+        assert_eq!(content, original);
+
+        let result = service
+            .with_read_only_policy(ReadOnlyPolicy::Skip)
+            .replace_image_links_batch(replacements)
+            .await
+            .unwrap();
+        assert_eq!(result.total_files, 1);
+        assert_eq!(result.total_successful_replacements, 1);
+        assert_eq!(result.total_failed_replacements, 0);
+        assert_eq!(result.skipped_read_only.len(), 1);
+        assert_eq!(
+            result.skipped_read_only[0].file_path,
+            locked.to_string_lossy()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_atomic_rollback_is_recorded_in_operation_log() {
+        let temp_dir = tempdir().unwrap();
+        let log = HistoryService::new_with_dir(temp_dir.path().join("data")).unwrap();
+        let backup_service = BackupService::new_with_dir(temp_dir.path().join("backups"))
+            .unwrap()
+            .with_operation_log(Some(log.clone()));
+        let service = FileService::new()
+            .unwrap()
+            .with_operation_log(Some(log.clone()));
+
+        let good = temp_dir.path().join("a.md");
+        let broken = temp_dir.path().join("b.md");
+        async_fs::write(&good, "![a](./a.png)\n").await.unwrap();
+        // GBK can't store the new link of b.md
+        let (gbk, _, _) = encoding_rs::GBK.encode("![a](./a.png) 笔记\n");
+        async_fs::write(&broken, &gbk).await.unwrap();
+        let replacements = [
+            (&good, "https://cdn.example.com/a.png"),
+            (&broken, "https://cdn.example.com/😀.png"),
+        ]
+        .iter()
+        .map(|(path, new_link)| {
+            replacement_at(path, 1, 6, "./a.png", new_link, LinkContext::MarkdownInline)
+        })
+        .collect();
+
+        service
+            .replace_image_links_batch_atomic(replacements, &backup_service)
+            .await
+            .unwrap_err();
+
+        let operations: Vec<_> = log
+            .get_file_operations(10, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .rev()
+            .map(|op| {
+                (
+                    op.operation_type,
+                    op.file_path.ends_with("a.md"),
+                    op.success,
+                )
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            vec![
+                (FileOperationType::Backup, true, true),
+                (FileOperationType::Backup, false, true),
+                (FileOperationType::Replace, true, true),
+                (FileOperationType::Replace, false, false),
+                (FileOperationType::Restore, true, true),
+            ]
+        );
+    }
 }
@@ -1,19 +1,68 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use uuid::Uuid;
 
-use crate::models::{UploadHistoryRecord, UploadMode};
+use crate::models::{
+    ChecksumAlgorithm, DuplicateScope, FileOperation, FileOperationType, StoreKind,
+    StoreRecoveryResult, TrashedHistoryRecord, UploadHistoryRecord, UploadMode, UploadTarget,
+};
+use crate::utils::app_paths;
 use crate::utils::error::AppError;
+use crate::utils::store_lock::lock_store;
+use crate::utils::store_recovery::{
+    latest_corrupt_file, salvage_array, write_atomic, CORRUPT_STORES,
+};
+use crate::{log_info, log_warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Oldest entries are dropped beyond these counts
+const MAX_UPLOAD_RECORDS: usize = 1000;
+const MAX_FILE_OPERATIONS: usize = 500;
+
+/// Records shown per source file group
+const SOURCE_GROUP_PREVIEW_RECORDS: usize = 5;
+
+/// Shorter checksum prefixes match too much to be a useful lookup
+pub const MIN_CHECKSUM_PREFIX_LEN: usize = 8;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HistoryQuery {
     pub upload_mode: Option<UploadMode>,
     pub start_date: Option<DateTime<Utc>>,
     pub end_date: Option<DateTime<Utc>>,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
+    /// Case-insensitive match on image name, URL, source file or checksum
+    #[serde(default)]
+    pub search_term: Option<String>,
+    /// Newest first unless set
+    #[serde(default)]
+    pub sort_by: Option<HistorySortField>,
+    /// Descending unless set
+    #[serde(default)]
+    pub sort_order: Option<SortOrder>,
+    /// Also match records in the trash
+    #[serde(default)]
+    pub include_trashed: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum HistorySortField {
+    #[default]
+    Timestamp,
+    FileSize,
+    ImageName,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,39 +71,57 @@ pub struct HistoryStatistics {
     pub total_images_processed: usize,
     pub total_size_processed: u64,
     pub upload_modes: std::collections::HashMap<String, usize>,
+    // Records per checksum algorithm, unprefixed legacy checksums count as sha256
+    #[serde(default)]
+    pub checksum_algorithms: std::collections::HashMap<String, usize>,
     pub oldest_record: Option<DateTime<Utc>>,
     pub newest_record: Option<DateTime<Utc>>,
+    /// Records in the trash, counted in the totals above only when asked to
+    #[serde(default)]
+    pub trashed_records: usize,
+}
+
+/// Uploads sharing a source markdown file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySourceGroup {
+    /// None for direct uploads that aren't tied to an article
+    pub source_file: Option<String>,
+    pub record_count: usize,
+    pub total_bytes: u64,
+    pub newest_timestamp: DateTime<Utc>,
+    /// The newest few records of the group
+    pub preview: Vec<UploadHistoryRecord>,
 }
 
+#[derive(Clone)]
 pub struct HistoryService {
     upload_history_file: PathBuf,
+    file_operations_file: PathBuf,
+    // Deleted upload records, until restored or purged
+    trash_file: PathBuf,
+    // Modification time of each store file as of our last read or write, to
+    // notice another instance writing in between (e.g. a synced data folder)
+    seen_modified: Arc<Mutex<HashMap<PathBuf, SystemTime>>>,
 }
 
 impl HistoryService {
     pub fn new() -> Result<Self, AppError> {
-        let data_dir = Self::get_data_directory()?;
+        Self::new_with_dir(app_paths::data_dir()?)
+    }
 
+    pub fn new_with_dir(data_dir: PathBuf) -> Result<Self, AppError> {
         // Ensure data directory exists
         fs::create_dir_all(&data_dir)
             .map_err(|e| AppError::FileSystem(format!("Failed to create data directory: {}", e)))?;
 
-        let upload_history_file = data_dir.join("upload_history.json");
-
         Ok(Self {
-            upload_history_file,
+            upload_history_file: data_dir.join("upload_history.json"),
+            file_operations_file: data_dir.join("file_operations.json"),
+            trash_file: data_dir.join("history_trash.json"),
+            seen_modified: Arc::default(),
         })
     }
 
-    fn get_data_directory() -> Result<PathBuf, AppError> {
-        let app_data_dir = dirs::data_dir()
-            .ok_or_else(|| {
-                AppError::Configuration("Could not determine data directory".to_string())
-            })?
-            .join("imgtoss");
-
-        Ok(app_data_dir)
-    }
-
     // 添加上传历史记录
     pub async fn add_upload_record(
         &self,
@@ -64,12 +131,14 @@ impl HistoryService {
             record.id = Uuid::new_v4().to_string();
         }
 
+        // Re-read under the lock so records another instance just added survive
+        let _lock = lock_store(&self.upload_history_file).await?;
         let mut records = self.load_upload_records().await?;
         records.insert(0, record.clone());
 
         // Keep only the last 1000 records to prevent excessive storage
-        if records.len() > 1000 {
-            records.truncate(1000);
+        if records.len() > MAX_UPLOAD_RECORDS {
+            records.truncate(MAX_UPLOAD_RECORDS);
         }
 
         self.save_upload_records(&records).await?;
@@ -89,6 +158,7 @@ impl HistoryService {
             ids.push(record.id.clone());
         }
 
+        let _lock = lock_store(&self.upload_history_file).await?;
         let mut existing_records = self.load_upload_records().await?;
 
         // Insert new records at the beginning
@@ -97,8 +167,8 @@ impl HistoryService {
         }
 
         // Keep only the last 1000 records
-        if existing_records.len() > 1000 {
-            existing_records.truncate(1000);
+        if existing_records.len() > MAX_UPLOAD_RECORDS {
+            existing_records.truncate(MAX_UPLOAD_RECORDS);
         }
 
         self.save_upload_records(&existing_records).await?;
@@ -110,37 +180,92 @@ impl HistoryService {
         &self,
         query: Option<HistoryQuery>,
     ) -> Result<Vec<UploadHistoryRecord>, AppError> {
-        let mut records = self.load_upload_records().await?;
+        match query {
+            Some(q) => Ok(self.get_upload_records_page(q).await?.0),
+            None => self.load_upload_records().await,
+        }
+    }
 
-        if let Some(q) = query {
-            // Apply filters
-            if let Some(upload_mode) = q.upload_mode {
-                records.retain(|r| r.upload_mode == upload_mode);
-            }
+    // 一次读取同时返回当前页和过滤后的总数
+    pub async fn get_upload_records_page(
+        &self,
+        q: HistoryQuery,
+    ) -> Result<(Vec<UploadHistoryRecord>, usize), AppError> {
+        let mut records = self.load_query_records(&q).await?;
 
-            if let Some(start) = q.start_date {
-                records.retain(|r| r.timestamp >= start);
-            }
+        apply_filters(&mut records, &q);
 
-            if let Some(end) = q.end_date {
-                records.retain(|r| r.timestamp <= end);
+        // Sort before paginating so pages don't overlap, stable for equal keys
+        let sort_by = q.sort_by.unwrap_or_default();
+        let descending = q.sort_order.unwrap_or_default() == SortOrder::Desc;
+        records.sort_by(|a, b| {
+            let ordering = match sort_by {
+                HistorySortField::Timestamp => a.timestamp.cmp(&b.timestamp),
+                HistorySortField::FileSize => a.file_size.cmp(&b.file_size),
+                HistorySortField::ImageName => a
+                    .image_name
+                    .to_lowercase()
+                    .cmp(&b.image_name.to_lowercase()),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
             }
+        });
 
-            // Apply pagination
-            if let Some(offset) = q.offset {
-                if offset < records.len() {
-                    records = records.into_iter().skip(offset).collect();
-                } else {
-                    records.clear();
-                }
-            }
+        // Apply pagination
+        let total = records.len();
+        let page = records
+            .into_iter()
+            .skip(q.offset.unwrap_or(0))
+            .take(q.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok((page, total))
+    }
 
-            if let Some(limit) = q.limit {
-                records.truncate(limit);
+    // 按来源文件分组，最近更新的分组在前；分页作用于分组
+    pub async fn get_grouped_by_source(
+        &self,
+        q: HistoryQuery,
+    ) -> Result<(Vec<HistorySourceGroup>, usize), AppError> {
+        let mut records = self.load_query_records(&q).await?;
+        apply_filters(&mut records, &q);
+        records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        let mut groups: Vec<HistorySourceGroup> = Vec::new();
+        let mut positions: HashMap<Option<String>, usize> = HashMap::new();
+        for record in records {
+            // Records are newest first, so the first one of a group sets its timestamp
+            let position = *positions
+                .entry(record.source_file.clone())
+                .or_insert_with(|| {
+                    groups.push(HistorySourceGroup {
+                        source_file: record.source_file.clone(),
+                        record_count: 0,
+                        total_bytes: 0,
+                        newest_timestamp: record.timestamp,
+                        preview: Vec::new(),
+                    });
+                    groups.len() - 1
+                });
+            let group = &mut groups[position];
+            group.record_count += 1;
+            group.total_bytes += record.file_size;
+            if group.preview.len() < SOURCE_GROUP_PREVIEW_RECORDS {
+                group.preview.push(record);
             }
         }
 
-        Ok(records)
+        let total = groups.len();
+        let page = groups
+            .into_iter()
+            .skip(q.offset.unwrap_or(0))
+            .take(q.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok((page, total))
     }
 
     // 根据ID获取单个记录
@@ -153,71 +278,251 @@ impl HistoryService {
         Ok(records.into_iter().find(|r| r.id == id))
     }
 
-    // 删除上传记录
+    // 删除上传记录（移到回收站）
     pub async fn delete_upload_record(&self, id: &str) -> Result<bool, AppError> {
+        let _lock = lock_store(&self.upload_history_file).await?;
+        let records = self.load_upload_records().await?;
+        let (removed, kept): (Vec<_>, Vec<_>) = records.into_iter().partition(|r| r.id == id);
+
+        if removed.is_empty() {
+            return Ok(false);
+        }
+        self.move_to_trash(removed).await?;
+        self.save_upload_records(&kept).await?;
+        Ok(true)
+    }
+
+    // 逐条修改记录，`update` 返回 true 表示改过；返回被修改记录的 id（dry_run 时不保存）
+    pub async fn update_upload_records<F>(
+        &self,
+        mut update: F,
+        dry_run: bool,
+    ) -> Result<Vec<String>, AppError>
+    where
+        F: FnMut(&mut UploadHistoryRecord) -> bool,
+    {
+        let _lock = lock_store(&self.upload_history_file).await?;
         let mut records = self.load_upload_records().await?;
-        let initial_len = records.len();
-        records.retain(|r| r.id != id);
+        let updated: Vec<String> = records
+            .iter_mut()
+            .filter_map(|record| update(record).then(|| record.id.clone()))
+            .collect();
 
-        if records.len() < initial_len {
+        if !dry_run && !updated.is_empty() {
             self.save_upload_records(&records).await?;
-            Ok(true)
-        } else {
-            Ok(false)
         }
+
+        Ok(updated)
     }
 
-    // 清空历史记录
+    // 清空历史记录，删除的记录移到回收站并返回（dry_run 时只计算，不删除）
     pub async fn clear_upload_history(
         &self,
         upload_mode: Option<UploadMode>,
         older_than_days: Option<u32>,
-    ) -> Result<usize, AppError> {
+        dry_run: bool,
+    ) -> Result<Vec<UploadHistoryRecord>, AppError> {
+        let _lock = lock_store(&self.upload_history_file).await?;
+        let records = self.load_upload_records().await?;
+        let cutoff = older_than_days.map(|days| Utc::now() - chrono::Duration::days(days as i64));
+
+        let (removed, kept): (Vec<_>, Vec<_>) = records.into_iter().partition(|r| {
+            let old_enough = cutoff.is_none_or(|cutoff| r.timestamp <= cutoff);
+            let mode_matches = upload_mode
+                .as_ref()
+                .is_none_or(|mode| r.upload_mode == *mode);
+            old_enough && mode_matches
+        });
+
+        if !dry_run && !removed.is_empty() {
+            self.move_to_trash(removed.clone()).await?;
+            self.save_upload_records(&kept).await?;
+        }
+
+        Ok(removed)
+    }
+
+    // ========================================================================
+    // 回收站
+    // ========================================================================
+
+    /// Records in the trash, most recently deleted first
+    pub async fn get_trashed_records(&self) -> Result<Vec<TrashedHistoryRecord>, AppError> {
+        let mut trashed = self.load_trash().await?;
+        trashed.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        Ok(trashed)
+    }
+
+    /// Move the trashed records with `ids` back into the history. Returns the
+    /// ids restored; unknown ids and ids already in the history are skipped.
+    /// A restore that would take the history past its record limit is refused,
+    /// the records stay in the trash.
+    pub async fn restore_records(&self, ids: &[String]) -> Result<Vec<String>, AppError> {
+        // Upload history before the trash, like every method taking both
+        let _records_lock = lock_store(&self.upload_history_file).await?;
+        let _trash_lock = lock_store(&self.trash_file).await?;
         let mut records = self.load_upload_records().await?;
-        let initial_count = records.len();
+        let live: HashSet<String> = records.iter().map(|r| r.id.clone()).collect();
 
-        if let Some(days) = older_than_days {
-            let cutoff = Utc::now() - chrono::Duration::days(days as i64);
-            records.retain(|r| {
-                let should_keep_by_date = r.timestamp > cutoff;
-                let should_keep_by_mode = upload_mode
-                    .as_ref()
-                    .is_none_or(|mode| r.upload_mode != *mode);
-                should_keep_by_date || should_keep_by_mode
-            });
-        } else if let Some(mode) = upload_mode {
-            records.retain(|r| r.upload_mode != mode);
-        } else {
-            records.clear();
+        let (restored, kept): (Vec<_>, Vec<_>) = self
+            .load_trash()
+            .await?
+            .into_iter()
+            .partition(|trashed| ids.contains(&trashed.record.id));
+        if restored.is_empty() {
+            return Ok(Vec::new());
         }
 
-        let deleted_count = initial_count - records.len();
-        if deleted_count > 0 {
-            self.save_upload_records(&records).await?;
+        let mut restored_ids = Vec::new();
+        for trashed in restored {
+            if !live.contains(&trashed.record.id) {
+                restored_ids.push(trashed.record.id.clone());
+                records.push(trashed.record);
+            }
         }
+        if records.len() > MAX_UPLOAD_RECORDS {
+            return Err(AppError::Validation(format!(
+                "Restoring {} records would take the history past its limit of {} records, delete some first",
+                restored_ids.len(),
+                MAX_UPLOAD_RECORDS
+            )));
+        }
+        // Restored records go back to their place by upload time
+        records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        self.save_upload_records(&records).await?;
+        self.save_trash(&kept)?;
+        log_info!(
+            operation = "restore_history_records",
+            restored = restored_ids.len(),
+            "History records restored from the trash"
+        );
+        Ok(restored_ids)
+    }
+
+    /// Delete trashed records for good: those deleted at least
+    /// `older_than_days` ago, or all of them. Returns the purged records.
+    pub async fn purge_trash(
+        &self,
+        older_than_days: Option<u32>,
+        dry_run: bool,
+    ) -> Result<Vec<UploadHistoryRecord>, AppError> {
+        let _lock = lock_store(&self.trash_file).await?;
+        let cutoff = older_than_days.map(|days| Utc::now() - chrono::Duration::days(days as i64));
+        let (purged, kept): (Vec<_>, Vec<_>) = self
+            .load_trash()
+            .await?
+            .into_iter()
+            .partition(|trashed| cutoff.is_none_or(|cutoff| trashed.deleted_at <= cutoff));
+
+        if !dry_run && !purged.is_empty() {
+            self.save_trash(&kept)?;
+        }
+        Ok(purged.into_iter().map(|trashed| trashed.record).collect())
+    }
+
+    // The caller holds the upload history lock
+    async fn move_to_trash(&self, records: Vec<UploadHistoryRecord>) -> Result<(), AppError> {
+        let _lock = lock_store(&self.trash_file).await?;
+        let mut trashed = self.load_trash().await?;
+        let deleted_at = Utc::now();
+        trashed.retain(|t| !records.iter().any(|r| r.id == t.record.id));
+        trashed.extend(
+            records
+                .into_iter()
+                .map(|record| TrashedHistoryRecord { record, deleted_at }),
+        );
+        self.save_trash(&trashed)
+    }
+
+    async fn load_trash(&self) -> Result<Vec<TrashedHistoryRecord>, AppError> {
+        let Some(content) = self
+            .read_store(&self.trash_file)
+            .map_err(|e| AppError::FileSystem(format!("Failed to read history trash: {}", e)))?
+        else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self.parse_store(StoreKind::HistoryTrash, &self.trash_file, &content))
+    }
 
-        Ok(deleted_count)
+    fn save_trash(&self, trashed: &[TrashedHistoryRecord]) -> Result<(), AppError> {
+        let content = serde_json::to_string(trashed).map_err(AppError::Serialization)?;
+
+        self.write_store(&self.trash_file, &content)
+            .map_err(|e| AppError::FileSystem(format!("Failed to write history trash: {}", e)))
+    }
+
+    // 根据checksum查找所有重复记录（只在相同算法内比较），最新的在前
+    pub async fn find_duplicates_by_checksum(
+        &self,
+        checksum: &str,
+    ) -> Result<Vec<UploadHistoryRecord>, AppError> {
+        let mut records = self.load_upload_records().await?;
+        records.retain(|record| {
+            record.success && ChecksumAlgorithm::checksums_match(&record.checksum, checksum)
+        });
+        records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(records)
     }
 
-    // 根据checksum查找重复记录
+    // 查找可复用的重复记录，见 `DuplicateScope::pick`
     pub async fn find_duplicate_by_checksum(
         &self,
         checksum: &str,
+        target: Option<&UploadTarget>,
+        scope: DuplicateScope,
     ) -> Result<Option<UploadHistoryRecord>, AppError> {
-        let records = self.load_upload_records().await?;
+        let matches = self.find_duplicates_by_checksum(checksum).await?;
+        Ok(scope.pick(&matches, target).cloned())
+    }
 
-        for record in records {
-            if record.checksum == checksum {
-                return Ok(Some(record));
-            }
+    // 按checksum前缀查找，最新的在前
+    // `prefix` is hex, optionally with an `{algo}:` prefix to match one algorithm only
+    pub async fn search_by_checksum_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<UploadHistoryRecord>, AppError> {
+        let (algorithm, hex_prefix) = match prefix.split_once(':') {
+            Some((algorithm, hex)) => (Some(algorithm), hex),
+            None => (None, prefix),
+        };
+        if hex_prefix.len() < MIN_CHECKSUM_PREFIX_LEN
+            || !hex_prefix.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(AppError::Validation(format!(
+                "Checksum prefix must be at least {} hex characters",
+                MIN_CHECKSUM_PREFIX_LEN
+            )));
         }
+        let hex_prefix = hex_prefix.to_lowercase();
+
+        let mut records = self.load_upload_records().await?;
+        records.retain(|record| {
+            // Legacy records store plain sha256 hex
+            let (record_algorithm, hex) = record
+                .checksum
+                .split_once(':')
+                .unwrap_or(("sha256", &record.checksum));
+            algorithm.is_none_or(|algorithm| algorithm == record_algorithm)
+                && hex.to_lowercase().starts_with(&hex_prefix)
+        });
+        records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
 
-        Ok(None)
+        Ok(records)
     }
 
-    // 获取统计信息
-    pub async fn get_statistics(&self) -> Result<HistoryStatistics, AppError> {
-        let records = self.load_upload_records().await?;
+    // 获取统计信息，`include_trashed` 时回收站中的记录也计入
+    pub async fn get_statistics(
+        &self,
+        include_trashed: bool,
+    ) -> Result<HistoryStatistics, AppError> {
+        let mut records = self.load_upload_records().await?;
+        let trashed = self.load_trash().await?;
+        let trashed_records = trashed.len();
+        if include_trashed {
+            records.extend(trashed.into_iter().map(|trashed| trashed.record));
+        }
 
         if records.is_empty() {
             return Ok(HistoryStatistics {
@@ -225,8 +530,10 @@ impl HistoryService {
                 total_images_processed: 0,
                 total_size_processed: 0,
                 upload_modes: std::collections::HashMap::new(),
+                checksum_algorithms: std::collections::HashMap::new(),
                 oldest_record: None,
                 newest_record: None,
+                trashed_records,
             });
         }
 
@@ -236,11 +543,19 @@ impl HistoryService {
 
         let mut upload_modes = std::collections::HashMap::new();
         for record in &records {
-            let mode_name = match record.upload_mode {
-                UploadMode::ImageUpload => "image_upload",
-                UploadMode::ArticleUpload => "article_upload",
-            };
-            *upload_modes.entry(mode_name.to_string()).or_insert(0) += 1;
+            *upload_modes
+                .entry(record.upload_mode.stats_key().to_string())
+                .or_insert(0) += 1;
+        }
+
+        let mut checksum_algorithms = std::collections::HashMap::new();
+        for record in &records {
+            let algorithm = ChecksumAlgorithm::split_checksum(&record.checksum)
+                .map(|(algorithm, _)| algorithm.as_str())
+                .unwrap_or("unknown");
+            *checksum_algorithms
+                .entry(algorithm.to_string())
+                .or_insert(0) += 1;
         }
 
         let oldest_record = records.iter().map(|r| r.timestamp).min();
@@ -251,35 +566,1378 @@ impl HistoryService {
             total_images_processed,
             total_size_processed,
             upload_modes,
+            checksum_algorithms,
             oldest_record,
             newest_record,
+            trashed_records,
         })
     }
 
+    /// Bring in history from an app data archive.
+    ///
+    /// With `merge` the entries are added to the existing ones (upload records
+    /// already present by id are skipped), otherwise they replace them. Returns
+    /// how many upload records and file operations were added.
+    pub async fn import_history(
+        &self,
+        records: Vec<UploadHistoryRecord>,
+        operations: Vec<FileOperation>,
+        merge: bool,
+    ) -> Result<(usize, usize), AppError> {
+        // Always upload history before the operations log, so two writers
+        // can't each hold one and wait for the other
+        let _records_lock = lock_store(&self.upload_history_file).await?;
+        let _operations_lock = lock_store(&self.file_operations_file).await?;
+        let (mut all_records, mut all_operations) = if merge {
+            (
+                self.load_upload_records().await?,
+                self.load_file_operations().await?,
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        let known: HashSet<String> = all_records.iter().map(|r| r.id.clone()).collect();
+        let new_records: Vec<_> = records
+            .into_iter()
+            .filter(|r| !known.contains(&r.id))
+            .collect();
+        let records_added = new_records.len();
+        all_records.extend(new_records);
+        all_records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        all_records.truncate(MAX_UPLOAD_RECORDS);
+
+        let operations_added = operations.len();
+        all_operations.extend(operations);
+        all_operations.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        all_operations.truncate(MAX_FILE_OPERATIONS);
+
+        self.save_upload_records(&all_records).await?;
+        self.save_file_operations(&all_operations).await?;
+        Ok((records_added, operations_added))
+    }
+
+    // ========================================================================
+    // 文件操作日志
+    // ========================================================================
+
+    /// Append a backup/replace/restore entry to the file operations log.
+    ///
+    /// The log is diagnostic, so a failure to write it is logged and ignored
+    /// rather than failing the operation being recorded.
+    pub async fn record_file_operation(
+        &self,
+        operation_type: FileOperationType,
+        file_path: &str,
+        error: Option<String>,
+    ) {
+        let operation = FileOperation {
+            operation_type,
+            file_path: file_path.to_string(),
+            timestamp: std::time::SystemTime::now(),
+            success: error.is_none(),
+            error,
+        };
+
+        let result = async {
+            let _lock = lock_store(&self.file_operations_file).await?;
+            let mut operations = self.load_file_operations().await?;
+            operations.insert(0, operation);
+            operations.truncate(MAX_FILE_OPERATIONS);
+            self.save_file_operations(&operations).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            log_warn!(
+                operation = "record_file_operation",
+                file_path = %file_path,
+                error = %e,
+                "Failed to record file operation"
+            );
+        }
+    }
+
+    /// Most recent file operations first, optionally only those of one type
+    pub async fn get_file_operations(
+        &self,
+        limit: usize,
+        operation_type: Option<FileOperationType>,
+    ) -> Result<Vec<FileOperation>, AppError> {
+        Ok(self
+            .load_file_operations()
+            .await?
+            .into_iter()
+            .filter(|op| operation_type.is_none_or(|t| op.operation_type == t))
+            .take(limit)
+            .collect())
+    }
+
+    /// Empty the file operations log, returning how many entries were removed
+    pub async fn clear_file_operations(&self) -> Result<usize, AppError> {
+        let _lock = lock_store(&self.file_operations_file).await?;
+        let removed = self.load_file_operations().await?.len();
+        self.save_file_operations(&[]).await?;
+        Ok(removed)
+    }
+
+    async fn load_file_operations(&self) -> Result<Vec<FileOperation>, AppError> {
+        let Some(content) = self.read_store(&self.file_operations_file).map_err(|e| {
+            AppError::FileSystem(format!("Failed to read file operations log: {}", e))
+        })?
+        else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self.parse_store(
+            StoreKind::FileOperations,
+            &self.file_operations_file,
+            &content,
+        ))
+    }
+
+    async fn save_file_operations(&self, operations: &[FileOperation]) -> Result<(), AppError> {
+        let content = serde_json::to_string(operations).map_err(AppError::Serialization)?;
+
+        self.write_store(&self.file_operations_file, &content)
+            .map_err(|e| {
+                AppError::FileSystem(format!("Failed to write file operations log: {}", e))
+            })
+    }
+
+    // 查询的记录来源：上传记录，`include_trashed` 时加上回收站
+    async fn load_query_records(
+        &self,
+        q: &HistoryQuery,
+    ) -> Result<Vec<UploadHistoryRecord>, AppError> {
+        let mut records = self.load_upload_records().await?;
+        if q.include_trashed {
+            records.extend(
+                self.load_trash()
+                    .await?
+                    .into_iter()
+                    .map(|trashed| trashed.record),
+            );
+        }
+        Ok(records)
+    }
+
     // 私有辅助方法：加载上传记录
     async fn load_upload_records(&self) -> Result<Vec<UploadHistoryRecord>, AppError> {
-        if !self.upload_history_file.exists() {
+        let Some(content) = self.read_store(&self.upload_history_file).map_err(|e| {
+            AppError::FileSystem(format!("Failed to read upload history file: {}", e))
+        })?
+        else {
             return Ok(Vec::new());
+        };
+
+        Ok(self.parse_store(
+            StoreKind::UploadHistory,
+            &self.upload_history_file,
+            &content,
+        ))
+    }
+
+    // 解析失败（例如断电写了一半）时把文件移到一边，从空记录开始
+    fn parse_store<T: serde::de::DeserializeOwned>(
+        &self,
+        kind: StoreKind,
+        path: &Path,
+        content: &str,
+    ) -> Vec<T> {
+        match serde_json::from_str(content) {
+            Ok(records) => records,
+            Err(e) => {
+                CORRUPT_STORES.quarantine(kind, path, &e.to_string());
+                Vec::new()
+            }
         }
+    }
 
-        let content = fs::read_to_string(&self.upload_history_file).map_err(|e| {
-            AppError::FileSystem(format!("Failed to read upload history file: {}", e))
+    /// Salvage the complete records of the newest corrupt copy of the upload
+    /// history, file operations log or trash and merge them into the live
+    /// store. Records already present (same id) are skipped.
+    pub async fn recover_corrupt_store(
+        &self,
+        kind: StoreKind,
+    ) -> Result<StoreRecoveryResult, AppError> {
+        let path = match kind {
+            StoreKind::UploadHistory => &self.upload_history_file,
+            StoreKind::FileOperations => &self.file_operations_file,
+            StoreKind::HistoryTrash => &self.trash_file,
+            _ => {
+                return Err(AppError::Validation(format!(
+                    "{:?} is not a history store",
+                    kind
+                )))
+            }
+        };
+        let source = latest_corrupt_file(path).ok_or_else(|| {
+            AppError::Validation(format!("No corrupt copy of {} to recover", path.display()))
         })?;
+        let content = String::from_utf8_lossy(&fs::read(&source)?).to_string();
+        let values = salvage_array(&content, None);
+        let total = values.len();
 
-        let records: Vec<UploadHistoryRecord> =
-            serde_json::from_str(&content).map_err(AppError::Serialization)?;
+        let _lock = lock_store(path).await?;
+        let (recovered, unreadable) = if kind == StoreKind::HistoryTrash {
+            let salvaged: Vec<TrashedHistoryRecord> = values
+                .into_iter()
+                .filter_map(|value| serde_json::from_value(value).ok())
+                .collect();
+            let unreadable = total - salvaged.len();
+            let mut trashed = self.load_trash().await?;
+            let known: HashSet<String> = trashed.iter().map(|t| t.record.id.clone()).collect();
+            let before = trashed.len();
+            trashed.extend(
+                salvaged
+                    .into_iter()
+                    .filter(|t| !known.contains(&t.record.id)),
+            );
+            let recovered = trashed.len() - before;
+            self.save_trash(&trashed)?;
+            (recovered, unreadable)
+        } else if kind == StoreKind::UploadHistory {
+            let salvaged: Vec<UploadHistoryRecord> = values
+                .into_iter()
+                .filter_map(|value| serde_json::from_value(value).ok())
+                .collect();
+            let unreadable = total - salvaged.len();
+            let mut records = self.load_upload_records().await?;
+            let known: HashSet<String> = records.iter().map(|r| r.id.clone()).collect();
+            let before = records.len();
+            records.extend(salvaged.into_iter().filter(|r| !known.contains(&r.id)));
+            let recovered = records.len() - before;
+            records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+            records.truncate(MAX_UPLOAD_RECORDS);
+            self.save_upload_records(&records).await?;
+            (recovered, unreadable)
+        } else {
+            let salvaged: Vec<FileOperation> = values
+                .into_iter()
+                .filter_map(|value| serde_json::from_value(value).ok())
+                .collect();
+            let unreadable = total - salvaged.len();
+            let recovered = salvaged.len();
+            // Everything logged since the file was moved aside is newer
+            let mut operations = self.load_file_operations().await?;
+            operations.extend(salvaged);
+            operations.truncate(MAX_FILE_OPERATIONS);
+            self.save_file_operations(&operations).await?;
+            (recovered, unreadable)
+        };
 
-        Ok(records)
+        CORRUPT_STORES.resolve(kind);
+        log_info!(
+            operation = "recover_corrupt_store",
+            kind = ?kind,
+            source = %source.display(),
+            recovered = recovered,
+            "Recovered records from a corrupt store"
+        );
+
+        Ok(StoreRecoveryResult {
+            kind,
+            source: source.to_string_lossy().to_string(),
+            recovered,
+            unreadable,
+        })
     }
 
     // 私有辅助方法：保存上传记录
     async fn save_upload_records(&self, records: &[UploadHistoryRecord]) -> Result<(), AppError> {
         let content = serde_json::to_string_pretty(records).map_err(AppError::Serialization)?;
 
-        fs::write(&self.upload_history_file, content).map_err(|e| {
-            AppError::FileSystem(format!("Failed to write upload history file: {}", e))
-        })?;
+        self.write_store(&self.upload_history_file, &content)
+            .map_err(|e| {
+                AppError::FileSystem(format!("Failed to write upload history file: {}", e))
+            })?;
 
         Ok(())
     }
+
+    // 读取存储文件并记下修改时间，文件不存在时返回 None
+    fn read_store(&self, path: &Path) -> std::io::Result<Option<String>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        self.remember_modified(path);
+        Ok(Some(content))
+    }
+
+    // 写临时文件再重命名，同步盘或崩溃都不会看到半截 JSON
+    fn write_store(&self, path: &Path, content: &str) -> std::io::Result<()> {
+        // Instances on one machine share a lock, across machines the last
+        // writer wins, but say so
+        if self.changed_since_read(path) {
+            log_warn!(
+                operation = "history_write_conflict",
+                file_path = %path.display(),
+                "History file was changed by another instance since it was read, overwriting it"
+            );
+        }
+
+        write_atomic(path, content)?;
+        self.remember_modified(path);
+        Ok(())
+    }
+
+    fn changed_since_read(&self, path: &Path) -> bool {
+        let seen = self
+            .seen_modified
+            .lock()
+            .ok()
+            .and_then(|seen| seen.get(path).copied());
+        match (seen, fs::metadata(path).and_then(|m| m.modified())) {
+            (Some(seen), Ok(current)) => current != seen,
+            _ => false,
+        }
+    }
+
+    fn remember_modified(&self, path: &Path) {
+        if let (Ok(modified), Ok(mut seen)) = (
+            fs::metadata(path).and_then(|m| m.modified()),
+            self.seen_modified.lock(),
+        ) {
+            seen.insert(path.to_path_buf(), modified);
+        }
+    }
+}
+
+// 按查询条件过滤（分页和排序除外）
+fn apply_filters(records: &mut Vec<UploadHistoryRecord>, q: &HistoryQuery) {
+    if let Some(upload_mode) = &q.upload_mode {
+        records.retain(|r| r.upload_mode == *upload_mode);
+    }
+
+    if let Some(start) = q.start_date {
+        records.retain(|r| r.timestamp >= start);
+    }
+
+    if let Some(end) = q.end_date {
+        records.retain(|r| r.timestamp <= end);
+    }
+
+    if let Some(term) = &q.search_term {
+        let term_lower = term.to_lowercase();
+        records.retain(|r| {
+            r.image_name.to_lowercase().contains(&term_lower)
+                || r.uploaded_url.to_lowercase().contains(&term_lower)
+                || r.checksum.to_lowercase().contains(&term_lower)
+                || r.source_file
+                    .as_ref()
+                    .is_some_and(|f| f.to_lowercase().contains(&term_lower))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PaginatedResult;
+    use tempfile::TempDir;
+
+    // Upload history as written before config tagging and local paths
+    const LEGACY_UPLOAD_HISTORY: &str = r#"[
+        {
+            "id": "u1",
+            "timestamp": "2024-03-01T10:00:00Z",
+            "image_name": "hero.png",
+            "uploaded_url": "https://cdn.example.com/hero.png",
+            "upload_mode": "ImageUpload",
+            "source_file": null,
+            "file_size": 2048,
+            "checksum": "3a7bd3e2360a3d29eea436fcfb7e44c735d117c42d1c1835420b6b9942dd4f1b"
+        }
+    ]"#;
+
+    // The image history shape: failures kept, null URL and checksum
+    const IMAGE_HISTORY: &str = r#"[
+        {
+            "id": "i1",
+            "timestamp": "2024-03-02T10:00:00Z",
+            "image_name": "diagram.png",
+            "original_path": "/home/me/docs/diagram.png",
+            "uploaded_url": "https://cdn.example.com/diagram.png",
+            "upload_mode": "ArticleUpload",
+            "source_file": "/home/me/docs/post.md",
+            "success": true,
+            "file_size": 4096,
+            "error_message": null,
+            "checksum": "sha256:3a7bd3e2360a3d29eea436fcfb7e44c735d117c42d1c1835420b6b9942dd4f1b"
+        },
+        {
+            "id": "i2",
+            "timestamp": "2024-03-02T11:00:00Z",
+            "image_name": "broken.png",
+            "original_path": "/home/me/docs/broken.png",
+            "uploaded_url": null,
+            "upload_mode": "ImageUpload",
+            "source_file": null,
+            "success": false,
+            "file_size": 0,
+            "error_message": "Access denied",
+            "checksum": null
+        }
+    ]"#;
+
+    #[tokio::test]
+    async fn test_loads_both_old_history_shapes() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+
+        fs::write(&service.upload_history_file, LEGACY_UPLOAD_HISTORY).unwrap();
+        let records = service.get_upload_records(None).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].success);
+        assert_eq!(records[0].original_path, None);
+        assert_eq!(records[0].error_message, None);
+
+        fs::write(&service.upload_history_file, IMAGE_HISTORY).unwrap();
+        let records = service.get_upload_records(None).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].original_path.as_deref(),
+            Some("/home/me/docs/diagram.png")
+        );
+        let failed = &records[1];
+        assert!(!failed.success);
+        assert_eq!(failed.uploaded_url, "");
+        assert_eq!(failed.checksum, "");
+        assert_eq!(failed.error_message.as_deref(), Some("Access denied"));
+
+        // Failed uploads are never offered as duplicates
+        let checksum = "3a7bd3e2360a3d29eea436fcfb7e44c735d117c42d1c1835420b6b9942dd4f1b";
+        let matches = service.find_duplicates_by_checksum(checksum).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, "i1");
+
+        // Saving writes the unified shape, which loads again
+        service.delete_upload_record("i1").await.unwrap();
+        let records = service.get_upload_records(None).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "i2");
+    }
+
+    #[tokio::test]
+    async fn test_file_operations_are_newest_first_and_filterable() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+
+        service
+            .record_file_operation(FileOperationType::Backup, "/docs/a.md", None)
+            .await;
+        service
+            .record_file_operation(FileOperationType::Replace, "/docs/a.md", None)
+            .await;
+        service
+            .record_file_operation(
+                FileOperationType::Restore,
+                "/docs/a.md",
+                Some("disk full".to_string()),
+            )
+            .await;
+
+        let all = service.get_file_operations(10, None).await.unwrap();
+        let types: Vec<_> = all.iter().map(|op| op.operation_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                FileOperationType::Restore,
+                FileOperationType::Replace,
+                FileOperationType::Backup
+            ]
+        );
+        assert!(!all[0].success);
+        assert_eq!(all[0].error.as_deref(), Some("disk full"));
+
+        let backups = service
+            .get_file_operations(10, Some(FileOperationType::Backup))
+            .await
+            .unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(service.get_file_operations(2, None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_file_operations_log_is_bounded_and_clearable() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+
+        for i in 0..MAX_FILE_OPERATIONS + 5 {
+            service
+                .record_file_operation(FileOperationType::Replace, &format!("/docs/{}.md", i), None)
+                .await;
+        }
+
+        let operations = service.get_file_operations(usize::MAX, None).await.unwrap();
+        assert_eq!(operations.len(), MAX_FILE_OPERATIONS);
+        assert_eq!(
+            operations[0].file_path,
+            format!("/docs/{}.md", MAX_FILE_OPERATIONS + 4)
+        );
+
+        assert_eq!(
+            service.clear_file_operations().await.unwrap(),
+            MAX_FILE_OPERATIONS
+        );
+        assert!(service
+            .get_file_operations(10, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detects_writes_by_another_instance() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        service
+            .record_file_operation(FileOperationType::Replace, "/docs/a.md", None)
+            .await;
+        let file = temp_dir.path().join("file_operations.json");
+        assert!(!service.changed_since_read(&file));
+
+        // Another machine syncs its own version of the log in
+        let other = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        other.save_file_operations(&[]).await.unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert!(service.changed_since_read(&file));
+
+        // Last writer wins
+        service
+            .record_file_operation(FileOperationType::Backup, "/docs/b.md", None)
+            .await;
+        assert!(!service.changed_since_read(&file));
+        assert_eq!(
+            service.get_file_operations(10, None).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_term_filters_before_pagination() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        for (name, source) in [
+            ("a.png", Some("/blog/post.md")),
+            ("b.png", None),
+            ("c.png", Some("/Blog/other.md")),
+        ] {
+            service
+                .add_upload_record(UploadHistoryRecord {
+                    id: String::new(),
+                    timestamp: Utc::now(),
+                    image_name: name.to_string(),
+                    uploaded_url: format!("https://cdn.example.com/{}", name),
+                    upload_mode: UploadMode::ArticleUpload,
+                    source_file: source.map(str::to_string),
+                    file_size: 1,
+                    checksum: String::new(),
+                    variants: vec![],
+                    config_id: None,
+                    bucket: None,
+                    original_path: None,
+                    success: true,
+                    error_message: None,
+                    request_id: None,
+                    original_archived: false,
+                    object_key: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let query = HistoryQuery {
+            upload_mode: None,
+            start_date: None,
+            end_date: None,
+            limit: Some(1),
+            offset: Some(1),
+            search_term: Some("BLOG".to_string()),
+            sort_by: None,
+            sort_order: None,
+            include_trashed: false,
+        };
+        let records = service.get_upload_records(Some(query)).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].source_file.as_deref().unwrap().contains("log"));
+        assert_ne!(records[0].image_name, "b.png");
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicate_is_scoped_to_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let checksum = format!("sha256:{}", "ab".repeat(32));
+        service
+            .add_upload_record(UploadHistoryRecord {
+                id: String::new(),
+                timestamp: Utc::now(),
+                image_name: "a.png".to_string(),
+                uploaded_url: "https://blog.example.com/a.png".to_string(),
+                upload_mode: UploadMode::ImageUpload,
+                source_file: None,
+                file_size: 1,
+                checksum: checksum.clone(),
+                variants: vec![],
+                config_id: Some("blog".to_string()),
+                bucket: Some("blog-bucket".to_string()),
+                original_path: None,
+                success: true,
+                error_message: None,
+                request_id: None,
+                original_archived: false,
+                object_key: None,
+            })
+            .await
+            .unwrap();
+
+        let wiki = UploadTarget {
+            config_id: Some("wiki".to_string()),
+            bucket: Some("wiki-bucket".to_string()),
+        };
+        assert!(service
+            .find_duplicate_by_checksum(&checksum, Some(&wiki), DuplicateScope::SameConfig)
+            .await
+            .unwrap()
+            .is_none());
+
+        let any = service
+            .find_duplicate_by_checksum(&checksum, Some(&wiki), DuplicateScope::AnyConfig)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(any.config_id.as_deref(), Some("blog"));
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicates_returns_all_newest_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let checksum = format!("sha256:{}", "cd".repeat(32));
+        for (config_id, age) in [("wiki", 3), ("blog", 1), ("blog", 2)] {
+            service
+                .add_upload_record(UploadHistoryRecord {
+                    id: format!("{}-{}", config_id, age),
+                    timestamp: Utc::now() - chrono::Duration::days(age),
+                    image_name: "a.png".to_string(),
+                    uploaded_url: format!("https://{}.example.com/a.png", config_id),
+                    upload_mode: UploadMode::ImageUpload,
+                    source_file: None,
+                    file_size: 1,
+                    checksum: checksum.clone(),
+                    variants: vec![],
+                    config_id: Some(config_id.to_string()),
+                    bucket: None,
+                    original_path: None,
+                    success: true,
+                    error_message: None,
+                    request_id: None,
+                    original_archived: false,
+                    object_key: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let matches = service
+            .find_duplicates_by_checksum(&checksum)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = matches.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["blog-1", "blog-2", "wiki-3"]);
+
+        // The selected config's match wins over a newer one elsewhere
+        let wiki = UploadTarget {
+            config_id: Some("wiki".to_string()),
+            bucket: None,
+        };
+        let picked = DuplicateScope::AnyConfig
+            .pick(&matches, Some(&wiki))
+            .unwrap();
+        assert_eq!(picked.id, "wiki-3");
+    }
+
+    #[tokio::test]
+    async fn test_search_by_checksum_prefix_newest_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let shared = "deadbeef".to_string();
+        for (name, checksum, age) in [
+            ("old.png", format!("sha256:{}{}", shared, "0".repeat(56)), 2),
+            (
+                "new.png",
+                format!("{}{}", shared.to_uppercase(), "1".repeat(56)),
+                1,
+            ),
+            (
+                "blake.png",
+                format!("blake3:{}{}", shared, "2".repeat(56)),
+                3,
+            ),
+            ("other.png", format!("sha256:{}", "3".repeat(64)), 0),
+        ] {
+            service
+                .add_upload_record(UploadHistoryRecord {
+                    id: String::new(),
+                    timestamp: Utc::now() - chrono::Duration::days(age),
+                    image_name: name.to_string(),
+                    uploaded_url: format!("https://cdn.example.com/{}", name),
+                    upload_mode: UploadMode::ImageUpload,
+                    source_file: None,
+                    file_size: 1,
+                    checksum,
+                    variants: vec![],
+                    config_id: None,
+                    bucket: None,
+                    original_path: None,
+                    success: true,
+                    error_message: None,
+                    request_id: None,
+                    original_archived: false,
+                    object_key: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let names = |records: Vec<UploadHistoryRecord>| -> Vec<String> {
+            records.into_iter().map(|r| r.image_name).collect()
+        };
+        assert_eq!(
+            names(service.search_by_checksum_prefix("DEADBEEF").await.unwrap()),
+            vec!["new.png", "old.png", "blake.png"]
+        );
+        assert_eq!(
+            names(
+                service
+                    .search_by_checksum_prefix("sha256:deadbeef0")
+                    .await
+                    .unwrap()
+            ),
+            vec!["old.png"]
+        );
+        assert!(service.search_by_checksum_prefix("deadbee").await.is_err());
+        assert!(service.search_by_checksum_prefix("deadbeeg").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_upload_records_saves_only_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        for name in ["a.png", "b.png"] {
+            service
+                .add_upload_record(UploadHistoryRecord {
+                    id: name.to_string(),
+                    timestamp: Utc::now(),
+                    image_name: name.to_string(),
+                    uploaded_url: format!("https://img.old.com/{}", name),
+                    upload_mode: UploadMode::ImageUpload,
+                    source_file: None,
+                    file_size: 10,
+                    checksum: String::new(),
+                    variants: vec![],
+                    config_id: None,
+                    bucket: None,
+                    original_path: None,
+                    success: true,
+                    error_message: None,
+                    request_id: None,
+                    original_archived: false,
+                    object_key: None,
+                })
+                .await
+                .unwrap();
+        }
+        let rename = |record: &mut UploadHistoryRecord| {
+            let matched = record.image_name == "a.png";
+            if matched {
+                record.uploaded_url = "https://cdn.new.com/a.png".to_string();
+            }
+            matched
+        };
+
+        let preview = service.update_upload_records(rename, true).await.unwrap();
+        assert_eq!(preview, vec!["a.png".to_string()]);
+        let records = service.get_upload_records(None).await.unwrap();
+        assert!(records
+            .iter()
+            .all(|r| r.uploaded_url.starts_with("https://img.old.com/")));
+
+        service.update_upload_records(rename, false).await.unwrap();
+        let record = service.get_upload_record("a.png").await.unwrap().unwrap();
+        assert_eq!(record.uploaded_url, "https://cdn.new.com/a.png");
+    }
+
+    #[tokio::test]
+    async fn test_clear_upload_history_dry_run_keeps_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        for (name, age, upload_mode) in [
+            ("old.png", 40, UploadMode::ImageUpload),
+            ("old-article.png", 40, UploadMode::ArticleUpload),
+            ("new.png", 1, UploadMode::ImageUpload),
+        ] {
+            service
+                .add_upload_record(UploadHistoryRecord {
+                    id: String::new(),
+                    timestamp: Utc::now() - chrono::Duration::days(age),
+                    image_name: name.to_string(),
+                    uploaded_url: format!("https://cdn.example.com/{}", name),
+                    upload_mode,
+                    source_file: None,
+                    file_size: 10,
+                    checksum: String::new(),
+                    variants: vec![],
+                    config_id: None,
+                    bucket: None,
+                    original_path: None,
+                    success: true,
+                    error_message: None,
+                    request_id: None,
+                    original_archived: false,
+                    object_key: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let preview = service
+            .clear_upload_history(Some(UploadMode::ImageUpload), Some(30), true)
+            .await
+            .unwrap();
+        assert_eq!(preview.len(), 1);
+        assert_eq!(preview[0].image_name, "old.png");
+        assert_eq!(service.get_upload_records(None).await.unwrap().len(), 3);
+
+        let removed = service
+            .clear_upload_history(None, Some(30), false)
+            .await
+            .unwrap();
+        assert_eq!(removed.len(), 2);
+        let left = service.get_upload_records(None).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].image_name, "new.png");
+    }
+
+    #[tokio::test]
+    async fn test_deleted_records_go_through_the_trash() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let checksum = format!("sha256:{}", "ab".repeat(32));
+        let records = ["a", "b", "c"]
+            .into_iter()
+            .enumerate()
+            .map(|(age, id)| UploadHistoryRecord {
+                id: id.to_string(),
+                timestamp: Utc::now() - chrono::Duration::days(age as i64),
+                image_name: format!("{}.png", id),
+                uploaded_url: format!("https://cdn.example.com/{}.png", id),
+                upload_mode: UploadMode::ImageUpload,
+                source_file: None,
+                file_size: 10,
+                checksum: checksum.clone(),
+                variants: vec![],
+                config_id: None,
+                bucket: None,
+                original_path: None,
+                success: true,
+                error_message: None,
+                request_id: None,
+                original_archived: false,
+                object_key: None,
+            })
+            .collect();
+        service.add_batch_upload_records(records).await.unwrap();
+
+        assert!(service.delete_upload_record("a").await.unwrap());
+        service
+            .clear_upload_history(None, None, false)
+            .await
+            .unwrap();
+        assert!(service.get_upload_records(None).await.unwrap().is_empty());
+
+        // Trashed records are no duplicates and only counted when asked to
+        assert!(service
+            .find_duplicates_by_checksum(&checksum)
+            .await
+            .unwrap()
+            .is_empty());
+        let stats = service.get_statistics(false).await.unwrap();
+        assert_eq!((stats.total_records, stats.trashed_records), (0, 3));
+        assert_eq!(service.get_statistics(true).await.unwrap().total_records, 3);
+        let query = HistoryQuery {
+            include_trashed: true,
+            ..HistoryQuery::default()
+        };
+        assert_eq!(
+            service.get_upload_records(Some(query)).await.unwrap().len(),
+            3
+        );
+
+        let trashed = service.get_trashed_records().await.unwrap();
+        assert_eq!(trashed.len(), 3);
+        assert_eq!(trashed.last().unwrap().record.id, "a");
+
+        let restored = service
+            .restore_records(&["a".to_string(), "c".to_string(), "x".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(restored, vec!["a", "c"]);
+        let ids: Vec<String> = service
+            .get_upload_records(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec!["a", "c"]);
+
+        // Only "b" is left in the trash, deleted just now
+        assert!(service
+            .purge_trash(Some(1), false)
+            .await
+            .unwrap()
+            .is_empty());
+        let purged = service.purge_trash(None, false).await.unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].id, "b");
+        assert!(service.get_trashed_records().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_past_the_record_limit_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let record = |id: usize| UploadHistoryRecord {
+            id: format!("r{}", id),
+            timestamp: Utc::now() - chrono::Duration::seconds(id as i64),
+            image_name: format!("{}.png", id),
+            uploaded_url: format!("https://cdn.example.com/{}.png", id),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 10,
+            checksum: format!("sha256:{:064x}", id),
+            variants: vec![],
+            config_id: None,
+            bucket: None,
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        };
+        service
+            .add_batch_upload_records((0..MAX_UPLOAD_RECORDS).map(record).collect())
+            .await
+            .unwrap();
+        assert!(service.delete_upload_record("r0").await.unwrap());
+        service
+            .add_batch_upload_records(vec![record(MAX_UPLOAD_RECORDS)])
+            .await
+            .unwrap();
+
+        let result = service.restore_records(&["r0".to_string()]).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+        assert_eq!(
+            service.get_upload_records(None).await.unwrap().len(),
+            MAX_UPLOAD_RECORDS
+        );
+        assert_eq!(service.get_trashed_records().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_history_is_moved_aside_and_recovered() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let record = |id: &str| {
+            format!(
+                r#"{{"id": "{}", "timestamp": "2024-03-01T10:00:00Z", "image_name": "{}.png",
+                "uploaded_url": "https://cdn.example.com/{}.png", "upload_mode": "ImageUpload",
+                "source_file": null, "file_size": 10, "checksum": ""}}"#,
+                id, id, id
+            )
+        };
+        // Two complete records, the third cut off mid-write
+        let content = format!(
+            "[{},\n{},\n{}",
+            record("one"),
+            record("two"),
+            &record("three")[..40]
+        );
+        fs::write(&service.upload_history_file, content).unwrap();
+
+        assert!(service.get_upload_records(None).await.unwrap().is_empty());
+        assert!(!service.upload_history_file.exists());
+        fs::write(&service.file_operations_file, "[{\"operation_type\": ").unwrap();
+        assert!(service
+            .get_file_operations(10, None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Recorded after the corruption, kept by the recovery
+        let new_record: UploadHistoryRecord = serde_json::from_str(&record("new")).unwrap();
+        service.add_upload_record(new_record).await.unwrap();
+
+        let result = service
+            .recover_corrupt_store(StoreKind::UploadHistory)
+            .await
+            .unwrap();
+        assert_eq!(result.recovered, 2);
+        let ids: HashSet<String> = service
+            .get_upload_records(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.contains("one") && ids.contains("two") && ids.contains("new"));
+
+        let result = service
+            .recover_corrupt_store(StoreKind::FileOperations)
+            .await
+            .unwrap();
+        assert_eq!(result.recovered, 0);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_count_each_upload_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        for (name, upload_mode) in [
+            ("a.png", UploadMode::ClipboardUpload),
+            ("b.png", UploadMode::ClipboardUpload),
+            ("c.png", UploadMode::Reupload),
+            ("d.png", UploadMode::ImageUpload),
+        ] {
+            service
+                .add_upload_record(UploadHistoryRecord {
+                    id: String::new(),
+                    timestamp: Utc::now(),
+                    image_name: name.to_string(),
+                    uploaded_url: format!("https://cdn.example.com/{}", name),
+                    upload_mode,
+                    source_file: None,
+                    file_size: 10,
+                    checksum: String::new(),
+                    variants: vec![],
+                    config_id: None,
+                    bucket: None,
+                    original_path: None,
+                    success: true,
+                    error_message: None,
+                    request_id: None,
+                    original_archived: false,
+                    object_key: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let stats = service.get_statistics(false).await.unwrap();
+        assert_eq!(stats.upload_modes.get("clipboard_upload"), Some(&2));
+        assert_eq!(stats.upload_modes.get("reupload"), Some(&1));
+        assert_eq!(stats.upload_modes.get("image_upload"), Some(&1));
+        assert_eq!(stats.upload_modes.len(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_writers_keep_every_record() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Two services on one directory stand in for two app instances
+        let writers: Vec<_> = ["gui", "cli"]
+            .into_iter()
+            .map(|writer| {
+                let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+                tokio::spawn(async move {
+                    for i in 0..40 {
+                        service
+                            .add_upload_record(UploadHistoryRecord {
+                                id: format!("{}-{}", writer, i),
+                                timestamp: Utc::now(),
+                                image_name: format!("{}.png", i),
+                                uploaded_url: format!(
+                                    "https://cdn.example.com/{}/{}.png",
+                                    writer, i
+                                ),
+                                upload_mode: UploadMode::ImageUpload,
+                                source_file: None,
+                                file_size: 1,
+                                checksum: String::new(),
+                                variants: vec![],
+                                config_id: None,
+                                bucket: None,
+                                original_path: None,
+                                success: true,
+                                error_message: None,
+                                request_id: None,
+                                original_archived: false,
+                                object_key: None,
+                            })
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let ids: HashSet<String> = service
+            .get_upload_records(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids.len(), 80);
+        assert!(ids.contains("gui-39") && ids.contains("cli-0"));
+    }
+
+    #[tokio::test]
+    async fn test_page_total_counts_filtered_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let records = (0..250)
+            .map(|i| UploadHistoryRecord {
+                id: String::new(),
+                timestamp: Utc::now(),
+                image_name: format!("{}.png", i),
+                uploaded_url: format!("https://cdn.example.com/{}.png", i),
+                upload_mode: if i % 2 == 0 {
+                    UploadMode::ImageUpload
+                } else {
+                    UploadMode::ArticleUpload
+                },
+                source_file: None,
+                file_size: 1,
+                checksum: String::new(),
+                variants: vec![],
+                config_id: None,
+                bucket: None,
+                original_path: None,
+                success: true,
+                error_message: None,
+                request_id: None,
+                original_archived: false,
+                object_key: None,
+            })
+            .collect();
+        service.add_batch_upload_records(records).await.unwrap();
+
+        let page = |page: usize, upload_mode: Option<UploadMode>| HistoryQuery {
+            upload_mode,
+            start_date: None,
+            end_date: None,
+            limit: Some(20),
+            offset: Some((page - 1) * 20),
+            search_term: None,
+            sort_by: None,
+            sort_order: None,
+            include_trashed: false,
+        };
+
+        let (items, total) = service
+            .get_upload_records_page(page(13, None))
+            .await
+            .unwrap();
+        let last = PaginatedResult::new(items, total, 13, 20);
+        assert_eq!(
+            (last.items.len(), last.total, last.has_more),
+            (10, 250, false)
+        );
+
+        let (items, total) = service
+            .get_upload_records_page(page(12, None))
+            .await
+            .unwrap();
+        assert!(PaginatedResult::new(items, total, 12, 20).has_more);
+
+        // The total follows the filter, not the whole history
+        let (items, total) = service
+            .get_upload_records_page(page(7, Some(UploadMode::ArticleUpload)))
+            .await
+            .unwrap();
+        let last = PaginatedResult::new(items, total, 7, 20);
+        assert_eq!(
+            (last.items.len(), last.total, last.has_more),
+            (5, 125, false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sorting_applies_before_pagination() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let now = Utc::now();
+        // Stored in neither size nor name order
+        let records = [
+            (3, "c.png", 30),
+            (1, "A.png", 50),
+            (5, "b.png", 10),
+            (2, "d.png", 40),
+            (4, "E.png", 20),
+        ]
+        .into_iter()
+        .map(|(age, name, size)| UploadHistoryRecord {
+            id: name.to_string(),
+            timestamp: now - chrono::Duration::days(age),
+            image_name: name.to_string(),
+            uploaded_url: format!("https://cdn.example.com/{}", name),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: size,
+            checksum: String::new(),
+            variants: vec![],
+            config_id: None,
+            bucket: None,
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        })
+        .collect();
+        service.add_batch_upload_records(records).await.unwrap();
+
+        let page = |page: usize, sort_by, sort_order| HistoryQuery {
+            upload_mode: None,
+            start_date: None,
+            end_date: None,
+            limit: Some(2),
+            offset: Some((page - 1) * 2),
+            search_term: None,
+            sort_by,
+            sort_order,
+            include_trashed: false,
+        };
+        let ids = |records: Vec<UploadHistoryRecord>| -> Vec<String> {
+            records.into_iter().map(|r| r.id).collect()
+        };
+
+        // Default is newest first
+        let first = service
+            .get_upload_records(Some(page(1, None, None)))
+            .await
+            .unwrap();
+        assert_eq!(ids(first), vec!["A.png", "d.png"]);
+
+        let mut by_size = Vec::new();
+        for n in 1..=3 {
+            let records = service
+                .get_upload_records(Some(page(n, Some(HistorySortField::FileSize), None)))
+                .await
+                .unwrap();
+            by_size.extend(ids(records));
+        }
+        assert_eq!(by_size, vec!["A.png", "d.png", "c.png", "E.png", "b.png"]);
+
+        let second = service
+            .get_upload_records(Some(page(
+                2,
+                Some(HistorySortField::ImageName),
+                Some(SortOrder::Asc),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(ids(second), vec!["c.png", "d.png"]);
+
+        let oldest = service
+            .get_upload_records(Some(page(
+                3,
+                Some(HistorySortField::Timestamp),
+                Some(SortOrder::Asc),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(ids(oldest), vec!["A.png"]);
+    }
+
+    #[tokio::test]
+    async fn test_grouped_by_source_pages_over_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = HistoryService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let now = Utc::now();
+        let records = [
+            (Some("/blog/foo.md"), 1, 100),
+            (Some("/blog/foo.md"), 9, 200),
+            (None, 2, 50),
+            (Some("/blog/bar.md"), 3, 10),
+            (Some("/blog/foo.md"), 40, 300),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (source, age, size))| UploadHistoryRecord {
+            id: i.to_string(),
+            timestamp: now - chrono::Duration::days(age),
+            image_name: format!("{}.png", i),
+            uploaded_url: format!("https://cdn.example.com/{}.png", i),
+            upload_mode: UploadMode::ArticleUpload,
+            source_file: source.map(str::to_string),
+            file_size: size,
+            checksum: String::new(),
+            variants: vec![],
+            config_id: None,
+            bucket: None,
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        })
+        .collect();
+        service.add_batch_upload_records(records).await.unwrap();
+
+        let query = |start_date, limit, offset| HistoryQuery {
+            upload_mode: None,
+            start_date,
+            end_date: None,
+            limit,
+            offset,
+            search_term: None,
+            sort_by: None,
+            sort_order: None,
+            include_trashed: false,
+        };
+
+        let (groups, total) = service
+            .get_grouped_by_source(query(None, Some(2), Some(0)))
+            .await
+            .unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(groups[0].source_file.as_deref(), Some("/blog/foo.md"));
+        assert_eq!((groups[0].record_count, groups[0].total_bytes), (3, 600));
+        assert_eq!(groups[0].preview[0].id, "0");
+        assert_eq!(groups[1].source_file, None);
+
+        // Date filters apply to the records before grouping
+        let (groups, total) = service
+            .get_grouped_by_source(query(Some(now - chrono::Duration::days(30)), None, Some(2)))
+            .await
+            .unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].source_file.as_deref(), Some("/blog/bar.md"));
+
+        let (groups, _) = service
+            .get_grouped_by_source(query(Some(now - chrono::Duration::days(30)), None, None))
+            .await
+            .unwrap();
+        assert_eq!((groups[0].record_count, groups[0].total_bytes), (2, 300));
+    }
 }
@@ -1,18 +1,46 @@
-use crate::models::ImageInfo;
-use crate::utils::{AppError, Result};
-use crate::{log_debug, log_error, log_info, log_timing};
-use image::{imageops::FilterType, GenericImageView, ImageFormat, ImageReader};
+use crate::models::{
+    CacheStats, ChecksumAlgorithm, ImageInfo, ImageProcessingOptions, ImageRejectReason,
+    ImageValidation, ThumbnailFormat,
+};
+use crate::services::original_archive::OriginalArchive;
+use crate::services::thumbnail_cache::{ThumbnailKey, THUMBNAIL_WORK};
+use crate::utils::crash_reports::run_blocking;
+use crate::utils::{app_paths, AppError, Result};
+use crate::{log_debug, log_error, log_info, log_timing, log_warn};
+use image::{
+    imageops::FilterType, ColorType, DynamicImage, GenericImageView, ImageDecoder, ImageFormat,
+    ImageReader,
+};
 use sha2::{Digest, Sha256};
 use std::fs;
-use std::io::Cursor;
+use std::io::{BufRead, BufReader, Cursor, Seek};
 use std::path::Path;
-use tokio::task;
+use std::time::SystemTime;
+
+// Compress-to-target search bounds
+const MIN_TARGET_QUALITY: u8 = 30;
+const MAX_TARGET_QUALITY: u8 = 90;
+const MAX_QUALITY_PASSES: u8 = 4;
+const MAX_DOWNSCALE_PASSES: u8 = 3;
+const MIN_TARGET_DIMENSION: u32 = 64;
+
+/// Extensions of the raster formats the image crate decodes in this build
+const DECODABLE_EXTENSIONS: [&str; 9] = [
+    "jpg", "jpeg", "png", "webp", "bmp", "tiff", "tif", "gif", "ico",
+];
+/// Common camera and web formats that need a codec this build doesn't include
+const MISSING_CODEC_EXTENSIONS: [&str; 3] = ["heic", "heif", "avif"];
 
 /// Image processing service for thumbnail generation, compression, format conversion, and metadata extraction
 #[derive(Clone)]
 pub struct ImageService {
     cache_dir: Option<std::path::PathBuf>,
     client: Option<reqwest::Client>,
+    checksum_algorithm: ChecksumAlgorithm,
+    // None means no limit
+    max_image_bytes: Option<u64>,
+    // Where archive_original keeps source files, None when archiving is off
+    original_archive: Option<OriginalArchive>,
 }
 
 impl ImageService {
@@ -20,6 +48,68 @@ impl ImageService {
         Self {
             cache_dir: None,
             client: None,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            max_image_bytes: None,
+            original_archive: None,
+        }
+    }
+
+    /// Use a different hash for calculate_checksum / calculate_checksum_from_data
+    pub fn with_checksum_algorithm(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum_algorithm = algorithm;
+        self
+    }
+
+    /// Reject image files larger than `max_bytes` before they are read
+    pub fn with_max_image_size(mut self, max_bytes: u64) -> Self {
+        self.max_image_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Keep source files in `archive` before processing changes them
+    pub fn with_original_archive(mut self, archive: Option<OriginalArchive>) -> Self {
+        self.original_archive = archive;
+        self
+    }
+
+    /// Store the unprocessed bytes of an upload in the originals archive,
+    /// returning their sha256. A failure only costs the copy, never the upload.
+    pub async fn archive_original(&self, data: &[u8]) -> Option<String> {
+        let archive = self.original_archive.as_ref()?;
+        archive
+            .store(data)
+            .await
+            .map_err(|e| {
+                log_warn!(
+                    operation = "archive_original",
+                    error = %e,
+                    "Failed to archive original, uploading without a copy"
+                );
+            })
+            .ok()
+    }
+
+    pub fn exceeds_size_limit(&self, size: u64) -> bool {
+        self.max_image_bytes.is_some_and(|max| size > max)
+    }
+
+    /// Fail fast with `FileTooLarge` when the file is over the size limit,
+    /// looking only at its metadata. Returns the file size.
+    pub fn check_image_size(&self, image_path: &str) -> Result<u64> {
+        let size = fs::metadata(image_path)
+            .map_err(|e| {
+                AppError::FileSystem(format!("Failed to read image file {}: {}", image_path, e))
+            })?
+            .len();
+
+        match self.max_image_bytes {
+            Some(max) if size > max => Err(AppError::FileTooLarge(format!(
+                "{} is {:.1} MB, the limit is {:.1} MB",
+                image_path,
+                size as f64 / (1024.0 * 1024.0),
+                max as f64 / (1024.0 * 1024.0)
+            ))),
+            _ => Ok(size),
         }
     }
 
@@ -39,19 +129,13 @@ impl ImageService {
         Ok(Self {
             cache_dir: Some(cache_dir),
             client: Some(client),
+            ..Self::new()
         })
     }
 
     /// Get cache directory path
     fn get_cache_directory() -> Result<std::path::PathBuf> {
-        let app_data_dir = dirs::data_dir()
-            .ok_or_else(|| {
-                AppError::Configuration("Could not determine data directory".to_string())
-            })?
-            .join("imgtoss")
-            .join("thumbnails");
-
-        Ok(app_data_dir)
+        Ok(app_paths::data_dir()?.join("thumbnails"))
     }
 
     /// Generate a thumbnail for the given image
@@ -59,20 +143,44 @@ impl ImageService {
     /// # Arguments
     /// * `image_path` - Path to the source image file
     /// * `size` - Maximum dimension (width or height) for the thumbnail
+    /// * `format` - Output encoding, PNG and WebP keep transparency
     ///
     /// # Returns
-    /// * `Result<Vec<u8>>` - JPEG encoded thumbnail data
-    pub async fn generate_thumbnail(&self, image_path: &str, size: u32) -> Result<Vec<u8>> {
+    /// * `Result<Vec<u8>>` - Thumbnail data encoded as `format`
+    pub async fn generate_thumbnail(
+        &self,
+        image_path: &str,
+        size: u32,
+        format: ThumbnailFormat,
+    ) -> Result<Vec<u8>> {
         log_info!(
             operation = "generate_thumbnail",
             image_path = image_path,
             thumbnail_size = size,
+            format = ?format,
             "Starting thumbnail generation"
         );
 
+        let key = ThumbnailKey::for_file(Path::new(image_path), size, format);
+        if let Some(data) = key.as_ref().and_then(|key| THUMBNAIL_WORK.get(key)) {
+            log_debug!(
+                image_path = image_path,
+                "Thumbnail served from memory cache"
+            );
+            return Ok(data);
+        }
+
+        // Decoding a large photo takes a core for a while, a screen full of
+        // them at once would take all of them
+        let _permit = THUMBNAIL_WORK.acquire().await;
+        // Another call for the same thumbnail may have finished while we waited
+        if let Some(data) = key.as_ref().and_then(|key| THUMBNAIL_WORK.peek(key)) {
+            return Ok(data);
+        }
+
         let image_path_clone = image_path.to_string();
 
-        let result = task::spawn_blocking(move || {
+        let result = run_blocking("generate_thumbnail", move || {
             log_timing!(
                 {
                     // Load the image
@@ -137,106 +245,12 @@ impl ImageService {
                         ))
                     })?;
 
-                    // Calculate thumbnail dimensions while maintaining aspect ratio
-                    let (width, height) = img.dimensions();
-                    log_debug!(
-                        original_width = width,
-                        original_height = height,
-                        "Original image dimensions"
-                    );
-
-                    let (thumb_width, thumb_height) = if width > height {
-                        let ratio = height as f32 / width as f32;
-                        (size, (size as f32 * ratio) as u32)
-                    } else {
-                        let ratio = width as f32 / height as f32;
-                        ((size as f32 * ratio) as u32, size)
-                    };
-
-                    log_debug!(
-                        thumb_width = thumb_width,
-                        thumb_height = thumb_height,
-                        "Calculated thumbnail dimensions"
-                    );
-
-                    // Resize the image using high-quality filtering
-                    log_debug!("Resizing image to thumbnail dimensions");
-                    let thumbnail = img.resize(thumb_width, thumb_height, FilterType::Lanczos3);
-
-                    // Convert RGBA to RGB if necessary (JPEG doesn't support alpha channel)
-                    log_debug!("Converting color format if needed");
-                    let thumbnail_rgb = match thumbnail.color() {
-                        image::ColorType::Rgba8 => {
-                            log_debug!("Converting RGBA8 to RGB8");
-                            // Convert RGBA to RGB by removing alpha channel
-                            image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
-                                thumbnail.width(),
-                                thumbnail.height(),
-                                |x, y| {
-                                    let rgba = thumbnail.get_pixel(x, y);
-                                    image::Rgb([rgba[0], rgba[1], rgba[2]])
-                                },
-                            ))
-                        }
-                        image::ColorType::Rgba16 => {
-                            log_debug!("Converting RGBA16 to RGB8");
-                            // Convert RGBA16 to RGB8
-                            let rgba16_img = thumbnail.to_rgba16();
-                            image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
-                                thumbnail.width(),
-                                thumbnail.height(),
-                                |x, y| {
-                                    let rgba = rgba16_img.get_pixel(x, y);
-                                    // Convert 16-bit to 8-bit
-                                    image::Rgb([
-                                        (rgba[0] >> 8) as u8,
-                                        (rgba[1] >> 8) as u8,
-                                        (rgba[2] >> 8) as u8,
-                                    ])
-                                },
-                            ))
-                        }
-                        _ => {
-                            log_debug!("Using original color format");
-                            thumbnail // Already RGB or other compatible format
-                        }
-                    };
-
-                    // Encode as JPEG with good quality
-                    log_debug!("Encoding thumbnail as JPEG");
-                    let mut buffer = Vec::new();
-                    let mut cursor = Cursor::new(&mut buffer);
-
-                    thumbnail_rgb
-                        .write_to(&mut cursor, ImageFormat::Jpeg)
-                        .map_err(|e| {
-                            log_error!(
-                                error = %e,
-                                operation = "encode_thumbnail",
-                                "Failed to encode thumbnail to JPEG"
-                            );
-                            AppError::ImageProcessing(format!("Failed to encode thumbnail: {}", e))
-                        })?;
-
-                    log_debug!(
-                        thumbnail_size_bytes = buffer.len(),
-                        "Thumbnail encoding completed"
-                    );
-
-                    Ok(buffer)
+                    encode_thumbnail(img, size, format)
                 },
                 "generate_thumbnail"
             )
         })
-        .await
-        .map_err(|e| {
-            log_error!(
-                error = %e,
-                operation = "generate_thumbnail_task",
-                "Task join error during thumbnail generation"
-            );
-            AppError::ImageProcessing(format!("Task join error: {}", e))
-        })?;
+        .await;
 
         match result {
             Ok(thumbnail_data) => {
@@ -247,6 +261,9 @@ impl ImageService {
                     success = true,
                     "Thumbnail generation completed successfully"
                 );
+                if let Some(key) = key {
+                    THUMBNAIL_WORK.insert(key, &thumbnail_data);
+                }
                 Ok(thumbnail_data)
             }
             Err(e) => {
@@ -274,7 +291,7 @@ impl ImageService {
     pub async fn compress_image(&self, image_path: &str, quality: u8) -> Result<Vec<u8>> {
         let image_path = image_path.to_string();
 
-        task::spawn_blocking(move || {
+        run_blocking("compress_image", move || {
             // Validate quality parameter
             if quality == 0 || quality > 100 {
                 return Err(AppError::ImageProcessing(
@@ -341,7 +358,6 @@ impl ImageService {
             Ok(buffer)
         })
         .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))?
     }
 
     /// Convert image data to a different format
@@ -352,12 +368,11 @@ impl ImageService {
     ///
     /// # Returns
     /// * `Result<Vec<u8>>` - Converted image data
-    #[allow(dead_code)]
     pub async fn convert_format(&self, image_data: &[u8], target_format: &str) -> Result<Vec<u8>> {
         let image_data = image_data.to_vec();
         let target_format = target_format.to_lowercase();
 
-        task::spawn_blocking(move || {
+        run_blocking("convert_format", move || {
             // Parse target format
             let format = match target_format.as_str() {
                 "jpeg" | "jpg" => ImageFormat::Jpeg,
@@ -436,7 +451,340 @@ impl ImageService {
             Ok(buffer)
         })
         .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))?
+    }
+
+    /// Run the upload processing pipeline (resize, format conversion, compression,
+    /// metadata stripping) on in-memory image data.
+    ///
+    /// This is the single implementation shared by uploads and
+    /// `preview_image_processing`, so previews always match what gets uploaded.
+    ///
+    /// # Arguments
+    /// * `image_data` - Original image bytes
+    /// * `options` - Processing options derived from the OSS config
+    ///
+    /// # Returns
+    /// * `Result<ProcessedImage>` - Bytes to upload plus before/after details
+    pub async fn process_image(
+        &self,
+        image_data: Vec<u8>,
+        options: &ImageProcessingOptions,
+    ) -> Result<ProcessedImage> {
+        let options = options.clone();
+
+        run_blocking("process_image", move || {
+            Self::process_image_sync(image_data, &options)
+        })
+        .await
+    }
+
+    fn process_image_sync(
+        image_data: Vec<u8>,
+        options: &ImageProcessingOptions,
+    ) -> Result<ProcessedImage> {
+        // Formats the image crate can't identify (e.g. SVG) are uploaded untouched
+        let source_format = match image::guess_format(&image_data) {
+            Ok(format) => format,
+            Err(_) => return Ok(ProcessedImage::unprocessed(image_data, None, (0, 0))),
+        };
+
+        let (original_width, original_height) = ImageReader::new(Cursor::new(&image_data))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or((0, 0));
+
+        // Re-encoding a GIF would drop its animation frames
+        if options.is_passthrough() || source_format == ImageFormat::Gif {
+            return Ok(ProcessedImage::unprocessed(
+                image_data,
+                Some(source_format),
+                (original_width, original_height),
+            ));
+        }
+
+        // The embedded profile goes into the re-encoded image too, the pixels
+        // are only right in its color space. Converted images are plain sRGB.
+        let embedded_profile = read_icc_profile(&image_data);
+        let icc_profile = embedded_profile
+            .clone()
+            .filter(|profile| !icc_profile_name(profile).is_some_and(|name| is_srgb_name(&name)));
+
+        let target_format = match &options.output_format {
+            Some(format) => parse_output_format(format)?,
+            None => source_format,
+        };
+
+        let img = image::load_from_memory(&image_data).map_err(|e| {
+            AppError::ImageProcessing(format!("Failed to load image from memory: {}", e))
+        })?;
+
+        let (img, converted) = match icc_profile.filter(|_| options.convert_to_srgb) {
+            Some(profile) => match convert_to_srgb(&img, &profile) {
+                Some(img) => (img, true),
+                // Profiles qcms can't read (CMYK, LUT-only) stay embedded instead
+                None => {
+                    log_warn!(
+                        operation = "process_image",
+                        "Color profile could not be converted to sRGB, keeping it embedded"
+                    );
+                    (img, false)
+                }
+            },
+            None => (img, false),
+        };
+        let output_profile = embedded_profile.filter(|_| !converted);
+
+        let img = match options.max_dimension {
+            Some(max) if max > 0 && original_width.max(original_height) > max => {
+                img.resize(max, max, FilterType::Lanczos3)
+            }
+            _ => img,
+        };
+        let (width, height) = img.dimensions();
+
+        let quality = if options.compression_enabled {
+            options.quality.clamp(1, 100)
+        } else {
+            90
+        };
+        let encoded = encode_image(&img, target_format, quality, output_profile.as_deref())?;
+
+        // Plain recompression that ends up larger isn't worth it; keep the original
+        let structurally_unchanged = options.output_format.is_none()
+            && (width, height) == (original_width, original_height)
+            && !options.strip_metadata
+            && !converted;
+        if structurally_unchanged && encoded.len() >= image_data.len() {
+            log_debug!(
+                original_size = image_data.len(),
+                processed_size = encoded.len(),
+                "Recompression did not reduce size, keeping original bytes"
+            );
+            return Ok(ProcessedImage::unprocessed(
+                image_data,
+                Some(source_format),
+                (original_width, original_height),
+            ));
+        }
+
+        Ok(ProcessedImage {
+            original_size: image_data.len() as u64,
+            original_format: format_name(source_format),
+            original_width,
+            original_height,
+            format: format_name(target_format),
+            extension: format_extension(target_format),
+            width,
+            height,
+            data: encoded,
+            reencoded: true,
+        })
+    }
+
+    /// Shrink image bytes until they fit in `max_bytes`.
+    ///
+    /// JPEG quality is binary-searched first (at most MAX_QUALITY_PASSES encodes);
+    /// when even the lowest quality is too big, or the format is lossless, the
+    /// dimensions are reduced. The source format is always kept. Images already
+    /// under the target are returned untouched, SVG and GIF are skipped with a warning.
+    ///
+    /// # Arguments
+    /// * `image_data` - Image bytes, usually the output of `process_image`
+    /// * `max_bytes` - Size the result should not exceed
+    ///
+    /// # Returns
+    /// * `Result<TargetSizeResult>` - Final bytes plus the quality / dimensions used
+    pub async fn compress_to_target(
+        &self,
+        image_data: Vec<u8>,
+        max_bytes: u64,
+    ) -> Result<TargetSizeResult> {
+        run_blocking("compress_to_target", move || {
+            Self::compress_to_target_sync(image_data, max_bytes)
+        })
+        .await
+    }
+
+    fn compress_to_target_sync(image_data: Vec<u8>, max_bytes: u64) -> Result<TargetSizeResult> {
+        let fits = |data: &[u8]| data.len() as u64 <= max_bytes;
+        if fits(&image_data) {
+            return Ok(TargetSizeResult::untouched(image_data, max_bytes, None));
+        }
+
+        let format =
+            match image::guess_format(&image_data) {
+                Ok(ImageFormat::Gif) => return Ok(TargetSizeResult::untouched(
+                    image_data,
+                    max_bytes,
+                    Some(
+                        "GIF can't be recompressed without losing its animation, uploaded as is"
+                            .to_string(),
+                    ),
+                )),
+                Ok(format) => format,
+                Err(_) => {
+                    return Ok(TargetSizeResult::untouched(
+                        image_data,
+                        max_bytes,
+                        Some(
+                            "Unsupported format (e.g. SVG) can't be recompressed, uploaded as is"
+                                .to_string(),
+                        ),
+                    ))
+                }
+            };
+
+        let img = image::load_from_memory(&image_data).map_err(|e| {
+            AppError::ImageProcessing(format!("Failed to load image from memory: {}", e))
+        })?;
+        let profile = read_icc_profile(&image_data);
+        let encode = |img: &DynamicImage, quality: u8| {
+            encode_image(img, format, quality, profile.as_deref())
+        };
+        let original_size = image_data.len() as u64;
+        let mut passes = 0u8;
+
+        // 1. JPEG: binary search for the highest quality that fits
+        let mut quality = None;
+        let mut best: Option<Vec<u8>> = None;
+        if format == ImageFormat::Jpeg {
+            let (mut low, mut high) = (MIN_TARGET_QUALITY, MAX_TARGET_QUALITY);
+            while low <= high && passes < MAX_QUALITY_PASSES {
+                let mid = low + (high - low) / 2;
+                let encoded = encode(&img, mid)?;
+                passes += 1;
+                if fits(&encoded) {
+                    quality = Some(mid);
+                    best = Some(encoded);
+                    low = mid + 1;
+                } else {
+                    high = mid - 1;
+                }
+            }
+        }
+
+        // 2. Quality alone wasn't enough (or the format is lossless): downscale
+        let (mut width, mut height) = img.dimensions();
+        let mut data = match best {
+            Some(data) => data,
+            None => {
+                let quality_floor = MIN_TARGET_QUALITY;
+                let mut current = encode(&img, quality_floor)?;
+                passes += 1;
+                if format == ImageFormat::Jpeg {
+                    quality = Some(quality_floor);
+                }
+
+                let mut downscale_passes = 0;
+                while !fits(&current)
+                    && downscale_passes < MAX_DOWNSCALE_PASSES
+                    && width.max(height) > MIN_TARGET_DIMENSION
+                {
+                    // Encoded size scales roughly with the pixel count
+                    let scale = ((max_bytes as f64 / current.len() as f64).sqrt() * 0.9).min(0.9);
+                    let new_width = ((width as f64 * scale) as u32).max(1);
+                    let new_height = ((height as f64 * scale) as u32).max(1);
+                    let resized = img.resize(new_width, new_height, FilterType::Lanczos3);
+                    (width, height) = resized.dimensions();
+                    current = encode(&resized, quality_floor)?;
+                    passes += 1;
+                    downscale_passes += 1;
+                }
+                current
+            }
+        };
+
+        // Never upload something bigger than what we started with
+        if data.len() as u64 >= original_size {
+            data = image_data;
+            (width, height) = img.dimensions();
+            quality = None;
+        }
+
+        let target_met = fits(&data);
+        log_debug!(
+            original_size = original_size,
+            final_size = data.len(),
+            max_bytes = max_bytes,
+            passes = passes,
+            target_met = target_met,
+            "Compress-to-target finished"
+        );
+
+        Ok(TargetSizeResult {
+            original_size,
+            final_size: data.len() as u64,
+            target_met,
+            quality,
+            width,
+            height,
+            passes,
+            warning: (!target_met).then(|| {
+                format!(
+                    "Could not get the image under {} KB (final size {} KB)",
+                    max_bytes / 1024,
+                    data.len() / 1024
+                )
+            }),
+            data,
+        })
+    }
+
+    /// Downscaled copies of an image for `srcset`, one per requested width.
+    ///
+    /// Widths not smaller than the source are skipped and duplicates collapse; the
+    /// result is sorted by width and keeps the source format. Formats that can't be
+    /// re-encoded (SVG, GIF) produce no variants.
+    ///
+    /// # Arguments
+    /// * `image_data` - Image bytes as they will be uploaded
+    /// * `widths` - Target widths in pixels
+    /// * `quality` - JPEG quality for the variants
+    ///
+    /// # Returns
+    /// * `Result<Vec<(u32, Vec<u8>)>>` - (width, encoded bytes) per variant
+    pub async fn generate_size_variants(
+        &self,
+        image_data: &[u8],
+        widths: &[u32],
+        quality: u8,
+    ) -> Result<Vec<(u32, Vec<u8>)>> {
+        let image_data = image_data.to_vec();
+        let widths = widths.to_vec();
+
+        run_blocking("generate_size_variants", move || {
+            let format = match image::guess_format(&image_data) {
+                Ok(ImageFormat::Gif) | Err(_) => return Ok(Vec::new()),
+                Ok(format) => format,
+            };
+            let img = image::load_from_memory(&image_data).map_err(|e| {
+                AppError::ImageProcessing(format!("Failed to load image from memory: {}", e))
+            })?;
+            let profile = read_icc_profile(&image_data);
+            let (source_width, source_height) = img.dimensions();
+
+            let mut widths: Vec<u32> = widths
+                .into_iter()
+                .filter(|width| *width > 0 && *width < source_width)
+                .collect();
+            widths.sort_unstable();
+            widths.dedup();
+
+            widths
+                .into_iter()
+                .map(|width| {
+                    let height =
+                        ((source_height as u64 * width as u64) / source_width as u64).max(1) as u32;
+                    let resized = img.resize_exact(width, height, FilterType::Lanczos3);
+                    Ok((
+                        width,
+                        encode_image(&resized, format, quality, profile.as_deref())?,
+                    ))
+                })
+                .collect()
+        })
+        .await
     }
 
     /// Extract metadata information from an image file
@@ -449,7 +797,7 @@ impl ImageService {
     pub async fn get_image_info(&self, image_path: &str) -> Result<ImageInfo> {
         let image_path = image_path.to_string();
 
-        task::spawn_blocking(move || {
+        run_blocking("get_image_info", move || {
             // Get file size
             let metadata = fs::metadata(&image_path).map_err(|e| {
                 AppError::ImageProcessing(format!("Failed to read file metadata: {}", e))
@@ -468,9 +816,12 @@ impl ImageService {
                 .unwrap_or_else(|| "unknown".to_string());
 
             // Decode to get dimensions and color info
-            let img = reader.decode().map_err(|e| {
+            let decode_error = |e: image::ImageError| {
                 AppError::ImageProcessing(format!("Failed to decode image {}: {}", image_path, e))
-            })?;
+            };
+            let mut decoder = reader.into_decoder().map_err(decode_error)?;
+            let icc_profile = decoder.icc_profile().ok().flatten();
+            let img = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
 
             let (width, height) = img.dimensions();
 
@@ -489,16 +840,28 @@ impl ImageService {
                 _ => None,
             };
 
+            // EXIF is best effort and never fails the call
+            let exif = fs::File::open(&image_path)
+                .map(|file| read_exif(&mut BufReader::new(file)))
+                .unwrap_or_default();
+
             Ok(ImageInfo {
                 width,
                 height,
                 format: format_name,
                 size: file_size,
                 color_space,
+                taken_at: exif.taken_at,
+                camera_make: exif.camera_make,
+                camera_model: exif.camera_model,
+                orientation: exif.orientation,
+                has_gps: exif.has_gps,
+                icc_profile: icc_profile.map(|profile| {
+                    icc_profile_name(&profile).unwrap_or_else(|| "Unnamed ICC profile".to_string())
+                }),
             })
         })
         .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))?
     }
 
     /// Validate if a file is a supported image format
@@ -507,35 +870,95 @@ impl ImageService {
     /// * `image_path` - Path to the file to validate
     ///
     /// # Returns
-    /// * `Result<bool>` - True if the file is a supported image format
-    #[allow(dead_code)]
+    /// * `Result<bool>` - True if the extension is a decodable format and the
+    ///   file's header reads as one; the pixel data isn't decoded
     pub async fn is_supported_image(&self, image_path: &str) -> Result<bool> {
         let image_path = image_path.to_string();
 
-        task::spawn_blocking(move || {
+        run_blocking("is_supported_image", move || {
             // Check file extension first for quick validation
-            let path = Path::new(&image_path);
-            if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
-                let ext_lower = extension.to_lowercase();
-                match ext_lower.as_str() {
-                    "jpg" | "jpeg" | "png" | "webp" | "bmp" | "tiff" | "tif" | "gif" => {
-                        // Try to open the image to confirm it's valid
-                        match ImageReader::open(&image_path) {
-                            Ok(reader) => match reader.decode() {
-                                Ok(_) => Ok(true),
-                                Err(_) => Ok(false),
-                            },
-                            Err(_) => Ok(false),
-                        }
-                    }
-                    _ => Ok(false),
-                }
-            } else {
-                Ok(false)
-            }
+            let decodable = extension_lowercase(&image_path)
+                .is_some_and(|ext| DECODABLE_EXTENSIONS.contains(&ext.as_str()));
+            Ok(decodable
+                && ImageReader::open(&image_path)
+                    .is_ok_and(|reader| reader.into_dimensions().is_ok()))
         })
         .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))?
+    }
+
+    /// Whether `image_path` can be uploaded, with its format and dimensions.
+    /// Files over `max_bytes` are turned down before anything is decoded.
+    pub async fn validate_image(&self, image_path: &str, max_bytes: u64) -> ImageValidation {
+        let size = match tokio::fs::metadata(image_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                return ImageValidation::rejected(
+                    image_path,
+                    ImageRejectReason::NotFound,
+                    e.to_string(),
+                )
+            }
+        };
+        let rejected = |reason, message: String| ImageValidation {
+            size: Some(size),
+            ..ImageValidation::rejected(image_path, reason, message)
+        };
+        let extension = extension_lowercase(image_path).unwrap_or_default();
+
+        if MISSING_CODEC_EXTENSIONS.contains(&extension.as_str()) {
+            return rejected(
+                ImageRejectReason::MissingCodec,
+                format!(
+                    "{} images can't be decoded by this build, convert them to JPEG or PNG first",
+                    extension.to_uppercase()
+                ),
+            );
+        }
+        if size > max_bytes {
+            return rejected(
+                ImageRejectReason::TooLarge,
+                format!("Larger than the {} MB limit", max_bytes / 1024 / 1024),
+            );
+        }
+        if extension == "svg" {
+            return ImageValidation {
+                path: image_path.to_string(),
+                supported: true,
+                format: Some(extension),
+                width: None,
+                height: None,
+                size: Some(size),
+                reason: Some(ImageRejectReason::Svg),
+                message: Some("Vector image, uploaded as-is without processing".to_string()),
+            };
+        }
+        if !DECODABLE_EXTENSIONS.contains(&extension.as_str()) {
+            return rejected(
+                ImageRejectReason::UnsupportedFormat,
+                format!("Not a supported image format: .{}", extension),
+            );
+        }
+
+        let info = match self.is_supported_image(image_path).await {
+            Ok(true) => self.get_image_info(image_path).await,
+            Ok(false) => Err(AppError::ImageProcessing(
+                "File content is not a readable image".to_string(),
+            )),
+            Err(e) => Err(e),
+        };
+        match info {
+            Ok(info) => ImageValidation {
+                path: image_path.to_string(),
+                supported: true,
+                format: Some(info.format),
+                width: Some(info.width),
+                height: Some(info.height),
+                size: Some(size),
+                reason: None,
+                message: None,
+            },
+            Err(e) => rejected(ImageRejectReason::Corrupt, e.to_string()),
+        }
     }
 
     /// Get optimal compression quality based on image characteristics
@@ -546,7 +969,6 @@ impl ImageService {
     ///
     /// # Returns
     /// * `Result<u8>` - Recommended quality setting (1-100)
-    #[allow(dead_code)]
     pub async fn get_optimal_quality(
         &self,
         image_path: &str,
@@ -586,10 +1008,15 @@ impl ImageService {
     /// # Arguments
     /// * `image_data` - Image data as bytes
     /// * `size` - Maximum dimension (width or height) for the thumbnail
+    /// * `format` - Output encoding, PNG and WebP keep transparency
     ///
     /// # Returns
-    /// * `Result<Vec<u8>>` - JPEG encoded thumbnail data
-    fn generate_thumbnail_from_memory(image_data: &[u8], size: u32) -> Result<Vec<u8>> {
+    /// * `Result<Vec<u8>>` - Thumbnail data encoded as `format`
+    fn generate_thumbnail_from_memory(
+        image_data: &[u8],
+        size: u32,
+        format: ThumbnailFormat,
+    ) -> Result<Vec<u8>> {
         log_debug!(
             data_size = image_data.len(),
             thumbnail_size = size,
@@ -612,93 +1039,7 @@ impl ImageService {
             AppError::ImageProcessing(format!("Failed to load image from memory: {}", e))
         })?;
 
-        // Calculate thumbnail dimensions while maintaining aspect ratio
-        let (width, height) = img.dimensions();
-        log_debug!(
-            original_width = width,
-            original_height = height,
-            "Original image dimensions"
-        );
-
-        let (thumb_width, thumb_height) = if width > height {
-            let ratio = height as f32 / width as f32;
-            (size, (size as f32 * ratio) as u32)
-        } else {
-            let ratio = width as f32 / height as f32;
-            ((size as f32 * ratio) as u32, size)
-        };
-
-        log_debug!(
-            thumb_width = thumb_width,
-            thumb_height = thumb_height,
-            "Calculated thumbnail dimensions"
-        );
-
-        // Resize the image using high-quality filtering
-        log_debug!("Resizing image to thumbnail dimensions");
-        let thumbnail = img.resize(thumb_width, thumb_height, FilterType::Lanczos3);
-
-        // Convert RGBA to RGB if necessary (JPEG doesn't support alpha channel)
-        log_debug!("Converting color format if needed");
-        let thumbnail_rgb = match thumbnail.color() {
-            image::ColorType::Rgba8 => {
-                log_debug!("Converting RGBA8 to RGB8");
-                // Convert RGBA to RGB by removing alpha channel
-                image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
-                    thumbnail.width(),
-                    thumbnail.height(),
-                    |x, y| {
-                        let rgba = thumbnail.get_pixel(x, y);
-                        image::Rgb([rgba[0], rgba[1], rgba[2]])
-                    },
-                ))
-            }
-            image::ColorType::Rgba16 => {
-                log_debug!("Converting RGBA16 to RGB8");
-                // Convert RGBA16 to RGB8
-                let rgba16_img = thumbnail.to_rgba16();
-                image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
-                    thumbnail.width(),
-                    thumbnail.height(),
-                    |x, y| {
-                        let rgba = rgba16_img.get_pixel(x, y);
-                        // Convert 16-bit to 8-bit
-                        image::Rgb([
-                            (rgba[0] >> 8) as u8,
-                            (rgba[1] >> 8) as u8,
-                            (rgba[2] >> 8) as u8,
-                        ])
-                    },
-                ))
-            }
-            _ => {
-                log_debug!("Using original color format");
-                thumbnail // Already RGB or other compatible format
-            }
-        };
-
-        // Encode as JPEG with good quality
-        log_debug!("Encoding thumbnail as JPEG");
-        let mut buffer = Vec::new();
-        let mut cursor = Cursor::new(&mut buffer);
-
-        thumbnail_rgb
-            .write_to(&mut cursor, ImageFormat::Jpeg)
-            .map_err(|e| {
-                log_error!(
-                    error = %e,
-                    operation = "encode_thumbnail",
-                    "Failed to encode thumbnail to JPEG"
-                );
-                AppError::ImageProcessing(format!("Failed to encode thumbnail: {}", e))
-            })?;
-
-        log_debug!(
-            thumbnail_size_bytes = buffer.len(),
-            "Thumbnail encoding completed"
-        );
-
-        Ok(buffer)
+        encode_thumbnail(img, size, format)
     }
 
     /// Generate thumbnail synchronously (for use in blocking contexts)
@@ -769,145 +1110,50 @@ impl ImageService {
             AppError::ImageProcessing(format!("Failed to decode image {}: {}", image_path, e))
         })?;
 
-        // Calculate thumbnail dimensions while maintaining aspect ratio
-        let (width, height) = img.dimensions();
-        log_debug!(
-            original_width = width,
-            original_height = height,
-            "Original image dimensions"
-        );
-
-        let (thumb_width, thumb_height) = if width > height {
-            let ratio = height as f32 / width as f32;
-            (size, (size as f32 * ratio) as u32)
-        } else {
-            let ratio = width as f32 / height as f32;
-            ((size as f32 * ratio) as u32, size)
-        };
-
-        log_debug!(
-            thumb_width = thumb_width,
-            thumb_height = thumb_height,
-            "Calculated thumbnail dimensions"
-        );
-
-        // Resize the image using high-quality filtering
-        log_debug!("Resizing image to thumbnail dimensions");
-        let thumbnail = img.resize(thumb_width, thumb_height, FilterType::Lanczos3);
-
-        // Convert RGBA to RGB if necessary (JPEG doesn't support alpha channel)
-        log_debug!("Converting color format if needed");
-        let thumbnail_rgb = match thumbnail.color() {
-            image::ColorType::Rgba8 => {
-                log_debug!("Converting RGBA8 to RGB8");
-                // Convert RGBA to RGB by removing alpha channel
-                image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
-                    thumbnail.width(),
-                    thumbnail.height(),
-                    |x, y| {
-                        let rgba = thumbnail.get_pixel(x, y);
-                        image::Rgb([rgba[0], rgba[1], rgba[2]])
-                    },
-                ))
-            }
-            image::ColorType::Rgba16 => {
-                log_debug!("Converting RGBA16 to RGB8");
-                // Convert RGBA16 to RGB8
-                let rgba16_img = thumbnail.to_rgba16();
-                image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(
-                    thumbnail.width(),
-                    thumbnail.height(),
-                    |x, y| {
-                        let rgba = rgba16_img.get_pixel(x, y);
-                        // Convert 16-bit to 8-bit
-                        image::Rgb([
-                            (rgba[0] >> 8) as u8,
-                            (rgba[1] >> 8) as u8,
-                            (rgba[2] >> 8) as u8,
-                        ])
-                    },
-                ))
-            }
-            _ => {
-                log_debug!("Using original color format");
-                thumbnail // Already RGB or other compatible format
-            }
-        };
-
-        // Encode as JPEG with good quality
-        log_debug!("Encoding thumbnail as JPEG");
-        let mut buffer = Vec::new();
-        let mut cursor = Cursor::new(&mut buffer);
-
-        thumbnail_rgb
-            .write_to(&mut cursor, ImageFormat::Jpeg)
-            .map_err(|e| {
-                log_error!(
-                    error = %e,
-                    operation = "encode_thumbnail",
-                    "Failed to encode thumbnail to JPEG"
-                );
-                AppError::ImageProcessing(format!("Failed to encode thumbnail: {}", e))
-            })?;
-
-        log_debug!(
-            thumbnail_size_bytes = buffer.len(),
-            "Thumbnail encoding completed"
-        );
-
-        Ok(buffer)
+        encode_thumbnail(img, size, ThumbnailFormat::Jpeg)
     }
 
-    /// Calculate SHA256 checksum for an image file
+    /// Calculate the checksum of an image file with the configured algorithm
     ///
     /// # Arguments
     /// * `image_path` - Path to the image file
     ///
     /// # Returns
-    /// * `Result<String>` - Hexadecimal SHA256 checksum
+    /// * `Result<String>` - Checksum in `{algo}:{hex}` form
     pub async fn calculate_checksum(&self, image_path: &str) -> Result<String> {
         let image_path = image_path.to_string();
+        let algorithm = self.checksum_algorithm;
 
-        task::spawn_blocking(move || {
-            // Read the file
-            let data = fs::read(&image_path).map_err(|e| {
+        run_blocking("calculate_checksum", move || {
+            let file = fs::File::open(&image_path).map_err(|e| {
                 AppError::FileSystem(format!("Failed to read image file {}: {}", image_path, e))
             })?;
 
-            // Calculate SHA256 hash
-            let mut hasher = Sha256::new();
-            hasher.update(&data);
-            let result = hasher.finalize();
-
-            // Convert to hex string
-            Ok(format!("{:x}", result))
+            // Stream the file so large images aren't loaded into memory at once
+            hash_reader(algorithm, BufReader::new(file)).map_err(|e| {
+                AppError::FileSystem(format!("Failed to read image file {}: {}", image_path, e))
+            })
         })
         .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))?
     }
 
-    /// Calculate SHA256 checksum for image data
+    /// Calculate the checksum of in-memory image data with the configured algorithm
     ///
     /// # Arguments
     /// * `image_data` - Image data as bytes
     ///
     /// # Returns
-    /// * `Result<String>` - Hexadecimal SHA256 checksum
+    /// * `Result<String>` - Checksum in `{algo}:{hex}` form
     #[allow(dead_code)]
     pub async fn calculate_checksum_from_data(&self, image_data: &[u8]) -> Result<String> {
         let data = image_data.to_vec();
+        let algorithm = self.checksum_algorithm;
 
-        task::spawn_blocking(move || {
-            // Calculate SHA256 hash
-            let mut hasher = Sha256::new();
-            hasher.update(&data);
-            let result = hasher.finalize();
-
-            // Convert to hex string
-            Ok(format!("{:x}", result))
+        run_blocking("calculate_checksum_from_data", move || {
+            hash_reader(algorithm, Cursor::new(data))
+                .map_err(|e| AppError::ImageProcessing(format!("Failed to hash data: {}", e)))
         })
         .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))?
     }
 
     // ============================================================================
@@ -915,7 +1161,15 @@ impl ImageService {
     // ============================================================================
 
     /// Get thumbnail with caching support (200x200px)
-    pub async fn get_cached_thumbnail(&self, record_id: &str, image_url: &str) -> Result<Vec<u8>> {
+    ///
+    /// `sources` are tried in order, moving to the next one only when access is
+    /// denied (e.g. the public URL first, then a presigned one). The cache is keyed
+    /// by record id, so expiring presigned URLs don't matter once cached.
+    pub async fn get_cached_thumbnail(
+        &self,
+        record_id: &str,
+        sources: &[String],
+    ) -> Result<Vec<u8>> {
         // Check if caching is enabled
         let (cache_dir, _client) = match (&self.cache_dir, &self.client) {
             (Some(dir), Some(client)) => (dir, client),
@@ -929,7 +1183,7 @@ impl ImageService {
         log_info!(
             operation = "get_cached_thumbnail",
             record_id = record_id,
-            image_url = image_url,
+            source_count = sources.len(),
             "Getting cached thumbnail"
         );
 
@@ -964,15 +1218,55 @@ impl ImageService {
 
         // Cache miss, generate new thumbnail
         log_debug!("Cache miss, generating new thumbnail");
-        self.generate_and_cache_thumbnail(record_id, image_url)
-            .await
+        self.generate_and_cache_thumbnail(record_id, sources).await
+    }
+
+    /// Thumbnail already in the cache, without downloading anything
+    pub fn read_cached_thumbnail(&self, record_id: &str) -> Option<Vec<u8>> {
+        let cache_dir = self.cache_dir.as_ref()?;
+        std::fs::read(cache_dir.join(format!("{}_200.jpg", record_id))).ok()
     }
 
-    /// Generate and cache thumbnail from URL
+    /// Delete the cached thumbnails of `record_ids`, or only measure them on a
+    /// dry run. Returns how many files there were and their total size.
+    pub fn remove_cached_thumbnails<'a>(
+        &self,
+        record_ids: impl IntoIterator<Item = &'a str>,
+        dry_run: bool,
+    ) -> (usize, u64) {
+        let Some(cache_dir) = &self.cache_dir else {
+            return (0, 0);
+        };
+
+        let (mut count, mut bytes) = (0, 0);
+        for record_id in record_ids {
+            let cache_path = cache_dir.join(format!("{}_200.jpg", record_id));
+            let Ok(metadata) = std::fs::metadata(&cache_path) else {
+                continue;
+            };
+            if !dry_run {
+                if let Err(e) = std::fs::remove_file(&cache_path) {
+                    log_warn!(
+                        operation = "remove_cached_thumbnails",
+                        cache_path = %cache_path.display(),
+                        error = %e,
+                        "Failed to delete cached thumbnail"
+                    );
+                    continue;
+                }
+            }
+            count += 1;
+            bytes += metadata.len();
+        }
+
+        (count, bytes)
+    }
+
+    /// Generate and cache thumbnail from the first of `sources` that is readable
     pub async fn generate_and_cache_thumbnail(
         &self,
         record_id: &str,
-        image_url: &str,
+        sources: &[String],
     ) -> Result<Vec<u8>> {
         // Check if caching is enabled
         let (cache_dir, client) = match (&self.cache_dir, &self.client) {
@@ -987,140 +1281,119 @@ impl ImageService {
         log_info!(
             operation = "generate_and_cache_thumbnail",
             record_id = record_id,
-            image_url = image_url,
+            source_count = sources.len(),
             "Generating thumbnail from URL"
         );
 
+        let image_data = Self::download_thumbnail_source(client, sources).await?;
+
         let record_id_clone = record_id.to_string();
-        let image_url = image_url.to_string();
         let cache_path = cache_dir.join(format!("{}_200.jpg", record_id));
-        let client = client.clone();
-
-        let thumbnail_data = task::spawn_blocking(move || -> Result<Vec<u8>> {
-            // Download image
-            log_debug!("Downloading image from URL: {}", image_url);
-
-            let rt = tokio::runtime::Handle::current();
-            let image_data = rt
-                .block_on(async {
-                    let response = client
-                        .get(&image_url)
-                        .send()
-                        .await
-                        .map_err(|e| format!("Failed to send request: {}", e))?;
-
-                    // Check response status
-                    if !response.status().is_success() {
-                        return Err(format!("HTTP error: {}", response.status()));
-                    }
 
-                    let bytes = response
-                        .bytes()
-                        .await
-                        .map_err(|e| format!("Failed to read response bytes: {}", e))?;
-                    Ok::<Vec<u8>, String>(bytes.to_vec())
-                })
-                .map_err(|e| {
-                    AppError::ImageProcessing(format!("Failed to download image: {}", e))
-                })?;
+        let _permit = THUMBNAIL_WORK.acquire().await;
+        let thumbnail_data = run_blocking(
+            "generate_and_cache_thumbnail",
+            move || -> Result<Vec<u8>> {
+                log_debug!(
+                    image_size = image_data.len(),
+                    "Image downloaded successfully"
+                );
 
-            log_debug!(
-                image_size = image_data.len(),
-                "Image downloaded successfully"
-            );
+                // Validate that we have actual image data
+                if image_data.is_empty() {
+                    return Err(AppError::ImageProcessing(
+                        "Downloaded image data is empty".to_string(),
+                    ));
+                }
 
-            // Validate that we have actual image data
-            if image_data.is_empty() {
-                return Err(AppError::ImageProcessing(
-                    "Downloaded image data is empty".to_string(),
-                ));
-            }
+                // Try to generate thumbnail directly from memory first
+                match Self::generate_thumbnail_from_memory(&image_data, 200, ThumbnailFormat::Jpeg)
+                {
+                    Ok(thumbnail) => {
+                        log_debug!(
+                            thumbnail_size = thumbnail.len(),
+                            "Thumbnail generated from memory successfully"
+                        );
 
-            // Try to generate thumbnail directly from memory first
-            match Self::generate_thumbnail_from_memory(&image_data, 200) {
-                Ok(thumbnail) => {
-                    log_debug!(
-                        thumbnail_size = thumbnail.len(),
-                        "Thumbnail generated from memory successfully"
-                    );
+                        // Cache thumbnail
+                        if let Err(e) = std::fs::write(&cache_path, &thumbnail) {
+                            log_debug!(
+                                error = %e,
+                                cache_path = %cache_path.display(),
+                                "Failed to cache thumbnail, but continuing"
+                            );
+                        } else {
+                            log_debug!(
+                                cache_path = %cache_path.display(),
+                                "Thumbnail cached successfully"
+                            );
+                        }
 
-                    // Cache thumbnail
-                    if let Err(e) = std::fs::write(&cache_path, &thumbnail) {
+                        return Ok(thumbnail);
+                    }
+                    Err(e) => {
                         log_debug!(
                             error = %e,
-                            cache_path = %cache_path.display(),
-                            "Failed to cache thumbnail, but continuing"
-                        );
-                    } else {
-                        log_debug!(
-                            cache_path = %cache_path.display(),
-                            "Thumbnail cached successfully"
+                            "Failed to generate thumbnail from memory, trying file approach"
                         );
                     }
-
-                    return Ok(thumbnail);
-                }
-                Err(e) => {
-                    log_debug!(
-                        error = %e,
-                        "Failed to generate thumbnail from memory, trying file approach"
-                    );
                 }
-            }
 
-            // Fallback: use file-based approach
-            // Detect image format from data
-            let format = image::guess_format(&image_data).map_err(|e| {
-                AppError::ImageProcessing(format!("Failed to detect image format: {}", e))
-            })?;
+                // Fallback: use file-based approach
+                // Detect image format from data
+                let format = image::guess_format(&image_data).map_err(|e| {
+                    AppError::ImageProcessing(format!("Failed to detect image format: {}", e))
+                })?;
 
-            // Get appropriate file extension
-            let extension = match format {
-                image::ImageFormat::Jpeg => "jpg",
-                image::ImageFormat::Png => "png",
-                image::ImageFormat::WebP => "webp",
-                image::ImageFormat::Bmp => "bmp",
-                image::ImageFormat::Tiff => "tiff",
-                image::ImageFormat::Gif => "gif",
-                _ => "jpg", // Default fallback
-            };
+                // Get appropriate file extension
+                let extension = match format {
+                    image::ImageFormat::Jpeg => "jpg",
+                    image::ImageFormat::Png => "png",
+                    image::ImageFormat::WebP => "webp",
+                    image::ImageFormat::Bmp => "bmp",
+                    image::ImageFormat::Tiff => "tiff",
+                    image::ImageFormat::Gif => "gif",
+                    _ => "jpg", // Default fallback
+                };
 
-            // Write to temporary file with correct extension
-            let temp_dir = std::env::temp_dir();
-            let temp_path = temp_dir.join(format!("temp_image_{}.{}", record_id_clone, extension));
+                // Write to temporary file with correct extension
+                let temp_dir = std::env::temp_dir();
+                let temp_path =
+                    temp_dir.join(format!("temp_image_{}.{}", record_id_clone, extension));
 
-            std::fs::write(&temp_path, &image_data)
-                .map_err(|e| AppError::FileSystem(format!("Failed to write temp file: {}", e)))?;
+                std::fs::write(&temp_path, &image_data).map_err(|e| {
+                    AppError::FileSystem(format!("Failed to write temp file: {}", e))
+                })?;
 
-            // Generate thumbnail from file
-            let thumbnail = Self::generate_thumbnail_sync(temp_path.to_str().unwrap(), 200)?;
+                // Generate thumbnail from file
+                let thumbnail = Self::generate_thumbnail_sync(temp_path.to_str().unwrap(), 200)?;
 
-            // Clean up temp file
-            let _ = std::fs::remove_file(&temp_path);
+                // Clean up temp file
+                let _ = std::fs::remove_file(&temp_path);
 
-            log_debug!(
-                thumbnail_size = thumbnail.len(),
-                "Thumbnail generated from file successfully"
-            );
-
-            // Cache thumbnail
-            if let Err(e) = std::fs::write(&cache_path, &thumbnail) {
-                log_debug!(
-                    error = %e,
-                    cache_path = %cache_path.display(),
-                    "Failed to cache thumbnail, but continuing"
-                );
-            } else {
                 log_debug!(
-                    cache_path = %cache_path.display(),
-                    "Thumbnail cached successfully"
+                    thumbnail_size = thumbnail.len(),
+                    "Thumbnail generated from file successfully"
                 );
-            }
 
-            Ok(thumbnail)
-        })
-        .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))??;
+                // Cache thumbnail
+                if let Err(e) = std::fs::write(&cache_path, &thumbnail) {
+                    log_debug!(
+                        error = %e,
+                        cache_path = %cache_path.display(),
+                        "Failed to cache thumbnail, but continuing"
+                    );
+                } else {
+                    log_debug!(
+                        cache_path = %cache_path.display(),
+                        "Thumbnail cached successfully"
+                    );
+                }
+
+                Ok(thumbnail)
+            },
+        )
+        .await?;
 
         log_info!(
             operation = "generate_and_cache_thumbnail",
@@ -1133,6 +1406,66 @@ impl ImageService {
         Ok(thumbnail_data)
     }
 
+    /// Download the first readable source. 403/401 falls through to the next source,
+    /// 404 stops right away: the object is gone and a presigned URL won't bring it back.
+    async fn download_thumbnail_source(
+        client: &reqwest::Client,
+        sources: &[String],
+    ) -> Result<Vec<u8>> {
+        let mut access_denied = None;
+
+        for (index, url) in sources.iter().enumerate() {
+            // 不记录完整 URL，预签名链接里带着签名
+            log_debug!(source_index = index, "Downloading image for thumbnail");
+
+            let response = client.get(url).send().await.map_err(|e| {
+                AppError::ImageProcessing(format!(
+                    "Failed to download image: Failed to send request: {}",
+                    e
+                ))
+            })?;
+
+            match response.status() {
+                status if status.is_success() => {
+                    let bytes = response.bytes().await.map_err(|e| {
+                        AppError::ImageProcessing(format!(
+                            "Failed to download image: Failed to read response bytes: {}",
+                            e
+                        ))
+                    })?;
+                    return Ok(bytes.to_vec());
+                }
+                reqwest::StatusCode::NOT_FOUND => {
+                    return Err(AppError::NotFound(
+                        "Image no longer exists in storage (HTTP 404)".to_string(),
+                    ));
+                }
+                status @ (reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::UNAUTHORIZED) => {
+                    log_debug!(
+                        source_index = index,
+                        status = %status,
+                        "Access denied, trying next thumbnail source"
+                    );
+                    access_denied = Some(status);
+                }
+                status => {
+                    return Err(AppError::ImageProcessing(format!(
+                        "Failed to download image: HTTP error: {}",
+                        status
+                    )));
+                }
+            }
+        }
+
+        Err(match access_denied {
+            Some(status) => AppError::PermissionDenied(format!(
+                "Access to the image was denied (HTTP {})",
+                status.as_u16()
+            )),
+            None => AppError::InvalidInput("No image URL to download".to_string()),
+        })
+    }
+
     /// Clean up old cache files (30+ days)
     pub async fn cleanup_old_cache(&self) -> Result<usize> {
         let cache_dir = match &self.cache_dir {
@@ -1146,7 +1479,7 @@ impl ImageService {
             "Starting cache cleanup"
         );
 
-        let deleted_count = task::spawn_blocking(move || -> Result<usize> {
+        let deleted_count = run_blocking("cleanup_old_cache", move || -> Result<usize> {
             let mut deleted = 0;
             let cutoff_time =
                 std::time::SystemTime::now() - std::time::Duration::from_secs(30 * 24 * 60 * 60); // 30天
@@ -1204,8 +1537,7 @@ impl ImageService {
 
             Ok(deleted)
         })
-        .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))??;
+        .await?;
 
         log_info!(
             operation = "cleanup_old_cache",
@@ -1216,8 +1548,7 @@ impl ImageService {
         Ok(deleted_count)
     }
 
-    /// Clean up cache by size limit
-    #[allow(dead_code)]
+    /// Clean up cache by size limit, oldest thumbnails first
     pub async fn cleanup_cache_by_size(&self, max_size_mb: u64) -> Result<usize> {
         let cache_dir = match &self.cache_dir {
             Some(dir) => dir.clone(),
@@ -1232,7 +1563,7 @@ impl ImageService {
 
         let max_size_bytes = max_size_mb * 1024 * 1024;
 
-        let deleted_count = task::spawn_blocking(move || -> Result<usize> {
+        let deleted_count = run_blocking("cleanup_cache_by_size", move || -> Result<usize> {
             if !cache_dir.exists() {
                 return Ok(0);
             }
@@ -1304,8 +1635,7 @@ impl ImageService {
 
             Ok(deleted)
         })
-        .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))??;
+        .await?;
 
         log_info!(
             operation = "cleanup_cache_by_size",
@@ -1328,28 +1658,33 @@ impl ImageService {
         }
     }
 
-    /// Get cache statistics
-    #[allow(dead_code)]
+    /// Get cache statistics: hit/miss counters of the in-memory thumbnail
+    /// cache, and the disk cache when this service has one
     pub async fn get_cache_stats(&self) -> Result<CacheStats> {
-        let cache_dir = match &self.cache_dir {
-            Some(dir) => dir.clone(),
-            None => return Err(AppError::Configuration("Caching not enabled".to_string())),
+        let memory = THUMBNAIL_WORK.stats();
+        let mut stats = CacheStats {
+            memory_hits: memory.hits,
+            memory_misses: memory.misses,
+            memory_entries: memory.entries,
+            memory_bytes: memory.bytes,
+            total_files: 0,
+            total_size_bytes: 0,
+            oldest_file: None,
+            newest_file: None,
+        };
+        let Some(cache_dir) = self.cache_dir.clone() else {
+            return Ok(stats);
         };
 
-        let stats = task::spawn_blocking(move || -> Result<CacheStats> {
+        let stats = run_blocking("get_cache_stats", move || -> Result<CacheStats> {
             if !cache_dir.exists() {
-                return Ok(CacheStats {
-                    total_files: 0,
-                    total_size_bytes: 0,
-                    oldest_file: None,
-                    newest_file: None,
-                });
+                return Ok(stats);
             }
 
             let mut total_files = 0;
             let mut total_size = 0;
-            let mut oldest: Option<std::time::SystemTime> = None;
-            let mut newest: Option<std::time::SystemTime> = None;
+            let mut oldest: Option<SystemTime> = None;
+            let mut newest: Option<SystemTime> = None;
 
             let entries = std::fs::read_dir(&cache_dir).map_err(|e| {
                 AppError::FileSystem(format!("Failed to read cache directory: {}", e))
@@ -1386,28 +1721,412 @@ impl ImageService {
                 }
             }
 
-            Ok(CacheStats {
-                total_files,
-                total_size_bytes: total_size,
-                oldest_file: oldest,
-                newest_file: newest,
-            })
+            stats.total_files = total_files;
+            stats.total_size_bytes = total_size;
+            stats.oldest_file = oldest.map(chrono::DateTime::from);
+            stats.newest_file = newest.map(chrono::DateTime::from);
+            Ok(stats)
         })
-        .await
-        .map_err(|e| AppError::ImageProcessing(format!("Task join error: {}", e)))??;
+        .await?;
 
         Ok(stats)
     }
 }
 
-/// Cache statistics
+/// Result of the upload processing pipeline
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
-pub struct CacheStats {
-    pub total_files: usize,
-    pub total_size_bytes: u64,
-    pub oldest_file: Option<std::time::SystemTime>,
-    pub newest_file: Option<std::time::SystemTime>,
+pub struct ProcessedImage {
+    pub data: Vec<u8>,
+    pub format: String,
+    /// File extension matching `format`, None when the format is unknown
+    pub extension: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub original_size: u64,
+    pub original_format: String,
+    pub original_width: u32,
+    pub original_height: u32,
+    /// Re-encoding drops EXIF (including GPS); false means the original bytes are kept
+    pub reencoded: bool,
+}
+
+impl ProcessedImage {
+    fn unprocessed(data: Vec<u8>, format: Option<ImageFormat>, dimensions: (u32, u32)) -> Self {
+        let (width, height) = dimensions;
+        let format_label = format
+            .map(format_name)
+            .unwrap_or_else(|| "unknown".to_string());
+
+        Self {
+            original_size: data.len() as u64,
+            original_format: format_label.clone(),
+            original_width: width,
+            original_height: height,
+            format: format_label,
+            extension: None,
+            width,
+            height,
+            data,
+            reencoded: false,
+        }
+    }
+}
+
+/// Result of `ImageService::compress_to_target`
+#[derive(Debug, Clone)]
+pub struct TargetSizeResult {
+    pub data: Vec<u8>,
+    pub original_size: u64,
+    pub final_size: u64,
+    pub target_met: bool,
+    /// JPEG quality used, None when the image wasn't re-encoded with a quality
+    pub quality: Option<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Number of encodes it took
+    pub passes: u8,
+    /// Why the target wasn't met or the image was skipped
+    pub warning: Option<String>,
+}
+
+impl TargetSizeResult {
+    fn untouched(data: Vec<u8>, max_bytes: u64, warning: Option<String>) -> Self {
+        let (width, height) = ImageReader::new(Cursor::new(&data))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or((0, 0));
+
+        Self {
+            original_size: data.len() as u64,
+            final_size: data.len() as u64,
+            target_met: data.len() as u64 <= max_bytes,
+            quality: None,
+            width,
+            height,
+            passes: 0,
+            warning,
+            data,
+        }
+    }
+}
+
+/// Resize `img` to fit `size` pixels on its longer edge and encode it as
+/// `format`. PNG and WebP keep the alpha channel, JPEG can't carry one.
+fn encode_thumbnail(img: DynamicImage, size: u32, format: ThumbnailFormat) -> Result<Vec<u8>> {
+    let (width, height) = img.dimensions();
+    let (thumb_width, thumb_height) = if width > height {
+        let ratio = height as f32 / width as f32;
+        (size, (size as f32 * ratio) as u32)
+    } else {
+        let ratio = width as f32 / height as f32;
+        ((size as f32 * ratio) as u32, size)
+    };
+    log_debug!(
+        original_width = width,
+        original_height = height,
+        thumb_width = thumb_width,
+        thumb_height = thumb_height,
+        "Calculated thumbnail dimensions"
+    );
+
+    let thumbnail = img.resize(thumb_width, thumb_height, FilterType::Lanczos3);
+    // Each encoder only takes some color types
+    let (thumbnail, image_format) = match format {
+        ThumbnailFormat::Jpeg => match thumbnail.color() {
+            ColorType::L8 | ColorType::Rgb8 => (thumbnail, ImageFormat::Jpeg),
+            _ => (
+                DynamicImage::ImageRgb8(thumbnail.to_rgb8()),
+                ImageFormat::Jpeg,
+            ),
+        },
+        ThumbnailFormat::Webp => match thumbnail.color() {
+            ColorType::Rgb8 | ColorType::Rgba8 => (thumbnail, ImageFormat::WebP),
+            color if color.has_alpha() => (
+                DynamicImage::ImageRgba8(thumbnail.to_rgba8()),
+                ImageFormat::WebP,
+            ),
+            _ => (
+                DynamicImage::ImageRgb8(thumbnail.to_rgb8()),
+                ImageFormat::WebP,
+            ),
+        },
+        ThumbnailFormat::Png => match thumbnail.color() {
+            ColorType::Rgb32F => (
+                DynamicImage::ImageRgb16(thumbnail.to_rgb16()),
+                ImageFormat::Png,
+            ),
+            ColorType::Rgba32F => (
+                DynamicImage::ImageRgba16(thumbnail.to_rgba16()),
+                ImageFormat::Png,
+            ),
+            _ => (thumbnail, ImageFormat::Png),
+        },
+    };
+
+    let mut buffer = Vec::new();
+    thumbnail
+        .write_to(&mut Cursor::new(&mut buffer), image_format)
+        .map_err(|e| {
+            log_error!(
+                error = %e,
+                format = ?format,
+                operation = "encode_thumbnail",
+                "Failed to encode thumbnail"
+            );
+            AppError::ImageProcessing(format!("Failed to encode thumbnail: {}", e))
+        })?;
+    log_debug!(
+        thumbnail_size_bytes = buffer.len(),
+        "Thumbnail encoding completed"
+    );
+    Ok(buffer)
+}
+
+/// Hash everything from `reader` and format it as `{algo}:{hex}`
+fn hash_reader<R: std::io::Read>(
+    algorithm: ChecksumAlgorithm,
+    mut reader: R,
+) -> std::io::Result<String> {
+    let hex = match algorithm {
+        ChecksumAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut reader, &mut hasher)?;
+            format!("{:x}", hasher.finalize())
+        }
+        ChecksumAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            std::io::copy(&mut reader, &mut hasher)?;
+            hasher.finalize().to_hex().to_string()
+        }
+    };
+
+    Ok(format!("{}:{}", algorithm.as_str(), hex))
+}
+
+/// EXIF fields imgtoss cares about
+#[derive(Debug, Clone, Default)]
+pub struct ExifMetadata {
+    pub taken_at: Option<chrono::NaiveDateTime>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub orientation: Option<u16>,
+    pub has_gps: Option<bool>,
+}
+
+/// Read EXIF from an image container (JPEG, TIFF, HEIF, PNG, WebP).
+///
+/// Missing or corrupt EXIF never fails: whatever could be parsed is returned
+/// and everything else stays None.
+pub fn read_exif<R: BufRead + Seek>(reader: &mut R) -> ExifMetadata {
+    let mut exif_reader = exif::Reader::new();
+    exif_reader.continue_on_error(true);
+
+    let exif = match exif_reader.read_from_container(reader).or_else(|e| {
+        e.distill_partial_result(|errors| {
+            log_debug!(error_count = errors.len(), "Ignoring partial EXIF errors");
+        })
+    }) {
+        Ok(exif) => exif,
+        Err(_) => return ExifMetadata::default(),
+    };
+
+    let ascii_field = |tag: exif::Tag| -> Option<String> {
+        match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+            exif::Value::Ascii(values) => values
+                .first()
+                .map(|v| {
+                    String::from_utf8_lossy(v)
+                        .trim_end_matches('\0')
+                        .trim()
+                        .to_string()
+                })
+                .filter(|v| !v.is_empty()),
+            _ => None,
+        }
+    };
+
+    let taken_at = [exif::Tag::DateTimeOriginal, exif::Tag::DateTime]
+        .into_iter()
+        .find_map(|tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+            exif::Value::Ascii(values) => {
+                let dt = exif::DateTime::from_ascii(values.first()?).ok()?;
+                chrono::NaiveDate::from_ymd_opt(dt.year as i32, dt.month as u32, dt.day as u32)?
+                    .and_hms_opt(dt.hour as u32, dt.minute as u32, dt.second as u32)
+            }
+            _ => None,
+        });
+
+    let orientation = exif
+        .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .and_then(|value| u16::try_from(value).ok());
+
+    let has_gps = exif
+        .fields()
+        .any(|field| field.tag.context() == exif::Context::Gps);
+
+    ExifMetadata {
+        taken_at,
+        camera_make: ascii_field(exif::Tag::Make),
+        camera_model: ascii_field(exif::Tag::Model),
+        orientation,
+        has_gps: Some(has_gps),
+    }
+}
+
+fn extension_lowercase(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+fn format_name(format: ImageFormat) -> String {
+    format!("{:?}", format).to_lowercase()
+}
+
+fn format_extension(format: ImageFormat) -> Option<String> {
+    format.extensions_str().first().map(|ext| ext.to_string())
+}
+
+fn parse_output_format(format: &str) -> Result<ImageFormat> {
+    match format.to_lowercase().as_str() {
+        "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+        "png" => Ok(ImageFormat::Png),
+        "webp" => Ok(ImageFormat::WebP),
+        _ => Err(AppError::ImageProcessing(format!(
+            "Unsupported output format: {}",
+            format
+        ))),
+    }
+}
+
+/// Embedded ICC profile of encoded image bytes; None when there is none or
+/// the format can't carry one
+fn read_icc_profile(image_data: &[u8]) -> Option<Vec<u8>> {
+    ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?
+        .icc_profile()
+        .ok()
+        .flatten()
+}
+
+fn icc_u32(bytes: &[u8], at: usize) -> Option<usize> {
+    let field = bytes.get(at..at.checked_add(4)?)?;
+    Some(u32::from_be_bytes(field.try_into().ok()?) as usize)
+}
+
+/// Name of an ICC profile from its 'desc' tag: ASCII in v2 profiles, the
+/// first UTF-16 record of a 'mluc' in v4 ones
+fn icc_profile_name(profile: &[u8]) -> Option<String> {
+    // The tag table follows the 128-byte header: a count, then 12-byte
+    // (signature, offset, size) entries
+    let count = icc_u32(profile, 128)?;
+    let entry = (0..count.min(256))
+        .map(|i| 132 + i * 12)
+        .find(|&entry| profile.get(entry..entry + 4) == Some(b"desc"))?;
+    let offset = icc_u32(profile, entry + 4)?;
+    let tag = profile.get(offset..offset.checked_add(icc_u32(profile, entry + 8)?)?)?;
+
+    let name = match tag.get(..4)? {
+        b"desc" => {
+            let length = icc_u32(tag, 8)?;
+            String::from_utf8_lossy(tag.get(12..12 + length)?).into_owned()
+        }
+        b"mluc" => {
+            let length = icc_u32(tag, 20)?;
+            let start = icc_u32(tag, 24)?;
+            let units: Vec<u16> = tag
+                .get(start..start.checked_add(length)?)?
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let name = name.trim_matches(char::from(0)).trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// sRGB profiles are left alone: browsers assume sRGB for untagged images
+fn is_srgb_name(name: &str) -> bool {
+    name.to_lowercase().contains("srgb")
+}
+
+/// Pixels of `img` converted from the RGB ICC `profile` to sRGB; None when
+/// the profile isn't RGB or qcms can't build a transform from it
+fn convert_to_srgb(img: &DynamicImage, profile: &[u8]) -> Option<DynamicImage> {
+    if profile.get(16..20) != Some(b"RGB ") {
+        return None;
+    }
+    let input = qcms::Profile::new_from_slice(profile, false)?;
+    let output = qcms::Profile::new_sRGB();
+    let transform =
+        |data_type| qcms::Transform::new(&input, &output, data_type, qcms::Intent::default());
+    if img.color().has_alpha() {
+        let transform = transform(qcms::DataType::RGBA8)?;
+        let mut pixels = img.to_rgba8();
+        transform.apply(&mut pixels);
+        Some(DynamicImage::ImageRgba8(pixels))
+    } else {
+        let transform = transform(qcms::DataType::RGB8)?;
+        let mut pixels = img.to_rgb8();
+        transform.apply(&mut pixels);
+        Some(DynamicImage::ImageRgb8(pixels))
+    }
+}
+
+/// Set `icc_profile` on `encoder`, logging formats that can't carry it
+fn embed_icc_profile(encoder: &mut impl image::ImageEncoder, icc_profile: Option<&[u8]>) {
+    let Some(profile) = icc_profile else {
+        return;
+    };
+    if let Err(e) = encoder.set_icc_profile(profile.to_vec()) {
+        log_warn!(
+            operation = "encode_image",
+            error = %e,
+            "Color profile could not be embedded"
+        );
+    }
+}
+
+/// Encode `img` as `format`, embedding `icc_profile` where the format can
+/// carry one (JPEG, PNG, WebP). JPEG uses `quality` and drops the alpha channel.
+fn encode_image(
+    img: &image::DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+    icc_profile: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut cursor = Cursor::new(&mut buffer);
+
+    let result = match format {
+        ImageFormat::Jpeg => {
+            let mut encoder =
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut cursor, quality);
+            embed_icc_profile(&mut encoder, icc_profile);
+            image::DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)
+        }
+        ImageFormat::WebP => {
+            let mut encoder = image::codecs::webp::WebPEncoder::new_lossless(&mut cursor);
+            embed_icc_profile(&mut encoder, icc_profile);
+            image::DynamicImage::ImageRgba8(img.to_rgba8()).write_with_encoder(encoder)
+        }
+        ImageFormat::Png => {
+            let mut encoder = image::codecs::png::PngEncoder::new(&mut cursor);
+            embed_icc_profile(&mut encoder, icc_profile);
+            img.write_with_encoder(encoder)
+        }
+        _ => img.write_to(&mut cursor, format),
+    };
+
+    result.map_err(|e| AppError::ImageProcessing(format!("Failed to encode image: {}", e)))?;
+    Ok(buffer)
 }
 
 #[cfg(test)]
@@ -1481,7 +2200,10 @@ mod tests {
         let image_path = create_test_image_file(&temp_dir, "test.png", 800, 600);
 
         // Generate thumbnail
-        let thumbnail_data = service.generate_thumbnail(&image_path, 150).await.unwrap();
+        let thumbnail_data = service
+            .generate_thumbnail(&image_path, 150, ThumbnailFormat::Jpeg)
+            .await
+            .unwrap();
 
         // Verify thumbnail was generated
         assert!(!thumbnail_data.is_empty());
@@ -1500,6 +2222,72 @@ mod tests {
         assert!((112..=113).contains(&thumb_height));
     }
 
+    #[tokio::test]
+    async fn test_thumbnail_keeps_transparency_where_format_allows() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = ImageService::new();
+        let image_path = temp_dir.path().join("logo.png");
+        image::RgbaImage::from_fn(40, 20, |x, _| {
+            image::Rgba([255, 0, 0, if x < 20 { 0 } else { 255 }])
+        })
+        .save(&image_path)
+        .unwrap();
+        let image_path = image_path.to_string_lossy().to_string();
+
+        for format in [ThumbnailFormat::Png, ThumbnailFormat::Webp] {
+            let data = service
+                .generate_thumbnail(&image_path, 20, format)
+                .await
+                .unwrap();
+            let thumbnail = image::load_from_memory(&data).unwrap();
+            assert!(thumbnail.color().has_alpha(), "{:?}", format);
+            assert_eq!(thumbnail.dimensions(), (20, 10));
+            assert_eq!(thumbnail.to_rgba8().get_pixel(0, 5)[3], 0);
+        }
+
+        let data = service
+            .generate_thumbnail(&image_path, 20, ThumbnailFormat::Jpeg)
+            .await
+            .unwrap();
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Jpeg);
+        assert!(!image::load_from_memory(&data).unwrap().color().has_alpha());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_thumbnail_comes_from_memory_until_file_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = ImageService::new();
+        let image_path = create_test_image_file(&temp_dir, "cached.png", 400, 300);
+
+        let first = service
+            .generate_thumbnail(&image_path, 100, ThumbnailFormat::Png)
+            .await
+            .unwrap();
+        let hits = service.get_cache_stats().await.unwrap().memory_hits;
+        let second = service
+            .generate_thumbnail(&image_path, 100, ThumbnailFormat::Png)
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        // Other tests share the counters, they only go up
+        assert!(service.get_cache_stats().await.unwrap().memory_hits > hits);
+
+        // A different image at the same path is a new thumbnail
+        let replaced = create_test_image_file(&temp_dir, "cached.png", 300, 400);
+        std::fs::File::options()
+            .write(true)
+            .open(&replaced)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        let third = service
+            .generate_thumbnail(&replaced, 100, ThumbnailFormat::Png)
+            .await
+            .unwrap();
+        let (width, height) = image::load_from_memory(&third).unwrap().dimensions();
+        assert!(width < height);
+    }
+
     #[tokio::test]
     async fn test_generate_thumbnail_portrait() {
         let temp_dir = TempDir::new().unwrap();
@@ -1509,7 +2297,10 @@ mod tests {
         let image_path = create_test_image_file(&temp_dir, "portrait.png", 600, 800);
 
         // Generate thumbnail
-        let thumbnail_data = service.generate_thumbnail(&image_path, 150).await.unwrap();
+        let thumbnail_data = service
+            .generate_thumbnail(&image_path, 150, ThumbnailFormat::Jpeg)
+            .await
+            .unwrap();
 
         // Load thumbnail and verify dimensions
         let thumbnail_img = image::load_from_memory(&thumbnail_data).unwrap();
@@ -1525,7 +2316,9 @@ mod tests {
     async fn test_generate_thumbnail_invalid_path() {
         let service = ImageService::new();
 
-        let result = service.generate_thumbnail("nonexistent.png", 150).await;
+        let result = service
+            .generate_thumbnail("nonexistent.png", 150, ThumbnailFormat::Jpeg)
+            .await;
         assert!(result.is_err());
 
         if let Err(AppError::ImageProcessing(msg)) = result {
@@ -1672,6 +2465,64 @@ mod tests {
         assert!(!service.is_supported_image("nonexistent.png").await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_validate_image_reasons() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = ImageService::new();
+        let max_bytes = 10 * 1024 * 1024;
+
+        let png_path = create_test_image_file(&temp_dir, "ok.png", 40, 30);
+        let valid = service.validate_image(&png_path, max_bytes).await;
+        assert!(valid.supported);
+        assert_eq!(valid.reason, None);
+        assert_eq!((valid.width, valid.height), (Some(40), Some(30)));
+        assert_eq!(valid.format.as_deref(), Some("png"));
+
+        let write = |name: &str, content: &[u8]| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let reason = |validation: ImageValidation| (validation.supported, validation.reason);
+
+        let svg = write(
+            "logo.svg",
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>",
+        );
+        assert_eq!(
+            reason(service.validate_image(&svg, max_bytes).await),
+            (true, Some(ImageRejectReason::Svg))
+        );
+        let heic = write("photo.HEIC", b"not decodable here");
+        assert_eq!(
+            reason(service.validate_image(&heic, max_bytes).await),
+            (false, Some(ImageRejectReason::MissingCodec))
+        );
+        let broken = write("broken.jpg", b"not a jpeg");
+        assert_eq!(
+            reason(service.validate_image(&broken, max_bytes).await),
+            (false, Some(ImageRejectReason::Corrupt))
+        );
+        let text = write("notes.txt", b"hello");
+        assert_eq!(
+            reason(service.validate_image(&text, max_bytes).await),
+            (false, Some(ImageRejectReason::UnsupportedFormat))
+        );
+        assert_eq!(
+            reason(service.validate_image(&png_path, 10).await),
+            (false, Some(ImageRejectReason::TooLarge))
+        );
+        let missing = temp_dir.path().join("gone.png");
+        assert_eq!(
+            reason(
+                service
+                    .validate_image(&missing.to_string_lossy(), max_bytes)
+                    .await
+            ),
+            (false, Some(ImageRejectReason::NotFound))
+        );
+    }
+
     #[tokio::test]
     async fn test_get_optimal_quality() {
         let temp_dir = TempDir::new().unwrap();
@@ -1716,7 +2567,10 @@ mod tests {
 
         // Test very small image
         let tiny_image = create_test_image_file(&temp_dir, "tiny.png", 10, 10);
-        let thumbnail = service.generate_thumbnail(&tiny_image, 150).await.unwrap();
+        let thumbnail = service
+            .generate_thumbnail(&tiny_image, 150, ThumbnailFormat::Jpeg)
+            .await
+            .unwrap();
         let thumb_img = image::load_from_memory(&thumbnail).unwrap();
         let (w, h) = thumb_img.dimensions();
         assert!(w <= 150 && h <= 150);
@@ -1724,7 +2578,7 @@ mod tests {
         // Test square image
         let square_image = create_test_image_file(&temp_dir, "square.png", 500, 500);
         let thumbnail = service
-            .generate_thumbnail(&square_image, 100)
+            .generate_thumbnail(&square_image, 100, ThumbnailFormat::Jpeg)
             .await
             .unwrap();
         let thumb_img = image::load_from_memory(&thumbnail).unwrap();
@@ -1748,4 +2602,569 @@ mod tests {
         assert!(image::load_from_memory(&jpeg_lower).is_ok());
         assert!(image::load_from_memory(&jpg).is_ok());
     }
+
+    fn processing_options() -> ImageProcessingOptions {
+        ImageProcessingOptions {
+            compression_enabled: false,
+            quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_image_passthrough() {
+        let service = ImageService::new();
+        let data = create_test_image(40, 20);
+
+        let processed = service
+            .process_image(data.clone(), &processing_options())
+            .await
+            .unwrap();
+
+        assert_eq!(processed.data, data);
+        assert_eq!(processed.format, "png");
+        assert_eq!((processed.width, processed.height), (40, 20));
+    }
+
+    // Minimal ICC v2 display profile with Display P3 primaries (D50-adapted)
+    // and a 2.2 gamma curve
+    fn display_p3_profile() -> Vec<u8> {
+        let xyz = |v: [f64; 3]| {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for c in v {
+                tag.extend_from_slice(&((c * 65536.0).round() as i32).to_be_bytes());
+            }
+            tag
+        };
+        let mut desc = b"desc\0\0\0\0".to_vec();
+        desc.extend_from_slice(&11u32.to_be_bytes());
+        desc.extend_from_slice(b"Display P3\0");
+        desc.resize(desc.len() + 78, 0);
+        let curve = b"curv\0\0\0\0\0\0\0\x01\x02\x33".to_vec();
+        let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+            (b"desc", desc),
+            (b"wtpt", xyz([0.9642, 1.0, 0.8249])),
+            (b"rXYZ", xyz([0.5151, 0.2412, -0.0011])),
+            (b"gXYZ", xyz([0.2920, 0.6922, 0.0419])),
+            (b"bXYZ", xyz([0.1571, 0.0666, 0.7841])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ];
+
+        let mut header = vec![0u8; 128];
+        header[8..12].copy_from_slice(&[2, 0x10, 0, 0]);
+        header[12..16].copy_from_slice(b"mntr");
+        header[16..20].copy_from_slice(b"RGB ");
+        header[20..24].copy_from_slice(b"XYZ ");
+        header[36..40].copy_from_slice(b"acsp");
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = Vec::new();
+        for (signature, tag) in &tags {
+            let offset = 128 + 4 + tags.len() * 12 + data.len();
+            table.extend_from_slice(*signature);
+            table.extend_from_slice(&(offset as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            data.extend_from_slice(tag);
+            // Tags start on 4-byte boundaries
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        let mut profile = [header, table, data].concat();
+        let size = (profile.len() as u32).to_be_bytes();
+        profile[..4].copy_from_slice(&size);
+        profile
+    }
+
+    // JPEG tagged with the Display P3 profile: gray on the left half, a
+    // muted red on the right
+    fn display_p3_jpeg() -> Vec<u8> {
+        use image::{ImageBuffer, ImageEncoder, Rgb};
+
+        let img = ImageBuffer::from_fn(32, 16, |x, _| {
+            if x < 16 {
+                Rgb([128u8, 128, 128])
+            } else {
+                Rgb([200, 60, 60])
+            }
+        });
+        let mut buffer = Vec::new();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, 100);
+        encoder.set_icc_profile(display_p3_profile()).unwrap();
+        encoder
+            .write_image(img.as_raw(), 32, 16, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        buffer
+    }
+
+    #[tokio::test]
+    async fn test_display_p3_jpeg_is_converted_to_srgb() {
+        let service = ImageService::new();
+        let data = display_p3_jpeg();
+        let profile = read_icc_profile(&data).unwrap();
+        assert_eq!(icc_profile_name(&profile).as_deref(), Some("Display P3"));
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("p3.jpg");
+        fs::write(&path, &data).unwrap();
+        let info = service
+            .get_image_info(&path.to_string_lossy())
+            .await
+            .unwrap();
+        assert_eq!(info.icc_profile.as_deref(), Some("Display P3"));
+
+        // Without conversion the pipeline still runs and the profile is kept
+        let compress = ImageProcessingOptions {
+            compression_enabled: true,
+            quality: 95,
+            max_dimension: Some(16),
+            ..processing_options()
+        };
+        let processed = service
+            .process_image(data.clone(), &compress)
+            .await
+            .unwrap();
+        assert!(processed.reencoded);
+        assert_eq!((processed.width, processed.height), (16, 8));
+        assert_eq!(read_icc_profile(&processed.data), Some(profile.clone()));
+
+        // So does every other re-encode of the upload
+        let target = service
+            .compress_to_target(data.clone(), data.len() as u64 - 1)
+            .await
+            .unwrap();
+        assert!(target.target_met && target.quality.is_some());
+        assert_eq!(read_icc_profile(&target.data), Some(profile.clone()));
+        let variants = service
+            .generate_size_variants(&data, &[8], 80)
+            .await
+            .unwrap();
+        assert_eq!(read_icc_profile(&variants[0].1), Some(profile.clone()));
+
+        let compress = ImageProcessingOptions {
+            max_dimension: None,
+            ..compress
+        };
+
+        let convert = ImageProcessingOptions {
+            convert_to_srgb: true,
+            ..compress
+        };
+        let processed = service.process_image(data, &convert).await.unwrap();
+        assert!(processed.reencoded);
+        assert!(read_icc_profile(&processed.data).is_none());
+        let pixels = image::load_from_memory(&processed.data).unwrap().to_rgb8();
+        let near = |actual: &image::Rgb<u8>, expected: [u8; 3]| {
+            actual
+                .0
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| a.abs_diff(e) <= 4)
+        };
+        // Gray shares the white point; the red is more saturated in sRGB terms
+        assert!(
+            near(pixels.get_pixel(8, 8), [129, 129, 129]),
+            "{:?}",
+            pixels.get_pixel(8, 8)
+        );
+        assert!(
+            near(pixels.get_pixel(24, 8), [219, 37, 49]),
+            "{:?}",
+            pixels.get_pixel(24, 8)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_image_resize_and_convert() {
+        let service = ImageService::new();
+        let data = create_test_image(400, 200);
+        let options = ImageProcessingOptions {
+            compression_enabled: true,
+            quality: 70,
+            output_format: Some("jpeg".to_string()),
+            max_dimension: Some(100),
+            ..processing_options()
+        };
+
+        let processed = service.process_image(data, &options).await.unwrap();
+
+        assert_eq!(processed.format, "jpeg");
+        assert_eq!(processed.extension.as_deref(), Some("jpg"));
+        assert_eq!(
+            (processed.original_width, processed.original_height),
+            (400, 200)
+        );
+        assert_eq!((processed.width, processed.height), (100, 50));
+        assert!(image::load_from_memory(&processed.data).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_process_image_unknown_format_untouched() {
+        let service = ImageService::new();
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>".to_vec();
+        let options = ImageProcessingOptions {
+            compression_enabled: true,
+            ..processing_options()
+        };
+
+        let processed = service.process_image(svg.clone(), &options).await.unwrap();
+        assert_eq!(processed.data, svg);
+        assert_eq!(processed.format, "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_checksum_algorithms() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_image_file(&temp_dir, "hash.png", 20, 20);
+        let data = fs::read(&path).unwrap();
+
+        let sha = ImageService::new().calculate_checksum(&path).await.unwrap();
+        assert_eq!(sha, format!("sha256:{:x}", Sha256::digest(&data)));
+
+        let service = ImageService::new().with_checksum_algorithm(ChecksumAlgorithm::Blake3);
+        let blake = service.calculate_checksum(&path).await.unwrap();
+        assert_eq!(blake, format!("blake3:{}", blake3::hash(&data).to_hex()));
+        assert_eq!(
+            service.calculate_checksum_from_data(&data).await.unwrap(),
+            blake
+        );
+    }
+
+    // Build a JPEG with an APP1 segment holding the given TIFF/EXIF payload
+    fn jpeg_with_app1(tiff: &[u8]) -> Vec<u8> {
+        let img = DynamicImage::new_rgb8(8, 8);
+        let mut jpeg = Vec::new();
+        img.write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+
+        let segment_len = (2 + 6 + tiff.len()) as u16;
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&segment_len.to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(tiff);
+
+        // Insert right after SOI
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&app1);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    fn exif_payload() -> Vec<u8> {
+        use exif::{Field, In, Tag, Value};
+
+        let make = Field {
+            tag: Tag::Make,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"Canon".to_vec()]),
+        };
+        let model = Field {
+            tag: Tag::Model,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"EOS R6".to_vec()]),
+        };
+        let orientation = Field {
+            tag: Tag::Orientation,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![6]),
+        };
+        let taken = Field {
+            tag: Tag::DateTimeOriginal,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"2023:07:14 18:30:05".to_vec()]),
+        };
+        let gps = Field {
+            tag: Tag::GPSLatitudeRef,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"N".to_vec()]),
+        };
+
+        let mut writer = exif::experimental::Writer::new();
+        for field in [&make, &model, &orientation, &taken, &gps] {
+            writer.push_field(field);
+        }
+        let mut buffer = Cursor::new(Vec::new());
+        writer.write(&mut buffer, false).unwrap();
+        buffer.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_get_image_info_reads_exif() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("photo.jpg");
+        fs::write(&path, jpeg_with_app1(&exif_payload())).unwrap();
+
+        let info = ImageService::new()
+            .get_image_info(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(info.camera_make.as_deref(), Some("Canon"));
+        assert_eq!(info.camera_model.as_deref(), Some("EOS R6"));
+        assert_eq!(info.orientation, Some(6));
+        assert_eq!(info.has_gps, Some(true));
+        assert_eq!(
+            info.taken_at.unwrap().to_string(),
+            "2023-07-14 18:30:05".to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_image_info_without_exif() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_image_file(&temp_dir, "plain.png", 10, 10);
+
+        let info = ImageService::new().get_image_info(&path).await.unwrap();
+        assert!(info.taken_at.is_none());
+        assert!(info.camera_make.is_none());
+        assert!(info.has_gps.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_exif_degrades_to_none() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("corrupt.jpg");
+        let mut payload = exif_payload();
+        payload.truncate(payload.len() / 2);
+        fs::write(&path, jpeg_with_app1(&payload)).unwrap();
+
+        let info = ImageService::new()
+            .get_image_info(path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!((info.width, info.height), (8, 8));
+
+        let garbage = jpeg_with_app1(b"II*\0garbage");
+        let exif = read_exif(&mut Cursor::new(&garbage));
+        assert!(exif.taken_at.is_none());
+        assert!(exif.camera_make.is_none());
+    }
+
+    // Noise doesn't compress, so JPEG size tracks quality and pixel count
+    fn create_noisy_jpeg(width: u32, height: u32) -> Vec<u8> {
+        use image::{ImageBuffer, Rgb};
+
+        let mut seed: u32 = 12345;
+        let img = ImageBuffer::from_fn(width, height, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgb([r, g, b])
+        });
+        encode_image(&DynamicImage::ImageRgb8(img), ImageFormat::Jpeg, 95, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_compress_to_target_quality_search() {
+        let service = ImageService::new();
+        let data = create_noisy_jpeg(300, 300);
+        let max_bytes = data.len() as u64 / 2;
+
+        let result = service.compress_to_target(data, max_bytes).await.unwrap();
+        assert!(result.target_met);
+        assert!(result.final_size <= max_bytes);
+        assert_eq!(result.final_size, result.data.len() as u64);
+        assert!(result.quality.is_some());
+        assert!(result.passes <= MAX_QUALITY_PASSES);
+        assert_eq!((result.width, result.height), (300, 300));
+        assert!(result.warning.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_compress_to_target_downscales() {
+        let service = ImageService::new();
+        let data = create_noisy_jpeg(600, 400);
+
+        let result = service.compress_to_target(data, 20 * 1024).await.unwrap();
+        assert!(result.target_met);
+        assert_eq!(result.quality, Some(MIN_TARGET_QUALITY));
+        assert!(result.width < 600 && result.height < 400);
+        let decoded = image::load_from_memory(&result.data).unwrap();
+        assert_eq!(decoded.dimensions(), (result.width, result.height));
+    }
+
+    #[tokio::test]
+    async fn test_compress_to_target_passthrough_and_skips() {
+        let service = ImageService::new();
+
+        let small = create_test_image(10, 10);
+        let result = service
+            .compress_to_target(small.clone(), 1024 * 1024)
+            .await
+            .unwrap();
+        assert_eq!(result.data, small);
+        assert_eq!(result.passes, 0);
+        assert!(result.warning.is_none());
+
+        let svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\">{}</svg>",
+            "<g/>".repeat(100)
+        )
+        .into_bytes();
+        let result = service.compress_to_target(svg.clone(), 10).await.unwrap();
+        assert_eq!(result.data, svg);
+        assert!(!result.target_met);
+        assert!(result.warning.unwrap().contains("SVG"));
+
+        let gif = encode_image(
+            &DynamicImage::ImageRgb8(image::ImageBuffer::new(20, 20)),
+            ImageFormat::Gif,
+            90,
+            None,
+        )
+        .unwrap();
+        let result = service.compress_to_target(gif.clone(), 10).await.unwrap();
+        assert_eq!(result.data, gif);
+        assert!(result.warning.unwrap().contains("GIF"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_size_variants() {
+        let service = ImageService::new();
+        let data = create_test_image_jpeg(1000, 500);
+
+        let variants = service
+            .generate_size_variants(&data, &[960, 480, 1920, 480, 0], 80)
+            .await
+            .unwrap();
+        let widths: Vec<u32> = variants.iter().map(|(width, _)| *width).collect();
+        assert_eq!(widths, vec![480, 960]);
+
+        let small = image::load_from_memory(&variants[0].1).unwrap();
+        assert_eq!(small.dimensions(), (480, 240));
+        assert_eq!(
+            image::guess_format(&variants[0].1).unwrap(),
+            ImageFormat::Jpeg
+        );
+
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>".to_vec();
+        assert!(service
+            .generate_size_variants(&svg, &[10], 80)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    // Tiny HTTP server: /private.png answers 403, /gone.png 404, anything else the PNG
+    fn serve_thumbnail_sources(png: Vec<u8>) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0u8; 2048];
+                let read = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]);
+                let (status, body) = if request.starts_with("GET /private.png") {
+                    ("403 Forbidden", Vec::new())
+                } else if request.starts_with("GET /gone.png") {
+                    ("404 Not Found", Vec::new())
+                } else {
+                    ("200 OK", png.clone())
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn test_remove_cached_thumbnails_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = ImageService {
+            cache_dir: Some(temp_dir.path().to_path_buf()),
+            ..ImageService::new()
+        };
+        fs::write(temp_dir.path().join("a_200.jpg"), [0u8; 100]).unwrap();
+        fs::write(temp_dir.path().join("b_200.jpg"), [0u8; 50]).unwrap();
+
+        assert_eq!(
+            service.remove_cached_thumbnails(["a", "b", "missing"], true),
+            (2, 150)
+        );
+        assert!(temp_dir.path().join("a_200.jpg").exists());
+
+        assert_eq!(service.remove_cached_thumbnails(["a"], false), (1, 100));
+        assert!(!temp_dir.path().join("a_200.jpg").exists());
+        assert!(temp_dir.path().join("b_200.jpg").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cached_thumbnail_falls_back_to_presigned_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = ImageService {
+            cache_dir: Some(temp_dir.path().to_path_buf()),
+            client: Some(reqwest::Client::new()),
+            ..ImageService::new()
+        };
+        let server = serve_thumbnail_sources(create_test_image(300, 300));
+        let private = format!("{}/private.png", server);
+        let gone = format!("{}/gone.png", server);
+        let presigned = format!("{}/signed.png?Signature=abc", server);
+
+        let error = service
+            .get_cached_thumbnail("denied", std::slice::from_ref(&private))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::PermissionDenied(_)));
+
+        // 404 doesn't fall through to the presigned URL
+        let error = service
+            .get_cached_thumbnail("gone", &[gone, presigned.clone()])
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::NotFound(_)));
+
+        let thumbnail = service
+            .get_cached_thumbnail("record-1", &[private, presigned])
+            .await
+            .unwrap();
+        assert!(!thumbnail.is_empty());
+        assert!(temp_dir.path().join("record-1_200.jpg").exists());
+
+        // Served from cache even once every source is unreachable
+        let cached = service
+            .get_cached_thumbnail("record-1", &[format!("{}/gone.png", server)])
+            .await
+            .unwrap();
+        assert_eq!(cached, thumbnail);
+    }
+
+    #[test]
+    fn test_check_image_size_rejects_oversized_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("big.gif");
+        std::fs::write(&path, vec![0u8; 2048]).unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        assert_eq!(ImageService::new().check_image_size(&path).unwrap(), 2048);
+        assert_eq!(
+            ImageService::new()
+                .with_max_image_size(4096)
+                .check_image_size(&path)
+                .unwrap(),
+            2048
+        );
+
+        let limited = ImageService::new().with_max_image_size(1024);
+        assert!(limited.exceeds_size_limit(2048));
+        assert!(matches!(
+            limited.check_image_size(&path),
+            Err(AppError::FileTooLarge(_))
+        ));
+        assert!(matches!(
+            limited.check_image_size("/nonexistent/image.png"),
+            Err(AppError::FileSystem(_))
+        ));
+    }
 }
@@ -0,0 +1,391 @@
+use crate::models::{OSSProvider, PathTemplateCheck};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::path::Path;
+
+/// Used when a config has no path template, matches the historical key layout
+pub const DEFAULT_PATH_TEMPLATE: &str = "images/{timestamp}_{filename}";
+
+/// Values available to `OSSConfig::path_template`
+#[derive(Debug, Clone)]
+pub struct KeyTemplateContext<'a> {
+    /// File name of the uploaded object, extension already adjusted for format conversion
+    pub filename: &'a str,
+    /// EXIF capture time, `{taken_date}` falls back to the upload date without it
+    pub taken_at: Option<NaiveDateTime>,
+    pub now: DateTime<Utc>,
+    /// Hex digest of the bytes being uploaded, for `{hash}` and `{hash8}`
+    pub hash: Option<&'a str>,
+}
+
+impl<'a> KeyTemplateContext<'a> {
+    pub fn new(filename: &'a str) -> Self {
+        Self {
+            filename,
+            taken_at: None,
+            now: Utc::now(),
+            hash: None,
+        }
+    }
+
+    pub fn with_taken_at(mut self, taken_at: Option<NaiveDateTime>) -> Self {
+        self.taken_at = taken_at;
+        self
+    }
+
+    /// Content hash for `{hash}`/`{hash8}`, given as a checksum (`sha256:3fa9…`
+    /// or plain hex). It must be the checksum of the bytes actually uploaded:
+    /// once compression, resizing or format conversion re-encodes an image, the
+    /// source file's checksum names a different object.
+    pub fn with_hash(mut self, checksum: &'a str) -> Self {
+        self.hash = Some(checksum.split_once(':').map_or(checksum, |(_, hex)| hex));
+        self
+    }
+}
+
+/// Placeholders understood by `render_object_key`
+pub const TEMPLATE_PLACEHOLDERS: [&str; 12] = [
+    "filename",
+    "name",
+    "ext",
+    "date",
+    "year",
+    "month",
+    "day",
+    "timestamp",
+    "uuid",
+    "taken_date",
+    "hash",
+    "hash8",
+];
+
+// Every `{...}` in `template`, in order
+fn placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(&rest[start + 1..start + len]);
+        rest = &rest[start + len + 1..];
+    }
+    names
+}
+
+/// `{...}` placeholders in `template` that `render_object_key` would leave as-is
+pub fn unknown_placeholders(template: &str) -> Vec<String> {
+    let mut unknown: Vec<String> = Vec::new();
+    for name in placeholders(template) {
+        if !TEMPLATE_PLACEHOLDERS.contains(&name) && !unknown.iter().any(|u| u == name) {
+            unknown.push(name.to_string());
+        }
+    }
+    unknown
+}
+
+/// True when keys rendered from `template` need the content hash
+pub fn uses_content_hash(template: &str) -> bool {
+    placeholders(template)
+        .iter()
+        .any(|name| matches!(*name, "hash" | "hash8"))
+}
+
+/// True when the content alone decides the key: the template has `{hash}` or
+/// `{hash8}` and otherwise only `{ext}`. The same bytes always get the same
+/// key, so an object already at that key is this image and the upload can
+/// be skipped.
+pub fn is_content_addressed(template: &str) -> bool {
+    uses_content_hash(template)
+        && placeholders(template)
+            .iter()
+            .all(|name| matches!(*name, "hash" | "hash8" | "ext"))
+}
+
+/// True when keys from `template` differ between two uploads of the same file
+/// name: it has `{timestamp}`, `{uuid}` or a hash. An empty template gets
+/// the default layout, which has a timestamp.
+pub fn has_unique_component(template: &str) -> bool {
+    template.trim().is_empty()
+        || placeholders(template)
+            .iter()
+            .any(|name| matches!(*name, "timestamp" | "uuid" | "hash" | "hash8"))
+}
+
+/// The template uploads use: `template` itself when it has a unique component,
+/// else the default layout, so a template like `images/{filename}` doesn't
+/// replace the object of an earlier upload with the same file name
+pub fn upload_template(template: &str) -> &str {
+    if has_unique_component(template) {
+        template
+    } else {
+        DEFAULT_PATH_TEMPLATE
+    }
+}
+
+/// File name the sample key of `validate_path_template` is rendered for
+const SAMPLE_FILENAME: &str = "IMG_2048.jpg";
+/// A SHA-256 digest, as long as any hash `{hash}` can get
+const SAMPLE_HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+/// Longest object key in bytes each provider accepts
+fn max_key_bytes(provider: &OSSProvider) -> usize {
+    match provider {
+        OSSProvider::Aliyun => 1023,
+        OSSProvider::Tencent => 850,
+        OSSProvider::Aws | OSSProvider::Custom => 1024,
+    }
+}
+
+/// Check `template` against the key rules of `provider` and render the key a
+/// sample upload would get. Every error names the placeholder or the
+/// character (with its position) that breaks the rule.
+pub fn validate_path_template(template: &str, provider: &OSSProvider) -> PathTemplateCheck {
+    let mut errors = Vec::new();
+    let trimmed = template.trim();
+
+    let unknown = unknown_placeholders(trimmed);
+    if !unknown.is_empty() {
+        errors.push(format!(
+            "Unknown path template placeholder(s): {}",
+            unknown
+                .iter()
+                .map(|name| format!("{{{}}}", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    // A `{` without its `}` ends up in every key as-is
+    if let Some(start) = trimmed.rfind('{').filter(|&i| !trimmed[i..].contains('}')) {
+        errors.push(format!(
+            "Path template has an unclosed `{{` at position {}",
+            char_position(trimmed, start)
+        ));
+    }
+
+    if trimmed.starts_with('/') {
+        errors.push(
+            "Path template must not start with `/`, keys are relative to the bucket".to_string(),
+        );
+    }
+    if let Some(start) = trimmed.find("//") {
+        errors.push(format!(
+            "Path template has an empty folder (`//`) at position {}",
+            char_position(trimmed, start)
+        ));
+    }
+    for (position, c) in trimmed.chars().enumerate() {
+        if c == '\\' {
+            errors.push(format!(
+                "Path template has a `\\` at position {}, use `/` to separate folders",
+                position + 1
+            ));
+        } else if c.is_control() {
+            errors.push(format!(
+                "Path template has a control character (U+{:04X}) at position {}",
+                c as u32,
+                position + 1
+            ));
+        }
+    }
+
+    let context = KeyTemplateContext::new(SAMPLE_FILENAME).with_hash(SAMPLE_HASH);
+    let sample_key = render_object_key(template, &context);
+    let limit = max_key_bytes(provider);
+    if sample_key.len() > limit {
+        errors.push(format!(
+            "Keys from this template are {} bytes long, {} allows at most {}",
+            sample_key.len(),
+            provider.display_name(),
+            limit
+        ));
+    }
+
+    PathTemplateCheck {
+        valid: errors.is_empty(),
+        sample_key,
+        errors,
+    }
+}
+
+// 1-based position of the character at byte `index`, as an editor counts
+fn char_position(text: &str, index: usize) -> usize {
+    text[..index].chars().count() + 1
+}
+
+/// Render an object key from a path template.
+///
+/// Supported placeholders: `{filename}`, `{name}`, `{ext}`, `{date}`, `{year}`,
+/// `{month}`, `{day}`, `{timestamp}`, `{uuid}`, `{taken_date}`, `{hash}` and
+/// `{hash8}` (the first 8 hex digits). Without a hash in the context the hash
+/// placeholders get a random UUID, so keys never collide by accident.
+pub fn render_object_key(template: &str, context: &KeyTemplateContext) -> String {
+    let template = if template.trim().is_empty() {
+        DEFAULT_PATH_TEMPLATE
+    } else {
+        template.trim()
+    };
+
+    let path = Path::new(context.filename);
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(context.filename);
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+
+    let taken_date = context
+        .taken_at
+        .map(|taken| taken.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| context.now.format("%Y-%m-%d").to_string());
+
+    let random;
+    let hash = match context.hash {
+        Some(hash) => hash,
+        None => {
+            random = uuid::Uuid::new_v4().simple().to_string();
+            &random
+        }
+    };
+
+    let key = template
+        .replace("{hash8}", &hash[..hash.len().min(8)])
+        .replace("{hash}", hash)
+        .replace("{filename}", context.filename)
+        .replace("{name}", name)
+        .replace("{ext}", ext)
+        .replace("{taken_date}", &taken_date)
+        .replace("{date}", &context.now.format("%Y-%m-%d").to_string())
+        .replace("{year}", &context.now.format("%Y").to_string())
+        .replace("{month}", &context.now.format("%m").to_string())
+        .replace("{day}", &context.now.format("%d").to_string())
+        .replace(
+            "{timestamp}",
+            &context.now.format("%Y%m%d_%H%M%S").to_string(),
+        )
+        .replace("{uuid}", &uuid::Uuid::new_v4().to_string());
+
+    // Object keys never start with a slash and empty segments are dropped
+    key.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context(filename: &str) -> KeyTemplateContext<'_> {
+        KeyTemplateContext {
+            filename,
+            taken_at: None,
+            now: Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 6).unwrap(),
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn test_render_date_placeholders() {
+        let key = render_object_key("images/{year}/{month}/{filename}", &context("a.png"));
+        assert_eq!(key, "images/2024/03/a.png");
+
+        let key = render_object_key("/img/{date}//{name}.{ext}", &context("a.png"));
+        assert_eq!(key, "img/2024-03-09/a.png");
+    }
+
+    #[test]
+    fn test_unknown_placeholders() {
+        assert!(unknown_placeholders("images/{year}/{month}/{filename}").is_empty());
+        assert_eq!(
+            unknown_placeholders("{date}/{sha}/{Filename}/{sha}"),
+            vec!["sha", "Filename"]
+        );
+    }
+
+    #[test]
+    fn test_render_hash_placeholders() {
+        let checksum = "sha256:3fa9c2d1e8b7a6f5d4c3b2a1";
+        let key = render_object_key("img/{hash8}.{ext}", &context("a.png").with_hash(checksum));
+        assert_eq!(key, "img/3fa9c2d1.png");
+
+        let key = render_object_key("img/{hash}{ext}", &context("a").with_hash("abc"));
+        assert_eq!(key, "img/abc");
+
+        // No hash given: random, never empty
+        let key = render_object_key("img/{hash8}.{ext}", &context("a.png"));
+        assert_eq!(key.len(), "img/12345678.png".len());
+    }
+
+    #[test]
+    fn test_content_addressed_templates() {
+        assert!(is_content_addressed("img/{hash8}.{ext}"));
+        assert!(is_content_addressed("{hash}"));
+        assert!(!is_content_addressed("img/{year}/{hash8}.{ext}"));
+        assert!(!is_content_addressed("img/{filename}"));
+
+        assert!(uses_content_hash("img/{year}/{hash8}.{ext}"));
+        assert!(!uses_content_hash("img/{uuid}.{ext}"));
+    }
+
+    #[test]
+    fn test_unique_components() {
+        assert!(has_unique_component(""));
+        assert!(has_unique_component("images/{timestamp}_{filename}"));
+        assert!(has_unique_component("img/{hash8}.{ext}"));
+        assert!(!has_unique_component("images/{filename}"));
+        assert!(!has_unique_component("images/{year}/{month}/{filename}"));
+        assert_eq!(upload_template("images/{filename}"), DEFAULT_PATH_TEMPLATE);
+        assert_eq!(upload_template("img/{uuid}.{ext}"), "img/{uuid}.{ext}");
+    }
+
+    #[test]
+    fn test_validate_path_template_renders_sample_key() {
+        let check = validate_path_template("img/{hash8}/{name}.{ext}", &OSSProvider::Aliyun);
+        assert!(check.valid, "{:?}", check.errors);
+        assert_eq!(check.sample_key, "img/9f86d081/IMG_2048.jpg");
+    }
+
+    #[test]
+    fn test_validate_path_template_pinpoints_problems() {
+        let errors =
+            |template: &str| validate_path_template(template, &OSSProvider::Tencent).errors;
+
+        assert_eq!(
+            errors("img/{sha}/{filename}"),
+            vec!["Unknown path template placeholder(s): {sha}"]
+        );
+        assert!(errors("/img/{filename}")[0].contains("must not start with `/`"));
+        assert!(errors("img//{filename}")[0].contains("`//`) at position 4"));
+        assert!(errors("img\\{filename}")[0].contains("`\\` at position 4"));
+        assert!(errors("img/\u{7}{filename}")[0].contains("U+0007) at position 5"));
+        assert!(errors("图片/{filename")
+            .iter()
+            .any(|e| e.contains("unclosed `{` at position 4")));
+
+        // Tencent takes shorter keys than S3
+        let long = format!("{}/{{filename}}", "a".repeat(900));
+        assert!(errors(&long)[0].contains("Tencent COS allows at most 850"));
+        assert!(validate_path_template(&long, &OSSProvider::Aws).valid);
+    }
+
+    #[test]
+    fn test_empty_template_uses_default_layout() {
+        let key = render_object_key("", &context("a.png"));
+        assert_eq!(key, "images/20240309_140506_a.png");
+    }
+
+    #[test]
+    fn test_taken_date_falls_back_to_upload_date() {
+        let key = render_object_key("photos/{taken_date}/{filename}", &context("a.jpg"));
+        assert_eq!(key, "photos/2024-03-09/a.jpg");
+
+        let taken = chrono::NaiveDate::from_ymd_opt(2019, 12, 31)
+            .unwrap()
+            .and_hms_opt(23, 59, 0);
+        let key = render_object_key(
+            "photos/{taken_date}/{filename}",
+            &context("a.jpg").with_taken_at(taken),
+        );
+        assert_eq!(key, "photos/2019-12-31/a.jpg");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::models::{
+    ClassifiedLink, ConfigItem, ConfigLinkCount, FileLinkAnalysis, ImageReference, LinkLocation,
+};
+use crate::services::oss_service::object_key_from_url;
+use std::collections::HashMap;
+
+/// Where `link` points. Links are checked against the current config first, so
+/// a host shared by several saved configs counts as already migrated.
+pub fn classify_link(
+    link: &str,
+    current: &ConfigItem,
+    configs: &[ConfigItem],
+) -> (LinkLocation, Option<String>, Option<String>) {
+    if !link.starts_with("http://") && !link.starts_with("https://") {
+        return (LinkLocation::Local, None, None);
+    }
+
+    if let Some(key) = object_key_from_url(&current.config, link) {
+        return (
+            LinkLocation::CurrentConfig,
+            Some(current.id.clone()),
+            Some(key),
+        );
+    }
+
+    configs
+        .iter()
+        .filter(|item| item.id != current.id)
+        .find_map(|item| {
+            object_key_from_url(&item.config, link)
+                .map(|key| (LinkLocation::OtherConfig, Some(item.id.clone()), Some(key)))
+        })
+        .unwrap_or((LinkLocation::External, None, None))
+}
+
+/// Classify every link of one file and count them by location
+pub fn analyze_file(
+    file_path: &str,
+    references: &[ImageReference],
+    current: &ConfigItem,
+    configs: &[ConfigItem],
+) -> FileLinkAnalysis {
+    let links: Vec<ClassifiedLink> = references
+        .iter()
+        .map(|reference| {
+            let (location, config_id, object_key) =
+                classify_link(&reference.original_path, current, configs);
+            ClassifiedLink {
+                link: reference.original_path.clone(),
+                line: reference.markdown_line,
+                column: reference.markdown_column,
+                context: reference.context,
+                location,
+                config_id,
+                object_key,
+            }
+        })
+        .collect();
+
+    let count = |location| links.iter().filter(|l| l.location == location).count();
+    FileLinkAnalysis {
+        file_path: file_path.to_string(),
+        local: count(LinkLocation::Local),
+        current_config: count(LinkLocation::CurrentConfig),
+        other_config: count(LinkLocation::OtherConfig),
+        external: count(LinkLocation::External),
+        links,
+        error: None,
+    }
+}
+
+/// Links on other saved configs across all files, most used config first
+pub fn other_config_counts(
+    files: &[FileLinkAnalysis],
+    configs: &[ConfigItem],
+) -> Vec<ConfigLinkCount> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for link in files.iter().flat_map(|file| &file.links) {
+        if let (LinkLocation::OtherConfig, Some(config_id)) = (link.location, &link.config_id) {
+            *counts.entry(config_id.as_str()).or_default() += 1;
+        }
+    }
+
+    let mut result: Vec<ConfigLinkCount> = configs
+        .iter()
+        .filter_map(|item| {
+            counts
+                .get(item.id.as_str())
+                .map(|&link_count| ConfigLinkCount {
+                    config_id: item.id.clone(),
+                    config_name: item.name.clone(),
+                    link_count,
+                })
+        })
+        .collect();
+    result.sort_by(|a, b| b.link_count.cmp(&a.link_count));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OSSConfig, OSSProvider, ObjectAcl};
+
+    fn config_item(id: &str, bucket: &str, cdn_domain: Option<&str>) -> ConfigItem {
+        ConfigItem {
+            id: id.to_string(),
+            name: format!("{} config", id),
+            config: OSSConfig {
+                provider: OSSProvider::Aliyun,
+                endpoint: "oss-cn-hangzhou.aliyuncs.com".to_string(),
+                access_key_id: "id".to_string(),
+                access_key_secret: "secret".to_string(),
+                bucket: bucket.to_string(),
+                region: "cn-hangzhou".to_string(),
+                path_template: "images/{filename}".to_string(),
+                cdn_domain: cdn_domain.map(|d| d.to_string()),
+                compression_enabled: false,
+                compression_quality: 80,
+                output_format: None,
+                max_dimension: None,
+                strip_metadata: false,
+                convert_to_srgb: false,
+                object_acl: ObjectAcl::Default,
+                max_upload_size_kb: None,
+                size_variants: Vec::new(),
+            },
+            is_active: false,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_analyze_file_counts_links_by_location() {
+        let current = config_item("new", "new-bucket", Some("img.example.com"));
+        let old = config_item("old", "old-bucket", Some("cdn.old-site.com"));
+        let configs = vec![old.clone(), current.clone()];
+
+        let references: Vec<ImageReference> = [
+            "./local.png",
+            "https://img.example.com/images/a.png",
+            "https://cdn.old-site.com/images/b.png",
+            "https://old-bucket.oss-cn-hangzhou.aliyuncs.com/images/c.png",
+            "https://example.org/d.png",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, link)| ImageReference::new(link.to_string(), String::new(), i + 1, 5))
+        .collect();
+
+        let analysis = analyze_file("/docs/post.md", &references, &current, &configs);
+        assert_eq!(
+            (
+                analysis.local,
+                analysis.current_config,
+                analysis.other_config,
+                analysis.external
+            ),
+            (1, 1, 2, 1)
+        );
+        assert_eq!(analysis.links[2].config_id.as_deref(), Some("old"));
+        assert_eq!(
+            analysis.links[3].object_key.as_deref(),
+            Some("images/c.png")
+        );
+
+        let counts = other_config_counts(&[analysis], &configs);
+        assert_eq!(
+            counts,
+            [ConfigLinkCount {
+                config_id: "old".to_string(),
+                config_name: "old config".to_string(),
+                link_count: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_current_config_wins_shared_hosts() {
+        let current = config_item("a", "bucket", Some("img.example.com"));
+        let twin = config_item("b", "bucket", Some("img.example.com"));
+
+        let (location, config_id, _) = classify_link(
+            "https://img.example.com/x.png",
+            &current,
+            &[twin, current.clone()],
+        );
+        assert_eq!(location, LinkLocation::CurrentConfig);
+        assert_eq!(config_id.as_deref(), Some("a"));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::models::{ImageVariant, LinkFormat};
+use crate::utils::html::escape_html;
+use crate::utils::{AppError, Result};
+use std::path::Path;
+
+/// A single uploaded image to render as a link
+#[derive(Debug, Clone)]
+pub struct LinkSource {
+    pub url: String,
+    pub filename: String,
+    /// Uploaded size variants, used for `srcset`
+    pub variants: Vec<ImageVariant>,
+}
+
+impl LinkSource {
+    /// Build a source from a bare URL, taking the filename from the last path segment
+    pub fn from_url(url: &str) -> Self {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let filename = path
+            .rsplit('/')
+            .next()
+            .map(|segment| {
+                urlencoding::decode(segment)
+                    .map(|s| s.into_owned())
+                    .unwrap_or_else(|_| segment.to_string())
+            })
+            .unwrap_or_default();
+
+        Self {
+            url: url.to_string(),
+            filename,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Alt text defaults to the filename without its extension
+    pub fn alt(&self) -> String {
+        Path::new(&self.filename)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.filename.clone())
+    }
+
+    /// `url 480w, url 960w, ...`; just the URL when there are no variants
+    pub fn srcset(&self) -> String {
+        if self.variants.is_empty() {
+            return self.url.clone();
+        }
+        self.variants
+            .iter()
+            .map(|variant| format!("{} {}w", variant.url, variant.width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Custom templates must at least reference the URL (or the srcset)
+pub fn validate_link_template(template: &str) -> Result<()> {
+    if template.trim().is_empty() {
+        return Err(AppError::Validation(
+            "Link template cannot be empty".to_string(),
+        ));
+    }
+
+    if !template.contains("{url}") && !template.contains("{srcset}") {
+        return Err(AppError::Validation(
+            "Link template must contain the {url} or {srcset} placeholder".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Render one link in the requested format
+pub fn format_link(
+    source: &LinkSource,
+    format: LinkFormat,
+    custom_template: Option<&str>,
+) -> Result<String> {
+    let alt = source.alt();
+
+    let link = match format {
+        LinkFormat::Markdown => format!("![{}]({})", alt, source.url),
+        LinkFormat::Html if !source.variants.is_empty() => format!(
+            "<img src=\"{}\" srcset=\"{}\" alt=\"{}\" />",
+            escape_html(&source.url),
+            escape_html(&source.srcset()),
+            escape_html(&alt)
+        ),
+        LinkFormat::Html => format!(
+            "<img src=\"{}\" alt=\"{}\" />",
+            escape_html(&source.url),
+            escape_html(&alt)
+        ),
+        LinkFormat::Bbcode => format!("[img]{}[/img]", source.url),
+        LinkFormat::Url => source.url.clone(),
+        LinkFormat::Custom => {
+            let template = custom_template.ok_or_else(|| {
+                AppError::Configuration("No custom link template configured".to_string())
+            })?;
+            validate_link_template(template)?;
+            template
+                .replace("{srcset}", &source.srcset())
+                .replace("{url}", &source.url)
+                .replace("{filename}", &source.filename)
+                .replace("{alt}", &alt)
+        }
+    };
+
+    Ok(link)
+}
+
+/// Render all sources and join them one per line, ready for the clipboard
+pub fn format_links(
+    sources: &[LinkSource],
+    format: LinkFormat,
+    custom_template: Option<&str>,
+) -> Result<String> {
+    let links = sources
+        .iter()
+        .map(|source| format_link(source, format, custom_template))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(links.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> LinkSource {
+        LinkSource::from_url("https://cdn.example.com/images/hero%20shot.png")
+    }
+
+    #[test]
+    fn test_from_url_extracts_filename_and_alt() {
+        let source = source();
+        assert_eq!(source.filename, "hero shot.png");
+        assert_eq!(source.alt(), "hero shot");
+    }
+
+    #[test]
+    fn test_format_markdown() {
+        let link = format_link(&source(), LinkFormat::Markdown, None).unwrap();
+        assert_eq!(
+            link,
+            "![hero shot](https://cdn.example.com/images/hero%20shot.png)"
+        );
+    }
+
+    #[test]
+    fn test_format_html() {
+        let link = format_link(&source(), LinkFormat::Html, None).unwrap();
+        assert_eq!(
+            link,
+            "<img src=\"https://cdn.example.com/images/hero%20shot.png\" alt=\"hero shot\" />"
+        );
+    }
+
+    #[test]
+    fn test_format_bbcode() {
+        let link = format_link(&source(), LinkFormat::Bbcode, None).unwrap();
+        assert_eq!(
+            link,
+            "[img]https://cdn.example.com/images/hero%20shot.png[/img]"
+        );
+    }
+
+    #[test]
+    fn test_format_url() {
+        let link = format_link(&source(), LinkFormat::Url, None).unwrap();
+        assert_eq!(link, "https://cdn.example.com/images/hero%20shot.png");
+    }
+
+    #[test]
+    fn test_format_custom_template() {
+        let link = format_link(
+            &source(),
+            LinkFormat::Custom,
+            Some("{{< figure src=\"{url}\" title=\"{alt}\" >}}"),
+        )
+        .unwrap();
+        assert_eq!(
+            link,
+            "{{< figure src=\"https://cdn.example.com/images/hero%20shot.png\" title=\"hero shot\" >}}"
+        );
+    }
+
+    #[test]
+    fn test_custom_template_requires_url_placeholder() {
+        assert!(validate_link_template("![{alt}]").is_err());
+        assert!(validate_link_template("   ").is_err());
+        assert!(format_link(&source(), LinkFormat::Custom, None).is_err());
+    }
+
+    #[test]
+    fn test_srcset_from_variants() {
+        let mut source = source();
+        assert_eq!(source.srcset(), source.url);
+
+        source.variants = vec![
+            ImageVariant {
+                width: 480,
+                url: "https://cdn.example.com/images/hero%20shot_480.png".to_string(),
+                bytes: 10,
+            },
+            ImageVariant {
+                width: 1200,
+                url: source.url.clone(),
+                bytes: 40,
+            },
+        ];
+        let srcset = "https://cdn.example.com/images/hero%20shot_480.png 480w, https://cdn.example.com/images/hero%20shot.png 1200w";
+
+        let link = format_link(
+            &source,
+            LinkFormat::Custom,
+            Some("<img srcset=\"{srcset}\" sizes=\"100vw\">"),
+        )
+        .unwrap();
+        assert_eq!(link, format!("<img srcset=\"{}\" sizes=\"100vw\">", srcset));
+
+        let html = format_link(&source, LinkFormat::Html, None).unwrap();
+        assert!(html.contains(&format!("srcset=\"{}\"", srcset)));
+    }
+
+    #[test]
+    fn test_format_links_joins_lines() {
+        let sources = vec![
+            LinkSource::from_url("https://a.com/1.png"),
+            LinkSource::from_url("https://a.com/2.png"),
+        ];
+        let text = format_links(&sources, LinkFormat::Url, None).unwrap();
+        assert_eq!(text, "https://a.com/1.png\nhttps://a.com/2.png");
+    }
+}
@@ -0,0 +1,231 @@
+use crate::models::{LocalDuplicateFile, LocalDuplicateGroup};
+use crate::services::ImageService;
+use crate::utils::crash_reports::run_blocking;
+use crate::utils::{AppError, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Image files under `directory` with their sizes, sorted by path. Hidden
+/// entries (`.git`, `.obsidian`, ...) are skipped like in markdown scans.
+pub async fn list_image_files(
+    directory: &str,
+    recursive: bool,
+    extensions: &[String],
+) -> Result<Vec<(String, u64)>> {
+    let directory = directory.to_string();
+    let extensions = extensions.to_vec();
+    run_blocking("list_image_files", move || {
+        let walker = walkdir::WalkDir::new(&directory)
+            .max_depth(if recursive { usize::MAX } else { 1 })
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+            });
+
+        let mut files = Vec::new();
+        for entry in walker {
+            let entry = entry.map_err(|e| AppError::FileSystem(e.to_string()))?;
+            if !entry.file_type().is_file() || !has_extension(entry.path(), &extensions) {
+                continue;
+            }
+            let size = entry
+                .metadata()
+                .map_err(|e| AppError::FileSystem(e.to_string()))?
+                .len();
+            files.push((entry.path().to_string_lossy().to_string(), size));
+        }
+        files.sort();
+        Ok(files)
+    })
+    .await
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .map(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase()))
+        .unwrap_or(false)
+}
+
+/// What `find_duplicates` hashed and found
+#[derive(Debug, Default)]
+pub struct DuplicateSearch {
+    pub groups: Vec<LocalDuplicateGroup>,
+    pub files_hashed: usize,
+    pub bytes_hashed: u64,
+    pub unreadable_files: Vec<String>,
+    pub cancelled: bool,
+}
+
+/// Group byte-identical files. Files are bucketed by size first and only
+/// sizes shared by two or more files are hashed, `concurrency` files at a
+/// time. `on_progress(done, total, bytes_done, total_bytes)` runs after every
+/// hashed file. Once `cancelled` is set, files not started yet are skipped.
+pub async fn find_duplicates<F>(
+    image_service: &ImageService,
+    files: Vec<(String, u64)>,
+    concurrency: usize,
+    cancelled: Arc<AtomicBool>,
+    mut on_progress: F,
+) -> DuplicateSearch
+where
+    F: FnMut(usize, usize, u64, u64),
+{
+    let mut by_size: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for (path, size) in files {
+        // Empty files are trivially identical, they aren't images anyway
+        if size > 0 {
+            by_size.entry(size).or_default().push(path);
+        }
+    }
+    let candidates: Vec<(String, u64)> = by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (path, size)))
+        .collect();
+    let total = candidates.len();
+    let total_bytes: u64 = candidates.iter().map(|(_, size)| size).sum();
+
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (path, size) in candidates {
+        let semaphore = semaphore.clone();
+        let image_service = image_service.clone();
+        let cancelled = cancelled.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            let checksum = image_service.calculate_checksum(&path).await;
+            Some((path, size, checksum))
+        });
+    }
+
+    let mut search = DuplicateSearch::default();
+    let mut by_content: HashMap<(u64, String), Vec<String>> = HashMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        let Ok(Some((path, size, checksum))) = joined else {
+            continue;
+        };
+        search.files_hashed += 1;
+        search.bytes_hashed += size;
+        match checksum {
+            Ok(checksum) => by_content.entry((size, checksum)).or_default().push(path),
+            Err(e) => search.unreadable_files.push(format!("{}: {}", path, e)),
+        }
+        on_progress(search.files_hashed, total, search.bytes_hashed, total_bytes);
+    }
+    search.cancelled = cancelled.load(Ordering::Relaxed) && search.files_hashed < total;
+
+    search.groups = by_content
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, checksum), mut paths)| {
+            paths.sort();
+            LocalDuplicateGroup {
+                checksum,
+                size,
+                wasted_bytes: size * (paths.len() as u64 - 1),
+                files: paths
+                    .into_iter()
+                    .map(|path| LocalDuplicateFile {
+                        path,
+                        usage_count: None,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    search.groups.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.files[0].path.cmp(&b.files[0].path))
+    });
+    search.unreadable_files.sort();
+    search
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, name: &str, content: &[u8]) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn extensions() -> Vec<String> {
+        vec!["png".to_string(), "jpg".to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_groups_identical_files_and_hashes_shared_sizes_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "a.png", b"same bytes");
+        write(dir, "nested/b.png", b"same bytes");
+        write(dir, "nested/deeper/c.jpg", b"same bytes");
+        // Same size, different content
+        write(dir, "d.png", b"diff bytes");
+        write(dir, "unique.png", b"a size nobody else has");
+        write(dir, ".hidden/e.png", b"same bytes");
+        write(dir, "notes.txt", b"same bytes");
+
+        let files = list_image_files(&dir.to_string_lossy(), true, &extensions())
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 5);
+
+        let mut progress = Vec::new();
+        let search = find_duplicates(
+            &ImageService::new(),
+            files,
+            2,
+            Arc::new(AtomicBool::new(false)),
+            |done, total, _, _| progress.push((done, total)),
+        )
+        .await;
+
+        // unique.png was never hashed
+        assert_eq!(search.files_hashed, 4);
+        assert_eq!(progress.last(), Some(&(4, 4)));
+        assert!(!search.cancelled);
+        assert_eq!(search.groups.len(), 1);
+        let group = &search.groups[0];
+        assert_eq!(group.files.len(), 3);
+        assert_eq!(group.wasted_bytes, 20);
+        assert!(group.files[0].path.ends_with("a.png"));
+    }
+
+    #[tokio::test]
+    async fn test_non_recursive_listing_and_cancellation() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "a.png", b"same bytes");
+        write(dir, "b.png", b"same bytes");
+        write(dir, "nested/c.png", b"same bytes");
+
+        let files = list_image_files(&dir.to_string_lossy(), false, &extensions())
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 2);
+
+        let search = find_duplicates(
+            &ImageService::new(),
+            files,
+            1,
+            Arc::new(AtomicBool::new(true)),
+            |_, _, _, _| {},
+        )
+        .await;
+        assert!(search.cancelled);
+        assert_eq!(search.files_hashed, 0);
+        assert!(search.groups.is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::models::{ChecksumAlgorithm, LocalIntegrityFile, UploadHistoryRecord};
+use crate::services::ImageService;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::Metadata;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// What `verify_records` hashed and found
+#[derive(Debug, Default)]
+pub struct IntegrityCheck {
+    pub matched: Vec<LocalIntegrityFile>,
+    pub modified: Vec<LocalIntegrityFile>,
+    pub missing: Vec<LocalIntegrityFile>,
+    pub unverifiable: Vec<String>,
+    pub files_hashed: usize,
+    pub bytes_hashed: u64,
+    pub cancelled: bool,
+}
+
+/// Compare the local originals of `records` with the checksums stored when
+/// they were uploaded. Records without an original path are left out. Each
+/// file is hashed once per algorithm its records used, `concurrency` files at
+/// a time. `on_progress(done, total, bytes_done, total_bytes)` runs after
+/// every hashed file. Once `cancelled` is set, files not started yet are
+/// skipped and their records don't show up in any list.
+pub async fn verify_records<F>(
+    records: Vec<UploadHistoryRecord>,
+    concurrency: usize,
+    cancelled: Arc<AtomicBool>,
+    mut on_progress: F,
+) -> IntegrityCheck
+where
+    F: FnMut(usize, usize, u64, u64),
+{
+    let mut check = IntegrityCheck::default();
+    let mut by_path: BTreeMap<String, Vec<UploadHistoryRecord>> = BTreeMap::new();
+    for record in records {
+        let Some(path) = record.original_path.clone() else {
+            continue;
+        };
+        if ChecksumAlgorithm::split_checksum(&record.checksum).is_some() {
+            by_path.entry(path).or_default().push(record);
+        } else {
+            check.unverifiable.push(format!(
+                "{}: history record {} has no usable checksum",
+                path, record.id
+            ));
+        }
+    }
+
+    let mut present = Vec::new();
+    for (path, records) in by_path {
+        match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => present.push((path, metadata, records)),
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                check.unverifiable.push(format!("{}: {}", path, e));
+            }
+            _ => check
+                .missing
+                .extend(records.iter().map(|record| integrity_file(record, None))),
+        }
+    }
+    let total = present.len();
+    let total_bytes: u64 = present.iter().map(|(_, metadata, _)| metadata.len()).sum();
+
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (path, metadata, records) in present {
+        let semaphore = semaphore.clone();
+        let cancelled = cancelled.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            let algorithms: HashSet<ChecksumAlgorithm> = records
+                .iter()
+                .filter_map(|record| ChecksumAlgorithm::split_checksum(&record.checksum))
+                .map(|(algorithm, _)| algorithm)
+                .collect();
+            let mut checksums = Vec::with_capacity(algorithms.len());
+            for algorithm in algorithms {
+                let checksum = ImageService::new()
+                    .with_checksum_algorithm(algorithm)
+                    .calculate_checksum(&path)
+                    .await;
+                match checksum {
+                    Ok(checksum) => checksums.push(checksum),
+                    Err(e) => return Some((path, metadata, records, Err(e))),
+                }
+            }
+            Some((path, metadata, records, Ok(checksums)))
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let Ok(Some((path, metadata, records, checksums))) = joined else {
+            continue;
+        };
+        check.files_hashed += 1;
+        check.bytes_hashed += metadata.len();
+        match checksums {
+            Ok(checksums) => {
+                for record in &records {
+                    let matches = checksums.iter().any(|checksum| {
+                        ChecksumAlgorithm::checksums_match(&record.checksum, checksum)
+                    });
+                    let file = integrity_file(record, Some(&metadata));
+                    if matches {
+                        check.matched.push(file);
+                    } else {
+                        check.modified.push(file);
+                    }
+                }
+            }
+            Err(e) => check.unverifiable.push(format!("{}: {}", path, e)),
+        }
+        on_progress(check.files_hashed, total, check.bytes_hashed, total_bytes);
+    }
+    check.cancelled = cancelled.load(Ordering::Relaxed) && check.files_hashed < total;
+
+    for files in [&mut check.matched, &mut check.modified, &mut check.missing] {
+        files.sort_by(|a, b| {
+            a.original_path
+                .cmp(&b.original_path)
+                .then_with(|| a.uploaded_at.cmp(&b.uploaded_at))
+        });
+    }
+    check.unverifiable.sort();
+    check
+}
+
+fn integrity_file(record: &UploadHistoryRecord, metadata: Option<&Metadata>) -> LocalIntegrityFile {
+    LocalIntegrityFile {
+        record_id: record.id.clone(),
+        image_name: record.image_name.clone(),
+        uploaded_url: record.uploaded_url.clone(),
+        original_path: record.original_path.clone().unwrap_or_default(),
+        uploaded_at: record.timestamp,
+        uploaded_size: record.file_size,
+        local_size: metadata.map(Metadata::len),
+        modified_at: metadata
+            .and_then(|metadata| metadata.modified().ok())
+            .map(chrono::DateTime::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UploadMode;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn record(id: &str, original_path: Option<&Path>, checksum: String) -> UploadHistoryRecord {
+        UploadHistoryRecord {
+            id: id.to_string(),
+            timestamp: chrono::Utc::now(),
+            image_name: format!("{}.png", id),
+            uploaded_url: format!("https://cdn.example.com/{}.png", id),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 8,
+            checksum,
+            variants: vec![],
+            config_id: None,
+            bucket: None,
+            original_path: original_path.map(|path| path.to_string_lossy().to_string()),
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sorts_records_into_matched_modified_and_missing() {
+        let temp_dir = TempDir::new().unwrap();
+        let kept = temp_dir.path().join("kept.png");
+        let edited = temp_dir.path().join("edited.png");
+        let gone = temp_dir.path().join("gone.png");
+        std::fs::write(&kept, b"original").unwrap();
+        std::fs::write(&edited, b"original").unwrap();
+
+        let sha256 = ImageService::new();
+        let blake3 = ImageService::new().with_checksum_algorithm(ChecksumAlgorithm::Blake3);
+        let kept_path = kept.to_string_lossy();
+        let records = vec![
+            record(
+                "kept",
+                Some(&kept),
+                sha256.calculate_checksum(&kept_path).await.unwrap(),
+            ),
+            record(
+                "kept-blake3",
+                Some(&kept),
+                blake3.calculate_checksum(&kept_path).await.unwrap(),
+            ),
+            record(
+                "edited",
+                Some(&edited),
+                sha256.calculate_checksum(&kept_path).await.unwrap(),
+            ),
+            record("gone", Some(&gone), format!("sha256:{}", "ab".repeat(32))),
+            record("legacy", Some(&kept), String::new()),
+            record("pasted", None, format!("sha256:{}", "cd".repeat(32))),
+        ];
+        std::fs::write(&edited, b"retouched").unwrap();
+
+        let mut progress = Vec::new();
+        let check = verify_records(
+            records,
+            2,
+            Arc::new(AtomicBool::new(false)),
+            |done, total, _, _| progress.push((done, total)),
+        )
+        .await;
+
+        let ids = |files: &[LocalIntegrityFile]| -> Vec<String> {
+            files.iter().map(|file| file.record_id.clone()).collect()
+        };
+        assert_eq!(ids(&check.matched), vec!["kept", "kept-blake3"]);
+        assert_eq!(ids(&check.modified), vec!["edited"]);
+        assert_eq!(ids(&check.missing), vec!["gone"]);
+        assert_eq!(check.modified[0].local_size, Some(9));
+        assert!(check.modified[0].modified_at.is_some());
+        assert_eq!(check.missing[0].local_size, None);
+        assert_eq!(check.unverifiable.len(), 1);
+        assert!(check.unverifiable[0].contains("legacy"));
+
+        // kept.png is hashed once per algorithm but counted once
+        assert_eq!((check.files_hashed, check.bytes_hashed), (2, 17));
+        assert_eq!(progress.last(), Some(&(2, 2)));
+        assert!(!check.cancelled);
+
+        let cancelled = verify_records(
+            vec![record(
+                "kept",
+                Some(&kept),
+                String::from("sha256:") + &"0".repeat(64),
+            )],
+            1,
+            Arc::new(AtomicBool::new(true)),
+            |_, _, _, _| {},
+        )
+        .await;
+        assert!(cancelled.cancelled);
+        assert!(cancelled.matched.is_empty() && cancelled.modified.is_empty());
+    }
+}
@@ -0,0 +1,437 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::models::{EtaRange, OSSProvider};
+use crate::utils::app_paths;
+use crate::utils::error::AppError;
+
+const METRICS_FILE_NAME: &str = "upload_metrics.jsonl";
+
+// The log is cut down to the newest half of its lines once it grows past this
+const MAX_METRICS_BYTES: u64 = 2 * 1024 * 1024;
+
+// Compaction rewrites the file, appends from other uploads must wait for it
+static METRICS_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Timing of one upload request to the provider, appended to the metrics log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadMetric {
+    pub timestamp: DateTime<Utc>,
+    pub provider: OSSProvider,
+    /// Saved config the upload went through, None for unsaved configs
+    pub config_id: Option<String>,
+    pub bytes: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+}
+
+impl UploadMetric {
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 * 1000.0 / self.duration_ms.max(1) as f64
+    }
+}
+
+/// Period covered by `get_upload_performance_stats`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum StatsRange {
+    Day,
+    #[default]
+    Week,
+    Month,
+    All,
+}
+
+impl StatsRange {
+    fn since(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            StatsRange::Day => Some(now - Duration::days(1)),
+            StatsRange::Week => Some(now - Duration::days(7)),
+            StatsRange::Month => Some(now - Duration::days(30)),
+            StatsRange::All => None,
+        }
+    }
+
+    // Hourly points for a day, daily points otherwise
+    fn bucket(&self) -> Duration {
+        match self {
+            StatsRange::Day => Duration::hours(1),
+            _ => Duration::days(1),
+        }
+    }
+}
+
+/// Throughput of successful uploads and the failure rate of one provider/config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderPerformance {
+    pub provider: OSSProvider,
+    pub config_id: Option<String>,
+    pub uploads: usize,
+    pub failures: usize,
+    pub failure_rate: f64,
+    pub total_bytes: u64,
+    pub avg_bytes_per_sec: f64,
+    pub median_bytes_per_sec: f64,
+    pub p95_bytes_per_sec: f64,
+}
+
+/// Average throughput of one provider/config within one time bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThroughputPoint {
+    /// Start of the bucket
+    pub timestamp: DateTime<Utc>,
+    pub provider: OSSProvider,
+    pub config_id: Option<String>,
+    pub uploads: usize,
+    pub avg_bytes_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadPerformanceStats {
+    pub range: StatsRange,
+    pub since: Option<DateTime<Utc>>,
+    pub providers: Vec<ProviderPerformance>,
+    /// Oldest bucket first
+    pub series: Vec<ThroughputPoint>,
+}
+
+impl UploadPerformanceStats {
+    /// Average throughput of one upload through `config_id`, or through every
+    /// config of `provider` when that config has no successful uploads yet.
+    /// None when there is nothing to go by.
+    pub fn average_throughput(
+        &self,
+        provider: &OSSProvider,
+        config_id: Option<&str>,
+    ) -> Option<f64> {
+        let successes = |entry: &ProviderPerformance| entry.uploads - entry.failures;
+        let same_provider = |entry: &&ProviderPerformance| {
+            format!("{:?}", entry.provider) == format!("{:?}", provider) && successes(entry) > 0
+        };
+
+        if let Some(entry) = self
+            .providers
+            .iter()
+            .filter(same_provider)
+            .find(|entry| config_id.is_some() && entry.config_id.as_deref() == config_id)
+        {
+            return Some(entry.avg_bytes_per_sec);
+        }
+
+        // Weighted by successful uploads, so a config used once doesn't count as much
+        let (weighted, count) = self.providers.iter().filter(same_provider).fold(
+            (0.0, 0),
+            |(weighted, count), entry| {
+                (
+                    weighted + entry.avg_bytes_per_sec * successes(entry) as f64,
+                    count + successes(entry),
+                )
+            },
+        );
+        (count > 0).then(|| weighted / count as f64)
+    }
+}
+
+/// How long `uploads` uploads totalling `total_bytes` take at `bytes_per_sec`
+/// per upload, `concurrency` at a time. The fast end assumes every parallel
+/// upload keeps that speed, the slow end that they share one connection's worth.
+pub fn eta_range(
+    total_bytes: u64,
+    bytes_per_sec: f64,
+    uploads: usize,
+    concurrency: usize,
+) -> EtaRange {
+    if total_bytes == 0 || bytes_per_sec <= 0.0 {
+        return EtaRange::default();
+    }
+    let sequential = total_bytes as f64 / bytes_per_sec;
+    let parallel = concurrency.clamp(1, uploads.max(1)) as f64;
+    EtaRange {
+        min_secs: (sequential / parallel).ceil() as u64,
+        max_secs: sequential.ceil() as u64,
+    }
+}
+
+/// Append-only log of upload timings, kept apart from the upload history so
+/// failed uploads and repeated uploads of one image are counted too
+#[derive(Clone)]
+pub struct MetricsService {
+    metrics_file: PathBuf,
+}
+
+impl MetricsService {
+    pub fn new() -> Result<Self, AppError> {
+        Self::new_with_dir(app_paths::data_dir()?)
+    }
+
+    pub fn new_with_dir(data_dir: PathBuf) -> Result<Self, AppError> {
+        fs::create_dir_all(&data_dir)
+            .map_err(|e| AppError::FileSystem(format!("Failed to create data directory: {}", e)))?;
+
+        Ok(Self {
+            metrics_file: data_dir.join(METRICS_FILE_NAME),
+        })
+    }
+
+    pub fn record(&self, metric: &UploadMetric) -> Result<(), AppError> {
+        let mut line = serde_json::to_string(metric).map_err(AppError::Serialization)?;
+        line.push('\n');
+
+        let _guard = METRICS_WRITE_LOCK
+            .lock()
+            .map_err(|e| AppError::FileSystem(e.to_string()))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.metrics_file)
+            .map_err(|e| AppError::FileSystem(format!("Failed to open metrics log: {}", e)))?;
+        file.write_all(line.as_bytes())
+            .map_err(|e| AppError::FileSystem(format!("Failed to write metrics log: {}", e)))?;
+
+        if file.metadata().map(|m| m.len()).unwrap_or(0) > MAX_METRICS_BYTES {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Entries recorded at or after `since`, oldest first
+    pub fn load(&self, since: Option<DateTime<Utc>>) -> Result<Vec<UploadMetric>, AppError> {
+        let content = match fs::read_to_string(&self.metrics_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(AppError::FileSystem(format!(
+                    "Failed to read metrics log: {}",
+                    e
+                )))
+            }
+        };
+
+        // A line cut short by a crash is skipped rather than failing the whole log
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<UploadMetric>(line).ok())
+            .filter(|metric| since.is_none_or(|since| metric.timestamp >= since))
+            .collect())
+    }
+
+    pub fn performance_stats(&self, range: StatsRange) -> Result<UploadPerformanceStats, AppError> {
+        let since = range.since(Utc::now());
+        Ok(performance_stats(&self.load(since)?, range, since))
+    }
+
+    // Keep the newest half of the lines
+    fn compact(&self) -> Result<(), AppError> {
+        let content = fs::read_to_string(&self.metrics_file)
+            .map_err(|e| AppError::FileSystem(format!("Failed to read metrics log: {}", e)))?;
+        let lines: Vec<&str> = content.lines().collect();
+        let mut kept = lines[lines.len() / 2..].join("\n");
+        kept.push('\n');
+        fs::write(&self.metrics_file, kept)
+            .map_err(|e| AppError::FileSystem(format!("Failed to write metrics log: {}", e)))
+    }
+}
+
+type GroupKey = (String, Option<String>);
+
+fn group_key(metric: &UploadMetric) -> GroupKey {
+    (format!("{:?}", metric.provider), metric.config_id.clone())
+}
+
+// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn average(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn performance_stats(
+    metrics: &[UploadMetric],
+    range: StatsRange,
+    since: Option<DateTime<Utc>>,
+) -> UploadPerformanceStats {
+    let mut groups: BTreeMap<GroupKey, Vec<&UploadMetric>> = BTreeMap::new();
+    for metric in metrics {
+        groups.entry(group_key(metric)).or_default().push(metric);
+    }
+
+    let providers = groups
+        .values()
+        .map(|group| {
+            let failures = group.iter().filter(|m| !m.success).count();
+            let successful: Vec<&&UploadMetric> = group.iter().filter(|m| m.success).collect();
+            let mut throughput: Vec<f64> = successful.iter().map(|m| m.bytes_per_sec()).collect();
+            throughput.sort_by(f64::total_cmp);
+            ProviderPerformance {
+                provider: group[0].provider.clone(),
+                config_id: group[0].config_id.clone(),
+                uploads: group.len(),
+                failures,
+                failure_rate: failures as f64 / group.len() as f64,
+                total_bytes: successful.iter().map(|m| m.bytes).sum(),
+                avg_bytes_per_sec: average(&throughput),
+                median_bytes_per_sec: percentile(&throughput, 50.0),
+                p95_bytes_per_sec: percentile(&throughput, 95.0),
+            }
+        })
+        .collect();
+
+    let bucket = range.bucket();
+    let mut points: BTreeMap<(DateTime<Utc>, GroupKey), Vec<&UploadMetric>> = BTreeMap::new();
+    for metric in metrics.iter().filter(|m| m.success) {
+        let start = metric
+            .timestamp
+            .duration_trunc(bucket)
+            .unwrap_or(metric.timestamp);
+        points
+            .entry((start, group_key(metric)))
+            .or_default()
+            .push(metric);
+    }
+    let series = points
+        .into_iter()
+        .map(|((timestamp, _), group)| {
+            let throughput: Vec<f64> = group.iter().map(|m| m.bytes_per_sec()).collect();
+            ThroughputPoint {
+                timestamp,
+                provider: group[0].provider.clone(),
+                config_id: group[0].config_id.clone(),
+                uploads: group.len(),
+                avg_bytes_per_sec: average(&throughput),
+            }
+        })
+        .collect();
+
+    UploadPerformanceStats {
+        range,
+        since,
+        providers,
+        series,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn metric(provider: OSSProvider, bytes: u64, duration_ms: u64, success: bool) -> UploadMetric {
+        UploadMetric {
+            timestamp: Utc::now(),
+            provider,
+            config_id: Some("main".to_string()),
+            bytes,
+            duration_ms,
+            success,
+        }
+    }
+
+    #[test]
+    fn test_average_throughput_prefers_the_config() {
+        let mut other = metric(OSSProvider::Aliyun, 4_000_000, 1000, true);
+        other.config_id = Some("other".to_string());
+        let metrics = vec![
+            metric(OSSProvider::Aliyun, 1_000_000, 1000, true),
+            other.clone(),
+            other,
+            metric(OSSProvider::Aws, 9_000_000, 1000, true),
+        ];
+        let stats = performance_stats(&metrics, StatsRange::Week, None);
+
+        let main = stats.average_throughput(&OSSProvider::Aliyun, Some("main"));
+        assert_eq!(main, Some(1_000_000.0));
+        // Unknown config: every Aliyun upload, weighted by count
+        let unknown = stats.average_throughput(&OSSProvider::Aliyun, Some("new"));
+        assert_eq!(unknown, Some(3_000_000.0));
+        assert!(stats
+            .average_throughput(&OSSProvider::Tencent, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_eta_range() {
+        // 90 MB at 1 MB/s, 3 at a time
+        let eta = eta_range(90_000_000, 1_000_000.0, 30, 3);
+        assert_eq!(
+            eta,
+            EtaRange {
+                min_secs: 30,
+                max_secs: 90
+            }
+        );
+        // Concurrency can't exceed the number of uploads
+        let eta = eta_range(10_000_000, 1_000_000.0, 1, 5);
+        assert_eq!(
+            eta,
+            EtaRange {
+                min_secs: 10,
+                max_secs: 10
+            }
+        );
+        assert_eq!(eta_range(0, 1_000_000.0, 0, 3), EtaRange::default());
+    }
+
+    #[test]
+    fn test_stats_per_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = MetricsService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        for ms in [1000, 2000, 4000] {
+            service
+                .record(&metric(OSSProvider::Aliyun, 1_000_000, ms, true))
+                .unwrap();
+        }
+        service
+            .record(&metric(OSSProvider::Aliyun, 1_000_000, 500, false))
+            .unwrap();
+        service
+            .record(&metric(OSSProvider::Aws, 2_000_000, 1000, true))
+            .unwrap();
+
+        let stats = service.performance_stats(StatsRange::Day).unwrap();
+        assert_eq!(stats.providers.len(), 2);
+        let aliyun = &stats.providers[0];
+        assert!(matches!(aliyun.provider, OSSProvider::Aliyun));
+        assert_eq!((aliyun.uploads, aliyun.failures), (4, 1));
+        assert_eq!(aliyun.failure_rate, 0.25);
+        assert_eq!(aliyun.total_bytes, 3_000_000);
+        assert_eq!(aliyun.median_bytes_per_sec, 500_000.0);
+        assert_eq!(aliyun.p95_bytes_per_sec, 1_000_000.0);
+        assert_eq!(stats.providers[1].avg_bytes_per_sec, 2_000_000.0);
+
+        // One point per provider in the current hour
+        assert_eq!(stats.series.len(), 2);
+        assert_eq!(stats.series[0].uploads, 3);
+    }
+
+    #[test]
+    fn test_log_is_capped_and_skips_broken_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = MetricsService::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        let entry = metric(OSSProvider::Tencent, 1, 1, true);
+        let line_len = serde_json::to_string(&entry).unwrap().len() as u64 + 1;
+        let lines = MAX_METRICS_BYTES / line_len + 1;
+        for _ in 0..lines {
+            service.record(&entry).unwrap();
+        }
+        let kept = service.load(None).unwrap().len() as u64;
+        assert!(kept > 0 && kept < lines);
+        assert!(fs::metadata(&service.metrics_file).unwrap().len() <= MAX_METRICS_BYTES);
+
+        fs::write(&service.metrics_file, "{\"timestamp\":").unwrap();
+        assert!(service.load(None).unwrap().is_empty());
+    }
+}
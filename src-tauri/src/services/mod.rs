@@ -1,11 +1,29 @@
+pub mod app_data_service;
+pub mod backup_service;
 pub mod config_service;
+pub mod connection_diagnostics;
 pub mod file_service;
 pub mod history_service;
 pub mod image_service;
+pub mod key_template;
+pub mod link_analysis;
+pub mod link_formatter;
+pub mod local_duplicates;
+pub mod local_integrity;
+pub mod metrics_service;
+pub mod original_archive;
+pub mod oss_registry;
 pub mod oss_service;
+pub mod replacement_journal;
+pub mod report;
+pub mod scan_globs;
+pub mod thumbnail_cache;
 
+pub use app_data_service::AppDataService;
+pub use backup_service::BackupService;
 pub use config_service::ConfigService;
 pub use file_service::FileService;
 pub use history_service::HistoryService;
 pub use image_service::ImageService;
 pub use oss_service::OSSService;
+pub use replacement_journal::ReplacementJournal;
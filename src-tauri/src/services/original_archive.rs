@@ -0,0 +1,403 @@
+use crate::models::{ArchivedOriginal, OriginalArchiveCleanupResult, OriginalArchiveUsage};
+use crate::utils::store_lock::lock_store;
+use crate::utils::store_recovery::write_atomic;
+use crate::utils::{app_paths, AppError, Result};
+use crate::{log_debug, log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::{fs, task};
+
+const ARCHIVE_INDEX_FILE: &str = "index.json";
+
+/// A stored file no record points at is only deleted once it is this old, so
+/// cleanup can't race an upload that archived it and hasn't linked it yet
+const UNLINKED_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Which archived originals cleanup keeps
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveRetention {
+    /// Originals archived longer ago than this are removed
+    pub max_age_days: u32,
+    /// Oldest originals go first until the archive fits
+    pub max_bytes: u64,
+}
+
+impl ArchiveRetention {
+    pub fn from_settings(settings: &crate::models::AppSettings) -> Self {
+        Self {
+            max_age_days: settings.maintenance.originals_max_age_days,
+            max_bytes: settings
+                .maintenance
+                .originals_max_mb
+                .saturating_mul(1024 * 1024),
+        }
+    }
+}
+
+// One history record uploaded from an archived file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveEntry {
+    record_id: String,
+    sha256: String,
+    size: u64,
+    archived_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Source images kept before processing changed what got uploaded.
+///
+/// Files live in `<data dir>/originals/{sha256[0..2]}/{sha256}`, so uploading
+/// the same file twice stores it once. `index.json` maps history record ids
+/// to the hash of the file they were uploaded from.
+#[derive(Debug, Clone)]
+pub struct OriginalArchive {
+    root: PathBuf,
+}
+
+impl OriginalArchive {
+    pub fn new() -> Result<Self> {
+        Self::new_with_dir(app_paths::data_dir()?.join("originals"))
+    }
+
+    pub fn new_with_dir(root: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&root).map_err(|e| {
+            AppError::FileSystem(format!("Failed to create originals archive: {}", e))
+        })?;
+        Ok(Self { root })
+    }
+
+    /// Store `data` under its sha256 and return the hash; content that is
+    /// already archived isn't written again
+    pub async fn store(&self, data: &[u8]) -> Result<String> {
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        let path = self.file_path(&sha256)?;
+        if fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok(sha256);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // Written under a temporary name, a crash never leaves a truncated
+        // file behind a valid hash
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, data)
+            .await
+            .map_err(|e| AppError::FileSystem(format!("Failed to archive original: {}", e)))?;
+        fs::rename(&temp_path, &path).await?;
+
+        log_debug!(
+            operation = "archive_original",
+            sha256 = %sha256,
+            size = data.len(),
+            "Original archived"
+        );
+        Ok(sha256)
+    }
+
+    /// Record that history record `record_id` was uploaded from the archived `sha256`
+    pub async fn link(&self, record_id: &str, sha256: &str) -> Result<()> {
+        let size = fs::metadata(self.file_path(sha256)?).await?.len();
+        let _lock = lock_store(&self.index_path()).await?;
+        let mut index = self.load_index().await?;
+        index.retain(|entry| entry.record_id != record_id);
+        index.push(ArchiveEntry {
+            record_id: record_id.to_string(),
+            sha256: sha256.to_string(),
+            size,
+            archived_at: chrono::Utc::now(),
+        });
+        self.save_index(&index)
+    }
+
+    /// The archived original of a history record, None when there is none or
+    /// cleanup removed it
+    pub async fn lookup(&self, record_id: &str) -> Result<Option<ArchivedOriginal>> {
+        let index = self.load_index().await?;
+        let Some(entry) = index.into_iter().find(|entry| entry.record_id == record_id) else {
+            return Ok(None);
+        };
+        let path = self.file_path(&entry.sha256)?;
+        if !fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok(None);
+        }
+        Ok(Some(ArchivedOriginal {
+            record_id: entry.record_id,
+            sha256: entry.sha256,
+            path: path.to_string_lossy().to_string(),
+            size: entry.size,
+            archived_at: entry.archived_at,
+        }))
+    }
+
+    /// Drop index entries of records not in `live_records` (when given) and
+    /// those past `retention.max_age_days`, then the oldest files until the
+    /// archive fits `retention.max_bytes`. Files no entry points at anymore
+    /// are deleted.
+    pub async fn cleanup(
+        &self,
+        retention: &ArchiveRetention,
+        live_records: Option<&HashSet<String>>,
+    ) -> Result<OriginalArchiveCleanupResult> {
+        let _lock = lock_store(&self.index_path()).await?;
+        let index = self.load_index().await?;
+        let expire_before =
+            chrono::Utc::now() - chrono::Duration::days(i64::from(retention.max_age_days));
+
+        let (mut kept, mut released): (Vec<_>, Vec<_>) = index.into_iter().partition(|entry| {
+            entry.archived_at >= expire_before
+                && live_records.is_none_or(|live| live.contains(&entry.record_id))
+        });
+
+        // Per file the newest link decides its age; oldest files go first
+        let mut files: HashMap<&str, (u64, chrono::DateTime<chrono::Utc>)> = HashMap::new();
+        for entry in &kept {
+            let file = files
+                .entry(entry.sha256.as_str())
+                .or_insert((entry.size, entry.archived_at));
+            file.1 = file.1.max(entry.archived_at);
+        }
+        let mut total: u64 = files.values().map(|(size, _)| size).sum();
+        let mut by_age: Vec<_> = files.into_iter().collect();
+        by_age.sort_by_key(|(_, (_, newest))| *newest);
+        let mut evicted = HashSet::new();
+        for (sha256, (size, _)) in by_age {
+            if total <= retention.max_bytes {
+                break;
+            }
+            total -= size;
+            evicted.insert(sha256.to_string());
+        }
+        if !evicted.is_empty() {
+            let (evicted_entries, still_kept): (Vec<_>, Vec<_>) = kept
+                .into_iter()
+                .partition(|entry| evicted.contains(&entry.sha256));
+            released.extend(evicted_entries);
+            kept = still_kept;
+        }
+
+        let referenced: HashSet<String> = kept.iter().map(|entry| entry.sha256.clone()).collect();
+        let (removed_files, removed_bytes) = self.remove_unreferenced(referenced).await?;
+        if !released.is_empty() {
+            self.save_index(&kept)?;
+        }
+
+        let result = OriginalArchiveCleanupResult {
+            removed_files,
+            removed_bytes,
+            released_records: released.into_iter().map(|entry| entry.record_id).collect(),
+        };
+        log_info!(
+            operation = "cleanup_original_archive",
+            removed_files = result.removed_files,
+            removed_bytes = result.removed_bytes,
+            released_records = result.released_records.len(),
+            "Originals archive cleaned up"
+        );
+        Ok(result)
+    }
+
+    pub async fn usage(&self) -> Result<OriginalArchiveUsage> {
+        let record_count = self.load_index().await?.len();
+        let root = self.root.clone();
+        let (file_count, total_bytes) = task::spawn_blocking(move || {
+            archived_files(&root)
+                .into_iter()
+                .fold((0, 0), |(count, bytes), (_, size, _)| {
+                    (count + 1, bytes + size)
+                })
+        })
+        .await
+        .map_err(|e| AppError::FileSystem(format!("Task join error: {}", e)))?;
+        Ok(OriginalArchiveUsage {
+            file_count,
+            total_bytes,
+            record_count,
+        })
+    }
+
+    // Delete archived files not in `referenced`, except recently written ones
+    async fn remove_unreferenced(&self, referenced: HashSet<String>) -> Result<(usize, u64)> {
+        let root = self.root.clone();
+        task::spawn_blocking(move || {
+            let mut removed = (0, 0);
+            for (path, size, modified) in archived_files(&root) {
+                let sha256 = path.file_name().unwrap_or_default().to_string_lossy();
+                let recent = modified
+                    .elapsed()
+                    .map(|age| age < UNLINKED_GRACE)
+                    .unwrap_or(true);
+                if referenced.contains(sha256.as_ref()) || recent {
+                    continue;
+                }
+                match std::fs::remove_file(&path) {
+                    Ok(()) => {
+                        removed.0 += 1;
+                        removed.1 += size;
+                    }
+                    Err(e) => {
+                        log_warn!(
+                            operation = "cleanup_original_archive",
+                            path = %path.display(),
+                            error = %e,
+                            "Failed to delete archived original"
+                        );
+                    }
+                }
+            }
+            removed
+        })
+        .await
+        .map_err(|e| AppError::FileSystem(format!("Task join error: {}", e)))
+    }
+
+    // The hash comes from the index too, so it's checked before it becomes a path
+    fn file_path(&self, sha256: &str) -> Result<PathBuf> {
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(AppError::Validation(format!(
+                "Invalid archive hash: {}",
+                sha256
+            )));
+        }
+        Ok(self.root.join(&sha256[..2]).join(sha256))
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root.join(ARCHIVE_INDEX_FILE)
+    }
+
+    // 读取索引，文件不存在时视为空
+    async fn load_index(&self) -> Result<Vec<ArchiveEntry>> {
+        let content = match fs::read_to_string(self.index_path()).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        if content.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save_index(&self, index: &[ArchiveEntry]) -> Result<()> {
+        let content = serde_json::to_string_pretty(index)?;
+        write_atomic(&self.index_path(), &content)?;
+        Ok(())
+    }
+}
+
+// Every `{xx}/{sha256}` file below `root` with its size and modification time
+fn archived_files(root: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let Ok(prefixes) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    prefixes
+        .flatten()
+        .filter(|prefix| prefix.path().is_dir())
+        .filter_map(|prefix| std::fs::read_dir(prefix.path()).ok())
+        .flat_map(|files| files.flatten())
+        .filter_map(|file| {
+            let metadata = file.metadata().ok()?;
+            let is_archived = metadata.is_file() && file.path().extension().is_none();
+            is_archived.then(|| {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                (file.path(), metadata.len(), modified)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn retention(max_age_days: u32, max_bytes: u64) -> ArchiveRetention {
+        ArchiveRetention {
+            max_age_days,
+            max_bytes,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_is_content_addressed_and_linked_records_resolve() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = OriginalArchive::new_with_dir(temp_dir.path().join("originals")).unwrap();
+
+        let sha256 = archive.store(b"original bytes").await.unwrap();
+        assert_eq!(archive.store(b"original bytes").await.unwrap(), sha256);
+        archive.link("record-1", &sha256).await.unwrap();
+        archive.link("record-2", &sha256).await.unwrap();
+
+        let original = archive.lookup("record-2").await.unwrap().unwrap();
+        assert!(original
+            .path
+            .ends_with(&format!("{}/{}", &sha256[..2], sha256)));
+        assert_eq!(std::fs::read(&original.path).unwrap(), b"original bytes");
+        assert!(archive.lookup("unknown").await.unwrap().is_none());
+
+        let usage = archive.usage().await.unwrap();
+        assert_eq!(usage.file_count, 1);
+        assert_eq!(usage.total_bytes, 14);
+        assert_eq!(usage.record_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_drops_deleted_records_and_enforces_size_cap() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = OriginalArchive::new_with_dir(temp_dir.path().join("originals")).unwrap();
+        let old = archive.store(&[1u8; 100]).await.unwrap();
+        let new = archive.store(&[2u8; 100]).await.unwrap();
+        archive.link("old", &old).await.unwrap();
+        archive.link("new", &new).await.unwrap();
+        archive.link("deleted", &new).await.unwrap();
+
+        // Files past the grace period, as if archived a while ago
+        let past = SystemTime::now() - UNLINKED_GRACE * 2;
+        for sha256 in [&old, &new] {
+            std::fs::File::options()
+                .write(true)
+                .open(archive.file_path(sha256).unwrap())
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+
+        let live: HashSet<String> = ["old".to_string(), "new".to_string()].into();
+        let result = archive
+            .cleanup(&retention(365, 150), Some(&live))
+            .await
+            .unwrap();
+
+        // "deleted" lost its entry, "old" its file to the size cap
+        let mut released = result.released_records.clone();
+        released.sort();
+        assert_eq!(released, vec!["deleted", "old"]);
+        assert_eq!(result.removed_files, 1);
+        assert_eq!(result.removed_bytes, 100);
+        assert!(archive.lookup("old").await.unwrap().is_none());
+        assert!(archive.lookup("new").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_recent_unlinked_files_survive_cleanup() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = OriginalArchive::new_with_dir(temp_dir.path().join("originals")).unwrap();
+        // Stored by an upload that hasn't linked it yet
+        let pending = archive.store(b"in flight").await.unwrap();
+
+        let result = archive.cleanup(&retention(365, 0), None).await.unwrap();
+        assert_eq!(result.removed_files, 0);
+        assert!(archive.file_path(&pending).unwrap().exists());
+    }
+
+    #[test]
+    fn test_invalid_hashes_never_become_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = OriginalArchive::new_with_dir(temp_dir.path().to_path_buf()).unwrap();
+        assert!(archive.file_path("../../etc/passwd").is_err());
+        assert!(archive.file_path(&"a".repeat(64)).is_ok());
+    }
+}
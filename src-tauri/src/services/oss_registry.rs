@@ -0,0 +1,264 @@
+use crate::log_debug;
+use crate::models::OSSConfig;
+use crate::services::OSSService;
+use crate::utils::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A service nobody used for this long is dropped with its idle connections
+const IDLE_EVICTION: Duration = Duration::from_secs(10 * 60);
+/// Configs kept at once, the least recently used goes first
+const MAX_CACHED_SERVICES: usize = 16;
+
+struct CachedService {
+    service: Arc<OSSService>,
+    last_used: Instant,
+}
+
+/// OSS services shared between commands, one per config. Each provider keeps
+/// its reqwest client, so back-to-back commands reuse pooled connections
+/// instead of repeating DNS lookups and TLS handshakes.
+pub struct OSSServiceRegistry {
+    services: Mutex<HashMap<String, CachedService>>,
+}
+
+impl OSSServiceRegistry {
+    pub fn new() -> Self {
+        Self {
+            services: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached service for `config`, created on first use
+    pub fn get(&self, config: &OSSConfig) -> Result<Arc<OSSService>> {
+        let key = config_fingerprint(config)?;
+        let mut services = self.services.lock().unwrap_or_else(|e| e.into_inner());
+
+        let now = Instant::now();
+        services.retain(|_, cached| now.duration_since(cached.last_used) < IDLE_EVICTION);
+
+        if let Some(cached) = services.get_mut(&key) {
+            cached.last_used = now;
+            return Ok(cached.service.clone());
+        }
+
+        let service = Arc::new(OSSService::new(config.clone())?);
+        if services.len() >= MAX_CACHED_SERVICES {
+            if let Some(oldest) = services
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone())
+            {
+                services.remove(&oldest);
+            }
+        }
+        log_debug!(
+            operation = "oss_registry_get",
+            provider = ?config.provider,
+            bucket = %config.bucket,
+            cached_services = services.len() + 1,
+            "Created OSS service"
+        );
+        services.insert(
+            key,
+            CachedService {
+                service: service.clone(),
+                last_used: now,
+            },
+        );
+        Ok(service)
+    }
+
+    /// Drop every cached service. Called whenever configs are saved, so a
+    /// changed secret or a deleted config doesn't outlive the change in memory.
+    pub fn clear(&self) {
+        self.services
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.services.lock().map(|s| s.len()).unwrap_or(0)
+    }
+}
+
+impl Default for OSSServiceRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Every field counts: providers read the CDN domain, ACL and size limits too,
+// not only the connection parameters
+fn config_fingerprint(config: &OSSConfig) -> Result<String> {
+    let json = serde_json::to_vec(config)?;
+    Ok(hex::encode(Sha256::digest(json)))
+}
+
+lazy_static::lazy_static! {
+    pub static ref OSS_SERVICES: OSSServiceRegistry = OSSServiceRegistry::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OSSProvider, ObjectAcl};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn config(bucket: &str) -> OSSConfig {
+        OSSConfig {
+            provider: OSSProvider::Aws,
+            endpoint: String::new(),
+            access_key_id: "id".to_string(),
+            access_key_secret: "secret".to_string(),
+            bucket: bucket.to_string(),
+            region: "us-east-1".to_string(),
+            path_template: "images/{filename}".to_string(),
+            cdn_domain: None,
+            compression_enabled: false,
+            compression_quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_same_config_reuses_service() {
+        let registry = OSSServiceRegistry::new();
+        let first = registry.get(&config("a")).unwrap();
+        assert!(Arc::ptr_eq(&first, &registry.get(&config("a")).unwrap()));
+
+        // Any changed field is a different service
+        let mut changed = config("a");
+        changed.access_key_secret = "rotated".to_string();
+        assert!(!Arc::ptr_eq(&first, &registry.get(&changed).unwrap()));
+        assert_eq!(registry.len(), 2);
+
+        registry.clear();
+        assert!(!Arc::ptr_eq(&first, &registry.get(&config("a")).unwrap()));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_least_recently_used_service_is_evicted() {
+        let registry = OSSServiceRegistry::new();
+        let first = registry.get(&config("bucket-0")).unwrap();
+        for i in 1..=MAX_CACHED_SERVICES {
+            registry.get(&config(&format!("bucket-{}", i))).unwrap();
+        }
+        assert_eq!(registry.len(), MAX_CACHED_SERVICES);
+        assert!(!Arc::ptr_eq(
+            &first,
+            &registry.get(&config("bucket-0")).unwrap()
+        ));
+    }
+
+    // Answers every request with an empty 200 over keep-alive connections and
+    // counts the connections it accepted
+    async fn mock_server() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buffer = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        // One request: headers, then Content-Length bytes of body
+                        let request_len = loop {
+                            if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                                let headers =
+                                    String::from_utf8_lossy(&buffer[..end]).to_lowercase();
+                                let body_len = headers
+                                    .lines()
+                                    .find_map(|l| l.strip_prefix("content-length:"))
+                                    .and_then(|v| v.trim().parse::<usize>().ok())
+                                    .unwrap_or(0);
+                                if buffer.len() >= end + 4 + body_len {
+                                    break end + 4 + body_len;
+                                }
+                            }
+                            match socket.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                            }
+                        };
+                        buffer.drain(..request_len);
+                        let response = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+                        if socket.write_all(response).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (port, connections)
+    }
+
+    fn loopback_config(port: u16) -> OSSConfig {
+        OSSConfig {
+            provider: OSSProvider::Aliyun,
+            endpoint: format!("http://localhost:{}", port),
+            ..config("registry-test")
+        }
+    }
+
+    // What the registry saves: 20 small uploads back-to-back open one
+    // connection through the cached service, and one each through fresh services
+    #[tokio::test]
+    async fn test_cached_service_reuses_connections_for_back_to_back_uploads() {
+        let body = vec![0u8; 2048];
+
+        // Saving configs clears the registry, a test doing that concurrently
+        // hands out a second service, so that attempt doesn't count
+        let mut reused = false;
+        for _ in 0..3 {
+            let (port, connections) = mock_server().await;
+            let config = loopback_config(port);
+            let first = OSS_SERVICES.get(&config).unwrap();
+            let mut same_service = true;
+            for i in 0..20 {
+                let service = OSS_SERVICES.get(&config).unwrap();
+                same_service &= Arc::ptr_eq(&first, &service);
+                service
+                    .upload_image(&format!("images/{}.png", i), &body, None)
+                    .await
+                    .unwrap();
+            }
+            if same_service {
+                assert_eq!(connections.load(Ordering::SeqCst), 1);
+                reused = true;
+                break;
+            }
+        }
+        assert!(reused, "the registry was cleared during every attempt");
+
+        let (port, connections) = mock_server().await;
+        let config = loopback_config(port);
+        for i in 0..20 {
+            OSSService::new(config.clone())
+                .unwrap()
+                .upload_image(&format!("images/{}.png", i), &body, None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 20);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::models::{
+    BackupInfo, ReplacementOperation, ReplacementOperationFile, ReplacementUndoResult,
+    UndoFileError,
+};
+use crate::services::BackupService;
+use crate::utils::store_lock::lock_store;
+use crate::utils::store_recovery::write_atomic;
+use crate::utils::{app_paths, AppError, Result};
+use crate::{log_info, log_warn};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tokio::fs;
+
+const JOURNAL_FILE: &str = "replacement_journal.json";
+
+/// Replacement runs kept for undo; older ones are dropped from the journal
+/// (their backups stay until backup cleanup removes them)
+const MAX_OPERATIONS: usize = 20;
+
+/// The last replacement runs that took backups, so the most recent one can be
+/// undone as a whole, also after a restart.
+///
+/// Each run keeps its backups and the checksum of every file right after the
+/// rewrite. Undo only goes ahead when all files still match that checksum and
+/// all backups are intact, then restores every file. Files restored by an
+/// undo that failed part way are marked, so retrying it finishes the rest.
+pub struct ReplacementJournal {
+    file: PathBuf,
+}
+
+impl ReplacementJournal {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_file(app_paths::data_dir()?.join(JOURNAL_FILE)))
+    }
+
+    pub fn with_file(file: PathBuf) -> Self {
+        Self { file }
+    }
+
+    /// Journaled runs, newest first
+    pub async fn list(&self) -> Result<Vec<ReplacementOperation>> {
+        let mut operations = self.load().await?;
+        operations.reverse();
+        Ok(operations)
+    }
+
+    /// Record a finished run whose files were backed up to `backups`
+    pub async fn record(
+        &self,
+        source: &str,
+        backups: &[BackupInfo],
+    ) -> Result<ReplacementOperation> {
+        let mut files = Vec::with_capacity(backups.len());
+        for backup in backups {
+            files.push(ReplacementOperationFile {
+                backup: backup.clone(),
+                checksum_after: file_checksum(&backup.original_path).await?,
+                restored: false,
+            });
+        }
+        let operation = ReplacementOperation {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: chrono::Utc::now(),
+            source: source.to_string(),
+            files,
+            undone_at: None,
+        };
+
+        let _lock = lock_store(&self.file).await?;
+        let mut operations = self.load().await?;
+        operations.push(operation.clone());
+        if operations.len() > MAX_OPERATIONS {
+            let excess = operations.len() - MAX_OPERATIONS;
+            operations.drain(..excess);
+        }
+        self.save(&operations)?;
+
+        log_info!(
+            operation = "journal_replacement",
+            operation_id = %operation.id,
+            source = source,
+            file_count = operation.files.len(),
+            "Replacement run recorded for undo"
+        );
+        Ok(operation)
+    }
+
+    /// Restore every file of the run `operation_id`, or of the most recent
+    /// run not undone yet. Nothing is restored when a file changed since the
+    /// run or a backup is missing or damaged; those files are returned as
+    /// conflicts.
+    pub async fn undo(
+        &self,
+        operation_id: Option<&str>,
+        backup_service: &BackupService,
+    ) -> Result<ReplacementUndoResult> {
+        let _lock = lock_store(&self.file).await?;
+        let mut operations = self.load().await?;
+        let index = match operation_id {
+            Some(id) => operations
+                .iter()
+                .position(|operation| operation.id == id)
+                .ok_or_else(|| {
+                    AppError::Validation(format!("Replacement run not found: {}", id))
+                })?,
+            None => operations
+                .iter()
+                .rposition(|operation| operation.undone_at.is_none())
+                .ok_or_else(|| AppError::Validation("No replacement run to undo".to_string()))?,
+        };
+        let operation = &operations[index];
+        if operation.undone_at.is_some() {
+            return Err(AppError::Validation(format!(
+                "Replacement run {} was already undone",
+                operation.id
+            )));
+        }
+
+        let mut result = ReplacementUndoResult {
+            operation_id: operation.id.clone(),
+            undone: false,
+            restored_files: Vec::new(),
+            conflicts: Vec::new(),
+            failed: Vec::new(),
+        };
+        for file in &operation.files {
+            if let Some(message) = undo_conflict(file).await {
+                result.conflicts.push(UndoFileError {
+                    file_path: file.backup.original_path.clone(),
+                    message,
+                });
+            }
+        }
+        if !result.conflicts.is_empty() {
+            log_warn!(
+                operation = "undo_replacement",
+                operation_id = %result.operation_id,
+                conflicts = result.conflicts.len(),
+                "Undo refused, files changed since the replacement run"
+            );
+            return Ok(result);
+        }
+
+        let mut newly_restored = 0;
+        for file in &mut operations[index].files {
+            if !file.restored {
+                if let Err(e) = backup_service.restore_backup(&file.backup).await {
+                    result.failed.push(UndoFileError {
+                        file_path: file.backup.original_path.clone(),
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+                file.restored = true;
+                newly_restored += 1;
+            }
+            result
+                .restored_files
+                .push(file.backup.original_path.clone());
+        }
+        result.undone = result.failed.is_empty();
+        if result.undone {
+            operations[index].undone_at = Some(chrono::Utc::now());
+        }
+        if newly_restored > 0 || result.undone {
+            self.save(&operations)?;
+        }
+
+        log_info!(
+            operation = "undo_replacement",
+            operation_id = %result.operation_id,
+            restored = result.restored_files.len(),
+            failed = result.failed.len(),
+            "Replacement run undone"
+        );
+        Ok(result)
+    }
+
+    async fn load(&self) -> Result<Vec<ReplacementOperation>> {
+        let content = match fs::read_to_string(&self.file).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(AppError::FileSystem(format!(
+                    "Failed to read replacement journal: {}",
+                    e
+                )))
+            }
+        };
+        if content.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, operations: &[ReplacementOperation]) -> Result<()> {
+        let content = serde_json::to_string_pretty(operations)?;
+        write_atomic(&self.file, &content).map_err(|e| {
+            AppError::FileSystem(format!("Failed to write replacement journal: {}", e))
+        })
+    }
+}
+
+async fn file_checksum(path: &str) -> Result<String> {
+    let content = fs::read(path)
+        .await
+        .map_err(|e| AppError::FileSystem(format!("Failed to read {}: {}", path, e)))?;
+    Ok(format!("{:x}", Sha256::digest(&content)))
+}
+
+// Why `file` can't be put back, None when it can
+async fn undo_conflict(file: &ReplacementOperationFile) -> Option<String> {
+    let (expected, message) = if file.restored {
+        (&file.backup.checksum, "Modified after it was restored")
+    } else {
+        (&file.checksum_after, "Modified after the replacement run")
+    };
+    match file_checksum(&file.backup.original_path).await {
+        Ok(checksum) if &checksum != expected => return Some(message.to_string()),
+        Ok(_) => {}
+        Err(e) => return Some(e.to_string()),
+    }
+    match file_checksum(&file.backup.backup_path).await {
+        Ok(checksum) if checksum != file.backup.checksum => Some(format!(
+            "Backup {} doesn't match its checksum",
+            file.backup.id
+        )),
+        Ok(_) => None,
+        Err(_) => Some(format!("Backup {} no longer exists", file.backup.id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct Fixture {
+        _temp_dir: TempDir,
+        journal: ReplacementJournal,
+        backups: BackupService,
+        files: Vec<String>,
+    }
+
+    // Two files rewritten by one backed-up run
+    async fn rewritten_files() -> (Fixture, Vec<BackupInfo>) {
+        let temp_dir = TempDir::new().unwrap();
+        let backups = BackupService::new_with_dir(temp_dir.path().join("backups")).unwrap();
+        let mut files = Vec::new();
+        let mut infos = Vec::new();
+        for name in ["a.md", "b.md"] {
+            let path = temp_dir.path().join(name).to_string_lossy().to_string();
+            std::fs::write(&path, "![x](./x.png)\n").unwrap();
+            infos.push(backups.create_backup(&path).await.unwrap());
+            std::fs::write(&path, "![x](https://cdn.example.com/x.png)\n").unwrap();
+            files.push(path);
+        }
+        let journal = ReplacementJournal::with_file(temp_dir.path().join(JOURNAL_FILE));
+        let fixture = Fixture {
+            _temp_dir: temp_dir,
+            journal,
+            backups,
+            files,
+        };
+        (fixture, infos)
+    }
+
+    #[tokio::test]
+    async fn test_undo_restores_every_file_of_the_last_run() {
+        let (fixture, infos) = rewritten_files().await;
+        let recorded = fixture
+            .journal
+            .record("process_markdown_files", &infos)
+            .await
+            .unwrap();
+
+        // A new journal reads the same file, as after a restart
+        let journal = ReplacementJournal::with_file(fixture.journal.file.clone());
+        let listed = journal.list().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, recorded.id);
+
+        let result = journal.undo(None, &fixture.backups).await.unwrap();
+        assert!(result.undone);
+        assert_eq!(result.restored_files, fixture.files);
+        for file in &fixture.files {
+            assert_eq!(std::fs::read_to_string(file).unwrap(), "![x](./x.png)\n");
+        }
+        assert!(journal.list().await.unwrap()[0].undone_at.is_some());
+        assert!(journal.undo(None, &fixture.backups).await.is_err());
+        assert!(journal
+            .undo(Some(&recorded.id), &fixture.backups)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_finishes_a_partial_undo() {
+        let (fixture, infos) = rewritten_files().await;
+        fixture
+            .journal
+            .record("process_markdown_files", &infos)
+            .await
+            .unwrap();
+
+        // An earlier undo put a.md back, then failed on b.md
+        let mut operations = fixture.journal.load().await.unwrap();
+        operations[0].files[0].restored = true;
+        fixture.journal.save(&operations).unwrap();
+        std::fs::copy(&infos[0].backup_path, &fixture.files[0]).unwrap();
+
+        let result = fixture.journal.undo(None, &fixture.backups).await.unwrap();
+        assert!(result.undone, "{:?}", result.conflicts);
+        assert_eq!(result.restored_files, fixture.files);
+        for file in &fixture.files {
+            assert_eq!(std::fs::read_to_string(file).unwrap(), "![x](./x.png)\n");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_undo_refuses_when_a_file_changed_since_the_run() {
+        let (fixture, infos) = rewritten_files().await;
+        fixture
+            .journal
+            .record("migrate_cdn_domain", &infos)
+            .await
+            .unwrap();
+        std::fs::write(&fixture.files[1], "edited by hand\n").unwrap();
+        std::fs::remove_file(&infos[0].backup_path).unwrap();
+
+        let result = fixture.journal.undo(None, &fixture.backups).await.unwrap();
+        assert!(!result.undone);
+        assert!(result.restored_files.is_empty());
+        assert_eq!(
+            result.conflicts,
+            vec![
+                UndoFileError {
+                    file_path: fixture.files[0].clone(),
+                    message: format!("Backup {} no longer exists", infos[0].id),
+                },
+                UndoFileError {
+                    file_path: fixture.files[1].clone(),
+                    message: "Modified after the replacement run".to_string(),
+                },
+            ]
+        );
+        // Nothing was put back, not even the file that could have been
+        assert_eq!(
+            std::fs::read_to_string(&fixture.files[0]).unwrap(),
+            "![x](https://cdn.example.com/x.png)\n"
+        );
+        assert!(fixture.journal.list().await.unwrap()[0].undone_at.is_none());
+    }
+}
@@ -0,0 +1,841 @@
+use crate::models::{
+    BatchReplacementResult, ConfigItem, GalleryFormat, LocalDuplicateReport, MissingImage,
+    ReplacementChange, ReplacementChangelog, ReportFormat, ScanResult, ScanStatus,
+    UploadHistoryRecord,
+};
+use crate::services::oss_service::object_key_from_url;
+use crate::utils::html::escape_html;
+use crate::utils::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Per-file line of a scan report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanFileSummary {
+    pub file_path: String,
+    pub status: ScanStatus,
+    pub error: Option<String>,
+    pub image_count: usize,
+    pub missing_count: usize,
+}
+
+/// Audit trail of a scan, written by export_scan_report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    pub total_files: usize,
+    pub total_images: usize,
+    pub total_missing: usize,
+    pub files: Vec<ScanFileSummary>,
+    pub missing_images: Vec<MissingImage>,
+}
+
+impl ScanReport {
+    pub fn from_results(results: &[ScanResult]) -> Self {
+        let mut files = Vec::with_capacity(results.len());
+        let mut missing_images = Vec::new();
+
+        for result in results {
+            // Results coming back from the frontend may predate missing_images
+            let missing = MissingImage::collect(&result.file_path, &result.images);
+
+            files.push(ScanFileSummary {
+                file_path: result.file_path.clone(),
+                status: result.status.clone(),
+                error: result.error.clone(),
+                image_count: result.images.len(),
+                missing_count: missing.len(),
+            });
+            missing_images.extend(missing);
+        }
+
+        Self {
+            generated_at: chrono::Utc::now(),
+            total_files: files.len(),
+            total_images: files.iter().map(|f| f.image_count).sum(),
+            total_missing: missing_images.len(),
+            files,
+            missing_images,
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# imgtoss scan report\n\n");
+        out.push_str(&format!(
+            "Generated: {}\n\n",
+            self.generated_at.to_rfc3339()
+        ));
+        out.push_str(&format!(
+            "- Files scanned: {}\n- Images found: {}\n- Missing images: {}\n\n",
+            self.total_files, self.total_images, self.total_missing
+        ));
+
+        out.push_str("| File | Images | Missing | Status |\n");
+        out.push_str("| --- | ---: | ---: | --- |\n");
+        for file in &self.files {
+            let status = match (&file.status, &file.error) {
+                (ScanStatus::Success, _) => "ok".to_string(),
+                (ScanStatus::Error, Some(error)) => format!("error: {}", error),
+                (ScanStatus::Error, None) => "error".to_string(),
+                (ScanStatus::Skipped, Some(reason)) => format!("skipped: {}", reason),
+                (ScanStatus::Skipped, None) => "skipped".to_string(),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                escape_cell(&file.file_path),
+                file.image_count,
+                file.missing_count,
+                escape_cell(&status)
+            ));
+        }
+
+        if !self.missing_images.is_empty() {
+            out.push_str("\n## Missing images\n\n");
+            out.push_str("| File | Line | Column | Image |\n");
+            out.push_str("| --- | ---: | ---: | --- |\n");
+            for missing in &self.missing_images {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    escape_cell(&missing.file_path),
+                    missing.line,
+                    missing.column,
+                    escape_cell(&missing.original_path)
+                ));
+            }
+        }
+
+        out
+    }
+}
+
+/// Human-readable summary of a batch replacement, failed errors are kept verbatim
+pub fn replacement_report_markdown(result: &BatchReplacementResult) -> String {
+    let mut out = String::new();
+    out.push_str("# imgtoss replacement report\n\n");
+    out.push_str(&format!(
+        "Generated: {}\n\n",
+        chrono::DateTime::<chrono::Utc>::from(result.timestamp).to_rfc3339()
+    ));
+    out.push_str(&format!(
+        "- Files: {}\n- Successful replacements: {}\n- Failed replacements: {}\n- Duration: {} ms\n",
+        result.total_files,
+        result.total_successful_replacements,
+        result.total_failed_replacements,
+        result.duration.as_millis()
+    ));
+    if !result.skipped_read_only.is_empty() {
+        out.push_str(&format!(
+            "- Skipped (read-only): {}\n",
+            result.skipped_read_only.len()
+        ));
+    }
+    out.push('\n');
+
+    out.push_str("| File | Total | Successful | Failed |\n");
+    out.push_str("| --- | ---: | ---: | ---: |\n");
+    for file in &result.results {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            escape_cell(&file.file_path),
+            file.total_replacements,
+            file.successful_replacements,
+            file.failed_replacements.len()
+        ));
+    }
+
+    if !result.skipped_read_only.is_empty() {
+        out.push_str("\n## Skipped read-only files\n\n");
+        out.push_str("| File | Error |\n");
+        out.push_str("| --- | --- |\n");
+        for file in &result.skipped_read_only {
+            out.push_str(&format!(
+                "| {} | {} |\n",
+                escape_cell(&file.file_path),
+                escape_cell(&file.error)
+            ));
+        }
+    }
+
+    let failures: Vec<_> = result
+        .results
+        .iter()
+        .flat_map(|file| file.failed_replacements.iter())
+        .collect();
+    if !failures.is_empty() {
+        out.push_str("\n## Failed replacements\n\n");
+        out.push_str("| File | Line | Column | Old link | New link | Error |\n");
+        out.push_str("| --- | ---: | ---: | --- | --- | --- |\n");
+        for failure in failures {
+            let replacement = &failure.replacement;
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                escape_cell(&replacement.file_path),
+                replacement.line,
+                replacement.column,
+                escape_cell(&replacement.old_link),
+                escape_cell(&replacement.new_link),
+                escape_cell(&failure.error)
+            ));
+        }
+    }
+
+    let warnings: Vec<_> = result
+        .results
+        .iter()
+        .flat_map(|file| file.warnings.iter().map(move |warning| (file, warning)))
+        .collect();
+    if !warnings.is_empty() {
+        out.push_str("\n## Verification warnings\n\n");
+        out.push_str("| File | Line | Warning | Restored |\n");
+        out.push_str("| --- | ---: | --- | --- |\n");
+        for (file, warning) in warnings {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                escape_cell(&file.file_path),
+                warning
+                    .line
+                    .map(|line| line.to_string())
+                    .unwrap_or_default(),
+                escape_cell(&warning.message),
+                if file.restored { "yes" } else { "no" }
+            ));
+        }
+    }
+
+    out
+}
+
+/// Render a scan report in the requested format
+pub fn render_scan_report(report: &ScanReport, format: ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(report).map_err(AppError::Serialization),
+        ReportFormat::Markdown => Ok(report.to_markdown()),
+    }
+}
+
+/// Render a local duplicate report in the requested format
+pub fn render_duplicate_report(
+    report: &LocalDuplicateReport,
+    format: ReportFormat,
+) -> Result<String> {
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(report).map_err(AppError::Serialization),
+        ReportFormat::Markdown => Ok(duplicate_report_markdown(report)),
+    }
+}
+
+fn duplicate_report_markdown(report: &LocalDuplicateReport) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "# imgtoss duplicate image report
+
+",
+    );
+    out.push_str(&format!(
+        "Generated: {}
+
+Directory: `{}`{}
+
+",
+        report.generated_at.to_rfc3339(),
+        report.directory,
+        if report.recursive { " (recursive)" } else { "" }
+    ));
+    out.push_str(&format!(
+        "- Images scanned: {}
+- Images hashed: {}
+- Duplicate groups: {}
+- Wasted space: {}
+",
+        report.files_scanned,
+        report.files_hashed,
+        report.groups.len(),
+        format_size(report.wasted_bytes)
+    ));
+    if report.cancelled {
+        out.push_str(
+            "- Cancelled before every image was hashed
+",
+        );
+    }
+
+    for (index, group) in report.groups.iter().enumerate() {
+        out.push_str(&format!(
+            "
+## Group {}: {} copies of {} ({} wasted)
+
+",
+            index + 1,
+            group.files.len(),
+            format_size(group.size),
+            format_size(group.wasted_bytes)
+        ));
+        out.push_str(&format!(
+            "Checksum: `{}`
+
+",
+            group.checksum
+        ));
+        out.push_str(
+            "| File | Markdown references |
+",
+        );
+        out.push_str(
+            "| --- | ---: |
+",
+        );
+        for file in &group.files {
+            let usage = file
+                .usage_count
+                .map(|count| count.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            out.push_str(&format!(
+                "| {} | {} |
+",
+                escape_cell(&file.path),
+                usage
+            ));
+        }
+    }
+
+    if !report.unreadable_files.is_empty() {
+        out.push_str(
+            "
+## Unreadable files
+
+",
+        );
+        for file in &report.unreadable_files {
+            out.push_str(&format!(
+                "- {}
+",
+                file
+            ));
+        }
+    }
+
+    out
+}
+
+// ============================================================================
+// Upload history gallery
+// ============================================================================
+
+const GALLERY_STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2rem;color:#222;background:#fafafa}\
+h1{font-size:1.4rem}\
+.meta{color:#666;font-size:.9rem}\
+.grid{display:grid;grid-template-columns:repeat(auto-fill,minmax(220px,1fr));gap:1rem}\
+.card{margin:0;padding:.75rem;background:#fff;border:1px solid #ddd;border-radius:6px}\
+.card img{width:100%;height:160px;object-fit:contain;background:#f0f0f0}\
+.card figcaption{font-size:.85rem;margin-top:.5rem;word-break:break-all}\
+.card input{width:100%;box-sizing:border-box;margin-top:.4rem;font-size:.8rem}";
+
+/// Render upload records as a self-contained gallery page, newest first.
+///
+/// `thumbnails` maps record ids to an image source to show instead of the
+/// uploaded URL, e.g. a data URI of the cached thumbnail.
+pub fn render_history_gallery(
+    records: &[UploadHistoryRecord],
+    thumbnails: &HashMap<String, String>,
+    format: GalleryFormat,
+) -> String {
+    let mut sorted: Vec<&UploadHistoryRecord> = records.iter().collect();
+    sorted.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    match format {
+        GalleryFormat::Markdown => gallery_markdown(&sorted, thumbnails),
+        GalleryFormat::Html => gallery_html(&sorted, thumbnails),
+    }
+}
+
+fn gallery_markdown(
+    records: &[&UploadHistoryRecord],
+    thumbnails: &HashMap<String, String>,
+) -> String {
+    let mut out = String::new();
+    out.push_str("# imgtoss upload gallery\n\n");
+    out.push_str(&format!(
+        "Generated: {}\n\n- Images: {}\n",
+        chrono::Utc::now().to_rfc3339(),
+        records.len()
+    ));
+
+    // 按月分节
+    let mut current_month = String::new();
+    for record in records {
+        let month = record.timestamp.format("%Y-%m").to_string();
+        if month != current_month {
+            out.push_str(&format!("\n## {}\n\n", month));
+            out.push_str("| Preview | File | Uploaded | Size | URL |\n");
+            out.push_str("| --- | --- | --- | ---: | --- |\n");
+            current_month = month;
+        }
+        let src = thumbnails.get(&record.id).unwrap_or(&record.uploaded_url);
+        out.push_str(&format!(
+            "| ![{}](<{}>) | {} | {} | {} | `{}` |\n",
+            escape_cell(&record.image_name.replace(['[', ']'], "")),
+            src,
+            escape_cell(&record.image_name),
+            record.timestamp.format("%Y-%m-%d %H:%M"),
+            format_size(record.file_size),
+            escape_cell(&record.uploaded_url)
+        ));
+    }
+
+    out
+}
+
+fn gallery_html(records: &[&UploadHistoryRecord], thumbnails: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<title>imgtoss upload gallery</title>\n");
+    out.push_str(&format!("<style>{}</style>\n", GALLERY_STYLE));
+    out.push_str("</head>\n<body>\n<h1>imgtoss upload gallery</h1>\n");
+    out.push_str(&format!(
+        "<p class=\"meta\">Generated {} &middot; {} images</p>\n",
+        chrono::Utc::now().to_rfc3339(),
+        records.len()
+    ));
+
+    out.push_str("<div class=\"grid\">\n");
+    for record in records {
+        let src = thumbnails.get(&record.id).unwrap_or(&record.uploaded_url);
+        out.push_str(&format!(
+            "<figure class=\"card\">\
+<a href=\"{url}\"><img src=\"{src}\" alt=\"{name}\" loading=\"lazy\"></a>\
+<figcaption><strong>{name}</strong><br>{date} &middot; {size}\
+<input type=\"text\" readonly value=\"{url}\" onfocus=\"this.select()\"></figcaption>\
+</figure>\n",
+            url = escape_html(&record.uploaded_url),
+            src = escape_html(src),
+            name = escape_html(&record.image_name),
+            date = record.timestamp.format("%Y-%m-%d %H:%M"),
+            size = format_size(record.file_size),
+        ));
+    }
+    out.push_str("</div>\n</body>\n</html>\n");
+
+    out
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Paths of the JSON and Markdown files written for a replacement report
+pub fn replacement_report_paths(report_path: &str) -> (PathBuf, PathBuf) {
+    let path = Path::new(report_path);
+    (path.with_extension("json"), path.with_extension("md"))
+}
+
+/// Write a report file; callers validate the target first
+pub fn write_report(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content).map_err(|e| {
+        AppError::FileSystem(format!("Failed to write report {}: {}", path.display(), e))
+    })
+}
+
+// ============================================================================
+// Replacement changelog
+// ============================================================================
+
+/// File name of a replacement changelog when no path is given
+pub const CHANGELOG_FILE_NAME: &str = ".imgtoss-changes.json";
+
+/// `.imgtoss-changes.json` in the root of the git repository `file_path` is in,
+/// or next to the file when it isn't in one
+pub fn default_changelog_path(file_path: &Path) -> PathBuf {
+    let dir = file_path.parent().unwrap_or(Path::new("."));
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir)
+        .join(CHANGELOG_FILE_NAME)
+}
+
+/// Every change written by a batch replacement, in file order
+pub fn replacement_changelog(result: &BatchReplacementResult) -> ReplacementChangelog {
+    ReplacementChangelog {
+        generated_at: chrono::DateTime::<chrono::Utc>::from(result.timestamp),
+        changes: result
+            .results
+            .iter()
+            .flat_map(|file| file.changes.iter().cloned())
+            .collect(),
+    }
+}
+
+/// Fill in the upload each new link points at. A record matches when its URL is
+/// the new link, ignoring a ?query/#fragment kept from the old link; the object
+/// key needs the saved config the record was uploaded with.
+pub fn annotate_changes(
+    changes: &mut [ReplacementChange],
+    records: &[UploadHistoryRecord],
+    configs: &[ConfigItem],
+) {
+    for change in changes {
+        let Some(record) = records.iter().find(|record| {
+            record.success
+                && !record.uploaded_url.is_empty()
+                && change
+                    .new_link
+                    .strip_prefix(record.uploaded_url.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['?', '#']))
+        }) else {
+            continue;
+        };
+
+        change.uploaded_url = Some(record.uploaded_url.clone());
+        change.history_record_id = Some(record.id.clone());
+        change.object_key = record.object_key.clone().or_else(|| {
+            record
+                .config_id
+                .as_ref()
+                .and_then(|id| configs.iter().find(|item| &item.id == id))
+                .and_then(|item| object_key_from_url(&item.config, &record.uploaded_url))
+        });
+    }
+}
+
+/// Write a replacement changelog, replacing any earlier one at `path`
+pub fn write_changelog(path: &Path, changelog: &ReplacementChangelog) -> Result<()> {
+    let json = serde_json::to_string_pretty(changelog).map_err(AppError::Serialization)?;
+    write_report(path, &json)
+}
+
+pub fn read_changelog(path: &Path) -> Result<ReplacementChangelog> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        AppError::FileSystem(format!(
+            "Failed to read changelog {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    serde_json::from_str(&content).map_err(AppError::Serialization)
+}
+
+// Keep table layout intact when a cell contains pipes or newlines
+fn escape_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        ImageReference, LinkContext, LinkReplacement, LocalDuplicateFile, LocalDuplicateGroup,
+        OSSConfig, OSSProvider, ObjectAcl, ReplacementError, ReplacementResult, ReplacementWarning,
+        ReplacementWarningCode, UnwritableFile, UploadMode,
+    };
+    use std::time::{Duration, SystemTime};
+
+    fn image(path: &str, exists: bool, line: usize) -> ImageReference {
+        let mut image = ImageReference::new(path.to_string(), format!("/docs/{}", path), line, 3);
+        image.exists = exists;
+        image
+    }
+
+    fn scan_results() -> Vec<ScanResult> {
+        vec![
+            ScanResult {
+                file_path: "/docs/a.md".to_string(),
+                images: vec![image("one.png", true, 2), image("gone.png", false, 7)],
+                status: ScanStatus::Success,
+                error: None,
+                missing_images: vec![],
+                unique_image_count: 0,
+                warnings: vec![],
+                encoding: None,
+                content_hash: None,
+            },
+            ScanResult {
+                file_path: "/docs/b|c.md".to_string(),
+                images: vec![],
+                status: ScanStatus::Error,
+                error: Some("permission denied".to_string()),
+                missing_images: vec![],
+                unique_image_count: 0,
+                warnings: vec![],
+                encoding: None,
+                content_hash: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_scan_report_counts_and_missing_lines() {
+        let report = ScanReport::from_results(&scan_results());
+        assert_eq!(report.total_files, 2);
+        assert_eq!(report.total_images, 2);
+        assert_eq!(report.total_missing, 1);
+        assert_eq!(report.missing_images[0].line, 7);
+        assert_eq!(report.files[0].missing_count, 1);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("| /docs/a.md | 2 | 1 | ok |"));
+        assert!(markdown.contains("| /docs/b\\|c.md | 0 | 0 | error: permission denied |"));
+        assert!(markdown.contains("| /docs/a.md | 7 | 3 | gone.png |"));
+
+        let json = render_scan_report(&report, ReportFormat::Json).unwrap();
+        let parsed: ScanReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.total_missing, 1);
+    }
+
+    #[test]
+    fn test_duplicate_report_lists_groups_and_usage() {
+        let report = LocalDuplicateReport {
+            directory: "/docs".to_string(),
+            recursive: true,
+            generated_at: chrono::Utc::now(),
+            files_scanned: 5,
+            files_hashed: 3,
+            bytes_hashed: 6144,
+            groups: vec![LocalDuplicateGroup {
+                checksum: "abc123".to_string(),
+                size: 2048,
+                files: vec![
+                    LocalDuplicateFile {
+                        path: "/docs/a|b.png".to_string(),
+                        usage_count: Some(2),
+                    },
+                    LocalDuplicateFile {
+                        path: "/docs/copy.png".to_string(),
+                        usage_count: None,
+                    },
+                ],
+                wasted_bytes: 2048,
+            }],
+            wasted_bytes: 2048,
+            unreadable_files: vec!["/docs/locked.png: Permission denied".to_string()],
+            cancelled: false,
+        };
+
+        let markdown = render_duplicate_report(&report, ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains("- Wasted space: 2.0 KB"));
+        assert!(markdown.contains("## Group 1: 2 copies of 2.0 KB (2.0 KB wasted)"));
+        assert!(markdown.contains("| /docs/a\\|b.png | 2 |"));
+        assert!(markdown.contains("| /docs/copy.png | - |"));
+        assert!(markdown.contains("- /docs/locked.png: Permission denied"));
+
+        let json = render_duplicate_report(&report, ReportFormat::Json).unwrap();
+        let parsed: LocalDuplicateReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.groups[0].files[0].usage_count, Some(2));
+    }
+
+    #[test]
+    fn test_replacement_report_keeps_errors_verbatim() {
+        let replacement = LinkReplacement {
+            file_path: "/docs/a.md".to_string(),
+            line: 4,
+            column: 1,
+            old_link: "./x.png".to_string(),
+            new_link: "https://cdn.example.com/x.png".to_string(),
+            context: LinkContext::MarkdownInline,
+        };
+        let result = BatchReplacementResult {
+            results: vec![ReplacementResult {
+                file_path: "/docs/a.md".to_string(),
+                total_replacements: 2,
+                successful_replacements: 1,
+                failed_replacements: vec![ReplacementError {
+                    replacement,
+                    error: "Link not found at line 4".to_string(),
+                }],
+                duration: SystemTime::now(),
+                changes: vec![],
+                encoding: None,
+                converted_to_utf8: false,
+                warnings: vec![ReplacementWarning {
+                    code: ReplacementWarningCode::LineChanged,
+                    message: "Line differs from the original outside the replaced links"
+                        .to_string(),
+                    line: Some(7),
+                }],
+                restored: true,
+            }],
+            total_files: 1,
+            total_successful_replacements: 1,
+            total_failed_replacements: 1,
+            duration: Duration::from_millis(12),
+            timestamp: SystemTime::now(),
+            skipped_read_only: vec![UnwritableFile {
+                file_path: "/docs/locked.md".to_string(),
+                error: "Permission denied".to_string(),
+            }],
+            backups: vec![],
+        };
+
+        let markdown = replacement_report_markdown(&result);
+        assert!(markdown.contains("| /docs/a.md | 2 | 1 | 1 |"));
+        assert!(markdown.contains(
+            "| /docs/a.md | 4 | 1 | ./x.png | https://cdn.example.com/x.png | Link not found at line 4 |"
+        ));
+        assert!(markdown.contains("- Skipped (read-only): 1"));
+        assert!(markdown.contains("| /docs/locked.md | Permission denied |"));
+        assert!(markdown.contains(
+            "| /docs/a.md | 7 | Line differs from the original outside the replaced links | yes |"
+        ));
+    }
+
+    fn upload_record(id: &str, name: &str, timestamp: &str) -> UploadHistoryRecord {
+        UploadHistoryRecord {
+            id: id.to_string(),
+            timestamp: chrono::DateTime::parse_from_rfc3339(timestamp)
+                .unwrap()
+                .with_timezone(&chrono::Utc),
+            image_name: name.to_string(),
+            uploaded_url: format!("https://cdn.example.com/{}", name),
+            upload_mode: UploadMode::ImageUpload,
+            source_file: None,
+            file_size: 2048,
+            checksum: String::new(),
+            variants: vec![],
+            config_id: None,
+            bucket: None,
+            original_path: None,
+            success: true,
+            error_message: None,
+            request_id: None,
+            original_archived: false,
+            object_key: None,
+        }
+    }
+
+    #[test]
+    fn test_history_gallery_markdown_groups_by_month() {
+        let records = vec![
+            upload_record("1", "old.png", "2026-08-30T10:00:00Z"),
+            upload_record("2", "new.png", "2026-09-02T10:00:00Z"),
+            upload_record("3", "mid|dle.png", "2026-09-01T10:00:00Z"),
+        ];
+        let thumbnails =
+            HashMap::from([("2".to_string(), "data:image/jpeg;base64,AAAA".to_string())]);
+
+        let markdown = render_history_gallery(&records, &thumbnails, GalleryFormat::Markdown);
+        let september = markdown.find("## 2026-09").unwrap();
+        let august = markdown.find("## 2026-08").unwrap();
+        assert!(september < august);
+        assert!(markdown.contains(
+            "| ![new.png](<data:image/jpeg;base64,AAAA>) | new.png | 2026-09-02 10:00 | 2.0 KB | `https://cdn.example.com/new.png` |"
+        ));
+        assert!(markdown.contains("| mid\\|dle.png |"));
+        assert!(markdown.find("new.png").unwrap() < markdown.find("mid").unwrap());
+    }
+
+    #[test]
+    fn test_history_gallery_html_is_escaped_and_lazy() {
+        let records = vec![upload_record("1", "<script>.png", "2026-09-02T10:00:00Z")];
+
+        let html = render_history_gallery(&records, &HashMap::new(), GalleryFormat::Html);
+        assert!(html.contains("<style>"));
+        assert!(html.contains("loading=\"lazy\""));
+        assert!(html.contains("src=\"https://cdn.example.com/&lt;script&gt;.png\""));
+        assert!(!html.contains("<script>"));
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+
+    fn change(new_link: &str) -> ReplacementChange {
+        ReplacementChange {
+            file_path: "/repo/docs/a.md".to_string(),
+            line: 3,
+            column: 5,
+            old_link: "./a.png".to_string(),
+            new_link: new_link.to_string(),
+            uploaded_url: None,
+            object_key: None,
+            history_record_id: None,
+            replaced_text: "./a.png".to_string(),
+            written_text: new_link.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_annotate_changes_from_history() {
+        let config = OSSConfig {
+            provider: OSSProvider::Custom,
+            endpoint: "https://s3.example.com".to_string(),
+            access_key_id: "id".to_string(),
+            access_key_secret: "secret".to_string(),
+            bucket: "bucket".to_string(),
+            region: "auto".to_string(),
+            path_template: "images/{filename}".to_string(),
+            cdn_domain: Some("cdn.example.com".to_string()),
+            compression_enabled: false,
+            compression_quality: 80,
+            output_format: None,
+            max_dimension: None,
+            strip_metadata: false,
+            convert_to_srgb: false,
+            object_acl: ObjectAcl::Default,
+            max_upload_size_kb: None,
+            size_variants: Vec::new(),
+        };
+        let configs = vec![ConfigItem {
+            id: "cfg".to_string(),
+            name: "cdn".to_string(),
+            config,
+            is_active: true,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }];
+        let mut saved = upload_record("r1", "images/a.png", "2026-09-02T10:00:00Z");
+        saved.config_id = Some("cfg".to_string());
+        let unsaved = upload_record("r2", "b.png", "2026-09-01T10:00:00Z");
+
+        let mut changes = vec![
+            change("https://cdn.example.com/images/a.png#dark"),
+            change("https://cdn.example.com/b.png"),
+            change("https://cdn.example.com/b.png2"),
+        ];
+        annotate_changes(&mut changes, &[saved, unsaved], &configs);
+
+        assert_eq!(changes[0].history_record_id.as_deref(), Some("r1"));
+        assert_eq!(
+            changes[0].uploaded_url.as_deref(),
+            Some("https://cdn.example.com/images/a.png")
+        );
+        assert_eq!(changes[0].object_key.as_deref(), Some("images/a.png"));
+        // No saved config, no key
+        assert_eq!(changes[1].history_record_id.as_deref(), Some("r2"));
+        assert_eq!(changes[1].object_key, None);
+        assert_eq!(changes[2].history_record_id, None);
+    }
+
+    #[test]
+    fn test_default_changelog_path_uses_repo_root() {
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(repo.path().join(".git")).unwrap();
+        std::fs::create_dir_all(repo.path().join("docs/posts")).unwrap();
+
+        assert_eq!(
+            default_changelog_path(&repo.path().join("docs/posts/a.md")),
+            repo.path().join(CHANGELOG_FILE_NAME)
+        );
+
+        let loose = tempfile::tempdir().unwrap();
+        assert_eq!(
+            default_changelog_path(&loose.path().join("a.md")),
+            loose.path().join(CHANGELOG_FILE_NAME)
+        );
+    }
+
+    #[test]
+    fn test_replacement_report_paths() {
+        let (json, markdown) = replacement_report_paths("/tmp/report");
+        assert_eq!(json, PathBuf::from("/tmp/report.json"));
+        assert_eq!(markdown, PathBuf::from("/tmp/report.md"));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::utils::{AppError, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// One list of glob patterns, compiled. Patterns are matched against absolute
+/// paths with `/` separators: `*` stays within one path segment and `**` spans
+/// any number. A pattern that doesn't start with `/`, `**` or a drive letter
+/// matches at any depth (`drafts/**` is `**/drafts/**`), and a pattern naming
+/// a directory covers everything below it (`node_modules`).
+struct GlobList {
+    patterns: Vec<String>,
+    set: GlobSet,
+    // Index into `patterns` for every glob in `set`
+    owners: Vec<usize>,
+}
+
+impl GlobList {
+    fn new(setting: &str, patterns: &[String]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        let mut owners = Vec::new();
+        for (index, pattern) in patterns.iter().enumerate() {
+            let anchored = pattern.trim().replace('\\', "/");
+            let anchored = if anchored.starts_with('/')
+                || anchored.starts_with("**")
+                || anchored.get(1..2) == Some(":")
+            {
+                anchored
+            } else {
+                format!("**/{}", anchored)
+            };
+            for glob in [
+                anchored.clone(),
+                format!("{}/**", anchored.trim_end_matches('/')),
+            ] {
+                let glob = GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| {
+                        AppError::Validation(format!(
+                            "Invalid pattern `{}` in {}: {}",
+                            pattern,
+                            setting,
+                            e.kind()
+                        ))
+                    })?;
+                builder.add(glob);
+                owners.push(index);
+            }
+        }
+        let set = builder
+            .build()
+            .map_err(|e| AppError::Validation(format!("Invalid {}: {}", setting, e)))?;
+        Ok(Self {
+            patterns: patterns.to_vec(),
+            set,
+            owners,
+        })
+    }
+
+    /// The first pattern matching `path`
+    fn first_match(&self, path: &str) -> Option<&str> {
+        let path = path.replace('\\', "/");
+        self.set
+            .matches(&path)
+            .first()
+            .map(|&glob| self.patterns[self.owners[glob]].as_str())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+/// Include/exclude rules a scan applies to image references and to the
+/// markdown files themselves, see `AppSettings::include_image_globs`
+pub struct ScanGlobs {
+    include_images: GlobList,
+    exclude_images: GlobList,
+    exclude_files: GlobList,
+    /// `AppSettings::allowed_extensions`, empty to keep every extension
+    image_extensions: Vec<String>,
+}
+
+impl ScanGlobs {
+    pub fn new(
+        include_images: &[String],
+        exclude_images: &[String],
+        exclude_files: &[String],
+    ) -> Result<Self> {
+        Ok(Self {
+            include_images: GlobList::new("include_image_globs", include_images)?,
+            exclude_images: GlobList::new("exclude_image_globs", exclude_images)?,
+            exclude_files: GlobList::new("exclude_file_globs", exclude_files)?,
+            image_extensions: Vec::new(),
+        })
+    }
+
+    /// Also leave out images whose extension isn't one of `extensions`
+    pub fn with_image_extensions(mut self, extensions: &[String]) -> Self {
+        self.image_extensions = extensions.to_vec();
+        self
+    }
+
+    /// Why the image at `absolute_path` is left out of the scan, None to keep it.
+    /// Excludes win over includes; with no includes every image is included.
+    pub fn image_skip_reason(&self, absolute_path: &str) -> Option<String> {
+        if let Some(pattern) = self.exclude_images.first_match(absolute_path) {
+            return Some(format!("Matches exclude_image_globs pattern `{}`", pattern));
+        }
+        if let Some(ext) = Path::new(absolute_path).extension() {
+            let ext = ext.to_string_lossy().to_lowercase();
+            if !self.image_extensions.is_empty() && !self.image_extensions.contains(&ext) {
+                return Some(format!(".{} is not one of the allowed_extensions", ext));
+            }
+        }
+        if !self.include_images.is_empty()
+            && self.include_images.first_match(absolute_path).is_none()
+        {
+            return Some("Matches none of the include_image_globs patterns".to_string());
+        }
+        None
+    }
+
+    /// Why the markdown file at `file_path` is not scanned, None to scan it
+    pub fn file_skip_reason(&self, file_path: &str) -> Option<String> {
+        self.exclude_files
+            .first_match(file_path)
+            .map(|pattern| format!("Matches exclude_file_globs pattern `{}`", pattern))
+    }
+}
+
+impl Default for ScanGlobs {
+    fn default() -> Self {
+        Self::new(&[], &[], &[]).expect("empty glob lists always compile")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(include: &[&str], exclude: &[&str], files: &[&str]) -> ScanGlobs {
+        let list = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        ScanGlobs::new(&list(include), &list(exclude), &list(files)).unwrap()
+    }
+
+    #[test]
+    fn test_relative_patterns_match_at_any_depth() {
+        let globs = globs(&[], &["drafts/**", "node_modules", "*.gif"], &[]);
+        assert!(globs
+            .image_skip_reason("/home/me/blog/drafts/a/b.png")
+            .unwrap()
+            .contains("`drafts/**`"));
+        assert!(globs
+            .image_skip_reason("/home/me/blog/theme/node_modules/demo/c.png")
+            .unwrap()
+            .contains("`node_modules`"));
+        assert!(globs.image_skip_reason("/home/me/blog/anim.gif").is_some());
+        assert!(globs
+            .image_skip_reason("/home/me/blog/posts/a.png")
+            .is_none());
+        // `*` does not cross directories, but relative patterns match anywhere
+        assert!(globs.image_skip_reason("/home/me/drafts.png").is_none());
+        assert!(globs.image_skip_reason(r"C:\blog\drafts\a.png").is_some());
+    }
+
+    #[test]
+    fn test_includes_and_excludes() {
+        let globs = globs(
+            &["/home/me/blog/images/**"],
+            &["**/private/**"],
+            &["**/README.md"],
+        );
+        assert!(globs
+            .image_skip_reason("/home/me/blog/images/a.png")
+            .is_none());
+        assert!(globs
+            .image_skip_reason("/home/me/blog/static/a.png")
+            .unwrap()
+            .contains("include_image_globs"));
+        // Excludes win
+        assert!(globs
+            .image_skip_reason("/home/me/blog/images/private/a.png")
+            .unwrap()
+            .contains("exclude_image_globs"));
+
+        assert!(globs.file_skip_reason("/home/me/blog/README.md").is_some());
+        assert!(globs.file_skip_reason("/home/me/blog/post.md").is_none());
+        assert!(ScanGlobs::default()
+            .image_skip_reason("/anything.png")
+            .is_none());
+    }
+
+    #[test]
+    fn test_allowed_extensions_filter_images() {
+        let globs = globs(&[], &[], &[]).with_image_extensions(&["png".to_string()]);
+        assert!(globs.image_skip_reason("/blog/a.PNG").is_none());
+        assert!(globs
+            .image_skip_reason("/blog/anim.gif")
+            .unwrap()
+            .contains(".gif is not one of the allowed_extensions"));
+        // Without an extension there's nothing to check
+        assert!(globs.image_skip_reason("/blog/image").is_none());
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_rejected() {
+        let err = ScanGlobs::new(&[], &["images/[a".to_string()], &[])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("exclude_image_globs"));
+        assert!(err.contains("images/[a"));
+    }
+}
@@ -0,0 +1,320 @@
+use crate::log_debug;
+use crate::models::ThumbnailFormat;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Thumbnails kept in memory, a few screens of review thumbnails
+const MEMORY_CACHE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Resizes allowed at once when the setting is 0: half the cores, so the UI
+/// thread and the webview keep some
+pub fn default_thumbnail_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|cores| cores.get() / 2)
+        .unwrap_or(1)
+        .max(1)
+}
+
+/// A thumbnail of one version of a file. A rewritten file gets a new mtime,
+/// and a new length catches rewrites on filesystems with coarse timestamps.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThumbnailKey {
+    path: PathBuf,
+    modified: SystemTime,
+    len: u64,
+    size: u32,
+    format: ThumbnailFormat,
+}
+
+impl ThumbnailKey {
+    /// Key for `path` as it is on disk now, None when it can't be stat'ed
+    pub fn for_file(path: &Path, size: u32, format: ThumbnailFormat) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            path: path.to_path_buf(),
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+            size,
+            format,
+        })
+    }
+}
+
+struct CachedThumbnail {
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    entries: HashMap<ThumbnailKey, CachedThumbnail>,
+    bytes: usize,
+    // Bumped on every access, orders the entries for eviction
+    clock: u64,
+}
+
+/// Hit/miss counters and size of the in-memory thumbnail cache
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+/// Shared by every generate_thumbnail call: a semaphore bounding how many
+/// decodes and resizes run at once, and an LRU of recent results so a screen
+/// that renders again doesn't redo its thumbnails.
+pub struct ThumbnailWork {
+    // The configured permit count with its semaphore; replaced when the setting
+    // changes, calls already holding a permit of the old one finish normally
+    limiter: Mutex<(usize, Arc<Semaphore>)>,
+    cache: Mutex<MemoryCache>,
+    capacity_bytes: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ThumbnailWork {
+    pub fn new(permits: usize, capacity_bytes: usize) -> Self {
+        let permits = permits.max(1);
+        Self {
+            limiter: Mutex::new((permits, Arc::new(Semaphore::new(permits)))),
+            cache: Mutex::new(MemoryCache::default()),
+            capacity_bytes,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Apply the thumbnail_concurrency setting, 0 picks the default
+    pub fn set_concurrency(&self, permits: usize) {
+        let permits = if permits == 0 {
+            default_thumbnail_concurrency()
+        } else {
+            permits
+        };
+        let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+        if limiter.0 != permits {
+            *limiter = (permits, Arc::new(Semaphore::new(permits)));
+        }
+    }
+
+    /// Wait for a free slot, held until the returned permit is dropped
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        let semaphore = self
+            .limiter
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .1
+            .clone();
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("thumbnail semaphore is never closed")
+    }
+
+    /// Cached thumbnail for `key`, counted as a hit or a miss
+    pub fn get(&self, key: &ThumbnailKey) -> Option<Vec<u8>> {
+        let data = self.peek(key);
+        let counter = if data.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        data
+    }
+
+    /// Cached thumbnail for `key` without touching the counters, for the second
+    /// look after waiting on the semaphore
+    pub fn peek(&self, key: &ThumbnailKey) -> Option<Vec<u8>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.clock += 1;
+        let now = cache.clock;
+        cache.entries.get_mut(key).map(|cached| {
+            cached.last_used = now;
+            cached.data.clone()
+        })
+    }
+
+    pub fn insert(&self, key: ThumbnailKey, data: &[u8]) {
+        // One huge thumbnail would push out everything else
+        if data.len() > self.capacity_bytes / 4 {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Older versions of the file won't be asked for again
+        let stale: Vec<ThumbnailKey> = cache
+            .entries
+            .keys()
+            .filter(|k| k.path == key.path && (k.modified != key.modified || k.len != key.len))
+            .cloned()
+            .collect();
+        for stale_key in stale {
+            if let Some(removed) = cache.entries.remove(&stale_key) {
+                cache.bytes -= removed.data.len();
+            }
+        }
+
+        while cache.bytes + data.len() > self.capacity_bytes {
+            let Some(oldest) = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(removed) = cache.entries.remove(&oldest) {
+                cache.bytes -= removed.data.len();
+            }
+        }
+
+        cache.clock += 1;
+        let last_used = cache.clock;
+        cache.bytes += data.len();
+        if let Some(replaced) = cache.entries.insert(
+            key,
+            CachedThumbnail {
+                data: data.to_vec(),
+                last_used,
+            },
+        ) {
+            cache.bytes -= replaced.data.len();
+        }
+        log_debug!(
+            operation = "thumbnail_memory_cache",
+            entries = cache.entries.len(),
+            bytes = cache.bytes,
+            "Thumbnail added to memory cache"
+        );
+    }
+
+    pub fn stats(&self) -> MemoryCacheStats {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        MemoryCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: cache.entries.len(),
+            bytes: cache.bytes,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref THUMBNAIL_WORK: ThumbnailWork =
+        ThumbnailWork::new(default_thumbnail_concurrency(), MEMORY_CACHE_BYTES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn key(dir: &TempDir, name: &str, size: u32) -> ThumbnailKey {
+        let path = dir.path().join(name);
+        if !path.exists() {
+            std::fs::write(&path, b"image").unwrap();
+        }
+        ThumbnailKey::for_file(&path, size, ThumbnailFormat::Jpeg).unwrap()
+    }
+
+    #[test]
+    fn test_hits_and_misses_are_counted() {
+        let dir = TempDir::new().unwrap();
+        let work = ThumbnailWork::new(1, 1024);
+        let a = key(&dir, "a.png", 100);
+
+        assert!(work.get(&a).is_none());
+        work.insert(a.clone(), &[1, 2, 3]);
+        assert_eq!(work.get(&a), Some(vec![1, 2, 3]));
+        // Other size, other entry
+        assert!(work.get(&key(&dir, "a.png", 200)).is_none());
+
+        let stats = work.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+        assert_eq!((stats.entries, stats.bytes), (1, 3));
+    }
+
+    #[test]
+    fn test_rewritten_file_misses_and_drops_old_version() {
+        let dir = TempDir::new().unwrap();
+        let work = ThumbnailWork::new(1, 1024);
+        let before = key(&dir, "a.png", 100);
+        work.insert(before.clone(), &[1]);
+
+        let path = dir.path().join("a.png");
+        std::fs::write(&path, b"edited image").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        let after = ThumbnailKey::for_file(&path, 100, ThumbnailFormat::Jpeg).unwrap();
+
+        assert_ne!(before, after);
+        assert!(work.get(&after).is_none());
+        work.insert(after.clone(), &[2]);
+        assert!(work.peek(&before).is_none());
+        assert_eq!(work.stats().entries, 1);
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted_over_capacity() {
+        let dir = TempDir::new().unwrap();
+        let work = ThumbnailWork::new(1, 40);
+        let (a, b, c) = (
+            key(&dir, "a.png", 10),
+            key(&dir, "b.png", 10),
+            key(&dir, "c.png", 10),
+        );
+        work.insert(a.clone(), &[0; 10]);
+        work.insert(b.clone(), &[0; 10]);
+        work.peek(&a);
+        work.insert(c.clone(), &[0; 10]);
+        work.insert(key(&dir, "d.png", 10), &[0; 10]);
+        work.insert(key(&dir, "e.png", 10), &[0; 10]);
+
+        assert!(work.peek(&b).is_none());
+        assert!(work.peek(&a).is_some());
+        assert!(work.stats().bytes <= 40);
+        // Too large for this cache
+        work.insert(key(&dir, "f.png", 10), &[0; 11]);
+        assert!(work.peek(&key(&dir, "f.png", 10)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_is_bounded_by_permits() {
+        let work = Arc::new(ThumbnailWork::new(2, 1024));
+        let running = Arc::new(AtomicU64::new(0));
+        let peak = Arc::new(AtomicU64::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let (work, running, peak) = (work.clone(), running.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let _permit = work.acquire().await;
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        work.set_concurrency(0);
+        assert_eq!(
+            work.limiter.lock().unwrap().0,
+            default_thumbnail_concurrency()
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::models::StorageMode;
+use crate::utils::{AppError, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const APP_DIR_NAME: &str = "imgtoss";
+
+/// Presence of this file next to the executable turns on portable mode
+pub const PORTABLE_FLAG_FILE: &str = "portable.flag";
+/// Same as the flag file, for a single run
+pub const PORTABLE_ARG: &str = "--portable";
+const PORTABLE_DATA_DIR: &str = "data";
+
+/// Kept in the default config directory, points at a custom data directory
+const BOOTSTRAP_FILE: &str = "location.json";
+
+// Not carried over when moving to a custom data directory: caches and
+// per-machine files
+const NOT_MIGRATED: [&str; 3] = [BOOTSTRAP_FILE, "thumbnails", "logs"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Bootstrap {
+    #[serde(default)]
+    data_dir_override: Option<PathBuf>,
+}
+
+/// The directories every service stores its files in.
+///
+/// Services ask this module instead of calling `dirs::*` themselves, so that
+/// portable mode and a custom data directory move everything at once.
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    pub mode: StorageMode,
+    config_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+}
+
+impl AppPaths {
+    /// Paths for this process, from the executable location, arguments and
+    /// bootstrap file
+    fn detect() -> Self {
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        let portable_arg = std::env::args().any(|arg| arg == PORTABLE_ARG);
+        let data_dir_override = bootstrap_file().and_then(|file| read_bootstrap(&file));
+        Self::resolve(
+            exe_dir.as_deref(),
+            portable_arg,
+            data_dir_override.as_deref(),
+        )
+    }
+
+    /// Portable mode wins over a data directory override, which wins over the
+    /// platform defaults
+    pub fn resolve(
+        exe_dir: Option<&Path>,
+        portable_arg: bool,
+        data_dir_override: Option<&Path>,
+    ) -> Self {
+        match (exe_dir, data_dir_override) {
+            (Some(exe_dir), _) if portable_arg || exe_dir.join(PORTABLE_FLAG_FILE).exists() => {
+                Self::portable(&exe_dir.join(PORTABLE_DATA_DIR))
+            }
+            (_, Some(root)) => Self::custom(root),
+            _ => Self::standard(),
+        }
+    }
+
+    fn standard() -> Self {
+        Self {
+            mode: StorageMode::Standard,
+            config_dir: dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME)),
+            data_dir: dirs::data_dir().map(|dir| dir.join(APP_DIR_NAME)),
+            log_dir: Self::standard_log_dir(),
+        }
+    }
+
+    fn portable(root: &Path) -> Self {
+        Self {
+            mode: StorageMode::Portable,
+            config_dir: Some(root.join("config")),
+            data_dir: Some(root.to_path_buf()),
+            log_dir: Some(root.join("logs")),
+        }
+    }
+
+    // Logs stay on the machine, a shared folder would mix both machines' logs
+    fn custom(root: &Path) -> Self {
+        Self {
+            mode: StorageMode::Custom,
+            config_dir: Some(root.join("config")),
+            data_dir: Some(root.to_path_buf()),
+            log_dir: Self::standard_log_dir(),
+        }
+    }
+
+    fn standard_log_dir() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join(APP_DIR_NAME).join("logs"))
+    }
+
+    pub fn config_dir(&self) -> Result<PathBuf> {
+        self.config_dir
+            .clone()
+            .ok_or_else(|| AppError::Configuration("Failed to get config directory".to_string()))
+    }
+
+    pub fn data_dir(&self) -> Result<PathBuf> {
+        self.data_dir.clone().ok_or_else(|| {
+            AppError::Configuration("Could not determine data directory".to_string())
+        })
+    }
+
+    /// Falls back to the working directory, logging must not fail startup
+    pub fn log_dir(&self) -> PathBuf {
+        self.log_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(".").join(APP_DIR_NAME).join("logs"))
+    }
+
+    /// Copy the current configs and data into `root`, laid out the way a
+    /// custom data directory expects. Files `root` already has are kept, so
+    /// pointing a second machine at a synced folder doesn't clobber it.
+    /// Returns the number of files copied and skipped.
+    pub fn copy_to(&self, root: &Path) -> Result<(usize, usize)> {
+        let targets = [
+            (self.config_dir()?, root.join("config")),
+            (self.data_dir()?, root.to_path_buf()),
+        ];
+        for (source, _) in &targets {
+            if root.starts_with(source) || source.starts_with(root) {
+                return Err(AppError::Validation(format!(
+                    "New data directory can't be inside or contain {}",
+                    source.display()
+                )));
+            }
+        }
+
+        let (mut copied, mut skipped) = (0, 0);
+        // The config directory may sit inside (or be) the data directory
+        let mut visited = HashSet::new();
+        for (source, target) in &targets {
+            if !source.is_dir() {
+                continue;
+            }
+            let entries = walkdir::WalkDir::new(source)
+                .into_iter()
+                .filter_entry(|entry| {
+                    entry.depth() != 1
+                        || !NOT_MIGRATED.contains(&entry.file_name().to_string_lossy().as_ref())
+                });
+            for entry in entries {
+                let entry = entry.map_err(|e| AppError::FileSystem(e.to_string()))?;
+                if !entry.file_type().is_file() || !visited.insert(entry.path().to_path_buf()) {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+                let destination = target.join(relative);
+                if destination.exists() {
+                    skipped += 1;
+                    continue;
+                }
+                if let Some(parent) = destination.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(entry.path(), &destination).map_err(|e| {
+                    AppError::FileSystem(format!(
+                        "Failed to copy {} to {}: {}",
+                        entry.path().display(),
+                        destination.display(),
+                        e
+                    ))
+                })?;
+                copied += 1;
+            }
+        }
+
+        Ok((copied, skipped))
+    }
+}
+
+// Resolved once, a new location only takes effect after a restart
+static APP_PATHS: Lazy<AppPaths> = Lazy::new(AppPaths::detect);
+
+pub fn app_paths() -> &'static AppPaths {
+    &APP_PATHS
+}
+
+/// `<config root>`: OSS configs, settings, connection cache
+pub fn config_dir() -> Result<PathBuf> {
+    APP_PATHS.config_dir()
+}
+
+/// `<data root>`: history, backups, thumbnails, pending uploads
+pub fn data_dir() -> Result<PathBuf> {
+    APP_PATHS.data_dir()
+}
+
+pub fn log_dir() -> PathBuf {
+    APP_PATHS.log_dir()
+}
+
+/// Use `root` as the data directory from the next start, or the default
+/// location again when None
+pub fn save_data_dir_override(root: Option<&Path>) -> Result<()> {
+    let file = bootstrap_file()
+        .ok_or_else(|| AppError::Configuration("Failed to get config directory".to_string()))?;
+    write_bootstrap(&file, root)
+}
+
+// The bootstrap file always lives in the default location, it is what points
+// elsewhere
+fn bootstrap_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME).join(BOOTSTRAP_FILE))
+}
+
+// Runs before logging is set up, a broken file just means no override
+fn read_bootstrap(file: &Path) -> Option<PathBuf> {
+    let content = std::fs::read_to_string(file).ok()?;
+    serde_json::from_str::<Bootstrap>(&content)
+        .ok()?
+        .data_dir_override
+}
+
+fn write_bootstrap(file: &Path, root: Option<&Path>) -> Result<()> {
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let bootstrap = Bootstrap {
+        data_dir_override: root.map(Path::to_path_buf),
+    };
+    std::fs::write(file, serde_json::to_string_pretty(&bootstrap)?).map_err(|e| {
+        AppError::Configuration(format!("Failed to save data directory location: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_flag_file_enables_portable_mode() {
+        let exe_dir = TempDir::new().unwrap();
+        assert_eq!(
+            AppPaths::resolve(Some(exe_dir.path()), false, None).mode,
+            StorageMode::Standard
+        );
+
+        std::fs::write(exe_dir.path().join(PORTABLE_FLAG_FILE), "").unwrap();
+        let paths = AppPaths::resolve(Some(exe_dir.path()), false, None);
+        assert_eq!(paths.mode, StorageMode::Portable);
+        let root = exe_dir.path().join(PORTABLE_DATA_DIR);
+        assert_eq!(paths.config_dir().unwrap(), root.join("config"));
+        assert_eq!(paths.data_dir().unwrap(), root);
+        assert_eq!(paths.log_dir(), root.join("logs"));
+    }
+
+    #[test]
+    fn test_portable_arg_enables_portable_mode() {
+        let exe_dir = TempDir::new().unwrap();
+        let paths = AppPaths::resolve(Some(exe_dir.path()), true, None);
+        assert_eq!(paths.mode, StorageMode::Portable);
+        assert!(paths.data_dir().unwrap().starts_with(exe_dir.path()));
+
+        // Without a known executable location there is nowhere to be portable
+        assert_eq!(
+            AppPaths::resolve(None, true, None).mode,
+            StorageMode::Standard
+        );
+    }
+
+    #[test]
+    fn test_bootstrap_override_selects_custom_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join(BOOTSTRAP_FILE);
+        let synced = temp_dir.path().join("Dropbox").join("imgtoss");
+
+        write_bootstrap(&file, Some(&synced)).unwrap();
+        let root = read_bootstrap(&file).unwrap();
+        let paths = AppPaths::resolve(Some(temp_dir.path()), false, Some(&root));
+        assert_eq!(paths.mode, StorageMode::Custom);
+        assert_eq!(paths.config_dir().unwrap(), synced.join("config"));
+        assert_eq!(paths.data_dir().unwrap(), synced);
+
+        // Portable mode still wins
+        assert_eq!(
+            AppPaths::resolve(Some(temp_dir.path()), true, Some(&root)).mode,
+            StorageMode::Portable
+        );
+
+        write_bootstrap(&file, None).unwrap();
+        assert!(read_bootstrap(&file).is_none());
+    }
+
+    #[test]
+    fn test_copy_to_keeps_existing_files_and_skips_caches() {
+        let temp_dir = TempDir::new().unwrap();
+        let current = AppPaths::portable(&temp_dir.path().join("current"));
+        let config_dir = current.config_dir().unwrap();
+        let data_dir = current.data_dir().unwrap();
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::create_dir_all(data_dir.join("backups")).unwrap();
+        std::fs::create_dir_all(data_dir.join("thumbnails")).unwrap();
+        std::fs::write(config_dir.join("configs.json"), "local").unwrap();
+        std::fs::write(data_dir.join("upload_history.json"), "[]").unwrap();
+        std::fs::write(data_dir.join("backups").join("a_post.md"), "# a").unwrap();
+        std::fs::write(data_dir.join("thumbnails").join("t.jpg"), "jpg").unwrap();
+
+        // The synced folder already has the other machine's configs
+        let synced = temp_dir.path().join("synced");
+        std::fs::create_dir_all(synced.join("config")).unwrap();
+        std::fs::write(synced.join("config").join("configs.json"), "shared").unwrap();
+
+        let (copied, skipped) = current.copy_to(&synced).unwrap();
+        assert_eq!((copied, skipped), (2, 1));
+        assert_eq!(
+            std::fs::read_to_string(synced.join("config").join("configs.json")).unwrap(),
+            "shared"
+        );
+        assert!(synced.join("backups").join("a_post.md").exists());
+        assert!(!synced.join("thumbnails").exists());
+
+        assert!(current.copy_to(&data_dir.join("nested")).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use crate::log_error;
+use crate::models::{CrashReport, CrashReportSummary};
+use crate::utils::store_recovery::write_atomic;
+use crate::utils::{app_paths, AppError, Result};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const CRASH_DIR: &str = "crashes";
+/// Reports kept; the oldest are removed when a new one is written
+const MAX_REPORTS: usize = 50;
+/// Backtraces are cut here, the frames after it are runtime plumbing
+const MAX_BACKTRACE_LINES: usize = 80;
+
+thread_local! {
+    // Guarded work running on this thread, named in its crash report
+    static CURRENT_OPERATION: Cell<Option<&'static str>> = const { Cell::new(None) };
+    // Report the hook wrote for a panic in guarded work, picked up once it unwound
+    static LAST_REPORT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Crash reports in `{data_dir}/crashes/`, one JSON file each
+pub struct CrashReports {
+    dir: PathBuf,
+}
+
+impl CrashReports {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_dir(app_paths::data_dir()?.join(CRASH_DIR)))
+    }
+
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Write `report` and drop the oldest past `MAX_REPORTS`. Synchronous,
+    /// it runs inside the panic hook.
+    pub fn save(&self, report: &CrashReport) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string_pretty(report).map_err(std::io::Error::other)?;
+        write_atomic(&self.dir.join(format!("{}.json", report.id)), &content)?;
+
+        // Ids start with their timestamp, so they sort oldest first
+        let mut files = self.report_files()?;
+        if files.len() > MAX_REPORTS {
+            files.sort();
+            for file in &files[..files.len() - MAX_REPORTS] {
+                let _ = fs::remove_file(file);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every readable report, newest first
+    pub fn list(&self) -> Result<Vec<CrashReportSummary>> {
+        let mut summaries: Vec<CrashReportSummary> = self
+            .report_files()?
+            .iter()
+            .filter_map(|file| fs::read_to_string(file).ok())
+            .filter_map(|content| serde_json::from_str::<CrashReport>(&content).ok())
+            .map(|report| CrashReportSummary {
+                id: report.id,
+                timestamp: report.timestamp,
+                operation: report.operation,
+                message: report.message,
+            })
+            .collect();
+        summaries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(summaries)
+    }
+
+    pub fn get(&self, id: &str) -> Result<CrashReport> {
+        // Ids are timestamps and hex, anything else could point outside the directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(AppError::Validation(format!(
+                "Invalid crash report ID: {}",
+                id
+            )));
+        }
+        let content = match fs::read_to_string(self.dir.join(format!("{}.json", id))) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(AppError::NotFound(format!("Crash report {}", id)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn report_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect())
+    }
+}
+
+/// Write a crash report for every panic, then print it as before. Panics in
+/// work run through `catch_panic` / `run_blocking` / `catch_panic_async` are
+/// reported with the operation's name and come back as errors; any other
+/// panic is only reported.
+pub fn install_panic_hook() {
+    let reports = CrashReports::new().ok();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let operation = CURRENT_OPERATION.try_with(Cell::get).ok().flatten();
+        let report = crash_report(
+            operation,
+            panic_message(info.payload()),
+            info.location().map(|location| location.to_string()),
+        );
+        let saved = reports
+            .as_ref()
+            .is_some_and(|reports| reports.save(&report).is_ok());
+        if saved && operation.is_some() {
+            let _ = LAST_REPORT.try_with(|last| last.replace(Some(report.id)));
+        }
+        default_hook(info);
+    }));
+}
+
+fn crash_report(
+    operation: Option<&'static str>,
+    message: String,
+    location: Option<String>,
+) -> CrashReport {
+    let timestamp = chrono::Utc::now();
+    let backtrace = Backtrace::force_capture().to_string();
+    CrashReport {
+        id: format!(
+            "{}-{}",
+            timestamp.format("%Y%m%d-%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        ),
+        timestamp,
+        operation: operation.map(str::to_string),
+        message,
+        location,
+        thread: std::thread::current().name().map(str::to_string),
+        backtrace: backtrace
+            .lines()
+            .take(MAX_BACKTRACE_LINES)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+// Run `work` as `operation`; a panic in it becomes an error naming its report
+fn guarded<R>(operation: &'static str, work: impl FnOnce() -> R) -> Result<R> {
+    let previous = CURRENT_OPERATION.replace(Some(operation));
+    let outcome = std::panic::catch_unwind(AssertUnwindSafe(work));
+    CURRENT_OPERATION.set(previous);
+
+    outcome.map_err(|payload| {
+        let message = panic_message(payload.as_ref());
+        let report = LAST_REPORT.take();
+        log_error!(
+            operation = "crash_report",
+            crashed_operation = operation,
+            report = ?report,
+            error = %message,
+            "Recovered from a panic"
+        );
+        AppError::Panicked(match report {
+            Some(id) => format!("{} crashed: {} (crash report {})", operation, message, id),
+            None => format!("{} crashed: {}", operation, message),
+        })
+    })
+}
+
+/// `work()`, with a panic in it turned into an error
+pub fn catch_panic<T>(operation: &'static str, work: impl FnOnce() -> Result<T>) -> Result<T> {
+    guarded(operation, work)?
+}
+
+/// `work` on the blocking thread pool, with a panic in it turned into an error
+pub async fn run_blocking<T, F>(operation: &'static str, work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || catch_panic(operation, work))
+        .await
+        .map_err(|e| AppError::Panicked(format!("{} task failed: {}", operation, e)))?
+}
+
+/// `work`, with a panic while it is polled turned into an error
+pub async fn catch_panic_async<T, F>(operation: &'static str, work: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    CatchPanic {
+        operation,
+        work: Box::pin(work),
+    }
+    .await
+}
+
+struct CatchPanic<F> {
+    operation: &'static str,
+    work: Pin<Box<F>>,
+}
+
+impl<T, F: Future<Output = Result<T>>> Future for CatchPanic<F> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let work = this.work.as_mut();
+        match guarded(this.operation, || work.poll(cx)) {
+            Ok(poll) => poll,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_panics_in_guarded_work_become_errors() {
+        let error = catch_panic::<()>("decode_image", || panic!("corrupt header")).unwrap_err();
+        assert!(matches!(error, AppError::Panicked(_)));
+        assert!(error
+            .to_string()
+            .contains("decode_image crashed: corrupt header"));
+        assert_eq!(CURRENT_OPERATION.get(), None);
+        assert_eq!(catch_panic("decode_image", || Ok(7)).unwrap(), 7);
+
+        let error = run_blocking::<(), _>("process_image", || panic!("index {} out of range", 3))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("index 3 out of range"));
+
+        let error = catch_panic_async::<(), _>("upload_image", async {
+            tokio::task::yield_now().await;
+            panic!("upload state lost")
+        })
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("upload_image crashed"));
+    }
+
+    #[test]
+    fn test_reports_are_listed_newest_first_and_pruned() {
+        let temp_dir = TempDir::new().unwrap();
+        let reports = CrashReports::with_dir(temp_dir.path().join(CRASH_DIR));
+        assert!(reports.list().unwrap().is_empty());
+
+        let mut first = crash_report(Some("process_image"), "boom".to_string(), None);
+        first.id = format!("20260101-000000-{}", first.id.rsplit('-').next().unwrap());
+        first.timestamp -= chrono::Duration::hours(1);
+        let second = crash_report(None, "later".to_string(), Some("src/lib.rs:1:1".into()));
+        reports.save(&first).unwrap();
+        reports.save(&second).unwrap();
+
+        let listed = reports.list().unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, second.id);
+        assert_eq!(listed[1].operation.as_deref(), Some("process_image"));
+        assert_eq!(reports.get(&first.id).unwrap(), first);
+        assert!(!first.backtrace.is_empty());
+        assert!(matches!(
+            reports.get("20260101-000000-00000000"),
+            Err(AppError::NotFound(_))
+        ));
+        assert!(matches!(
+            reports.get("../settings"),
+            Err(AppError::Validation(_))
+        ));
+
+        for _ in 0..MAX_REPORTS {
+            reports
+                .save(&crash_report(None, "again".to_string(), None))
+                .unwrap();
+        }
+        let listed = reports.list().unwrap();
+        assert_eq!(listed.len(), MAX_REPORTS);
+        // The oldest report went first
+        assert!(listed.iter().all(|summary| summary.id != first.id));
+    }
+}
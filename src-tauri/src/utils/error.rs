@@ -1,3 +1,4 @@
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,7 +13,7 @@ pub enum AppError {
     ImageProcessing(String),
 
     #[error("OSS operation error: {0}")]
-    OSSOperation(String),
+    OSSOperation(OSSError),
 
     #[error("Configuration error: {0}")]
     Configuration(String),
@@ -48,8 +49,56 @@ pub enum AppError {
     Cancelled,
 
     #[error("Permission denied: {0}")]
-    #[allow(dead_code)]
     PermissionDenied(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("File too large: {0}")]
+    FileTooLarge(String),
+
+    #[error("Internal error: {0}")]
+    Panicked(String),
 }
 
 pub type Result<T> = std::result::Result<T, AppError>;
+
+/// What went wrong in a failed provider request, normalized across providers
+#[derive(Debug, Clone, PartialEq)]
+pub enum OSSErrorKind {
+    InvalidCredentials,
+    BucketNotFound,
+    AccessDenied,
+    QuotaExceeded,
+    ObjectTooLarge,
+    RequestTimeout,
+    /// Provider error code, or the HTTP status when the response had none
+    Unknown {
+        code: String,
+    },
+}
+
+/// A provider request that came back with an error response
+#[derive(Debug, Clone)]
+pub struct OSSError {
+    pub kind: OSSErrorKind,
+    /// e.g. "Upload failed: SignatureDoesNotMatch"
+    pub message: String,
+    /// What the user can do about it, None when there is nothing specific
+    pub hint: Option<String>,
+    /// ID the provider gave the request, what its support asks for
+    pub request_id: Option<String>,
+}
+
+impl fmt::Display for OSSError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, " - {}", hint)?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request ID: {})", request_id)?;
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,19 @@
-use crate::utils::{AppError, Result};
-use dirs;
+use crate::utils::{app_paths, AppError, Result};
 use std::path::PathBuf;
-use std::sync::Once;
+use std::sync::{Once, OnceLock};
 use tracing::{info, warn};
 use tracing_appender::{non_blocking::WorkerGuard, rolling};
 use tracing_subscriber::{
     fmt::{self, time::UtcTime},
     layer::SubscriberExt,
+    reload,
     util::SubscriberInitExt,
-    EnvFilter, Layer,
+    EnvFilter, Layer, Registry,
 };
 
 static LOGGER_INIT: Once = Once::new();
+// Swaps the level filter of the running subscriber, see `set_log_level`
+static LEVEL_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 static mut WORKER_GUARD: Option<WorkerGuard> = None;
 
 #[derive(Debug, Clone)]
@@ -36,10 +38,7 @@ pub struct LogConfig {
 
 impl Default for LogConfig {
     fn default() -> Self {
-        let log_dir = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("imgtoss")
-            .join("logs");
+        let log_dir = app_paths::log_dir();
 
         // Different settings for dev vs release
         let (console_output, file_output) = if cfg!(debug_assertions) {
@@ -88,15 +87,12 @@ impl Logger {
         // Create log directory if it doesn't exist
         std::fs::create_dir_all(&self.config.log_dir).map_err(AppError::IO)?;
 
-        // Create env filter for console
-        let console_env_filter = EnvFilter::try_from_default_env()
-            .or_else(|_| EnvFilter::try_new(&self.config.level))
-            .map_err(|e| AppError::InvalidInput(format!("Invalid log level: {}", e)))?;
-
-        // Create env filter for file
-        let file_env_filter = EnvFilter::try_from_default_env()
+        // One filter for console and file, reloadable so the log level
+        // setting applies without a restart
+        let env_filter = EnvFilter::try_from_default_env()
             .or_else(|_| EnvFilter::try_new(&self.config.level))
             .map_err(|e| AppError::InvalidInput(format!("Invalid log level: {}", e)))?;
+        let (level_filter, level_handle) = reload::Layer::new(env_filter);
 
         let mut layers = Vec::new();
 
@@ -110,8 +106,7 @@ impl Logger {
                     .with_ansi(true)
                     .with_line_number(true)
                     .with_file(true)
-                    .compact()
-                    .with_filter(console_env_filter);
+                    .compact();
                 layers.push(console_layer.boxed());
             } else {
                 // Release: structured format
@@ -119,8 +114,7 @@ impl Logger {
                     .with_target(true)
                     .with_timer(UtcTime::rfc_3339())
                     .with_level(true)
-                    .with_ansi(false)
-                    .with_filter(console_env_filter);
+                    .with_ansi(false);
                 layers.push(console_layer.boxed());
             }
         }
@@ -153,18 +147,19 @@ impl Logger {
                 .with_timer(UtcTime::rfc_3339())
                 .with_level(true)
                 .with_ansi(false)
-                .json()
-                .with_filter(file_env_filter);
+                .json();
             layers.push(file_layer.boxed());
         }
 
         // Initialize subscriber
         tracing_subscriber::registry()
+            .with(level_filter)
             .with(layers)
             .try_init()
             .map_err(|e| {
                 AppError::InvalidInput(format!("Failed to initialize tracing subscriber: {}", e))
             })?;
+        let _ = LEVEL_FILTER.set(level_handle);
 
         info!("Logger initialized with config: {:?}", self.config);
 
@@ -240,6 +235,22 @@ pub fn init_logger(config: Option<LogConfig>) -> Result<()> {
     Ok(())
 }
 
+/// Log at `level` from now on. RUST_LOG, when set, keeps precedence as it
+/// does at startup; before the logger is initialized this does nothing.
+pub fn set_log_level(level: &str) -> Result<()> {
+    if std::env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+        return Ok(());
+    }
+    let Some(handle) = LEVEL_FILTER.get() else {
+        return Ok(());
+    };
+    let filter = EnvFilter::try_new(level)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid log level: {}", e)))?;
+    handle
+        .reload(filter)
+        .map_err(|e| AppError::InvalidInput(format!("Failed to change log level: {}", e)))
+}
+
 #[allow(dead_code)]
 #[allow(static_mut_refs)]
 pub fn get_logger() -> Option<&'static Logger> {
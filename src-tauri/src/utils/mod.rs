@@ -1,5 +1,12 @@
+pub mod app_paths;
+pub mod crash_reports;
 pub mod error;
+pub mod html;
 pub mod logger;
+pub mod paths;
+pub mod store_lock;
+pub mod store_recovery;
+pub mod text_encoding;
 
 pub use error::{AppError, Result};
 pub use logger::init_logger;
@@ -0,0 +1,461 @@
+use crate::log_warn;
+use crate::utils::{app_paths, AppError, Result};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+/// Default for `AppSettings::allowed_extensions`: everything the image dialog
+/// offers plus the formats the converter reads
+pub const DEFAULT_IMAGE_EXTENSIONS: [&str; 10] = [
+    "jpg", "jpeg", "png", "gif", "bmp", "webp", "svg", "ico", "tif", "tiff",
+];
+
+/// What a command expects a user supplied path to point at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    Markdown,
+    Image,
+    Directory,
+    Any,
+}
+
+impl PathKind {
+    fn noun(self) -> &'static str {
+        match self {
+            PathKind::Image => "image",
+            PathKind::Directory => "directory",
+            PathKind::Markdown | PathKind::Any => "file",
+        }
+    }
+
+    fn empty_message(self) -> &'static str {
+        match self {
+            PathKind::Image => "Image path cannot be empty",
+            PathKind::Directory => "Directory cannot be empty",
+            PathKind::Markdown | PathKind::Any => "File path cannot be empty",
+        }
+    }
+
+    fn not_found_message(self, path: &str) -> String {
+        match self {
+            PathKind::Image => format!("Image file not found: {}", path),
+            PathKind::Directory => format!("Directory not found: {}", path),
+            PathKind::Markdown | PathKind::Any => format!("File not found: {}", path),
+        }
+    }
+
+    fn check_extension(self, path: &str, image_extensions: &[String]) -> Result<()> {
+        if matches!(self, PathKind::Directory | PathKind::Any) {
+            return Ok(());
+        }
+        let Some(ext) = Path::new(path).extension() else {
+            return Err(AppError::Validation(format!(
+                "File has no extension: {}",
+                path
+            )));
+        };
+        let ext = ext.to_string_lossy().to_lowercase();
+        let allowed = match self {
+            PathKind::Markdown => MARKDOWN_EXTENSIONS.contains(&ext.as_str()),
+            _ => image_extensions.contains(&ext),
+        };
+        if allowed {
+            return Ok(());
+        }
+        Err(AppError::Validation(match self {
+            PathKind::Markdown => format!("File is not a markdown file: {}", path),
+            _ => format!("File is not a supported image: {}", path),
+        }))
+    }
+}
+
+/// Files and folders the user granted to the app.
+///
+/// A granted folder covers everything below it, a granted file only itself.
+/// Entries are canonical so symlinks and `.` segments can't step outside.
+pub struct AllowedRoots {
+    roots: RwLock<Vec<PathBuf>>,
+    /// Extensions `PathKind::Image` accepts, see `AppSettings::allowed_extensions`
+    image_extensions: RwLock<Vec<String>>,
+}
+
+impl AllowedRoots {
+    pub fn new() -> Self {
+        Self {
+            roots: RwLock::new(Vec::new()),
+            image_extensions: RwLock::new(
+                DEFAULT_IMAGE_EXTENSIONS
+                    .iter()
+                    .map(|ext| ext.to_string())
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn set_image_extensions(&self, extensions: &[String]) {
+        *self
+            .image_extensions
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = extensions.to_vec();
+    }
+
+    fn check_extension(&self, path: &str, kind: PathKind) -> Result<()> {
+        let image_extensions = self
+            .image_extensions
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        kind.check_extension(path, &image_extensions)
+    }
+
+    pub fn allow(&self, path: &Path) {
+        let root = match path.canonicalize() {
+            Ok(root) => root,
+            // A save dialog grants a file that doesn't exist yet
+            Err(_) if path.parent().is_some_and(Path::is_dir) => resolve_for_check(path),
+            Err(e) => {
+                log_warn!(
+                    operation = "allow_user_path",
+                    path = %path.display(),
+                    error = %e,
+                    "Cannot register path that doesn't resolve"
+                );
+                return;
+            }
+        };
+        if let Ok(mut roots) = self.roots.write() {
+            if !roots.iter().any(|existing| root.starts_with(existing)) {
+                roots.push(root);
+            }
+        }
+    }
+
+    pub fn is_allowed(&self, path: &Path) -> bool {
+        let resolved = resolve_for_check(path);
+        self.roots
+            .read()
+            .map(|roots| roots.iter().any(|root| resolved.starts_with(root)))
+            .unwrap_or(false)
+    }
+
+    /// Check an existing path of `kind` and return it canonicalized
+    pub fn validate(&self, path: &str, kind: PathKind) -> Result<PathBuf> {
+        check_path_syntax(path, kind)?;
+
+        let path_obj = Path::new(path);
+        if !path_obj.exists() {
+            return Err(AppError::FileSystem(kind.not_found_message(path)));
+        }
+        match kind {
+            PathKind::Directory if !path_obj.is_dir() => {
+                return Err(AppError::Validation(format!(
+                    "Path is not a directory: {}",
+                    path
+                )));
+            }
+            PathKind::Markdown | PathKind::Image | PathKind::Any if !path_obj.is_file() => {
+                return Err(AppError::Validation(format!(
+                    "Path is not a file: {}",
+                    path
+                )));
+            }
+            _ => {}
+        }
+        self.check_extension(path, kind)?;
+
+        let canonical = path_obj
+            .canonicalize()
+            .map_err(|e| AppError::FileSystem(format!("Cannot resolve {}: {}", path, e)))?;
+        self.check_allowed(path, &canonical)?;
+        Ok(canonical)
+    }
+
+    /// Like `validate`, but a path that doesn't exist yet passes as long as it
+    /// would be inside a granted root. Batch commands report missing files per
+    /// item instead of failing the whole call.
+    pub fn validate_maybe_missing(&self, path: &str, kind: PathKind) -> Result<()> {
+        check_path_syntax(path, kind)?;
+        if kind != PathKind::Directory {
+            self.check_extension(path, kind)?;
+        }
+        self.check_allowed(path, Path::new(path))
+    }
+
+    /// Check a file a command is about to create or replace: `validate_maybe_missing`,
+    /// plus it isn't a directory, exists only when `overwrite` allows replacing
+    /// it, and its folder exists
+    pub fn validate_write_target(&self, path: &str, kind: PathKind, overwrite: bool) -> Result<()> {
+        self.validate_maybe_missing(path, kind)?;
+
+        let path_obj = Path::new(path);
+        if path_obj.is_dir() {
+            return Err(AppError::Validation(format!(
+                "Target path is a directory: {}",
+                path
+            )));
+        }
+        if path_obj.exists() && !overwrite {
+            return Err(AppError::Validation(format!(
+                "File already exists: {} (pass overwrite to replace it)",
+                path
+            )));
+        }
+        match path_obj.parent() {
+            Some(parent) if parent.is_dir() => Ok(()),
+            _ => Err(AppError::FileSystem(format!(
+                "Target directory does not exist: {}",
+                path
+            ))),
+        }
+    }
+
+    fn check_allowed(&self, path: &str, resolved: &Path) -> Result<()> {
+        if self.is_allowed(resolved) || in_data_dir(resolved) {
+            return Ok(());
+        }
+        Err(AppError::Security(format!(
+            "Path was not selected in the app: {}",
+            path
+        )))
+    }
+}
+
+impl Default for AllowedRoots {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reject empty paths and traversal before touching the filesystem
+pub fn check_path_syntax(path: &str, kind: PathKind) -> Result<()> {
+    if path.is_empty() {
+        return Err(AppError::Validation(kind.empty_message().to_string()));
+    }
+    if path.contains("..") || path.contains("~") {
+        return Err(AppError::Security(format!(
+            "Invalid {} path detected",
+            kind.noun()
+        )));
+    }
+    Ok(())
+}
+
+// A missing path is resolved through its parent so it compares against
+// canonical roots
+fn resolve_for_check(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|parent| parent.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+// The app's own files (backups, exports) are always reachable
+fn in_data_dir(path: &Path) -> bool {
+    app_paths::data_dir()
+        .ok()
+        .and_then(|dir| dir.canonicalize().ok())
+        .is_some_and(|dir| resolve_for_check(path).starts_with(dir))
+}
+
+lazy_static::lazy_static! {
+    /// Filled from the asset protocol scope, which the dialog plugin extends
+    /// with every picked file or folder, and from dropped files (see `run`)
+    pub static ref USER_PATHS: AllowedRoots = {
+        let roots = AllowedRoots::new();
+        // Command tests work on temp files nobody picked in a dialog
+        #[cfg(test)]
+        roots.allow(&std::env::temp_dir());
+        roots
+    };
+}
+
+/// Validate an existing path the frontend passed to a command: no traversal,
+/// right type and extension for `kind`, and inside what the user picked
+pub fn validate_user_path(path: &str, kind: PathKind) -> Result<PathBuf> {
+    USER_PATHS.validate(path, kind)
+}
+
+/// `validate_user_path` for batch items whose missing files fail on their own
+pub fn validate_user_path_maybe_missing(path: &str, kind: PathKind) -> Result<()> {
+    USER_PATHS.validate_maybe_missing(path, kind)
+}
+
+/// `validate_user_path_maybe_missing` for a file a command writes, see
+/// `AllowedRoots::validate_write_target`
+pub fn validate_user_write_target(path: &str, kind: PathKind, overwrite: bool) -> Result<()> {
+    USER_PATHS.validate_write_target(path, kind, overwrite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // A fresh allow-list with one temp folder granted, the way a folder pick
+    // in the dialog would register it
+    fn registered_root() -> (TempDir, AllowedRoots) {
+        let root = TempDir::new().unwrap();
+        let roots = AllowedRoots::new();
+        roots.allow(root.path());
+        (root, roots)
+    }
+
+    fn write(dir: &Path, name: &str) -> String {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "x").unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_accepts_paths_inside_registered_root() {
+        let (root, roots) = registered_root();
+        let markdown = write(root.path(), "docs/post.md");
+        let image = write(root.path(), "docs/img/a.PNG");
+
+        let canonical = roots.validate(&markdown, PathKind::Markdown).unwrap();
+        assert!(canonical.is_absolute());
+        assert!(roots.validate(&image, PathKind::Image).is_ok());
+        assert!(roots
+            .validate(
+                &root.path().join("docs").to_string_lossy(),
+                PathKind::Directory
+            )
+            .is_ok());
+        // Not uploaded yet, still inside the root
+        assert!(roots
+            .validate_maybe_missing(
+                &root.path().join("docs/new.png").to_string_lossy(),
+                PathKind::Image
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rejects_paths_outside_registered_root() {
+        let (_root, roots) = registered_root();
+        let elsewhere = TempDir::new().unwrap();
+        let markdown = write(elsewhere.path(), "post.md");
+
+        let error = roots.validate(&markdown, PathKind::Markdown).unwrap_err();
+        assert!(matches!(error, AppError::Security(_)));
+        assert!(roots
+            .validate_maybe_missing(
+                &elsewhere.path().join("missing.png").to_string_lossy(),
+                PathKind::Image
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_granted_file_covers_only_itself() {
+        let dir = TempDir::new().unwrap();
+        let picked = write(dir.path(), "picked.md");
+        let sibling = write(dir.path(), "sibling.md");
+        let roots = AllowedRoots::new();
+        roots.allow(Path::new(&picked));
+
+        assert!(roots.validate(&picked, PathKind::Markdown).is_ok());
+        assert!(roots.validate(&sibling, PathKind::Markdown).is_err());
+    }
+
+    #[test]
+    fn test_write_targets_must_be_granted() {
+        let (root, roots) = registered_root();
+        let existing = write(root.path(), "report.json");
+        let new = root.path().join("new.json").to_string_lossy().to_string();
+
+        assert!(roots
+            .validate_write_target(&new, PathKind::Any, false)
+            .is_ok());
+        assert!(roots
+            .validate_write_target(&existing, PathKind::Any, false)
+            .unwrap_err()
+            .to_string()
+            .contains("already exists"));
+        assert!(roots
+            .validate_write_target(&existing, PathKind::Any, true)
+            .is_ok());
+        assert!(roots
+            .validate_write_target(&new, PathKind::Image, false)
+            .is_err());
+        assert!(roots
+            .validate_write_target(
+                &root.path().join("missing/new.json").to_string_lossy(),
+                PathKind::Any,
+                false
+            )
+            .is_err());
+
+        // Outside every root, unless a save dialog granted that very file
+        let elsewhere = TempDir::new().unwrap();
+        let saved = elsewhere.path().join("saved.json");
+        let error = roots
+            .validate_write_target(&saved.to_string_lossy(), PathKind::Any, false)
+            .unwrap_err();
+        assert!(matches!(error, AppError::Security(_)));
+        roots.allow(&saved);
+        assert!(roots
+            .validate_write_target(&saved.to_string_lossy(), PathKind::Any, false)
+            .is_ok());
+        assert!(roots
+            .validate_write_target(
+                &elsewhere.path().join("other.json").to_string_lossy(),
+                PathKind::Any,
+                false
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_kind_policy_applies_inside_root() {
+        let (root, roots) = registered_root();
+        let text = write(root.path(), "notes.txt");
+
+        assert!(roots
+            .validate(&text, PathKind::Markdown)
+            .unwrap_err()
+            .to_string()
+            .contains("not a markdown file"));
+        assert!(roots
+            .validate(&text, PathKind::Image)
+            .unwrap_err()
+            .to_string()
+            .contains("not a supported image"));
+        assert!(roots.validate(&text, PathKind::Any).is_ok());
+        assert!(roots
+            .validate(&root.path().to_string_lossy(), PathKind::Any)
+            .unwrap_err()
+            .to_string()
+            .contains("not a file"));
+        assert!(matches!(
+            roots.validate("../post.md", PathKind::Markdown),
+            Err(AppError::Security(_))
+        ));
+    }
+
+    #[test]
+    fn test_image_extensions_follow_settings() {
+        let (root, roots) = registered_root();
+        let tiff = write(root.path(), "scan.tiff");
+        assert!(roots.validate(&tiff, PathKind::Image).is_ok());
+
+        roots.set_image_extensions(&["png".to_string()]);
+        assert!(roots
+            .validate(&tiff, PathKind::Image)
+            .unwrap_err()
+            .to_string()
+            .contains("not a supported image"));
+        assert!(roots
+            .validate_maybe_missing(
+                &root.path().join("new.PNG").to_string_lossy(),
+                PathKind::Image
+            )
+            .is_ok());
+    }
+}
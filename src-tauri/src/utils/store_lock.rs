@@ -0,0 +1,97 @@
+use crate::log_warn;
+use crate::utils::{AppError, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Writers hold the lock for one read-modify-write, a few milliseconds
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Held while a JSON store is read, changed and written back. The lock is
+/// released when this is dropped.
+pub struct StoreLock {
+    // None when the filesystem doesn't support locking
+    _file: Option<File>,
+}
+
+fn lock_path(store: &Path) -> PathBuf {
+    let mut name = store.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    store.with_file_name(name)
+}
+
+/// Take the advisory lock of `store` (`<store>.lock` next to it), shared by
+/// every imgtoss process: the GUI, a second window, the CLI. Waits without
+/// blocking the runtime, and gives up after a few seconds so a hung instance
+/// can't freeze this one.
+///
+/// Locks don't reach across machines, so a synced data folder is still last
+/// writer wins.
+pub async fn lock_store(store: &Path) -> Result<StoreLock> {
+    let path = lock_path(store);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| {
+            AppError::FileSystem(format!(
+                "Failed to open lock file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(StoreLock { _file: Some(file) }),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(AppError::FileSystem(format!(
+                    "{} is locked by another imgtoss instance",
+                    store.display()
+                )));
+            }
+            // Network shares may not support locks, don't make the store unusable
+            Err(TryLockError::Error(e)) => {
+                log_warn!(
+                    operation = "lock_store",
+                    file_path = %store.display(),
+                    error = %e,
+                    "File locking unavailable, writing without it"
+                );
+                return Ok(StoreLock { _file: None });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_second_lock_waits_for_the_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = temp_dir.path().join("history.json");
+
+        let first = lock_store(&store).await.unwrap();
+        assert!(temp_dir.path().join("history.json.lock").exists());
+
+        let waiter = tokio::spawn({
+            let store = store.clone();
+            async move { lock_store(&store).await.map(|_| Instant::now()) }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let released = Instant::now();
+        drop(first);
+
+        let acquired = waiter.await.unwrap().unwrap();
+        assert!(acquired >= released);
+    }
+}
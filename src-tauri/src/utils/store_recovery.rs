@@ -0,0 +1,277 @@
+use crate::log_error;
+use crate::models::{CorruptStore, StoreKind};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Write `content` to a temp file next to `path` and rename it over `path`,
+/// so a crash or power loss never leaves half a JSON file behind. The temp
+/// file is synced before the rename and the directory after it, otherwise
+/// the rename can reach the disk before the data does.
+pub fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+// Persists the rename itself. Windows can't open a directory as a file and
+// commits renames with the file system metadata anyway.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        Some(parent) => fs::File::open(parent)?.sync_all(),
+        None => fs::File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Stores found corrupt during this run, until recovered. Each one also
+/// shows up as a HealthError in `get_system_health`.
+pub struct CorruptStores {
+    stores: Mutex<Vec<CorruptStore>>,
+}
+
+impl CorruptStores {
+    pub fn new() -> Self {
+        Self {
+            stores: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Move the unparsable `path` aside as `<path>.corrupt-<timestamp>` so the
+    /// caller can start from an empty store. Returns None when another
+    /// caller moved it first.
+    pub fn quarantine(&self, kind: StoreKind, path: &Path, error: &str) -> Option<CorruptStore> {
+        let detected_at = chrono::Utc::now();
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(
+            ".corrupt-{}",
+            detected_at.format("%Y%m%d-%H%M%S-%3f")
+        ));
+        let moved_to = path.with_file_name(name);
+        if let Err(e) = fs::rename(path, &moved_to) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log_error!(
+                    operation = "quarantine_store",
+                    file_path = %path.display(),
+                    error = %e,
+                    "Failed to move corrupt store aside"
+                );
+            }
+            return None;
+        }
+
+        log_error!(
+            operation = "quarantine_store",
+            file_path = %path.display(),
+            moved_to = %moved_to.display(),
+            error = %error,
+            "Store could not be parsed, moved it aside and started empty"
+        );
+        let store = CorruptStore {
+            kind,
+            path: path.to_string_lossy().to_string(),
+            moved_to: moved_to.to_string_lossy().to_string(),
+            error: error.to_string(),
+            detected_at,
+        };
+        self.lock().push(store.clone());
+        Some(store)
+    }
+
+    pub fn list(&self) -> Vec<CorruptStore> {
+        self.lock().clone()
+    }
+
+    /// Forget the stores of `kind` once they have been recovered
+    pub fn resolve(&self, kind: StoreKind) {
+        self.lock().retain(|store| store.kind != kind);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<CorruptStore>> {
+        self.stores.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for CorruptStores {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref CORRUPT_STORES: CorruptStores = CorruptStores::new();
+}
+
+/// Newest `<path>.corrupt-*` file next to `path`, also finds those moved
+/// aside by an earlier run
+pub fn latest_corrupt_file(path: &Path) -> Option<PathBuf> {
+    let mut prefix = path.file_name()?.to_string_lossy().to_string();
+    prefix.push_str(".corrupt-");
+    fs::read_dir(path.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            candidate
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        // The timestamp suffix sorts chronologically
+        .max()
+}
+
+/// Complete elements of the first JSON array in `content`, or of the array
+/// under `key` when given. Parsing stops at the first incomplete element, so
+/// a file cut off mid-write gives back everything before the cut.
+pub fn salvage_array(content: &str, key: Option<&str>) -> Vec<Value> {
+    let start = match key {
+        Some(key) => content
+            .find(&format!("\"{}\"", key))
+            .and_then(|at| content[at..].find('[').map(|offset| at + offset)),
+        None => content.find('['),
+    };
+    let Some(start) = start else {
+        return Vec::new();
+    };
+
+    let mut values = Vec::new();
+    let mut rest = &content[start + 1..];
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() || rest.starts_with(']') {
+            break;
+        }
+        let Some((value, after)) = next_complete_value(rest) else {
+            break;
+        };
+        values.push(value);
+        rest = after;
+    }
+    values
+}
+
+/// Complete `"key": value` entries of the top-level JSON object in `content`,
+/// up to the first incomplete one
+pub fn salvage_object(content: &str) -> Map<String, Value> {
+    let mut entries = Map::new();
+    let Some(start) = content.find('{') else {
+        return entries;
+    };
+
+    let mut rest = &content[start + 1..];
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+        let Some(Ok(key)) = stream.next() else {
+            break;
+        };
+        rest = rest[stream.byte_offset()..].trim_start();
+        let Some(after_colon) = rest.strip_prefix(':') else {
+            break;
+        };
+        let Some((value, after)) = next_complete_value(after_colon) else {
+            break;
+        };
+        entries.insert(key, value);
+        rest = after;
+    }
+    entries
+}
+
+// The value at the start of `input` and what follows it. A value running up
+// to the end of the input may have been cut (`12` of `125`), so it doesn't count.
+fn next_complete_value(input: &str) -> Option<(Value, &str)> {
+    let mut stream = serde_json::Deserializer::from_str(input).into_iter::<Value>();
+    let value = stream.next()?.ok()?;
+    let after = &input[stream.byte_offset()..];
+    if after.trim().is_empty() {
+        return None;
+    }
+    Some((value, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_salvage_array_stops_at_the_cut() {
+        let truncated = r#"[
+  {"id": "a", "n": 1},
+  {"id": "b", "n": [1, 2]},
+  {"id": "c", "n": "#;
+        let values = salvage_array(truncated, None);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1]["id"], "b");
+
+        let nested = r#"{"configs": [{"id": "x"}, {"id": "y""#;
+        assert_eq!(salvage_array(nested, Some("configs")).len(), 1);
+        assert!(salvage_array("not json", None).is_empty());
+    }
+
+    #[test]
+    fn test_salvage_object_keeps_complete_entries() {
+        let truncated = r#"{
+  "language": "en",
+  "upload_concurrency": 5,
+  "asset_roots": ["/a", "/b"#;
+        let entries = salvage_object(truncated);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["upload_concurrency"], 5);
+
+        // Cut inside a number: 12 might have been 125
+        assert_eq!(salvage_object(r#"{"a": "x", "b": 12"#).len(), 1);
+    }
+
+    #[test]
+    fn test_quarantine_moves_the_file_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("configs.json");
+        fs::write(&path, "{\"configs\": [").unwrap();
+
+        let stores = CorruptStores::new();
+        let store = stores
+            .quarantine(StoreKind::Configs, &path, "EOF while parsing")
+            .unwrap();
+        assert!(!path.exists());
+        assert!(Path::new(&store.moved_to).exists());
+        assert_eq!(
+            latest_corrupt_file(&path).unwrap(),
+            PathBuf::from(&store.moved_to)
+        );
+        assert!(stores
+            .quarantine(StoreKind::Configs, &path, "again")
+            .is_none());
+        assert_eq!(stores.list().len(), 1);
+
+        stores.resolve(StoreKind::Configs);
+        assert!(stores.list().is_empty());
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("settings.json");
+        write_atomic(&path, "{}").unwrap();
+        write_atomic(&path, "{\"a\": 1}").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\": 1}");
+        assert!(!temp_dir.path().join("settings.json.tmp").exists());
+    }
+}
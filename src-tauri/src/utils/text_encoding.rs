@@ -0,0 +1,160 @@
+use crate::utils::{AppError, Result};
+use encoding_rs::{Encoding, BIG5, EUC_KR, GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+// Legacy encodings tried, in order, when a file isn't UTF-8. Ties go to the
+// earlier one; windows-1252 decodes any bytes and is the last resort.
+const CANDIDATES: [&Encoding; 4] = [GBK, BIG5, SHIFT_JIS, EUC_KR];
+
+/// Text of a file and the encoding it was stored in
+#[derive(Debug, Clone)]
+pub struct DecodedText {
+    pub text: String,
+    /// None for UTF-8
+    pub encoding: Option<&'static Encoding>,
+}
+
+/// Decode file contents: UTF-8 when they are valid UTF-8, UTF-16 when they
+/// start with its BOM, otherwise the legacy encoding that reads most like text
+/// (GBK, Big5, Shift_JIS, EUC-KR), falling back to windows-1252.
+pub fn decode_text(bytes: &[u8]) -> DecodedText {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return DecodedText {
+            text: text.to_string(),
+            encoding: None,
+        };
+    }
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            let (text, _) = encoding.decode_with_bom_removal(bytes);
+            return DecodedText {
+                text: text.into_owned(),
+                encoding: Some(encoding),
+            };
+        }
+    }
+
+    let mut best: Option<(f64, &'static Encoding, String)> = None;
+    for encoding in CANDIDATES {
+        let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(bytes) else {
+            continue;
+        };
+        let score = text_score(&text, encoding);
+        if best
+            .as_ref()
+            .is_none_or(|(best_score, ..)| score > *best_score)
+        {
+            best = Some((score, encoding, text.into_owned()));
+        }
+    }
+    match best {
+        Some((_, encoding, text)) => DecodedText {
+            text,
+            encoding: Some(encoding),
+        },
+        None => DecodedText {
+            text: WINDOWS_1252
+                .decode_without_bom_handling(bytes)
+                .0
+                .into_owned(),
+            encoding: Some(WINDOWS_1252),
+        },
+    }
+}
+
+/// Bytes of `text` in `encoding` (None for UTF-8). Fails instead of writing
+/// replacement characters when the text has characters the encoding lacks.
+pub fn encode_text(text: &str, encoding: Option<&'static Encoding>) -> Result<Vec<u8>> {
+    let Some(encoding) = encoding else {
+        return Ok(text.as_bytes().to_vec());
+    };
+    // encoding_rs only encodes to UTF-8 for UTF-16, the units are written here
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut bytes = Vec::with_capacity(2 + text.len() * 2);
+        for unit in std::iter::once(0xFEFF).chain(text.encode_utf16()) {
+            if encoding == UTF_16LE {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        return Ok(bytes);
+    }
+
+    let (bytes, _, had_unmappable) = encoding.encode(text);
+    if had_unmappable {
+        return Err(AppError::Validation(format!(
+            "The new text has characters {} can't store; enable conversion to UTF-8 to rewrite the file",
+            encoding.name()
+        )));
+    }
+    Ok(bytes.into_owned())
+}
+
+// Share of the non-ASCII characters that are ordinary text in the language of
+// `encoding`. Text decoded with the wrong CJK encoding turns into symbols,
+// kana, half-width katakana or private-use characters.
+fn text_score(text: &str, encoding: &'static Encoding) -> f64 {
+    let mut non_ascii = 0usize;
+    let mut plausible = 0usize;
+    for ch in text.chars().filter(|ch| !ch.is_ascii()) {
+        non_ascii += 1;
+        let common = match ch as u32 {
+            0x4E00..=0x9FFF => true, // CJK ideographs
+            0x3000..=0x303F => true, // CJK punctuation
+            0xFF01..=0xFF5E => true, // full-width ASCII
+            0x2010..=0x2027 => true, // dashes, quotes, ellipsis
+            0x3040..=0x30FF => encoding == SHIFT_JIS,
+            0xAC00..=0xD7AF => encoding == EUC_KR,
+            _ => false,
+        };
+        if common {
+            plausible += 1;
+        }
+    }
+    if non_ascii == 0 {
+        return 0.0;
+    }
+    plausible as f64 / non_ascii as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIMPLIFIED: &str =
+        "# 旅行笔记\n\n今天我们去了西湖，天气很好。\n\n![截图](./图片/西湖 一.png)\n";
+    const TRADITIONAL: &str =
+        "# 旅行筆記\n\n今天我們去了台北的博物館，看到很多國寶。\n\n![照片](./圖片/故宮.png)\n";
+
+    #[test]
+    fn test_utf8_is_kept_as_is() {
+        let decoded = decode_text(SIMPLIFIED.as_bytes());
+        assert_eq!(decoded.text, SIMPLIFIED);
+        assert!(decoded.encoding.is_none());
+    }
+
+    #[test]
+    fn test_detects_gbk_and_big5() {
+        let (gbk, _, _) = GBK.encode(SIMPLIFIED);
+        let decoded = decode_text(&gbk);
+        assert_eq!(decoded.encoding, Some(GBK));
+        assert_eq!(decoded.text, SIMPLIFIED);
+
+        let (big5, _, _) = BIG5.encode(TRADITIONAL);
+        let decoded = decode_text(&big5);
+        assert_eq!(decoded.encoding, Some(BIG5));
+        assert_eq!(decoded.text, TRADITIONAL);
+    }
+
+    #[test]
+    fn test_encode_round_trips_and_refuses_unmappable_text() {
+        let (gbk, _, _) = GBK.encode(SIMPLIFIED);
+        assert_eq!(encode_text(SIMPLIFIED, Some(GBK)).unwrap(), gbk.to_vec());
+        assert!(encode_text("![x](https://cdn.example.com/😀.png)", Some(GBK)).is_err());
+
+        let utf16 = encode_text(TRADITIONAL, Some(UTF_16LE)).unwrap();
+        let decoded = decode_text(&utf16);
+        assert_eq!(decoded.encoding, Some(UTF_16LE));
+        assert_eq!(decoded.text, TRADITIONAL);
+    }
+}